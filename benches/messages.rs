@@ -0,0 +1,322 @@
+//! SendMessage, ReceiveMessage and DeleteMessage round trips against the
+//! library-mode server at several queue depths.
+//!
+//! Run with `cargo bench --bench messages`; pass a filter such as
+//! `send_message/100000` to run a single case. `receive_message_backlog`
+//! receives from a backlog of 200,000 messages, visible or nearly all in
+//! flight. `receive_message_in_process` compares receiving large bodies
+//! stored plain and compressed. `delete_message_in_flight` deletes with
+//! 100,000 other receipt handles outstanding.
+
+use axum::body::{Body, Bytes};
+use axum::http::{HeaderMap, Request};
+use axum::Router;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use local_sqs::{dispatch, AppState, Config};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tower::ServiceExt;
+
+const DEPTHS: [usize; 3] = [0, 10_000, 100_000];
+
+/// Depth of the queues in `receive_message_backlog`.
+const BACKLOG: usize = 200_000;
+
+/// Messages held in flight beside the one deleted in
+/// `delete_message_in_flight`.
+const IN_FLIGHT: usize = 100_000;
+
+/// Body size for `send_message_in_process`: the default maximum message size.
+const LARGE_BODY: usize = 256 * 1024;
+
+struct Server {
+    client: reqwest::Client,
+    endpoint: String,
+    router: Router,
+    state: AppState,
+}
+
+impl Server {
+    async fn start(config: Config) -> Server {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = Config {
+            host: "127.0.0.1".to_string(),
+            port: listener.local_addr().unwrap().port(),
+            ..config
+        };
+        let state = AppState::new(&config);
+        let router = local_sqs::sqs_router(state.clone());
+        let app = router.clone();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        Server {
+            client: reqwest::Client::new(),
+            endpoint: format!("http://127.0.0.1:{}/", config.port),
+            router,
+            state,
+        }
+    }
+
+    async fn call(&self, action: &str, body: &Value) -> Value {
+        let response = self
+            .client
+            .post(&self.endpoint)
+            .header("X-Amz-Target", format!("AmazonSQS.{}", action))
+            .header("Content-Type", "application/x-amz-json-1.0")
+            .body(body.to_string())
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success(), "{} failed", action);
+        response.json().await.unwrap()
+    }
+
+    /// Like [`call`](Self::call), but handing the request straight to the
+    /// router, so that the server's own work isn't lost in HTTP overhead.
+    async fn call_in_process(&self, action: &str, body: Bytes) -> Value {
+        let request = Request::post("/")
+            .header("X-Amz-Target", format!("AmazonSQS.{}", action))
+            .header("Content-Type", "application/x-amz-json-1.0")
+            .body(Body::from(body))
+            .unwrap();
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        assert!(response.status().is_success(), "{} failed", action);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    /// Removes the message `message_id` from `queue_url` directly.
+    async fn remove(&self, queue_url: &str, message_id: &str) {
+        let removed = self
+            .state
+            .store
+            .update(queue_url, |queue| {
+                let seq = queue.messages.get_mut_by_id(message_id).unwrap().seq;
+                queue.remove_message(seq)
+            })
+            .await;
+        removed.unwrap();
+    }
+
+    /// A fresh queue holding `depth` messages. They are sent in process
+    /// rather than over HTTP so that setting up the deep queues stays quick.
+    async fn queue(&self, name: &str, depth: usize) -> String {
+        let created = self.call("CreateQueue", &json!({ "QueueName": name })).await;
+        let queue_url = created["QueueUrl"].as_str().unwrap().to_string();
+        let headers = HeaderMap::new();
+        for start in (0..depth).step_by(10) {
+            let entries: Vec<Value> = (start..depth.min(start + 10))
+                .map(|i| json!({ "Id": i.to_string(), "MessageBody": payload() }))
+                .collect();
+            let body = json!({ "QueueUrl": queue_url, "Entries": entries }).to_string();
+            let response = dispatch::sqs_actions()
+                .dispatch(self.state.clone(), "SendMessageBatch", &headers, &body)
+                .await;
+            assert!(response.status().is_success());
+        }
+        queue_url
+    }
+
+    /// Receives `count` messages from `queue_url` in process, leaving them in
+    /// flight for an hour.
+    async fn hold_in_flight(&self, queue_url: &str, count: usize) {
+        let headers = HeaderMap::new();
+        let body = json!({
+            "QueueUrl": queue_url,
+            "MaxNumberOfMessages": 10,
+            "VisibilityTimeout": 3600,
+        });
+        let body = body.to_string();
+        for _ in 0..count.div_ceil(10) {
+            let response = dispatch::sqs_actions()
+                .dispatch(self.state.clone(), "ReceiveMessage", &headers, &body)
+                .await;
+            assert!(response.status().is_success());
+        }
+    }
+
+    async fn receive(&self, queue_url: &str, visibility_timeout: u32) -> String {
+        let body = json!({
+            "QueueUrl": queue_url,
+            "VisibilityTimeout": visibility_timeout,
+        });
+        let received = self.call("ReceiveMessage", &body).await;
+        received["Messages"][0]["ReceiptHandle"].as_str().unwrap().to_string()
+    }
+}
+
+/// A 1 KiB message body.
+fn payload() -> String {
+    "x".repeat(1024)
+}
+
+/// A compressible JSON body of about 200 KB.
+fn json_blob() -> String {
+    let items: Vec<Value> = (0..4000)
+        .map(|i| json!({ "id": i, "status": "pending", "tags": ["a", "b"] }))
+        .collect();
+    serde_json::to_string(&items).unwrap()
+}
+
+fn messages(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let server = runtime.block_on(Server::start(Config::default()));
+
+    let mut group = c.benchmark_group("send_message");
+    for depth in DEPTHS {
+        let queue_url = runtime.block_on(server.queue(&format!("send-{}", depth), depth));
+        let body = json!({ "QueueUrl": queue_url, "MessageBody": payload() });
+        group.bench_function(BenchmarkId::from_parameter(depth), |b| {
+            b.to_async(&runtime).iter(|| server.call("SendMessage", &body));
+        });
+    }
+    group.finish();
+
+    // Large bodies sent in process, where copying them is a visible part of
+    // the cost. Each message is removed again, untimed, to hold the depth.
+    let mut group = c.benchmark_group("send_message_in_process");
+    group.throughput(Throughput::Bytes(LARGE_BODY as u64));
+    for depth in DEPTHS {
+        let queue_url = runtime.block_on(server.queue(&format!("large-{}", depth), depth));
+        let body = json!({ "QueueUrl": queue_url, "MessageBody": "x".repeat(LARGE_BODY) });
+        let body = Bytes::from(body.to_string());
+        let server = &server;
+        group.bench_function(BenchmarkId::from_parameter(depth), |b| {
+            b.to_async(&runtime).iter_custom(|iters| {
+                let (queue_url, body) = (queue_url.clone(), body.clone());
+                async move {
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iters {
+                        let start = Instant::now();
+                        let sent = server.call_in_process("SendMessage", body.clone()).await;
+                        elapsed += start.elapsed();
+                        server.remove(&queue_url, sent["MessageId"].as_str().unwrap()).await;
+                    }
+                    elapsed
+                }
+            });
+        });
+    }
+    group.finish();
+
+    // A zero visibility timeout leaves the message visible, so the depth
+    // doesn't drain as the benchmark runs. An empty queue has nothing to
+    // receive, so the shallowest case holds one message.
+    let mut group = c.benchmark_group("receive_message");
+    for depth in DEPTHS {
+        let depth = depth.max(1);
+        let queue_url = runtime.block_on(server.queue(&format!("receive-{}", depth), depth));
+        group.bench_function(BenchmarkId::from_parameter(depth), |b| {
+            b.to_async(&runtime).iter(|| server.receive(&queue_url, 0));
+        });
+    }
+    group.finish();
+
+    // With every message of the backlog visible, any will do; with all but
+    // one in flight, the receive has to find the one left.
+    let mut group = c.benchmark_group("receive_message_backlog");
+    let queue_url = runtime.block_on(server.queue("backlog-visible", BACKLOG));
+    group.bench_function("visible", |b| {
+        b.to_async(&runtime).iter(|| server.receive(&queue_url, 0));
+    });
+    let queue_url = runtime.block_on(async {
+        let queue_url = server.queue("backlog-in-flight", BACKLOG).await;
+        server.hold_in_flight(&queue_url, BACKLOG).await;
+        let send = json!({ "QueueUrl": queue_url, "MessageBody": payload() });
+        server.call("SendMessage", &send).await;
+        queue_url
+    });
+    group.bench_function("in_flight", |b| {
+        b.to_async(&runtime).iter(|| server.receive(&queue_url, 0));
+    });
+    group.finish();
+
+    // The cost of decompressing bodies stored with `compress_bodies_above`,
+    // against the same bodies stored as sent.
+    let mut group = c.benchmark_group("receive_message_in_process");
+    group.throughput(Throughput::Bytes(json_blob().len() as u64));
+    for (storage, compress_bodies_above) in [("plain", None), ("compressed", Some(1024))] {
+        let server = runtime.block_on(Server::start(Config {
+            compress_bodies_above,
+            ..Default::default()
+        }));
+        let queue_url = runtime.block_on(async {
+            let queue_url = server.queue("blobs", 0).await;
+            let send = json!({ "QueueUrl": queue_url, "MessageBody": json_blob() });
+            server.call("SendMessage", &send).await;
+            queue_url
+        });
+        let receive = json!({ "QueueUrl": queue_url, "VisibilityTimeout": 0 });
+        let receive = Bytes::from(receive.to_string());
+        group.bench_function(storage, |b| {
+            b.to_async(&runtime)
+                .iter(|| server.call_in_process("ReceiveMessage", receive.clone()));
+        });
+    }
+    group.finish();
+
+    // Each deleted message is replaced before the next one is timed, so the
+    // depth stays put.
+    let mut group = c.benchmark_group("delete_message");
+    for depth in DEPTHS {
+        let queue_url = runtime.block_on(server.queue(&format!("delete-{}", depth), depth));
+        let send = json!({ "QueueUrl": queue_url, "MessageBody": payload() });
+        let server = &server;
+        group.bench_function(BenchmarkId::from_parameter(depth), |b| {
+            b.to_async(&runtime).iter_custom(|iters| {
+                let (queue_url, send) = (queue_url.clone(), send.clone());
+                async move {
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iters {
+                        server.call("SendMessage", &send).await;
+                        let receipt_handle = server.receive(&queue_url, 30).await;
+                        let body = json!({
+                            "QueueUrl": queue_url,
+                            "ReceiptHandle": receipt_handle,
+                        });
+                        let start = Instant::now();
+                        server.call("DeleteMessage", &body).await;
+                        elapsed += start.elapsed();
+                    }
+                    elapsed
+                }
+            });
+        });
+    }
+    group.finish();
+
+    // As above, but with every other message in the queue in flight, so that
+    // the receipt handle is one of many outstanding.
+    let mut group = c.benchmark_group("delete_message_in_flight");
+    let queue_url = runtime.block_on(async {
+        let queue_url = server.queue("delete-in-flight", IN_FLIGHT).await;
+        server.hold_in_flight(&queue_url, IN_FLIGHT).await;
+        queue_url
+    });
+    let send = json!({ "QueueUrl": queue_url, "MessageBody": payload() });
+    let server = &server;
+    group.bench_function(BenchmarkId::from_parameter(IN_FLIGHT), |b| {
+        b.to_async(&runtime).iter_custom(|iters| {
+            let (queue_url, send) = (queue_url.clone(), send.clone());
+            async move {
+                let mut elapsed = Duration::ZERO;
+                for _ in 0..iters {
+                    server.call("SendMessage", &send).await;
+                    let receipt_handle = server.receive(&queue_url, 30).await;
+                    let body = json!({
+                        "QueueUrl": queue_url,
+                        "ReceiptHandle": receipt_handle,
+                    });
+                    let start = Instant::now();
+                    server.call("DeleteMessage", &body).await;
+                    elapsed += start.elapsed();
+                }
+                elapsed
+            }
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, messages);
+criterion_main!(benches);
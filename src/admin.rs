@@ -0,0 +1,1045 @@
+use crate::chaos::ChaosSettings;
+use crate::checkpoints::CheckpointInfo;
+use crate::error::SqsError;
+use crate::events::Event;
+use crate::fixtures::{self, FixtureSummary, Fixtures};
+use crate::generators::{self, GeneratorInfo, GeneratorSpec};
+use crate::history::{HistoryEntry, MessageHistory};
+use crate::maintenance;
+use crate::metrics::{self, QueueLatency};
+use crate::move_tasks;
+use crate::namespaces;
+use crate::queue::{self, CreateQueueRequest};
+use crate::search::{MessageFilter, SearchParams};
+use crate::serde_helpers::parse_duration;
+use crate::snapshot::{self, ImportMode, ImportSummary, MessageSnapshot, StateSnapshot};
+use crate::state::{AppState, Message, MoveReason, QueueStats};
+use crate::store::Selection;
+use crate::validation::ValidationMode;
+use crate::webhooks;
+use axum::extract::{Path, Query, State};
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::Ordering;
+
+/// Non-AWS endpoints under `/_admin` for driving the emulator from tests.
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/fixtures", post(load_fixtures))
+        .route("/export", get(export))
+        .route("/import", post(import))
+        .route("/checkpoints", get(list_checkpoints))
+        .route("/checkpoints/{name}", post(save_checkpoint).delete(delete_checkpoint))
+        .route("/checkpoints/{name}/restore", post(restore_checkpoint))
+        .route("/generators", get(list_generators).post(start_generator))
+        .route("/generators/{id}/stop", post(stop_generator))
+        .route("/usage", get(usage))
+        .route("/metrics", get(prometheus_metrics))
+        .route("/reset", post(reset))
+        .route("/namespaces/{namespace}", delete(delete_namespace))
+        .route("/queues", get(list_queues))
+        .route("/queues/{name}/messages", get(peek_messages))
+        .route("/queues/{name}/messages/{message_id}/history", get(message_history))
+        .route("/queues/{name}/search", get(search_messages))
+        .route("/queues/{name}/in-flight", get(in_flight_messages))
+        .route("/queues/{name}/stats", get(queue_stats).delete(reset_queue_stats))
+        .route("/queues/{name}/pause", post(pause_queue))
+        .route("/queues/{name}/resume", post(resume_queue))
+        .route("/queues/{name}/redrive", post(redrive))
+        .route("/queues/{name}/clone", post(clone_queue))
+        .route("/queues/{name}/events", get(queue_events))
+        .route("/events", get(server_events))
+        .route(
+            "/queues/{name}/webhook",
+            get(get_webhook).put(set_webhook).delete(delete_webhook),
+        )
+        .route(
+            "/queues/{name}/chaos",
+            get(get_chaos).put(set_chaos).delete(delete_chaos),
+        )
+        .route("/health", get(health))
+        .route("/validation", get(get_validation).put(set_validation))
+        .route("/clock", get(clock))
+        .route("/clock/advance", post(advance_clock))
+}
+
+/// Accepts a fixture document as YAML or JSON.
+async fn load_fixtures(
+    State(state): State<AppState>,
+    body: String,
+) -> Result<Json<FixtureSummary>, SqsError> {
+    let fixtures = Fixtures::from_yaml(&body)
+        .map_err(|e| SqsError::InvalidParameterValue(format!("Invalid fixtures: {}", e)))?;
+    fixtures::load(&state, fixtures).await.map(Json)
+}
+
+async fn export(State(state): State<AppState>) -> Json<StateSnapshot> {
+    Json(snapshot::export(&state).await)
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportParams {
+    #[serde(default)]
+    mode: ImportMode,
+}
+
+async fn import(
+    State(state): State<AppState>,
+    Query(params): Query<ImportParams>,
+    body: String,
+) -> Result<Json<ImportSummary>, SqsError> {
+    let snapshot = snapshot::parse(&body)?;
+    snapshot::import(&state, snapshot, params.mode).await.map(Json)
+}
+
+async fn list_checkpoints(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<CheckpointInfo>>, SqsError> {
+    state.checkpoints.list().map(Json)
+}
+
+async fn save_checkpoint(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<CheckpointInfo>, SqsError> {
+    state.checkpoints.save(&state, &name).await.map(Json)
+}
+
+/// Replaces the current queues with the checkpoint's, as an import would.
+async fn restore_checkpoint(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<ImportSummary>, SqsError> {
+    state.checkpoints.restore(&state, &name).await.map(Json)
+}
+
+async fn delete_checkpoint(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<CheckpointInfo>, SqsError> {
+    state.checkpoints.delete(&name).map(Json)
+}
+
+async fn list_generators(State(state): State<AppState>) -> Json<Vec<GeneratorInfo>> {
+    Json(state.generators.list())
+}
+
+/// Starts a generator from a spec as the config file's `generators` take.
+async fn start_generator(
+    State(state): State<AppState>,
+    body: String,
+) -> Result<Json<GeneratorInfo>, SqsError> {
+    let spec: GeneratorSpec = serde_json::from_str(&body)
+        .map_err(|e| SqsError::InvalidParameterValue(format!("Invalid generator: {}", e)))?;
+    generators::start(&state, spec).map(Json)
+}
+
+async fn stop_generator(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<GeneratorInfo>, SqsError> {
+    state.generators.stop(&id).map(Json)
+}
+
+#[derive(Debug, Serialize)]
+struct Usage {
+    total_bytes: u64,
+    max_total_bytes: Option<u64>,
+    max_messages_per_queue: Option<usize>,
+}
+
+async fn usage(State(state): State<AppState>) -> Json<Usage> {
+    Json(Usage {
+        total_bytes: state.total_bytes.load(Ordering::Relaxed),
+        max_total_bytes: state.max_total_bytes,
+        max_messages_per_queue: state.max_messages_per_queue,
+    })
+}
+
+/// Per-queue gauges and histograms, and server-wide counters, in the
+/// Prometheus text format.
+async fn prometheus_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let mut latencies: Vec<(String, QueueLatency, u64, u64)> = state.store.collect(|q| {
+        let polls = q.long_polls.in_use() as u64;
+        Some((q.name.clone(), q.latency.clone(), polls, q.stats.sends_rejected))
+    });
+    latencies.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut body =
+        metrics::prometheus(latencies.iter().map(|(name, l, _, _)| (name.as_str(), l)));
+    body.push_str(&metrics::prometheus_counter(
+        "local_sqs_idle_queues_deleted_total",
+        "Queues deleted for being idle.",
+        state.idle_queues_deleted.load(Ordering::Relaxed),
+    ));
+    body.push_str(&metrics::prometheus_gauge(
+        "local_sqs_requests_in_flight",
+        "SQS requests being handled.",
+        state.requests.in_use() as u64,
+    ));
+    body.push_str(&metrics::prometheus_gauge(
+        "local_sqs_connections_open",
+        "Open client connections.",
+        state.connections.in_use() as u64,
+    ));
+    body.push_str(&metrics::prometheus_labelled(
+        "local_sqs_long_polls_waiting",
+        "Long polls waiting on the queue.",
+        "gauge",
+        "queue",
+        latencies.iter().map(|(name, _, polls, _)| (name.as_str(), *polls)),
+    ));
+    body.push_str(&metrics::prometheus_labelled(
+        "local_sqs_sends_rejected_total",
+        "Sends refused because the queue was at its MaxQueueLength.",
+        "counter",
+        "queue",
+        latencies.iter().map(|(name, _, _, rejected)| (name.as_str(), *rejected)),
+    ));
+    body.push_str(&metrics::prometheus_labelled(
+        "local_sqs_limit_rejections_total",
+        "Requests turned away by a concurrency limit.",
+        "counter",
+        "limit",
+        state.rejections.counts(),
+    ));
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
+#[derive(Debug, Serialize)]
+struct Health {
+    status: &'static str,
+    validation: ValidationMode,
+}
+
+async fn health(State(state): State<AppState>) -> Json<Health> {
+    Json(Health {
+        status: "ok",
+        validation: state.validation.mode(),
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ValidationSettings {
+    mode: ValidationMode,
+}
+
+async fn get_validation(State(state): State<AppState>) -> Json<ValidationSettings> {
+    Json(ValidationSettings {
+        mode: state.validation.mode(),
+    })
+}
+
+/// Switches between strict and lenient validation for subsequent requests.
+async fn set_validation(
+    State(state): State<AppState>,
+    body: String,
+) -> Result<Json<ValidationSettings>, SqsError> {
+    let settings: ValidationSettings = serde_json::from_str(&body).map_err(|e| {
+        SqsError::InvalidParameterValue(format!("Invalid validation settings: {}", e))
+    })?;
+    state.validation.set_mode(settings.mode);
+    Ok(Json(settings))
+}
+
+#[derive(Debug, Serialize)]
+struct QueueSummary {
+    name: String,
+    url: String,
+    arn: String,
+    fifo: bool,
+    /// Messages currently stored, in any state.
+    messages: usize,
+    visible: usize,
+    in_flight: usize,
+    delayed: usize,
+    oldest_message_age_seconds: u64,
+    created_timestamp: i64,
+    tags: BTreeMap<String, String>,
+    paused: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListQueuesParams {
+    /// Only queues whose name starts with this.
+    prefix: Option<String>,
+    /// `name` (the default) or `depth`, for most visible messages first.
+    sort: Option<String>,
+}
+
+/// Every queue with its depth by message state. Counts come from the
+/// message store's indices, so listing does not scan each queue's messages.
+async fn list_queues(
+    State(state): State<AppState>,
+    Query(params): Query<ListQueuesParams>,
+) -> Result<Json<Vec<QueueSummary>>, SqsError> {
+    let by_depth = match params.sort.as_deref() {
+        None | Some("name") => false,
+        Some("depth") => true,
+        Some(other) => {
+            return Err(SqsError::InvalidParameterValue(format!(
+                "Invalid sort {:?}; expected name or depth.",
+                other
+            )));
+        }
+    };
+    let now = state.clock.now();
+    let prefix = params.prefix.unwrap_or_default();
+    let listed: Vec<QueueSummary> = state.store.collect(|queue| {
+        if !queue.name.starts_with(&prefix) {
+            return None;
+        }
+        Some(QueueSummary {
+            name: queue.name.clone(),
+            url: queue.url.clone(),
+            arn: queue.arn.clone(),
+            fifo: queue.is_fifo(),
+            messages: queue.messages.len(),
+            visible: 0,
+            in_flight: 0,
+            delayed: 0,
+            oldest_message_age_seconds: 0,
+            created_timestamp: queue.created_timestamp,
+            tags: queue.tags.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            paused: queue.paused,
+        })
+    });
+    // Counted apart from the listing, as the store may keep messages out of
+    // memory. Queues deleted meanwhile are left out.
+    let mut queues = Vec::with_capacity(listed.len());
+    for mut queue in listed {
+        let Ok(counts) = state.store.message_counts(&queue.url, now).await else {
+            continue;
+        };
+        let Ok(age) = state.store.oldest_visible_message_age(&queue.url, now).await else {
+            continue;
+        };
+        queue.visible = counts.visible;
+        queue.in_flight = counts.in_flight;
+        queue.delayed = counts.delayed;
+        queue.oldest_message_age_seconds = age as u64;
+        queues.push(queue);
+    }
+    queues.sort_by(|a, b| a.name.cmp(&b.name));
+    if by_depth {
+        // Stable, so equally deep queues stay in name order.
+        queues.sort_by_key(|queue| std::cmp::Reverse(queue.visible));
+    }
+    Ok(Json(queues))
+}
+
+#[derive(Debug, Serialize)]
+struct PauseResponse {
+    paused: bool,
+}
+
+/// Stops `ReceiveMessage` from handing out messages on the queue until it
+/// is resumed. Sends are unaffected.
+async fn pause_queue(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<PauseResponse>, SqsError> {
+    set_paused(&state, &name, true).await
+}
+
+/// Restarts delivery on a paused queue, waking long polls that are waiting
+/// on it.
+async fn resume_queue(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<PauseResponse>, SqsError> {
+    set_paused(&state, &name, false).await
+}
+
+async fn set_paused(
+    state: &AppState,
+    name: &str,
+    paused: bool,
+) -> Result<Json<PauseResponse>, SqsError> {
+    state
+        .store
+        .update_selected(&state.queue_url(name), |_| Selection::none(), |queue| {
+            queue.paused = paused;
+            if !paused {
+                queue.notify.notify_waiters();
+            }
+            Json(PauseResponse { paused })
+        })
+        .await
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RedriveRequest {
+    /// Name or ARN of the queue to move messages to. Without it, each
+    /// message goes back to the queue it was dead-lettered from.
+    destination: Option<String>,
+    /// Move at most this many messages.
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct RedriveSummary {
+    /// Messages moved, by destination queue name.
+    moved: BTreeMap<String, usize>,
+    total: usize,
+    /// Why the redrive stopped before running out of messages, if it did.
+    error: Option<String>,
+}
+
+/// Synchronously moves visible messages off a dead-letter queue, resetting
+/// their receive counts: a one-shot alternative to `StartMessageMoveTask`.
+async fn redrive(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    body: String,
+) -> Result<Json<RedriveSummary>, SqsError> {
+    let request: RedriveRequest = if body.trim().is_empty() {
+        RedriveRequest::default()
+    } else {
+        serde_json::from_str(&body)
+            .map_err(|e| SqsError::InvalidParameterValue(format!("Invalid redrive request: {}", e)))?
+    };
+
+    let (source_arn, available) = state
+        .store
+        .read(&state.queue_url(&name), |q| (q.arn.clone(), q.messages.len()))?;
+
+    let destination_arn = match request.destination {
+        Some(destination) => {
+            let arn = if destination.starts_with("arn:") {
+                destination
+            } else {
+                state.queue_arn(&destination)
+            };
+            if state.queue_url_by_arn(&arn).is_none() {
+                return Err(SqsError::QueueDoesNotExist);
+            }
+            if arn == source_arn {
+                return Err(SqsError::InvalidParameterValue(
+                    "Cannot redrive a queue into itself.".to_string(),
+                ));
+            }
+            Some(arn)
+        }
+        None => {
+            let has_sources = state.store.any(|q| {
+                q.redrive_policy
+                    .as_ref()
+                    .is_some_and(|rp| rp.dead_letter_target_arn == source_arn)
+            });
+            if !has_sources {
+                return Err(SqsError::InvalidParameterValue(format!(
+                    "Queue {} is not the dead-letter queue of any queue; specify a destination.",
+                    name
+                )));
+            }
+            None
+        }
+    };
+
+    let limit = request.limit.unwrap_or(available);
+    let mut summary = RedriveSummary::default();
+    while summary.total < limit {
+        let destination_arn = destination_arn.as_deref();
+        let reason = MoveReason::AdminRedrive;
+        match move_tasks::move_one(&state, &source_arn, destination_arn, reason).await {
+            Ok(Some(destination)) => {
+                *summary.moved.entry(destination).or_default() += 1;
+                summary.total += 1;
+            }
+            Ok(None) => break,
+            Err(reason) => {
+                summary.error = Some(reason);
+                break;
+            }
+        }
+    }
+
+    Ok(Json(summary))
+}
+
+#[derive(Debug, Deserialize)]
+struct CloneRequest {
+    /// The new queue's name. Like any queue's, a FIFO queue's must end in
+    /// `.fifo`.
+    name: String,
+    /// Copy the messages as well as the attributes and tags.
+    #[serde(default)]
+    messages: bool,
+    /// Keep the messages' ids instead of giving the copies new ones.
+    #[serde(default)]
+    preserve_ids: bool,
+    /// Copy messages as never received.
+    #[serde(default)]
+    reset_receive_counts: bool,
+    /// Copy every message as visible. Otherwise in-flight messages stay in
+    /// flight until their deadline, under receipt handles that no one
+    /// holds, and delayed ones stay delayed.
+    #[serde(default)]
+    reset_visibility: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct CloneResponse {
+    queue_url: String,
+    messages_copied: usize,
+}
+
+/// Creates a queue with another's attributes and tags, and optionally a
+/// copy of its messages, through the same validation as `CreateQueue`.
+/// The source queue is left as it is.
+async fn clone_queue(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    body: String,
+) -> Result<Json<CloneResponse>, SqsError> {
+    let request: CloneRequest = serde_json::from_str(&body)
+        .map_err(|e| SqsError::InvalidParameterValue(format!("Invalid clone request: {}", e)))?;
+    let url = state.queue_url(&name);
+    let source = state.store.inspect(&url, |queue| {
+        let messages: Vec<Message> = if request.messages {
+            queue.messages.iter().cloned().collect()
+        } else {
+            Vec::new()
+        };
+        (queue.configured_attributes(), queue.tags.clone(), messages)
+    });
+    let (attributes, tags, messages) = source.await?;
+    // CreateQueue would hand back an identical queue rather than fail.
+    if state.store.read(&state.queue_url(&request.name), |_| ()).is_ok() {
+        return Err(SqsError::QueueNameExists);
+    }
+    let created = queue::create_queue(
+        State(state.clone()),
+        Json(CreateQueueRequest {
+            queue_name: request.name.clone(),
+            attributes,
+            tags,
+            template: None,
+        }),
+    )
+    .await?;
+
+    let now = state.clock.now();
+    let messages_copied = messages.len();
+    state.store.update_selected(&created.queue_url, |_| Selection::none(), |queue| {
+        for message in messages {
+            let message = cloned_message(&state, &request, &queue.name, message, now);
+            queue.push_message(message, now);
+        }
+        queue.notify.notify_waiters();
+    })
+    .await?;
+    Ok(Json(CloneResponse {
+        queue_url: created.queue_url,
+        messages_copied,
+    }))
+}
+
+/// `message` as copied onto the queue `queue` as `request` asks.
+fn cloned_message(
+    state: &AppState,
+    request: &CloneRequest,
+    queue: &str,
+    mut message: Message,
+    now: DateTime<Utc>,
+) -> Message {
+    if !request.preserve_ids {
+        message.id = state.ids.uuid().to_string();
+    }
+    message.history = MessageHistory::new(state.message_history);
+    message.claimed_by = None;
+    if request.reset_receive_counts {
+        message.receive_count = 0;
+        message.first_received = None;
+        message.attributes.remove("ApproximateFirstReceiveTimestamp");
+        message
+            .attributes
+            .insert("ApproximateReceiveCount".to_string(), "0".to_string());
+    }
+    if request.reset_visibility {
+        message.receipt_handle = None;
+        message.visible_from = now;
+    } else if message.receipt_handle.is_some() {
+        // The source's receipt handles only resolve on the source.
+        let handle = state.receipt_handles.issue(queue, &message.id, message.receive_count, now);
+        message.receipt_handle = Some(handle);
+    }
+    message
+}
+
+#[derive(Debug, Deserialize)]
+struct PeekParams {
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct PeekedMessage {
+    #[serde(flatten)]
+    message: MessageSnapshot,
+    /// Present if message histories are kept.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    history: Option<Vec<HistoryEntry>>,
+}
+
+/// Lists a queue's messages in send order, in any state, without receiving
+/// them.
+async fn peek_messages(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(params): Query<PeekParams>,
+) -> Result<Json<Vec<PeekedMessage>>, SqsError> {
+    state.store.inspect(&state.queue_url(&name), |queue| Json(
+        queue
+            .messages
+            .iter()
+            .take(params.limit.unwrap_or(usize::MAX))
+            .map(PeekedMessage::from)
+            .collect(),
+    ))
+    .await
+}
+
+/// The most search results returned at once, and the default.
+const MAX_SEARCH_RESULTS: usize = 1000;
+const DEFAULT_SEARCH_RESULTS: usize = 100;
+
+#[derive(Debug, Serialize)]
+struct SearchResponse {
+    messages: Vec<PeekedMessage>,
+    /// Pass back as `next_token` for the next page, if there may be one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_token: Option<String>,
+}
+
+/// Finds a queue's messages meeting every given criterion, in send order,
+/// without receiving them.
+async fn search_messages(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<SearchResponse>, SqsError> {
+    let limit = params.limit.unwrap_or(DEFAULT_SEARCH_RESULTS);
+    if !(1..=MAX_SEARCH_RESULTS).contains(&limit) {
+        return Err(SqsError::InvalidParameterValue(format!(
+            "Invalid limit {}; must be between 1 and {}.",
+            limit, MAX_SEARCH_RESULTS
+        )));
+    }
+    let after = match params.next_token.as_deref() {
+        None => None,
+        Some(token) => Some(token.parse::<u64>().map_err(|_| {
+            SqsError::InvalidParameterValue(format!("Invalid next_token {:?}.", token))
+        })?),
+    };
+    let filter = MessageFilter::new(params)?;
+    let now = state.clock.now();
+    state.store.inspect(&state.queue_url(&name), |queue| {
+        let messages: Box<dyn Iterator<Item = _>> = match after {
+            Some(seq) => Box::new(queue.messages.iter_after(seq)),
+            None => Box::new(queue.messages.iter()),
+        };
+        // One more than asked for tells whether there is another page.
+        let mut found: Vec<_> = messages
+            .filter(|message| filter.matches(message, now))
+            .take(limit + 1)
+            .collect();
+        let next_token = (found.len() > limit).then(|| {
+            found.truncate(limit);
+            found[limit - 1].seq.to_string()
+        });
+        Json(SearchResponse {
+            messages: found.into_iter().map(PeekedMessage::from).collect(),
+            next_token,
+        })
+    })
+    .await
+}
+
+impl From<&Message> for PeekedMessage {
+    fn from(message: &Message) -> Self {
+        Self {
+            message: MessageSnapshot::from(message),
+            history: message.history.as_ref().map(|history| history.entries()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct HistoryResponse {
+    history: Vec<HistoryEntry>,
+}
+
+/// What has happened to a message, oldest first: one on the queue, or one
+/// recently deleted from it. Messages moved to a dead-letter queue are
+/// found there.
+async fn message_history(
+    State(state): State<AppState>,
+    Path((name, message_id)): Path<(String, String)>,
+) -> Result<Json<HistoryResponse>, SqsError> {
+    if state.message_history == 0 {
+        return Err(SqsError::InvalidParameterValue(
+            "Message histories aren't kept; start the server with --message-history.".to_string(),
+        ));
+    }
+    let url = state.queue_url(&name);
+    let history = state.store.inspect(&url, |queue| {
+        match queue.messages.iter().find(|m| m.id == message_id) {
+            // Restored from a snapshot or a store, so without a history.
+            Some(message) => Some(message.history.as_ref().map_or_else(Vec::new, |h| h.entries())),
+            None => queue.deleted_histories.get(&message_id).map(|history| history.entries()),
+        }
+    });
+    let history = history.await?;
+    let history = history.ok_or_else(|| {
+        SqsError::ResourceNotFound(format!(
+            "Message {} is not on queue {} and was not recently deleted from it.",
+            message_id, name
+        ))
+    })?;
+    Ok(Json(HistoryResponse { history }))
+}
+
+#[derive(Debug, Deserialize)]
+struct InFlightParams {
+    /// Only messages due back within this long, e.g. `30s`, `500ms`, `5m`.
+    expiring_within: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct InFlightMessage {
+    message_id: String,
+    receipt_handle: Option<String>,
+    receive_count: u32,
+    /// When the message becomes visible again unless it is deleted first.
+    visible_from: DateTime<Utc>,
+    /// The request id of the `ReceiveMessage` that received it.
+    claimed_by: Option<String>,
+}
+
+/// Lists a queue's in-flight messages, soonest visibility deadline first.
+/// Messages whose deadline has passed count as visible and are left out.
+async fn in_flight_messages(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(params): Query<InFlightParams>,
+) -> Result<Json<Vec<InFlightMessage>>, SqsError> {
+    let now = state.clock.now();
+    let until = match &params.expiring_within {
+        Some(value) => {
+            let within = parse_duration(value).ok_or_else(|| {
+                SqsError::InvalidParameterValue(format!(
+                    "Invalid expiring_within {:?}; expected a duration like 30s, 500ms or 5m.",
+                    value
+                ))
+            })?;
+            Some(now + within)
+        }
+        None => None,
+    };
+    state.store.inspect(&state.queue_url(&name), |queue| Json(
+        queue
+            .messages
+            .in_flight()
+            .skip_while(|message| message.visible_from <= now)
+            .take_while(|message| until.is_none_or(|until| message.visible_from <= until))
+            .map(|message| InFlightMessage {
+                message_id: message.id.clone(),
+                receipt_handle: message.receipt_handle.clone(),
+                receive_count: message.receive_count,
+                visible_from: message.visible_from,
+                claimed_by: message.claimed_by.clone(),
+            })
+            .collect(),
+    ))
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+struct EventsParams {
+    /// Only events at or after this time, in epoch milliseconds.
+    #[serde(default)]
+    since: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct EventsResponse {
+    events: Vec<Event>,
+}
+
+/// The queue's recent lifecycle events, oldest first.
+async fn queue_events(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(params): Query<EventsParams>,
+) -> Result<Json<EventsResponse>, SqsError> {
+    state.store.read(&state.queue_url(&name), |queue| Json(EventsResponse {
+        events: queue.events.since(params.since),
+    }))
+}
+
+/// Recent queue creations and deletions across the server, oldest first.
+async fn server_events(
+    State(state): State<AppState>,
+    Query(params): Query<EventsParams>,
+) -> Json<EventsResponse> {
+    let events = state.server_events.lock().unwrap().since(params.since);
+    Json(EventsResponse { events })
+}
+
+#[derive(Debug, Serialize)]
+struct QueueStatsResponse {
+    #[serde(flatten)]
+    counters: QueueStats,
+    latency: QueueLatency,
+    /// Messages currently stored, in any state.
+    messages: usize,
+    /// Body bytes as held in memory, after any compression.
+    stored_bytes: u64,
+    /// Body bytes as sent. Exceeds `stored_bytes` by what compression saves.
+    logical_bytes: u64,
+}
+
+async fn queue_stats(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<QueueStatsResponse>, SqsError> {
+    state.store.inspect(&state.queue_url(&name), |queue| Json(QueueStatsResponse {
+        counters: queue.stats.clone(),
+        latency: queue.latency.clone(),
+        messages: queue.messages.len(),
+        stored_bytes: queue.stored_bytes,
+        logical_bytes: queue.messages.iter().map(|m| m.body.len() as u64).sum(),
+    }))
+    .await
+}
+
+async fn reset_queue_stats(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<QueueStats>, SqsError> {
+    state
+        .store
+        .update_selected(&state.queue_url(&name), |_| Selection::none(), |queue| {
+            queue.latency = QueueLatency::new(state.histogram_buckets.clone());
+            Json(std::mem::take(&mut queue.stats))
+        })
+        .await
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ResetRequest {
+    /// Only remove queues whose name starts with this prefix.
+    prefix: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct ResetSummary {
+    queues: usize,
+    messages: usize,
+}
+
+/// Removes all queues (or those matching `prefix`) along with everything
+/// stored on them. Long polls on removed queues are woken and fail with
+/// `QueueDoesNotExist`.
+async fn reset(State(state): State<AppState>, body: String) -> Result<Json<ResetSummary>, SqsError> {
+    let request: ResetRequest = if body.trim().is_empty() {
+        ResetRequest::default()
+    } else {
+        serde_json::from_str(&body)
+            .map_err(|e| SqsError::InvalidParameterValue(format!("Invalid reset request: {}", e)))?
+    };
+
+    remove_queues(&state, request.prefix.as_deref(), "reset").await.map(Json)
+}
+
+/// Removes every queue in `namespace`, as a reset with its prefix would.
+async fn delete_namespace(
+    State(state): State<AppState>,
+    Path(namespace): Path<String>,
+) -> Result<Json<ResetSummary>, SqsError> {
+    namespaces::validate(&namespace)?;
+    let prefix = namespaces::prefix(&namespace);
+    remove_queues(&state, Some(&prefix), "namespace deleted").await.map(Json)
+}
+
+/// Deletes the queues whose names start with `prefix`, or all of them, one
+/// by one through [`queue::remove_queue_if`] as `DeleteQueue` would.
+async fn remove_queues(
+    state: &AppState,
+    prefix: Option<&str>,
+    reason: &str,
+) -> Result<ResetSummary, SqsError> {
+    let mut summary = ResetSummary::default();
+    let mut urls = state.store.collect(|q| {
+        prefix.is_none_or(|prefix| q.name.starts_with(prefix)).then(|| q.url.clone())
+    });
+    urls.sort();
+    for url in urls {
+        if let Some(queue) = queue::remove_queue_if(state, &url, reason, |_| true).await? {
+            summary.queues += 1;
+            summary.messages += queue.messages.len();
+        }
+    }
+    Ok(summary)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Webhook {
+    url: Option<String>,
+}
+
+async fn get_webhook(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<Webhook>, SqsError> {
+    state.store.read(&state.queue_url(&name), |queue| Json(Webhook {
+        url: queue.webhook.clone(),
+    }))
+}
+
+async fn set_webhook(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    body: String,
+) -> Result<Json<Webhook>, SqsError> {
+    let webhook: Webhook = serde_json::from_str(&body)
+        .map_err(|e| SqsError::InvalidParameterValue(format!("Invalid webhook: {}", e)))?;
+    if let Some(url) = &webhook.url {
+        webhooks::validate_url(url)?;
+    }
+    state
+        .store
+        .update_selected(&state.queue_url(&name), |_| Selection::none(), |queue| {
+            queue.webhook = webhook.url.clone();
+            Json(webhook)
+        })
+        .await
+}
+
+async fn delete_webhook(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<Webhook>, SqsError> {
+    state
+        .store
+        .update_selected(&state.queue_url(&name), |_| Selection::none(), |queue| {
+            Json(Webhook { url: queue.webhook.take() })
+        })
+        .await
+}
+
+#[derive(Debug, Serialize)]
+struct QueueChaos {
+    /// The queue's own settings, if it overrides the server-wide ones.
+    #[serde(rename = "override")]
+    queue: Option<ChaosSettings>,
+    /// The settings receives on the queue actually use.
+    effective: ChaosSettings,
+    /// The server's chaos RNG seed, for reproducing a run.
+    seed: u64,
+}
+
+impl QueueChaos {
+    fn new(state: &AppState, queue: Option<ChaosSettings>) -> Self {
+        Self {
+            queue,
+            effective: queue.unwrap_or(state.chaos),
+            seed: state.chaos_rng.seed(),
+        }
+    }
+}
+
+async fn get_chaos(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<QueueChaos>, SqsError> {
+    state
+        .store
+        .read(&state.queue_url(&name), |queue| Json(QueueChaos::new(&state, queue.chaos)))
+}
+
+/// Overrides the server-wide chaos settings for one queue.
+async fn set_chaos(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    body: String,
+) -> Result<Json<QueueChaos>, SqsError> {
+    let settings: ChaosSettings = serde_json::from_str(&body)
+        .map_err(|e| SqsError::InvalidParameterValue(format!("Invalid chaos settings: {}", e)))?;
+    settings.validate()?;
+    state
+        .store
+        .update_selected(&state.queue_url(&name), |_| Selection::none(), |queue| {
+            queue.chaos = Some(settings);
+            Json(QueueChaos::new(&state, queue.chaos))
+        })
+        .await
+}
+
+/// Drops a queue's override so it follows the server-wide settings again.
+async fn delete_chaos(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<QueueChaos>, SqsError> {
+    state
+        .store
+        .update_selected(&state.queue_url(&name), |_| Selection::none(), |queue| {
+            queue.chaos = None;
+            Json(QueueChaos::new(&state, None))
+        })
+        .await
+}
+
+#[derive(Debug, Serialize)]
+struct ClockResponse {
+    manual: bool,
+    /// The server's current time in epoch milliseconds.
+    now: i64,
+}
+
+async fn clock(State(state): State<AppState>) -> Json<ClockResponse> {
+    Json(ClockResponse {
+        manual: state.clock.is_manual(),
+        now: state.clock.now().timestamp_millis(),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct AdvanceClockRequest {
+    seconds: u64,
+}
+
+/// Moves a manual clock forward, then runs a maintenance sweep at the new
+/// time so expired visibility timeouts, elapsed delays and retention take
+/// effect (and wake long polls) right away rather than on the next tick.
+async fn advance_clock(
+    State(state): State<AppState>,
+    Json(request): Json<AdvanceClockRequest>,
+) -> Result<Json<ClockResponse>, SqsError> {
+    let by = i64::try_from(request.seconds)
+        .ok()
+        .and_then(chrono::Duration::try_seconds)
+        .ok_or_else(|| {
+            SqsError::InvalidParameterValue(format!(
+                "Cannot advance the clock by {} seconds.",
+                request.seconds
+            ))
+        })?;
+    let now = state.clock.advance(by).ok_or_else(|| {
+        SqsError::InvalidParameterValue(
+            "The clock can only be advanced when the server runs with a manual clock.".to_string(),
+        )
+    })?;
+    maintenance::sweep(&state, now).await;
+
+    Ok(Json(ClockResponse {
+        manual: true,
+        now: now.timestamp_millis(),
+    }))
+}
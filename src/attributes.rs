@@ -0,0 +1,464 @@
+use crate::error::SqsError;
+use std::collections::HashMap;
+
+/// The value type of a queue attribute and the values it accepts.
+#[derive(Debug, Clone, Copy)]
+pub enum AttributeKind {
+    /// An integer in `min..=max`.
+    Integer { min: i64, max: i64 },
+    /// `"true"` or `"false"`.
+    Boolean,
+    /// One of a fixed set of strings.
+    Enum(&'static [&'static str]),
+    /// A JSON document. An empty string clears the attribute.
+    Json,
+    /// Any non-empty string.
+    String,
+}
+
+/// Who may set a queue attribute, and when.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutability {
+    /// Set by `CreateQueue` or `SetQueueAttributes`.
+    Mutable,
+    /// Set by `CreateQueue` only.
+    CreateOnly,
+    /// Reported by `GetQueueAttributes`, and computed from the queue's
+    /// state; clients setting it get `InvalidAttributeName`.
+    ReadOnly,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AttributeSpec {
+    pub name: &'static str,
+    pub kind: AttributeKind,
+    /// Applied to new queues that don't set the attribute.
+    pub default: Option<&'static str>,
+    pub mutability: Mutability,
+    /// Whether only FIFO queues have the attribute. Standard queues reject
+    /// it as an unknown attribute, as AWS does.
+    pub fifo_only: bool,
+    /// Whether `GetQueueAttributes` returns the attribute for `All`, rather
+    /// than only when asked for by name.
+    pub in_all: bool,
+}
+
+/// The classic upper bound of `MaximumMessageSize`, and the default limit.
+pub const MAX_MESSAGE_SIZE: usize = 262144;
+
+/// The largest `MaximumMessageSize` SQS allows today.
+pub const EXTENDED_MAX_MESSAGE_SIZE: usize = 1048576;
+
+/// Every queue attribute, whether clients set it, at creation or later, or
+/// only read it, as [`AttributeSpec::mutability`] says. `CreateQueue`,
+/// `SetQueueAttributes`, the server's default attributes and the parameters
+/// that override attributes for a single request are all checked against
+/// it. New attributes are added here.
+pub const QUEUE_ATTRIBUTES: &[AttributeSpec] = &[
+    AttributeSpec {
+        name: "DelaySeconds",
+        kind: AttributeKind::Integer { min: 0, max: 900 },
+        default: Some("0"),
+        mutability: Mutability::Mutable,
+        fifo_only: false,
+        in_all: true,
+    },
+    AttributeSpec {
+        name: "MaximumMessageSize",
+        kind: AttributeKind::Integer {
+            min: 1024,
+            max: 262144,
+        },
+        default: Some("262144"),
+        mutability: Mutability::Mutable,
+        fifo_only: false,
+        in_all: true,
+    },
+    AttributeSpec {
+        name: "MessageRetentionPeriod",
+        kind: AttributeKind::Integer {
+            min: 60,
+            max: 1209600,
+        },
+        default: Some("345600"),
+        mutability: Mutability::Mutable,
+        fifo_only: false,
+        in_all: true,
+    },
+    AttributeSpec {
+        name: "Policy",
+        kind: AttributeKind::Json,
+        default: None,
+        mutability: Mutability::Mutable,
+        fifo_only: false,
+        in_all: true,
+    },
+    AttributeSpec {
+        name: "ReceiveMessageWaitTimeSeconds",
+        kind: AttributeKind::Integer { min: 0, max: 20 },
+        default: Some("0"),
+        mutability: Mutability::Mutable,
+        fifo_only: false,
+        in_all: true,
+    },
+    AttributeSpec {
+        name: "RedrivePolicy",
+        kind: AttributeKind::Json,
+        default: None,
+        mutability: Mutability::Mutable,
+        fifo_only: false,
+        in_all: true,
+    },
+    AttributeSpec {
+        name: "RedriveAllowPolicy",
+        kind: AttributeKind::Json,
+        default: None,
+        mutability: Mutability::Mutable,
+        fifo_only: false,
+        in_all: true,
+    },
+    AttributeSpec {
+        name: "VisibilityTimeout",
+        kind: AttributeKind::Integer {
+            min: 0,
+            max: 43200,
+        },
+        default: Some("30"),
+        mutability: Mutability::Mutable,
+        fifo_only: false,
+        in_all: true,
+    },
+    AttributeSpec {
+        name: "KmsMasterKeyId",
+        kind: AttributeKind::String,
+        default: None,
+        mutability: Mutability::Mutable,
+        fifo_only: false,
+        in_all: true,
+    },
+    AttributeSpec {
+        name: "KmsDataKeyReusePeriodSeconds",
+        kind: AttributeKind::Integer {
+            min: 60,
+            max: 86400,
+        },
+        default: None,
+        mutability: Mutability::Mutable,
+        fifo_only: false,
+        in_all: true,
+    },
+    AttributeSpec {
+        name: "SqsManagedSseEnabled",
+        kind: AttributeKind::Boolean,
+        default: Some("true"),
+        mutability: Mutability::Mutable,
+        fifo_only: false,
+        in_all: true,
+    },
+    AttributeSpec {
+        name: "FifoQueue",
+        kind: AttributeKind::Boolean,
+        default: None,
+        mutability: Mutability::CreateOnly,
+        fifo_only: false,
+        in_all: true,
+    },
+    AttributeSpec {
+        name: "ContentBasedDeduplication",
+        kind: AttributeKind::Boolean,
+        default: Some("false"),
+        mutability: Mutability::Mutable,
+        fifo_only: true,
+        in_all: true,
+    },
+    AttributeSpec {
+        name: "DeduplicationScope",
+        kind: AttributeKind::Enum(&["messageGroup", "queue"]),
+        default: Some("queue"),
+        mutability: Mutability::Mutable,
+        fifo_only: true,
+        in_all: true,
+    },
+    AttributeSpec {
+        name: "FifoThroughputLimit",
+        kind: AttributeKind::Enum(&["perQueue", "perMessageGroupId"]),
+        default: Some("perQueue"),
+        mutability: Mutability::Mutable,
+        fifo_only: true,
+        in_all: true,
+    },
+    // Non-AWS: sends to a queue holding this many messages, in any state,
+    // fail. See `Config::queue_full_error_code`.
+    AttributeSpec {
+        name: "MaxQueueLength",
+        kind: AttributeKind::Integer {
+            min: 1,
+            max: u32::MAX as i64,
+        },
+        default: None,
+        mutability: Mutability::Mutable,
+        fifo_only: false,
+        in_all: true,
+    },
+    // Non-AWS extension: with "true", receives return higher priority
+    // messages first, by their `local-sqs.priority` message attribute.
+    // Ignored on FIFO queues.
+    AttributeSpec {
+        name: "LocalSqsPriority",
+        kind: AttributeKind::Boolean,
+        default: None,
+        mutability: Mutability::Mutable,
+        fifo_only: false,
+        in_all: true,
+    },
+    read_only("ApproximateNumberOfMessages", COUNT, true),
+    read_only("ApproximateNumberOfMessagesDelayed", COUNT, true),
+    read_only("ApproximateNumberOfMessagesNotVisible", COUNT, true),
+    read_only("CreatedTimestamp", COUNT, true),
+    read_only("LastModifiedTimestamp", COUNT, true),
+    read_only("QueueArn", AttributeKind::String, true),
+    // Non-AWS: whether delivery is paused through the admin API.
+    read_only("LocalSqsPaused", AttributeKind::Boolean, true),
+    // Non-AWS, named after the CloudWatch metric: the age in seconds of the
+    // oldest visible message, or 0 if none is.
+    read_only("ApproximateAgeOfOldestMessage", COUNT, false),
+];
+
+/// The kind of counts and timestamps.
+const COUNT: AttributeKind = AttributeKind::Integer {
+    min: 0,
+    max: i64::MAX,
+};
+
+const fn read_only(name: &'static str, kind: AttributeKind, in_all: bool) -> AttributeSpec {
+    AttributeSpec {
+        name,
+        kind,
+        default: None,
+        mutability: Mutability::ReadOnly,
+        fifo_only: false,
+        in_all,
+    }
+}
+
+pub fn spec(name: &str) -> Option<&'static AttributeSpec> {
+    QUEUE_ATTRIBUTES.iter().find(|spec| spec.name == name)
+}
+
+/// Checks that `name` is a known attribute and `value` is valid for it.
+/// `MaximumMessageSize` may go up to `max_message_size_limit` instead of the
+/// registry's bound; see [`Config::max_message_size_limit`](crate::Config).
+pub fn validate(
+    name: &str,
+    value: &str,
+    max_message_size_limit: usize,
+) -> Result<&'static AttributeSpec, SqsError> {
+    let spec = spec(name)
+        .filter(|spec| spec.mutability != Mutability::ReadOnly)
+        .ok_or_else(|| SqsError::InvalidAttributeName(name.to_string()))?;
+
+    let valid = match spec.kind {
+        AttributeKind::Integer { min, max } => {
+            let max = match name {
+                "MaximumMessageSize" => max_message_size_limit as i64,
+                _ => max,
+            };
+            value
+                .parse::<i64>()
+                .is_ok_and(|n| (min..=max).contains(&n))
+        }
+        AttributeKind::Boolean => value == "true" || value == "false",
+        AttributeKind::Enum(values) => values.contains(&value),
+        AttributeKind::Json => {
+            value.is_empty() || serde_json::from_str::<serde_json::Value>(value).is_ok()
+        }
+        AttributeKind::String => !value.is_empty(),
+    };
+
+    if valid && name == "Policy" && !value.is_empty() {
+        validate_policy(value)?;
+    }
+
+    if valid {
+        Ok(spec)
+    } else {
+        Err(SqsError::InvalidAttributeValue(format!(
+            "Invalid value for the parameter {}: {}.",
+            name, value
+        )))
+    }
+}
+
+/// Checks a request parameter that stands in for queue attribute
+/// `attribute` for a single request, such as `ReceiveMessage`'s
+/// `WaitTimeSeconds` for `ReceiveMessageWaitTimeSeconds`, against the
+/// attribute's range.
+pub fn validate_parameter(parameter: &str, attribute: &str, value: i64) -> Result<(), SqsError> {
+    let Some(AttributeKind::Integer { min, max }) = spec(attribute).map(|spec| spec.kind) else {
+        return Ok(());
+    };
+    if (min..=max).contains(&value) {
+        return Ok(());
+    }
+    Err(SqsError::InvalidParameterValue(format!(
+        "Value {} for parameter {} is invalid. Reason: Must be between {} and {}.",
+        value, parameter, min, max
+    )))
+}
+
+/// Like [`validate`], but also rejects attributes the queue type doesn't
+/// have, as `CreateQueue` does.
+pub fn validate_for_queue(
+    name: &str,
+    value: &str,
+    is_fifo: bool,
+    max_message_size_limit: usize,
+) -> Result<&'static AttributeSpec, SqsError> {
+    if spec(name).is_some_and(|spec| spec.fifo_only && !is_fifo) {
+        return Err(SqsError::InvalidAttributeName(name.to_string()));
+    }
+    validate(name, value, max_message_size_limit)
+}
+
+/// Like [`validate_for_queue`], but also rejects attributes that can only be
+/// set when a queue is created, as `SetQueueAttributes` does.
+pub fn validate_settable(
+    name: &str,
+    value: &str,
+    is_fifo: bool,
+    max_message_size_limit: usize,
+) -> Result<&'static AttributeSpec, SqsError> {
+    check_mutable(name)?;
+    validate_for_queue(name, value, is_fifo, max_message_size_limit)
+}
+
+fn check_mutable(name: &str) -> Result<(), SqsError> {
+    if spec(name).is_some_and(|spec| spec.mutability != Mutability::Mutable) {
+        return Err(SqsError::InvalidAttributeName(name.to_string()));
+    }
+    Ok(())
+}
+
+/// The largest policy document AWS accepts, in bytes.
+pub const MAX_POLICY_SIZE: usize = 20480;
+
+/// Checks that `value` is an IAM policy document: a JSON object with a
+/// `Statement` that is a statement object or a list of them.
+fn validate_policy(value: &str) -> Result<(), SqsError> {
+    let invalid = |reason: &str| {
+        SqsError::InvalidAttributeValue(format!(
+            "Invalid value for the parameter Policy. Reason: {}",
+            reason
+        ))
+    };
+
+    if value.len() > MAX_POLICY_SIZE {
+        return Err(invalid(&format!(
+            "Policy must be at most {} bytes.",
+            MAX_POLICY_SIZE
+        )));
+    }
+    let policy: serde_json::Value = serde_json::from_str(value)
+        .map_err(|e| invalid(&format!("Policy is not valid JSON: {}.", e)))?;
+    let valid_statements = match policy.get("Statement") {
+        Some(serde_json::Value::Array(statements)) => statements.iter().all(|s| s.is_object()),
+        Some(serde_json::Value::Object(_)) => true,
+        _ => false,
+    };
+    if !valid_statements {
+        return Err(invalid("Policy must contain a Statement."));
+    }
+    Ok(())
+}
+
+/// Fills in registry defaults for attributes that aren't set, FIFO-only
+/// ones only on FIFO queues. A queue using KMS encryption defaults to
+/// SQS-managed encryption being off.
+pub fn apply_defaults(attributes: &mut HashMap<String, String>) {
+    if attributes.contains_key("KmsMasterKeyId") {
+        attributes
+            .entry("SqsManagedSseEnabled".to_string())
+            .or_insert_with(|| "false".to_string());
+    }
+    let fifo = attributes.get("FifoQueue").is_some_and(|v| v == "true");
+    for spec in QUEUE_ATTRIBUTES {
+        if spec.fifo_only && !fifo {
+            continue;
+        }
+        if let Some(default) = spec.default {
+            attributes
+                .entry(spec.name.to_string())
+                .or_insert_with(|| default.to_string());
+        }
+    }
+}
+
+/// Checks a configured [`Config::max_message_size_limit`](crate::Config).
+pub fn validate_message_size_limit(limit: usize) -> Result<(), String> {
+    if (MAX_MESSAGE_SIZE..=EXTENDED_MAX_MESSAGE_SIZE).contains(&limit) {
+        Ok(())
+    } else {
+        Err(format!(
+            "must be between {} and {} bytes",
+            MAX_MESSAGE_SIZE, EXTENDED_MAX_MESSAGE_SIZE
+        ))
+    }
+}
+
+/// Checks server-wide default attributes with the rules `SetQueueAttributes`
+/// applies. FIFO-only attributes are allowed; they only apply to FIFO queues.
+pub fn validate_server_defaults(
+    defaults: &HashMap<String, String>,
+    max_message_size_limit: usize,
+) -> Result<(), SqsError> {
+    let mut names: Vec<&String> = defaults.keys().collect();
+    names.sort();
+    for name in names {
+        check_mutable(name)?;
+        validate(name, &defaults[name], max_message_size_limit)?;
+    }
+    validate_combination(defaults)
+}
+
+/// Fills in server-wide defaults for attributes that aren't set, ahead of
+/// the registry defaults. A default encryption setting is skipped when the
+/// attributes choose the other kind of encryption explicitly, and FIFO-only
+/// defaults are skipped for standard queues.
+pub fn apply_server_defaults(
+    attributes: &mut HashMap<String, String>,
+    defaults: &HashMap<String, String>,
+) {
+    let is_fifo = attributes.get("FifoQueue").is_some_and(|v| v == "true");
+    let kms = attributes.contains_key("KmsMasterKeyId");
+    let sqs_managed = attributes
+        .get("SqsManagedSseEnabled")
+        .is_some_and(|v| v == "true");
+    for (name, value) in defaults {
+        let conflicts = match name.as_str() {
+            "SqsManagedSseEnabled" => kms,
+            "KmsMasterKeyId" | "KmsDataKeyReusePeriodSeconds" => sqs_managed,
+            _ => false,
+        };
+        let fifo_only = spec(name).is_some_and(|spec| spec.fifo_only);
+        if !conflicts && (is_fifo || !fifo_only) {
+            attributes
+                .entry(name.clone())
+                .or_insert_with(|| value.clone());
+        }
+    }
+}
+
+/// Checks for attributes that are each valid on their own but can't be used
+/// together.
+pub fn validate_combination(attributes: &HashMap<String, String>) -> Result<(), SqsError> {
+    let kms = attributes.get("KmsMasterKeyId").is_some_and(|v| !v.is_empty());
+    let sqs_managed = attributes
+        .get("SqsManagedSseEnabled")
+        .is_some_and(|v| v == "true");
+    if kms && sqs_managed {
+        return Err(SqsError::InvalidAttributeValue(
+            "You can use one type of server-side encryption (SSE) at one time. You can either enable KMS SSE or SQS SSE.".to_string(),
+        ));
+    }
+    Ok(())
+}
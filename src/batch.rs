@@ -0,0 +1,303 @@
+use crate::error::{Fault, SqsError};
+use crate::queue::{self, ChangeMessageVisibilityRequest, DeleteMessageRequest, SendMessageRequest};
+use crate::serde_helpers;
+use crate::state::{self, AppState, MessageAttributeValue};
+use crate::telemetry;
+use axum::extract::State;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// The most entries a single batch request may carry.
+pub const MAX_BATCH_ENTRIES: usize = 10;
+
+/// Request-level checks shared by every batch action. Any failure rejects
+/// the whole request before a single entry is applied.
+///
+/// `entry_type` names the entry shape in the `EmptyBatchRequest` message,
+/// e.g. `SendMessageBatchRequestEntry`.
+pub fn validate_batch_entries<'a>(
+    entry_type: &'static str,
+    ids: impl ExactSizeIterator<Item = &'a str>,
+) -> Result<(), SqsError> {
+    match ids.len() {
+        0 => return Err(SqsError::EmptyBatchRequest(entry_type)),
+        n if n > MAX_BATCH_ENTRIES => return Err(SqsError::TooManyEntriesInBatchRequest(n)),
+        _ => {}
+    }
+
+    let mut seen = HashSet::new();
+    for id in ids {
+        let valid = (1..=80).contains(&id.len())
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(SqsError::InvalidBatchEntryId);
+        }
+        if !seen.insert(id) {
+            return Err(SqsError::BatchEntryIdsNotDistinct(id.to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// A batch entry that could not be applied.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BatchResultErrorEntry {
+    pub id: String,
+    pub sender_fault: bool,
+    pub code: String,
+    pub message: String,
+}
+
+impl BatchResultErrorEntry {
+    fn new(id: String, error: SqsError) -> Self {
+        Self {
+            id,
+            sender_fault: error.fault() == Fault::Sender,
+            code: error.code().to_string(),
+            message: error.message(),
+        }
+    }
+}
+
+/// Entries that fail because the queue itself is gone fail the whole
+/// request, as in AWS; anything else is reported per entry.
+fn entry_error(id: String, error: SqsError) -> Result<BatchResultErrorEntry, SqsError> {
+    match error {
+        SqsError::QueueDoesNotExist => Err(error),
+        error => Ok(BatchResultErrorEntry::new(id, error)),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SendMessageBatchRequest {
+    #[serde(deserialize_with = "serde_helpers::deserialize_queue_url")]
+    pub queue_url: String,
+    pub entries: Vec<SendMessageBatchRequestEntry>,
+    /// The `X-Amzn-Trace-Id` header of the HTTP request, applied to every
+    /// entry that doesn't set its own `AWSTraceHeader`.
+    #[serde(skip)]
+    pub trace_header: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SendMessageBatchRequestEntry {
+    pub id: String,
+    pub message_body: String,
+    #[serde(default)]
+    pub message_attributes: HashMap<String, MessageAttributeValue>,
+    #[serde(default)]
+    #[serde(deserialize_with = "serde_helpers::deserialize_optional_number")]
+    pub delay_seconds: Option<u32>,
+    #[serde(default)]
+    pub message_system_attributes: HashMap<String, MessageAttributeValue>,
+    #[serde(default)]
+    pub message_deduplication_id: Option<String>,
+    #[serde(default)]
+    pub message_group_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SendMessageBatchResponse {
+    pub successful: Vec<SendMessageBatchResultEntry>,
+    pub failed: Vec<BatchResultErrorEntry>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SendMessageBatchResultEntry {
+    pub id: String,
+    pub message_id: String,
+    #[serde(rename = "MD5OfMessageBody")]
+    pub md5_of_message_body: String,
+    #[serde(rename = "MD5OfMessageAttributes")]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub md5_of_message_attributes: String,
+    #[serde(rename = "MD5OfMessageSystemAttributes")]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub md5_of_message_system_attributes: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sequence_number: Option<String>,
+}
+
+pub async fn send_message_batch(
+    State(state): State<AppState>,
+    Json(request): Json<SendMessageBatchRequest>,
+) -> Result<SendMessageBatchResponse, SqsError> {
+    validate_batch_entries(
+        "SendMessageBatchRequestEntry",
+        request.entries.iter().map(|e| e.id.as_str()),
+    )?;
+
+    let payload_bytes: usize = request
+        .entries
+        .iter()
+        .map(|e| state::message_size_bytes(&e.message_body, &e.message_attributes))
+        .sum();
+    // A batch may carry as many message bytes, summed over its entries, as
+    // a single message may.
+    if payload_bytes > state.max_message_size_limit {
+        state.validation.violation(SqsError::BatchRequestTooLong {
+            size: payload_bytes,
+            limit: state.max_message_size_limit,
+        })?;
+    }
+
+    let mut response = SendMessageBatchResponse {
+        successful: Vec::new(),
+        failed: Vec::new(),
+    };
+    for entry in request.entries {
+        let result = queue::send_message(
+            State(state.clone()),
+            Json(SendMessageRequest {
+                queue_url: request.queue_url.clone(),
+                message_body: entry.message_body,
+                message_attributes: entry.message_attributes,
+                delay_seconds: entry.delay_seconds,
+                message_system_attributes: entry.message_system_attributes,
+                message_deduplication_id: entry.message_deduplication_id,
+                message_group_id: entry.message_group_id,
+                trace_header: request.trace_header.clone(),
+            }),
+        )
+        .await;
+        match result {
+            Ok(sent) => response.successful.push(SendMessageBatchResultEntry {
+                id: entry.id,
+                message_id: sent.message_id,
+                md5_of_message_body: sent.md5_of_message_body,
+                md5_of_message_attributes: sent.md5_of_message_attributes,
+                md5_of_message_system_attributes: sent.md5_of_message_system_attributes,
+                sequence_number: sent.sequence_number,
+            }),
+            Err(e) => response.failed.push(entry_error(entry.id, e)?),
+        }
+    }
+    telemetry::record_message_ids(response.successful.iter().map(|e| e.message_id.as_str()));
+    Ok(response)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DeleteMessageBatchRequest {
+    #[serde(deserialize_with = "serde_helpers::deserialize_queue_url")]
+    pub queue_url: String,
+    pub entries: Vec<DeleteMessageBatchRequestEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DeleteMessageBatchRequestEntry {
+    pub id: String,
+    pub receipt_handle: String,
+}
+
+/// The response shape shared by `DeleteMessageBatch` and
+/// `ChangeMessageVisibilityBatch`, whose successful entries carry only the id.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BatchResponse {
+    pub successful: Vec<BatchResultEntry>,
+    pub failed: Vec<BatchResultErrorEntry>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BatchResultEntry {
+    pub id: String,
+}
+
+pub async fn delete_message_batch(
+    State(state): State<AppState>,
+    Json(request): Json<DeleteMessageBatchRequest>,
+) -> Result<BatchResponse, SqsError> {
+    validate_batch_entries(
+        "DeleteMessageBatchRequestEntry",
+        request.entries.iter().map(|e| e.id.as_str()),
+    )?;
+
+    let mut response = BatchResponse {
+        successful: Vec::new(),
+        failed: Vec::new(),
+    };
+    let mut message_ids = Vec::new();
+    for entry in request.entries {
+        let result = queue::delete(
+            &state,
+            &DeleteMessageRequest {
+                queue_url: request.queue_url.clone(),
+                receipt_handle: entry.receipt_handle,
+            },
+        )
+        .await;
+        match result {
+            Ok(message_id) => {
+                message_ids.push(message_id);
+                response.successful.push(BatchResultEntry { id: entry.id });
+            }
+            Err(e) => response.failed.push(entry_error(entry.id, e)?),
+        }
+    }
+    telemetry::record_message_ids(message_ids.iter().map(String::as_str));
+    Ok(response)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ChangeMessageVisibilityBatchRequest {
+    #[serde(deserialize_with = "serde_helpers::deserialize_queue_url")]
+    pub queue_url: String,
+    pub entries: Vec<ChangeMessageVisibilityBatchRequestEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ChangeMessageVisibilityBatchRequestEntry {
+    pub id: String,
+    pub receipt_handle: String,
+    #[serde(deserialize_with = "serde_helpers::deserialize_number")]
+    pub visibility_timeout: u32,
+}
+
+pub async fn change_message_visibility_batch(
+    State(state): State<AppState>,
+    Json(request): Json<ChangeMessageVisibilityBatchRequest>,
+) -> Result<BatchResponse, SqsError> {
+    validate_batch_entries(
+        "ChangeMessageVisibilityBatchRequestEntry",
+        request.entries.iter().map(|e| e.id.as_str()),
+    )?;
+
+    let mut response = BatchResponse {
+        successful: Vec::new(),
+        failed: Vec::new(),
+    };
+    let mut message_ids = Vec::new();
+    for entry in request.entries {
+        let result = queue::change_visibility(
+            &state,
+            &ChangeMessageVisibilityRequest {
+                queue_url: request.queue_url.clone(),
+                receipt_handle: entry.receipt_handle,
+                visibility_timeout: entry.visibility_timeout,
+            },
+        )
+        .await;
+        match result {
+            Ok(message_id) => {
+                message_ids.push(message_id);
+                response.successful.push(BatchResultEntry { id: entry.id });
+            }
+            Err(e) => response.failed.push(entry_error(entry.id, e)?),
+        }
+    }
+    telemetry::record_message_ids(message_ids.iter().map(String::as_str));
+    Ok(response)
+}
@@ -0,0 +1,77 @@
+use crate::error::SqsError;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// Opt-in misbehaviour that real SQS is allowed to exhibit but the emulator
+/// otherwise never does, for flushing out consumers that depend on it not
+/// happening. Everything is off by default.
+///
+/// Settings apply server-wide, and can be overridden per queue through the
+/// admin API.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChaosSettings {
+    /// Chance that a `ReceiveMessage` call also returns another copy of a
+    /// message that is already in flight, under a receipt handle of its own.
+    #[serde(default)]
+    pub duplicate_delivery_probability: f64,
+    /// Hand out ready messages in random order rather than send order.
+    /// FIFO queues are never shuffled.
+    #[serde(default)]
+    pub shuffle_delivery: bool,
+}
+
+impl ChaosSettings {
+    pub fn validate(&self) -> Result<(), SqsError> {
+        if !(0.0..=1.0).contains(&self.duplicate_delivery_probability) {
+            return Err(SqsError::InvalidParameterValue(format!(
+                "duplicate_delivery_probability must be between 0 and 1, got {}.",
+                self.duplicate_delivery_probability
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// The random number generator behind every chaos decision, shared by all
+/// queues. Runs that make the same requests in the same order with the same
+/// seed see the same chaos.
+#[derive(Debug, Clone)]
+pub struct ChaosRng {
+    seed: u64,
+    rng: Arc<Mutex<StdRng>>,
+}
+
+impl Default for ChaosRng {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl ChaosRng {
+    /// Seeds from `seed`, or from the OS if it is `None`.
+    pub fn new(seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(rand::random);
+        Self {
+            seed,
+            rng: Arc::new(Mutex::new(StdRng::seed_from_u64(seed))),
+        }
+    }
+
+    /// The seed to pin (see `Config::chaos_seed`) to reproduce this run.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns true with probability `p`.
+    pub fn chance(&self, p: f64) -> bool {
+        p > 0.0 && self.rng.lock().unwrap().random_bool(p.min(1.0))
+    }
+
+    /// A uniformly chosen index below `n`, which must be non-zero.
+    pub fn index(&self, n: usize) -> usize {
+        self.rng.lock().unwrap().random_range(0..n)
+    }
+}
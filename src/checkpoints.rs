@@ -0,0 +1,209 @@
+//! Named copies of the server's state, taken and restored through the admin
+//! API so that a test suite can set up a topology once and start each case
+//! from it.
+//!
+//! A checkpoint is an exported [`StateSnapshot`], kept as JSON: in memory,
+//! or with [`Config::checkpoint_dir`](crate::Config) set, as
+//! `{name}.json` in that directory, where it outlives the server. Like
+//! any snapshot it holds every queue and message, in flight or delayed,
+//! and the FIFO deduplication IDs in effect, but not webhooks or chaos
+//! settings.
+
+use crate::error::SqsError;
+use crate::snapshot::{self, ImportMode, ImportSummary};
+use crate::state::AppState;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// The longest checkpoint name accepted.
+const MAX_NAME_LENGTH: usize = 80;
+
+/// What is known of a checkpoint without reading it back.
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckpointInfo {
+    pub name: String,
+    /// The size of its JSON, in bytes.
+    pub size: usize,
+    /// When it was taken, or for a checkpoint on disk, last written.
+    pub created: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+struct Saved {
+    created: DateTime<Utc>,
+    json: Arc<str>,
+}
+
+/// The server's checkpoints, by name.
+#[derive(Debug)]
+pub struct Checkpoints {
+    /// Where checkpoints are written, if not kept in memory.
+    dir: Option<PathBuf>,
+    saved: Mutex<BTreeMap<String, Saved>>,
+}
+
+impl Checkpoints {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            saved: Mutex::default(),
+        }
+    }
+
+    /// Captures the current state as `name`, replacing any checkpoint of
+    /// that name.
+    pub async fn save(&self, state: &AppState, name: &str) -> Result<CheckpointInfo, SqsError> {
+        validate_name(name)?;
+        let snapshot = snapshot::export(state).await;
+        let json = serde_json::to_string(&snapshot).expect("snapshots serialize");
+        let created = state.clock.now();
+        let info = CheckpointInfo {
+            name: name.to_string(),
+            size: json.len(),
+            created,
+        };
+        match &self.dir {
+            Some(dir) => write_file(dir, name, &json).map_err(|e| io_error(name, e))?,
+            None => {
+                let saved = Saved {
+                    created,
+                    json: json.into(),
+                };
+                self.saved.lock().unwrap().insert(name.to_string(), saved);
+            }
+        }
+        Ok(info)
+    }
+
+    /// Atomically replaces every queue with those in the checkpoint `name`.
+    /// Long polls on the replaced queues are woken, and carry on against
+    /// the restored queue of the same name if there is one.
+    pub async fn restore(&self, state: &AppState, name: &str) -> Result<ImportSummary, SqsError> {
+        validate_name(name)?;
+        let json: Arc<str> = match &self.dir {
+            Some(dir) => match fs::read_to_string(file_path(dir, name)) {
+                Ok(json) => json.into(),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    return Err(not_found(name));
+                }
+                Err(e) => return Err(io_error(name, e)),
+            },
+            None => {
+                let saved = self.saved.lock().unwrap();
+                saved.get(name).map(|s| s.json.clone()).ok_or_else(|| not_found(name))?
+            }
+        };
+        snapshot::import(state, snapshot::parse(&json)?, ImportMode::Replace).await
+    }
+
+    /// Removes and describes the checkpoint `name`.
+    pub fn delete(&self, name: &str) -> Result<CheckpointInfo, SqsError> {
+        validate_name(name)?;
+        let Some(dir) = &self.dir else {
+            let saved = self.saved.lock().unwrap().remove(name).ok_or_else(|| not_found(name))?;
+            return Ok(CheckpointInfo {
+                name: name.to_string(),
+                size: saved.json.len(),
+                created: saved.created,
+            });
+        };
+        let path = file_path(dir, name);
+        let info = match fs::metadata(&path) {
+            Ok(metadata) => file_info(name, &metadata)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(not_found(name)),
+            Err(e) => return Err(io_error(name, e)),
+        };
+        fs::remove_file(&path).map_err(|e| io_error(name, e))?;
+        Ok(info)
+    }
+
+    /// Every checkpoint, by name.
+    pub fn list(&self) -> Result<Vec<CheckpointInfo>, SqsError> {
+        let Some(dir) = &self.dir else {
+            let saved = self.saved.lock().unwrap();
+            let info = saved.iter().map(|(name, saved)| CheckpointInfo {
+                name: name.clone(),
+                size: saved.json.len(),
+                created: saved.created,
+            });
+            return Ok(info.collect());
+        };
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(list_error(e)),
+        };
+        let mut checkpoints = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(list_error)?;
+            let path = entry.path();
+            let Some(name) = path
+                .file_name()
+                .and_then(|f| f.to_str())
+                .and_then(|f| f.strip_suffix(".json"))
+                .filter(|name| validate_name(name).is_ok())
+            else {
+                continue;
+            };
+            let metadata = entry.metadata().map_err(|e| io_error(name, e))?;
+            checkpoints.push(file_info(name, &metadata)?);
+        }
+        checkpoints.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(checkpoints)
+    }
+}
+
+/// Checkpoint names become file names: 1 to 80 alphanumeric characters,
+/// hyphens and underscores.
+fn validate_name(name: &str) -> Result<(), SqsError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(SqsError::InvalidParameterValue(format!(
+            "Invalid checkpoint name {:?}: can only include alphanumeric characters, hyphens, or underscores. 1 to {} in length.",
+            name, MAX_NAME_LENGTH
+        )))
+    }
+}
+
+fn file_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{}.json", name))
+}
+
+/// Writes the checkpoint beside its final path and renames it into place,
+/// so that a checkpoint being replaced is never read half-written.
+fn write_file(dir: &Path, name: &str, json: &str) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let partial = dir.join(format!(".{}.json.partial", name));
+    fs::write(&partial, json)?;
+    fs::rename(&partial, file_path(dir, name))
+}
+
+fn file_info(name: &str, metadata: &fs::Metadata) -> Result<CheckpointInfo, SqsError> {
+    Ok(CheckpointInfo {
+        name: name.to_string(),
+        size: metadata.len() as usize,
+        created: metadata.modified().map_err(|e| io_error(name, e))?.into(),
+    })
+}
+
+fn not_found(name: &str) -> SqsError {
+    SqsError::ResourceNotFound(format!("Checkpoint {} does not exist.", name))
+}
+
+fn io_error(name: &str, e: std::io::Error) -> SqsError {
+    SqsError::InternalError(format!("Failed to access checkpoint {}: {}", name, e))
+}
+
+fn list_error(e: std::io::Error) -> SqsError {
+    SqsError::InternalError(format!("Failed to list checkpoints: {}", e))
+}
@@ -0,0 +1,207 @@
+use crate::queue::{
+    CreateQueueRequest, CreateQueueResponse, GetQueueUrlRequest, GetQueueUrlResponse,
+    ListQueuesRequest, ListQueuesResponse, PurgeQueueRequest, ReceiveMessageRequest,
+    ReceiveMessageResponse, SendMessageRequest, SendMessageResponse,
+};
+use crate::snapshot::MessageSnapshot;
+use crate::state::MessageAttributeValue;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// A thin HTTP client for a running server, speaking the same JSON protocol
+/// as the AWS SDKs. Backs the binary's client subcommands.
+#[derive(Debug, Clone)]
+pub struct Client {
+    endpoint: String,
+    http: reqwest::Client,
+}
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// The server couldn't be reached or sent something unreadable.
+    Http(reqwest::Error),
+    /// The server answered with an SQS error.
+    Sqs { code: String, message: String },
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Http(e) => write!(f, "request failed: {}", e),
+            ClientError::Sqs { code, message } => write!(f, "{}: {}", code, message),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(e: reqwest::Error) -> Self {
+        ClientError::Http(e)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ErrorBody {
+    #[serde(rename = "__type", default)]
+    error_type: String,
+    #[serde(default)]
+    message: String,
+}
+
+impl Client {
+    /// `endpoint` is the server's base URL, e.g. `http://localhost:9324`.
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into().trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    pub async fn create_queue(
+        &self,
+        name: &str,
+        attributes: HashMap<String, String>,
+    ) -> Result<CreateQueueResponse, ClientError> {
+        let request = CreateQueueRequest {
+            queue_name: name.to_string(),
+            attributes,
+            tags: HashMap::new(),
+            template: None,
+        };
+        self.call("CreateQueue", &request).await
+    }
+
+    pub async fn list_queues(&self, prefix: Option<&str>) -> Result<Vec<String>, ClientError> {
+        let mut queue_urls = Vec::new();
+        let mut next_token = None;
+        loop {
+            let request = ListQueuesRequest {
+                queue_name_prefix: prefix.map(str::to_string),
+                max_results: None,
+                next_token,
+            };
+            let response: ListQueuesResponse = self.call("ListQueues", &request).await?;
+            queue_urls.extend(response.queue_urls);
+            match response.next_token {
+                Some(token) => next_token = Some(token),
+                None => return Ok(queue_urls),
+            }
+        }
+    }
+
+    /// Resolves `queue`, either a queue name or a queue URL, to a URL.
+    pub async fn queue_url(&self, queue: &str) -> Result<String, ClientError> {
+        if queue.starts_with("http://") || queue.starts_with("https://") {
+            return Ok(queue.to_string());
+        }
+        let request = GetQueueUrlRequest {
+            queue_name: queue.to_string(),
+            queue_owner_aws_account_id: None,
+        };
+        let response: GetQueueUrlResponse = self.call("GetQueueUrl", &request).await?;
+        Ok(response.queue_url)
+    }
+
+    pub async fn send_message(
+        &self,
+        queue: &str,
+        body: &str,
+        message_attributes: HashMap<String, MessageAttributeValue>,
+        delay_seconds: Option<u32>,
+    ) -> Result<SendMessageResponse, ClientError> {
+        let request = SendMessageRequest {
+            queue_url: self.queue_url(queue).await?,
+            message_body: body.to_string(),
+            message_attributes,
+            delay_seconds,
+            message_system_attributes: HashMap::new(),
+            message_deduplication_id: None,
+            message_group_id: None,
+            trace_header: None,
+        };
+        self.call("SendMessage", &request).await
+    }
+
+    pub async fn receive_message(
+        &self,
+        queue: &str,
+        max_number_of_messages: u32,
+        wait_time_seconds: Option<u32>,
+        visibility_timeout: Option<u32>,
+    ) -> Result<ReceiveMessageResponse, ClientError> {
+        let request = ReceiveMessageRequest {
+            queue_url: self.queue_url(queue).await?,
+            max_number_of_messages,
+            visibility_timeout,
+            wait_time_seconds,
+            message_attribute_names: vec!["All".to_string()],
+            ..Default::default()
+        };
+        self.call("ReceiveMessage", &request).await
+    }
+
+    pub async fn purge_queue(&self, queue: &str) -> Result<(), ClientError> {
+        let request = PurgeQueueRequest {
+            queue_url: self.queue_url(queue).await?,
+        };
+        self.call::<_, serde_json::Value>("PurgeQueue", &request)
+            .await
+            .map(drop)
+    }
+
+    /// Lists up to `limit` of a queue's messages through the admin API,
+    /// without receiving them.
+    pub async fn peek(
+        &self,
+        queue: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<MessageSnapshot>, ClientError> {
+        // Queue URLs end in the queue name.
+        let name = queue.rsplit('/').next().unwrap_or(queue);
+        let mut url = format!("{}/_admin/queues/{}/messages", self.endpoint, name);
+        if let Some(limit) = limit {
+            url.push_str(&format!("?limit={}", limit));
+        }
+        let response = self.http.get(url).send().await?;
+        Self::parse(response).await
+    }
+
+    async fn call<Req: Serialize, Resp: DeserializeOwned>(
+        &self,
+        action: &str,
+        request: &Req,
+    ) -> Result<Resp, ClientError> {
+        let response = self
+            .http
+            .post(format!("{}/", self.endpoint))
+            .header("X-Amz-Target", format!("AmazonSQS.{}", action))
+            .header("Content-Type", "application/x-amz-json-1.0")
+            .json(request)
+            .send()
+            .await?;
+        Self::parse(response).await
+    }
+
+    async fn parse<Resp: DeserializeOwned>(
+        response: reqwest::Response,
+    ) -> Result<Resp, ClientError> {
+        if response.status().is_success() {
+            return Ok(response.json().await?);
+        }
+        let status = response.status();
+        let body: ErrorBody = response.json().await.unwrap_or_default();
+        // `__type` is namespaced, e.g. `com.amazonaws.sqs#QueueDoesNotExist`.
+        let code = match body.error_type.rsplit_once('#') {
+            Some((_, code)) => code.to_string(),
+            None if body.error_type.is_empty() => status.to_string(),
+            None => body.error_type,
+        };
+        Err(ClientError::Sqs {
+            code,
+            message: body.message,
+        })
+    }
+}
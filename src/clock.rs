@@ -0,0 +1,47 @@
+use chrono::{DateTime, Duration, Utc};
+use std::sync::{Arc, Mutex};
+
+/// The time source for everything time-dependent about messages and
+/// queues: delays, visibility timeouts, retention and timestamps.
+///
+/// `Real` follows the system clock. `Manual` starts at the system time when
+/// it is created and only moves when [`Clock::advance`] is called, so tests
+/// can skip over delays instead of sleeping through them. Long-poll waits
+/// still count down in real time; they are woken early whenever an advance
+/// makes messages visible.
+#[derive(Debug, Clone, Default)]
+pub enum Clock {
+    #[default]
+    Real,
+    Manual(Arc<Mutex<DateTime<Utc>>>),
+}
+
+impl Clock {
+    pub fn manual() -> Self {
+        Clock::Manual(Arc::new(Mutex::new(Utc::now())))
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        match self {
+            Clock::Real => Utc::now(),
+            Clock::Manual(now) => *now.lock().unwrap(),
+        }
+    }
+
+    pub fn is_manual(&self) -> bool {
+        matches!(self, Clock::Manual(_))
+    }
+
+    /// Moves a manual clock forward by `by` and returns the new time, or
+    /// `None` for the real clock, which can't be moved.
+    pub fn advance(&self, by: Duration) -> Option<DateTime<Utc>> {
+        match self {
+            Clock::Real => None,
+            Clock::Manual(now) => {
+                let mut now = now.lock().unwrap();
+                *now += by;
+                Some(*now)
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Message bodies as stored in memory: as sent, or LZ4-compressed when
+//! longer than [`Config::compress_bodies_above`](crate::Config::compress_bodies_above).
+//!
+//! Compression is invisible outside the store. A body serializes as the
+//! string that was sent, and `MD5OfBody` is always computed over that
+//! string, never over the compressed bytes.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::fmt;
+
+#[derive(Clone)]
+pub enum MessageBody {
+    Plain(String),
+    /// An LZ4 block of a body of `len` bytes.
+    Compressed { data: Box<[u8]>, len: usize },
+}
+
+impl MessageBody {
+    /// `body`, compressed if it is longer than `threshold` bytes and
+    /// compressing it actually saves space.
+    pub fn new(body: String, threshold: Option<usize>) -> Self {
+        match threshold {
+            Some(threshold) if body.len() > threshold => {
+                let data = lz4_flex::block::compress(body.as_bytes());
+                if data.len() < body.len() {
+                    MessageBody::Compressed {
+                        data: data.into_boxed_slice(),
+                        len: body.len(),
+                    }
+                } else {
+                    MessageBody::Plain(body)
+                }
+            }
+            _ => MessageBody::Plain(body),
+        }
+    }
+
+    /// The body as sent, decompressing it if need be.
+    pub fn as_str(&self) -> Cow<'_, str> {
+        match self {
+            MessageBody::Plain(body) => Cow::Borrowed(body),
+            MessageBody::Compressed { data, len } => {
+                let bytes = lz4_flex::block::decompress(data, *len)
+                    .expect("compressed bodies decompress");
+                Cow::Owned(String::from_utf8(bytes).expect("bodies are compressed from strings"))
+            }
+        }
+    }
+
+    pub fn into_string(self) -> String {
+        match self {
+            MessageBody::Plain(body) => body,
+            compressed => compressed.as_str().into_owned(),
+        }
+    }
+
+    /// Length of the body as sent, in bytes.
+    pub fn len(&self) -> usize {
+        match self {
+            MessageBody::Plain(body) => body.len(),
+            MessageBody::Compressed { len, .. } => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bytes the body takes up as stored.
+    pub fn stored_len(&self) -> usize {
+        match self {
+            MessageBody::Plain(body) => body.len(),
+            MessageBody::Compressed { data, .. } => data.len(),
+        }
+    }
+}
+
+impl Default for MessageBody {
+    fn default() -> Self {
+        MessageBody::Plain(String::new())
+    }
+}
+
+impl From<String> for MessageBody {
+    fn from(body: String) -> Self {
+        MessageBody::Plain(body)
+    }
+}
+
+/// Bodies are equal if they hold the same text, however each is stored.
+impl PartialEq for MessageBody {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for MessageBody {}
+
+impl PartialEq<str> for MessageBody {
+    fn eq(&self, other: &str) -> bool {
+        self.len() == other.len() && self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for MessageBody {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl fmt::Debug for MessageBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.as_str(), f)
+    }
+}
+
+impl Serialize for MessageBody {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for MessageBody {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(MessageBody::Plain)
+    }
+}
@@ -0,0 +1,666 @@
+use crate::attributes::MAX_MESSAGE_SIZE;
+use crate::chaos::ChaosSettings;
+use crate::fixtures::QueueFixture;
+use crate::generators::GeneratorSpec;
+use crate::metrics::DEFAULT_BUCKETS;
+use crate::store::Storage;
+use crate::templates::Templates;
+use crate::validation::ValidationMode;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Default of [`Config::event_log_capacity`].
+pub const DEFAULT_EVENT_LOG_CAPACITY: usize = 100;
+
+/// Server settings. `Config::from_env` reads the `LOCAL_SQS_*` environment
+/// variables; `Config::default` gives the same defaults without consulting
+/// the environment, which is what embedding tests usually want.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The host in queue URLs, and unless `bind` is set, the host to listen
+    /// on: at the default of `localhost`, both `127.0.0.1` and, where IPv6
+    /// is available, `::1`.
+    pub host: String,
+    /// The port in queue URLs and, unless `bind` is set, to listen on. With
+    /// `bind`, the port the addresses were bound to, which must be the same
+    /// for all of them, is used in URLs.
+    pub port: u16,
+    /// Addresses to listen on, such as `127.0.0.1:9324` or `[::1]:9324`,
+    /// each with a listener of its own, instead of `host` and `port`. Both
+    /// are ignored when the process is socket-activated; see
+    /// [`crate::socket_activation`].
+    pub bind: Vec<String>,
+    /// Region used in queue ARNs.
+    pub region: String,
+    /// Account ID used in queue ARNs and URLs.
+    pub account_id: String,
+    /// How often the maintenance task sweeps queues.
+    pub sweep_interval: Duration,
+    /// How long after a `PurgeQueue` further purges of the same queue fail
+    /// with `PurgeQueueInProgress`, like AWS's 60 seconds. Off if unset.
+    pub purge_cooldown: Option<Duration>,
+    /// Fixture file loaded before the server starts accepting requests.
+    pub fixtures: Option<PathBuf>,
+    /// Config file whose queues are created at startup and kept in sync
+    /// while the server runs. See [`ConfigFile`].
+    pub config_file: Option<PathBuf>,
+    /// Sends to a queue already holding this many messages fail with `OverLimit`.
+    pub max_messages_per_queue: Option<usize>,
+    /// Sends that would push the total body bytes across all queues past
+    /// this fail with `OverLimit`.
+    pub max_total_bytes: Option<u64>,
+    /// The error code of sends to a queue at its `MaxQueueLength`, answered
+    /// with status 403. `OverLimit` by default; a throttling code such as
+    /// `RequestThrottled` exercises a producer's retry handling instead.
+    pub queue_full_error_code: String,
+    /// The largest `MaximumMessageSize` a queue may set, and so the largest
+    /// message it may hold. 262144 by default, as SQS used to allow; up to
+    /// 1048576.
+    pub max_message_size_limit: usize,
+    /// Whether invalid requests fail or are let through with a warning.
+    pub validation: ValidationMode,
+    /// Run on a [`Clock::Manual`](crate::clock::Clock) that only moves when
+    /// advanced through the admin API.
+    pub manual_clock: bool,
+    /// Attributes every new queue gets unless `CreateQueue` sets them,
+    /// overriding the AWS defaults.
+    pub default_queue_attributes: HashMap<String, String>,
+    /// Server-wide chaos settings, all off by default.
+    pub chaos: ChaosSettings,
+    /// Seed for the chaos RNG; a random one is picked (and logged) if unset.
+    pub chaos_seed: Option<u64>,
+    /// Path the SQS endpoint is served under, e.g. `/sqs`, included in every
+    /// queue URL. Empty serves it at the root.
+    pub base_path: String,
+    /// Whether the router serves the `/_admin` API.
+    pub admin_api: bool,
+    /// Upper bounds, in seconds, of the buckets of the per-queue latency
+    /// histograms.
+    pub histogram_buckets: Vec<f64>,
+    /// Key receipt handles are signed with, so clients can't forge them.
+    /// Unsigned if unset.
+    pub receipt_handle_secret: Option<String>,
+    /// Seed for message IDs, receipt handle nonces, request IDs and other
+    /// generated IDs, so that the same requests get the same IDs on every
+    /// run; random if unset. Timestamps still follow the clock, and receipt
+    /// handles embed the time they were issued.
+    pub deterministic_ids: Option<u64>,
+    /// Empty queues that go unused (no sends, receives or configuration
+    /// changes) for longer than this are deleted by the sweeper. Off if
+    /// unset.
+    pub idle_queue_ttl: Option<Duration>,
+    /// Names of queues never deleted for being idle. Queues tagged
+    /// [`KEEP_TAG`](crate::state::KEEP_TAG)`=true` are exempt too.
+    pub idle_queue_exempt: Vec<String>,
+    /// How many lifecycle events are kept per queue, and for the server as
+    /// a whole, before the oldest are dropped.
+    pub event_log_capacity: usize,
+    /// How many entries of each message's history are kept; see
+    /// [`history`](crate::history). 0, the default, keeps none.
+    pub message_history: usize,
+    /// SQS requests handled at once; further requests fail with
+    /// `ServiceUnavailable`. Unlimited if unset. The admin API isn't counted.
+    pub max_concurrent_requests: Option<usize>,
+    /// Long polls waiting on a single queue at once; further long polls on
+    /// it fail with `ServiceUnavailable`. Unlimited if unset.
+    pub max_long_polls_per_queue: Option<usize>,
+    /// Open connections; further connections are answered with
+    /// `ServiceUnavailable` and closed. Unlimited if unset. Only [`serve`]
+    /// enforces this, not a mounted [`sqs_router`].
+    ///
+    /// [`serve`]: crate::serve
+    /// [`sqs_router`]: crate::sqs_router
+    pub max_connections: Option<usize>,
+    /// Message bodies longer than this many bytes are stored LZ4-compressed
+    /// and decompressed whenever they are read. Bodies are stored as sent
+    /// if unset. Repetitive JSON shrinks around tenfold, at the cost of a
+    /// decompression on every receive; the `receive_message_in_process`
+    /// benchmark measures it.
+    pub compress_bodies_above: Option<usize>,
+    /// Where queues are kept. [`Storage::Sqlite`] keeps their messages in
+    /// the database at `db_path` rather than in memory, and
+    /// [`Storage::Redis`] on the Redis server at `redis_url`; both reload
+    /// them on start.
+    pub storage: Storage,
+    /// The SQLite database used with [`Storage::Sqlite`], created if missing.
+    pub db_path: PathBuf,
+    /// The Redis server used with [`Storage::Redis`].
+    pub redis_url: String,
+    /// Where admin API checkpoints are written, as `{name}.json`, so that
+    /// they outlive the server. Checkpoints are kept in memory if unset.
+    pub checkpoint_dir: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 9324,
+            bind: Vec::new(),
+            region: "us-east-1".to_string(),
+            account_id: "000000000000".to_string(),
+            sweep_interval: Duration::from_secs(1),
+            fixtures: None,
+            config_file: None,
+            max_messages_per_queue: None,
+            max_total_bytes: None,
+            queue_full_error_code: "OverLimit".to_string(),
+            max_message_size_limit: MAX_MESSAGE_SIZE,
+            validation: ValidationMode::Strict,
+            manual_clock: false,
+            default_queue_attributes: HashMap::new(),
+            chaos: ChaosSettings::default(),
+            chaos_seed: None,
+            base_path: String::new(),
+            admin_api: true,
+            histogram_buckets: DEFAULT_BUCKETS.to_vec(),
+            receipt_handle_secret: None,
+            deterministic_ids: None,
+            idle_queue_ttl: None,
+            purge_cooldown: None,
+            idle_queue_exempt: Vec::new(),
+            event_log_capacity: DEFAULT_EVENT_LOG_CAPACITY,
+            message_history: 0,
+            max_concurrent_requests: None,
+            max_long_polls_per_queue: None,
+            max_connections: None,
+            compress_bodies_above: None,
+            storage: Storage::Memory,
+            db_path: PathBuf::from("local-sqs.db"),
+            redis_url: "redis://127.0.0.1/".to_string(),
+            checkpoint_dir: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        config.apply_env();
+        config
+    }
+
+    /// Overrides settings with any `LOCAL_SQS_*` environment variables that
+    /// are set.
+    pub fn apply_env(&mut self) {
+        if let Ok(host) = env::var("LOCAL_SQS_HOST") {
+            self.host = host;
+        }
+        if let Some(port) = env::var("LOCAL_SQS_PORT").ok().and_then(|s| s.parse().ok()) {
+            self.port = port;
+        }
+        if let Ok(addrs) = env::var("LOCAL_SQS_BIND") {
+            // Comma-separated addresses.
+            self.bind = addrs
+                .split(',')
+                .map(str::trim)
+                .filter(|addr| !addr.is_empty())
+                .map(String::from)
+                .collect();
+        }
+        if let Ok(region) = env::var("LOCAL_SQS_REGION") {
+            self.region = region;
+        }
+        if let Ok(account_id) = env::var("LOCAL_SQS_ACCOUNT_ID") {
+            self.account_id = account_id;
+        }
+        if let Some(ms) = env::var("LOCAL_SQS_SWEEP_INTERVAL_MS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            self.sweep_interval = Duration::from_millis(ms);
+        }
+        if let Some(fixtures) = env::var_os("LOCAL_SQS_FIXTURES") {
+            self.fixtures = Some(PathBuf::from(fixtures));
+        }
+        if let Some(config_file) = env::var_os("LOCAL_SQS_CONFIG") {
+            self.config_file = Some(PathBuf::from(config_file));
+        }
+        if let Some(max) = env::var("LOCAL_SQS_MAX_MESSAGES_PER_QUEUE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            self.max_messages_per_queue = Some(max);
+        }
+        if let Some(max) = env::var("LOCAL_SQS_MAX_TOTAL_BYTES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            self.max_total_bytes = Some(max);
+        }
+        if let Ok(code) = env::var("LOCAL_SQS_QUEUE_FULL_ERROR_CODE") {
+            self.queue_full_error_code = code;
+        }
+        if let Some(limit) = env::var("LOCAL_SQS_MAX_MESSAGE_SIZE_LIMIT")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            self.max_message_size_limit = limit;
+        }
+        if let Some(mode) = env::var("LOCAL_SQS_VALIDATION")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            self.validation = mode;
+        }
+        if let Some(manual_clock) = env::var("LOCAL_SQS_MANUAL_CLOCK")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            self.manual_clock = manual_clock;
+        }
+        if let Ok(defaults) = env::var("LOCAL_SQS_DEFAULT_QUEUE_ATTRIBUTES") {
+            // `Name=Value` pairs separated by commas; values containing
+            // commas (e.g. policies) have to come from the config file.
+            for pair in defaults.split(',').filter(|pair| !pair.trim().is_empty()) {
+                if let Some((name, value)) = pair.split_once('=') {
+                    self.default_queue_attributes
+                        .insert(name.trim().to_string(), value.trim().to_string());
+                }
+            }
+        }
+        if let Some(p) = env::var("LOCAL_SQS_DUPLICATE_DELIVERY_PROBABILITY")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            self.chaos.duplicate_delivery_probability = p;
+        }
+        if let Some(shuffle) = env::var("LOCAL_SQS_SHUFFLE_DELIVERY")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            self.chaos.shuffle_delivery = shuffle;
+        }
+        if let Some(seed) = env::var("LOCAL_SQS_CHAOS_SEED")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            self.chaos_seed = Some(seed);
+        }
+        if let Ok(base_path) = env::var("LOCAL_SQS_BASE_PATH") {
+            self.base_path = base_path;
+        }
+        if let Ok(buckets) = env::var("LOCAL_SQS_HISTOGRAM_BUCKETS") {
+            // Comma-separated seconds; unparsable lists are ignored.
+            if let Ok(buckets) = buckets.split(',').map(|b| b.trim().parse()).collect() {
+                self.histogram_buckets = buckets;
+            }
+        }
+        if let Ok(secret) = env::var("LOCAL_SQS_RECEIPT_HANDLE_SECRET") {
+            self.receipt_handle_secret = Some(secret);
+        }
+        if let Some(seed) = env::var("LOCAL_SQS_DETERMINISTIC_IDS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            self.deterministic_ids = Some(seed);
+        }
+        if let Some(secs) = env::var("LOCAL_SQS_IDLE_QUEUE_TTL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            self.idle_queue_ttl = Some(Duration::from_secs(secs));
+        }
+        if let Some(secs) = env::var("LOCAL_SQS_PURGE_COOLDOWN_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            self.purge_cooldown = Some(Duration::from_secs(secs));
+        }
+        if let Ok(names) = env::var("LOCAL_SQS_IDLE_QUEUE_EXEMPT") {
+            // Comma-separated queue names.
+            self.idle_queue_exempt = names
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(String::from)
+                .collect();
+        }
+        if let Some(capacity) = env::var("LOCAL_SQS_EVENT_LOG_CAPACITY")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            self.event_log_capacity = capacity;
+        }
+        if let Some(capacity) = env::var("LOCAL_SQS_MESSAGE_HISTORY")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            self.message_history = capacity;
+        }
+        if let Some(max) = env::var("LOCAL_SQS_MAX_CONCURRENT_REQUESTS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            self.max_concurrent_requests = Some(max);
+        }
+        if let Some(max) = env::var("LOCAL_SQS_MAX_LONG_POLLS_PER_QUEUE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            self.max_long_polls_per_queue = Some(max);
+        }
+        if let Some(max) = env::var("LOCAL_SQS_MAX_CONNECTIONS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            self.max_connections = Some(max);
+        }
+        if let Some(threshold) = env::var("LOCAL_SQS_COMPRESS_BODIES_ABOVE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            self.compress_bodies_above = Some(threshold);
+        }
+        if let Some(storage) = env::var("LOCAL_SQS_STORAGE").ok().and_then(|s| s.parse().ok()) {
+            self.storage = storage;
+        }
+        if let Some(path) = env::var_os("LOCAL_SQS_DB_PATH") {
+            self.db_path = PathBuf::from(path);
+        }
+        if let Ok(url) = env::var("LOCAL_SQS_REDIS_URL") {
+            self.redis_url = url;
+        }
+        if let Some(dir) = env::var_os("LOCAL_SQS_CHECKPOINT_DIR") {
+            self.checkpoint_dir = Some(PathBuf::from(dir));
+        }
+    }
+}
+
+/// A YAML config file: server settings plus the queues that should exist.
+///
+/// ```yaml
+/// port: 9324
+/// region: eu-west-1
+/// default_queue_attributes:
+///   VisibilityTimeout: "5"
+/// prune: false
+/// templates:
+///   worker:
+///     tags:
+///       team: payments
+///     dlq_suffix: -dlq
+/// queues:
+///   - name: jobs
+///     template: worker
+///     attributes:
+///       VisibilityTimeout: "5"
+/// ```
+///
+/// Server settings rank below environment variables and command-line flags
+/// and are only read at startup; changing them in a running server logs a
+/// warning. `queues` are reloaded whenever the file changes: new queues are
+/// created and changed attributes applied, while queues removed from the
+/// file are only deleted if `prune` is set. `templates` are reloaded along
+/// with them; see [`QueueTemplate`](crate::templates::QueueTemplate).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub bind: Option<Vec<String>>,
+    pub region: Option<String>,
+    pub account_id: Option<String>,
+    pub sweep_interval_ms: Option<u64>,
+    pub max_messages_per_queue: Option<usize>,
+    pub max_total_bytes: Option<u64>,
+    pub queue_full_error_code: Option<String>,
+    pub max_message_size_limit: Option<usize>,
+    pub validation: Option<ValidationMode>,
+    pub manual_clock: Option<bool>,
+    #[serde(default)]
+    pub default_queue_attributes: HashMap<String, String>,
+    pub chaos: Option<ChaosSettings>,
+    pub chaos_seed: Option<u64>,
+    pub base_path: Option<String>,
+    pub histogram_buckets: Option<Vec<f64>>,
+    pub receipt_handle_secret: Option<String>,
+    pub deterministic_ids: Option<u64>,
+    pub idle_queue_ttl_secs: Option<u64>,
+    pub purge_cooldown_secs: Option<u64>,
+    pub idle_queue_exempt: Option<Vec<String>>,
+    pub event_log_capacity: Option<usize>,
+    pub message_history: Option<usize>,
+    pub max_concurrent_requests: Option<usize>,
+    pub max_long_polls_per_queue: Option<usize>,
+    pub max_connections: Option<usize>,
+    pub compress_bodies_above: Option<usize>,
+    pub storage: Option<Storage>,
+    pub db_path: Option<PathBuf>,
+    pub redis_url: Option<String>,
+    pub checkpoint_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub prune: bool,
+    #[serde(default)]
+    pub templates: Templates,
+    /// Started once the file's queues are created, and not restarted when
+    /// the file changes.
+    #[serde(default)]
+    pub generators: Vec<GeneratorSpec>,
+    #[serde(default)]
+    pub queues: Vec<QueueFixture>,
+}
+
+impl ConfigFile {
+    pub fn from_yaml(s: &str) -> Result<Self, serde_yaml::Error> {
+        serde_yaml::from_str(s)
+    }
+
+    pub fn read(path: &Path) -> std::io::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::from_yaml(&contents).map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("invalid config file {}: {}", path.display(), e),
+            )
+        })
+    }
+
+    /// Copies the server settings this file sets into `config`.
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(host) = &self.host {
+            config.host = host.clone();
+        }
+        if let Some(port) = self.port {
+            config.port = port;
+        }
+        if let Some(addrs) = &self.bind {
+            config.bind = addrs.clone();
+        }
+        if let Some(region) = &self.region {
+            config.region = region.clone();
+        }
+        if let Some(account_id) = &self.account_id {
+            config.account_id = account_id.clone();
+        }
+        if let Some(ms) = self.sweep_interval_ms {
+            config.sweep_interval = Duration::from_millis(ms);
+        }
+        if let Some(max) = self.max_messages_per_queue {
+            config.max_messages_per_queue = Some(max);
+        }
+        if let Some(max) = self.max_total_bytes {
+            config.max_total_bytes = Some(max);
+        }
+        if let Some(code) = &self.queue_full_error_code {
+            config.queue_full_error_code = code.clone();
+        }
+        if let Some(limit) = self.max_message_size_limit {
+            config.max_message_size_limit = limit;
+        }
+        if let Some(mode) = self.validation {
+            config.validation = mode;
+        }
+        if let Some(manual_clock) = self.manual_clock {
+            config.manual_clock = manual_clock;
+        }
+        config
+            .default_queue_attributes
+            .extend(self.default_queue_attributes.clone());
+        if let Some(chaos) = self.chaos {
+            config.chaos = chaos;
+        }
+        if let Some(seed) = self.chaos_seed {
+            config.chaos_seed = Some(seed);
+        }
+        if let Some(base_path) = &self.base_path {
+            config.base_path = base_path.clone();
+        }
+        if let Some(buckets) = &self.histogram_buckets {
+            config.histogram_buckets = buckets.clone();
+        }
+        if let Some(secret) = &self.receipt_handle_secret {
+            config.receipt_handle_secret = Some(secret.clone());
+        }
+        if let Some(seed) = self.deterministic_ids {
+            config.deterministic_ids = Some(seed);
+        }
+        if let Some(secs) = self.idle_queue_ttl_secs {
+            config.idle_queue_ttl = Some(Duration::from_secs(secs));
+        }
+        if let Some(secs) = self.purge_cooldown_secs {
+            config.purge_cooldown = Some(Duration::from_secs(secs));
+        }
+        if let Some(names) = &self.idle_queue_exempt {
+            config.idle_queue_exempt = names.clone();
+        }
+        if let Some(capacity) = self.event_log_capacity {
+            config.event_log_capacity = capacity;
+        }
+        if let Some(capacity) = self.message_history {
+            config.message_history = capacity;
+        }
+        if let Some(max) = self.max_concurrent_requests {
+            config.max_concurrent_requests = Some(max);
+        }
+        if let Some(max) = self.max_long_polls_per_queue {
+            config.max_long_polls_per_queue = Some(max);
+        }
+        if let Some(max) = self.max_connections {
+            config.max_connections = Some(max);
+        }
+        if let Some(threshold) = self.compress_bodies_above {
+            config.compress_bodies_above = Some(threshold);
+        }
+        if let Some(storage) = self.storage {
+            config.storage = storage;
+        }
+        if let Some(path) = &self.db_path {
+            config.db_path = path.clone();
+        }
+        if let Some(url) = &self.redis_url {
+            config.redis_url = url.clone();
+        }
+        if let Some(dir) = &self.checkpoint_dir {
+            config.checkpoint_dir = Some(dir.clone());
+        }
+    }
+
+    /// Names of the server settings that differ between `self` and `other`.
+    pub fn changed_settings(&self, other: &ConfigFile) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.host != other.host {
+            changed.push("host");
+        }
+        if self.port != other.port {
+            changed.push("port");
+        }
+        if self.bind != other.bind {
+            changed.push("bind");
+        }
+        if self.region != other.region {
+            changed.push("region");
+        }
+        if self.account_id != other.account_id {
+            changed.push("account_id");
+        }
+        if self.sweep_interval_ms != other.sweep_interval_ms {
+            changed.push("sweep_interval_ms");
+        }
+        if self.max_messages_per_queue != other.max_messages_per_queue {
+            changed.push("max_messages_per_queue");
+        }
+        if self.max_total_bytes != other.max_total_bytes {
+            changed.push("max_total_bytes");
+        }
+        if self.queue_full_error_code != other.queue_full_error_code {
+            changed.push("queue_full_error_code");
+        }
+        if self.max_message_size_limit != other.max_message_size_limit {
+            changed.push("max_message_size_limit");
+        }
+        if self.validation != other.validation {
+            changed.push("validation");
+        }
+        if self.manual_clock != other.manual_clock {
+            changed.push("manual_clock");
+        }
+        if self.default_queue_attributes != other.default_queue_attributes {
+            changed.push("default_queue_attributes");
+        }
+        if self.chaos != other.chaos {
+            changed.push("chaos");
+        }
+        if self.chaos_seed != other.chaos_seed {
+            changed.push("chaos_seed");
+        }
+        if self.base_path != other.base_path {
+            changed.push("base_path");
+        }
+        if self.histogram_buckets != other.histogram_buckets {
+            changed.push("histogram_buckets");
+        }
+        if self.receipt_handle_secret != other.receipt_handle_secret {
+            changed.push("receipt_handle_secret");
+        }
+        if self.deterministic_ids != other.deterministic_ids {
+            changed.push("deterministic_ids");
+        }
+        if self.idle_queue_ttl_secs != other.idle_queue_ttl_secs {
+            changed.push("idle_queue_ttl_secs");
+        }
+        if self.purge_cooldown_secs != other.purge_cooldown_secs {
+            changed.push("purge_cooldown_secs");
+        }
+        if self.idle_queue_exempt != other.idle_queue_exempt {
+            changed.push("idle_queue_exempt");
+        }
+        if self.event_log_capacity != other.event_log_capacity {
+            changed.push("event_log_capacity");
+        }
+        if self.message_history != other.message_history {
+            changed.push("message_history");
+        }
+        if self.max_concurrent_requests != other.max_concurrent_requests {
+            changed.push("max_concurrent_requests");
+        }
+        if self.max_long_polls_per_queue != other.max_long_polls_per_queue {
+            changed.push("max_long_polls_per_queue");
+        }
+        if self.max_connections != other.max_connections {
+            changed.push("max_connections");
+        }
+        if self.compress_bodies_above != other.compress_bodies_above {
+            changed.push("compress_bodies_above");
+        }
+        if self.storage != other.storage {
+            changed.push("storage");
+        }
+        if self.db_path != other.db_path {
+            changed.push("db_path");
+        }
+        if self.redis_url != other.redis_url {
+            changed.push("redis_url");
+        }
+        if self.checkpoint_dir != other.checkpoint_dir {
+            changed.push("checkpoint_dir");
+        }
+        if self.generators != other.generators {
+            changed.push("generators");
+        }
+        changed
+    }
+}
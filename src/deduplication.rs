@@ -0,0 +1,120 @@
+//! FIFO deduplication. For five minutes after a message is accepted, sends
+//! carrying the same deduplication ID succeed without enqueuing anything,
+//! answering with the original message's `MessageId` and `SequenceNumber`.
+
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+
+/// How long a deduplication ID suppresses repeated sends.
+pub const DEDUPLICATION_INTERVAL: Duration = Duration::minutes(5);
+
+/// The message a deduplication ID was first accepted with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Accepted {
+    pub message_id: String,
+    pub sequence_number: String,
+}
+
+/// Deduplication IDs are scoped to the queue, or with a
+/// `DeduplicationScope` of `messageGroup`, to the message group.
+pub type DeduplicationKey = (Option<String>, String);
+
+/// A queue's deduplication IDs accepted within the last
+/// [`DEDUPLICATION_INTERVAL`].
+#[derive(Debug, Clone, Default)]
+pub struct DeduplicationCache {
+    accepted: HashMap<DeduplicationKey, (Accepted, DateTime<Utc>)>,
+    /// Keys by expiry, oldest first, for pruning without a scan.
+    expiries: VecDeque<(DateTime<Utc>, DeduplicationKey)>,
+    /// What changed since the last [`take_changes`](Self::take_changes),
+    /// once tracking is on.
+    changes: Option<DeduplicationChanges>,
+}
+
+/// An accepted deduplication ID and when it stops suppressing sends.
+pub type Entry = (DeduplicationKey, Accepted, DateTime<Utc>);
+
+/// What changed in a [`DeduplicationCache`] since it was last asked.
+#[derive(Debug, Clone, Default)]
+pub struct DeduplicationChanges {
+    pub inserted: Vec<Entry>,
+    /// Keys whose entries expired and haven't been inserted again since.
+    pub expired: Vec<DeduplicationKey>,
+}
+
+impl DeduplicationCache {
+    /// The message accepted under `key` within the interval before `now`.
+    pub fn get(&mut self, key: &DeduplicationKey, now: DateTime<Utc>) -> Option<&Accepted> {
+        self.expire(now);
+        self.accepted.get(key).map(|(accepted, _)| accepted)
+    }
+
+    /// Records that `key` was accepted as `accepted` at `now`.
+    pub fn insert(&mut self, key: DeduplicationKey, accepted: Accepted, now: DateTime<Utc>) {
+        self.restore(key, accepted, now + DEDUPLICATION_INTERVAL);
+    }
+
+    /// Records `key` as accepted until `expires`, as when reloading a stored
+    /// queue. Entries must be restored in order of expiry.
+    pub fn restore(&mut self, key: DeduplicationKey, accepted: Accepted, expires: DateTime<Utc>) {
+        if let Some(changes) = &mut self.changes {
+            changes.expired.retain(|expired| *expired != key);
+            changes.inserted.push((key.clone(), accepted.clone(), expires));
+        }
+        self.expiries.push_back((expires, key.clone()));
+        self.accepted.insert(key, (accepted, expires));
+    }
+
+    /// Starts recording changes, for a store that writes only those back.
+    /// Every current entry counts as inserted.
+    pub fn track_changes(&mut self) {
+        self.changes = Some(DeduplicationChanges {
+            inserted: self.entries(),
+            expired: Vec::new(),
+        });
+    }
+
+    /// Every entry, in order of expiry as [`restore`](Self::restore) takes
+    /// them.
+    pub fn entries(&self) -> Vec<Entry> {
+        let mut entries: Vec<Entry> = self
+            .accepted
+            .iter()
+            .map(|(key, (accepted, expires))| (key.clone(), accepted.clone(), *expires))
+            .collect();
+        entries.sort_by(|a, b| (a.2, &a.0).cmp(&(b.2, &b.0)));
+        entries
+    }
+
+    /// What changed since tracking started or this was last called. Empty
+    /// unless tracking.
+    pub fn take_changes(&mut self) -> DeduplicationChanges {
+        self.changes.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Forgets the entries that stop suppressing sends by `now`.
+    pub fn expire(&mut self, now: DateTime<Utc>) {
+        while let Some((expires, _)) = self.expiries.front()
+            && *expires <= now
+        {
+            let (expires, key) = self.expiries.pop_front().expect("checked above");
+            if self.accepted.get(&key).is_some_and(|(_, e)| *e == expires) {
+                self.accepted.remove(&key);
+                if let Some(changes) = &mut self.changes {
+                    changes.inserted.retain(|(inserted, _, _)| *inserted != key);
+                    changes.expired.push(key);
+                }
+            }
+        }
+    }
+}
+
+/// The deduplication ID of `body` on a queue with
+/// `ContentBasedDeduplication`: its SHA-256 digest, in hex.
+pub fn content_deduplication_id(body: &str) -> String {
+    Sha256::digest(body.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
@@ -0,0 +1,340 @@
+use crate::batch;
+use crate::error::SqsError;
+use crate::move_tasks;
+use crate::queue;
+use crate::state::AppState;
+use crate::telemetry;
+use crate::templates;
+use crate::urls;
+use axum::extract::State;
+use axum::http::{header, HeaderMap};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::de::{self, DeserializeOwned, Deserializer, IgnoredAny, MapAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::LazyLock;
+
+type BoxFuture = Pin<Box<dyn Future<Output = Response> + Send>>;
+
+/// Runs one action against a request body: deserializes it, calls the
+/// handler and serializes the result or error.
+type Action = Box<dyn Fn(AppState, &HeaderMap, &str) -> BoxFuture + Send + Sync>;
+
+/// Maps action names (`SendMessage`, without the `AmazonSQS.` target prefix)
+/// to their handlers, so that every action shares the same request parsing
+/// and response encoding.
+#[derive(Default)]
+pub struct Registry {
+    actions: BTreeMap<&'static str, Action>,
+}
+
+impl Registry {
+    /// Registers `handler` for `action`. Its result is sent back as JSON;
+    /// handlers with nothing to return answer [`EmptyResponse`].
+    pub fn register<Req, Resp, F, Fut>(&mut self, action: &'static str, handler: F) -> &mut Self
+    where
+        Req: DeserializeOwned + Send + 'static,
+        Resp: Serialize,
+        F: Fn(State<AppState>, Json<Req>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Resp, SqsError>> + Send + 'static,
+    {
+        self.register_with(action, |_, _| {}, handler)
+    }
+
+    /// Like [`register`](Self::register), with `prepare` filling in request
+    /// fields that come from HTTP headers rather than the body.
+    pub fn register_with<Req, Resp, F, Fut>(
+        &mut self,
+        action: &'static str,
+        prepare: fn(&mut Req, &HeaderMap),
+        handler: F,
+    ) -> &mut Self
+    where
+        Req: DeserializeOwned + Send + 'static,
+        Resp: Serialize,
+        F: Fn(State<AppState>, Json<Req>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Resp, SqsError>> + Send + 'static,
+    {
+        let known_fields = field_names::<Req>();
+        let run = move |state: AppState, headers: &HeaderMap, body: &str| -> BoxFuture {
+            let mut request: Req = match serde_json::from_str(body) {
+                Ok(request) => request,
+                Err(e) => {
+                    let error = match missing_field(&e) {
+                        Some(field) => SqsError::MissingParameter(field),
+                        None => SqsError::InvalidParameterValue(format!(
+                            "Invalid request for {}: {}",
+                            action, e
+                        )),
+                    };
+                    return Box::pin(async move { error_response(error) });
+                }
+            };
+            if let Err(error) = check_request(&state, action, known_fields, body) {
+                return Box::pin(async move { error_response(error) });
+            }
+            prepare(&mut request, headers);
+            let response = handler(State(state), Json(request));
+            Box::pin(async move {
+                match response.await {
+                    Ok(response) => json_response(&response),
+                    Err(e) => error_response(e),
+                }
+            })
+        };
+        self.actions.insert(action, Box::new(run));
+        self
+    }
+
+    pub fn contains(&self, action: &str) -> bool {
+        self.actions.contains_key(action)
+    }
+
+    /// Registered action names, sorted.
+    pub fn actions(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.actions.keys().copied()
+    }
+
+    /// Runs `action` on a JSON request body, answering `InvalidAction` if it
+    /// isn't registered.
+    pub async fn dispatch(
+        &self,
+        state: AppState,
+        action: &str,
+        headers: &HeaderMap,
+        body: &str,
+    ) -> Response {
+        match self.actions.get(action) {
+            Some(run) => run(state, headers, body).await,
+            None => error_response(SqsError::InvalidAction(action.to_string())),
+        }
+    }
+}
+
+/// The result of actions with nothing to return, such as `DeleteMessage`:
+/// `{}`, as in the AWS JSON protocol. Returning `()` would answer `null`,
+/// which some SDKs warn about or reject.
+#[derive(Debug, Default, Serialize)]
+pub struct EmptyResponse {}
+
+/// The response to a request that failed with `error`, which is recorded on
+/// the request's span.
+fn error_response(error: SqsError) -> Response {
+    telemetry::record_error(&error);
+    error.into_response()
+}
+
+/// The content type of AWS JSON protocol responses, errors included.
+pub(crate) const AMZ_JSON: &str = "application/x-amz-json-1.0";
+
+/// Bytes reserved up front for a serialized response: enough for a send or
+/// a receive of a small message without the buffer growing.
+const RESPONSE_CAPACITY: usize = 512;
+
+/// `response` as an AWS JSON body, serialized into a buffer sized for
+/// typical responses, or an `InternalError` if it can't be serialized.
+fn json_response<T: Serialize>(response: &T) -> Response {
+    let mut body = Vec::with_capacity(RESPONSE_CAPACITY);
+    match serde_json::to_writer(&mut body, response) {
+        Ok(()) => ([(header::CONTENT_TYPE, AMZ_JSON)], body).into_response(),
+        Err(e) => SqsError::InternalError(e.to_string()).into_response(),
+    }
+}
+
+/// Every SQS action the emulator implements.
+pub fn sqs_actions() -> &'static Registry {
+    static ACTIONS: LazyLock<Registry> = LazyLock::new(|| {
+        let mut registry = Registry::default();
+        registry
+            .register_with("CreateQueue", set_template_header, templates::create_queue)
+            .register("GetQueueUrl", queue::get_queue_url)
+            .register("ListQueues", queue::list_queues)
+            .register("DeleteQueue", queue::delete_queue)
+            .register("PurgeQueue", queue::purge_queue)
+            .register("GetQueueAttributes", queue::get_queue_attributes)
+            .register("SetQueueAttributes", queue::set_queue_attributes)
+            .register_with("SendMessage", set_trace_header, queue::send_message)
+            .register("ReceiveMessage", queue::receive_message)
+            .register("DeleteMessage", queue::delete_message)
+            .register("ChangeMessageVisibility", queue::change_message_visibility)
+            .register_with(
+                "SendMessageBatch",
+                set_batch_trace_header,
+                batch::send_message_batch,
+            )
+            .register("DeleteMessageBatch", batch::delete_message_batch)
+            .register(
+                "ChangeMessageVisibilityBatch",
+                batch::change_message_visibility_batch,
+            )
+            .register("AddPermission", queue::add_permission)
+            .register("RemovePermission", queue::remove_permission)
+            .register("StartMessageMoveTask", move_tasks::start_message_move_task)
+            .register("ListMessageMoveTasks", move_tasks::list_message_move_tasks)
+            .register("CancelMessageMoveTask", move_tasks::cancel_message_move_task)
+            .register("ListQueueTags", queue::list_queue_tags)
+            .register("TagQueue", queue::tag_queue)
+            .register("UntagQueue", queue::untag_queue);
+        registry
+    });
+    &ACTIONS
+}
+
+/// The required field a request failed to deserialize for lacking, if that
+/// is why it failed. Request structs leave `#[serde(default)]` off exactly
+/// the fields AWS requires, and serde reports those as
+/// ``missing field `Name` ``.
+fn missing_field(error: &serde_json::Error) -> Option<String> {
+    if !error.is_data() {
+        return None;
+    }
+    let message = error.to_string();
+    let (field, _) = message.strip_prefix("missing field `")?.split_once('`')?;
+    Some(field.to_string())
+}
+
+/// Checks what deserializing a request body can't: that its `QueueUrl`, if
+/// it has one, could name a queue on this server, and that it has no fields
+/// `known` doesn't list.
+fn check_request(
+    state: &AppState,
+    action: &str,
+    known: &[&str],
+    body: &str,
+) -> Result<(), SqsError> {
+    let Ok(fields) = serde_json::from_str::<TopLevelFields>(body) else {
+        return Ok(());
+    };
+    if let Some(queue_url) = &fields.queue_url {
+        let valid = urls::validate_queue_url(queue_url, &state.queue_url(""));
+        state.validation.check(valid)?;
+    }
+    check_fields(state, action, known, &fields.names)
+}
+
+/// Reports top-level request fields that `known` doesn't list, such as a
+/// misspelled `MessageGroupID`, which serde would otherwise drop silently.
+/// Only strict validation rejects the request; lenient mode logs a warning.
+fn check_fields(
+    state: &AppState,
+    action: &str,
+    known: &[&str],
+    fields: &[String],
+) -> Result<(), SqsError> {
+    // An empty list means the field names couldn't be determined.
+    if known.is_empty() {
+        return Ok(());
+    }
+    for field in fields.iter().filter(|field| !known.contains(&field.as_str())) {
+        state.validation.violation(SqsError::InvalidParameterValue(format!(
+            "Unknown field {} in {} request.",
+            field, action
+        )))?;
+    }
+    Ok(())
+}
+
+/// A request body's top-level field names, and its `QueueUrl` if it is a
+/// string, found in one pass that skips over every other value.
+struct TopLevelFields {
+    names: Vec<String>,
+    queue_url: Option<String>,
+}
+
+impl<'de> Deserialize<'de> for TopLevelFields {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FieldsVisitor;
+
+        impl<'de> Visitor<'de> for FieldsVisitor {
+            type Value = TopLevelFields;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut fields = TopLevelFields {
+                    names: Vec::new(),
+                    queue_url: None,
+                };
+                while let Some(name) = map.next_key::<String>()? {
+                    if name == "QueueUrl" {
+                        fields.queue_url = map.next_value()?;
+                    } else {
+                        map.next_value::<IgnoredAny>()?;
+                    }
+                    fields.names.push(name);
+                }
+                Ok(fields)
+            }
+        }
+
+        deserializer.deserialize_map(FieldsVisitor)
+    }
+}
+
+/// The top-level field names `T` deserializes from. Derived `Deserialize`
+/// impls hand them to `deserialize_struct`, so a deserializer that records
+/// them and then gives up is enough to find them. Empty for types that
+/// aren't plain structs.
+fn field_names<T: DeserializeOwned>() -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> Deserializer<'de> for FieldNames<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(de::Error::custom("field names recorded"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+        ignored_any
+    }
+}
+
+fn set_template_header(request: &mut queue::CreateQueueRequest, headers: &HeaderMap) {
+    request.template = headers
+        .get(templates::TEMPLATE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty())
+        .map(str::to_string);
+}
+
+fn set_trace_header(request: &mut queue::SendMessageRequest, headers: &HeaderMap) {
+    request.trace_header = trace_header(headers);
+}
+
+fn set_batch_trace_header(request: &mut batch::SendMessageBatchRequest, headers: &HeaderMap) {
+    request.trace_header = trace_header(headers);
+}
+
+/// The X-Ray trace header of a request, which `SendMessage` and
+/// `SendMessageBatch` attach to messages as `AWSTraceHeader`.
+fn trace_header(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get("X-Amzn-Trace-Id")
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
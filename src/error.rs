@@ -1,50 +1,258 @@
-use axum::http::StatusCode;
+use crate::dispatch::AMZ_JSON;
+use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
-use axum::Json;
 use serde_json::json;
+use std::fmt;
+use std::sync::Arc;
 
+/// Whether an error was caused by the request (`Sender`) or by the service
+/// (`Receiver`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    Sender,
+    Receiver,
+}
+
+impl Fault {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Fault::Sender => "Sender",
+            Fault::Receiver => "Receiver",
+        }
+    }
+}
+
+#[derive(Debug)]
 pub enum SqsError {
     QueueNameExists,
     QueueDoesNotExist,
     InvalidParameterValue(String),
+    MissingParameter(String),
+    /// A message body or attribute value with characters SQS doesn't allow.
+    InvalidMessageContents(String),
     InvalidAction(String),
     MessageNotInflight,
+    ReceiptHandleIsInvalid(String),
+    OverLimit(String),
+    /// A purge within [`Config::purge_cooldown`](crate::Config) of the last.
+    PurgeQueueInProgress(String),
+    /// A send to a queue at its `MaxQueueLength`, failing with the code set
+    /// by [`Config::queue_full_error_code`](crate::Config).
+    QueueFull {
+        code: Arc<str>,
+        message: String,
+    },
+    InvalidAttributeName(String),
+    InvalidAttributeValue(String),
+    TooManyEntriesInBatchRequest(usize),
+    EmptyBatchRequest(&'static str),
+    BatchEntryIdsNotDistinct(String),
+    InvalidBatchEntryId,
+    BatchRequestTooLong { size: usize, limit: usize },
+    RequestEntityTooLarge(usize),
+    ServiceUnavailable(String),
+    /// Something went wrong on the server's side, such as the queue store
+    /// failing to write.
+    InternalError(String),
+    ResourceNotFound(String),
     // ... other errors
 }
 
-impl IntoResponse for SqsError {
-    fn into_response(self) -> Response {
-        let (status, error_code, message) = match self {
-            SqsError::QueueNameExists => (
-                StatusCode::BAD_REQUEST,
-                "QueueNameExists",
-                "A queue with this name already exists.".to_string(),
+/// How an error appears on the wire, declared once per variant in
+/// [`SqsError::classification`]. The JSON body and the query-protocol error
+/// header are both derived from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Classification<'a> {
+    pub status: StatusCode,
+    /// The error's shape name, e.g. `QueueDoesNotExist`.
+    pub code: &'a str,
+    /// The code used by the query protocol, where it differs from `code`.
+    pub query_code: Option<&'a str>,
+    pub fault: Fault,
+}
+
+impl<'a> Classification<'a> {
+    const fn sender(status: StatusCode, code: &'a str) -> Self {
+        Self {
+            status,
+            code,
+            query_code: None,
+            fault: Fault::Sender,
+        }
+    }
+
+    const fn receiver(status: StatusCode, code: &'a str) -> Self {
+        Self {
+            status,
+            code,
+            query_code: None,
+            fault: Fault::Receiver,
+        }
+    }
+
+    const fn query(self, query_code: &'a str) -> Self {
+        Self {
+            query_code: Some(query_code),
+            ..self
+        }
+    }
+}
+
+impl SqsError {
+    /// The error's status, code and fault, as AWS documents them for SQS.
+    pub fn classification(&self) -> Classification<'_> {
+        use Classification as C;
+        const BAD_REQUEST: StatusCode = StatusCode::BAD_REQUEST;
+        match self {
+            SqsError::QueueNameExists => {
+                C::sender(BAD_REQUEST, "QueueNameExists").query("QueueAlreadyExists")
+            }
+            SqsError::QueueDoesNotExist => C::sender(BAD_REQUEST, "QueueDoesNotExist")
+                .query("AWS.SimpleQueueService.NonExistentQueue"),
+            SqsError::InvalidParameterValue(_) => C::sender(BAD_REQUEST, "InvalidParameterValue"),
+            SqsError::MissingParameter(_) => C::sender(BAD_REQUEST, "MissingParameter"),
+            SqsError::InvalidMessageContents(_) => C::sender(BAD_REQUEST, "InvalidMessageContents"),
+            SqsError::InvalidAction(_) => C::sender(BAD_REQUEST, "InvalidAction"),
+            SqsError::MessageNotInflight => C::sender(BAD_REQUEST, "MessageNotInflight")
+                .query("AWS.SimpleQueueService.MessageNotInflight"),
+            SqsError::ReceiptHandleIsInvalid(_) => {
+                C::sender(StatusCode::NOT_FOUND, "ReceiptHandleIsInvalid")
+            }
+            SqsError::OverLimit(_) => C::sender(StatusCode::FORBIDDEN, "OverLimit"),
+            SqsError::PurgeQueueInProgress(_) => {
+                C::sender(StatusCode::FORBIDDEN, "PurgeQueueInProgress")
+                    .query("AWS.SimpleQueueService.PurgeQueueInProgress")
+            }
+            SqsError::QueueFull { code, .. } => C::sender(StatusCode::FORBIDDEN, code),
+            SqsError::InvalidAttributeName(_) => C::sender(BAD_REQUEST, "InvalidAttributeName"),
+            SqsError::InvalidAttributeValue(_) => C::sender(BAD_REQUEST, "InvalidAttributeValue"),
+            SqsError::TooManyEntriesInBatchRequest(_) => {
+                C::sender(BAD_REQUEST, "TooManyEntriesInBatchRequest")
+                    .query("AWS.SimpleQueueService.TooManyEntriesInBatchRequest")
+            }
+            SqsError::EmptyBatchRequest(_) => C::sender(BAD_REQUEST, "EmptyBatchRequest")
+                .query("AWS.SimpleQueueService.EmptyBatchRequest"),
+            SqsError::BatchEntryIdsNotDistinct(_) => {
+                C::sender(BAD_REQUEST, "BatchEntryIdsNotDistinct")
+                    .query("AWS.SimpleQueueService.BatchEntryIdsNotDistinct")
+            }
+            SqsError::InvalidBatchEntryId => C::sender(BAD_REQUEST, "InvalidBatchEntryId")
+                .query("AWS.SimpleQueueService.InvalidBatchEntryId"),
+            SqsError::BatchRequestTooLong { .. } => C::sender(BAD_REQUEST, "BatchRequestTooLong")
+                .query("AWS.SimpleQueueService.BatchRequestTooLong"),
+            SqsError::RequestEntityTooLarge(_) => {
+                C::sender(StatusCode::PAYLOAD_TOO_LARGE, "RequestEntityTooLarge")
+            }
+            SqsError::ServiceUnavailable(_) => {
+                C::receiver(StatusCode::SERVICE_UNAVAILABLE, "ServiceUnavailable")
+            }
+            SqsError::InternalError(_) => {
+                C::receiver(StatusCode::INTERNAL_SERVER_ERROR, "InternalError")
+            }
+            SqsError::ResourceNotFound(_) => {
+                C::sender(StatusCode::NOT_FOUND, "ResourceNotFoundException")
+            }
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.classification().status
+    }
+
+    /// Whether an error was caused by the request or by the service.
+    pub fn fault(&self) -> Fault {
+        self.classification().fault
+    }
+
+    /// The error's shape name, as used for the `Code` of failed batch entries.
+    pub fn code(&self) -> &str {
+        self.classification().code
+    }
+
+    /// The error code used by the query protocol, which query-compatible
+    /// SDKs read from the `x-amzn-query-error` header.
+    pub fn query_code(&self) -> &str {
+        let classification = self.classification();
+        classification.query_code.unwrap_or(classification.code)
+    }
+
+    /// The `__type` of the JSON error body: the error's shape name qualified
+    /// by the SQS service namespace, as the real service sends it.
+    pub fn error_type(&self) -> String {
+        format!("com.amazonaws.sqs#{}", self.code())
+    }
+
+    /// The value of the `x-amzn-query-error` header: the query code and the
+    /// fault, separated by a semicolon.
+    pub fn query_error(&self) -> String {
+        format!("{};{}", self.query_code(), self.fault().as_str())
+    }
+
+    /// The JSON protocol's error body.
+    pub fn json_body(&self) -> serde_json::Value {
+        json!({
+            "__type": self.error_type(),
+            "message": self.message(),
+        })
+    }
+
+    /// The human-readable message, without the code.
+    pub fn message(&self) -> String {
+        match self {
+            SqsError::QueueNameExists => "A queue with this name already exists.".to_string(),
+            SqsError::QueueDoesNotExist => "The specified queue does not exist.".to_string(),
+            SqsError::InvalidParameterValue(msg)
+            | SqsError::InvalidMessageContents(msg)
+            | SqsError::OverLimit(msg)
+            | SqsError::PurgeQueueInProgress(msg)
+            | SqsError::InvalidAttributeValue(msg)
+            | SqsError::ServiceUnavailable(msg)
+            | SqsError::InternalError(msg)
+            | SqsError::ResourceNotFound(msg)
+            | SqsError::QueueFull { message: msg, .. } => msg.clone(),
+            SqsError::MissingParameter(name) => {
+                format!("The request must contain the parameter {}.", name)
+            }
+            SqsError::InvalidAction(action) => format!("Invalid action: {}", action),
+            SqsError::MessageNotInflight => "The specified message is not in flight.".to_string(),
+            SqsError::ReceiptHandleIsInvalid(handle) => format!(
+                "The input receipt handle \"{}\" is not a valid receipt handle.",
+                handle
             ),
-            SqsError::QueueDoesNotExist => (
-                StatusCode::BAD_REQUEST,
-                "QueueDoesNotExist",
-                "The specified queue does not exist.".to_string(),
+            SqsError::InvalidAttributeName(name) => format!("Unknown Attribute {}.", name),
+            SqsError::TooManyEntriesInBatchRequest(count) => format!(
+                "Maximum number of entries per request are 10. You have sent {}.",
+                count
             ),
-            SqsError::InvalidParameterValue(msg) => {
-                (StatusCode::BAD_REQUEST, "InvalidParameterValue", msg)
+            SqsError::EmptyBatchRequest(entry_type) => {
+                format!("There should be at least one {} in the request.", entry_type)
             }
-            SqsError::InvalidAction(action) => (
-                StatusCode::BAD_REQUEST,
-                "InvalidAction",
-                format!("Invalid action: {}", action),
+            SqsError::BatchEntryIdsNotDistinct(id) => format!("Id {} repeated.", id),
+            SqsError::InvalidBatchEntryId => "A batch entry id can only contain alphanumeric characters, hyphens and underscores. It can be at most 80 letters long.".to_string(),
+            SqsError::BatchRequestTooLong { size, limit } => format!(
+                "Batch requests cannot be longer than {} bytes. You have sent {} bytes.",
+                limit, size
             ),
-            SqsError::MessageNotInflight => (
-                StatusCode::BAD_REQUEST,
-                "MessageNotInflight",
-                "The specified message is not in flight.".to_string(),
-            ),
-        };
+            SqsError::RequestEntityTooLarge(limit) => {
+                format!("Request bodies cannot be longer than {} bytes.", limit)
+            }
+        }
+    }
+}
+
+impl fmt::Display for SqsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message())
+    }
+}
 
-        let body = Json(json!({
-            "__type": error_code,
-            "message": message,
-        }));
+impl std::error::Error for SqsError {}
 
-        (status, body).into_response()
+impl IntoResponse for SqsError {
+    fn into_response(self) -> Response {
+        let content_type = [(header::CONTENT_TYPE, AMZ_JSON)];
+        let headers = [("x-amzn-query-error", self.query_error())];
+        let body = self.json_body().to_string();
+        (self.status(), content_type, headers, body).into_response()
     }
 }
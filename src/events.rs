@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::future::Future;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Runs `future` with `request_id` as the id events recorded while it runs
+/// are attributed to.
+pub async fn with_request_id<F: Future>(request_id: String, future: F) -> F::Output {
+    REQUEST_ID.scope(request_id, future).await
+}
+
+/// The id of the SQS request being handled, if any; background work such as
+/// the sweeper has none.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    QueueCreated,
+    QueueDeleted,
+    AttributesChanged,
+    TagsChanged,
+    Purged,
+    /// Messages moved from a queue to its dead-letter queue, recorded on
+    /// both.
+    DeadLettered,
+}
+
+/// Something that happened to a queue.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub kind: EventKind,
+    /// Epoch milliseconds, on the server's clock.
+    pub timestamp: i64,
+    pub queue: String,
+    /// The request that caused the event, if one did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// What changed, depending on `kind`.
+    pub details: Value,
+}
+
+/// The most recent events, oldest first. Once `capacity` events are held,
+/// each new one evicts the oldest.
+#[derive(Debug, Clone, Default)]
+pub struct EventLog {
+    events: VecDeque<Event>,
+    capacity: usize,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, event: Event) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.events.len() >= self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    /// Events at or after `since` (epoch milliseconds), oldest first.
+    pub fn since(&self, since: i64) -> Vec<Event> {
+        self.events
+            .iter()
+            .filter(|event| event.timestamp >= since)
+            .cloned()
+            .collect()
+    }
+}
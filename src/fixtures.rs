@@ -0,0 +1,170 @@
+use crate::error::SqsError;
+use crate::queue::{self, CreateQueueRequest, SendMessageRequest};
+use crate::state::{AppState, MessageAttributeValue};
+use crate::templates::{self, ExpandedQueue};
+use crate::store::Selection;
+use crate::webhooks;
+use axum::extract::State;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A fixture document: queues to create and the messages to seed them with.
+///
+/// ```yaml
+/// queues:
+///   - name: jobs
+///     attributes:
+///       VisibilityTimeout: "5"
+///     webhook: http://localhost:8080/jobs
+///     messages:
+///       - body: '{"id": 1}'
+///         message_attributes:
+///           kind: { DataType: String, StringValue: created }
+///       - body: later
+///         delay_seconds: 30
+///       - body: retried
+///         receive_count: 2
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fixtures {
+    #[serde(default)]
+    pub queues: Vec<QueueFixture>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QueueFixture {
+    pub name: String,
+    #[serde(default)]
+    pub attributes: HashMap<String, String>,
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+    /// The queue template this queue starts from; `attributes` and `tags`
+    /// override the template's.
+    #[serde(default)]
+    pub template: Option<String>,
+    /// URL notified of every message enqueued on the queue.
+    #[serde(default)]
+    pub webhook: Option<String>,
+    #[serde(default)]
+    pub messages: Vec<MessageFixture>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MessageFixture {
+    pub body: String,
+    #[serde(default)]
+    pub message_attributes: HashMap<String, MessageAttributeValue>,
+    #[serde(default)]
+    pub delay_seconds: Option<u32>,
+    /// Required for messages on FIFO queues, as with `SendMessage`.
+    #[serde(default)]
+    pub message_group_id: Option<String>,
+    /// Required for messages on FIFO queues without
+    /// `ContentBasedDeduplication`, as with `SendMessage`.
+    #[serde(default)]
+    pub message_deduplication_id: Option<String>,
+    /// Marks the message as already received this many times, e.g. to seed
+    /// messages that are one receive away from being dead-lettered.
+    #[serde(default)]
+    pub receive_count: u32,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct FixtureSummary {
+    pub queues: usize,
+    pub messages: usize,
+}
+
+impl Fixtures {
+    pub fn from_yaml(s: &str) -> Result<Self, serde_yaml::Error> {
+        serde_yaml::from_str(s)
+    }
+}
+
+/// Creates the fixture queues and enqueues their messages through the same
+/// `CreateQueue`/`SendMessage` handlers the API uses, so fixtures are subject
+/// to the same validation. Queues with a redrive policy are created last, so
+/// that their dead-letter queues exist by then.
+pub async fn load(state: &AppState, fixtures: Fixtures) -> Result<FixtureSummary, SqsError> {
+    let mut summary = FixtureSummary::default();
+
+    let templates = state.templates.lock().unwrap().clone();
+    let mut queues = templates::expand(state, &templates, fixtures.queues)?;
+    queues.sort_by_key(|expanded| expanded.queue.attributes.contains_key("RedrivePolicy"));
+    for ExpandedQueue { queue, template } in queues {
+        let name = queue.name.clone();
+        load_queue(state, queue, &mut summary).await.map_err(|e| match &template {
+            Some(template) => templates::in_template(e, &name, template),
+            None => e,
+        })?;
+    }
+
+    Ok(summary)
+}
+
+async fn load_queue(
+    state: &AppState,
+    queue_fixture: QueueFixture,
+    summary: &mut FixtureSummary,
+) -> Result<(), SqsError> {
+    if let Some(url) = &queue_fixture.webhook {
+        webhooks::validate_url(url)?;
+    }
+    let created = queue::create_queue(
+        State(state.clone()),
+        Json(CreateQueueRequest {
+            queue_name: queue_fixture.name,
+            attributes: queue_fixture.attributes,
+            tags: queue_fixture.tags,
+            template: None,
+        }),
+    )
+    .await?;
+    summary.queues += 1;
+
+    if let Some(url) = queue_fixture.webhook {
+        state
+            .store
+            .update_selected(&created.queue_url, |_| Selection::none(), |queue| {
+                queue.webhook = Some(url)
+            })
+            .await?;
+    }
+
+    for message in queue_fixture.messages {
+        let sent = queue::send_message(
+            State(state.clone()),
+            Json(SendMessageRequest {
+                queue_url: created.queue_url.clone(),
+                message_body: message.body,
+                message_attributes: message.message_attributes,
+                delay_seconds: message.delay_seconds,
+                message_system_attributes: HashMap::new(),
+                message_deduplication_id: message.message_deduplication_id,
+                message_group_id: message.message_group_id,
+                trace_header: None,
+            }),
+        )
+        .await?;
+        summary.messages += 1;
+
+        if message.receive_count > 0 {
+            state.store.update(&created.queue_url, |queue| {
+                if let Some(stored) = queue.messages.get_mut_by_id(&sent.message_id) {
+                    stored.receive_count = message.receive_count;
+                    stored.attributes.insert(
+                        "ApproximateReceiveCount".to_string(),
+                        message.receive_count.to_string(),
+                    );
+                }
+            })
+            .await?;
+        }
+    }
+
+    Ok(())
+}
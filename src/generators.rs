@@ -0,0 +1,234 @@
+//! Message generators: background tasks that send a message to a queue at a
+//! fixed interval, for demoing consumers and soak tests. They are declared
+//! in the config file's `generators` or started through the admin API, and
+//! send through [`queue::send_message`] like any client, so validation,
+//! limits, metrics and webhooks all apply.
+//!
+//! `GET /_admin/generators` lists them, `POST /_admin/generators` starts one
+//! from a [`GeneratorSpec`] in JSON, and `POST /_admin/generators/{id}/stop`
+//! stops one. A generator also stops after `count` messages, when a send
+//! fails, and when the server shuts down.
+
+use crate::error::SqsError;
+use crate::ids::Ids;
+use crate::queue::{self, SendMessageRequest};
+use crate::serde_helpers::parse_duration;
+use crate::state::AppState;
+use axum::extract::State;
+use axum::Json;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+/// ```yaml
+/// generators:
+///   - queue: jobs
+///     interval: 500ms
+///     body_template: '{"n": {{seq}}, "id": "{{uuid}}", "at": "{{now}}"}'
+///     count: 1000
+/// ```
+///
+/// In `body_template`, `{{seq}}` is the message's number, from 1,
+/// `{{uuid}}` a fresh UUID and `{{now}}` the time of the send in RFC 3339.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GeneratorSpec {
+    /// The name of the queue to send to.
+    pub queue: String,
+    /// The time between sends, e.g. `500ms` or `2s`. The first message is
+    /// sent at once.
+    pub interval: String,
+    pub body_template: String,
+    /// How many messages to send before stopping; unlimited if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<u64>,
+    /// The message group of every message, for FIFO queues. Each message is
+    /// deduplicated by its generator and number.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_group_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GeneratorStatus {
+    Running,
+    /// Sent `count` messages.
+    Completed,
+    /// Stopped through the admin API or by the server shutting down.
+    Stopped,
+    /// Stopped by a send failing.
+    Failed,
+}
+
+#[derive(Debug)]
+struct Generator {
+    id: String,
+    spec: GeneratorSpec,
+    interval: Duration,
+    started: DateTime<Utc>,
+    sent: AtomicU64,
+    state: Mutex<(GeneratorStatus, Option<String>)>,
+    stop: CancellationToken,
+}
+
+impl Generator {
+    fn info(&self) -> GeneratorInfo {
+        let (status, failure_reason) = self.state.lock().unwrap().clone();
+        GeneratorInfo {
+            id: self.id.clone(),
+            spec: self.spec.clone(),
+            status,
+            failure_reason,
+            sent: self.sent.load(Ordering::Relaxed),
+            started: self.started,
+        }
+    }
+}
+
+/// A generator as listed by the admin API.
+#[derive(Debug, Clone, Serialize)]
+pub struct GeneratorInfo {
+    pub id: String,
+    #[serde(flatten)]
+    pub spec: GeneratorSpec,
+    pub status: GeneratorStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_reason: Option<String>,
+    /// Messages sent so far.
+    pub sent: u64,
+    pub started: DateTime<Utc>,
+}
+
+/// Every generator started since the server did, in the order they were.
+#[derive(Debug, Default)]
+pub struct Generators {
+    started: Mutex<Vec<Arc<Generator>>>,
+}
+
+impl Generators {
+    pub fn list(&self) -> Vec<GeneratorInfo> {
+        let started = self.started.lock().unwrap();
+        started.iter().map(|generator| generator.info()).collect()
+    }
+
+    /// Stops generator `id`, if it is still running, once any send under way
+    /// has finished.
+    pub fn stop(&self, id: &str) -> Result<GeneratorInfo, SqsError> {
+        let started = self.started.lock().unwrap();
+        let generator = started.iter().find(|generator| generator.id == id).ok_or_else(|| {
+            SqsError::ResourceNotFound(format!("Generator {} does not exist.", id))
+        })?;
+        generator.stop.cancel();
+        let mut state = generator.state.lock().unwrap();
+        if state.0 == GeneratorStatus::Running {
+            *state = (GeneratorStatus::Stopped, None);
+        }
+        drop(state);
+        Ok(generator.info())
+    }
+}
+
+/// Checks `spec` and starts a generator running it.
+pub fn start(state: &AppState, spec: GeneratorSpec) -> Result<GeneratorInfo, SqsError> {
+    let interval = parse_duration(&spec.interval)
+        .and_then(|interval| interval.to_std().ok())
+        .filter(|interval| !interval.is_zero())
+        .ok_or_else(|| {
+            SqsError::InvalidParameterValue(format!(
+                "Invalid generator interval {:?}; expected a duration like 500ms, 2s or 1m.",
+                spec.interval
+            ))
+        })?;
+    if spec.count == Some(0) {
+        return Err(SqsError::InvalidParameterValue(
+            "A generator's count must be at least 1.".to_string(),
+        ));
+    }
+    if !state.store.contains(&state.queue_url(&spec.queue)) {
+        return Err(SqsError::InvalidParameterValue(format!(
+            "Generator queue {} does not exist.",
+            spec.queue
+        )));
+    }
+
+    let generator = Arc::new(Generator {
+        id: state.ids.uuid().to_string(),
+        spec,
+        interval,
+        started: state.clock.now(),
+        sent: AtomicU64::new(0),
+        state: Mutex::new((GeneratorStatus::Running, None)),
+        stop: CancellationToken::new(),
+    });
+    state.generators.started.lock().unwrap().push(generator.clone());
+    tokio::spawn(run(state.clone(), generator.clone()));
+    Ok(generator.info())
+}
+
+async fn run(state: AppState, generator: Arc<Generator>) {
+    let spec = &generator.spec;
+    let queue_url = state.queue_url(&spec.queue);
+    let mut ticks = tokio::time::interval(generator.interval);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let (status, failure_reason) = loop {
+        let sent = generator.sent.load(Ordering::Relaxed);
+        if spec.count.is_some_and(|count| sent >= count) {
+            break (GeneratorStatus::Completed, None);
+        }
+        tokio::select! {
+            _ = state.shutdown.cancelled() => break (GeneratorStatus::Stopped, None),
+            _ = generator.stop.cancelled() => break (GeneratorStatus::Stopped, None),
+            _ = ticks.tick() => {}
+        }
+
+        let seq = sent + 1;
+        let request = SendMessageRequest {
+            queue_url: queue_url.clone(),
+            message_body: render(&spec.body_template, seq, state.clock.now(), &state.ids),
+            message_attributes: HashMap::new(),
+            delay_seconds: None,
+            message_system_attributes: HashMap::new(),
+            message_deduplication_id: spec
+                .message_group_id
+                .as_ref()
+                .map(|_| format!("{}-{}", generator.id, seq)),
+            message_group_id: spec.message_group_id.clone(),
+            trace_header: None,
+        };
+        match queue::send_message(State(state.clone()), Json(request)).await {
+            Ok(_) => {
+                generator.sent.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) => break (GeneratorStatus::Failed, Some(e.to_string())),
+        }
+    };
+
+    debug!(
+        generator = %generator.id,
+        queue = %spec.queue,
+        ?status,
+        sent = generator.sent.load(Ordering::Relaxed),
+        "generator finished"
+    );
+    // A generator stopped through the admin API already says so.
+    let mut current = generator.state.lock().unwrap();
+    if current.0 == GeneratorStatus::Running {
+        *current = (status, failure_reason);
+    }
+}
+
+/// `template` with its placeholders filled in for message number `seq`,
+/// sent at `now`, taking UUIDs from `ids`.
+fn render(template: &str, seq: u64, now: DateTime<Utc>, ids: &Ids) -> String {
+    template
+        .replace("{{seq}}", &seq.to_string())
+        .replace("{{uuid}}", &ids.uuid().to_string())
+        .replace("{{now}}", &now.to_rfc3339_opts(SecondsFormat::Millis, true))
+}
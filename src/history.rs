@@ -0,0 +1,128 @@
+//! Per-message histories, for working out what became of a message: each
+//! receive, with the request that claimed it, each visibility change, and
+//! how it left the queue. They are kept only with
+//! [`Config::message_history`](crate::Config) set. The SQLite and Redis
+//! stores keep them with their messages; snapshots leave them out, and the
+//! histories of deleted messages are only kept in memory.
+
+use crate::events;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryKind {
+    Received,
+    VisibilityChanged,
+    /// The visibility timeout lapsed and the message became visible again.
+    Released,
+    Deleted,
+    /// Moved to a dead-letter queue, where its history carries on.
+    DeadLettered,
+}
+
+/// Something that happened to a message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub kind: HistoryKind,
+    /// Epoch milliseconds, on the server's clock.
+    pub timestamp: i64,
+    /// The request that caused the entry, if one did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// The receipt handle issued by a receive, or used by a visibility
+    /// change or delete, or that lapsed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub receipt_handle: Option<String>,
+    /// For receives and visibility changes, when the message becomes
+    /// visible again unless it is deleted first (epoch milliseconds).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visible_until: Option<i64>,
+    /// For dead-letter moves, the ARN of the dead-letter queue.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dead_letter_target_arn: Option<String>,
+}
+
+impl HistoryEntry {
+    /// An entry of `kind` at `at`, attributed to the current request if
+    /// there is one.
+    pub fn new(kind: HistoryKind, at: DateTime<Utc>) -> Self {
+        Self {
+            kind,
+            timestamp: at.timestamp_millis(),
+            request_id: events::current_request_id(),
+            receipt_handle: None,
+            visible_until: None,
+            dead_letter_target_arn: None,
+        }
+    }
+}
+
+/// A message's most recent history entries, oldest first. Once `capacity`
+/// entries are held, each new one evicts the oldest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageHistory {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+}
+
+impl MessageHistory {
+    /// A history of up to `capacity` entries, or none if `capacity` is 0.
+    pub fn new(capacity: usize) -> Option<Box<Self>> {
+        (capacity > 0).then(|| {
+            Box::new(Self {
+                entries: VecDeque::new(),
+                capacity,
+            })
+        })
+    }
+
+    pub fn push(&mut self, entry: HistoryEntry) {
+        while self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        self.entries.iter().cloned().collect()
+    }
+}
+
+/// The histories of the messages most recently deleted from a queue, so
+/// that they can be looked up once the messages are gone. Once `capacity`
+/// are held, each new one evicts the oldest.
+#[derive(Debug, Clone, Default)]
+pub struct DeletedHistories {
+    histories: VecDeque<(String, Box<MessageHistory>)>,
+    capacity: usize,
+}
+
+impl DeletedHistories {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            histories: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, message_id: String, history: Box<MessageHistory>) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.histories.len() >= self.capacity {
+            self.histories.pop_front();
+        }
+        self.histories.push_back((message_id, history));
+    }
+
+    /// The history of the deleted message `message_id`, if still kept.
+    pub fn get(&self, message_id: &str) -> Option<&MessageHistory> {
+        self.histories
+            .iter()
+            .rev()
+            .find(|(id, _)| id == message_id)
+            .map(|(_, history)| history.as_ref())
+    }
+}
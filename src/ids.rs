@@ -0,0 +1,37 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::{Arc, Mutex};
+use uuid::{Builder, Uuid};
+
+/// Where message IDs, receipt handle nonces, request IDs and the IDs of
+/// move tasks and generators come from. Random by default; given a seed (see
+/// `Config::deterministic_ids`), each is drawn in turn from an RNG seeded
+/// with it, so the same requests in the same order get the same IDs on
+/// every run.
+#[derive(Debug, Clone, Default)]
+pub struct Ids {
+    seeded: Option<Arc<Mutex<StdRng>>>,
+}
+
+impl Ids {
+    pub fn new(seed: Option<u64>) -> Self {
+        Self {
+            seeded: seed.map(|seed| Arc::new(Mutex::new(StdRng::seed_from_u64(seed)))),
+        }
+    }
+
+    /// A version 4 UUID, random or next in the seeded sequence.
+    pub fn uuid(&self) -> Uuid {
+        match &self.seeded {
+            Some(rng) => Builder::from_random_bytes(rng.lock().unwrap().random()).into_uuid(),
+            None => Uuid::new_v4(),
+        }
+    }
+
+    pub fn nonce(&self) -> u32 {
+        match &self.seeded {
+            Some(rng) => rng.lock().unwrap().random(),
+            None => rand::random(),
+        }
+    }
+}
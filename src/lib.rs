@@ -0,0 +1,43 @@
+mod admin;
+pub mod attributes;
+pub mod batch;
+pub mod chaos;
+pub mod checkpoints;
+pub mod client;
+pub mod clock;
+pub mod compression;
+pub mod config;
+pub mod deduplication;
+pub mod dispatch;
+pub mod error;
+pub mod events;
+pub mod fixtures;
+pub mod generators;
+pub mod history;
+pub mod ids;
+pub mod limits;
+pub mod maintenance;
+pub mod message_attributes;
+pub mod messages;
+pub mod metrics;
+pub mod move_tasks;
+pub mod namespaces;
+pub mod queue;
+pub mod receipt;
+pub mod reload;
+pub mod search;
+mod serde_helpers;
+mod server;
+pub mod snapshot;
+pub mod socket_activation;
+pub mod state;
+pub mod store;
+pub mod telemetry;
+pub mod templates;
+pub mod urls;
+pub mod validation;
+pub mod webhooks;
+
+pub use config::{Config, ConfigFile};
+pub use server::{request_body_limit, serve, sqs_router, ShutdownHandle};
+pub use state::AppState;
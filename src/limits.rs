@@ -0,0 +1,191 @@
+use crate::error::SqsError;
+use axum::serve::Listener;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::warn;
+
+/// Counts how many of something are in use, and hands out [`Permit`]s as
+/// long as that stays within a maximum.
+#[derive(Debug, Default)]
+pub struct Limiter {
+    in_use: AtomicUsize,
+}
+
+impl Limiter {
+    /// Takes a permit, unless `max` of them are already out. `None` never
+    /// refuses.
+    pub fn try_acquire(self: &Arc<Self>, max: Option<usize>) -> Option<Permit> {
+        let previous = self.in_use.fetch_add(1, Ordering::AcqRel);
+        if max.is_some_and(|max| previous >= max) {
+            self.in_use.fetch_sub(1, Ordering::AcqRel);
+            return None;
+        }
+        Some(Permit(self.clone()))
+    }
+
+    pub fn in_use(&self) -> usize {
+        self.in_use.load(Ordering::Acquire)
+    }
+}
+
+/// A slot taken from a [`Limiter`], given back when dropped.
+#[derive(Debug)]
+pub struct Permit(Arc<Limiter>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.0.in_use.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// How many requests each limit has turned away since the server started.
+#[derive(Debug, Default)]
+pub struct Rejections {
+    pub requests: AtomicU64,
+    pub long_polls: AtomicU64,
+    pub connections: AtomicU64,
+}
+
+impl Rejections {
+    /// `(limit, count)` pairs, as labelled in the metrics.
+    pub fn counts(&self) -> [(&'static str, u64); 3] {
+        [
+            ("requests", self.requests.load(Ordering::Relaxed)),
+            ("long_polls", self.long_polls.load(Ordering::Relaxed)),
+            ("connections", self.connections.load(Ordering::Relaxed)),
+        ]
+    }
+}
+
+/// A [`TcpListener`] that keeps at most `max` connections open. Connections
+/// beyond that are answered with a `503 ServiceUnavailable` error and closed
+/// straight away, so clients fail fast instead of queueing in the backlog.
+pub struct LimitedListener {
+    listener: TcpListener,
+    max: Option<usize>,
+    connections: Arc<Limiter>,
+    rejections: Arc<Rejections>,
+}
+
+impl LimitedListener {
+    pub fn new(
+        listener: TcpListener,
+        max: Option<usize>,
+        connections: Arc<Limiter>,
+        rejections: Arc<Rejections>,
+    ) -> Self {
+        Self {
+            listener,
+            max,
+            connections,
+            rejections,
+        }
+    }
+}
+
+impl Listener for LimitedListener {
+    type Io = LimitedStream;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        loop {
+            let (stream, addr) = Listener::accept(&mut self.listener).await;
+            match self.connections.try_acquire(self.max) {
+                Some(permit) => {
+                    let stream = LimitedStream {
+                        stream,
+                        _permit: permit,
+                    };
+                    return (stream, addr);
+                }
+                None => {
+                    self.rejections.connections.fetch_add(1, Ordering::Relaxed);
+                    warn!(%addr, "connection limit reached; refusing connection");
+                    tokio::spawn(refuse(stream));
+                }
+            }
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        self.listener.local_addr()
+    }
+}
+
+/// Writes a `503` SQS error to a connection that won't be served.
+async fn refuse(mut stream: TcpStream) {
+    let error = SqsError::ServiceUnavailable("Too many open connections.".to_string());
+    let body = error.json_body().to_string();
+    let response = format!(
+        "HTTP/1.1 503 Service Unavailable\r\ncontent-type: application/x-amz-json-1.0\r\n\
+         x-amzn-query-error: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        error.query_error(),
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+    // Closing with the request still unread would reset the connection and
+    // could discard the response before the client reads it.
+    let mut discard = [0; 4096];
+    let drain = async {
+        while let Ok(n) = stream.read(&mut discard).await
+            && n > 0
+        {}
+    };
+    let _ = tokio::time::timeout(Duration::from_secs(1), drain).await;
+}
+
+/// A connection accepted by [`LimitedListener`], holding its slot until it
+/// closes.
+pub struct LimitedStream {
+    stream: TcpStream,
+    _permit: Permit,
+}
+
+impl AsyncRead for LimitedStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for LimitedStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+}
@@ -1,125 +1,598 @@
-use axum::http::HeaderMap;
-use axum::response::IntoResponse;
-use axum::routing::post;
-use axum::{extract::State, Json, Router};
-use tracing::info;
+use clap::{Parser, Subcommand};
+use local_sqs::client::{Client, ClientError};
+use local_sqs::queue::ListQueuesResponse;
+use local_sqs::state::MessageAttributeValue;
+use local_sqs::store::Storage;
+use local_sqs::validation::ValidationMode;
+use local_sqs::{Config, ConfigFile};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
 
-mod error;
-mod queue;
-mod state;
-mod serde_helpers;
+/// Runs the server, unless a subcommand is given to talk to a running one.
+#[derive(Debug, Parser)]
+#[command(version, about = "A local Amazon SQS emulator", args_conflicts_with_subcommands = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// YAML config file of server settings and queues, reloaded on change [env: LOCAL_SQS_CONFIG]
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Host to use in queue URLs and, without --bind, to listen on; localhost listens on
+    /// both 127.0.0.1 and ::1 [env: LOCAL_SQS_HOST]
+    #[arg(long)]
+    host: Option<String>,
+    /// Port to listen on; 0 picks a free port [env: LOCAL_SQS_PORT]
+    #[arg(long)]
+    port: Option<u16>,
+    /// Address to listen on instead of host and port, e.g. 127.0.0.1:9324 or "[::1]:9324";
+    /// repeatable, all with the same port [env: LOCAL_SQS_BIND, comma-separated]
+    #[arg(long, value_name = "ADDR")]
+    bind: Vec<String>,
+    /// Region used in queue ARNs [env: LOCAL_SQS_REGION]
+    #[arg(long)]
+    region: Option<String>,
+    /// Account ID used in queue ARNs and URLs [env: LOCAL_SQS_ACCOUNT_ID]
+    #[arg(long)]
+    account_id: Option<String>,
+    /// YAML file of queues and messages to seed at startup [env: LOCAL_SQS_FIXTURES]
+    #[arg(long)]
+    fixtures: Option<PathBuf>,
+    /// Maximum number of messages a single queue may hold [env: LOCAL_SQS_MAX_MESSAGES_PER_QUEUE]
+    #[arg(long)]
+    max_messages_per_queue: Option<usize>,
+    /// Maximum total message body bytes across all queues [env: LOCAL_SQS_MAX_TOTAL_BYTES]
+    #[arg(long)]
+    max_total_bytes: Option<u64>,
+    /// Error code of sends to a queue at its MaxQueueLength (default OverLimit)
+    /// [env: LOCAL_SQS_QUEUE_FULL_ERROR_CODE]
+    #[arg(long)]
+    queue_full_error_code: Option<String>,
+    /// Largest MaximumMessageSize a queue may set, up to 1048576 (default 262144)
+    /// [env: LOCAL_SQS_MAX_MESSAGE_SIZE_LIMIT]
+    #[arg(long)]
+    max_message_size_limit: Option<usize>,
+    /// strict fails invalid requests as SQS would; lenient logs them and carries on
+    /// [env: LOCAL_SQS_VALIDATION]
+    #[arg(long, value_name = "strict|lenient")]
+    validation: Option<ValidationMode>,
+    /// Freeze time until advanced via POST /_admin/clock/advance [env: LOCAL_SQS_MANUAL_CLOCK]
+    #[arg(long)]
+    manual_clock: bool,
+    /// Attribute for new queues that don't set it, e.g. VisibilityTimeout=5; repeatable
+    /// [env: LOCAL_SQS_DEFAULT_QUEUE_ATTRIBUTES, comma-separated]
+    #[arg(
+        long = "default-queue-attribute",
+        value_name = "NAME=VALUE",
+        value_parser = parse_attribute
+    )]
+    default_queue_attributes: Vec<(String, String)>,
+    /// Chance (0 to 1) that a receive also returns a duplicate of an in-flight message
+    /// [env: LOCAL_SQS_DUPLICATE_DELIVERY_PROBABILITY]
+    #[arg(long)]
+    duplicate_delivery_probability: Option<f64>,
+    /// Hand out messages from standard queues in random order [env: LOCAL_SQS_SHUFFLE_DELIVERY]
+    #[arg(long)]
+    shuffle_delivery: bool,
+    /// Seed for chaos decisions, to reproduce an earlier run [env: LOCAL_SQS_CHAOS_SEED]
+    #[arg(long)]
+    chaos_seed: Option<u64>,
+    /// Path to serve the SQS endpoint under, e.g. /sqs [env: LOCAL_SQS_BASE_PATH]
+    #[arg(long)]
+    base_path: Option<String>,
+    /// Latency histogram bucket bounds in seconds, e.g. 0.1,1,10
+    /// [env: LOCAL_SQS_HISTOGRAM_BUCKETS]
+    #[arg(long, value_delimiter = ',')]
+    histogram_buckets: Option<Vec<f64>>,
+    /// Sign receipt handles with this key so clients can't forge them
+    /// [env: LOCAL_SQS_RECEIPT_HANDLE_SECRET]
+    #[arg(long)]
+    receipt_handle_secret: Option<String>,
+    /// Generate IDs from this seed, 0 if none is given, instead of at random, so the same
+    /// requests get the same IDs on every run [env: LOCAL_SQS_DETERMINISTIC_IDS]
+    #[arg(long, value_name = "SEED", num_args = 0..=1, default_missing_value = "0")]
+    deterministic_ids: Option<u64>,
+    /// Delete empty queues unused for this many seconds [env: LOCAL_SQS_IDLE_QUEUE_TTL_SECS]
+    #[arg(long)]
+    idle_queue_ttl_secs: Option<u64>,
+    /// Refuse a queue's further purges for this many seconds after one
+    /// [env: LOCAL_SQS_PURGE_COOLDOWN_SECS]
+    #[arg(long)]
+    purge_cooldown_secs: Option<u64>,
+    /// Queue never deleted for being idle; repeatable
+    /// [env: LOCAL_SQS_IDLE_QUEUE_EXEMPT, comma-separated]
+    #[arg(long, value_name = "NAME")]
+    idle_queue_exempt: Vec<String>,
+    /// Lifecycle events kept per queue and server-wide (default 100)
+    /// [env: LOCAL_SQS_EVENT_LOG_CAPACITY]
+    #[arg(long)]
+    event_log_capacity: Option<usize>,
+    /// History entries kept per message, for the admin API (default 0, off)
+    /// [env: LOCAL_SQS_MESSAGE_HISTORY]
+    #[arg(long)]
+    message_history: Option<usize>,
+    /// SQS requests handled at once before answering ServiceUnavailable
+    /// [env: LOCAL_SQS_MAX_CONCURRENT_REQUESTS]
+    #[arg(long)]
+    max_concurrent_requests: Option<usize>,
+    /// Long polls waiting on one queue at once before answering
+    /// ServiceUnavailable [env: LOCAL_SQS_MAX_LONG_POLLS_PER_QUEUE]
+    #[arg(long)]
+    max_long_polls_per_queue: Option<usize>,
+    /// Open connections before refusing new ones [env: LOCAL_SQS_MAX_CONNECTIONS]
+    #[arg(long)]
+    max_connections: Option<usize>,
+    /// Store message bodies longer than this many bytes LZ4-compressed
+    /// [env: LOCAL_SQS_COMPRESS_BODIES_ABOVE]
+    #[arg(long)]
+    compress_bodies_above: Option<usize>,
+    /// Where queues are kept: memory, or sqlite or redis to keep them across
+    /// restarts [env: LOCAL_SQS_STORAGE]
+    #[arg(long, value_name = "memory|sqlite|redis")]
+    storage: Option<Storage>,
+    /// SQLite database used with --storage sqlite (default local-sqs.db)
+    /// [env: LOCAL_SQS_DB_PATH]
+    #[arg(long)]
+    db_path: Option<PathBuf>,
+    /// Redis server used with --storage redis (default redis://127.0.0.1/)
+    /// [env: LOCAL_SQS_REDIS_URL]
+    #[arg(long)]
+    redis_url: Option<String>,
+    /// Write admin API checkpoints to this directory instead of keeping
+    /// them in memory [env: LOCAL_SQS_CHECKPOINT_DIR]
+    #[arg(long)]
+    checkpoint_dir: Option<PathBuf>,
+    /// Export request spans over OTLP/HTTP, e.g. http://localhost:4318
+    /// [env: LOCAL_SQS_OTLP_ENDPOINT]
+    #[cfg(feature = "otel")]
+    #[arg(long)]
+    otlp_endpoint: Option<String>,
+}
 
-use state::AppState;
+/// Client commands against a running server. Each prints the server's JSON
+/// response on stdout.
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Send a message
+    Send {
+        /// Queue name or URL
+        #[arg(long)]
+        queue: String,
+        /// Message body
+        #[arg(long)]
+        body: String,
+        /// Message attribute, e.g. color=String:red; repeatable
+        #[arg(
+            long = "attr",
+            value_name = "NAME=TYPE:VALUE",
+            value_parser = parse_message_attribute
+        )]
+        attributes: Vec<(String, MessageAttributeValue)>,
+        /// Seconds before the message becomes visible
+        #[arg(long)]
+        delay: Option<u32>,
+        #[command(flatten)]
+        client: ClientArgs,
+    },
+    /// Receive messages, leaving them in flight
+    Receive {
+        /// Queue name or URL
+        #[arg(long)]
+        queue: String,
+        #[arg(long, default_value_t = 1)]
+        max: u32,
+        /// Seconds to long-poll for
+        #[arg(long)]
+        wait: Option<u32>,
+        #[arg(long)]
+        visibility_timeout: Option<u32>,
+        #[command(flatten)]
+        client: ClientArgs,
+    },
+    /// List queue URLs
+    ListQueues {
+        #[arg(long)]
+        prefix: Option<String>,
+        #[command(flatten)]
+        client: ClientArgs,
+    },
+    /// Create a queue
+    CreateQueue {
+        #[arg(long)]
+        queue: String,
+        /// Queue attribute, e.g. VisibilityTimeout=5; repeatable
+        #[arg(long = "attribute", value_name = "NAME=VALUE", value_parser = parse_attribute)]
+        attributes: Vec<(String, String)>,
+        #[command(flatten)]
+        client: ClientArgs,
+    },
+    /// Delete every message in a queue
+    Purge {
+        /// Queue name or URL
+        #[arg(long)]
+        queue: String,
+        #[command(flatten)]
+        client: ClientArgs,
+    },
+    /// List a queue's messages without receiving them
+    Peek {
+        /// Queue name or URL
+        #[arg(long)]
+        queue: String,
+        #[arg(long)]
+        limit: Option<usize>,
+        #[command(flatten)]
+        client: ClientArgs,
+    },
+}
 
-#[tokio::main]
-async fn main() {
-    tracing_subscriber::fmt::init();
+#[derive(Debug, clap::Args)]
+struct ClientArgs {
+    /// Server URL [env: LOCAL_SQS_ENDPOINT; defaults from LOCAL_SQS_HOST and LOCAL_SQS_PORT]
+    #[arg(long)]
+    endpoint: Option<String>,
+    /// Pretty-print the JSON output
+    #[arg(long)]
+    pretty: bool,
+}
 
-    let state = AppState::new();
+impl ClientArgs {
+    fn client(&self) -> Client {
+        let endpoint = self
+            .endpoint
+            .clone()
+            .or_else(|| std::env::var("LOCAL_SQS_ENDPOINT").ok())
+            .unwrap_or_else(|| {
+                let config = Config::from_env();
+                format!("http://{}", local_sqs::urls::authority(&config.host, config.port))
+            });
+        Client::new(endpoint)
+    }
 
-    let app = Router::new()
-        .route("/", post(handler))
-        .with_state(state.clone());
+    fn print(&self, output: &impl Serialize) {
+        let json = if self.pretty {
+            serde_json::to_string_pretty(output)
+        } else {
+            serde_json::to_string(output)
+        };
+        // Ignore write errors so that piping into `head` doesn't panic.
+        let _ = writeln!(std::io::stdout(), "{}", json.expect("responses serialize to JSON"));
+    }
+}
 
-    let addr = format!("{}:{}", state.host, state.port);
-    info!("listening on {}", addr);
+fn parse_attribute(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected NAME=VALUE, got {}", s))
+}
 
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+/// Parses `NAME=TYPE:VALUE`. Binary values are given base64-encoded.
+fn parse_message_attribute(s: &str) -> Result<(String, MessageAttributeValue), String> {
+    let (name, typed) = parse_attribute(s)?;
+    let (data_type, value) = typed
+        .split_once(':')
+        .ok_or_else(|| format!("expected NAME=TYPE:VALUE, got {}", s))?;
+    let binary = data_type == "Binary" || data_type.starts_with("Binary.");
+    let value = MessageAttributeValue {
+        string_value: (!binary).then(|| value.to_string()),
+        binary_value: binary.then(|| value.to_string()),
+        data_type: data_type.to_string(),
+    };
+    Ok((name, value))
 }
 
-async fn handler(
-    State(state): State<AppState>,
-    headers: HeaderMap,
-    body: String,
-) -> impl IntoResponse {
-    let target = headers
-        .get("X-Amz-Target")
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or_default();
-
-    info!(target);
-    info!(body);
-
-    match target {
-        "AmazonSQS.CreateQueue" => {
-            let request: queue::CreateQueueRequest = serde_json::from_str(&body).unwrap();
-            match queue::create_queue(State(state), Json(request)).await {
-                Ok(response) => Json(response).into_response(),
-                Err(e) => e.into_response(),
-            }
+async fn run(command: Command) -> Result<(), ClientError> {
+    match command {
+        Command::Send {
+            queue,
+            body,
+            attributes,
+            delay,
+            client,
+        } => {
+            let attributes = attributes.into_iter().collect();
+            let response = client
+                .client()
+                .send_message(&queue, &body, attributes, delay)
+                .await?;
+            client.print(&response);
         }
-        "AmazonSQS.GetQueueUrl" => {
-            let request: queue::GetQueueUrlRequest = serde_json::from_str(&body).unwrap();
-            match queue::get_queue_url(State(state), Json(request)).await {
-                Ok(response) => Json(response).into_response(),
-                Err(e) => e.into_response(),
-            }
+        Command::Receive {
+            queue,
+            max,
+            wait,
+            visibility_timeout,
+            client,
+        } => {
+            let response = client
+                .client()
+                .receive_message(&queue, max, wait, visibility_timeout)
+                .await?;
+            client.print(&response);
         }
-        "AmazonSQS.ListQueues" => {
-            let request: queue::ListQueuesRequest = serde_json::from_str(&body).unwrap();
-            let response = queue::list_queues(State(state), Json(request)).await;
-            Json(response).into_response()
+        Command::ListQueues { prefix, client } => {
+            let queue_urls = client.client().list_queues(prefix.as_deref()).await?;
+            client.print(&ListQueuesResponse {
+                queue_urls,
+                next_token: None,
+            });
         }
-        "AmazonSQS.DeleteQueue" => {
-            let request: queue::DeleteQueueRequest = serde_json::from_str(&body).unwrap();
-            match queue::delete_queue(State(state), Json(request)).await {
-                Ok(_) => Json(()).into_response(),
-                Err(e) => e.into_response(),
-            }
+        Command::CreateQueue {
+            queue,
+            attributes,
+            client,
+        } => {
+            let attributes = attributes.into_iter().collect();
+            let response = client.client().create_queue(&queue, attributes).await?;
+            client.print(&response);
         }
-        "AmazonSQS.PurgeQueue" => {
-            let request: queue::PurgeQueueRequest = serde_json::from_str(&body).unwrap();
-            match queue::purge_queue(State(state), Json(request)).await {
-                Ok(_) => Json(()).into_response(),
-                Err(e) => e.into_response(),
-            }
-        }
-        "AmazonSQS.GetQueueAttributes" => {
-            let request: queue::GetQueueAttributesRequest = serde_json::from_str(&body).unwrap();
-            match queue::get_queue_attributes(State(state), Json(request)).await {
-                Ok(response) => Json(response).into_response(),
-                Err(e) => e.into_response(),
-            }
+        Command::Purge { queue, client } => {
+            client.client().purge_queue(&queue).await?;
+            client.print(&HashMap::<String, String>::new());
         }
-        "AmazonSQS.SendMessage" => {
-            let request: queue::SendMessageRequest = serde_json::from_str(&body).unwrap();
-            match queue::send_message(State(state), Json(request)).await {
-                Ok(response) => Json(response).into_response(),
-                Err(e) => e.into_response(),
-            }
+        Command::Peek {
+            queue,
+            limit,
+            client,
+        } => {
+            let messages = client.client().peek(&queue, limit).await?;
+            client.print(&messages);
         }
-        "AmazonSQS.ReceiveMessage" => {
-            let request: queue::ReceiveMessageRequest = serde_json::from_str(&body).unwrap();
-            match queue::receive_message(State(state), Json(request)).await {
-                Ok(response) => Json(response).into_response(),
-                Err(e) => e.into_response(),
-            }
+    }
+    Ok(())
+}
+
+/// Logs to stdout as usual and, given an OTLP endpoint, also exports spans
+/// there. The returned guard flushes them on exit.
+#[cfg(feature = "otel")]
+fn init_tracing(endpoint: Option<String>) -> Option<local_sqs::telemetry::OtlpGuard> {
+    use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+    use tracing_subscriber::prelude::*;
+
+    let Some(endpoint) =
+        endpoint.or_else(|| std::env::var("LOCAL_SQS_OTLP_ENDPOINT").ok().filter(|e| !e.is_empty()))
+    else {
+        tracing_subscriber::fmt::init();
+        return None;
+    };
+    let (otel, guard) = match local_sqs::telemetry::init(&endpoint) {
+        Ok(otel) => otel,
+        Err(e) => {
+            eprintln!("invalid OTLP endpoint {}: {}", endpoint, e);
+            std::process::exit(1);
         }
-        "AmazonSQS.DeleteMessage" => {
-            let request: queue::DeleteMessageRequest = serde_json::from_str(&body).unwrap();
-            match queue::delete_message(State(state), Json(request)).await {
-                Ok(_) => Json(()).into_response(),
-                Err(e) => e.into_response(),
-            }
+    };
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(EnvFilter::from_default_env()))
+        .with(otel.with_filter(LevelFilter::INFO))
+        .init();
+    Some(guard)
+}
+
+#[tokio::main]
+async fn main() {
+    let mut args = Args::parse();
+    if let Some(command) = args.command.take() {
+        if let Err(e) = run(command).await {
+            eprintln!("{}", e);
+            std::process::exit(1);
         }
-        "AmazonSQS.SetQueueAttributes" => {
-            let request: queue::SetQueueAttributesRequest = serde_json::from_str(&body).unwrap();
-            match queue::set_queue_attributes(State(state), Json(request)).await {
-                Ok(_) => Json(()).into_response(),
-                Err(e) => e.into_response(),
+        return;
+    }
+
+    #[cfg(feature = "otel")]
+    let _otlp = init_tracing(args.otlp_endpoint.take());
+    #[cfg(not(feature = "otel"))]
+    tracing_subscriber::fmt::init();
+
+    // Settings are layered: defaults, then the config file, then the
+    // environment, then flags.
+    let mut config = Config::default();
+    config.config_file = args
+        .config
+        .clone()
+        .or_else(|| std::env::var_os("LOCAL_SQS_CONFIG").map(PathBuf::from));
+    if let Some(path) = &config.config_file {
+        match ConfigFile::read(path) {
+            Ok(file) => file.apply(&mut config),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
             }
         }
-        "AmazonSQS.AddPermission" => {
-            let request: queue::AddPermissionRequest = serde_json::from_str(&body).unwrap();
-            match queue::add_permission(State(state), Json(request)).await {
-                Ok(_) => Json(()).into_response(),
-                Err(e) => e.into_response(),
-            }
+    }
+    config.apply_env();
+    apply_args(&mut config, args);
+
+    let (_addrs, server, shutdown) = match local_sqs::serve(config).await {
+        Ok(serving) => serving,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
         }
-        _ => {
-            let error = error::SqsError::InvalidAction(target.to_string());
-            error.into_response()
+    };
+
+    tokio::signal::ctrl_c().await.ok();
+    shutdown.shutdown();
+    server.await.ok();
+}
+
+/// Layers the server flags in `args` over `config`, so that each flag given
+/// overrides the config file and the environment.
+fn apply_args(config: &mut Config, args: Args) {
+    if let Some(path) = args.config {
+        config.config_file = Some(path);
+    }
+    if let Some(host) = args.host {
+        config.host = host;
+    }
+    if let Some(port) = args.port {
+        config.port = port;
+    }
+    if !args.bind.is_empty() {
+        config.bind = args.bind;
+    }
+    if let Some(region) = args.region {
+        config.region = region;
+    }
+    if let Some(account_id) = args.account_id {
+        config.account_id = account_id;
+    }
+    if let Some(fixtures) = args.fixtures {
+        config.fixtures = Some(fixtures);
+    }
+    if let Some(max) = args.max_messages_per_queue {
+        config.max_messages_per_queue = Some(max);
+    }
+    if let Some(max) = args.max_total_bytes {
+        config.max_total_bytes = Some(max);
+    }
+    if let Some(code) = args.queue_full_error_code {
+        config.queue_full_error_code = code;
+    }
+    if let Some(limit) = args.max_message_size_limit {
+        config.max_message_size_limit = limit;
+    }
+    if let Some(mode) = args.validation {
+        config.validation = mode;
+    }
+    if args.manual_clock {
+        config.manual_clock = true;
+    }
+    config
+        .default_queue_attributes
+        .extend(args.default_queue_attributes);
+    if let Some(p) = args.duplicate_delivery_probability {
+        config.chaos.duplicate_delivery_probability = p;
+    }
+    if args.shuffle_delivery {
+        config.chaos.shuffle_delivery = true;
+    }
+    if let Some(seed) = args.chaos_seed {
+        config.chaos_seed = Some(seed);
+    }
+    if let Some(base_path) = args.base_path {
+        config.base_path = base_path;
+    }
+    if let Some(buckets) = args.histogram_buckets {
+        config.histogram_buckets = buckets;
+    }
+    if let Some(secret) = args.receipt_handle_secret {
+        config.receipt_handle_secret = Some(secret);
+    }
+    if let Some(seed) = args.deterministic_ids {
+        config.deterministic_ids = Some(seed);
+    }
+    if let Some(secs) = args.idle_queue_ttl_secs {
+        config.idle_queue_ttl = Some(Duration::from_secs(secs));
+    }
+    if let Some(secs) = args.purge_cooldown_secs {
+        config.purge_cooldown = Some(Duration::from_secs(secs));
+    }
+    if !args.idle_queue_exempt.is_empty() {
+        config.idle_queue_exempt = args.idle_queue_exempt;
+    }
+    if let Some(capacity) = args.event_log_capacity {
+        config.event_log_capacity = capacity;
+    }
+    if let Some(capacity) = args.message_history {
+        config.message_history = capacity;
+    }
+    if let Some(max) = args.max_concurrent_requests {
+        config.max_concurrent_requests = Some(max);
+    }
+    if let Some(max) = args.max_long_polls_per_queue {
+        config.max_long_polls_per_queue = Some(max);
+    }
+    if let Some(max) = args.max_connections {
+        config.max_connections = Some(max);
+    }
+    if let Some(threshold) = args.compress_bodies_above {
+        config.compress_bodies_above = Some(threshold);
+    }
+    if let Some(storage) = args.storage {
+        config.storage = storage;
+    }
+    if let Some(path) = args.db_path {
+        config.db_path = path;
+    }
+    if let Some(url) = args.redis_url {
+        config.redis_url = url;
+    }
+    if let Some(dir) = args.checkpoint_dir {
+        config.checkpoint_dir = Some(dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(flags: &[&str]) -> Args {
+        Args::try_parse_from(std::iter::once("local-sqs-rs").chain(flags.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn flags_override_settings_from_the_file_and_environment() {
+        let mut config = Config {
+            host: "sqs.internal".to_string(),
+            port: 9324,
+            region: "eu-west-1".to_string(),
+            ..Config::default()
+        };
+        let args = parse(&[
+            "--port",
+            "0",
+            "--bind",
+            "127.0.0.1:0",
+            "--bind",
+            "[::1]:0",
+            "--default-queue-attribute",
+            "VisibilityTimeout=5",
+            "--deterministic-ids",
+            "--storage",
+            "sqlite",
+        ]);
+        apply_args(&mut config, args);
+
+        // Port 0 is a setting like any other: `serve` binds an ephemeral
+        // port and puts the bound one in queue URLs.
+        assert_eq!(config.port, 0);
+        assert_eq!(config.bind, ["127.0.0.1:0", "[::1]:0"]);
+        assert_eq!(config.host, "sqs.internal");
+        assert_eq!(config.region, "eu-west-1");
+        assert_eq!(config.default_queue_attributes["VisibilityTimeout"], "5");
+        assert_eq!(config.deterministic_ids, Some(0));
+        assert_eq!(config.storage, Storage::Sqlite);
+    }
+
+    #[test]
+    fn unset_flags_leave_settings_alone() {
+        let mut config = Config {
+            port: 9325,
+            bind: vec!["0.0.0.0:9325".to_string()],
+            ..Config::default()
+        };
+        apply_args(&mut config, parse(&[]));
+        assert_eq!(config.port, 9325);
+        assert_eq!(config.bind, ["0.0.0.0:9325"]);
+        assert_eq!(config.deterministic_ids, None);
+    }
+
+    #[test]
+    fn malformed_flags_are_rejected() {
+        for flags in [
+            &["--port", "65536"][..],
+            &["--port", "sqs"],
+            &["--default-queue-attribute", "VisibilityTimeout"],
+            &["--storage", "postgres"],
+            &["--deterministic-ids", "seed"],
+        ] {
+            let args = std::iter::once("local-sqs-rs").chain(flags.iter().copied());
+            assert!(Args::try_parse_from(args).is_err(), "{:?}", flags);
         }
+        assert_eq!(parse(&["--deterministic-ids", "42"]).deterministic_ids, Some(42));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,238 @@
+use crate::queue;
+use crate::state::{AppState, Queue, KEEP_TAG};
+use chrono::{DateTime, Utc};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tracing::{debug, info};
+
+/// Spawns the background task that periodically sweeps every queue, owned
+/// by `state`.
+///
+/// The task runs every `state.sweep_interval` until `state.shutdown` is
+/// cancelled or [`AppState::shut_down`] aborts it.
+pub fn spawn(state: AppState) {
+    let task = tokio::spawn(run(state.clone()));
+    if let Some(previous) = state.maintenance.lock().unwrap().replace(task) {
+        previous.abort();
+    }
+}
+
+async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(state.sweep_interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            _ = state.shutdown.cancelled() => break,
+            _ = interval.tick() => sweep(&state, state.clock.now()).await,
+        }
+    }
+
+    debug!("maintenance task stopped");
+}
+
+/// Runs one maintenance pass over all queues as of `now`:
+/// drops messages past their retention period, returns messages whose
+/// visibility timeout expired (dead-lettering those over `maxReceiveCount`),
+/// wakes long polls on queues that have visible messages, clears purge
+/// tombstones whose cooldown is over, forgets expired FIFO deduplication
+/// IDs, and deletes idle queues if
+/// [`Config::idle_queue_ttl`](crate::Config) is set.
+///
+/// Each queue is locked only for the duration of its own sweep.
+pub async fn sweep(state: &AppState, now: DateTime<Utc>) {
+    for url in state.store.urls() {
+        let dead_letter = state.can_dead_letter(&url);
+        let swept = state.store.update_selected(&url, |queue| queue.sweep_selection(now), |queue| {
+            let expired = queue.expire_retention(now);
+            if expired > 0 {
+                debug!(queue = %queue.name, expired, "dropped messages past retention");
+            }
+
+            let dead_lettered = queue.release_expired(now, dead_letter);
+            queue.expire_purge_tombstone(now);
+            queue.deduplication.expire(now);
+
+            if queue.has_visible(now) {
+                queue.notify.notify_waiters();
+            }
+            queue.latency.oldest_visible_message_age = queue.oldest_visible_message_age(now);
+
+            (
+                dead_lettered,
+                queue
+                    .redrive_policy
+                    .as_ref()
+                    .map(|rp| rp.dead_letter_target_arn.clone()),
+            )
+        });
+        let Ok((dead_lettered, dead_letter_target_arn)) = swept.await else {
+            continue;
+        };
+
+        if let Some(arn) = dead_letter_target_arn {
+            state.move_to_dead_letter_queue(&arn, dead_lettered).await;
+        }
+    }
+
+    if let Some(ttl) = state.idle_queue_ttl {
+        delete_idle_queues(state, now, ttl).await;
+    }
+}
+
+async fn delete_idle_queues(state: &AppState, now: DateTime<Utc>, ttl: Duration) {
+    let deletable = |queue: &Queue| {
+        queue.is_idle(now, ttl)
+            && !state.idle_queue_exempt.contains(&queue.name)
+            && queue.tags.get(KEEP_TAG).is_none_or(|keep| keep != "true")
+    };
+    let idle = state
+        .store
+        .collect(|queue| deletable(queue).then(|| queue.url.clone()));
+
+    // Checked again on removal, in case the queue was used in the meantime.
+    for url in idle {
+        if let Ok(Some(queue)) = queue::remove_queue_if(state, &url, "idle", deletable).await {
+            state.idle_queues_deleted.fetch_add(1, Ordering::Relaxed);
+            info!(queue = %queue.name, ttl_secs = ttl.as_secs(), "deleted idle queue");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::error::SqsError;
+    use axum::extract::State;
+    use axum::Json;
+    use serde::de::DeserializeOwned;
+    use serde_json::{json, Value};
+
+    /// State on a manual clock, so sweeps can be run at any later time.
+    fn state(config: Config) -> AppState {
+        AppState::new(&Config {
+            manual_clock: true,
+            ..config
+        })
+    }
+
+    fn request<T: DeserializeOwned>(request: Value) -> Json<T> {
+        Json(serde_json::from_value(request).unwrap())
+    }
+
+    async fn create_queue(state: &AppState, name: &str, attributes: Value) -> String {
+        let request = request(json!({"QueueName": name, "Attributes": attributes}));
+        queue::create_queue(State(state.clone()), request).await.unwrap().queue_url
+    }
+
+    async fn send(state: &AppState, url: &str) {
+        let request = request(json!({"QueueUrl": url, "MessageBody": "m"}));
+        queue::send_message(State(state.clone()), request).await.unwrap();
+    }
+
+    async fn receive(state: &AppState, url: &str) -> usize {
+        let request = request(json!({"QueueUrl": url, "VisibilityTimeout": 30}));
+        let received = queue::receive_message(State(state.clone()), request).await.unwrap();
+        received.messages.len()
+    }
+
+    async fn purge(state: &AppState, url: &str) -> Result<(), SqsError> {
+        let request = request(json!({"QueueUrl": url}));
+        queue::purge_queue(State(state.clone()), request).await.map(|_| ())
+    }
+
+    fn depth(state: &AppState, url: &str) -> usize {
+        state.store.read(url, |queue| queue.messages.len()).unwrap()
+    }
+
+    fn advance(state: &AppState, secs: i64) -> DateTime<Utc> {
+        state.clock.advance(chrono::Duration::seconds(secs)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn sweeps_drop_messages_past_retention() {
+        let state = state(Config::default());
+        let url = create_queue(&state, "short", json!({"MessageRetentionPeriod": "60"})).await;
+        send(&state, &url).await;
+
+        sweep(&state, advance(&state, 59)).await;
+        assert_eq!(depth(&state, &url), 1);
+        sweep(&state, advance(&state, 1)).await;
+        assert_eq!(depth(&state, &url), 0);
+    }
+
+    #[tokio::test]
+    async fn sweeps_release_lapsed_visibility_and_dead_letter() {
+        let state = state(Config::default());
+        let dlq = create_queue(&state, "dlq", json!({})).await;
+        let arn = state.queue_arn("dlq");
+        let policy = json!({"deadLetterTargetArn": arn, "maxReceiveCount": 2}).to_string();
+        let url = create_queue(&state, "work", json!({"RedrivePolicy": policy})).await;
+        send(&state, &url).await;
+
+        assert_eq!(receive(&state, &url).await, 1);
+        sweep(&state, advance(&state, 29)).await;
+        assert_eq!(receive(&state, &url).await, 0);
+        // Received once, so the lapsed message becomes visible again.
+        sweep(&state, advance(&state, 1)).await;
+        assert_eq!(receive(&state, &url).await, 1);
+
+        // Received twice, so it is moved to the dead-letter queue instead.
+        sweep(&state, advance(&state, 30)).await;
+        assert_eq!(depth(&state, &url), 0);
+        assert_eq!(depth(&state, &dlq), 1);
+        let moved = state.store.read(&url, |queue| queue.stats.dlq_moved).unwrap();
+        assert_eq!(moved, 1);
+    }
+
+    #[tokio::test]
+    async fn sweeps_keep_messages_whose_dead_letter_queue_is_gone() {
+        let state = state(Config::default());
+        let dlq = create_queue(&state, "dlq", json!({})).await;
+        let arn = state.queue_arn("dlq");
+        let policy = json!({"deadLetterTargetArn": arn, "maxReceiveCount": 1}).to_string();
+        let url = create_queue(&state, "work", json!({"RedrivePolicy": policy})).await;
+        send(&state, &url).await;
+        assert_eq!(receive(&state, &url).await, 1);
+        let request = request(json!({"QueueUrl": dlq}));
+        queue::delete_queue(State(state.clone()), request).await.unwrap();
+
+        sweep(&state, advance(&state, 30)).await;
+        assert_eq!(depth(&state, &url), 1);
+        let moved = state.store.read(&url, |queue| queue.stats.dlq_moved).unwrap();
+        assert_eq!(moved, 0);
+        assert_eq!(receive(&state, &url).await, 1);
+    }
+
+    #[tokio::test]
+    async fn sweeps_clear_purge_tombstones_once_their_cooldown_is_over() {
+        let state = state(Config {
+            purge_cooldown: Some(Duration::from_secs(60)),
+            ..Config::default()
+        });
+        let url = create_queue(&state, "purged", json!({})).await;
+        purge(&state, &url).await.unwrap();
+        let error = purge(&state, &url).await.unwrap_err();
+        assert!(matches!(error, SqsError::PurgeQueueInProgress(_)), "{:?}", error);
+
+        let tombstone = |state: &AppState| state.store.read(&url, |q| q.purged_until).unwrap();
+        sweep(&state, advance(&state, 59)).await;
+        assert!(tombstone(&state).is_some());
+        sweep(&state, advance(&state, 1)).await;
+        assert_eq!(tombstone(&state), None);
+        purge(&state, &url).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn the_state_owns_and_stops_its_maintenance_task() {
+        let state = state(Config::default());
+        spawn(state.clone());
+        assert!(state.maintenance.lock().unwrap().is_some());
+
+        let task = state.shut_down().expect("a running task");
+        assert!(state.shutdown.is_cancelled());
+        let _ = task.await;
+        assert!(state.shut_down().is_none());
+    }
+}
@@ -0,0 +1,287 @@
+use crate::error::SqsError;
+use crate::state::MessageAttributeValue;
+use base64::{engine::general_purpose, Engine as _};
+use std::collections::{HashMap, HashSet};
+
+/// The most message attributes a single message may carry.
+pub const MAX_MESSAGE_ATTRIBUTES: usize = 10;
+
+/// The longest a message attribute name may be.
+pub const MAX_NAME_LENGTH: usize = 256;
+
+const RESERVED_PREFIXES: &[&str] = &["aws.", "amazon."];
+
+/// Numbers may carry at most this many significant digits.
+const MAX_NUMBER_DIGITS: usize = 38;
+
+/// The base type of a message attribute's `DataType`. A data type may add a
+/// custom label after a period, e.g. `Number.float` or `Binary.gif`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataType {
+    String,
+    Number,
+    Binary,
+}
+
+impl DataType {
+    /// Parses the base type of `data_type`, or `None` if it isn't one of
+    /// `String`, `Number` or `Binary` with an optional non-empty label.
+    pub fn parse(data_type: &str) -> Option<Self> {
+        let base = match data_type.split_once('.') {
+            Some((_, "")) => return None,
+            Some((base, _)) => base,
+            None => data_type,
+        };
+        match base {
+            "String" => Some(DataType::String),
+            "Number" => Some(DataType::Number),
+            "Binary" => Some(DataType::Binary),
+            _ => None,
+        }
+    }
+
+    /// The transport type byte that precedes the value in the attribute MD5.
+    pub fn transport_type(self) -> u8 {
+        match self {
+            DataType::String | DataType::Number => 1,
+            DataType::Binary => 2,
+        }
+    }
+}
+
+/// Checks message attributes against the rules SQS applies on send.
+pub fn validate(attributes: &HashMap<String, MessageAttributeValue>) -> Result<(), SqsError> {
+    if attributes.len() > MAX_MESSAGE_ATTRIBUTES {
+        return Err(SqsError::InvalidParameterValue(format!(
+            "Number of message attributes [{}] exceeds the allowed maximum [{}].",
+            attributes.len(),
+            MAX_MESSAGE_ATTRIBUTES
+        )));
+    }
+
+    // Sorted so that the reported attribute doesn't depend on hash order.
+    let mut names: Vec<&String> = attributes.keys().collect();
+    names.sort();
+
+    let mut seen = HashSet::new();
+    for name in names {
+        validate_name(name)?;
+        validate_value(name, &attributes[name])?;
+        if !seen.insert(name.to_lowercase()) {
+            return Err(invalid_name(name, "duplicate name (names are case-insensitive)"));
+        }
+    }
+    Ok(())
+}
+
+/// Keeps the attributes a receiver asked for with `MessageAttributeNames`:
+/// `All` or `.*` selects every attribute, `prefix.*` those whose name starts
+/// with `prefix.`, and any other entry the attribute of that name.
+pub fn select(
+    attributes: HashMap<String, MessageAttributeValue>,
+    requested: &[String],
+) -> HashMap<String, MessageAttributeValue> {
+    if requested.iter().any(|r| r == "All" || r == ".*") {
+        return attributes;
+    }
+    attributes
+        .into_iter()
+        .filter(|(name, _)| {
+            requested.iter().any(|r| match r.strip_suffix(".*") {
+                Some(prefix) => name
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with('.')),
+                None => r == name,
+            })
+        })
+        .collect()
+}
+
+/// The message attribute giving a message's priority on queues with
+/// [`PRIORITY_ATTRIBUTE`](crate::queue::PRIORITY_ATTRIBUTE) set. Non-AWS.
+pub const PRIORITY: &str = "local-sqs.priority";
+
+/// The priority of a message with `attributes`: its [`PRIORITY`] attribute
+/// if that is a `Number`, and 0 otherwise.
+pub fn priority(attributes: &HashMap<String, MessageAttributeValue>) -> f64 {
+    attributes
+        .get(PRIORITY)
+        .filter(|attr| DataType::parse(&attr.data_type) == Some(DataType::Number))
+        .and_then(|attr| attr.string_value.as_deref()?.parse().ok())
+        .unwrap_or(0.0)
+}
+
+/// The only message system attribute a sender may set.
+pub const AWS_TRACE_HEADER: &str = "AWSTraceHeader";
+
+/// Checks `MessageSystemAttributes` on send: only `AWSTraceHeader` may be
+/// set, and it must be a non-empty `String`.
+pub fn validate_system(
+    attributes: &HashMap<String, MessageAttributeValue>,
+) -> Result<(), SqsError> {
+    let mut names: Vec<&String> = attributes.keys().collect();
+    names.sort();
+
+    for name in names {
+        if name != AWS_TRACE_HEADER {
+            return Err(SqsError::InvalidParameterValue(format!(
+                "Message system attribute name '{}' is invalid.",
+                name
+            )));
+        }
+        let attr = &attributes[name];
+        let valid = attr.data_type == "String"
+            && attr.binary_value.is_none()
+            && attr.string_value.as_deref().is_some_and(|v| !v.is_empty());
+        if !valid {
+            return Err(SqsError::InvalidParameterValue(format!(
+                "Message system attribute '{}' is invalid. Reason: must be a non-empty String.",
+                name
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_name(name: &str) -> Result<(), SqsError> {
+    if name.is_empty() {
+        return Err(invalid_name(name, "must not be empty"));
+    }
+    if name.len() > MAX_NAME_LENGTH {
+        return Err(invalid_name(name, "must be at most 256 characters"));
+    }
+    let lower = name.to_lowercase();
+    if RESERVED_PREFIXES.iter().any(|prefix| lower.starts_with(prefix)) {
+        return Err(invalid_name(name, "prefixes AWS. and Amazon. are reserved"));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+    {
+        return Err(invalid_name(
+            name,
+            "may only contain alphanumeric characters, hyphens, underscores and periods",
+        ));
+    }
+    if name.starts_with('.') || name.ends_with('.') || name.contains("..") {
+        return Err(invalid_name(
+            name,
+            "must not start or end with a period or contain consecutive periods",
+        ));
+    }
+    Ok(())
+}
+
+fn validate_value(name: &str, attr: &MessageAttributeValue) -> Result<(), SqsError> {
+    let data_type = DataType::parse(&attr.data_type).ok_or_else(|| {
+        invalid_value(
+            name,
+            &format!(
+                "data type '{}' must be String, Number or Binary, optionally followed by a period and a custom label",
+                attr.data_type
+            ),
+        )
+    })?;
+
+    match data_type {
+        DataType::String | DataType::Number => {
+            if attr.binary_value.is_some() {
+                return Err(invalid_value(name, "BinaryValue is not allowed for this data type"));
+            }
+            let value = attr.string_value.as_deref().unwrap_or_default();
+            if value.is_empty() {
+                return Err(invalid_value(name, "StringValue must not be empty"));
+            }
+            if let Some(c) = invalid_character(value) {
+                return Err(invalid_contents(&format!("message attribute '{}'", name), c));
+            }
+            if data_type == DataType::Number && !is_valid_number(value) {
+                return Err(invalid_value(
+                    name,
+                    "StringValue must be a number with at most 38 digits of precision between 10^-128 and 10^126",
+                ));
+            }
+        }
+        DataType::Binary => {
+            if attr.string_value.is_some() {
+                return Err(invalid_value(name, "StringValue is not allowed for Binary"));
+            }
+            let decoded = attr
+                .binary_value
+                .as_deref()
+                .map(|value| general_purpose::STANDARD.decode(value));
+            match decoded {
+                Some(Ok(bytes)) if !bytes.is_empty() => {}
+                Some(Err(_)) => {
+                    return Err(invalid_value(name, "BinaryValue must be base64-encoded"));
+                }
+                _ => return Err(invalid_value(name, "BinaryValue must not be empty")),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The first character of `text` that SQS doesn't allow in message bodies
+/// and attribute values, if any.
+pub fn invalid_character(text: &str) -> Option<char> {
+    text.chars().find(|c| {
+        !matches!(
+            c,
+            '\t' | '\n' | '\r' | '\u{20}'..='\u{D7FF}' | '\u{E000}'..='\u{FFFD}' | '\u{10000}'..
+        )
+    })
+}
+
+/// The error for `c`, an [`invalid_character`] found in `what`.
+pub fn invalid_contents(what: &str, c: char) -> SqsError {
+    SqsError::InvalidMessageContents(format!(
+        "Invalid binary character '#x{:X}' was found in {}, the set of allowed characters is #x9 | #xA | #xD | #x20 to #xD7FF | #xE000 to #xFFFD | #x10000 to #x10FFFF",
+        c as u32, what
+    ))
+}
+
+fn is_valid_number(value: &str) -> bool {
+    let (mantissa, exponent) = match value.find(['e', 'E']) {
+        Some(i) => (&value[..i], Some(&value[i + 1..])),
+        None => (value, None),
+    };
+    let mantissa = mantissa.strip_prefix(['+', '-']).unwrap_or(mantissa);
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits_only = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if integer.is_empty() && fraction.is_empty() || !digits_only(integer) || !digits_only(fraction) {
+        return false;
+    }
+    if let Some(exponent) = exponent {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        if exponent.is_empty() || !digits_only(exponent) {
+            return false;
+        }
+    }
+
+    let digits = format!("{}{}", integer, fraction);
+    let significant = digits.trim_start_matches('0').trim_end_matches('0');
+    if significant.len() > MAX_NUMBER_DIGITS {
+        return false;
+    }
+
+    let Ok(n) = value.parse::<f64>() else {
+        return false;
+    };
+    // Zero is valid; anything else must fall within the representable range.
+    significant.is_empty() || (1e-128..=1e126).contains(&n.abs())
+}
+
+fn invalid_value(name: &str, reason: &str) -> SqsError {
+    SqsError::InvalidParameterValue(format!(
+        "Message attribute '{}' is invalid. Reason: {}.",
+        name, reason
+    ))
+}
+
+fn invalid_name(name: &str, reason: &str) -> SqsError {
+    SqsError::InvalidParameterValue(format!(
+        "Message attribute name '{}' is invalid. Reason: {}.",
+        name, reason
+    ))
+}
@@ -0,0 +1,526 @@
+use crate::history::{HistoryEntry, HistoryKind};
+use crate::state::Message;
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::mem;
+
+/// A queue's messages, indexed by state.
+///
+/// Every message is in exactly one of three indices:
+/// - `ready`: visible now, ordered by send sequence;
+/// - `delayed`: not yet visible because of `DelaySeconds`, ordered by the
+///   time it becomes visible;
+/// - `in_flight`: received and awaiting delete, ordered by visibility
+///   deadline.
+///
+/// In-flight messages are additionally indexed by their current receipt
+/// handle, and by the handles of any duplicate deliveries of them, and
+/// counted by message group.
+///
+/// Claiming the next visible message, expiring delays or visibility
+/// timeouts, and resolving a receipt handle are all `O(log n)` or better per
+/// message instead of a scan of the queue.
+///
+/// A store that keeps messages out of memory loads into a queue's
+/// `MessageStore` only the messages an operation needs, and unloads them
+/// again once it is done. The rest are counted in `unloaded`, so that
+/// [`len`](Self::len) still counts every message; everything else sees only
+/// those loaded.
+#[derive(Debug, Clone, Default)]
+pub struct MessageStore {
+    messages: BTreeMap<u64, Message>,
+    ready: BTreeSet<u64>,
+    delayed: BTreeSet<(DateTime<Utc>, u64)>,
+    in_flight: BTreeSet<(DateTime<Utc>, u64)>,
+    receipt_handles: HashMap<String, u64>,
+    /// Receipt handles of duplicate deliveries, by message. They resolve to
+    /// the same message as its own handle and are dropped along with it.
+    duplicate_handles: HashMap<u64, Vec<String>>,
+    /// In-flight messages by message group. On FIFO queues a group listed
+    /// here is locked: none of its other messages is handed out until these
+    /// are deleted or become visible again.
+    locked_groups: HashMap<String, usize>,
+    next_seq: u64,
+    /// Messages held elsewhere and not loaded.
+    unloaded: usize,
+    /// What changed since the last [`take_changes`](Self::take_changes),
+    /// once tracking is on. Only changes to what a stored message records
+    /// are tracked: a delay running out is not one.
+    changes: Option<MessageChanges>,
+}
+
+/// What changed in a [`MessageStore`] since it was last asked.
+#[derive(Debug, Clone, Default)]
+pub struct MessageChanges {
+    /// Messages added, changed or removed. Those no longer in the store
+    /// were removed.
+    pub messages: BTreeSet<u64>,
+    /// Receipt handles that stopped resolving, as in-flight messages were
+    /// deleted or became visible again.
+    pub released_handles: Vec<String>,
+    /// Whether every message was removed, those not loaded included.
+    pub cleared: bool,
+}
+
+/// Message counts by state, as of a given instant.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MessageCounts {
+    pub visible: usize,
+    pub delayed: usize,
+    pub in_flight: usize,
+}
+
+impl MessageStore {
+    /// A store of `count` messages, none of them loaded, numbering the next
+    /// message pushed `next_seq`.
+    pub fn unloaded(count: usize, next_seq: u64) -> Self {
+        Self {
+            next_seq,
+            unloaded: count,
+            ..Default::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len() + self.unloaded
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The sequence number the next message pushed gets.
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// All messages in send order.
+    pub fn iter(&self) -> impl Iterator<Item = &Message> {
+        self.messages.values()
+    }
+
+    /// Messages sent after message `seq`, in send order.
+    pub fn iter_after(&self, seq: u64) -> impl Iterator<Item = &Message> {
+        self.messages.range(seq + 1..).map(|(_, message)| message)
+    }
+
+    /// In-flight messages, soonest visibility deadline first.
+    pub fn in_flight(&self) -> impl Iterator<Item = &Message> {
+        self.in_flight.iter().map(|(_, seq)| &self.messages[seq])
+    }
+
+    /// Looks up a message by id. This is a scan; it is meant for admin and
+    /// setup paths, not the request hot path. Callers must not change
+    /// `visible_from` or `receipt_handle` through the returned reference.
+    pub fn get_mut_by_id(&mut self, id: &str) -> Option<&mut Message> {
+        let message = self.messages.values_mut().find(|m| m.id == id)?;
+        if let Some(changes) = &mut self.changes {
+            changes.messages.insert(message.seq);
+        }
+        Some(message)
+    }
+
+    /// Adds a message, filing it as in flight if it carries a receipt handle,
+    /// delayed if it isn't visible yet at `now`, and ready otherwise.
+    pub fn push(&mut self, mut message: Message, now: DateTime<Utc>) {
+        message.seq = self.next_seq;
+        self.restore(message, now);
+    }
+
+    /// Like [`push`](Self::push), but keeps the `seq` the message already
+    /// carries, as when reloading a stored queue. Messages pushed later are
+    /// numbered after it.
+    pub fn restore(&mut self, message: Message, now: DateTime<Utc>) {
+        let seq = message.seq;
+        self.next_seq = self.next_seq.max(seq + 1);
+        self.mark_changed(seq);
+
+        if let Some(receipt_handle) = &message.receipt_handle {
+            self.in_flight.insert((message.visible_from, seq));
+            self.receipt_handles.insert(receipt_handle.clone(), seq);
+            lock_group(&mut self.locked_groups, &message);
+        } else if message.visible_from > now {
+            self.delayed.insert((message.visible_from, seq));
+        } else {
+            self.ready.insert(seq);
+        }
+        self.messages.insert(seq, message);
+    }
+
+    /// Loads the unloaded message `message`, with the receipt handles of
+    /// any duplicate deliveries of it. Loading is not a change.
+    pub fn load(&mut self, message: Message, duplicate_handles: Vec<String>, now: DateTime<Utc>) {
+        let seq = message.seq;
+        let changes = self.changes.take();
+        self.restore(message, now);
+        self.changes = changes;
+        self.unloaded = self.unloaded.saturating_sub(1);
+        for receipt_handle in &duplicate_handles {
+            self.receipt_handles.insert(receipt_handle.clone(), seq);
+        }
+        if !duplicate_handles.is_empty() {
+            self.duplicate_handles.insert(seq, duplicate_handles);
+        }
+    }
+
+    /// Unloads every loaded message, keeping count of them. Unloading is
+    /// not a change.
+    pub fn unload(&mut self) {
+        self.unloaded += self.messages.len();
+        self.messages.clear();
+        self.ready.clear();
+        self.delayed.clear();
+        self.in_flight.clear();
+        self.receipt_handles.clear();
+        self.duplicate_handles.clear();
+        self.locked_groups.clear();
+    }
+
+    pub fn remove(&mut self, seq: u64) -> Option<Message> {
+        let message = self.messages.remove(&seq)?;
+        self.mark_changed(seq);
+        if let Some(receipt_handle) = &message.receipt_handle {
+            self.in_flight.remove(&(message.visible_from, seq));
+            self.receipt_handles.remove(receipt_handle);
+            self.mark_released(receipt_handle);
+            self.drop_duplicate_handles(seq);
+            unlock_group(&mut self.locked_groups, &message);
+        } else {
+            self.ready.remove(&seq);
+            self.delayed.remove(&(message.visible_from, seq));
+        }
+        Some(message)
+    }
+
+    /// Removes and returns every message matching `predicate`.
+    pub fn remove_where(&mut self, mut predicate: impl FnMut(&Message) -> bool) -> Vec<Message> {
+        let seqs: Vec<u64> = self
+            .messages
+            .iter()
+            .filter(|(_, m)| predicate(m))
+            .map(|(seq, _)| *seq)
+            .collect();
+        seqs.into_iter().filter_map(|seq| self.remove(seq)).collect()
+    }
+
+    pub fn clear(&mut self) {
+        if let Some(changes) = &mut self.changes {
+            changes.messages.extend(self.messages.keys());
+            changes.released_handles.extend(self.receipt_handles.keys().cloned());
+            changes.cleared = true;
+        }
+        self.unload();
+        self.unloaded = 0;
+    }
+
+    /// The in-flight message holding `receipt_handle`, if any.
+    pub fn find_by_receipt_handle(&self, receipt_handle: &str) -> Option<u64> {
+        self.receipt_handles.get(receipt_handle).copied()
+    }
+
+    pub fn get(&self, seq: u64) -> Option<&Message> {
+        self.messages.get(&seq)
+    }
+
+    /// The oldest ready message, if any.
+    pub fn first_ready(&self) -> Option<u64> {
+        self.ready.first().copied()
+    }
+
+    /// Makes delayed messages whose delay has passed visible, and returns
+    /// in-flight messages whose visibility timeout has lapsed to the ready
+    /// set. In-flight messages for which `dead_letter` returns true are
+    /// removed instead and handed back to the caller.
+    pub fn release_due(
+        &mut self,
+        now: DateTime<Utc>,
+        mut dead_letter: impl FnMut(&Message) -> bool,
+    ) -> Vec<Message> {
+        while let Some(&(visible_from, seq)) = self.delayed.first() {
+            if visible_from > now {
+                break;
+            }
+            self.delayed.pop_first();
+            self.ready.insert(seq);
+        }
+
+        let mut dead_lettered = Vec::new();
+        while let Some(&(deadline, seq)) = self.in_flight.first() {
+            if deadline > now {
+                break;
+            }
+            self.in_flight.pop_first();
+            self.drop_duplicate_handles(seq);
+            self.mark_changed(seq);
+            let message = self.messages.get_mut(&seq).expect("indexed message exists");
+            let lapsed = message.receipt_handle.take();
+            if let Some(receipt_handle) = &lapsed {
+                self.receipt_handles.remove(receipt_handle);
+                if let Some(changes) = &mut self.changes {
+                    changes.released_handles.push(receipt_handle.clone());
+                }
+            }
+            unlock_group(&mut self.locked_groups, message);
+            if dead_letter(message) {
+                dead_lettered.extend(self.messages.remove(&seq));
+            } else {
+                message.record(|| HistoryEntry {
+                    request_id: None,
+                    receipt_handle: lapsed,
+                    ..HistoryEntry::new(HistoryKind::Released, deadline)
+                });
+                self.ready.insert(seq);
+            }
+        }
+        dead_lettered
+    }
+
+    /// Claims up to `max` ready messages in send order, moving them in
+    /// flight until `visible_until`. Taking `&mut self` makes this atomic
+    /// with respect to every other claim on the same store. `claim` is
+    /// called on each message after its `visible_from` has been set and
+    /// must assign its receipt handle.
+    pub fn claim<T>(
+        &mut self,
+        max: usize,
+        visible_until: DateTime<Utc>,
+        claim: impl FnMut(&mut Message) -> T,
+    ) -> Vec<T> {
+        self.claim_from(max, visible_until, BTreeSet::pop_first, claim)
+    }
+
+    /// Like [`claim`](Self::claim), but takes ready messages in random order
+    /// instead of send order, given `pick(n)` returning an index below `n`.
+    pub fn claim_shuffled<T>(
+        &mut self,
+        max: usize,
+        visible_until: DateTime<Utc>,
+        mut pick: impl FnMut(usize) -> usize,
+        claim: impl FnMut(&mut Message) -> T,
+    ) -> Vec<T> {
+        let next = |ready: &mut BTreeSet<u64>| {
+            if ready.is_empty() {
+                return None;
+            }
+            let seq = *ready.iter().nth(pick(ready.len()))?;
+            ready.remove(&seq);
+            Some(seq)
+        };
+        self.claim_from(max, visible_until, next, claim)
+    }
+
+    /// Like [`claim`](Self::claim), for FIFO queues: passes over messages
+    /// whose group is locked, so that a group is received in order, one
+    /// batch at a time, while other groups keep flowing. A batch may hold
+    /// several messages of a group that was unlocked when the call began.
+    /// Finding them scans the ready messages of locked groups.
+    pub fn claim_unlocked_groups<T>(
+        &mut self,
+        max: usize,
+        visible_until: DateTime<Utc>,
+        claim: impl FnMut(&mut Message) -> T,
+    ) -> Vec<T> {
+        let unlocked: Vec<u64> = self
+            .ready
+            .iter()
+            .copied()
+            .filter(|seq| match &self.messages[seq].message_group_id {
+                Some(group) => !self.locked_groups.contains_key(group),
+                None => true,
+            })
+            .take(max)
+            .collect();
+        let mut unlocked = unlocked.into_iter();
+        let next = |ready: &mut BTreeSet<u64>| {
+            let seq = unlocked.next()?;
+            ready.remove(&seq);
+            Some(seq)
+        };
+        self.claim_from(max, visible_until, next, claim)
+    }
+
+    /// Like [`claim`](Self::claim), but takes ready messages in order of
+    /// `priority`, highest first, and in send order within a priority.
+    /// Finding them scans the ready messages.
+    pub fn claim_by_priority<T>(
+        &mut self,
+        max: usize,
+        visible_until: DateTime<Utc>,
+        priority: impl Fn(&Message) -> f64,
+        claim: impl FnMut(&mut Message) -> T,
+    ) -> Vec<T> {
+        let mut ready: Vec<(f64, u64)> = self
+            .ready
+            .iter()
+            .map(|seq| (priority(&self.messages[seq]), *seq))
+            .collect();
+        ready.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+        let mut ready = ready.into_iter().take(max).map(|(_, seq)| seq);
+        let next = |ready_set: &mut BTreeSet<u64>| {
+            let seq = ready.next()?;
+            ready_set.remove(&seq);
+            Some(seq)
+        };
+        self.claim_from(max, visible_until, next, claim)
+    }
+
+    fn claim_from<T>(
+        &mut self,
+        max: usize,
+        visible_until: DateTime<Utc>,
+        mut next: impl FnMut(&mut BTreeSet<u64>) -> Option<u64>,
+        mut claim: impl FnMut(&mut Message) -> T,
+    ) -> Vec<T> {
+        let mut claimed = Vec::new();
+        while claimed.len() < max {
+            let Some(seq) = next(&mut self.ready) else {
+                break;
+            };
+            self.mark_changed(seq);
+            let message = self.messages.get_mut(&seq).expect("indexed message exists");
+            message.visible_from = visible_until;
+            claimed.push(claim(message));
+            let receipt_handle = message
+                .receipt_handle
+                .clone()
+                .expect("claim must assign a receipt handle");
+            self.receipt_handles.insert(receipt_handle, seq);
+            self.in_flight.insert((visible_until, seq));
+            lock_group(&mut self.locked_groups, message);
+        }
+        claimed
+    }
+
+    /// A uniformly chosen in-flight message, given `pick(n)` returning an
+    /// index below `n`. This is a scan, meant only for chaos testing.
+    pub fn random_in_flight(&self, pick: impl FnOnce(usize) -> usize) -> Option<u64> {
+        if self.in_flight.is_empty() {
+            return None;
+        }
+        let index = pick(self.in_flight.len());
+        self.in_flight.iter().nth(index).map(|(_, seq)| *seq)
+    }
+
+    /// Delivers another copy of the in-flight message `seq` under
+    /// `receipt_handle`, leaving the original's handle and visibility
+    /// deadline untouched. Either handle deletes the message.
+    pub fn duplicate(&mut self, seq: u64, receipt_handle: String) -> Option<Message> {
+        let message = self.messages.get(&seq)?;
+        message.receipt_handle.as_ref()?;
+        let mut copy = message.clone();
+        copy.receipt_handle = Some(receipt_handle.clone());
+        self.receipt_handles.insert(receipt_handle.clone(), seq);
+        self.mark_changed(seq);
+        self.duplicate_handles
+            .entry(seq)
+            .or_default()
+            .push(receipt_handle);
+        Some(copy)
+    }
+
+    /// The receipt handles of duplicate deliveries of message `seq`.
+    pub fn duplicate_handles(&self, seq: u64) -> &[String] {
+        self.duplicate_handles.get(&seq).map_or(&[], Vec::as_slice)
+    }
+
+    fn drop_duplicate_handles(&mut self, seq: u64) {
+        if let Some(handles) = self.duplicate_handles.remove(&seq) {
+            for handle in handles {
+                self.receipt_handles.remove(&handle);
+                self.mark_released(&handle);
+            }
+        }
+    }
+
+    /// Moves the visibility deadline of an in-flight message. A deadline at
+    /// or before now makes the message visible again on the next release.
+    pub fn set_visible_from(&mut self, seq: u64, visible_from: DateTime<Utc>) {
+        self.mark_changed(seq);
+        let message = self.messages.get_mut(&seq).expect("indexed message exists");
+        debug_assert!(message.receipt_handle.is_some(), "message is in flight");
+        self.in_flight.remove(&(message.visible_from, seq));
+        message.visible_from = visible_from;
+        self.in_flight.insert((visible_from, seq));
+    }
+
+    /// Adds the entry `entry` builds to the history of message `seq`, if it
+    /// keeps one.
+    pub fn record(&mut self, seq: u64, entry: impl FnOnce() -> HistoryEntry) {
+        if let Some(message) = self.messages.get_mut(&seq)
+            && let Some(history) = &mut message.history
+        {
+            history.push(entry());
+            self.mark_changed(seq);
+        }
+    }
+
+    /// Starts recording what changes, for a store that writes only that
+    /// back. Every current message counts as changed.
+    pub fn track_changes(&mut self) {
+        self.changes = Some(MessageChanges {
+            messages: self.messages.keys().copied().collect(),
+            ..Default::default()
+        });
+    }
+
+    /// What changed since tracking started or this was last called. Empty
+    /// unless tracking.
+    pub fn take_changes(&mut self) -> MessageChanges {
+        self.changes.as_mut().map(mem::take).unwrap_or_default()
+    }
+
+    fn mark_changed(&mut self, seq: u64) {
+        if let Some(changes) = &mut self.changes {
+            changes.messages.insert(seq);
+        }
+    }
+
+    fn mark_released(&mut self, receipt_handle: &str) {
+        if let Some(changes) = &mut self.changes {
+            changes.released_handles.push(receipt_handle.to_string());
+        }
+    }
+
+    /// Whether any message is (or, at `now`, has become) visible.
+    pub fn has_visible(&self, now: DateTime<Utc>) -> bool {
+        !self.ready.is_empty()
+            || self.delayed.first().is_some_and(|(t, _)| *t <= now)
+            || self.in_flight.first().is_some_and(|(t, _)| *t <= now)
+    }
+
+    /// Counts by state as of `now`, treating delays and visibility timeouts
+    /// that have lapsed but not yet been released as visible.
+    pub fn counts(&self, now: DateTime<Utc>) -> MessageCounts {
+        let delayed_due = self.delayed.iter().take_while(|(t, _)| *t <= now).count();
+        let in_flight_due = self.in_flight.iter().take_while(|(t, _)| *t <= now).count();
+        MessageCounts {
+            visible: self.ready.len() + delayed_due + in_flight_due,
+            delayed: self.delayed.len() - delayed_due,
+            in_flight: self.in_flight.len() - in_flight_due,
+        }
+    }
+}
+
+fn lock_group(locked_groups: &mut HashMap<String, usize>, message: &Message) {
+    if let Some(group) = &message.message_group_id {
+        *locked_groups.entry(group.clone()).or_default() += 1;
+    }
+}
+
+fn unlock_group(locked_groups: &mut HashMap<String, usize>, message: &Message) {
+    if let Some(group) = &message.message_group_id
+        && let Some(count) = locked_groups.get_mut(group)
+    {
+        *count -= 1;
+        if *count == 0 {
+            locked_groups.remove(group);
+        }
+    }
+}
+
+impl Serialize for MessageStore {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
@@ -0,0 +1,232 @@
+use chrono::{DateTime, Utc};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// Histogram bucket upper bounds in seconds, used unless configured.
+pub const DEFAULT_BUCKETS: &[f64] = &[0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 900.0, 3600.0];
+
+/// Checks that bucket bounds are finite, positive and strictly increasing.
+pub fn validate_buckets(bounds: &[f64]) -> Result<(), String> {
+    if bounds.is_empty() {
+        return Err("at least one bucket is required".to_string());
+    }
+    if bounds.iter().any(|b| !b.is_finite() || *b <= 0.0) {
+        return Err("buckets must be positive numbers of seconds".to_string());
+    }
+    if bounds.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err("buckets must be in increasing order".to_string());
+    }
+    Ok(())
+}
+
+/// Seconds from `from` to `to`, never negative.
+pub fn seconds_between(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    ((to - from).num_milliseconds() as f64 / 1000.0).max(0.0)
+}
+
+/// A Prometheus-style histogram of durations in seconds. Serializes with
+/// cumulative bucket counts, the last bucket (`"+Inf"`) counting everything.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    bounds: Arc<[f64]>,
+    /// One count per bound plus one for values above the last bound; not
+    /// cumulative.
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    pub fn new(bounds: Arc<[f64]>) -> Self {
+        Self {
+            counts: vec![0; bounds.len() + 1],
+            bounds,
+            sum: 0.0,
+        }
+    }
+
+    pub fn observe(&mut self, seconds: f64) {
+        let bucket = self.bounds.partition_point(|bound| *bound < seconds);
+        self.counts[bucket] += 1;
+        self.sum += seconds;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Appends the histogram's `_bucket`, `_sum` and `_count` samples in the
+    /// Prometheus text format.
+    fn write_prometheus(&self, out: &mut String, name: &str, queue: &str) {
+        let mut cumulative = 0;
+        for (i, count) in self.counts.iter().enumerate() {
+            cumulative += count;
+            let le = self
+                .bounds
+                .get(i)
+                .map_or_else(|| "+Inf".to_string(), f64::to_string);
+            let _ = writeln!(
+                out,
+                "{}_bucket{{queue=\"{}\",le=\"{}\"}} {}",
+                name, queue, le, cumulative
+            );
+        }
+        let _ = writeln!(out, "{}_sum{{queue=\"{}\"}} {}", name, queue, self.sum);
+        let _ = writeln!(out, "{}_count{{queue=\"{}\"}} {}", name, queue, cumulative);
+    }
+}
+
+#[derive(serde::Serialize)]
+struct Bucket {
+    le: String,
+    count: u64,
+}
+
+impl Serialize for Histogram {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut cumulative = 0;
+        let buckets: Vec<Bucket> = self
+            .counts
+            .iter()
+            .enumerate()
+            .map(|(i, count)| {
+                cumulative += count;
+                Bucket {
+                    le: self
+                        .bounds
+                        .get(i)
+                        .map_or_else(|| "+Inf".to_string(), f64::to_string),
+                    count: cumulative,
+                }
+            })
+            .collect();
+        let mut state = serializer.serialize_struct("Histogram", 3)?;
+        state.serialize_field("count", &cumulative)?;
+        state.serialize_field("sum", &self.sum)?;
+        state.serialize_field("buckets", &buckets)?;
+        state.end()
+    }
+}
+
+/// Per-queue latency metrics, reset along with the queue's counters.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct QueueLatency {
+    /// Age of each message handed out by `ReceiveMessage`.
+    pub receive_age: Histogram,
+    /// Time from sending a message to its first receive.
+    pub send_to_first_receive: Histogram,
+    /// Time from a message's first receive to its deletion.
+    pub first_receive_to_delete: Histogram,
+    /// Age in seconds of the oldest visible message as of the last sweep;
+    /// zero if there was none.
+    pub oldest_visible_message_age: f64,
+}
+
+impl QueueLatency {
+    pub fn new(bounds: Arc<[f64]>) -> Self {
+        Self {
+            receive_age: Histogram::new(bounds.clone()),
+            send_to_first_receive: Histogram::new(bounds.clone()),
+            first_receive_to_delete: Histogram::new(bounds),
+            oldest_visible_message_age: 0.0,
+        }
+    }
+}
+
+impl Default for QueueLatency {
+    fn default() -> Self {
+        Self::new(DEFAULT_BUCKETS.into())
+    }
+}
+
+/// Renders per-queue latency metrics in the Prometheus text exposition
+/// format, one series per queue, in the order given.
+pub fn prometheus<'a>(queues: impl IntoIterator<Item = (&'a str, &'a QueueLatency)>) -> String {
+    let queues: Vec<(String, &QueueLatency)> = queues
+        .into_iter()
+        .map(|(name, latency)| (escape_label(name), latency))
+        .collect();
+    let mut out = String::new();
+
+    let gauge = "local_sqs_oldest_message_age_seconds";
+    let _ = writeln!(
+        out,
+        "# HELP {} Age of the oldest visible message as of the last sweep.",
+        gauge
+    );
+    let _ = writeln!(out, "# TYPE {} gauge", gauge);
+    for (queue, latency) in &queues {
+        let age = latency.oldest_visible_message_age;
+        let _ = writeln!(out, "{}{{queue=\"{}\"}} {}", gauge, queue, age);
+    }
+
+    write_histograms(
+        &mut out,
+        "local_sqs_receive_age_seconds",
+        "Age of messages when received.",
+        &queues,
+        |l| &l.receive_age,
+    );
+    write_histograms(
+        &mut out,
+        "local_sqs_send_to_first_receive_seconds",
+        "Time from sending a message to its first receive.",
+        &queues,
+        |l| &l.send_to_first_receive,
+    );
+    write_histograms(
+        &mut out,
+        "local_sqs_first_receive_to_delete_seconds",
+        "Time from a message's first receive to its deletion.",
+        &queues,
+        |l| &l.first_receive_to_delete,
+    );
+    out
+}
+
+/// Renders a server-wide counter in the Prometheus text exposition format.
+pub fn prometheus_counter(name: &str, help: &str, value: u64) -> String {
+    format!("# HELP {0} {1}\n# TYPE {0} counter\n{0} {2}\n", name, help, value)
+}
+
+/// Renders a server-wide gauge in the Prometheus text exposition format.
+pub fn prometheus_gauge(name: &str, help: &str, value: u64) -> String {
+    format!("# HELP {0} {1}\n# TYPE {0} gauge\n{0} {2}\n", name, help, value)
+}
+
+/// Renders a metric of type `kind` with one series per `(label value,
+/// value)` sample, in the order given.
+pub fn prometheus_labelled<'a>(
+    name: &str,
+    help: &str,
+    kind: &str,
+    label: &str,
+    samples: impl IntoIterator<Item = (&'a str, u64)>,
+) -> String {
+    let mut out = format!("# HELP {0} {1}\n# TYPE {0} {2}\n", name, help, kind);
+    for (value, sample) in samples {
+        let _ = writeln!(out, "{}{{{}=\"{}\"}} {}", name, label, escape_label(value), sample);
+    }
+    out
+}
+
+fn write_histograms(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    queues: &[(String, &QueueLatency)],
+    histogram: impl Fn(&QueueLatency) -> &Histogram,
+) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} histogram", name);
+    for (queue, latency) in queues {
+        histogram(latency).write_prometheus(out, name, queue);
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
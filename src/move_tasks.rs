@@ -0,0 +1,410 @@
+use crate::error::SqsError;
+use crate::serde_helpers;
+use crate::state::{AppState, DeadLetterInfo, Message, MoveReason};
+use crate::store::Selection;
+use axum::extract::State;
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+/// The highest `MaxNumberOfMessagesPerSecond` AWS accepts.
+pub const MAX_MESSAGES_PER_SECOND: u32 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MoveTaskStatus {
+    Running,
+    Completed,
+    Cancelling,
+    Cancelled,
+    Failed,
+}
+
+/// A running or finished `StartMessageMoveTask`.
+///
+/// Progress is updated by the mover as it goes, so it can be read at any
+/// time without stopping the move.
+#[derive(Debug)]
+pub struct MoveTask {
+    pub handle: String,
+    pub source_arn: String,
+    /// Where messages go. `None` returns each message to the queue it was
+    /// dead-lettered from.
+    pub destination_arn: Option<String>,
+    pub max_messages_per_second: Option<u32>,
+    pub started_timestamp: i64,
+    /// The source queue's depth when the task started; the task stops after
+    /// moving this many messages.
+    pub to_move: u64,
+    pub moved: AtomicU64,
+    state: Mutex<(MoveTaskStatus, Option<String>)>,
+    cancel: CancellationToken,
+}
+
+impl MoveTask {
+    pub fn status(&self) -> MoveTaskStatus {
+        self.state.lock().unwrap().0
+    }
+
+    pub fn failure_reason(&self) -> Option<String> {
+        self.state.lock().unwrap().1.clone()
+    }
+
+    fn finish(&self, status: MoveTaskStatus, failure_reason: Option<String>) {
+        *self.state.lock().unwrap() = (status, failure_reason);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct StartMessageMoveTaskRequest {
+    pub source_arn: String,
+    pub destination_arn: Option<String>,
+    #[serde(default, deserialize_with = "serde_helpers::deserialize_optional_number")]
+    pub max_number_of_messages_per_second: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct StartMessageMoveTaskResponse {
+    pub task_handle: String,
+}
+
+pub async fn start_message_move_task(
+    State(state): State<AppState>,
+    Json(request): Json<StartMessageMoveTaskRequest>,
+) -> Result<StartMessageMoveTaskResponse, SqsError> {
+    if let Some(rate) = request.max_number_of_messages_per_second
+        && !(1..=MAX_MESSAGES_PER_SECOND).contains(&rate)
+    {
+        state.validation.violation(SqsError::InvalidParameterValue(format!(
+            "Value {} for parameter MaxNumberOfMessagesPerSecond is invalid. Reason: Must be between 1 and {}.",
+            rate, MAX_MESSAGES_PER_SECOND
+        )))?;
+    }
+
+    let source_url = state.queue_url_by_arn(&request.source_arn).ok_or_else(|| {
+        SqsError::ResourceNotFound(
+            "The resource that you specified for the SourceArn parameter doesn't exist.".to_string(),
+        )
+    })?;
+    let is_dead_letter_queue = state.store.any(|q| {
+        q.redrive_policy
+            .as_ref()
+            .is_some_and(|rp| rp.dead_letter_target_arn == request.source_arn)
+    });
+    if !is_dead_letter_queue {
+        return Err(SqsError::InvalidParameterValue(
+            "Source queue must be configured as a Dead Letter Queue.".to_string(),
+        ));
+    }
+    if let Some(destination_arn) = &request.destination_arn
+        && state.queue_url_by_arn(destination_arn).is_none()
+    {
+        return Err(SqsError::ResourceNotFound(
+            "The resource that you specified for the DestinationArn parameter doesn't exist."
+                .to_string(),
+        ));
+    }
+
+    let task = {
+        let mut tasks = state.move_tasks.lock().unwrap();
+        let running = tasks.iter().any(|t| {
+            t.source_arn == request.source_arn
+                && matches!(t.status(), MoveTaskStatus::Running | MoveTaskStatus::Cancelling)
+        });
+        if running {
+            return Err(SqsError::InvalidParameterValue(
+                "There is already a task running. Only one active task is allowed for a source queue arn at a given time."
+                    .to_string(),
+            ));
+        }
+
+        let to_move = state
+            .store
+            .read(&source_url, |q| q.messages.len() as u64)
+            .unwrap_or(0);
+        let task = Arc::new(MoveTask {
+            handle: state.ids.uuid().to_string(),
+            source_arn: request.source_arn,
+            destination_arn: request.destination_arn,
+            max_messages_per_second: request.max_number_of_messages_per_second,
+            started_timestamp: state.clock.now().timestamp_millis(),
+            to_move,
+            moved: AtomicU64::new(0),
+            state: Mutex::new((MoveTaskStatus::Running, None)),
+            cancel: CancellationToken::new(),
+        });
+        tasks.push(task.clone());
+        task
+    };
+
+    let response = StartMessageMoveTaskResponse {
+        task_handle: task.handle.clone(),
+    };
+    tokio::spawn(run(state, task));
+    Ok(response)
+}
+
+/// Moves messages one at a time until `to_move` have been moved, the source
+/// runs dry, or the task is cancelled. With a rate limit, each message is
+/// followed by a pause of `1 / rate` seconds; a rate of 0, let through in
+/// lenient validation mode, means no limit.
+async fn run(state: AppState, task: Arc<MoveTask>) {
+    let pause = task
+        .max_messages_per_second
+        .filter(|rate| *rate > 0)
+        .map(|rate| Duration::from_secs_f64(1.0 / rate as f64));
+
+    let (status, failure_reason) = loop {
+        if task.cancel.is_cancelled() {
+            break (MoveTaskStatus::Cancelled, None);
+        }
+        if task.moved.load(Ordering::Relaxed) >= task.to_move {
+            break (MoveTaskStatus::Completed, None);
+        }
+        let destination_arn = task.destination_arn.as_deref();
+        match move_one(&state, &task.source_arn, destination_arn, MoveReason::MoveTask).await {
+            Ok(Some(_)) => {
+                task.moved.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(None) => break (MoveTaskStatus::Completed, None),
+            Err(reason) => break (MoveTaskStatus::Failed, Some(reason)),
+        }
+
+        let wait = pause.unwrap_or(Duration::ZERO);
+        tokio::select! {
+            _ = state.shutdown.cancelled() => return,
+            _ = task.cancel.cancelled() => {}
+            _ = tokio::time::sleep(wait) => {}
+        }
+    };
+
+    debug!(
+        task = %task.handle,
+        ?status,
+        moved = task.moved.load(Ordering::Relaxed),
+        "message move task finished"
+    );
+    task.finish(status, failure_reason);
+}
+
+/// Moves the oldest visible message from `source_arn` to `destination_arn`,
+/// or back to the queue it was dead-lettered from if that is `None`.
+/// Returns the name of the queue the message was moved to, `None` if there
+/// was nothing to move, or the reason the move failed.
+pub(crate) async fn move_one(
+    state: &AppState,
+    source_arn: &str,
+    destination_arn: Option<&str>,
+    reason: MoveReason,
+) -> Result<Option<String>, String> {
+    let now = state.clock.now();
+    let source_url = state
+        .queue_url_by_arn(source_arn)
+        .ok_or_else(|| "Source queue was deleted.".to_string())?;
+
+    let message = state
+        .store
+        .update_selected(&source_url, |_| Selection::visible(1), |source| {
+            let seq = source.messages.first_ready()?;
+            Some(source.remove_message(seq).expect("ready message exists"))
+        })
+        .await
+        .map_err(|_| "Source queue was deleted.".to_string())?;
+    let Some(message) = message else {
+        return Ok(None);
+    };
+
+    let destination_arn = destination_arn
+        .map(str::to_string)
+        .or_else(|| message.attributes.get("DeadLetterQueueSourceArn").cloned());
+    let destination_url = destination_arn
+        .as_deref()
+        .and_then(|arn| state.queue_url_by_arn(arn));
+
+    let mut message = Some(message);
+    let moved = match destination_url {
+        Some(url) => {
+            let moved = state.store.update_selected(&url, |_| Selection::none(), |destination| {
+                let message = message.take().expect("moved once");
+                let message = redriven(message, source_arn, reason, now);
+                state.webhooks.message_enqueued(destination, &message);
+                destination.push_message(message, now);
+                destination.notify.notify_waiters();
+                destination.name.clone()
+            });
+            moved.await.ok()
+        }
+        None => None,
+    };
+
+    if let Some(name) = moved {
+        return Ok(Some(name));
+    }
+    // Put the message back so a failed move loses nothing.
+    if let Some(message) = message {
+        let _ = state
+            .store
+            .update_selected(&source_url, |_| Selection::none(), |source| {
+                source.push_message(message, now)
+            })
+            .await;
+    }
+    Err(match destination_arn {
+        Some(arn) => format!("Destination queue {} does not exist.", arn),
+        None => "Message has no source queue to return to.".to_string(),
+    })
+}
+
+/// A dead-lettered message as it re-enters a queue, moved from `source_arn`
+/// for `reason`: visible, unreceived and without its dead-letter source.
+fn redriven(
+    mut message: Message,
+    source_arn: &str,
+    reason: MoveReason,
+    now: DateTime<Utc>,
+) -> Message {
+    message.dead_letter = Some(DeadLetterInfo {
+        source_arn: source_arn.to_string(),
+        moved_at: now,
+        receive_count: message.receive_count,
+        reason,
+    });
+    message.receipt_handle = None;
+    message.claimed_by = None;
+    message.visible_from = now;
+    message.receive_count = 0;
+    message.first_received = None;
+    message.attributes.remove("DeadLetterQueueSourceArn");
+    message.attributes.remove("ApproximateFirstReceiveTimestamp");
+    message
+        .attributes
+        .insert("ApproximateReceiveCount".to_string(), "0".to_string());
+    message
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListMessageMoveTasksRequest {
+    pub source_arn: String,
+    #[serde(default, deserialize_with = "serde_helpers::deserialize_optional_number")]
+    pub max_results: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListMessageMoveTasksResponse {
+    pub results: Vec<MessageMoveTaskEntry>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MessageMoveTaskEntry {
+    /// Only reported while the task is running.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_handle: Option<String>,
+    pub status: MoveTaskStatus,
+    pub source_arn: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destination_arn: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_number_of_messages_per_second: Option<u32>,
+    pub approximate_number_of_messages_moved: u64,
+    pub approximate_number_of_messages_to_move: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_reason: Option<String>,
+    pub started_timestamp: i64,
+}
+
+impl From<&MoveTask> for MessageMoveTaskEntry {
+    fn from(task: &MoveTask) -> Self {
+        let status = task.status();
+        Self {
+            task_handle: (status == MoveTaskStatus::Running).then(|| task.handle.clone()),
+            status,
+            source_arn: task.source_arn.clone(),
+            destination_arn: task.destination_arn.clone(),
+            max_number_of_messages_per_second: task.max_messages_per_second,
+            approximate_number_of_messages_moved: task.moved.load(Ordering::Relaxed),
+            approximate_number_of_messages_to_move: task.to_move,
+            failure_reason: task.failure_reason(),
+            started_timestamp: task.started_timestamp,
+        }
+    }
+}
+
+/// Lists the tasks for a source queue, most recent first.
+pub async fn list_message_move_tasks(
+    State(state): State<AppState>,
+    Json(request): Json<ListMessageMoveTasksRequest>,
+) -> Result<ListMessageMoveTasksResponse, SqsError> {
+    let max_results = request.max_results.unwrap_or(1);
+    if !(1..=10).contains(&max_results) {
+        state.validation.violation(SqsError::InvalidParameterValue(format!(
+            "Value {} for parameter MaxResults is invalid. Reason: Must be between 1 and 10.",
+            max_results
+        )))?;
+    }
+    if state.queue_url_by_arn(&request.source_arn).is_none() {
+        return Err(SqsError::ResourceNotFound(
+            "The resource that you specified for the SourceArn parameter doesn't exist.".to_string(),
+        ));
+    }
+
+    let tasks = state.move_tasks.lock().unwrap();
+    let results = tasks
+        .iter()
+        .rev()
+        .filter(|t| t.source_arn == request.source_arn)
+        .take(max_results as usize)
+        .map(|t| MessageMoveTaskEntry::from(t.as_ref()))
+        .collect();
+    Ok(ListMessageMoveTasksResponse { results })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CancelMessageMoveTaskRequest {
+    pub task_handle: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CancelMessageMoveTaskResponse {
+    pub approximate_number_of_messages_moved: u64,
+}
+
+pub async fn cancel_message_move_task(
+    State(state): State<AppState>,
+    Json(request): Json<CancelMessageMoveTaskRequest>,
+) -> Result<CancelMessageMoveTaskResponse, SqsError> {
+    let task = state
+        .move_tasks
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|t| t.handle == request.task_handle)
+        .cloned()
+        .ok_or_else(|| SqsError::ResourceNotFound("Task does not exist.".to_string()))?;
+
+    {
+        let mut task_state = task.state.lock().unwrap();
+        if task_state.0 != MoveTaskStatus::Running {
+            return Err(SqsError::InvalidParameterValue(
+                "Only active tasks can be cancelled.".to_string(),
+            ));
+        }
+        task_state.0 = MoveTaskStatus::Cancelling;
+    }
+    task.cancel.cancel();
+
+    Ok(CancelMessageMoveTaskResponse {
+        approximate_number_of_messages_moved: task.moved.load(Ordering::Relaxed),
+    })
+}
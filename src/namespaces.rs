@@ -0,0 +1,114 @@
+//! Namespaces, for sharing one server between test workers that must not
+//! see each other's queues. A request with an `X-LocalSqs-Namespace` header
+//! works on queues named `{namespace}_{name}`: queue names it gives are
+//! prefixed on the way in, `QueueUrl`s it gives are resolved the same way,
+//! and `ListQueues` only lists the namespace's queues. The ARNs it gets back
+//! carry the prefixed name.
+//!
+//! The URLs it gets back name the namespace as a path segment of its own,
+//! `.../{account}/{namespace}/{name}`, a form no URL built from a queue name
+//! can take, since names can't contain `/`. Such a URL resolves to the same
+//! queue with the header or without it, while any other URL a namespaced
+//! request gives is taken to be built from a bare name and is prefixed.
+//!
+//! Namespaces may only contain alphanumeric characters and hyphens, so the
+//! first underscore of a prefixed name always ends the namespace.
+
+use crate::error::SqsError;
+use crate::urls;
+use std::future::Future;
+
+/// The header naming the namespace of a request.
+pub const NAMESPACE_HEADER: &str = "X-LocalSqs-Namespace";
+
+/// The longest namespace accepted, leaving room in the 80 characters a
+/// queue name may have for the name itself.
+const MAX_NAMESPACE_LENGTH: usize = 40;
+
+tokio::task_local! {
+    static NAMESPACE: String;
+}
+
+/// Runs `future` in `namespace`, if there is one.
+pub async fn scope<F: Future>(namespace: Option<String>, future: F) -> F::Output {
+    match namespace {
+        Some(namespace) => NAMESPACE.scope(namespace, future).await,
+        None => future.await,
+    }
+}
+
+/// The namespace of the SQS request being handled, if it has one.
+pub fn current() -> Option<String> {
+    NAMESPACE.try_with(Clone::clone).ok()
+}
+
+pub fn validate(namespace: &str) -> Result<(), SqsError> {
+    if namespace.is_empty()
+        || namespace.len() > MAX_NAMESPACE_LENGTH
+        || !namespace.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Err(SqsError::InvalidParameterValue(format!(
+            "Invalid namespace {:?}: must be 1 to {} alphanumeric characters or hyphens.",
+            namespace, MAX_NAMESPACE_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+/// The prefix of the names of queues in `namespace`.
+pub fn prefix(namespace: &str) -> String {
+    format!("{}_", namespace)
+}
+
+/// `name` in the current namespace: prefixed if the request has one, and
+/// as it is otherwise.
+pub fn qualify(name: &str) -> String {
+    match current() {
+        Some(namespace) => format!("{}{}", prefix(&namespace), name),
+        None => name.to_string(),
+    }
+}
+
+/// The normalized queue URL `url` in the current namespace. A URL handed
+/// out in a namespace names the namespace's queue whatever the request's
+/// namespace; any other URL has the request's namespace, if it has one,
+/// prefixed to its queue name.
+pub fn qualify_url(url: String) -> String {
+    if let Some((queues, namespace, name)) = split_issued_url(&url) {
+        return format!("{}/{}{}", queues, prefix(namespace), name);
+    }
+    let Some(namespace) = current() else {
+        return url;
+    };
+    match url.rsplit_once('/') {
+        Some((base, name)) => format!("{}/{}{}", base, prefix(&namespace), name),
+        None => url,
+    }
+}
+
+/// The URL of the queue stored under `url` as handed out in the current
+/// namespace: `{queues}/{namespace}/{name}` for the namespace's queue
+/// `name`, and `url` as it is otherwise.
+pub fn issued_url(url: String) -> String {
+    let (Some(namespace), Some(queues)) = (current(), urls::queues_url()) else {
+        return url;
+    };
+    let name = url
+        .strip_prefix(&queues)
+        .and_then(|rest| rest.strip_prefix('/'))
+        .and_then(|name| name.strip_prefix(&prefix(&namespace)));
+    match name {
+        Some(name) => format!("{}/{}/{}", queues, namespace, name),
+        None => url,
+    }
+}
+
+/// The server's queue URL prefix, namespace and queue name of `url`, if it
+/// has the form of a URL handed out in a namespace.
+fn split_issued_url(url: &str) -> Option<(&str, &str, &str)> {
+    let queues = urls::queues_url()?;
+    let rest = url.strip_prefix(queues.as_str())?.strip_prefix('/')?;
+    let (namespace, name) = rest.split_once('/')?;
+    let issued = validate(namespace).is_ok() && !name.is_empty() && !name.contains('/');
+    issued.then(|| (&url[..queues.len()], namespace, name))
+}
@@ -1,25 +1,48 @@
+use crate::attributes::{self, AttributeKind};
+use crate::deduplication::{self, Accepted, DeduplicationKey};
+use crate::dispatch::EmptyResponse;
 use crate::error::SqsError;
-use crate::state::{AppState, Queue};
+use crate::events::{self, EventKind, EventLog};
+use crate::history::{DeletedHistories, HistoryEntry, HistoryKind, MessageHistory};
+use crate::message_attributes;
+use crate::messages::MessageCounts;
+use crate::metrics::{self, QueueLatency};
+use crate::namespaces;
+use crate::serde_helpers;
+use crate::state::{
+    md5_hex, message_size_bytes, AppState, Message, Queue, RedriveAllowPolicy,
+    RedrivePermission, RedrivePolicy,
+};
+use crate::store::Selection;
+use crate::telemetry;
 use axum::extract::State;
 use axum::Json;
-use chrono::Utc;
-use crate::state::MessageAttributeValue;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::sync::Notify;
 use tokio::time::Duration;
-use uuid::Uuid;
+use tracing::{info, trace};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct CreateQueueRequest {
+    #[serde(deserialize_with = "serde_helpers::deserialize_queue_name")]
     pub queue_name: String,
     #[serde(default)]
     pub attributes: HashMap<String, String>,
-    #[serde(default)]
+    // The one lowercase member of the SQS JSON protocol.
+    #[serde(rename = "tags", default)]
     pub tags: HashMap<String, String>,
+    /// The `X-Local-Sqs-Template` header of the HTTP request, naming the
+    /// queue template to start from.
+    #[serde(skip)]
+    pub template: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct CreateQueueResponse {
     pub queue_url: String,
@@ -30,72 +53,250 @@ pub async fn create_queue(
     Json(request): Json<CreateQueueRequest>,
 ) -> Result<CreateQueueResponse, SqsError> {
     let queue_name = request.queue_name;
-    let queue_url = format!("http://{}:{}/{}", state.host, state.port, &queue_name);
-
-    if let Some(existing_queue) = state.queues.get(&queue_url) {
-        if existing_queue.attributes != request.attributes {
-            return Err(SqsError::QueueNameExists);
-        } else {
-            return Ok(CreateQueueResponse {
-                queue_url: existing_queue.url.clone(),
-            });
-        }
+    let queue_url = state.queue_url(&queue_name);
+
+    state.validation.check(validate_queue_name(&queue_name))?;
+    state
+        .validation
+        .check(validate_tags(&HashMap::new(), &request.tags))?;
+    let requests_fifo = request
+        .attributes
+        .get("FifoQueue")
+        .is_some_and(|v| v == "true");
+    for (name, value) in &request.attributes {
+        state.validation.check(attributes::validate_for_queue(
+            name,
+            value,
+            requests_fifo,
+            state.max_message_size_limit,
+        ))?;
     }
+    state
+        .validation
+        .check(attributes::validate_combination(&request.attributes))?;
 
     let mut attributes = request.attributes;
-    attributes
-        .entry("VisibilityTimeout".to_string())
-        .or_insert("30".to_string());
-    attributes
-        .entry("DelaySeconds".to_string())
-        .or_insert("0".to_string());
-    attributes
-        .entry("MaximumMessageSize".to_string())
-        .or_insert("262144".to_string());
-    attributes
-        .entry("MessageRetentionPeriod".to_string())
-        .or_insert("345600".to_string());
-    attributes
-        .entry("ReceiveMessageWaitTimeSeconds".to_string())
-        .or_insert("0".to_string());
-
-    use crate::state::RedrivePolicy;
+    attributes::apply_server_defaults(&mut attributes, &state.default_queue_attributes);
+    attributes::apply_defaults(&mut attributes);
 
-    let redrive_policy = match attributes.get("RedrivePolicy") {
-        Some(policy_str) => match serde_json::from_str::<RedrivePolicy>(policy_str) {
-            Ok(policy) => Some(policy),
-            Err(e) => {
-                return Err(SqsError::InvalidParameterValue(format!(
-                    "Invalid value for RedrivePolicy: {}",
-                    e
-                )))
-            }
-        },
+    let redrive_policy = match attributes.remove("RedrivePolicy") {
+        Some(policy) => parse_redrive_policy(&state, &policy, requests_fifo)?,
+        None => None,
+    };
+    let redrive_allow_policy = match attributes.remove("RedriveAllowPolicy") {
+        Some(policy) => parse_redrive_allow_policy(&state, &policy)?,
         None => None,
     };
 
-    let now = Utc::now().timestamp();
-    let new_queue = Queue {
+    let now = state.clock.now();
+    let mut new_queue = Queue {
+        arn: state.queue_arn(&queue_name),
         name: queue_name,
         url: queue_url.clone(),
         messages: Default::default(),
-        attributes,
-        created_timestamp: now,
-        last_modified_timestamp: now,
-        redrive_policy,
+        attributes: attributes.clone(),
+        tags: request.tags.clone(),
+        created_timestamp: now.timestamp(),
+        last_modified_timestamp: now.timestamp(),
+        redrive_policy: redrive_policy.clone(),
+        redrive_allow_policy: redrive_allow_policy.clone(),
+        stats: Default::default(),
+        latency: QueueLatency::new(state.histogram_buckets.clone()),
+        stored_bytes: 0,
+        total_bytes: state.total_bytes.clone(),
+        notify: Default::default(),
+        long_polls: Default::default(),
+        purge_generation: 0,
+        purged_until: None,
+        deduplication: Default::default(),
+        sequence_number: 0,
+        webhook: None,
+        paused: false,
+        chaos: None,
+        last_used: now,
+        events: EventLog::new(state.event_log_capacity),
+        deleted_histories: DeletedHistories::new(state.event_log_capacity),
     };
-
-    state.queues.insert(queue_url.clone(), new_queue);
+    let created = state.event(
+        EventKind::QueueCreated,
+        &new_queue.name,
+        json!({ "attributes": new_queue.configured_attributes(), "tags": new_queue.tags }),
+    );
+    new_queue.events.push(created.clone());
+
+    // Creating a queue that exists is fine as long as nothing differs.
+    let mut matches_existing = false;
+    let created_queue = state
+        .store
+        .create_queue(new_queue, &mut |existing| {
+            matches_existing = attributes_match(&existing.attributes, &attributes)
+                && existing.redrive_policy == redrive_policy
+                && existing.redrive_allow_policy == redrive_allow_policy
+                && existing.tags == request.tags;
+        })
+        .await?;
+    if created_queue {
+        state.record_server_event(created);
+    } else if !matches_existing {
+        return Err(SqsError::QueueNameExists);
+    }
+    let queue_url = namespaces::issued_url(queue_url);
     Ok(CreateQueueResponse { queue_url })
 }
 
-#[derive(Debug, Deserialize)]
+/// The longest queue name SQS accepts, including any `.fifo` suffix.
+const MAX_QUEUE_NAME_LENGTH: usize = 80;
+
+/// Checks a queue name: 1 to 80 alphanumeric characters, hyphens and
+/// underscores, optionally followed by `.fifo`.
+fn validate_queue_name(name: &str) -> Result<(), SqsError> {
+    let base = name.strip_suffix(".fifo").unwrap_or(name);
+    let valid = !base.is_empty()
+        && name.len() <= MAX_QUEUE_NAME_LENGTH
+        && base
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(SqsError::InvalidParameterValue(format!(
+            "Invalid queue name {:?}: can only include alphanumeric characters, hyphens, or underscores. 1 to {} in length.",
+            name, MAX_QUEUE_NAME_LENGTH
+        )))
+    }
+}
+
+/// The range AWS accepts for a redrive policy's `maxReceiveCount`.
+const MAX_RECEIVE_COUNT: std::ops::RangeInclusive<u32> = 1..=1000;
+
+/// Parses a `RedrivePolicy` attribute value for a queue being created or
+/// changed, `None` for the empty value that clears it. The dead-letter queue
+/// must exist and be of the same type (standard or FIFO) as the source.
+///
+/// Looks queues up by ARN, so the caller must not hold a queue lock.
+fn parse_redrive_policy(
+    state: &AppState,
+    value: &str,
+    source_is_fifo: bool,
+) -> Result<Option<RedrivePolicy>, SqsError> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    let invalid = |reason: String| {
+        SqsError::InvalidParameterValue(format!(
+            "Value {} for parameter RedrivePolicy is invalid. Reason: {}",
+            value, reason
+        ))
+    };
+    let policy: RedrivePolicy = serde_json::from_str(value)
+        .map_err(|e| invalid(format!("Redrive policy is not a valid JSON map: {}.", e)))?;
+
+    if !MAX_RECEIVE_COUNT.contains(&policy.max_receive_count) {
+        state.validation.violation(invalid(format!(
+            "Invalid value for maxReceiveCount: {}, valid values are from {} to {} both inclusive.",
+            policy.max_receive_count,
+            MAX_RECEIVE_COUNT.start(),
+            MAX_RECEIVE_COUNT.end()
+        )))?;
+    }
+    let target_is_fifo = state
+        .queue_url_by_arn(&policy.dead_letter_target_arn)
+        .and_then(|url| state.store.read(&url, |q| q.is_fifo()).ok());
+    match target_is_fifo {
+        None => state
+            .validation
+            .violation(invalid("Dead letter target does not exist.".to_string()))?,
+        Some(target_is_fifo) if target_is_fifo != source_is_fifo => {
+            state.validation.violation(invalid(
+                "Dead-letter queue must be same type of queue as the source.".to_string(),
+            ))?
+        }
+        Some(_) => {}
+    }
+    Ok(Some(policy))
+}
+
+/// The most source queues a `byQueue` redrive allow policy may list.
+const MAX_SOURCE_QUEUE_ARNS: usize = 10;
+
+/// Parses a `RedriveAllowPolicy` attribute value, `None` for the empty value
+/// that clears it. Source queues are listed with, and only with, `byQueue`.
+fn parse_redrive_allow_policy(
+    state: &AppState,
+    value: &str,
+) -> Result<Option<RedriveAllowPolicy>, SqsError> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    let invalid = |reason: &str| {
+        SqsError::InvalidParameterValue(format!(
+            "Value {} for parameter RedriveAllowPolicy is invalid. Reason: {}",
+            value, reason
+        ))
+    };
+    let policy: RedriveAllowPolicy = serde_json::from_str(value).map_err(|e| {
+        invalid(&format!("Redrive allow policy is not a valid JSON map: {}.", e))
+    })?;
+
+    let arns = policy.source_queue_arns.len();
+    match policy.redrive_permission {
+        RedrivePermission::ByQueue if arns == 0 => state.validation.violation(invalid(
+            "sourceQueueArns is required when redrivePermission is byQueue.",
+        ))?,
+        RedrivePermission::ByQueue if arns > MAX_SOURCE_QUEUE_ARNS => {
+            state.validation.violation(invalid(&format!(
+                "At most {} sourceQueueArns are allowed.",
+                MAX_SOURCE_QUEUE_ARNS
+            )))?
+        }
+        RedrivePermission::AllowAll | RedrivePermission::DenyAll if arns > 0 => {
+            state.validation.violation(invalid(
+                "sourceQueueArns can only be set when redrivePermission is byQueue.",
+            ))?
+        }
+        _ => {}
+    }
+    Ok(Some(policy))
+}
+
+/// Compares two effective attribute maps the way CreateQueue's idempotency
+/// check does: JSON-valued attributes are compared as parsed documents, so
+/// key order and whitespace don't matter.
+fn attributes_match(a: &HashMap<String, String>, b: &HashMap<String, String>) -> bool {
+    a.len() == b.len()
+        && a.iter().all(|(name, value)| {
+            let Some(other) = b.get(name) else {
+                return false;
+            };
+            if matches!(
+                attributes::spec(name).map(|spec| spec.kind),
+                Some(AttributeKind::Json)
+            ) {
+                match (
+                    serde_json::from_str::<serde_json::Value>(value),
+                    serde_json::from_str::<serde_json::Value>(other),
+                ) {
+                    (Ok(x), Ok(y)) => x == y,
+                    _ => value == other,
+                }
+            } else {
+                value == other
+            }
+        })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct GetQueueUrlRequest {
+    #[serde(deserialize_with = "serde_helpers::deserialize_queue_name")]
     pub queue_name: String,
+    /// Accepted and ignored: every queue belongs to the configured account.
+    #[serde(rename = "QueueOwnerAWSAccountId", default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_owner_aws_account_id: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct GetQueueUrlResponse {
     pub queue_url: String,
@@ -106,49 +307,99 @@ pub async fn get_queue_url(
     Json(request): Json<GetQueueUrlRequest>,
 ) -> Result<GetQueueUrlResponse, SqsError> {
     let queue_name = request.queue_name;
-    let queue_url = format!("http://{}:{}/{}", state.host, state.port, queue_name);
+    let queue_url = state.queue_url(&queue_name);
 
-    if state.queues.contains_key(&queue_url) {
+    if state.store.contains(&queue_url) {
+        let queue_url = namespaces::issued_url(queue_url);
         Ok(GetQueueUrlResponse { queue_url })
     } else {
         Err(SqsError::QueueDoesNotExist)
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ListQueuesRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub queue_name_prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "serde_helpers::deserialize_optional_number")]
+    pub max_results: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_token: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ListQueuesResponse {
+    #[serde(default)]
     pub queue_urls: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_token: Option<String>,
 }
 
+const LIST_QUEUES_LIMIT: usize = 1000;
+
+/// Lists queues sorted by name. A `NextToken` is only issued when the caller
+/// asked for `MaxResults`; it encodes the last name returned, so queues
+/// created or deleted between pages don't invalidate it.
 pub async fn list_queues(
     State(state): State<AppState>,
     Json(request): Json<ListQueuesRequest>,
-) -> ListQueuesResponse {
-    let mut queue_urls: Vec<String> = Vec::new();
-    for queue in state.queues.iter() {
-        if let Some(prefix) = &request.queue_name_prefix {
-            if queue.value().name.starts_with(prefix) {
-                queue_urls.push(queue.value().url.clone());
-            }
-        } else {
-            queue_urls.push(queue.value().url.clone());
-        }
+) -> Result<ListQueuesResponse, SqsError> {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+
+    if let Some(max) = request.max_results
+        && !(1..=LIST_QUEUES_LIMIT as u32).contains(&max)
+    {
+        state.validation.violation(SqsError::InvalidParameterValue(format!(
+            "Value {} for parameter MaxResults is invalid. Reason: must be between 1 and {}.",
+            max, LIST_QUEUES_LIMIT
+        )))?;
     }
 
+    let start_after = match &request.next_token {
+        Some(token) => Some(
+            URL_SAFE_NO_PAD
+                .decode(token)
+                .ok()
+                .and_then(|bytes| String::from_utf8(bytes).ok())
+                .ok_or_else(|| {
+                    SqsError::InvalidParameterValue("Invalid NextToken value.".to_string())
+                })?,
+        ),
+        None => None,
+    };
+
+    // Within a namespace, only its queues are listed.
+    let prefix = namespaces::qualify(request.queue_name_prefix.as_deref().unwrap_or_default());
+    let mut queues: Vec<(String, String)> = state.store.collect(|q| {
+        let listed = q.name.starts_with(&prefix)
+            && start_after.as_ref().is_none_or(|after| &q.name > after);
+        listed.then(|| (q.name.clone(), q.url.clone()))
+    });
+    queues.sort();
+
+    let limit = request
+        .max_results
+        .map_or(LIST_QUEUES_LIMIT, |max| (max as usize).clamp(1, LIST_QUEUES_LIMIT));
+    let next_token = if request.max_results.is_some() && queues.len() > limit {
+        Some(URL_SAFE_NO_PAD.encode(&queues[limit - 1].0))
+    } else {
+        None
+    };
+    queues.truncate(limit);
 
-    ListQueuesResponse { queue_urls }
+    Ok(ListQueuesResponse {
+        queue_urls: queues.into_iter().map(|(_, url)| namespaces::issued_url(url)).collect(),
+        next_token,
+    })
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct GetQueueAttributesRequest {
+    #[serde(deserialize_with = "serde_helpers::deserialize_queue_url")]
     pub queue_url: String,
     pub attribute_names: Option<Vec<String>>,
 }
@@ -159,194 +410,478 @@ pub struct GetQueueAttributesResponse {
     pub attributes: HashMap<String, String>,
 }
 
+/// A non-AWS attribute reporting whether delivery is paused through the
+/// admin API.
+pub const PAUSED_ATTRIBUTE: &str = "LocalSqsPaused";
+
+/// The non-AWS attribute that, set to `true`, makes receives return
+/// messages by their [`message_attributes::PRIORITY`], highest first.
+/// Ignored on FIFO queues.
+pub const PRIORITY_ATTRIBUTE: &str = "LocalSqsPriority";
+
+/// The non-AWS attribute capping how many messages a queue holds.
+pub const MAX_QUEUE_LENGTH_ATTRIBUTE: &str = "MaxQueueLength";
+
+/// A non-AWS attribute (named after the CloudWatch metric) giving the age in
+/// seconds of the oldest visible message, or 0 if none is. Only returned
+/// when asked for by name, not for `All`; see [`attributes::QUEUE_ATTRIBUTES`].
+pub const OLDEST_MESSAGE_AGE_ATTRIBUTE: &str = "ApproximateAgeOfOldestMessage";
+
+/// Every attribute the queue reports: registry defaults, the attributes
+/// stored on the queue, and values computed from its state as of `now`.
+fn effective_attributes(queue: &Queue, counts: MessageCounts) -> HashMap<String, String> {
+    let mut attributes = queue.configured_attributes();
+    attributes::apply_defaults(&mut attributes);
+
+    attributes.insert(
+        "ApproximateNumberOfMessages".to_string(),
+        counts.visible.to_string(),
+    );
+    attributes.insert(
+        "ApproximateNumberOfMessagesDelayed".to_string(),
+        counts.delayed.to_string(),
+    );
+    attributes.insert(
+        "ApproximateNumberOfMessagesNotVisible".to_string(),
+        counts.in_flight.to_string(),
+    );
+    attributes.insert(
+        "CreatedTimestamp".to_string(),
+        queue.created_timestamp.to_string(),
+    );
+    attributes.insert(
+        "LastModifiedTimestamp".to_string(),
+        queue.last_modified_timestamp.to_string(),
+    );
+    attributes.insert("QueueArn".to_string(), queue.arn.clone());
+    attributes.insert(PAUSED_ATTRIBUTE.to_string(), queue.paused.to_string());
+    attributes
+}
+
 pub async fn get_queue_attributes(
     State(state): State<AppState>,
     Json(request): Json<GetQueueAttributesRequest>,
 ) -> Result<GetQueueAttributesResponse, SqsError> {
-    match state.queues.get(&request.queue_url) {
-        Some(queue) => {
-            let mut attributes = HashMap::new();
-            let requested_attributes =
-                request.attribute_names.unwrap_or_else(|| vec!["All".to_string()]);
-
-            let all_requested = requested_attributes.contains(&"All".to_string());
-
-            if all_requested {
-                for (key, value) in &queue.attributes {
-                    attributes.insert(key.clone(), value.clone());
-                }
-            } else {
-                for attr_name in &requested_attributes {
-                    if let Some(value) = queue.attributes.get(attr_name) {
-                        attributes.insert(attr_name.clone(), value.clone());
-                    }
-                }
-            }
-
-            if all_requested
-                || requested_attributes.contains(&"ApproximateNumberOfMessages".to_string())
-            {
-                attributes.insert(
-                    "ApproximateNumberOfMessages".to_string(),
-                    queue.messages.len().to_string(),
-                );
-            }
-            if all_requested
-                || requested_attributes.contains(&"ApproximateNumberOfMessagesDelayed".to_string())
-            {
-                let count = queue
-                    .messages
-                    .iter()
-                    .filter(|m| m.receipt_handle.is_none() && m.visible_from > Utc::now())
-                    .count();
-                attributes.insert(
-                    "ApproximateNumberOfMessagesDelayed".to_string(),
-                    count.to_string(),
-                );
-            }
-            if all_requested
-                || requested_attributes
-                    .contains(&"ApproximateNumberOfMessagesNotVisible".to_string())
-            {
-                let count = queue
-                    .messages
-                    .iter()
-                    .filter(|m| m.receipt_handle.is_some() && m.visible_from > Utc::now())
-                    .count();
-                attributes.insert(
-                    "ApproximateNumberOfMessagesNotVisible".to_string(),
-                    count.to_string(),
-                );
-            }
-            if all_requested || requested_attributes.contains(&"CreatedTimestamp".to_string()) {
-                attributes.insert(
-                    "CreatedTimestamp".to_string(),
-                    queue.created_timestamp.to_string(),
-                );
-            }
-            if all_requested || requested_attributes.contains(&"LastModifiedTimestamp".to_string())
-            {
-                attributes.insert(
-                    "LastModifiedTimestamp".to_string(),
-                    queue.last_modified_timestamp.to_string(),
-                );
-            }
-            if all_requested || requested_attributes.contains(&"QueueArn".to_string()) {
-                attributes.insert(
-                    "QueueArn".to_string(),
-                    format!("arn:aws:sqs:local:000000000000:{}", queue.name),
-                );
-            }
-
-            Ok(GetQueueAttributesResponse { attributes })
-        }
-        None => Err(SqsError::QueueDoesNotExist),
+    let now = state.clock.now();
+    let requested = request
+        .attribute_names
+        .unwrap_or_else(|| vec!["All".to_string()]);
+    // As in AWS, names that aren't attributes are an error rather than
+    // left out of the response.
+    if let Some(unknown) = requested
+        .iter()
+        .find(|name| *name != "All" && attributes::spec(name).is_none())
+    {
+        state
+            .validation
+            .violation(SqsError::InvalidAttributeName(unknown.clone()))?;
+    }
+    // As in AWS, `All` anywhere in the list returns everything, bar the
+    // attributes only returned when asked for by name.
+    let all = requested.iter().any(|name| name == "All");
+    let counts = state.store.message_counts(&request.queue_url, now).await?;
+    let mut attributes = state.store.read(&request.queue_url, |queue| {
+        let mut attributes = effective_attributes(queue, counts);
+        attributes.retain(|name, _| {
+            requested.contains(name)
+                || all && attributes::spec(name).is_none_or(|spec| spec.in_all)
+        });
+        attributes
+    })?;
+    if requested.iter().any(|name| name == OLDEST_MESSAGE_AGE_ATTRIBUTE) {
+        let age = state
+            .store
+            .oldest_visible_message_age(&request.queue_url, now)
+            .await? as u64;
+        attributes.insert(OLDEST_MESSAGE_AGE_ATTRIBUTE.to_string(), age.to_string());
     }
-}
 
+    Ok(GetQueueAttributesResponse { attributes })
+}
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct DeleteQueueRequest {
+    #[serde(deserialize_with = "serde_helpers::deserialize_queue_url")]
     pub queue_url: String,
 }
 
 pub async fn delete_queue(
     State(state): State<AppState>,
     Json(request): Json<DeleteQueueRequest>,
-) -> Result<(), SqsError> {
-    if state.queues.remove(&request.queue_url).is_none() {
-        return Err(SqsError::QueueDoesNotExist);
-    }
-    Ok(())
+) -> Result<EmptyResponse, SqsError> {
+    remove_queue_if(&state, &request.queue_url, "DeleteQueue", |_| true)
+        .await?
+        .map(|_| EmptyResponse {})
+        .ok_or(SqsError::QueueDoesNotExist)
 }
 
-#[derive(Debug, Deserialize)]
+/// Deletes the queue at `queue_url` if `condition` holds for it, returning
+/// it. Every way a queue is deleted goes through here; `reason` is recorded
+/// in the server's event log.
+pub async fn remove_queue_if(
+    state: &AppState,
+    queue_url: &str,
+    reason: &str,
+    condition: impl FnOnce(&Queue) -> bool + Send,
+) -> Result<Option<Queue>, SqsError> {
+    let mut condition = Some(condition);
+    let queue = state
+        .store
+        .remove_queue_if(queue_url, &mut |queue| {
+            condition.take().is_some_and(|condition| condition(queue))
+        })
+        .await?;
+    let Some(queue) = queue else {
+        return Ok(None);
+    };
+    queue.release_usage();
+    // Long polls on the queue find it gone and fail with QueueDoesNotExist.
+    queue.notify.notify_waiters();
+    state.record_server_event(state.event(
+        EventKind::QueueDeleted,
+        &queue.name,
+        json!({ "reason": reason, "messages": queue.messages.len() }),
+    ));
+    Ok(Some(queue))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct PurgeQueueRequest {
+    #[serde(deserialize_with = "serde_helpers::deserialize_queue_url")]
     pub queue_url: String,
 }
 
 pub async fn purge_queue(
     State(state): State<AppState>,
     Json(request): Json<PurgeQueueRequest>,
-) -> Result<(), SqsError> {
-    match state.queues.get_mut(&request.queue_url) {
-        Some(mut queue) => {
-            queue.messages.clear();
-            Ok(())
-        }
-        None => Err(SqsError::QueueDoesNotExist),
-    }
+) -> Result<EmptyResponse, SqsError> {
+    let now = state.clock.now();
+    state
+        .store
+        .try_update_selected(&request.queue_url, |_| Selection::none(), |queue| {
+            if let Some(until) = queue.purged_until
+                && until > now
+            {
+                return Err(SqsError::PurgeQueueInProgress(format!(
+                    "Only one PurgeQueue operation on {} is allowed every {} seconds.",
+                    queue.name,
+                    state.purge_cooldown.unwrap_or_default().as_secs()
+                )));
+            }
+            if let Some(cooldown) = state.purge_cooldown {
+                queue.purged_until = Some(now + cooldown);
+            }
+            let purged = queue.clear_messages() as u64;
+            queue.stats.purged += purged;
+            queue.purge_generation += 1;
+            queue.notify.notify_waiters();
+            let event = state.event(EventKind::Purged, &queue.name, json!({ "messages": purged }));
+            queue.events.push(event);
+            Ok(EmptyResponse {})
+        })
+        .await
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct SendMessageRequest {
+    #[serde(deserialize_with = "serde_helpers::deserialize_queue_url")]
     pub queue_url: String,
     pub message_body: String,
     #[serde(default)]
     pub message_attributes: HashMap<String, crate::state::MessageAttributeValue>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "serde_helpers::deserialize_optional_number")]
     pub delay_seconds: Option<u32>,
+    #[serde(default)]
+    pub message_system_attributes: HashMap<String, crate::state::MessageAttributeValue>,
+    /// FIFO deduplication ID; rejected by standard queues.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_deduplication_id: Option<String>,
+    /// FIFO message group; required by FIFO queues and rejected by
+    /// standard ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_group_id: Option<String>,
+    /// The `X-Amzn-Trace-Id` header of the HTTP request, used as the
+    /// `AWSTraceHeader` attribute unless `message_system_attributes` sets one.
+    #[serde(skip)]
+    pub trace_header: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct SendMessageResponse {
     pub message_id: String,
     #[serde(rename = "MD5OfMessageBody")]
     pub md5_of_message_body: String,
     #[serde(rename = "MD5OfMessageAttributes")]
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub md5_of_message_attributes: String,
+    #[serde(rename = "MD5OfMessageSystemAttributes")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub md5_of_message_system_attributes: String,
+    /// Set for FIFO queues only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence_number: Option<String>,
+}
+
+/// The error AWS gives for a parameter the queue's type (standard or FIFO)
+/// doesn't take.
+fn not_valid_for_queue_type(name: &str, value: &dyn std::fmt::Display) -> SqsError {
+    SqsError::InvalidParameterValue(format!(
+        "Value {} for parameter {} is invalid. Reason: The request include parameter that is not valid for this queue type.",
+        value, name
+    ))
+}
+
+/// Checks a `MessageGroupId` or `MessageDeduplicationId`: 1 to 128
+/// alphanumeric and punctuation characters.
+fn validate_fifo_id(parameter: &str, value: &str) -> Result<(), SqsError> {
+    let valid = (1..=128).contains(&value.chars().count())
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c.is_ascii_punctuation());
+    if valid {
+        return Ok(());
+    }
+    Err(SqsError::InvalidParameterValue(format!(
+        "Value {} for parameter {} is invalid. Reason: {} can only include alphanumeric and punctuation characters. 1 to 128 in length.",
+        value, parameter, parameter
+    )))
 }
 
 pub async fn send_message(
     State(state): State<AppState>,
     Json(request): Json<SendMessageRequest>,
 ) -> Result<SendMessageResponse, SqsError> {
-    match state.queues.get_mut(&request.queue_url) {
-        Some(mut queue) => {
-            let mut attributes = HashMap::new();
-            attributes.insert(
-                "SenderId".to_string(),
-                "AROASIVGLBUVGRUCIDMOF:botocore-session-1768915992".to_string(),
-            );
-            attributes.insert("ApproximateReceiveCount".to_string(), "0".to_string());
-            attributes.insert(
-                "SentTimestamp".to_string(),
-                Utc::now().timestamp_millis().to_string(),
-            );
-            attributes.insert(
-                "DeadLetterQueueSourceArn".to_string(),
-                "arn:aws:sqs:eu-central-1:156041415978:backend-sb1-fraud-persister-install-event-events"
-                    .to_string(),
-            );
+    // AWS takes an empty body for a missing one.
+    if request.message_body.is_empty() {
+        return Err(SqsError::MissingParameter("MessageBody".to_string()));
+    }
+    let queue_url = request.queue_url.clone();
+    state.store.enqueue(&state, &queue_url, request).await
+}
 
-            let message = crate::state::Message::new(
-                request.message_body,
-                attributes,
-                request.message_attributes,
-                request.delay_seconds,
+impl Queue {
+    /// `SendMessage` on this queue: checks `request` against the queue's
+    /// type and limits, and unless its deduplication ID was seen recently,
+    /// enqueues its message.
+    pub fn enqueue(
+        &mut self,
+        state: &AppState,
+        request: SendMessageRequest,
+    ) -> Result<SendMessageResponse, SqsError> {
+        let validation = &state.validation;
+        if let Some(c) = message_attributes::invalid_character(&request.message_body) {
+            validation.violation(message_attributes::invalid_contents("the message body", c))?;
+        }
+        validation.check(message_attributes::validate(&request.message_attributes))?;
+        validation.check(message_attributes::validate_system(
+            &request.message_system_attributes,
+        ))?;
+        if let Some(delay) = request.delay_seconds {
+            validation.check(attributes::validate_parameter(
+                "DelaySeconds",
+                "DelaySeconds",
+                delay.into(),
+            ))?;
+        }
+
+        if self.is_fifo() {
+            match &request.message_group_id {
+                Some(group) => validation.check(validate_fifo_id("MessageGroupId", group))?,
+                None => validation
+                    .violation(SqsError::MissingParameter("MessageGroupId".to_string()))?,
+            }
+            if let Some(id) = &request.message_deduplication_id {
+                validation.check(validate_fifo_id("MessageDeduplicationId", id))?;
+            }
+            if request.message_deduplication_id.is_none()
+                && !self.attribute_or("ContentBasedDeduplication", false)
+            {
+                validation.violation(SqsError::InvalidParameterValue(
+                    "The queue should either have ContentBasedDeduplication enabled or MessageDeduplicationId provided explicitly".to_string(),
+                ))?;
+            }
+            if let Some(delay) = request.delay_seconds.filter(|delay| *delay > 0) {
+                validation.violation(not_valid_for_queue_type("DelaySeconds", &delay))?;
+            }
+        } else {
+            if let Some(group) = &request.message_group_id {
+                validation.violation(not_valid_for_queue_type("MessageGroupId", group))?;
+            }
+            if let Some(id) = &request.message_deduplication_id {
+                validation.violation(not_valid_for_queue_type("MessageDeduplicationId", id))?;
+            }
+        }
+
+        let max_size = self.attribute_or("MaximumMessageSize", attributes::MAX_MESSAGE_SIZE);
+        let size = message_size_bytes(&request.message_body, &request.message_attributes);
+        if size > max_size {
+            validation.violation(SqsError::InvalidParameterValue(format!(
+                "One or more parameters are invalid. Reason: Message must be shorter than {} bytes.",
+                max_size
+            )))?;
+        }
+
+        let md5_of_message_system_attributes =
+            crate::state::md5_of_message_attributes(&request.message_system_attributes);
+        let deduplication_key = deduplication_key(self, &request);
+        if let Some(key) = &deduplication_key
+            && let Some(accepted) = self.deduplication.get(key, state.clock.now())
+        {
+            // Accepted again without being enqueued again. The digests
+            // are of what was sent this time, as AWS's are.
+            return Ok(SendMessageResponse {
+                message_id: accepted.message_id.clone(),
+                md5_of_message_body: md5_hex(request.message_body.as_bytes()),
+                md5_of_message_attributes: crate::state::md5_of_message_attributes(
+                    &request.message_attributes,
+                ),
+                md5_of_message_system_attributes,
+                sequence_number: Some(accepted.sequence_number.clone()),
+            });
+        }
+
+        if let Some(max) = state.max_messages_per_queue
+            && self.messages.len() >= max
+        {
+            return Err(SqsError::OverLimit(format!(
+                "Queue {} already holds the maximum of {} messages.",
+                self.name, max
+            )));
+        }
+        if let Some(max) = self.attributes.get(MAX_QUEUE_LENGTH_ATTRIBUTE)
+            && let Ok(max) = max.parse::<usize>()
+            && self.messages.len() >= max
+        {
+            self.stats.sends_rejected += 1;
+            return Err(SqsError::QueueFull {
+                code: state.queue_full_error_code.clone(),
+                message: format!(
+                    "Queue {} is at its MaxQueueLength of {} messages.",
+                    self.name, max
+                ),
+            });
+        }
+        let mut attributes = HashMap::new();
+        attributes.insert(
+            "SenderId".to_string(),
+            "AROASIVGLBUVGRUCIDMOF:botocore-session-1768915992".to_string(),
+        );
+        attributes.insert("ApproximateReceiveCount".to_string(), "0".to_string());
+        if self.is_fifo() {
+            if let Some(group) = request.message_group_id {
+                attributes.insert("MessageGroupId".to_string(), group);
+            }
+            if let Some((_, id)) = &deduplication_key {
+                attributes.insert("MessageDeduplicationId".to_string(), id.clone());
+            }
+        }
+        let trace_header = request
+            .message_system_attributes
+            .get(message_attributes::AWS_TRACE_HEADER)
+            .and_then(|attr| attr.string_value.clone())
+            .or(request.trace_header);
+        if let Some(trace_header) = trace_header {
+            attributes.insert(
+                message_attributes::AWS_TRACE_HEADER.to_string(),
+                trace_header,
             );
+        }
 
-            let resp = SendMessageResponse {
+        let mut message = crate::state::Message::new(
+            state.ids.uuid().to_string(),
+            request.message_body,
+            attributes,
+            request.message_attributes,
+            request
+                .delay_seconds
+                .or_else(|| Some(self.attribute_or("DelaySeconds", 0))),
+            state.clock.now(),
+        );
+        // The cap counts the body as stored, as the queue's accounting does.
+        message.compress_body(state.compress_bodies_above);
+        state.reserve_bytes(message.size())?;
+        let sequence_number = self.is_fifo().then(|| self.next_sequence_number());
+        if let Some(sequence_number) = &sequence_number {
+            let name = "SequenceNumber".to_string();
+            message.attributes.insert(name, sequence_number.clone());
+        }
+        message.trace_context = telemetry::current_trace_context();
+        message.history = MessageHistory::new(state.message_history);
+        telemetry::record_message_ids([message.id.as_str()]);
+
+        let resp = SendMessageResponse {
+            message_id: message.id.clone(),
+            md5_of_message_body: message.md5_of_body.clone(),
+            md5_of_message_attributes: message.md5_of_message_attributes.clone(),
+            md5_of_message_system_attributes,
+            sequence_number,
+        };
+        let now = state.clock.now();
+        if let (Some(key), Some(sequence_number)) = (deduplication_key, &resp.sequence_number) {
+            let accepted = Accepted {
                 message_id: message.id.clone(),
-                md5_of_message_body: message.md5_of_body.clone(),
-                md5_of_message_attributes: message.md5_of_message_attributes.clone(),
+                sequence_number: sequence_number.clone(),
             };
-            queue.messages.push_back(message);
+            self.deduplication.insert(key, accepted, now);
+        }
+        let visible = message.visible_from <= now;
+        self.last_used = now;
+        state.webhooks.message_enqueued(self, &message);
+        self.push_reserved(message, now);
+        self.stats.sent += 1;
+        if visible {
+            self.notify.notify_waiters();
+        }
+
+        Ok(resp)
+    }
+}
 
-            Ok(resp)
+/// The key a send to a FIFO queue is deduplicated under: its
+/// `MessageDeduplicationId`, or on a queue with `ContentBasedDeduplication`
+/// a digest of its body. Sends to standard queues aren't deduplicated, nor
+/// are FIFO sends with neither, which only lenient validation lets through.
+fn deduplication_key(queue: &Queue, request: &SendMessageRequest) -> Option<DeduplicationKey> {
+    if !queue.is_fifo() {
+        return None;
+    }
+    let id = match &request.message_deduplication_id {
+        Some(id) => id.clone(),
+        None if queue.attribute_or("ContentBasedDeduplication", false) => {
+            deduplication::content_deduplication_id(&request.message_body)
         }
-        None => Err(SqsError::QueueDoesNotExist),
+        None => return None,
+    };
+    let by_group = queue
+        .attributes
+        .get("DeduplicationScope")
+        .is_some_and(|scope| scope == "messageGroup");
+    let group = request.message_group_id.clone().filter(|_| by_group);
+    Some((group, id))
+}
+
+/// The in-flight message `receipt_handle` was issued for. A handle this
+/// server didn't issue for this queue is `ReceiptHandleIsInvalid`; one that
+/// is no longer current, because the message was deleted or received again,
+/// is `MessageNotInflight`.
+fn find_in_flight(state: &AppState, queue: &Queue, receipt_handle: &str) -> Result<u64, SqsError> {
+    let handle = state.receipt_handles.resolve(receipt_handle)?;
+    if handle.queue != queue.name {
+        return Err(SqsError::ReceiptHandleIsInvalid(receipt_handle.to_string()));
     }
+    queue
+        .messages
+        .find_by_receipt_handle(receipt_handle)
+        .ok_or(SqsError::MessageNotInflight)
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct DeleteMessageRequest {
+    #[serde(deserialize_with = "serde_helpers::deserialize_queue_url")]
     pub queue_url: String,
     pub receipt_handle: String,
 }
@@ -354,49 +889,162 @@ pub struct DeleteMessageRequest {
 pub async fn delete_message(
     State(state): State<AppState>,
     Json(request): Json<DeleteMessageRequest>,
-) -> Result<(), SqsError> {
-    match state.queues.get_mut(&request.queue_url) {
-        Some(mut queue) => {
-            let mut message_found = false;
-            queue.messages.retain(|m| {
-                if m.receipt_handle == Some(request.receipt_handle.clone()) {
-                    message_found = true;
-                    false
-                } else {
-                    true
-                }
-            });
+) -> Result<EmptyResponse, SqsError> {
+    let id = delete(&state, &request).await?;
+    telemetry::record_message_ids([id.as_str()]);
+    Ok(EmptyResponse {})
+}
 
-            if message_found {
-                Ok(())
-            } else {
-                Err(SqsError::MessageNotInflight)
-            }
+/// Deletes the message `request` names, returning its id.
+pub(crate) async fn delete(
+    state: &AppState,
+    request: &DeleteMessageRequest,
+) -> Result<String, SqsError> {
+    let url = &request.queue_url;
+    state
+        .store
+        .delete_by_handle(state, url, &request.receipt_handle)
+        .await
+}
+
+impl Queue {
+    /// `DeleteMessage` on this queue: deletes the in-flight message
+    /// `receipt_handle` was issued for, returning its id.
+    pub fn delete_by_handle(
+        &mut self,
+        state: &AppState,
+        receipt_handle: &str,
+    ) -> Result<String, SqsError> {
+        let seq = find_in_flight(state, self, receipt_handle)?;
+        let mut removed = self.remove_message(seq).expect("indexed message exists");
+        let id = removed.id.clone();
+        self.stats.deleted += 1;
+        let now = state.clock.now();
+        if let Some(mut history) = removed.history.take() {
+            history.push(HistoryEntry {
+                receipt_handle: Some(receipt_handle.to_string()),
+                ..HistoryEntry::new(HistoryKind::Deleted, now)
+            });
+            self.deleted_histories.push(removed.id, history);
+        }
+        if let Some(first_received) = removed.first_received {
+            self.latency
+                .first_receive_to_delete
+                .observe(metrics::seconds_between(first_received, now));
         }
-        None => Err(SqsError::QueueDoesNotExist),
+        Ok(id)
     }
 }
 
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ChangeMessageVisibilityRequest {
+    #[serde(deserialize_with = "serde_helpers::deserialize_queue_url")]
+    pub queue_url: String,
+    pub receipt_handle: String,
+    #[serde(deserialize_with = "serde_helpers::deserialize_number")]
+    pub visibility_timeout: u32,
+}
+
+pub async fn change_message_visibility(
+    State(state): State<AppState>,
+    Json(request): Json<ChangeMessageVisibilityRequest>,
+) -> Result<EmptyResponse, SqsError> {
+    let id = change_visibility(&state, &request).await?;
+    telemetry::record_message_ids([id.as_str()]);
+    Ok(EmptyResponse {})
+}
+
+/// Changes the visibility timeout of the message `request` names, returning
+/// its id.
+pub(crate) async fn change_visibility(
+    state: &AppState,
+    request: &ChangeMessageVisibilityRequest,
+) -> Result<String, SqsError> {
+    state.validation.check(attributes::validate_parameter(
+        "VisibilityTimeout",
+        "VisibilityTimeout",
+        request.visibility_timeout.into(),
+    ))?;
+
+    state
+        .store
+        .try_update_selected(
+            &request.queue_url,
+            |_| Selection::receipt_handle(&request.receipt_handle),
+            |queue| {
+                let seq = find_in_flight(state, queue, &request.receipt_handle)?;
+
+                let now = state.clock.now();
+                let visible_from = now + chrono::Duration::seconds(request.visibility_timeout as i64);
+                queue.messages.set_visible_from(seq, visible_from);
+                queue.messages.record(seq, || HistoryEntry {
+                    receipt_handle: Some(request.receipt_handle.clone()),
+                    visible_until: Some(visible_from.timestamp_millis()),
+                    ..HistoryEntry::new(HistoryKind::VisibilityChanged, now)
+                });
+                if request.visibility_timeout == 0 {
+                    queue.notify.notify_waiters();
+                }
+                Ok(queue.messages.get(seq).map(|m| m.id.clone()).unwrap_or_default())
+            },
+        )
+        .await
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ReceiveMessageRequest {
+    #[serde(deserialize_with = "serde_helpers::deserialize_queue_url")]
     pub queue_url: String,
     #[serde(default = "default_max_number_of_messages")]
+    #[serde(deserialize_with = "serde_helpers::deserialize_number")]
     pub max_number_of_messages: u32,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "serde_helpers::deserialize_optional_number")]
     pub visibility_timeout: Option<u32>,
-    #[serde(default)]
+    /// `None` only if the field is absent (or null), in which case the
+    /// queue's `ReceiveMessageWaitTimeSeconds` applies; an explicit 0
+    /// short-polls whatever the queue's setting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "serde_helpers::deserialize_optional_number")]
     pub wait_time_seconds: Option<u32>,
+    /// The system attributes to return, under the name the SDKs have since
+    /// deprecated in favor of `MessageSystemAttributeNames`. See
+    /// [`system_attribute_names`](Self::system_attribute_names).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attribute_names: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub message_system_attribute_names: Vec<String>,
+    /// The message attributes to return; none are unless asked for. See
+    /// [`message_attributes::select`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub message_attribute_names: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receive_request_attempt_id: Option<String>,
 }
 
 fn default_max_number_of_messages() -> u32 {
     1
 }
 
-#[derive(Debug, Serialize)]
+impl ReceiveMessageRequest {
+    /// The system attributes asked for: `MessageSystemAttributeNames`, or
+    /// the deprecated `AttributeNames` if only that was sent, or `None` if
+    /// neither was, in which case every system attribute is returned.
+    pub fn system_attribute_names(&self) -> Option<&[String]> {
+        [&self.message_system_attribute_names, &self.attribute_names]
+            .into_iter()
+            .find(|names| !names.is_empty())
+            .map(Vec::as_slice)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ReceiveMessageResponse {
-    #[serde(rename = "Messages")]
+    /// Omitted when empty: SQS answers an empty receive with `{}`.
+    #[serde(rename = "Messages", default, skip_serializing_if = "Vec::is_empty")]
     pub messages: Vec<crate::state::Message>,
 }
 
@@ -404,144 +1052,339 @@ pub async fn receive_message(
     State(state): State<AppState>,
     Json(request): Json<ReceiveMessageRequest>,
 ) -> Result<ReceiveMessageResponse, SqsError> {
-    let wait_time = request.wait_time_seconds.unwrap_or(0);
-    let start_time = Utc::now();
+    if !(1..=10).contains(&request.max_number_of_messages) {
+        state.validation.violation(SqsError::InvalidParameterValue(format!(
+            "Value {} for parameter MaxNumberOfMessages is invalid. Reason: Must be between 1 and 10.",
+            request.max_number_of_messages
+        )))?;
+    }
+    if let Some(wait_time) = request.wait_time_seconds {
+        state.validation.check(attributes::validate_parameter(
+            "WaitTimeSeconds",
+            "ReceiveMessageWaitTimeSeconds",
+            wait_time.into(),
+        ))?;
+    }
+    if let Some(visibility_timeout) = request.visibility_timeout {
+        state.validation.check(attributes::validate_parameter(
+            "VisibilityTimeout",
+            "VisibilityTimeout",
+            visibility_timeout.into(),
+        ))?;
+    }
+    let wait_time = match request.wait_time_seconds {
+        Some(wait_time) => wait_time,
+        None => state.store.read(&request.queue_url, |queue| {
+            queue.attribute_or("ReceiveMessageWaitTimeSeconds", 0)
+        })?,
+    };
+    if let Some(attempt_id) = &request.receive_request_attempt_id {
+        let is_fifo = state.store.read(&request.queue_url, |queue| queue.is_fifo())?;
+        if !is_fifo {
+            state.validation.violation(not_valid_for_queue_type(
+                "ReceiveRequestAttemptId",
+                attempt_id,
+            ))?;
+        }
+    }
+    let polled_at = tokio::time::Instant::now();
+    let deadline = polled_at + Duration::from_secs(wait_time as u64);
+    let _long_poll = if wait_time > 0 {
+        let long_polls = state
+            .store
+            .read(&request.queue_url, |queue| queue.long_polls.clone())?;
+        let Some(permit) = long_polls.try_acquire(state.max_long_polls_per_queue) else {
+            state.rejections.long_polls.fetch_add(1, Ordering::Relaxed);
+            return Err(SqsError::ServiceUnavailable(
+                "Too many long polls waiting on this queue.".to_string(),
+            ));
+        };
+        Some(permit)
+    } else {
+        None
+    };
 
+    let mut started: Option<(Arc<Notify>, u64)> = None;
     loop {
-        let redrive_policy;
-        let dead_letter_target_arn;
-        let mut messages_to_move = Vec::new();
-        let mut messages_to_return = Vec::new();
-
-        if let Some(mut queue) = state.queues.get_mut(&request.queue_url) {
-            let now = Utc::now();
-
-            // Reset visibility for messages that have timed out
-            for message in queue.messages.iter_mut() {
-                if message.receipt_handle.is_some() && now >= message.visible_from {
-                    message.receipt_handle = None;
+        let (notify, generation) = state
+            .store
+            .read(&request.queue_url, |q| (q.notify.clone(), q.purge_generation))?;
+        // A purge while waiting ends the wait with nothing received. A queue
+        // replaced meanwhile, as by restoring a checkpoint, is waited on
+        // afresh.
+        match &started {
+            Some((waited_on, started_generation)) if Arc::ptr_eq(waited_on, &notify) => {
+                if *started_generation != generation {
+                    break;
                 }
             }
+            _ => started = Some((notify.clone(), generation)),
+        }
+        // Register for wakeups before scanning so a send (or the queue being
+        // removed) between the scan and the wait below isn't missed.
+        let notified = notify.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+
+        let mut messages = claim_messages(&state, &request).await?;
+        if !messages.is_empty() {
+            let system_attribute_names = request
+                .system_attribute_names()
+                .filter(|names| !names.iter().any(|name| name == "All"));
+            for message in &mut messages {
+                if let Some(names) = system_attribute_names {
+                    message.attributes.retain(|name, _| names.contains(name));
+                }
+                let selected = message_attributes::select(
+                    std::mem::take(&mut message.message_attributes),
+                    &request.message_attribute_names,
+                );
+                // The digest covers the attributes returned, as the SDKs
+                // check it against them.
+                message.md5_of_message_attributes =
+                    crate::state::md5_of_message_attributes(&selected);
+                message.message_attributes = selected;
+
+                if let Some(traceparent) = &message.trace_context {
+                    telemetry::link_to(traceparent);
+                }
+            }
+            telemetry::record_message_ids(messages.iter().map(|m| m.id.as_str()));
+            telemetry::record_wait(polled_at.elapsed());
+            return Ok(ReceiveMessageResponse { messages });
+        }
 
-            let visibility_timeout_attr = queue
-                .attributes
-                .get("VisibilityTimeout")
-                .and_then(|s| s.parse().ok())
-                .unwrap_or(30);
+        // No queue lock is held here: sends and the maintenance task wake us
+        // when something becomes visible; otherwise we give up at the deadline.
+        trace!(waited_ms = polled_at.elapsed().as_millis() as u64, "waiting for messages");
+        if tokio::time::timeout_at(deadline, notified)
+            .await
+            .is_err()
+        {
+            trace!("wait time elapsed");
+            break;
+        }
+        trace!("woken to look for messages again");
+    }
+    telemetry::record_wait(polled_at.elapsed());
 
-            redrive_policy = queue.redrive_policy.clone();
-            if let Some(rp) = &redrive_policy {
-                dead_letter_target_arn = Some(rp.dead_letter_target_arn.clone());
-            } else {
-                dead_letter_target_arn = None;
-            }
+    // The queue may have been deleted meanwhile; there is nothing to count.
+    let _ = state
+        .store
+        .update_selected(&request.queue_url, |_| Selection::none(), |queue| {
+            queue.stats.empty_receives += 1
+        })
+        .await;
 
-            let max_messages = request.max_number_of_messages as usize;
-            let mut retained_messages = std::collections::VecDeque::new();
-            for mut message in queue.messages.drain(..) {
-                if messages_to_return.len() >= max_messages {
-                    retained_messages.push_back(message);
-                    continue;
-                }
+    Ok(ReceiveMessageResponse {
+        messages: Vec::new(),
+    })
+}
 
-                if message.receipt_handle.is_none() && now >= message.visible_from {
-                    if let Some(rp) = &redrive_policy {
-                        if message.receive_count >= rp.max_receive_count {
-                            messages_to_move.push(message);
-                            continue;
-                        }
-                    }
-                    // this should be after redrive check so we dont redrive to dlq early
-                    message.receive_count += 1;
-
-                    let mut message_clone = message.clone();
-
-                    let visibility_timeout =
-                        request.visibility_timeout.unwrap_or(visibility_timeout_attr);
-
-                    message.visible_from =
-                        Utc::now() + chrono::Duration::seconds(visibility_timeout as i64);
-
-                    let receipt_handle = Uuid::new_v4().to_string();
-                    message.receipt_handle = Some(receipt_handle.clone());
-                    message_clone.receipt_handle = Some(receipt_handle);
-
-                    let mut receive_count = message
-                        .attributes
-                        .get("ApproximateReceiveCount")
-                        .and_then(|s| s.parse::<i32>().ok())
-                        .unwrap_or(0);
-                    receive_count += 1;
-
-                    if receive_count == 1 {
-                        message_clone.attributes.insert(
-                            "ApproximateFirstReceiveTimestamp".to_string(),
-                            Utc::now().timestamp_millis().to_string(),
-                        );
-                    }
-
-                    message.attributes.insert(
-                        "ApproximateReceiveCount".to_string(),
-                        receive_count.to_string(),
-                    );
-                    message_clone.attributes.insert(
-                        "ApproximateReceiveCount".to_string(),
-                        receive_count.to_string(),
-                    );
-
-                    messages_to_return.push(message_clone);
-                }
-                retained_messages.push_back(message);
+/// Claims up to `MaxNumberOfMessages` visible messages from the queue, and
+/// moves any past their `maxReceiveCount` to its dead-letter queue.
+async fn claim_messages(
+    state: &AppState,
+    request: &ReceiveMessageRequest,
+) -> Result<Vec<Message>, SqsError> {
+    let claimed = state
+        .store
+        .claim_visible(state, &request.queue_url, request)
+        .await?;
+    if let Some((dlq_arn, dead_lettered)) = claimed.dead_lettered {
+        state
+            .move_to_dead_letter_queue(&dlq_arn, dead_lettered)
+            .await;
+    }
+    Ok(claimed.messages)
+}
+
+/// What [`Queue::claim_visible`] took from a queue.
+#[derive(Debug)]
+pub struct Claimed {
+    /// The messages claimed for the receive.
+    pub messages: Vec<Message>,
+    /// Messages past the queue's `maxReceiveCount`, removed from it for the
+    /// dead-letter queue with this ARN. `None` without a redrive policy.
+    pub dead_lettered: Option<(String, Vec<Message>)>,
+}
+
+impl Queue {
+    /// The messages [`claim_visible`](Self::claim_visible) needs for
+    /// `request`: those whose visibility timeout has lapsed, as many visible
+    /// ones as it may claim, or every visible one when it picks among them
+    /// rather than taking them in send order, and every in-flight one when
+    /// it may deliver a duplicate.
+    pub fn claim_selection(&self, state: &AppState, request: &ReceiveMessageRequest) -> Selection {
+        let max_messages = if self.paused {
+            0
+        } else {
+            request.max_number_of_messages as usize
+        };
+        let chaos = self.chaos.unwrap_or(state.chaos);
+        let by_priority = self
+            .attributes
+            .get(PRIORITY_ATTRIBUTE)
+            .is_some_and(|v| v == "true");
+        let picks = !self.is_fifo() && (by_priority || chaos.shuffle_delivery);
+        Selection {
+            visible: if picks && max_messages > 0 { usize::MAX } else { max_messages },
+            unlocked_groups: self.is_fifo(),
+            lapsed: true,
+            in_flight: max_messages > 0 && chaos.duplicate_delivery_probability > 0.0,
+            ..Selection::none()
+        }
+    }
+
+    /// `ReceiveMessage` on this queue, short of waiting: releases expired
+    /// visibility timeouts and claims up to `MaxNumberOfMessages` visible
+    /// messages. `dead_letter` is passed on to
+    /// [`release_expired`](Self::release_expired).
+    ///
+    /// Selecting and claiming is a single `MessageStore::claim` call, so
+    /// that with the queue held exclusively, concurrent receives never claim
+    /// the same message. It is deliberately synchronous: the queue can never
+    /// be held across an `.await` in the long-poll loop, where it would
+    /// block sends to the same queue.
+    pub fn claim_visible(
+        &mut self,
+        state: &AppState,
+        request: &ReceiveMessageRequest,
+        dead_letter: bool,
+    ) -> Claimed {
+        let now = state.clock.now();
+        self.last_used = now;
+
+        let released = self.release_expired(now, dead_letter);
+        let dead_lettered = self
+            .redrive_policy
+            .as_ref()
+            .map(|rp| (rp.dead_letter_target_arn.clone(), released));
+
+        let visibility_timeout = request
+            .visibility_timeout
+            .unwrap_or_else(|| self.attribute_or("VisibilityTimeout", 30));
+
+        // A paused queue still releases expired messages but hands none out.
+        let max_messages = if self.paused {
+            0
+        } else {
+            request.max_number_of_messages as usize
+        };
+        let visible_until = now + chrono::Duration::seconds(visibility_timeout as i64);
+
+        // Chosen before claiming so that only a message delivered by an
+        // earlier receive can come back as a duplicate.
+        let chaos = self.chaos.unwrap_or(state.chaos);
+        let duplicate_of =
+            if max_messages > 0 && state.chaos_rng.chance(chaos.duplicate_delivery_probability) {
+                self.messages.random_in_flight(|n| state.chaos_rng.index(n))
+            } else {
+                None
+            };
+
+        let queue_name = self.name.clone();
+        let request_id = events::current_request_id();
+        let mark_received = |message: &mut Message| {
+            message.receive_count += 1;
+            if message.receive_count == 1 {
+                message.first_received = Some(now);
+                message.attributes.insert(
+                    "ApproximateFirstReceiveTimestamp".to_string(),
+                    now.timestamp_millis().to_string(),
+                );
             }
-            queue.messages = retained_messages;
+            message.attributes.insert(
+                "ApproximateReceiveCount".to_string(),
+                message.receive_count.to_string(),
+            );
+            message.receipt_handle = Some(state.receipt_handles.issue(
+                &queue_name,
+                &message.id,
+                message.receive_count,
+                now,
+            ));
+            message.claimed_by.clone_from(&request_id);
+            let receipt_handle = &message.receipt_handle;
+            if let Some(history) = &mut message.history {
+                history.push(HistoryEntry {
+                    receipt_handle: receipt_handle.clone(),
+                    visible_until: Some(visible_until.timestamp_millis()),
+                    ..HistoryEntry::new(HistoryKind::Received, now)
+                });
+            }
+            message.clone()
+        };
+        let mut claimed = if self.is_fifo() {
+            self.messages
+                .claim_unlocked_groups(max_messages, visible_until, mark_received)
+        } else if self
+            .attributes
+            .get(PRIORITY_ATTRIBUTE)
+            .is_some_and(|v| v == "true")
+        {
+            self.messages.claim_by_priority(
+                max_messages,
+                visible_until,
+                |message| message_attributes::priority(&message.message_attributes),
+                mark_received,
+            )
+        } else if chaos.shuffle_delivery {
+            self.messages.claim_shuffled(
+                max_messages,
+                visible_until,
+                |n| state.chaos_rng.index(n),
+                mark_received,
+            )
         } else {
-            return Err(SqsError::QueueDoesNotExist);
-        }
-
-        // Now move the messages
-        if let Some(dlq_arn) = dead_letter_target_arn {
-            if !messages_to_move.is_empty() {
-                let dead_letter_queue_url = state
-                    .queues
-                    .iter()
-                    .find(|q| {
-                        let arn = format!("arn:aws:sqs:local:000000000000:{}", q.name);
-                        arn == dlq_arn
-                    })
-                    .map(|q| q.url.clone());
-
-                if let Some(url) = dead_letter_queue_url {
-                    if let Some(mut dead_letter_queue) = state.queues.get_mut(&url) {
-                        for mut msg in messages_to_move {
-                            msg.receipt_handle = None;
-                            dead_letter_queue.messages.push_back(msg);
-                        }
-                    }
-                }
+            self.messages
+                .claim(max_messages, visible_until, mark_received)
+        };
+        self.stats.received += claimed.len() as u64;
+        for message in &claimed {
+            let age = metrics::seconds_between(message.sent_timestamp, now);
+            self.latency.receive_age.observe(age);
+            if message.receive_count == 1 {
+                self.latency.send_to_first_receive.observe(age);
             }
         }
 
-        if !messages_to_return.is_empty() {
-            return Ok(ReceiveMessageResponse {
-                messages: messages_to_return,
+        if let Some(seq) = duplicate_of
+            && claimed.len() < max_messages
+            && let Some(original) = self.messages.get(seq)
+        {
+            let receipt_handle =
+                state
+                    .receipt_handles
+                    .issue(&self.name, &original.id, original.receive_count, now);
+            let duplicate = self
+                .messages
+                .duplicate(seq, receipt_handle)
+                .expect("duplicates are of in-flight messages");
+            self.messages.record(seq, || HistoryEntry {
+                receipt_handle: duplicate.receipt_handle.clone(),
+                visible_until: Some(duplicate.visible_from.timestamp_millis()),
+                ..HistoryEntry::new(HistoryKind::Received, now)
             });
+            info!(
+                queue = %self.name,
+                message_id = %duplicate.id,
+                "chaos: delivering a duplicate"
+            );
+            self.stats.duplicates_delivered += 1;
+            claimed.push(duplicate);
         }
-
-        if (Utc::now() - start_time).num_seconds() >= wait_time as i64 {
-            break;
+        Claimed {
+            messages: claimed,
+            dead_lettered,
         }
-
-        tokio::time::sleep(Duration::from_millis(100)).await;
     }
-
-    Ok(ReceiveMessageResponse {
-        messages: Vec::new(),
-    })
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct AddPermissionRequest {
+    #[serde(deserialize_with = "serde_helpers::deserialize_queue_url")]
     pub queue_url: String,
     pub label: String,
     #[serde(rename = "AWSAccountIds")]
@@ -549,64 +1392,171 @@ pub struct AddPermissionRequest {
     pub actions: Vec<String>,
 }
 
+/// Actions that `AddPermission` may grant. AWS rejects `*` here even though
+/// it is valid in a policy written by hand.
+const PERMISSION_ACTIONS: &[&str] = &[
+    "ChangeMessageVisibility",
+    "DeleteMessage",
+    "GetQueueAttributes",
+    "GetQueueUrl",
+    "ListDeadLetterSourceQueues",
+    "PurgeQueue",
+    "ReceiveMessage",
+    "SendMessage",
+];
+
+fn empty_policy() -> serde_json::Value {
+    serde_json::json!({
+        "Version": "2012-10-17",
+        "Statement": []
+    })
+}
+
+/// The queue's policy document, or an empty one if it has none.
+fn queue_policy(queue: &Queue) -> serde_json::Value {
+    let mut policy = queue
+        .attributes
+        .get("Policy")
+        .and_then(|policy| serde_json::from_str(policy).ok())
+        .unwrap_or_else(empty_policy);
+    // A policy set through SetQueueAttributes may hold a single statement
+    // object rather than a list.
+    policy["Statement"] = match policy["Statement"].take() {
+        serde_json::Value::Array(statements) => serde_json::Value::Array(statements),
+        serde_json::Value::Object(statement) => serde_json::json!([statement]),
+        _ => serde_json::json!([]),
+    };
+    policy
+}
+
+fn has_statement(policy: &serde_json::Value, label: &str) -> bool {
+    policy["Statement"]
+        .as_array()
+        .is_some_and(|statements| statements.iter().any(|s| s["Sid"] == label))
+}
+
 pub async fn add_permission(
     State(state): State<AppState>,
     Json(request): Json<AddPermissionRequest>,
-) -> Result<(), SqsError> {
-    match state.queues.get_mut(&request.queue_url) {
-        Some(mut queue) => {
-            let queue_name = queue.name.clone();
-            let policy_str = queue
-                .attributes
-                .entry("Policy".to_string())
-                .or_insert_with(|| {
-                    serde_json::to_string(&serde_json::json!({
-                        "Version": "2012-10-17",
-                        "Statement": []
-                    }))
-                    .unwrap()
-                });
-
-            let mut policy: serde_json::Value =
-                serde_json::from_str(policy_str).unwrap_or_else(|_| {
-                    serde_json::json!({
-                        "Version": "2012-10-17",
-                        "Statement": []
-                    })
-                });
+) -> Result<EmptyResponse, SqsError> {
+    let valid_label = (1..=80).contains(&request.label.len())
+        && request
+            .label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid_label {
+        state.validation.violation(SqsError::InvalidParameterValue(format!(
+            "Value {} for parameter Label is invalid.",
+            request.label
+        )))?;
+    }
+    if request.aws_account_ids.is_empty() {
+        state.validation.violation(SqsError::InvalidParameterValue(
+            "Value [] for parameter AWSAccountIds is invalid.".to_string(),
+        ))?;
+    }
+    if request.actions.is_empty() {
+        state.validation.violation(SqsError::InvalidParameterValue(
+            "Value [] for parameter Actions is invalid.".to_string(),
+        ))?;
+    }
+    if let Some(action) = request
+        .actions
+        .iter()
+        .find(|action| !PERMISSION_ACTIONS.contains(&action.as_str()))
+    {
+        state.validation.violation(SqsError::InvalidParameterValue(format!(
+            "Value SQS:{} for parameter ActionName is invalid. Reason: Only the queue owner is allowed to invoke this action.",
+            action
+        )))?;
+    }
 
-            if !policy["Statement"].is_array() {
-                policy["Statement"] = serde_json::json!([
-                ]);
+    state
+        .store
+        .try_update_selected(&request.queue_url, |_| Selection::none(), |queue| {
+            let mut policy = queue_policy(queue);
+            if has_statement(&policy, &request.label) {
+                return Err(SqsError::InvalidParameterValue(format!(
+                    "Value {} for parameter Label is invalid. Reason: Already exists.",
+                    request.label
+                )));
             }
 
+            let principals: Vec<String> = request
+                .aws_account_ids
+                .iter()
+                .map(|account| format!("arn:aws:iam::{}:root", account))
+                .collect();
+            let actions: Vec<String> = request
+                .actions
+                .iter()
+                .map(|action| format!("SQS:{}", action))
+                .collect();
             let statement = serde_json::json!({
                 "Sid": request.label,
                 "Effect": "Allow",
                 "Principal": {
-                    "AWS": request.aws_account_ids
+                    "AWS": principals
                 },
-                "Action": request.actions,
-                "Resource": format!("arn:aws:sqs:local:000000000000:{}", queue_name)
+                "Action": actions,
+                "Resource": queue.arn
             });
-
             policy["Statement"]
                 .as_array_mut()
-                .unwrap()
+                .expect("statement list")
                 .push(statement);
 
-            *policy_str = serde_json::to_string(&policy).unwrap();
+            queue
+                .attributes
+                .insert("Policy".to_string(), policy.to_string());
+            queue.touch(state.clock.now());
+            Ok(EmptyResponse {})
+        })
+        .await
+}
 
-            queue.last_modified_timestamp = Utc::now().timestamp();
-            Ok(())
-        }
-        None => Err(SqsError::QueueDoesNotExist),
-    }
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RemovePermissionRequest {
+    #[serde(deserialize_with = "serde_helpers::deserialize_queue_url")]
+    pub queue_url: String,
+    pub label: String,
+}
+
+pub async fn remove_permission(
+    State(state): State<AppState>,
+    Json(request): Json<RemovePermissionRequest>,
+) -> Result<EmptyResponse, SqsError> {
+    state
+        .store
+        .try_update_selected(&request.queue_url, |_| Selection::none(), |queue| {
+            let mut policy = queue_policy(queue);
+            if !has_statement(&policy, &request.label) {
+                return Err(SqsError::InvalidParameterValue(format!(
+                    "Value {} for parameter Label is invalid. Reason: can't find label.",
+                    request.label
+                )));
+            }
+
+            let statements = policy["Statement"].as_array_mut().expect("statement list");
+            statements.retain(|s| s["Sid"] != request.label.as_str());
+            if statements.is_empty() {
+                queue.attributes.remove("Policy");
+            } else {
+                queue
+                    .attributes
+                    .insert("Policy".to_string(), policy.to_string());
+            }
+            queue.touch(state.clock.now());
+            Ok(EmptyResponse {})
+        })
+        .await
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct SetQueueAttributesRequest {
+    #[serde(deserialize_with = "serde_helpers::deserialize_queue_url")]
     pub queue_url: String,
     pub attributes: HashMap<String, String>,
 }
@@ -614,32 +1564,236 @@ pub struct SetQueueAttributesRequest {
 pub async fn set_queue_attributes(
     State(state): State<AppState>,
     Json(request): Json<SetQueueAttributesRequest>,
-) -> Result<(), SqsError> {
-    match state.queues.get_mut(&request.queue_url) {
-        Some(mut queue) => {
-            if let Some(policy_str) = request.attributes.get("RedrivePolicy") {
-                if policy_str.is_empty() {
-                    queue.redrive_policy = None;
-                } else {
-                    use crate::state::RedrivePolicy;
-                    match serde_json::from_str::<RedrivePolicy>(policy_str) {
-                        Ok(policy) => queue.redrive_policy = Some(policy),
-                        Err(e) => {
-                            return Err(SqsError::InvalidParameterValue(format!(
-                                "Invalid value for RedrivePolicy: {}",
-                                e
-                            )))
-                        }
-                    }
-                }
-            }
+) -> Result<EmptyResponse, SqsError> {
+    // Resolved before locking the queue: the dead-letter queue is looked up
+    // in the same store, and may even be this queue.
+    let redrive_policy = match request.attributes.get("RedrivePolicy") {
+        Some(policy) => {
+            let is_fifo = state.store.read(&request.queue_url, |queue| queue.is_fifo())?;
+            Some(parse_redrive_policy(&state, policy, is_fifo)?)
+        }
+        None => None,
+    };
+    let redrive_allow_policy = match request.attributes.get("RedriveAllowPolicy") {
+        Some(policy) => Some(parse_redrive_allow_policy(&state, policy)?),
+        None => None,
+    };
+
+    let changes = AttributeChanges {
+        attributes: request.attributes,
+        redrive_policy,
+        redrive_allow_policy,
+    };
+    state
+        .store
+        .set_attributes(&state, &request.queue_url, changes)
+        .await?;
+    Ok(EmptyResponse {})
+}
+
+/// A `SetQueueAttributes` request, with its redrive policies resolved.
+#[derive(Debug)]
+pub struct AttributeChanges {
+    /// The attributes to set, as named in the request.
+    pub attributes: HashMap<String, String>,
+    /// The new `RedrivePolicy`, `Some(None)` to clear it.
+    pub redrive_policy: Option<Option<RedrivePolicy>>,
+    /// The new `RedriveAllowPolicy`, `Some(None)` to clear it.
+    pub redrive_allow_policy: Option<Option<RedriveAllowPolicy>>,
+}
+
+impl Queue {
+    /// `SetQueueAttributes` on this queue.
+    pub fn set_attributes(
+        &mut self,
+        state: &AppState,
+        changes: AttributeChanges,
+    ) -> Result<(), SqsError> {
+        for (name, value) in &changes.attributes {
+            state.validation.check(attributes::validate_settable(
+                name,
+                value,
+                self.is_fifo(),
+                state.max_message_size_limit,
+            ))?;
+        }
 
-            for (key, value) in request.attributes {
-                queue.attributes.insert(key, value);
+        let mut merged = self.attributes.clone();
+        merged.extend(changes.attributes.clone());
+        // Switching to KMS encryption turns SQS-managed encryption off
+        // unless the request says otherwise.
+        if changes.attributes.contains_key("KmsMasterKeyId")
+            && !changes.attributes.contains_key("SqsManagedSseEnabled")
+        {
+            merged.insert("SqsManagedSseEnabled".to_string(), "false".to_string());
+        }
+        state
+            .validation
+            .check(attributes::validate_combination(&merged))?;
+
+        if let Some(policy) = changes.redrive_policy {
+            self.redrive_policy = policy;
+        }
+        if let Some(policy) = changes.redrive_allow_policy {
+            self.redrive_allow_policy = policy;
+        }
+
+        if merged
+            .get("SqsManagedSseEnabled")
+            .is_some_and(|v| v == "false")
+        {
+            self.attributes
+                .insert("SqsManagedSseEnabled".to_string(), "false".to_string());
+        }
+        let event = state.event(
+            EventKind::AttributesChanged,
+            &self.name,
+            json!({ "attributes": changes.attributes }),
+        );
+        self.events.push(event);
+        for (key, value) in changes.attributes {
+            if key == "RedrivePolicy" || key == "RedriveAllowPolicy" {
+                // Kept in their typed fields, set above.
+                continue;
+            }
+            if value.is_empty() {
+                // Only JSON-valued attributes accept an empty value,
+                // which clears them.
+                self.attributes.remove(&key);
+            } else {
+                self.attributes.insert(key, value);
             }
-            queue.last_modified_timestamp = Utc::now().timestamp();
-            Ok(())
         }
-        None => Err(SqsError::QueueDoesNotExist),
+        self.touch(state.clock.now());
+        Ok(())
     }
 }
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListQueueTagsRequest {
+    #[serde(deserialize_with = "serde_helpers::deserialize_queue_url")]
+    pub queue_url: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListQueueTagsResponse {
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub tags: HashMap<String, String>,
+}
+
+pub async fn list_queue_tags(
+    State(state): State<AppState>,
+    Json(request): Json<ListQueueTagsRequest>,
+) -> Result<ListQueueTagsResponse, SqsError> {
+    state.store.read(&request.queue_url, |queue| ListQueueTagsResponse {
+        tags: queue.tags.clone(),
+    })
+}
+
+/// The most tags a queue may carry.
+pub const MAX_TAGS: usize = 50;
+const MAX_TAG_KEY_LENGTH: usize = 128;
+const MAX_TAG_VALUE_LENGTH: usize = 256;
+
+/// Checks tags being added to a queue that already has `existing`: keys of
+/// 1 to 128 characters without the reserved `aws:` prefix, values of at
+/// most 256 characters, and no more than 50 tags once merged.
+fn validate_tags(
+    existing: &HashMap<String, String>,
+    tags: &HashMap<String, String>,
+) -> Result<(), SqsError> {
+    // Sorted so that the reported tag doesn't depend on hash order.
+    let mut keys: Vec<&String> = tags.keys().collect();
+    keys.sort();
+    for key in keys {
+        let invalid = |reason: &str| {
+            SqsError::InvalidParameterValue(format!("Invalid tag key {:?}: {}.", key, reason))
+        };
+        let length = key.chars().count();
+        if length == 0 || length > MAX_TAG_KEY_LENGTH {
+            return Err(invalid("must be 1 to 128 characters long"));
+        }
+        if key.to_lowercase().starts_with("aws:") {
+            return Err(invalid("the aws: prefix is reserved"));
+        }
+        if tags[key].chars().count() > MAX_TAG_VALUE_LENGTH {
+            return Err(SqsError::InvalidParameterValue(format!(
+                "Invalid value for tag {:?}: must be at most 256 characters long.",
+                key
+            )));
+        }
+    }
+
+    let added = tags.keys().filter(|key| !existing.contains_key(*key)).count();
+    if existing.len() + added > MAX_TAGS {
+        return Err(SqsError::InvalidParameterValue(format!(
+            "Too many tags: a queue can have at most {} tags, and this would make {}.",
+            MAX_TAGS,
+            existing.len() + added
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TagQueueRequest {
+    #[serde(deserialize_with = "serde_helpers::deserialize_queue_url")]
+    pub queue_url: String,
+    pub tags: HashMap<String, String>,
+}
+
+pub async fn tag_queue(
+    State(state): State<AppState>,
+    Json(request): Json<TagQueueRequest>,
+) -> Result<EmptyResponse, SqsError> {
+    state
+        .store
+        .try_update_selected(&request.queue_url, |_| Selection::none(), |queue| {
+            state
+                .validation
+                .check(validate_tags(&queue.tags, &request.tags))?;
+            let event = state.event(
+                EventKind::TagsChanged,
+                &queue.name,
+                json!({ "added": request.tags }),
+            );
+            queue.events.push(event);
+            queue.tags.extend(request.tags);
+            queue.touch(state.clock.now());
+            Ok(EmptyResponse {})
+        })
+        .await
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UntagQueueRequest {
+    #[serde(deserialize_with = "serde_helpers::deserialize_queue_url")]
+    pub queue_url: String,
+    pub tag_keys: Vec<String>,
+}
+
+pub async fn untag_queue(
+    State(state): State<AppState>,
+    Json(request): Json<UntagQueueRequest>,
+) -> Result<EmptyResponse, SqsError> {
+    state
+        .store
+        .try_update_selected(&request.queue_url, |_| Selection::none(), |queue| {
+            for key in &request.tag_keys {
+                queue.tags.remove(key);
+            }
+            let event = state.event(
+                EventKind::TagsChanged,
+                &queue.name,
+                json!({ "removed": request.tag_keys }),
+            );
+            queue.events.push(event);
+            queue.touch(state.clock.now());
+            Ok(EmptyResponse {})
+        })
+        .await
+}
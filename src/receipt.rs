@@ -0,0 +1,102 @@
+use crate::error::SqsError;
+use crate::ids::Ids;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::Arc;
+
+/// What a receipt handle says about the receive that issued it. Handles are
+/// this struct as base64-encoded JSON, followed by `.` and an HMAC of that
+/// when the server has a [`Config::receipt_handle_secret`](crate::Config),
+/// so they can be told apart from garbage without any server state and read
+/// back when debugging.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceiptHandle {
+    /// Name of the queue the message was received from.
+    #[serde(rename = "q")]
+    pub queue: String,
+    #[serde(rename = "m")]
+    pub message_id: String,
+    /// The message's receive count when the handle was issued, so a handle
+    /// from an earlier receive is recognizably stale.
+    #[serde(rename = "g")]
+    pub receive_count: u32,
+    /// When the handle was issued, in epoch milliseconds.
+    #[serde(rename = "t")]
+    pub issued_at: i64,
+    /// Tells apart handles issued in the same millisecond for the same
+    /// receive, as duplicate deliveries are.
+    #[serde(rename = "n")]
+    pub nonce: u32,
+}
+
+/// Issues and resolves receipt handles, signing them if a secret is set.
+#[derive(Debug, Clone, Default)]
+pub struct ReceiptHandles {
+    secret: Option<Arc<[u8]>>,
+    ids: Ids,
+}
+
+impl ReceiptHandles {
+    /// Handles signed with `secret`, if any, whose nonces come from `ids`.
+    pub fn new(secret: Option<&str>, ids: Ids) -> Self {
+        Self {
+            secret: secret.map(|s| s.as_bytes().into()),
+            ids,
+        }
+    }
+
+    /// A fresh handle for the `receive_count`th receive of `message_id`.
+    pub fn issue(
+        &self,
+        queue: &str,
+        message_id: &str,
+        receive_count: u32,
+        now: DateTime<Utc>,
+    ) -> String {
+        let handle = ReceiptHandle {
+            queue: queue.to_string(),
+            message_id: message_id.to_string(),
+            receive_count,
+            issued_at: now.timestamp_millis(),
+            nonce: self.ids.nonce(),
+        };
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&handle).unwrap());
+        match self.mac(&payload) {
+            Some(mac) => {
+                let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+                format!("{}.{}", payload, signature)
+            }
+            None => payload,
+        }
+    }
+
+    /// Decodes a handle this server could have issued, failing with
+    /// `ReceiptHandleIsInvalid` for anything else. Whether the handle is
+    /// still current is up to the queue holding the message.
+    pub fn resolve(&self, handle: &str) -> Result<ReceiptHandle, SqsError> {
+        let invalid = || SqsError::ReceiptHandleIsInvalid(handle.to_string());
+        let (payload, signature) = match handle.split_once('.') {
+            Some((payload, signature)) => (payload, Some(signature)),
+            None => (handle, None),
+        };
+        if let Some(mac) = self.mac(payload) {
+            let signature = signature
+                .and_then(|s| URL_SAFE_NO_PAD.decode(s).ok())
+                .ok_or_else(invalid)?;
+            mac.verify_slice(&signature).map_err(|_| invalid())?;
+        }
+        let json = URL_SAFE_NO_PAD.decode(payload).map_err(|_| invalid())?;
+        serde_json::from_slice(&json).map_err(|_| invalid())
+    }
+
+    /// The HMAC of `payload`, if handles are signed.
+    fn mac(&self, payload: &str) -> Option<Hmac<Sha256>> {
+        let secret = self.secret.as_ref()?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC takes any key length");
+        mac.update(payload.as_bytes());
+        Some(mac)
+    }
+}
@@ -0,0 +1,255 @@
+use crate::config::ConfigFile;
+use crate::error::SqsError;
+use crate::fixtures::{self, Fixtures, QueueFixture};
+use crate::queue::{self, DeleteQueueRequest, SetQueueAttributesRequest};
+use crate::state::AppState;
+use crate::store::Selection;
+use crate::templates::{self, ExpandedQueue};
+use crate::webhooks;
+use axum::extract::State;
+use axum::Json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// How often the config file is checked for changes.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Default)]
+pub struct ReloadSummary {
+    pub created: usize,
+    pub updated: usize,
+    pub pruned: usize,
+}
+
+/// Brings the queues declared in `file` into the server.
+///
+/// Missing queues are created (and seeded with their messages) through the
+/// fixture loader. Existing queues get any declared attribute that differs
+/// from their current value through `SetQueueAttributes`, so both paths
+/// validate exactly as the API does. Queues that were declared in `previous`
+/// but no longer are deleted if `file.prune` is set and left alone otherwise.
+///
+/// Queues naming a template are expanded first, so that the dead-letter
+/// queues templates give count as declared, and the file's templates replace
+/// those `CreateQueue` requests can name.
+pub async fn apply(
+    state: &AppState,
+    file: &ConfigFile,
+    previous: Option<&ConfigFile>,
+) -> Result<ReloadSummary, SqsError> {
+    let mut summary = ReloadSummary::default();
+
+    if let Some(previous) = previous {
+        for setting in file.changed_settings(previous) {
+            warn!(
+                "config setting {} changed but is not reloadable; restart the server to apply it",
+                setting
+            );
+        }
+    }
+
+    // Dead-letter queues first, as `fixtures::load` does.
+    let mut declared_queues = templates::expand(state, &file.templates, file.queues.clone())?;
+    declared_queues.sort_by_key(|expanded| expanded.queue.attributes.contains_key("RedrivePolicy"));
+    for ExpandedQueue { queue, template } in &declared_queues {
+        apply_queue(state, queue, &mut summary).await.map_err(|e| match template {
+            Some(template) => templates::in_template(e, &queue.name, template),
+            None => e,
+        })?;
+    }
+    // Templates queues use were checked as they were applied; this catches
+    // mistakes in the rest before `CreateQueue` requests can name them.
+    templates::validate(state, &file.templates)?;
+    *state.templates.lock().unwrap() = file.templates.clone();
+
+    let Some(previous) = previous else {
+        return Ok(summary);
+    };
+    let previous_queues =
+        templates::expand(state, &previous.templates, previous.queues.clone())?;
+    let removed = previous_queues
+        .iter()
+        .map(|expanded| &expanded.queue)
+        .filter(|old| !declared_queues.iter().any(|e| e.queue.name == old.name));
+    for old in removed {
+        if !file.prune {
+            info!(
+                queue = %old.name,
+                "queue removed from config file; keeping it (set prune: true to delete)"
+            );
+            continue;
+        }
+        let result = queue::delete_queue(
+            State(state.clone()),
+            Json(DeleteQueueRequest {
+                queue_url: state.queue_url(&old.name),
+            }),
+        )
+        .await;
+        match result {
+            Ok(_) | Err(SqsError::QueueDoesNotExist) => {
+                info!(queue = %old.name, "deleted queue removed from config file");
+                summary.pruned += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Creates `declared` if it is missing, or brings its attributes and
+/// webhook in line with the declaration.
+async fn apply_queue(
+    state: &AppState,
+    declared: &QueueFixture,
+    summary: &mut ReloadSummary,
+) -> Result<(), SqsError> {
+    let url = state.queue_url(&declared.name);
+    if let Some(webhook) = &declared.webhook {
+        webhooks::validate_url(webhook)?;
+    }
+    let current = state
+        .store
+        .read(&url, |queue| (queue.configured_attributes(), queue.webhook.clone()));
+    let Ok((current, current_webhook)) = current else {
+        fixtures::load(
+            state,
+            Fixtures {
+                queues: vec![declared.clone()],
+            },
+        )
+        .await?;
+        info!(queue = %declared.name, "created queue declared in config file");
+        summary.created += 1;
+        return Ok(());
+    };
+
+    let changed: HashMap<String, String> = declared
+        .attributes
+        .iter()
+        .filter(|(name, value)| current.get(*name) != Some(*value))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    if current_webhook != declared.webhook
+        && state
+            .store
+            .update_selected(&url, |_| Selection::none(), |queue| {
+                queue.webhook = declared.webhook.clone()
+            })
+            .await
+            .is_ok()
+    {
+        info!(queue = %declared.name, "updated queue webhook from config file");
+    }
+
+    if !changed.is_empty() {
+        queue::set_queue_attributes(
+            State(state.clone()),
+            Json(SetQueueAttributesRequest {
+                queue_url: url,
+                attributes: changed,
+            }),
+        )
+        .await?;
+        info!(queue = %declared.name, "updated queue attributes from config file");
+        summary.updated += 1;
+    }
+    Ok(())
+}
+
+/// Spawns the task that reloads `path` whenever its contents change or, on
+/// Unix, the process receives `SIGHUP`. `loaded` is the file as already
+/// applied at startup and `contents` the text it was parsed from.
+///
+/// A file that fails to parse or apply is logged and otherwise ignored; the
+/// next successful reload is diffed against the last good one.
+pub fn spawn(
+    state: AppState,
+    path: PathBuf,
+    loaded: ConfigFile,
+    contents: String,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut current = loaded;
+        let mut last_contents = contents;
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut hangup = hangup_signal();
+
+        loop {
+            let forced = tokio::select! {
+                _ = state.shutdown.cancelled() => break,
+                _ = interval.tick() => false,
+                _ = recv_hangup(&mut hangup) => true,
+            };
+
+            let contents = match tokio::fs::read_to_string(&path).await {
+                Ok(contents) => contents,
+                Err(e) => {
+                    warn!("failed to read config file {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            if !forced && last_contents == contents {
+                continue;
+            }
+            last_contents = contents.clone();
+
+            let file = match ConfigFile::from_yaml(&contents) {
+                Ok(file) => file,
+                Err(e) => {
+                    warn!("ignoring invalid config file {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            match apply(&state, &file, Some(&current)).await {
+                Ok(summary) => {
+                    info!(
+                        created = summary.created,
+                        updated = summary.updated,
+                        pruned = summary.pruned,
+                        "reloaded config file {}",
+                        path.display()
+                    );
+                    current = file;
+                }
+                Err(e) => warn!("failed to apply config file {}: {}", path.display(), e),
+            }
+        }
+
+        debug!("config reload task stopped");
+    })
+}
+
+#[cfg(unix)]
+type Hangup = Option<tokio::signal::unix::Signal>;
+#[cfg(not(unix))]
+type Hangup = ();
+
+#[cfg(unix)]
+fn hangup_signal() -> Hangup {
+    use tokio::signal::unix::{SignalKind, signal};
+    signal(SignalKind::hangup()).ok()
+}
+
+#[cfg(not(unix))]
+fn hangup_signal() -> Hangup {}
+
+#[cfg(unix)]
+async fn recv_hangup(hangup: &mut Hangup) {
+    match hangup {
+        Some(signal) => {
+            signal.recv().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(not(unix))]
+async fn recv_hangup(_: &mut Hangup) {
+    std::future::pending().await
+}
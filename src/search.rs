@@ -0,0 +1,155 @@
+//! Filters for finding messages on a queue through the admin API, without
+//! receiving them.
+
+use crate::error::SqsError;
+use crate::state::Message;
+use chrono::{DateTime, Utc};
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+
+/// The longest `body_matches` pattern accepted.
+const MAX_PATTERN_LENGTH: usize = 1000;
+
+/// The most memory a compiled `body_matches` pattern may take. Matching
+/// takes time linear in the body whatever the pattern, so capping the
+/// pattern's size caps the cost of a search.
+const MAX_PATTERN_SIZE: usize = 1 << 20;
+
+/// Search criteria, as query parameters. Every one given must match.
+#[derive(Debug, Default, Deserialize)]
+pub struct SearchParams {
+    /// The body contains this.
+    pub body_contains: Option<String>,
+    /// The body matches this regular expression.
+    pub body_matches: Option<String>,
+    /// The message has this message attribute...
+    pub attribute_name: Option<String>,
+    /// ...with this string or number value.
+    pub attribute_value: Option<String>,
+    pub message_id: Option<String>,
+    /// The message has been received at least this many times.
+    pub min_receive_count: Option<u32>,
+    /// Sent at or after this time, in epoch milliseconds.
+    pub sent_after: Option<i64>,
+    /// Sent before this time, in epoch milliseconds.
+    pub sent_before: Option<i64>,
+    /// `visible`, `in_flight` or `delayed`.
+    pub state: Option<String>,
+    /// At most this many results.
+    pub limit: Option<usize>,
+    /// Where the previous page of results left off.
+    pub next_token: Option<String>,
+}
+
+/// Where a message is in its lifecycle. Lapsed delays and visibility
+/// timeouts count as visible, as they do in the queue's counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageState {
+    Visible,
+    InFlight,
+    Delayed,
+}
+
+impl MessageState {
+    pub fn of(message: &Message, now: DateTime<Utc>) -> Self {
+        if message.visible_from <= now {
+            MessageState::Visible
+        } else if message.receipt_handle.is_some() {
+            MessageState::InFlight
+        } else {
+            MessageState::Delayed
+        }
+    }
+}
+
+/// [`SearchParams`], checked and compiled.
+#[derive(Debug)]
+pub struct MessageFilter {
+    body_contains: Option<String>,
+    body_matches: Option<Regex>,
+    attribute: Option<(String, Option<String>)>,
+    message_id: Option<String>,
+    min_receive_count: Option<u32>,
+    sent_after: Option<i64>,
+    sent_before: Option<i64>,
+    state: Option<MessageState>,
+}
+
+impl MessageFilter {
+    pub fn new(params: SearchParams) -> Result<Self, SqsError> {
+        let body_matches = params.body_matches.as_deref().map(compile).transpose()?;
+        let attribute = match (params.attribute_name, params.attribute_value) {
+            (Some(name), value) => Some((name, value)),
+            (None, Some(_)) => {
+                return Err(SqsError::InvalidParameterValue(
+                    "attribute_value needs an attribute_name.".to_string(),
+                ));
+            }
+            (None, None) => None,
+        };
+        let state = match params.state.as_deref() {
+            None => None,
+            Some("visible") => Some(MessageState::Visible),
+            Some("in_flight") => Some(MessageState::InFlight),
+            Some("delayed") => Some(MessageState::Delayed),
+            Some(other) => {
+                return Err(SqsError::InvalidParameterValue(format!(
+                    "Invalid state {:?}; expected visible, in_flight or delayed.",
+                    other
+                )));
+            }
+        };
+        Ok(Self {
+            body_contains: params.body_contains,
+            body_matches,
+            attribute,
+            message_id: params.message_id,
+            min_receive_count: params.min_receive_count,
+            sent_after: params.sent_after,
+            sent_before: params.sent_before,
+            state,
+        })
+    }
+
+    /// Whether `message` meets every criterion as of `now`. The cheap ones
+    /// are checked before the body is looked at.
+    pub fn matches(&self, message: &Message, now: DateTime<Utc>) -> bool {
+        let sent = message.sent_timestamp.timestamp_millis();
+        if self.message_id.as_ref().is_some_and(|id| *id != message.id)
+            || self.min_receive_count.is_some_and(|min| message.receive_count < min)
+            || self.sent_after.is_some_and(|after| sent < after)
+            || self.sent_before.is_some_and(|before| sent >= before)
+            || self.state.is_some_and(|state| state != MessageState::of(message, now))
+        {
+            return false;
+        }
+        if let Some((name, value)) = &self.attribute {
+            let Some(attribute) = message.message_attributes.get(name) else {
+                return false;
+            };
+            if value.is_some() && attribute.string_value != *value {
+                return false;
+            }
+        }
+        if self.body_contains.is_none() && self.body_matches.is_none() {
+            return true;
+        }
+        let body = message.body.as_str();
+        self.body_contains.as_ref().is_none_or(|s| body.contains(s.as_str()))
+            && self.body_matches.as_ref().is_none_or(|re| re.is_match(&body))
+    }
+}
+
+fn compile(pattern: &str) -> Result<Regex, SqsError> {
+    if pattern.len() > MAX_PATTERN_LENGTH {
+        return Err(SqsError::InvalidParameterValue(format!(
+            "Invalid body_matches: longer than {} characters.",
+            MAX_PATTERN_LENGTH
+        )));
+    }
+    RegexBuilder::new(pattern)
+        .size_limit(MAX_PATTERN_SIZE)
+        .dfa_size_limit(MAX_PATTERN_SIZE)
+        .build()
+        .map_err(|e| SqsError::InvalidParameterValue(format!("Invalid body_matches: {}", e)))
+}
@@ -1,15 +1,101 @@
-use serde::{self, Deserialize, Deserializer, Serializer};
-use std::fmt::Display;
+use crate::namespaces;
+use crate::urls;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serializer};
+use std::fmt::{self, Display};
+use std::marker::PhantomData;
 use std::str::FromStr;
 
-pub fn deserialize_number_from_string<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+/// A number sent either as a JSON number or as a string holding one, since
+/// clients disagree on which to use for fields like `DelaySeconds` and
+/// `maxReceiveCount`.
+pub fn deserialize_number<'de, T, D>(deserializer: D) -> Result<T, D::Error>
 where
     T: FromStr,
     T::Err: Display,
     D: Deserializer<'de>,
 {
-    let s = String::deserialize(deserializer)?;
-    s.parse::<T>().map_err(serde::de::Error::custom)
+    deserializer.deserialize_any(NumberVisitor(PhantomData))
+}
+
+/// Like [`deserialize_number`], for optional fields; `null` is `None`.
+pub fn deserialize_optional_number<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: FromStr,
+    T::Err: Display,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_option(OptionalNumberVisitor(PhantomData))
+}
+
+struct NumberVisitor<T>(PhantomData<T>);
+
+impl<T> NumberVisitor<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    fn parse<E: de::Error>(value: &str) -> Result<T, E> {
+        value
+            .trim()
+            .parse()
+            .map_err(|e| E::custom(format!("invalid number {:?}: {}", value, e)))
+    }
+}
+
+impl<T> Visitor<'_> for NumberVisitor<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a number, or a string containing one")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<T, E> {
+        Self::parse(&value.to_string())
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<T, E> {
+        Self::parse(&value.to_string())
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<T, E> {
+        // Whole floats such as `10.0` print as `10` and parse as integers.
+        Self::parse(&value.to_string())
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<T, E> {
+        Self::parse(value)
+    }
+}
+
+struct OptionalNumberVisitor<T>(PhantomData<T>);
+
+impl<'de, T> Visitor<'de> for OptionalNumberVisitor<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    type Value = Option<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a number, a string containing one, or null")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Option<T>, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Option<T>, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Option<T>, D::Error> {
+        deserialize_number(deserializer).map(Some)
+    }
 }
 
 pub fn serialize_number_to_string<S, T>(n: &T, serializer: S) -> Result<S::Ok, S::Error>
@@ -19,3 +105,40 @@ where
 {
     serializer.serialize_str(&n.to_string())
 }
+
+/// A `QueueUrl` parameter, normalized so that it matches the stored URL,
+/// on this server's host and in the request's namespace.
+pub fn deserialize_queue_url<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let url = String::deserialize(deserializer)?;
+    let url = urls::on_this_server(urls::normalize_queue_url(&url));
+    Ok(namespaces::qualify_url(url))
+}
+
+/// A `QueueName` parameter, in the request's namespace.
+pub fn deserialize_queue_name<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let name = String::deserialize(deserializer)?;
+    Ok(namespaces::qualify(&name))
+}
+
+/// A duration such as `30s`, `500ms`, `5m` or `1h`; a bare number is
+/// seconds.
+pub fn parse_duration(value: &str) -> Option<chrono::Duration> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: i64 = amount.parse().ok()?;
+    match unit {
+        "ms" => chrono::Duration::try_milliseconds(amount),
+        "" | "s" => chrono::Duration::try_seconds(amount),
+        "m" => chrono::Duration::try_minutes(amount),
+        "h" => chrono::Duration::try_hours(amount),
+        _ => None,
+    }
+}
@@ -0,0 +1,412 @@
+use crate::config::{Config, ConfigFile};
+use crate::admin;
+use crate::attributes;
+use crate::dispatch;
+use crate::error::SqsError;
+use crate::events;
+use crate::fixtures::{self, Fixtures};
+use crate::generators;
+use crate::limits::LimitedListener;
+use crate::maintenance;
+use crate::metrics;
+use crate::namespaces;
+use crate::reload;
+use crate::socket_activation;
+use crate::state::AppState;
+use crate::store::redis::RedisStore;
+use crate::store::sqlite::SqliteStore;
+use crate::store::Storage;
+use crate::telemetry;
+use crate::urls;
+use crate::webhooks;
+use axum::body::Bytes;
+use axum::extract::rejection::BytesRejection;
+use axum::extract::DefaultBodyLimit;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{extract::State, Router};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, trace, Instrument};
+
+/// Stops a server started with [`serve`].
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    state: AppState,
+}
+
+impl ShutdownHandle {
+    /// Stops accepting connections, lets in-flight requests finish and stops
+    /// the maintenance task. Await the server's `JoinHandle` to wait for it.
+    pub fn shutdown(&self) {
+        self.state.shut_down();
+    }
+}
+
+/// Serves the sockets passed by a service manager if the process was
+/// socket-activated (see [`crate::socket_activation`]); otherwise binds
+/// `config.bind`, or `config.host:config.port` (port 0 picks a free port).
+/// Then runs the server and its maintenance task on background tasks,
+/// serving every address bound.
+///
+/// Returns the bound addresses, a handle that resolves once the server has
+/// fully stopped, and a handle to stop it.
+pub async fn serve(
+    mut config: Config,
+) -> std::io::Result<(Vec<SocketAddr>, JoinHandle<()>, ShutdownHandle)> {
+    attributes::validate_message_size_limit(config.max_message_size_limit).map_err(|e| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("invalid maximum message size limit: {}", e),
+        )
+    })?;
+    attributes::validate_server_defaults(
+        &config.default_queue_attributes,
+        config.max_message_size_limit,
+    )
+    .map_err(|e| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("invalid default queue attributes: {}", e),
+        )
+    })?;
+    metrics::validate_buckets(&config.histogram_buckets).map_err(|e| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("invalid histogram buckets: {}", e),
+        )
+    })?;
+    config.chaos.validate().map_err(|e| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("invalid chaos settings: {}", e),
+        )
+    })?;
+
+    let activated = socket_activation::take_listeners()?;
+    let socket_activated = activated.is_some();
+    let listeners = match activated {
+        Some(listeners) => listeners,
+        None => bind(&config).await?,
+    };
+    let addrs = listeners
+        .iter()
+        .map(TcpListener::local_addr)
+        .collect::<std::io::Result<Vec<_>>>()?;
+    // With port 0 the OS picks the port, and with socket activation the
+    // service manager does; queue URLs must embed the real one, which every
+    // listener must then share.
+    if let Some(other) = addrs.iter().find(|addr| addr.port() != addrs[0].port()) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "all listening addresses must have the port queue URLs carry: {} and {} differ",
+                addrs[0], other
+            ),
+        ));
+    }
+    config.port = addrs[0].port();
+    if socket_activated {
+        info!("using {} sockets passed by the service manager", listeners.len());
+        // An inherited socket bound to one address is only reachable there,
+        // which `localhost` may not resolve to.
+        if config.host == "localhost" && !addrs[0].ip().is_unspecified() {
+            config.host = addrs[0].ip().to_string();
+        }
+    }
+
+    let state = open_state(&config).await?;
+    info!(seed = state.chaos_rng.seed(), "chaos RNG seeded");
+    if let Some(seed) = config.deterministic_ids {
+        info!(seed, "generating deterministic IDs");
+    }
+
+    if let Some(path) = &config.fixtures {
+        let contents = tokio::fs::read_to_string(path).await?;
+        let fixtures = Fixtures::from_yaml(&contents)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let summary = fixtures::load(&state, fixtures).await.map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("failed to load fixtures from {}: {}", path.display(), e),
+            )
+        })?;
+        info!(
+            "loaded {} queues and {} messages from {}",
+            summary.queues,
+            summary.messages,
+            path.display()
+        );
+    }
+
+    let reloader = match &config.config_file {
+        Some(path) => {
+            let contents = tokio::fs::read_to_string(path).await?;
+            let file = ConfigFile::from_yaml(&contents).map_err(|e| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("invalid config file {}: {}", path.display(), e),
+                )
+            })?;
+            let summary = reload::apply(&state, &file, None).await.map_err(|e| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("failed to apply config file {}: {}", path.display(), e),
+                )
+            })?;
+            info!("created {} queues from {}", summary.created, path.display());
+            for spec in &file.generators {
+                generators::start(&state, spec.clone()).map_err(|e| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("invalid generator in {}: {}", path.display(), e),
+                    )
+                })?;
+            }
+            Some(reload::spawn(state.clone(), path.clone(), file, contents))
+        }
+        None => None,
+    };
+
+    let shutdown = ShutdownHandle {
+        state: state.clone(),
+    };
+
+    let listening: Vec<String> = addrs.iter().map(SocketAddr::to_string).collect();
+    info!("listening on {}", listening.join(", "));
+
+    maintenance::spawn(state.clone());
+    let webhooks = webhooks::spawn(state.clone());
+    let token = state.shutdown.clone();
+    let app = if state.base_path.is_empty() {
+        sqs_router(state.clone())
+    } else {
+        Router::new().nest(&format!("{}/", state.base_path), sqs_router(state.clone()))
+    };
+    // Connection limits are shared, so they hold across all the listeners.
+    let listeners: Vec<JoinHandle<()>> = listeners
+        .into_iter()
+        .map(|listener| {
+            let listener = LimitedListener::new(
+                listener,
+                state.max_connections,
+                state.connections.clone(),
+                state.rejections.clone(),
+            );
+            let serve = axum::serve(listener, app.clone())
+                .with_graceful_shutdown(token.clone().cancelled_owned());
+            tokio::spawn(async move {
+                if let Err(e) = serve.await {
+                    tracing::error!("server error: {}", e);
+                }
+            })
+        })
+        .collect();
+    let server = tokio::spawn(async move {
+        for listener in listeners {
+            listener.await.ok();
+        }
+        if let Some(maintenance) = state.shut_down() {
+            maintenance.await.ok();
+        }
+        webhooks.await.ok();
+        if let Some(reloader) = reloader {
+            reloader.await.ok();
+        }
+    });
+
+    Ok((addrs, server, shutdown))
+}
+
+/// Listeners for `config.bind`, where addresses after the first with port 0
+/// get the port the first was given, or for `config.host` and `config.port`. At
+/// the default host of `localhost` that is one listener on `127.0.0.1` and,
+/// where IPv6 is available, one on `::1` with the same port, so that clients
+/// resolving `localhost` to either reach the server.
+async fn bind(config: &Config) -> std::io::Result<Vec<TcpListener>> {
+    if !config.bind.is_empty() {
+        let mut listeners: Vec<TcpListener> = Vec::with_capacity(config.bind.len());
+        for addr in &config.bind {
+            let port = match listeners.first() {
+                Some(first) => first.local_addr()?.port(),
+                None => 0,
+            };
+            let listener = match addr.parse::<SocketAddr>() {
+                Ok(parsed) if parsed.port() == 0 => {
+                    TcpListener::bind(SocketAddr::new(parsed.ip(), port)).await
+                }
+                _ => TcpListener::bind(addr.as_str()).await,
+            };
+            let listener = listener.map_err(|e| {
+                std::io::Error::new(e.kind(), format!("failed to bind {}: {}", addr, e))
+            })?;
+            listeners.push(listener);
+        }
+        return Ok(listeners);
+    }
+    if config.host != "localhost" {
+        return Ok(vec![TcpListener::bind((config.host.as_str(), config.port)).await?]);
+    }
+    let ipv4 = TcpListener::bind((Ipv4Addr::LOCALHOST, config.port)).await?;
+    let port = ipv4.local_addr()?.port();
+    let mut listeners = vec![ipv4];
+    match TcpListener::bind((Ipv6Addr::LOCALHOST, port)).await {
+        Ok(ipv6) => listeners.push(ipv6),
+        Err(e) => debug!("not listening on [::1]:{}: {}", port, e),
+    }
+    Ok(listeners)
+}
+
+/// State for `config`, with the queues kept where `config.storage` says.
+async fn open_state(config: &Config) -> std::io::Result<AppState> {
+    match config.storage {
+        Storage::Memory => Ok(AppState::new(config)),
+        Storage::Sqlite => {
+            let path = &config.db_path;
+            let store = SqliteStore::open(path).map_err(|e| {
+                std::io::Error::other(format!("failed to open {}: {}", path.display(), e))
+            })?;
+            let store = Arc::new(store);
+            let state = AppState::with_store(config, store.clone());
+            let summary = store.load(&state).await.map_err(|e| {
+                std::io::Error::other(format!(
+                    "failed to load queues from {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            info!(
+                "loaded {} queues and {} messages from {}",
+                summary.queues,
+                summary.messages,
+                path.display()
+            );
+            Ok(state)
+        }
+        Storage::Redis => {
+            let url = &config.redis_url;
+            let store = RedisStore::open(url).map_err(|e| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("invalid Redis URL {}: {}", url, e),
+                )
+            })?;
+            let store = Arc::new(store);
+            let state = AppState::with_store(config, store.clone());
+            let summary = store.load(&state).await.map_err(|e| {
+                std::io::Error::other(format!("failed to load queues from {}: {}", url, e))
+            })?;
+            info!(
+                "loaded {} queues and {} messages from {}",
+                summary.queues, summary.messages, url
+            );
+            Ok(state)
+        }
+    }
+}
+
+/// The SQS endpoint as a router, for mounting into an existing axum app
+/// instead of calling [`serve`]:
+///
+/// ```no_run
+/// # async fn run(listener: tokio::net::TcpListener) {
+/// let mut config = local_sqs::Config::default();
+/// config.port = listener.local_addr().unwrap().port();
+/// config.base_path = "/sqs".to_string();
+/// let state = local_sqs::AppState::new(&config);
+/// local_sqs::maintenance::spawn(state.clone());
+/// local_sqs::webhooks::spawn(state.clone());
+/// let app = axum::Router::new().nest("/sqs/", local_sqs::sqs_router(state));
+/// axum::serve(listener, app).await.unwrap();
+/// # }
+/// ```
+///
+/// Nest it with a trailing slash: the SDKs post to `/sqs/` given an endpoint
+/// of `http://host/sqs`. `config.host`, `port` and `base_path` must match
+/// where the router ends up, since queue URLs are built from them. The
+/// `/_admin` API is included unless `config.admin_api` is off.
+pub fn sqs_router(state: AppState) -> Router {
+    let body_limit = DefaultBodyLimit::max(request_body_limit(state.max_message_size_limit));
+    let mut router = Router::new().route("/", post(handler).layer(body_limit));
+    if state.admin_api {
+        router = router.nest("/_admin", admin::router());
+    }
+    router.with_state(state)
+}
+
+/// The largest request body accepted, in bytes, given
+/// [`Config::max_message_size_limit`]. A batch may carry that many message
+/// bytes in total; JSON escaping can triple them, and the rest leaves room
+/// for everything else in a request.
+pub fn request_body_limit(max_message_size_limit: usize) -> usize {
+    max_message_size_limit * 4
+}
+
+async fn handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Result<Bytes, BytesRejection>,
+) -> Response {
+    // Borrowed from the request bytes rather than copied into a `String`;
+    // message bodies are copied once, when the request is deserialized.
+    let bytes = match body {
+        Ok(bytes) => bytes,
+        Err(rejection) if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+            let limit = request_body_limit(state.max_message_size_limit);
+            return SqsError::RequestEntityTooLarge(limit).into_response();
+        }
+        Err(rejection) => {
+            return SqsError::InvalidParameterValue(rejection.body_text()).into_response();
+        }
+    };
+    let Ok(body) = std::str::from_utf8(&bytes) else {
+        return SqsError::InvalidParameterValue("Request body is not valid UTF-8.".to_string())
+            .into_response();
+    };
+    let target = headers
+        .get("X-Amz-Target")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    debug!(target);
+    trace!(body);
+
+    let actions = dispatch::sqs_actions();
+    match target.strip_prefix("AmazonSQS.") {
+        Some(action) if actions.contains(action) => {
+            let Some(_permit) = state.requests.try_acquire(state.max_concurrent_requests) else {
+                state.rejections.requests.fetch_add(1, Ordering::Relaxed);
+                return SqsError::ServiceUnavailable("Too many concurrent requests.".to_string())
+                    .into_response();
+            };
+            let namespace = headers
+                .get(namespaces::NAMESPACE_HEADER)
+                .map(|v| v.to_str().unwrap_or_default().to_string());
+            if let Some(namespace) = &namespace
+                && let Err(e) = namespaces::validate(namespace)
+            {
+                return e.into_response();
+            }
+            let request_id = state.ids.uuid().to_string();
+            let span = telemetry::request_span(action, &request_id, &headers, body);
+            let server = state.queue_url("");
+            let dispatch = actions.dispatch(state, action, &headers, body);
+            let dispatch = urls::scope(&server, namespaces::scope(namespace, dispatch));
+            let mut response = events::with_request_id(request_id.clone(), dispatch)
+                .instrument(span.clone())
+                .await;
+            // An event inside the span, so that its fields are logged once
+            // the handler has filled them in.
+            span.in_scope(|| debug!(status = response.status().as_u16(), "request handled"));
+            if let Ok(value) = HeaderValue::from_str(&request_id) {
+                response.headers_mut().insert("x-amzn-requestid", value);
+            }
+            response
+        }
+        _ => SqsError::InvalidAction(target.to_string()).into_response(),
+    }
+}
@@ -0,0 +1,422 @@
+use crate::chaos::ChaosSettings;
+use crate::deduplication::{Accepted, DeduplicationCache};
+use crate::error::SqsError;
+use crate::events::EventLog;
+use crate::history::DeletedHistories;
+use crate::messages::MessageStore;
+use crate::metrics::QueueLatency;
+use crate::state::{
+    AppState, DeadLetterInfo, Message, MessageAttributeValue, Queue, QueueStats,
+    RedriveAllowPolicy, RedrivePolicy,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::Ordering;
+
+/// The snapshot format this build writes. Bump it whenever the format
+/// changes, adding a migration from the previous version to [`MIGRATIONS`].
+pub const SNAPSHOT_VERSION: u32 = 5;
+
+/// `MIGRATIONS[i]` rewrites a version `i + 1` snapshot into version `i + 2`.
+const MIGRATIONS: &[fn(&mut Value)] =
+    &[migrate_v1_to_v2, migrate_v2_to_v3, migrate_v3_to_v4, migrate_v4_to_v5];
+
+/// A point-in-time copy of every queue and message, including in-flight and
+/// delayed state. Maps are ordered and queues sorted by name so that the same
+/// state always serializes to the same bytes.
+///
+/// Read snapshots with [`parse`], which upgrades older formats.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    /// The format version, [`SNAPSHOT_VERSION`] when written by this build.
+    pub version: u32,
+    pub queues: Vec<QueueSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueSnapshot {
+    pub name: String,
+    pub attributes: BTreeMap<String, String>,
+    pub tags: BTreeMap<String, String>,
+    pub created_timestamp: i64,
+    pub last_modified_timestamp: i64,
+    pub paused: bool,
+    pub messages: Vec<MessageSnapshot>,
+    /// FIFO deduplication IDs still suppressing repeated sends, in order of
+    /// expiry.
+    pub deduplication: Vec<DeduplicationSnapshot>,
+    pub webhook: Option<String>,
+    pub chaos: Option<ChaosSettings>,
+    pub stats: QueueStats,
+    /// The last sequence number handed out, zero if none has been.
+    pub sequence_number: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageSnapshot {
+    pub id: String,
+    pub receipt_handle: Option<String>,
+    pub body: String,
+    pub md5_of_body: String,
+    pub attributes: BTreeMap<String, String>,
+    pub message_attributes: BTreeMap<String, MessageAttributeValue>,
+    pub md5_of_message_attributes: String,
+    pub visible_from: DateTime<Utc>,
+    pub sent_timestamp: DateTime<Utc>,
+    pub receive_count: u32,
+    pub first_received: Option<DateTime<Utc>>,
+    pub dead_letter: Option<DeadLetterInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeduplicationSnapshot {
+    /// The message group the ID is scoped to, with a `DeduplicationScope` of
+    /// `messageGroup`.
+    pub message_group_id: Option<String>,
+    pub deduplication_id: String,
+    pub message_id: String,
+    pub sequence_number: String,
+    pub expires: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Discard all current queues and install the snapshot in their place.
+    #[default]
+    Replace,
+    /// Add the snapshot's queues, overwriting existing queues with the same name.
+    Merge,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportSummary {
+    pub queues: usize,
+    pub messages: usize,
+}
+
+impl From<&Queue> for QueueSnapshot {
+    fn from(queue: &Queue) -> Self {
+        Self {
+            name: queue.name.clone(),
+            attributes: queue.configured_attributes().into_iter().collect(),
+            tags: queue.tags.clone().into_iter().collect(),
+            created_timestamp: queue.created_timestamp,
+            last_modified_timestamp: queue.last_modified_timestamp,
+            paused: queue.paused,
+            messages: queue.messages.iter().map(MessageSnapshot::from).collect(),
+            deduplication: queue
+                .deduplication
+                .entries()
+                .into_iter()
+                .map(|((message_group_id, deduplication_id), accepted, expires)| {
+                    DeduplicationSnapshot {
+                        message_group_id,
+                        deduplication_id,
+                        message_id: accepted.message_id,
+                        sequence_number: accepted.sequence_number,
+                        expires,
+                    }
+                })
+                .collect(),
+            webhook: queue.webhook.clone(),
+            chaos: queue.chaos,
+            stats: queue.stats.clone(),
+            sequence_number: queue.sequence_number,
+        }
+    }
+}
+
+impl From<&Message> for MessageSnapshot {
+    fn from(message: &Message) -> Self {
+        Self {
+            id: message.id.clone(),
+            receipt_handle: message.receipt_handle.clone(),
+            body: message.body.as_str().into_owned(),
+            md5_of_body: message.md5_of_body.clone(),
+            attributes: message.attributes.clone().into_iter().collect(),
+            message_attributes: message.message_attributes.clone().into_iter().collect(),
+            md5_of_message_attributes: message.md5_of_message_attributes.clone(),
+            visible_from: message.visible_from,
+            sent_timestamp: message.sent_timestamp,
+            receive_count: message.receive_count,
+            first_received: message.first_received,
+            dead_letter: message.dead_letter.clone(),
+        }
+    }
+}
+
+impl From<MessageSnapshot> for Message {
+    fn from(message: MessageSnapshot) -> Self {
+        let message_group_id = message.attributes.get("MessageGroupId").cloned();
+        Self {
+            id: message.id,
+            receipt_handle: message.receipt_handle,
+            body: message.body.into(),
+            md5_of_body: message.md5_of_body,
+            attributes: message.attributes.into_iter().collect(),
+            message_attributes: message.message_attributes.into_iter().collect(),
+            md5_of_message_attributes: message.md5_of_message_attributes,
+            visible_from: message.visible_from,
+            sent_timestamp: message.sent_timestamp,
+            receive_count: message.receive_count,
+            first_received: message.first_received,
+            message_group_id,
+            trace_context: None,
+            claimed_by: None,
+            seq: 0,
+            history: None,
+            dead_letter: message.dead_letter,
+        }
+    }
+}
+
+impl QueueSnapshot {
+    /// Builds the queue this snapshot describes, at `url`.
+    pub(crate) fn into_queue(mut self, state: &AppState, url: String) -> Result<Queue, SqsError> {
+        let redrive_policy = match self.attributes.remove("RedrivePolicy") {
+            Some(policy_str) => Some(
+                serde_json::from_str::<RedrivePolicy>(&policy_str).map_err(|e| {
+                    SqsError::InvalidParameterValue(format!(
+                        "Invalid value for RedrivePolicy on queue {}: {}",
+                        self.name, e
+                    ))
+                })?,
+            ),
+            None => None,
+        };
+        let redrive_allow_policy = match self.attributes.remove("RedriveAllowPolicy") {
+            Some(policy_str) => Some(
+                serde_json::from_str::<RedriveAllowPolicy>(&policy_str).map_err(|e| {
+                    SqsError::InvalidParameterValue(format!(
+                        "Invalid value for RedriveAllowPolicy on queue {}: {}",
+                        self.name, e
+                    ))
+                })?,
+            ),
+            None => None,
+        };
+
+        let now = state.clock.now();
+        let mut messages = MessageStore::default();
+        for message in self.messages {
+            let mut message = Message::from(message);
+            message.compress_body(state.compress_bodies_above);
+            messages.push(message, now);
+        }
+        let stored_bytes = messages.iter().map(Message::size).sum();
+        // Numbering carries on after the imported messages and after any
+        // deduplication ID still answering for a deleted one, which older
+        // snapshots leave to be worked out.
+        let numbered = messages
+            .iter()
+            .filter_map(|m| m.attributes.get("SequenceNumber"))
+            .chain(self.deduplication.iter().map(|entry| &entry.sequence_number))
+            .filter_map(|number| number.parse().ok())
+            .max()
+            .unwrap_or(0);
+        let sequence_number = self.sequence_number.max(numbered);
+        let mut deduplication = DeduplicationCache::default();
+        self.deduplication.sort_by_key(|entry| entry.expires);
+        for entry in self.deduplication {
+            let accepted = Accepted {
+                message_id: entry.message_id,
+                sequence_number: entry.sequence_number,
+            };
+            let key = (entry.message_group_id, entry.deduplication_id);
+            deduplication.restore(key, accepted, entry.expires);
+        }
+
+        Ok(Queue {
+            arn: state.queue_arn(&self.name),
+            name: self.name,
+            url,
+            messages,
+            attributes: self.attributes.into_iter().collect(),
+            tags: self.tags.into_iter().collect(),
+            created_timestamp: self.created_timestamp,
+            last_modified_timestamp: self.last_modified_timestamp,
+            redrive_policy,
+            redrive_allow_policy,
+            stats: self.stats,
+            latency: QueueLatency::new(state.histogram_buckets.clone()),
+            stored_bytes,
+            total_bytes: state.total_bytes.clone(),
+            notify: Default::default(),
+            long_polls: Default::default(),
+            purge_generation: 0,
+            purged_until: None,
+            deduplication,
+            sequence_number,
+            webhook: self.webhook,
+            paused: self.paused,
+            chaos: self.chaos,
+            last_used: now,
+            events: EventLog::new(state.event_log_capacity),
+            deleted_histories: DeletedHistories::new(state.event_log_capacity),
+        })
+    }
+}
+
+/// Queues deleted while the export runs are left out of it.
+pub async fn export(state: &AppState) -> StateSnapshot {
+    let mut queues = Vec::new();
+    for url in state.store.urls() {
+        if let Ok(queue) = state.store.inspect(&url, |q| QueueSnapshot::from(q)).await {
+            queues.push(queue);
+        }
+    }
+    queues.sort_by(|a, b| a.name.cmp(&b.name));
+    StateSnapshot {
+        version: SNAPSHOT_VERSION,
+        queues,
+    }
+}
+
+/// Rebuilds queues from `snapshot`. Every dead-letter target referenced by a
+/// redrive policy must be part of the snapshot (or, when merging, already
+/// exist). In [`ImportMode::Replace`] the new queues are built off to the
+/// side and swapped in as a whole.
+pub async fn import(
+    state: &AppState,
+    snapshot: StateSnapshot,
+    mode: ImportMode,
+) -> Result<ImportSummary, SqsError> {
+    let mut summary = ImportSummary::default();
+    let mut imported = HashMap::new();
+
+    for queue_snapshot in snapshot.queues {
+        let url = state.queue_url(&queue_snapshot.name);
+        let queue = queue_snapshot.into_queue(state, url.clone())?;
+        summary.queues += 1;
+        summary.messages += queue.messages.len();
+        imported.insert(url, queue);
+    }
+
+    let mut known_arns: HashSet<String> = imported.values().map(|q| q.arn.clone()).collect();
+    if let ImportMode::Merge = mode {
+        known_arns.extend(state.store.collect(|q| Some(q.arn.clone())));
+    }
+    for queue in imported.values() {
+        if let Some(rp) = &queue.redrive_policy
+            && !known_arns.contains(&rp.dead_letter_target_arn)
+        {
+            return Err(SqsError::InvalidParameterValue(format!(
+                "Dead-letter queue {} referenced by queue {} does not exist",
+                rp.dead_letter_target_arn, queue.name
+            )));
+        }
+    }
+
+    match mode {
+        ImportMode::Replace => {
+            let imported_bytes: u64 = imported.values().map(|q| q.stored_bytes).sum();
+            let old = state.store.replace_all(imported.into_values().collect()).await?;
+            state.total_bytes.fetch_add(imported_bytes, Ordering::Relaxed);
+            for queue in old {
+                queue.release_usage();
+                queue.notify.notify_waiters();
+            }
+        }
+        ImportMode::Merge => {
+            for queue in imported.into_values() {
+                let imported_bytes = queue.stored_bytes;
+                let old = state.store.put_queue(queue).await?;
+                state.total_bytes.fetch_add(imported_bytes, Ordering::Relaxed);
+                if let Some(old) = old {
+                    old.release_usage();
+                    old.notify.notify_waiters();
+                }
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Reads a JSON snapshot of any version up to [`SNAPSHOT_VERSION`],
+/// migrating older formats. Snapshots without a `version` are version 1,
+/// the format from before versioning.
+pub fn parse(json: &str) -> Result<StateSnapshot, SqsError> {
+    let invalid = |e: String| SqsError::InvalidParameterValue(format!("Invalid snapshot: {}", e));
+    let mut snapshot: Value = serde_json::from_str(json).map_err(|e| invalid(e.to_string()))?;
+    let Some(fields) = snapshot.as_object_mut() else {
+        return Err(invalid("expected a JSON object".to_string()));
+    };
+    let version = match fields.get("version") {
+        None => 1,
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .filter(|v| *v >= 1)
+            .ok_or_else(|| invalid(format!("invalid version {}", v)))?,
+    };
+    if version > SNAPSHOT_VERSION {
+        return Err(invalid(format!(
+            "format version {} is newer than this server supports ({})",
+            version, SNAPSHOT_VERSION
+        )));
+    }
+    for migrate in &MIGRATIONS[version as usize - 1..] {
+        migrate(&mut snapshot);
+    }
+    snapshot["version"] = SNAPSHOT_VERSION.into();
+    serde_json::from_value(snapshot).map_err(|e| invalid(e.to_string()))
+}
+
+/// Version 1 predates queue tags, pausing and first-receive times: queues get
+/// no tags and are not paused, and messages are treated as never received
+/// for latency purposes.
+fn migrate_v1_to_v2(snapshot: &mut Value) {
+    for queue in objects(snapshot.get_mut("queues")) {
+        queue.entry("tags").or_insert_with(|| Value::Object(Map::new()));
+        queue.entry("paused").or_insert(Value::Bool(false));
+        for message in objects(queue.get_mut("messages")) {
+            message.entry("first_received").or_insert(Value::Null);
+        }
+    }
+}
+
+/// Version 2 predates dead-letter metadata: messages are treated as never
+/// moved, though dead-lettered ones keep their `DeadLetterQueueSourceArn`.
+fn migrate_v2_to_v3(snapshot: &mut Value) {
+    for queue in objects(snapshot.get_mut("queues")) {
+        for message in objects(queue.get_mut("messages")) {
+            message.entry("dead_letter").or_insert(Value::Null);
+        }
+    }
+}
+
+/// Version 3 predates FIFO deduplication IDs: queues are imported without
+/// any, so repeated sends are accepted again.
+fn migrate_v3_to_v4(snapshot: &mut Value) {
+    for queue in objects(snapshot.get_mut("queues")) {
+        queue.entry("deduplication").or_insert_with(|| Value::Array(Vec::new()));
+    }
+}
+
+/// Version 4 predates queue webhooks, chaos overrides, counters and the
+/// sequence number: queues get none of the first two, counters start from
+/// zero, and numbering carries on from the queue's messages and
+/// deduplication IDs.
+fn migrate_v4_to_v5(snapshot: &mut Value) {
+    let stats = serde_json::to_value(QueueStats::default()).expect("counters serialize");
+    for queue in objects(snapshot.get_mut("queues")) {
+        queue.entry("webhook").or_insert(Value::Null);
+        queue.entry("chaos").or_insert(Value::Null);
+        queue.entry("stats").or_insert_with(|| stats.clone());
+        queue.entry("sequence_number").or_insert(0.into());
+    }
+}
+
+/// The objects in a JSON array, skipping anything else; malformed input is
+/// left for deserialization to reject.
+fn objects(array: Option<&mut Value>) -> impl Iterator<Item = &mut Map<String, Value>> {
+    array
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object_mut)
+}
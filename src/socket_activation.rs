@@ -0,0 +1,87 @@
+//! Socket activation: serving on listening sockets inherited from a service
+//! manager such as systemd instead of binding our own, following the
+//! `LISTEN_PID`/`LISTEN_FDS` protocol of `sd_listen_fds(3)`. The manager
+//! owns the port and starts the server on the first connection; the sockets
+//! arrive as file descriptors 3 onwards.
+
+use std::io::{Error, ErrorKind, Result};
+use std::ops::Range;
+use tokio::net::TcpListener;
+
+/// The first file descriptor passed by the service manager.
+pub const LISTEN_FDS_START: i32 = 3;
+
+/// The file descriptors passed to process `pid`, given the values of
+/// `LISTEN_PID` and `LISTEN_FDS`. `None` when nothing was passed, or when it
+/// was passed to another process, such as a parent that left its
+/// environment behind.
+pub fn listen_fds(
+    listen_pid: Option<&str>,
+    listen_fds: Option<&str>,
+    pid: u32,
+) -> Result<Option<Range<i32>>> {
+    let (Some(listen_pid), Some(listen_fds)) = (listen_pid, listen_fds) else {
+        return Ok(None);
+    };
+    let invalid = |name: &str, value: &str| {
+        Error::new(ErrorKind::InvalidInput, format!("invalid {}: {:?}", name, value))
+    };
+    let listen_pid: u32 =
+        listen_pid.trim().parse().map_err(|_| invalid("LISTEN_PID", listen_pid))?;
+    if listen_pid != pid {
+        return Ok(None);
+    }
+    let count: i32 = listen_fds.trim().parse().map_err(|_| invalid("LISTEN_FDS", listen_fds))?;
+    if !(0..=i32::MAX - LISTEN_FDS_START).contains(&count) {
+        return Err(invalid("LISTEN_FDS", listen_fds));
+    }
+    if count == 0 {
+        return Ok(None);
+    }
+    Ok(Some(LISTEN_FDS_START..LISTEN_FDS_START + count))
+}
+
+/// Listeners for the sockets passed to this process, or `None` if it was not
+/// socket-activated. The sockets are adopted by the first call only; later
+/// calls, such as a second server started in the same process, get `None`
+/// and bind as usual.
+#[cfg(unix)]
+pub(crate) fn take_listeners() -> Result<Option<Vec<TcpListener>>> {
+    use std::os::fd::FromRawFd;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    static TAKEN: AtomicBool = AtomicBool::new(false);
+
+    let listen_pid = std::env::var("LISTEN_PID").ok();
+    let listen_fds = std::env::var("LISTEN_FDS").ok();
+    let fds = self::listen_fds(listen_pid.as_deref(), listen_fds.as_deref(), std::process::id())?;
+    let Some(fds) = fds else {
+        return Ok(None);
+    };
+    if TAKEN.swap(true, Ordering::SeqCst) {
+        return Ok(None);
+    }
+
+    let mut listeners = Vec::with_capacity(fds.len());
+    for fd in fds {
+        // SAFETY: `LISTEN_PID` names this process, so the service manager
+        // passed it `fd` to own, and `TAKEN` ensures it is only adopted once.
+        let inherited = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+        let not_a_listener = |e: Error| {
+            let message = format!("file descriptor {} is not a listening socket: {}", fd, e);
+            Error::new(e.kind(), message)
+        };
+        inherited.local_addr().map_err(not_a_listener)?;
+        // Inherited descriptors are left open across exec; a duplicate is not.
+        let listener = inherited.try_clone().map_err(not_a_listener)?;
+        drop(inherited);
+        listener.set_nonblocking(true)?;
+        listeners.push(TcpListener::from_std(listener)?);
+    }
+    Ok(Some(listeners))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn take_listeners() -> Result<Option<Vec<TcpListener>>> {
+    Ok(None)
+}
@@ -1,57 +1,677 @@
+use crate::chaos::{ChaosRng, ChaosSettings};
+use crate::checkpoints::Checkpoints;
+use crate::clock::Clock;
+use crate::compression::MessageBody;
+use crate::metrics::{self, QueueLatency};
+use crate::config::Config;
+use crate::deduplication::DeduplicationCache;
+use crate::error::SqsError;
+use crate::events::{self, Event, EventKind, EventLog};
+use crate::generators::Generators;
+use crate::ids::Ids;
+use crate::history::{DeletedHistories, HistoryEntry, HistoryKind, MessageHistory};
+use crate::limits::{Limiter, Rejections};
+use crate::message_attributes::DataType;
+use crate::messages::MessageStore;
+use crate::move_tasks::MoveTask;
+use crate::receipt::ReceiptHandles;
 use crate::serde_helpers;
+use crate::store::{MemoryStore, QueueStore, Selection};
+use crate::templates::Templates;
+use crate::urls;
+use crate::validation::Validation;
+use crate::webhooks::Webhooks;
 use bytes::BufMut;
 use chrono::{DateTime, Utc};
-use dashmap::DashMap;
-use md5;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, VecDeque};
-use std::sync::Arc;
-use uuid::Uuid;
-
-use std::env;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::error;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 
 #[derive(Debug, Clone)]
 pub struct AppState {
-    pub queues: Arc<DashMap<String, Queue>>,
+    /// Every queue, keyed by URL.
+    pub store: Arc<dyn QueueStore>,
     pub host: String,
     pub port: u16,
+    /// Normalized [`Config::base_path`]: empty, or `/`-prefixed without a
+    /// trailing slash.
+    pub base_path: String,
+    pub admin_api: bool,
+    pub region: String,
+    pub account_id: String,
+    pub sweep_interval: Duration,
+    /// See [`Config::purge_cooldown`].
+    pub purge_cooldown: Option<Duration>,
+    pub shutdown: CancellationToken,
+    /// The maintenance task, once [`crate::maintenance::spawn`] has started it.
+    pub maintenance: Arc<Mutex<Option<JoinHandle<()>>>>,
+    /// Body bytes currently held across all queues.
+    pub total_bytes: Arc<AtomicU64>,
+    pub max_messages_per_queue: Option<usize>,
+    pub max_total_bytes: Option<u64>,
+    /// See [`Config::queue_full_error_code`].
+    pub queue_full_error_code: Arc<str>,
+    /// See [`Config::max_message_size_limit`].
+    pub max_message_size_limit: usize,
+    /// The validation mode, switchable through the admin API.
+    pub validation: Validation,
+    /// Message move tasks in the order they were started.
+    pub move_tasks: Arc<Mutex<Vec<Arc<MoveTask>>>>,
+    pub clock: Clock,
+    /// Attributes applied to new queues that don't set them, ahead of the
+    /// AWS defaults.
+    pub default_queue_attributes: Arc<HashMap<String, String>>,
+    pub webhooks: Arc<Webhooks>,
+    /// Server-wide chaos settings; see [`Queue::chaos`] for overrides.
+    pub chaos: ChaosSettings,
+    pub chaos_rng: ChaosRng,
+    /// Bucket bounds, in seconds, of every queue's latency histograms.
+    pub histogram_buckets: Arc<[f64]>,
+    pub receipt_handles: ReceiptHandles,
+    /// The source of generated IDs; see [`Config::deterministic_ids`].
+    pub ids: Ids,
+    /// See [`Config::idle_queue_ttl`].
+    pub idle_queue_ttl: Option<Duration>,
+    /// Names of queues never deleted for being idle.
+    pub idle_queue_exempt: Arc<HashSet<String>>,
+    /// Queues deleted for being idle since the server started.
+    pub idle_queues_deleted: Arc<AtomicU64>,
+    /// See [`Config::event_log_capacity`].
+    pub event_log_capacity: usize,
+    /// Queues created and deleted, across all queues.
+    pub server_events: Arc<Mutex<EventLog>>,
+    /// See [`Config::message_history`].
+    pub message_history: usize,
+    /// See [`Config::max_concurrent_requests`].
+    pub max_concurrent_requests: Option<usize>,
+    /// See [`Config::max_long_polls_per_queue`].
+    pub max_long_polls_per_queue: Option<usize>,
+    /// See [`Config::max_connections`].
+    pub max_connections: Option<usize>,
+    /// See [`Config::compress_bodies_above`].
+    pub compress_bodies_above: Option<usize>,
+    /// SQS requests being handled.
+    pub requests: Arc<Limiter>,
+    /// Open connections.
+    pub connections: Arc<Limiter>,
+    pub rejections: Arc<Rejections>,
+    /// Named copies of the state, taken through the admin API.
+    pub checkpoints: Arc<Checkpoints>,
+    /// The config file's queue templates, for `CreateQueue` requests that
+    /// name one.
+    pub templates: Arc<Mutex<Templates>>,
+    /// Message generators started from the config file or the admin API.
+    pub generators: Arc<Generators>,
 }
 
 impl AppState {
-    pub fn new() -> Self {
-        let host = env::var("LOCAL_SQS_HOST").unwrap_or_else(|_| "localhost".to_string());
-        let port = env::var("LOCAL_SQS_PORT")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(9324);
+    pub fn new(config: &Config) -> Self {
+        Self::with_store(config, Arc::new(MemoryStore::default()))
+    }
+
+    /// State for `config`, keeping queues in `store`.
+    pub fn with_store(config: &Config, store: Arc<dyn QueueStore>) -> Self {
+        let ids = Ids::new(config.deterministic_ids);
         Self {
-            queues: Arc::new(DashMap::new()),
-            host,
-            port,
+            store,
+            host: config.host.clone(),
+            port: config.port,
+            base_path: normalize_base_path(&config.base_path),
+            admin_api: config.admin_api,
+            region: config.region.clone(),
+            account_id: config.account_id.clone(),
+            sweep_interval: config.sweep_interval,
+            purge_cooldown: config.purge_cooldown,
+            shutdown: CancellationToken::new(),
+            maintenance: Default::default(),
+            total_bytes: Arc::new(AtomicU64::new(0)),
+            max_messages_per_queue: config.max_messages_per_queue,
+            max_total_bytes: config.max_total_bytes,
+            queue_full_error_code: config.queue_full_error_code.as_str().into(),
+            max_message_size_limit: config.max_message_size_limit,
+            validation: Validation::new(config.validation),
+            move_tasks: Default::default(),
+            clock: if config.manual_clock {
+                Clock::manual()
+            } else {
+                Clock::Real
+            },
+            default_queue_attributes: Arc::new(config.default_queue_attributes.clone()),
+            webhooks: Default::default(),
+            chaos: config.chaos,
+            chaos_rng: ChaosRng::new(config.chaos_seed),
+            histogram_buckets: config.histogram_buckets.as_slice().into(),
+            receipt_handles: ReceiptHandles::new(
+                config.receipt_handle_secret.as_deref(),
+                ids.clone(),
+            ),
+            ids,
+            idle_queue_ttl: config.idle_queue_ttl,
+            idle_queue_exempt: Arc::new(config.idle_queue_exempt.iter().cloned().collect()),
+            idle_queues_deleted: Arc::new(AtomicU64::new(0)),
+            event_log_capacity: config.event_log_capacity,
+            server_events: Arc::new(Mutex::new(EventLog::new(config.event_log_capacity))),
+            message_history: config.message_history,
+            max_concurrent_requests: config.max_concurrent_requests,
+            max_long_polls_per_queue: config.max_long_polls_per_queue,
+            max_connections: config.max_connections,
+            compress_bodies_above: config.compress_bodies_above,
+            requests: Default::default(),
+            connections: Default::default(),
+            rejections: Default::default(),
+            checkpoints: Arc::new(Checkpoints::new(config.checkpoint_dir.clone())),
+            templates: Default::default(),
+            generators: Default::default(),
+        }
+    }
+
+    /// An event of `kind` on `queue` as of now, attributed to the current
+    /// request if there is one.
+    pub fn event(&self, kind: EventKind, queue: &str, details: serde_json::Value) -> Event {
+        Event {
+            kind,
+            timestamp: self.clock.now().timestamp_millis(),
+            queue: queue.to_string(),
+            request_id: events::current_request_id(),
+            details,
+        }
+    }
+
+    /// Records `event` in the server-wide log.
+    pub fn record_server_event(&self, event: Event) {
+        self.server_events.lock().unwrap().push(event);
+    }
+
+    /// Tells every background task to stop and aborts the maintenance task,
+    /// returning it to await. Sweeps never yield, so none is cut short.
+    pub fn shut_down(&self) -> Option<JoinHandle<()>> {
+        self.shutdown.cancel();
+        let task = self.maintenance.lock().unwrap().take()?;
+        task.abort();
+        Some(task)
+    }
+
+    /// The URL of a queue, in the `host/account/name` layout AWS uses, below
+    /// the base path if there is one. Queues are keyed by this URL, in the
+    /// same normalized form that `QueueUrl` parameters are resolved in.
+    pub fn queue_url(&self, queue_name: &str) -> String {
+        urls::normalize_queue_url(&format!(
+            "http://{}{}/{}/{}",
+            urls::authority(&self.host, self.port),
+            self.base_path,
+            self.account_id,
+            queue_name
+        ))
+    }
+
+    pub fn queue_arn(&self, queue_name: &str) -> String {
+        format!(
+            "arn:aws:sqs:{}:{}:{}",
+            self.region, self.account_id, queue_name
+        )
+    }
+
+    /// Counts `bytes` against [`Config::max_total_bytes`] for a message
+    /// about to be stored with [`Queue::push_reserved`], unless that would
+    /// take the server over the limit. Concurrent sends can't both squeeze
+    /// in under it.
+    pub fn reserve_bytes(&self, bytes: u64) -> Result<(), SqsError> {
+        let fits = |total: u64| {
+            let total = total.checked_add(bytes)?;
+            self.max_total_bytes.is_none_or(|max| total <= max).then_some(total)
+        };
+        self.total_bytes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, fits)
+            .map(|_| ())
+            .map_err(|total| {
+                SqsError::OverLimit(format!(
+                    "Storing this message would exceed the server-wide limit of {} bytes ({} in use).",
+                    self.max_total_bytes.unwrap_or(u64::MAX),
+                    total
+                ))
+            })
+    }
+
+    /// The URL of the queue whose ARN is `arn`, if it exists.
+    pub fn queue_url_by_arn(&self, arn: &str) -> Option<String> {
+        self.store.url_by_arn(arn)
+    }
+
+    /// Whether the queue at `url` has a redrive policy whose dead-letter
+    /// queue exists, so that [`Queue::release_expired`] may take messages
+    /// off it for [`move_to_dead_letter_queue`](Self::move_to_dead_letter_queue).
+    pub fn can_dead_letter(&self, url: &str) -> bool {
+        let target = self.store.read(url, |queue| {
+            queue.redrive_policy.as_ref().map(|rp| rp.dead_letter_target_arn.clone())
+        });
+        matches!(target, Ok(Some(arn)) if self.queue_url_by_arn(&arn).is_some())
+    }
+
+    /// Moves `messages` onto the queue whose ARN is `dead_letter_target_arn`.
+    /// If that queue can't take them, as when it was deleted meanwhile or
+    /// the store failed to write it, they go back onto their source queue
+    /// instead. The move is recorded in the event logs of both queues.
+    pub async fn move_to_dead_letter_queue(
+        &self,
+        dead_letter_target_arn: &str,
+        messages: Vec<Message>,
+    ) {
+        if messages.is_empty() {
+            return;
+        }
+
+        let now = self.clock.now();
+        let source_arn = messages[0].attributes.get("DeadLetterQueueSourceArn").cloned();
+        let details = serde_json::json!({
+            "source": source_arn,
+            "target": dead_letter_target_arn,
+            "message_ids": messages.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(),
+        });
+        // Kept until the dead-letter queue has taken them, to hand back to
+        // the source queue if it can't.
+        let mut unmoved = Some(messages);
+        let moved = match self.queue_url_by_arn(dead_letter_target_arn) {
+            Some(url) => self
+                .store
+                .update_selected(&url, |_| Selection::none(), |dead_letter_queue| {
+                    let name = &dead_letter_queue.name;
+                    let event = self.event(EventKind::DeadLettered, name, details.clone());
+                    dead_letter_queue.events.push(event);
+                    for mut msg in unmoved.clone().unwrap_or_default() {
+                        msg.receipt_handle = None;
+                        msg.record(|| HistoryEntry {
+                            dead_letter_target_arn: Some(dead_letter_target_arn.to_string()),
+                            ..HistoryEntry::new(HistoryKind::DeadLettered, now)
+                        });
+                        self.webhooks.message_enqueued(dead_letter_queue, &msg);
+                        dead_letter_queue.push_message(msg, now);
+                    }
+                    dead_letter_queue.notify.notify_waiters();
+                })
+                .await
+                .is_ok(),
+            None => false,
+        };
+        if moved {
+            unmoved = None;
+        }
+
+        let Some(url) = source_arn.as_deref().and_then(|arn| self.queue_url_by_arn(arn)) else {
+            if let Some(messages) = unmoved {
+                error!(
+                    target = %dead_letter_target_arn,
+                    lost = messages.len(),
+                    "source queue is gone; dropping messages the dead-letter queue could not take"
+                );
+            }
+            return;
+        };
+        let returned = self
+            .store
+            .update_selected(&url, |_| Selection::none(), |source| match unmoved {
+                Some(messages) => source.return_dead_lettered(messages, now),
+                None => {
+                    let event = self.event(EventKind::DeadLettered, &source.name, details);
+                    source.events.push(event);
+                }
+            })
+            .await;
+        if let Err(e) = returned {
+            error!(queue = %url, error = %e, "failed to record a move to the dead-letter queue");
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RedrivePolicy {
     #[serde(rename = "deadLetterTargetArn")]
     pub dead_letter_target_arn: String,
     #[serde(rename = "maxReceiveCount")]
-    #[serde(deserialize_with = "serde_helpers::deserialize_number_from_string")]
+    #[serde(deserialize_with = "serde_helpers::deserialize_number")]
     #[serde(serialize_with = "serde_helpers::serialize_number_to_string")]
     pub max_receive_count: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl fmt::Display for RedrivePolicy {
+    /// The policy as the JSON document `GetQueueAttributes` returns.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+/// How and when a message was last moved onto its queue from another:
+/// dead-lettered, or moved on out of a dead-letter queue.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeadLetterInfo {
+    /// The ARN of the queue the message was moved from.
+    pub source_arn: String,
+    pub moved_at: DateTime<Utc>,
+    /// How many times the message had been received when it was moved.
+    pub receive_count: u32,
+    pub reason: MoveReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveReason {
+    /// Received `maxReceiveCount` times under the source's redrive policy.
+    RedrivePolicy,
+    /// Moved by a message move task.
+    MoveTask,
+    /// Moved through the admin API's redrive endpoint.
+    AdminRedrive,
+}
+
+/// Which source queues may use a queue as their dead-letter queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RedrivePermission {
+    AllowAll,
+    DenyAll,
+    ByQueue,
+}
+
+/// A queue's `RedriveAllowPolicy`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RedriveAllowPolicy {
+    pub redrive_permission: RedrivePermission,
+    /// The allowed source queues; only used, and then required, with
+    /// [`RedrivePermission::ByQueue`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub source_queue_arns: Vec<String>,
+}
+
+impl fmt::Display for RedriveAllowPolicy {
+    /// The policy as the JSON document `GetQueueAttributes` returns.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Queue {
     pub name: String,
     pub url: String,
-    pub messages: VecDeque<Message>,
+    pub arn: String,
+    pub messages: MessageStore,
     pub attributes: HashMap<String, String>,
+    pub tags: HashMap<String, String>,
     pub created_timestamp: i64,
     pub last_modified_timestamp: i64,
-    #[serde(default)]
+    /// The queue's `RedrivePolicy`. This is the only copy: `attributes`
+    /// never holds it, see [`Queue::configured_attributes`].
     pub redrive_policy: Option<RedrivePolicy>,
+    /// The queue's `RedriveAllowPolicy`, kept like `redrive_policy`.
+    pub redrive_allow_policy: Option<RedriveAllowPolicy>,
+    pub stats: QueueStats,
+    #[serde(skip)]
+    pub latency: QueueLatency,
+    /// Body bytes currently held by this queue.
+    #[serde(skip)]
+    pub stored_bytes: u64,
+    /// The server-wide byte counter this queue's `stored_bytes` feed into.
+    #[serde(skip)]
+    pub total_bytes: Arc<AtomicU64>,
+    /// Wakes long polls waiting on this queue when messages become visible.
+    #[serde(skip)]
+    pub notify: Arc<Notify>,
+    /// Long polls waiting on this queue.
+    #[serde(skip)]
+    pub long_polls: Arc<Limiter>,
+    /// Bumped by each `PurgeQueue`, so that long polls woken by a purge
+    /// return empty instead of waiting on.
+    #[serde(skip)]
+    pub purge_generation: u64,
+    /// The tombstone of the last `PurgeQueue` while
+    /// [`AppState::purge_cooldown`] lasts: further purges fail until then.
+    /// Cleared by the maintenance task once it has passed.
+    #[serde(skip)]
+    pub purged_until: Option<DateTime<Utc>>,
+    /// FIFO deduplication IDs seen in the last five minutes.
+    #[serde(skip)]
+    pub deduplication: DeduplicationCache,
+    /// The last `SequenceNumber` given to a message on this FIFO queue.
+    #[serde(skip)]
+    pub sequence_number: u64,
+    /// URL notified of every message enqueued on this queue.
+    pub webhook: Option<String>,
+    /// While set, `ReceiveMessage` hands out nothing; sends still succeed.
+    pub paused: bool,
+    /// Overrides the server-wide chaos settings for this queue.
+    pub chaos: Option<ChaosSettings>,
+    /// When the queue was created or last sent to or received from.
+    #[serde(skip)]
+    pub last_used: DateTime<Utc>,
+    /// What happened to the queue recently.
+    #[serde(skip)]
+    pub events: EventLog,
+    /// The histories of recently deleted messages, as many as
+    /// [`AppState::event_log_capacity`]. Empty unless
+    /// [`AppState::message_history`] is set.
+    #[serde(skip)]
+    pub deleted_histories: DeletedHistories,
+}
+
+/// Tag that exempts a queue from [`Config::idle_queue_ttl`] when set to
+/// `true`.
+pub const KEEP_TAG: &str = "local-sqs:keep";
+
+/// Monotonic per-queue counters, reset only through the admin API.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QueueStats {
+    pub sent: u64,
+    pub received: u64,
+    pub deleted: u64,
+    pub purged: u64,
+    pub empty_receives: u64,
+    pub dlq_moved: u64,
+    /// Extra copies handed out by the duplicate delivery chaos option.
+    pub duplicates_delivered: u64,
+    /// Sends refused because the queue was at its `MaxQueueLength`.
+    pub sends_rejected: u64,
+}
+
+impl Queue {
+    /// Parses a numeric attribute, falling back to `default` if it is unset.
+    pub fn attribute_or<T: std::str::FromStr>(&self, name: &str, default: T) -> T {
+        self.attributes
+            .get(name)
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(default)
+    }
+
+    pub fn is_fifo(&self) -> bool {
+        self.attributes.get("FifoQueue").is_some_and(|v| v == "true")
+    }
+
+    /// The next FIFO `SequenceNumber`. Like AWS's, they are 20-digit
+    /// decimals that increase with every accepted message.
+    pub fn next_sequence_number(&mut self) -> String {
+        self.sequence_number += 1;
+        format!("{:020}", self.sequence_number)
+    }
+
+    /// The attributes set on the queue, including the `RedrivePolicy` and
+    /// `RedriveAllowPolicy` rendered from their typed fields.
+    pub fn configured_attributes(&self) -> HashMap<String, String> {
+        let mut attributes = self.attributes.clone();
+        if let Some(policy) = &self.redrive_policy {
+            attributes.insert("RedrivePolicy".to_string(), policy.to_string());
+        }
+        if let Some(policy) = &self.redrive_allow_policy {
+            attributes.insert("RedriveAllowPolicy".to_string(), policy.to_string());
+        }
+        attributes
+    }
+
+    /// Whether the queue is empty and has been neither used nor changed for
+    /// longer than `ttl` as of `now`.
+    pub fn is_idle(&self, now: DateTime<Utc>, ttl: Duration) -> bool {
+        let last_active = self
+            .last_used
+            .timestamp_millis()
+            .max(self.last_modified_timestamp * 1000);
+        let idle_ms = now.timestamp_millis() - last_active;
+        self.messages.is_empty() && idle_ms > ttl.as_millis() as i64
+    }
+
+    /// Records a configuration change (attributes, tags, permissions) in
+    /// `last_modified_timestamp`, in epoch seconds like `created_timestamp`.
+    pub fn touch(&mut self, now: DateTime<Utc>) {
+        self.last_modified_timestamp = now.timestamp();
+    }
+
+    /// Appends a message as of `now`, accounting for its size.
+    pub fn push_message(&mut self, message: Message, now: DateTime<Utc>) {
+        self.account_added(message.size());
+        self.messages.push(message, now);
+    }
+
+    /// Like [`push_message`](Self::push_message), for a message whose bytes
+    /// [`AppState::reserve_bytes`] has already counted.
+    pub fn push_reserved(&mut self, message: Message, now: DateTime<Utc>) {
+        self.stored_bytes += message.size();
+        self.messages.push(message, now);
+    }
+
+    /// Seconds since the oldest visible message was sent, or zero if no
+    /// message is visible.
+    pub fn oldest_visible_message_age(&self, now: DateTime<Utc>) -> f64 {
+        self.messages
+            .first_ready()
+            .and_then(|seq| self.messages.get(seq))
+            .map_or(0.0, |message| metrics::seconds_between(message.sent_timestamp, now))
+    }
+
+    pub fn remove_message(&mut self, seq: u64) -> Option<Message> {
+        let message = self.messages.remove(seq)?;
+        self.account_removed(message.size());
+        Some(message)
+    }
+
+    /// Removes every message, returning how many there were.
+    pub fn clear_messages(&mut self) -> usize {
+        let count = self.messages.len();
+        self.messages.clear();
+        self.account_removed(self.stored_bytes);
+        count
+    }
+
+    /// Gives back this queue's bytes to the server-wide counter. Call when
+    /// the queue is removed from the queue map.
+    pub fn release_usage(&self) {
+        self.total_bytes.fetch_sub(self.stored_bytes, Ordering::Relaxed);
+    }
+
+    fn account_added(&mut self, bytes: u64) {
+        self.stored_bytes += bytes;
+        self.total_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn account_removed(&mut self, bytes: u64) {
+        // A store that reloads messages may count them differently from
+        // when they were stored, as with compression settings changed.
+        let bytes = bytes.min(self.stored_bytes);
+        self.stored_bytes -= bytes;
+        self.total_bytes.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    /// The messages a maintenance sweep at `now` needs: those past their
+    /// retention period, those whose visibility timeout has lapsed, and the
+    /// oldest visible one.
+    pub fn sweep_selection(&self, now: DateTime<Utc>) -> Selection {
+        Selection {
+            lapsed: true,
+            sent_by: Some(self.retention_cutoff(now)),
+            ..Selection::visible(1)
+        }
+    }
+
+    fn retention_cutoff(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let retention = self.attribute_or("MessageRetentionPeriod", 345600);
+        now - chrono::Duration::seconds(retention)
+    }
+
+    /// Drops messages that have outlived the queue's `MessageRetentionPeriod`.
+    pub fn expire_retention(&mut self, now: DateTime<Utc>) -> usize {
+        let cutoff = self.retention_cutoff(now);
+
+        let expired = self.messages.remove_where(|m| m.sent_timestamp <= cutoff);
+        self.account_removed(expired.iter().map(Message::size).sum());
+        expired.len()
+    }
+
+    /// Makes delayed messages that are due visible and returns in-flight
+    /// messages whose visibility timeout has lapsed to the visible pool.
+    /// With `dead_letter` set, as [`AppState::can_dead_letter`] tells,
+    /// messages that have already been received `maxReceiveCount` times are
+    /// removed instead and handed back so the caller can move them to the
+    /// dead-letter queue.
+    pub fn release_expired(&mut self, now: DateTime<Utc>, dead_letter: bool) -> Vec<Message> {
+        let max_receive_count = self
+            .redrive_policy
+            .as_ref()
+            .filter(|_| dead_letter)
+            .map(|rp| rp.max_receive_count);
+        let mut dead_lettered = self
+            .messages
+            .release_due(now, |m| max_receive_count.is_some_and(|max| m.receive_count >= max));
+
+        let source_arn = self.arn.clone();
+        for message in &mut dead_lettered {
+            message
+                .attributes
+                .insert("DeadLetterQueueSourceArn".to_string(), source_arn.clone());
+            message.dead_letter = Some(DeadLetterInfo {
+                source_arn: source_arn.clone(),
+                moved_at: now,
+                receive_count: message.receive_count,
+                reason: MoveReason::RedrivePolicy,
+            });
+        }
+
+        self.stats.dlq_moved += dead_lettered.len() as u64;
+        self.account_removed(dead_lettered.iter().map(Message::size).sum());
+        dead_lettered
+    }
+
+    /// Puts back messages [`release_expired`](Self::release_expired) took
+    /// off this queue that the dead-letter queue couldn't take, visible
+    /// again and in their place.
+    pub fn return_dead_lettered(&mut self, messages: Vec<Message>, now: DateTime<Utc>) {
+        self.stats.dlq_moved = self.stats.dlq_moved.saturating_sub(messages.len() as u64);
+        for mut message in messages {
+            message.attributes.remove("DeadLetterQueueSourceArn");
+            message.dead_letter = None;
+            self.account_added(message.size());
+            self.messages.restore(message, now);
+        }
+        self.notify.notify_waiters();
+    }
+
+    pub fn has_visible(&self, now: DateTime<Utc>) -> bool {
+        self.messages.has_visible(now)
+    }
+
+    /// Clears the purge tombstone if its cooldown is over as of `now`,
+    /// returning whether there was one to clear.
+    pub fn expire_purge_tombstone(&mut self, now: DateTime<Utc>) -> bool {
+        let expired = self.purged_until.is_some_and(|until| until <= now);
+        if expired {
+            self.purged_until = None;
+        }
+        expired
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -61,57 +681,167 @@ pub struct Message {
     pub id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub receipt_handle: Option<String>,
-    pub body: String,
+    pub body: MessageBody,
     #[serde(rename = "MD5OfBody")]
     pub md5_of_body: String,
     pub attributes: HashMap<String, String>,
-    #[serde(default)]
+    /// Omitted, like its digest, when there are none to return.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub message_attributes: HashMap<String, MessageAttributeValue>,
+    #[serde(rename = "MD5OfMessageAttributes")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub md5_of_message_attributes: String,
     #[serde(skip)]
     pub visible_from: DateTime<Utc>,
+    /// Reported to clients as the `SentTimestamp` attribute.
+    #[serde(skip)]
     pub sent_timestamp: DateTime<Utc>,
-    #[serde(default)]
+    /// Reported to clients as the `ApproximateReceiveCount` attribute.
+    #[serde(skip)]
     pub receive_count: u32,
+    /// Reported to clients as the `ApproximateFirstReceiveTimestamp`
+    /// attribute once the message has been received.
+    #[serde(skip)]
+    pub first_received: Option<DateTime<Utc>>,
+    /// The `traceparent` of the span that sent the message, if spans are
+    /// exported; see [`telemetry`](crate::telemetry).
+    #[serde(skip)]
+    pub trace_context: Option<String>,
+    /// The FIFO message group, also reported as the `MessageGroupId`
+    /// attribute. Standard queues' messages have none.
+    #[serde(skip)]
+    pub message_group_id: Option<String>,
+    /// The request id of the `ReceiveMessage` that last received the
+    /// message, for tracing where an in-flight message went.
+    #[serde(skip)]
+    pub claimed_by: Option<String>,
+    /// Position in the owning queue's send order, assigned by `MessageStore`.
+    #[serde(skip)]
+    pub seq: u64,
+    /// What has happened to the message, if histories are kept; see
+    /// [`history`](crate::history).
+    #[serde(skip)]
+    pub history: Option<Box<MessageHistory>>,
+    /// Set once the message has been moved from another queue. API
+    /// consumers see only the `DeadLetterQueueSourceArn` attribute.
+    #[serde(skip)]
+    pub dead_letter: Option<DeadLetterInfo>,
 }
 
 impl Message {
+    /// Bytes counted against the memory caps: the body as stored, so
+    /// compressed bodies count at their compressed size.
+    pub fn size(&self) -> u64 {
+        self.body.stored_len() as u64
+    }
+
+    /// Adds the entry `entry` builds to the message's history, if it keeps
+    /// one.
+    pub fn record(&mut self, entry: impl FnOnce() -> HistoryEntry) {
+        if let Some(history) = &mut self.history {
+            history.push(entry());
+        }
+    }
+
+    /// Stores the body compressed if it is longer than `threshold` bytes;
+    /// see [`MessageBody::new`].
+    pub fn compress_body(&mut self, threshold: Option<usize>) {
+        if let MessageBody::Plain(body) = &mut self.body {
+            self.body = MessageBody::new(std::mem::take(body), threshold);
+        }
+    }
+
     pub fn new(
+        id: String,
         body: String,
-        attributes: HashMap<String, String>,
+        mut attributes: HashMap<String, String>,
         message_attributes: HashMap<String, MessageAttributeValue>,
         delay_seconds: Option<u32>,
+        sent_timestamp: DateTime<Utc>,
     ) -> Self {
-        let md5_of_body = format!("{:x}", md5::compute(body.as_bytes()));
-        let md5_of_message_attributes = if message_attributes.is_empty() {
-            "".to_string()
+        let md5_of_body = md5_hex(body.as_bytes());
+        let md5_of_message_attributes = md5_of_message_attributes(&message_attributes);
+
+        let visible_from = if let Some(delay) = delay_seconds {
+            sent_timestamp + chrono::Duration::seconds(delay as i64)
         } else {
-            let mut sorted_keys: Vec<_> = message_attributes.keys().collect();
-            sorted_keys.sort();
-
-            let mut buffer = Vec::new();
-
-            for key in sorted_keys {
-                if let Some(attr) = message_attributes.get(key) {
-                    // Name
-                    buffer.put_u32(key.len() as u32);
-                    buffer.put(key.as_bytes());
-
-                    // Data Type
-                    buffer.put_u32(attr.data_type.len() as u32);
-                    buffer.put(attr.data_type.as_bytes());
-
-                    // Value
-                    if attr.data_type.starts_with("String")
-                        || attr.data_type.starts_with("Number")
-                    {
-                        buffer.put_u8(1);
+            sent_timestamp
+        };
+
+        let message_group_id = attributes.get("MessageGroupId").cloned();
+        attributes.insert(
+            "SentTimestamp".to_string(),
+            sent_timestamp.timestamp_millis().to_string(),
+        );
+
+        Self {
+            id,
+            receipt_handle: None,
+            body: body.into(),
+            md5_of_body,
+            attributes,
+            message_attributes,
+            md5_of_message_attributes,
+            visible_from,
+            sent_timestamp,
+            receive_count: 0,
+            first_received: None,
+            message_group_id,
+            trace_context: None,
+            claimed_by: None,
+            seq: 0,
+            history: None,
+            dead_letter: None,
+        }
+    }
+}
+
+/// The lowercase hex MD5 digest of `bytes`, as the `MD5Of*` fields carry it.
+pub fn md5_hex(bytes: &[u8]) -> String {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let digest = md5::compute(bytes);
+    let mut hex = String::with_capacity(32);
+    for byte in digest.iter() {
+        hex.push(HEX[(byte >> 4) as usize] as char);
+        hex.push(HEX[(byte & 0xf) as usize] as char);
+    }
+    hex
+}
+
+/// The `MD5OfMessageAttributes` digest of `attributes`, or an empty string
+/// if there are none. `MD5OfMessageSystemAttributes` uses the same encoding.
+pub fn md5_of_message_attributes(
+    attributes: &HashMap<String, MessageAttributeValue>,
+) -> String {
+    if attributes.is_empty() {
+        "".to_string()
+    } else {
+        let mut sorted_keys: Vec<_> = attributes.keys().collect();
+        sorted_keys.sort();
+
+        let mut buffer = Vec::new();
+
+        for key in sorted_keys {
+            if let Some(attr) = attributes.get(key) {
+                // Name
+                buffer.put_u32(key.len() as u32);
+                buffer.put(key.as_bytes());
+
+                // Data Type
+                buffer.put_u32(attr.data_type.len() as u32);
+                buffer.put(attr.data_type.as_bytes());
+
+                // Value
+                match DataType::parse(&attr.data_type) {
+                    Some(data_type @ (DataType::String | DataType::Number)) => {
+                        buffer.put_u8(data_type.transport_type());
                         if let Some(val) = &attr.string_value {
                             buffer.put_u32(val.len() as u32);
                             buffer.put(val.as_bytes());
                         }
-                    } else if attr.data_type.starts_with("Binary") {
-                        buffer.put_u8(2);
+                    }
+                    Some(data_type @ DataType::Binary) => {
+                        buffer.put_u8(data_type.transport_type());
                         if let Some(val) = &attr.binary_value {
                             use base64::{engine::general_purpose, Engine as _};
                             if let Ok(decoded) = general_purpose::STANDARD.decode(val) {
@@ -120,37 +850,52 @@ impl Message {
                             }
                         }
                     }
+                    None => {}
                 }
             }
+        }
 
-            format!("{:x}", md5::compute(&buffer))
-        };
+        md5_hex(&buffer)
+    }
+}
 
-        let visible_from = if let Some(delay) = delay_seconds {
-            Utc::now() + chrono::Duration::seconds(delay as i64)
-        } else {
-            Utc::now()
-        };
+/// The size SQS charges a message against `MaximumMessageSize`: the body
+/// plus, for each message attribute, its name, data type and value (decoded
+/// bytes for binary values).
+pub fn message_size_bytes(body: &str, attributes: &HashMap<String, MessageAttributeValue>) -> usize {
+    use base64::{engine::general_purpose, Engine as _};
 
-        Self {
-            id: Uuid::new_v4().to_string(),
-            receipt_handle: None,
-            body,
-            md5_of_body,
-            attributes,
-            message_attributes,
-            md5_of_message_attributes,
-            visible_from,
-            sent_timestamp: Utc::now(),
-            receive_count: 0,
-        }
-    }
+    let attributes_size: usize = attributes
+        .iter()
+        .map(|(name, attr)| {
+            let value_size = match (&attr.string_value, &attr.binary_value) {
+                (Some(value), _) => value.len(),
+                (None, Some(value)) => general_purpose::STANDARD
+                    .decode(value)
+                    .map_or(value.len(), |decoded| decoded.len()),
+                (None, None) => 0,
+            };
+            name.len() + attr.data_type.len() + value_size
+        })
+        .sum();
+    body.len() + attributes_size
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct MessageAttributeValue {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub string_value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub binary_value: Option<String>, // Representing binary as base64 encoded string
     pub data_type: String,
 }
+
+fn normalize_base_path(base_path: &str) -> String {
+    let trimmed = base_path.trim_matches('/');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", trimmed)
+    }
+}
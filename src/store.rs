@@ -0,0 +1,788 @@
+//! Where queues are kept. Request handlers, the admin API and the background
+//! tasks reach queues only through the [`QueueStore`] on
+//! [`AppState`](crate::AppState), so a backend other than the default
+//! [`MemoryStore`] can be swapped in without touching them.
+//!
+//! A store hands out access to one [`Queue`] at a time, under whatever lock
+//! makes that access atomic with respect to other requests on the queue.
+//! Handlers send, receive and delete messages and set attributes through
+//! the store's operations for them, such as [`enqueue`](QueueStore::enqueue)
+//! and [`claim_visible`](QueueStore::claim_visible). Those default to doing
+//! the SQS semantics — visibility, redrive, deduplication and so on — kept
+//! on `Queue`, inside a single [`update`](QueueStore::update_queue), so that,
+//! for example, claiming a message and setting its visibility deadline can't
+//! interleave with another receive.
+//!
+//! A store that keeps messages out of memory loads only those an operation
+//! needs, which it learns from the [`Selection`] passed to
+//! [`update_queue_selected`](QueueStore::update_queue_selected); the default
+//! operations pass the narrowest selection that serves them.
+//!
+//! [`sqlite`] and [`redis`] are [`Durable`](durable::Durable) stores: the
+//! database holds the messages, and every operation is a transaction against
+//! it.
+
+pub mod durable;
+pub mod redis;
+pub mod sqlite;
+
+use crate::chaos::ChaosSettings;
+use crate::deduplication::DeduplicationChanges;
+use crate::error::SqsError;
+use crate::history::MessageHistory;
+use crate::messages::{MessageCounts, MessageStore};
+use crate::queue::{
+    AttributeChanges, Claimed, ReceiveMessageRequest, SendMessageRequest, SendMessageResponse,
+};
+use crate::snapshot::{MessageSnapshot, QueueSnapshot};
+use crate::state::{AppState, Message, Queue, QueueStats};
+use chrono::{DateTime, Utc};
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use tracing::error;
+
+/// Which [`QueueStore`] the server keeps its queues in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Storage {
+    /// A [`MemoryStore`]: queues are gone once the server stops.
+    #[default]
+    Memory,
+    /// A [`SqliteStore`](sqlite::SqliteStore) at
+    /// [`Config::db_path`](crate::Config), reloaded on the next start.
+    Sqlite,
+    /// A [`RedisStore`](redis::RedisStore) at
+    /// [`Config::redis_url`](crate::Config), reloaded on the next start.
+    Redis,
+}
+
+impl FromStr for Storage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "memory" => Ok(Storage::Memory),
+            "sqlite" => Ok(Storage::Sqlite),
+            "redis" => Ok(Storage::Redis),
+            _ => Err(format!("unknown storage {:?}, expected memory, sqlite or redis", s)),
+        }
+    }
+}
+
+impl fmt::Display for Storage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Storage::Memory => "memory",
+            Storage::Sqlite => "sqlite",
+            Storage::Redis => "redis",
+        })
+    }
+}
+
+/// What the operations of a [`QueueStore`] that change queues return: the
+/// change, which a durable store may still be writing when the queue's lock
+/// is released.
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, SqsError>> + Send + 'a>>;
+
+/// Storage for queues, keyed by their normalized URL.
+///
+/// Callbacks are `&mut dyn FnMut` so the trait can be used as
+/// `Arc<dyn QueueStore>`; [`read`](Self::read), [`update`](Self::update) and
+/// friends on `dyn QueueStore` wrap them for ordinary closures. A callback
+/// must not call back into the store: it may hold a lock that doing so
+/// would need.
+///
+/// Operations that change queues are async, so that a store can write the
+/// change after the queue's lock is released, and fail with `InternalError`
+/// when the change can't be stored; reads are served at once and can't
+/// fail.
+///
+/// The operations on messages and attributes default to the [`Queue`]
+/// methods of the same name, applied in one
+/// [`update_queue`](Self::update_queue); a store that can do them itself
+/// overrides them. All fail with `QueueDoesNotExist` if there is no queue at
+/// `url`.
+pub trait QueueStore: Send + Sync + fmt::Debug {
+    /// Adds `queue` under its URL, returning true, unless a queue with that
+    /// URL exists already, in which case `existing` is called with it and
+    /// nothing is added.
+    fn create_queue<'a>(
+        &'a self,
+        queue: Queue,
+        existing: &'a mut (dyn FnMut(&Queue) + Send),
+    ) -> StoreFuture<'a, bool>;
+
+    /// Adds `queue` under its URL, replacing and returning any queue there.
+    fn put_queue(&self, queue: Queue) -> StoreFuture<'_, Option<Queue>>;
+
+    /// Calls `read` with the queue at `url`, returning whether there is one.
+    fn get_queue(&self, url: &str, read: &mut dyn FnMut(&Queue)) -> bool;
+
+    /// Calls `update` with exclusive access to the queue at `url`, returning
+    /// whether there is one. If the update can't be stored, the queue is
+    /// left as it was and the error is returned.
+    fn update_queue<'a>(
+        &'a self,
+        url: &'a str,
+        update: &'a mut (dyn FnMut(&mut Queue) + Send),
+    ) -> StoreFuture<'a, bool>;
+
+    /// Like [`update_queue`](Self::update_queue), for an update that needs
+    /// only the messages `select` picks for the queue: a store that keeps
+    /// messages out of memory loads just those, and the queue's other
+    /// messages may be missing from it. Defaults to `update_queue`.
+    fn update_queue_selected<'a>(
+        &'a self,
+        url: &'a str,
+        select: &'a (dyn Fn(&Queue) -> Selection + Sync),
+        update: &'a mut (dyn FnMut(&mut Queue) + Send),
+    ) -> StoreFuture<'a, bool> {
+        let _ = select;
+        self.update_queue(url, update)
+    }
+
+    /// Like [`get_queue`](Self::get_queue), with every message of the queue
+    /// in it, even in a store that keeps messages out of memory. Meant for
+    /// the admin API and snapshots, not the request hot path.
+    fn inspect_queue<'a>(
+        &'a self,
+        url: &'a str,
+        read: &'a mut (dyn FnMut(&Queue) + Send),
+    ) -> StoreFuture<'a, bool> {
+        Box::pin(async move { Ok(self.get_queue(url, read)) })
+    }
+
+    /// The messages on the queue at `url` by state, as of `now`; see
+    /// [`MessageStore::counts`].
+    fn message_counts<'a>(
+        &'a self,
+        url: &'a str,
+        now: DateTime<Utc>,
+    ) -> StoreFuture<'a, MessageCounts> {
+        Box::pin(async move {
+            let mut counts = None;
+            self.get_queue(url, &mut |queue| counts = Some(queue.messages.counts(now)));
+            counts.ok_or(SqsError::QueueDoesNotExist)
+        })
+    }
+
+    /// Seconds since the oldest visible message on the queue at `url` was
+    /// sent; see [`Queue::oldest_visible_message_age`].
+    fn oldest_visible_message_age<'a>(
+        &'a self,
+        url: &'a str,
+        now: DateTime<Utc>,
+    ) -> StoreFuture<'a, f64> {
+        Box::pin(async move {
+            let mut age = None;
+            self.get_queue(url, &mut |queue| age = Some(queue.oldest_visible_message_age(now)));
+            age.ok_or(SqsError::QueueDoesNotExist)
+        })
+    }
+
+    /// Removes and returns the queue at `url` if `condition` holds for it.
+    fn remove_queue_if<'a>(
+        &'a self,
+        url: &'a str,
+        condition: &'a mut (dyn FnMut(&Queue) -> bool + Send),
+    ) -> StoreFuture<'a, Option<Queue>>;
+
+    /// Calls `visit` with every queue, in no particular order. Queues
+    /// created or removed meanwhile may or may not be visited.
+    fn list(&self, visit: &mut dyn FnMut(&Queue));
+
+    /// Atomically replaces every queue with `queues`, returning the old ones.
+    fn replace_all(&self, queues: Vec<Queue>) -> StoreFuture<'_, Vec<Queue>>;
+
+    /// Sends `request` to the queue at `url`; see [`Queue::enqueue`].
+    fn enqueue<'a>(
+        &'a self,
+        state: &'a AppState,
+        url: &'a str,
+        request: SendMessageRequest,
+    ) -> StoreFuture<'a, SendMessageResponse> {
+        Box::pin(async move {
+            let update = |queue: &mut Queue| queue.enqueue(state, request);
+            updated(self, url, &|_| Selection::none(), update).await?
+        })
+    }
+
+    /// Receives from the queue at `url` without waiting; see
+    /// [`Queue::claim_visible`].
+    fn claim_visible<'a>(
+        &'a self,
+        state: &'a AppState,
+        url: &'a str,
+        request: &'a ReceiveMessageRequest,
+    ) -> StoreFuture<'a, Claimed> {
+        let select = move |queue: &Queue| queue.claim_selection(state, request);
+        Box::pin(async move {
+            let dead_letter = state.can_dead_letter(url);
+            updated(self, url, &select, |queue| queue.claim_visible(state, request, dead_letter))
+                .await
+        })
+    }
+
+    /// Deletes the message `receipt_handle` was issued for from the queue at
+    /// `url`, returning its id; see [`Queue::delete_by_handle`].
+    fn delete_by_handle<'a>(
+        &'a self,
+        state: &'a AppState,
+        url: &'a str,
+        receipt_handle: &'a str,
+    ) -> StoreFuture<'a, String> {
+        Box::pin(async move {
+            let select = |_: &Queue| Selection::receipt_handle(receipt_handle);
+            updated(self, url, &select, |queue| {
+                queue.delete_by_handle(state, receipt_handle)
+            })
+            .await?
+        })
+    }
+
+    /// Applies `changes` to the queue at `url`; see [`Queue::set_attributes`].
+    fn set_attributes<'a>(
+        &'a self,
+        state: &'a AppState,
+        url: &'a str,
+        changes: AttributeChanges,
+    ) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let update = |queue: &mut Queue| queue.set_attributes(state, changes);
+            updated(self, url, &|_| Selection::none(), update).await?
+        })
+    }
+}
+
+/// `update` applied to the queue at `url` in `store`, with the messages
+/// `select` picks, or `QueueDoesNotExist`.
+async fn updated<S: QueueStore + ?Sized, R: Send>(
+    store: &S,
+    url: &str,
+    select: &(dyn Fn(&Queue) -> Selection + Sync),
+    update: impl FnOnce(&mut Queue) -> R + Send,
+) -> Result<R, SqsError> {
+    let mut update = Some(update);
+    let mut result = None;
+    store
+        .update_queue_selected(url, select, &mut |queue| {
+            result = update.take().map(|update| update(queue));
+        })
+        .await?;
+    result.ok_or(SqsError::QueueDoesNotExist)
+}
+
+/// Which of a queue's messages an update needs, for a store that keeps
+/// messages out of memory to load. A message is needed if any of the
+/// fields picks it; [`Selection::none`] picks none. Visibility is as of
+/// when the update starts.
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    /// Every message.
+    pub all: bool,
+    /// Up to this many visible messages, in send order, leaving out those
+    /// in flight whatever their deadline.
+    pub visible: usize,
+    /// Whether `visible` passes over messages in groups that in-flight
+    /// messages hold locked.
+    pub unlocked_groups: bool,
+    /// In-flight messages whose visibility timeout has lapsed.
+    pub lapsed: bool,
+    /// Every in-flight message.
+    pub in_flight: bool,
+    /// Messages sent at or before this.
+    pub sent_by: Option<DateTime<Utc>>,
+    /// The message this receipt handle resolves to, if any.
+    pub receipt_handle: Option<String>,
+}
+
+impl Selection {
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    pub fn all() -> Self {
+        Self {
+            all: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn receipt_handle(receipt_handle: &str) -> Self {
+        Self {
+            receipt_handle: Some(receipt_handle.to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// Up to `count` visible messages; see [`visible`](Self::visible).
+    pub fn visible(count: usize) -> Self {
+        Self {
+            visible: count,
+            ..Self::default()
+        }
+    }
+}
+
+impl dyn QueueStore {
+    /// `read` applied to the queue at `url`, or `QueueDoesNotExist`.
+    pub fn read<R>(&self, url: &str, read: impl FnOnce(&Queue) -> R) -> Result<R, SqsError> {
+        let mut read = Some(read);
+        let mut result = None;
+        self.get_queue(url, &mut |queue| result = read.take().map(|read| read(queue)));
+        result.ok_or(SqsError::QueueDoesNotExist)
+    }
+
+    /// Like [`read`](Self::read), for a fallible `read`.
+    pub fn try_read<R>(
+        &self,
+        url: &str,
+        read: impl FnOnce(&Queue) -> Result<R, SqsError>,
+    ) -> Result<R, SqsError> {
+        self.read(url, read)?
+    }
+
+    /// `update` applied to the queue at `url`, or `QueueDoesNotExist`.
+    pub async fn update<R: Send>(
+        &self,
+        url: &str,
+        update: impl FnOnce(&mut Queue) -> R + Send,
+    ) -> Result<R, SqsError> {
+        updated(self, url, &|_| Selection::all(), update).await
+    }
+
+    /// Like [`update`](Self::update), for an `update` needing only the
+    /// messages `select` picks; see [`QueueStore::update_queue_selected`].
+    pub async fn update_selected<R: Send>(
+        &self,
+        url: &str,
+        select: impl Fn(&Queue) -> Selection + Sync,
+        update: impl FnOnce(&mut Queue) -> R + Send,
+    ) -> Result<R, SqsError> {
+        updated(self, url, &select, update).await
+    }
+
+    /// Like [`update_selected`](Self::update_selected), for a fallible
+    /// `update`.
+    pub async fn try_update_selected<R: Send>(
+        &self,
+        url: &str,
+        select: impl Fn(&Queue) -> Selection + Sync,
+        update: impl FnOnce(&mut Queue) -> Result<R, SqsError> + Send,
+    ) -> Result<R, SqsError> {
+        self.update_selected(url, select, update).await?
+    }
+
+    /// `read` applied to the queue at `url` with all of its messages, or
+    /// `QueueDoesNotExist`; see [`QueueStore::inspect_queue`].
+    pub async fn inspect<R: Send>(
+        &self,
+        url: &str,
+        read: impl FnOnce(&Queue) -> R + Send,
+    ) -> Result<R, SqsError> {
+        let mut read = Some(read);
+        let mut result = None;
+        self.inspect_queue(url, &mut |queue| result = read.take().map(|read| read(queue)))
+            .await?;
+        result.ok_or(SqsError::QueueDoesNotExist)
+    }
+
+    /// Like [`update`](Self::update), for a fallible `update`.
+    pub async fn try_update<R: Send>(
+        &self,
+        url: &str,
+        update: impl FnOnce(&mut Queue) -> Result<R, SqsError> + Send,
+    ) -> Result<R, SqsError> {
+        self.update(url, update).await?
+    }
+
+    pub fn contains(&self, url: &str) -> bool {
+        self.get_queue(url, &mut |_| {})
+    }
+
+    /// `f` applied to every queue, keeping the `Some` results.
+    pub fn collect<R>(&self, mut f: impl FnMut(&Queue) -> Option<R>) -> Vec<R> {
+        let mut results = Vec::new();
+        self.list(&mut |queue| results.extend(f(queue)));
+        results
+    }
+
+    /// Whether any queue satisfies `predicate`.
+    pub fn any(&self, mut predicate: impl FnMut(&Queue) -> bool) -> bool {
+        let mut found = false;
+        self.list(&mut |queue| found = found || predicate(queue));
+        found
+    }
+
+    /// The URL of the queue whose ARN is `arn`, if it exists.
+    pub fn url_by_arn(&self, arn: &str) -> Option<String> {
+        self.collect(|queue| (queue.arn == arn).then(|| queue.url.clone()))
+            .pop()
+    }
+
+    /// The URLs of every queue, in no particular order.
+    pub fn urls(&self) -> Vec<String> {
+        self.collect(|queue| Some(queue.url.clone()))
+    }
+}
+
+/// Queues held in memory, in a concurrent map sharded by URL so that
+/// requests on different queues rarely contend. This is the default store.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    /// Swapped whole by [`replace_all`](QueueStore::replace_all); every
+    /// operation works on the map current when it started.
+    queues: RwLock<Arc<DashMap<String, Queue>>>,
+}
+
+/// The operations of [`QueueStore`], done at once, for the stores built on
+/// a `MemoryStore` to use under their own locks.
+impl MemoryStore {
+    fn map(&self) -> Arc<DashMap<String, Queue>> {
+        self.queues.read().unwrap().clone()
+    }
+
+    fn create(&self, queue: Queue, existing: &mut dyn FnMut(&Queue)) -> bool {
+        match self.map().entry(queue.url.clone()) {
+            Entry::Occupied(entry) => {
+                existing(entry.get());
+                false
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(queue);
+                true
+            }
+        }
+    }
+
+    fn put(&self, queue: Queue) -> Option<Queue> {
+        self.map().insert(queue.url.clone(), queue)
+    }
+
+    fn modify(&self, url: &str, update: &mut dyn FnMut(&mut Queue)) -> bool {
+        match self.map().get_mut(url) {
+            Some(mut queue) => {
+                update(&mut queue);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove_if(&self, url: &str, condition: &mut dyn FnMut(&Queue) -> bool) -> Option<Queue> {
+        self.map()
+            .remove_if(url, |_, queue| condition(queue))
+            .map(|(_, queue)| queue)
+    }
+
+    fn replace(&self, queues: Vec<Queue>) -> Vec<Queue> {
+        let map: DashMap<String, Queue> = queues
+            .into_iter()
+            .map(|queue| (queue.url.clone(), queue))
+            .collect();
+        let old = std::mem::replace(&mut *self.queues.write().unwrap(), Arc::new(map));
+        Arc::unwrap_or_clone(old)
+            .into_iter()
+            .map(|(_, queue)| queue)
+            .collect()
+    }
+}
+
+impl QueueStore for MemoryStore {
+    fn create_queue<'a>(
+        &'a self,
+        queue: Queue,
+        existing: &'a mut (dyn FnMut(&Queue) + Send),
+    ) -> StoreFuture<'a, bool> {
+        Box::pin(async move { Ok(self.create(queue, existing)) })
+    }
+
+    fn put_queue(&self, queue: Queue) -> StoreFuture<'_, Option<Queue>> {
+        Box::pin(async move { Ok(self.put(queue)) })
+    }
+
+    fn get_queue(&self, url: &str, read: &mut dyn FnMut(&Queue)) -> bool {
+        match self.map().get(url) {
+            Some(queue) => {
+                read(&queue);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn update_queue<'a>(
+        &'a self,
+        url: &'a str,
+        update: &'a mut (dyn FnMut(&mut Queue) + Send),
+    ) -> StoreFuture<'a, bool> {
+        Box::pin(async move { Ok(self.modify(url, update)) })
+    }
+
+    fn remove_queue_if<'a>(
+        &'a self,
+        url: &'a str,
+        condition: &'a mut (dyn FnMut(&Queue) -> bool + Send),
+    ) -> StoreFuture<'a, Option<Queue>> {
+        Box::pin(async move { Ok(self.remove_if(url, condition)) })
+    }
+
+    fn list(&self, visit: &mut dyn FnMut(&Queue)) {
+        for queue in self.map().iter() {
+            visit(&queue);
+        }
+    }
+
+    fn replace_all(&self, queues: Vec<Queue>) -> StoreFuture<'_, Vec<Queue>> {
+        Box::pin(async move { Ok(self.replace(queues)) })
+    }
+}
+
+/// Why a [`Backend`](durable::Backend) couldn't read or write.
+pub type BackendError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A change for a [`Backend`](durable::Backend) to store outside of a
+/// queue's transactions.
+#[derive(Debug)]
+pub enum Write {
+    Queue(Box<QueueWrite>),
+    /// Drops the queue with this name.
+    Delete(String),
+    /// Replaces every stored queue, in one go.
+    ReplaceAll(Vec<QueueWrite>),
+}
+
+/// What a [`Backend`](durable::Backend) stores of one queue: its settings
+/// and tags, and the messages and deduplication IDs it has tracked as
+/// changed since it was loaded.
+#[derive(Debug)]
+pub struct QueueWrite {
+    pub(crate) name: String,
+    /// Whether whatever was stored for the queue is dropped first.
+    pub(crate) whole: bool,
+    pub(crate) settings: QueueSettings,
+    pub(crate) tags: BTreeMap<String, String>,
+    /// Messages added, changed or removed, by sequence number; `None` for
+    /// those removed.
+    pub(crate) messages: Vec<(u64, Option<StoredMessage>)>,
+    /// Receipt handles that stopped resolving.
+    pub(crate) released_handles: Vec<String>,
+    /// Whether every stored message is dropped before `messages` are
+    /// written, those the queue didn't have loaded included.
+    pub(crate) cleared: bool,
+    pub(crate) deduplication: DeduplicationChanges,
+}
+
+/// A message as a [`Backend`](durable::Backend) stores it.
+#[derive(Debug)]
+pub(crate) struct StoredMessage {
+    /// Unix milliseconds.
+    pub(crate) visible_from: i64,
+    /// Unix milliseconds.
+    pub(crate) sent_timestamp: i64,
+    pub(crate) receipt_handle: Option<String>,
+    /// Receipt handles of duplicate deliveries, which resolve to the message
+    /// as its own does.
+    pub(crate) duplicate_handles: Vec<String>,
+    pub(crate) message_group_id: Option<String>,
+    /// The message as a JSON [`MessageRecord`].
+    pub(crate) json: String,
+}
+
+/// A message as the durable stores keep it: a [`MessageSnapshot`], plus
+/// what snapshots leave out.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct MessageRecord {
+    #[serde(flatten)]
+    snapshot: MessageSnapshot,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    trace_context: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    claimed_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    history: Option<Box<MessageHistory>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    duplicate_handles: Vec<String>,
+}
+
+impl MessageRecord {
+    fn new(message: &Message, duplicate_handles: &[String]) -> Self {
+        Self {
+            snapshot: MessageSnapshot::from(message),
+            trace_context: message.trace_context.clone(),
+            claimed_by: message.claimed_by.clone(),
+            history: message.history.clone(),
+            duplicate_handles: duplicate_handles.to_vec(),
+        }
+    }
+
+    /// The message `seq`, with the receipt handles of its duplicate
+    /// deliveries.
+    pub(crate) fn into_message(self, seq: u64) -> (Message, Vec<String>) {
+        let mut message = Message::from(self.snapshot);
+        message.seq = seq;
+        message.trace_context = self.trace_context;
+        message.claimed_by = self.claimed_by;
+        message.history = self.history;
+        (message, self.duplicate_handles)
+    }
+}
+
+impl QueueWrite {
+    /// Takes what `queue` has tracked as changed, or if `whole`, all of it.
+    fn take(queue: &mut Queue, whole: bool) -> Self {
+        if whole {
+            track(queue);
+        }
+        let changes = queue.messages.take_changes();
+        let messages = changes
+            .messages
+            .into_iter()
+            .map(|seq| {
+                let stored = queue.messages.get(seq).map(|message| {
+                    let duplicate_handles = queue.messages.duplicate_handles(seq);
+                    let record = MessageRecord::new(message, duplicate_handles);
+                    StoredMessage {
+                        visible_from: message.visible_from.timestamp_millis(),
+                        sent_timestamp: message.sent_timestamp.timestamp_millis(),
+                        receipt_handle: message.receipt_handle.clone(),
+                        duplicate_handles: duplicate_handles.to_vec(),
+                        message_group_id: message.message_group_id.clone(),
+                        json: serde_json::to_string(&record).expect("messages serialize"),
+                    }
+                });
+                (seq, stored)
+            })
+            .collect();
+        Self {
+            name: queue.name.clone(),
+            whole,
+            settings: QueueSettings::from(&*queue),
+            tags: queue.tags.clone().into_iter().collect(),
+            messages,
+            released_handles: changes.released_handles,
+            cleared: changes.cleared,
+            deduplication: queue.deduplication.take_changes(),
+        }
+    }
+
+    /// Whether storing this would change nothing stored as `settings` and
+    /// `tags`, which were taken from the queue before its update.
+    pub(crate) fn changes_nothing(
+        &self,
+        settings: &QueueSettings,
+        tags: &BTreeMap<String, String>,
+    ) -> bool {
+        self.messages.is_empty()
+            && self.released_handles.is_empty()
+            && !self.cleared
+            && self.deduplication.inserted.is_empty()
+            && self.deduplication.expired.is_empty()
+            && self.settings == *settings
+            && self.tags == *tags
+    }
+}
+
+/// What a [`Backend`](durable::Backend) keeps of a queue besides its name, tags, messages and
+/// deduplication IDs.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct QueueSettings {
+    attributes: BTreeMap<String, String>,
+    created_timestamp: i64,
+    last_modified_timestamp: i64,
+    paused: bool,
+    webhook: Option<String>,
+    chaos: Option<ChaosSettings>,
+    stats: QueueStats,
+    sequence_number: u64,
+    /// How many messages the queue holds, and their bytes as counted
+    /// against the memory caps, for a store that doesn't load them.
+    #[serde(default)]
+    pub(crate) stored_messages: usize,
+    #[serde(default)]
+    pub(crate) stored_bytes: u64,
+    /// The sequence number the queue's next message gets.
+    #[serde(default)]
+    pub(crate) next_seq: u64,
+}
+
+impl From<&Queue> for QueueSettings {
+    fn from(queue: &Queue) -> Self {
+        Self {
+            attributes: queue.configured_attributes().into_iter().collect(),
+            created_timestamp: queue.created_timestamp,
+            last_modified_timestamp: queue.last_modified_timestamp,
+            paused: queue.paused,
+            webhook: queue.webhook.clone(),
+            chaos: queue.chaos,
+            stats: queue.stats.clone(),
+            sequence_number: queue.sequence_number,
+            stored_messages: queue.messages.len(),
+            stored_bytes: queue.stored_bytes,
+            next_seq: queue.messages.next_seq(),
+        }
+    }
+}
+
+impl QueueSettings {
+    /// The stored queue `name`, for `state`, without its messages or
+    /// deduplication IDs.
+    pub(crate) fn into_queue(
+        self,
+        state: &AppState,
+        name: &str,
+        tags: BTreeMap<String, String>,
+    ) -> Result<Queue, BackendError> {
+        let snapshot = QueueSnapshot {
+            name: name.to_string(),
+            attributes: self.attributes,
+            tags,
+            created_timestamp: self.created_timestamp,
+            last_modified_timestamp: self.last_modified_timestamp,
+            paused: self.paused,
+            messages: Vec::new(),
+            deduplication: Vec::new(),
+            webhook: self.webhook,
+            chaos: self.chaos,
+            stats: self.stats,
+            sequence_number: self.sequence_number,
+        };
+        let queue = snapshot.into_queue(state, state.queue_url(name));
+        Ok(queue.map_err(|e| e.message())?)
+    }
+
+    /// Like [`into_queue`](Self::into_queue), for a store that keeps
+    /// messages out of memory: the queue counts the stored messages and
+    /// their bytes, with none of them loaded.
+    pub(crate) fn into_unloaded_queue(
+        self,
+        state: &AppState,
+        name: &str,
+        tags: BTreeMap<String, String>,
+    ) -> Result<Queue, BackendError> {
+        let messages = MessageStore::unloaded(self.stored_messages, self.next_seq);
+        let stored_bytes = self.stored_bytes;
+        let mut queue = self.into_queue(state, name, tags)?;
+        queue.messages = messages;
+        queue.stored_bytes = stored_bytes;
+        Ok(queue)
+    }
+}
+
+/// Starts `queue` tracking its changes, with everything in it changed.
+fn track(queue: &mut Queue) {
+    queue.messages.track_changes();
+    queue.deduplication.track_changes();
+}
+
+fn failed(name: &str, e: BackendError) -> SqsError {
+    error!(queue = %name, error = %e, "failed to write to the queue store");
+    SqsError::InternalError(format!("Failed to store queue {}.", name))
+}
@@ -0,0 +1,497 @@
+//! The [`QueueStore`] the database-backed stores share. [`Durable`] keeps
+//! the messages in its [`Backend`], and in memory only each queue's
+//! settings, tags and deduplication IDs, along with what isn't stored at
+//! all: latency histograms, event logs and long-poll state.
+//!
+//! Every operation on a queue is one transaction in the backend. It reads
+//! the messages the operation's [`Selection`] picks, loads them into a copy
+//! of the queue for the operation to work on as on any queue in memory, and
+//! writes back what the operation changed. The copy replaces the queue only
+//! once that commits, so an operation that can't be stored leaves the queue
+//! as it was. Operations on a queue take turns; operations on different
+//! queues don't wait on each other.
+
+use super::{
+    failed, track, BackendError, MemoryStore, MessageRecord, QueueSettings, QueueStore,
+    QueueWrite, Selection, StoreFuture, Write,
+};
+use crate::clock::Clock;
+use crate::error::SqsError;
+use crate::messages::{MessageCounts, MessageStore};
+use crate::metrics;
+use crate::snapshot::ImportSummary;
+use crate::state::{AppState, Queue};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::RwLock;
+
+/// Messages as a [`Backend`] reads them: their sequence numbers and JSON
+/// [`MessageRecord`]s.
+pub type Records = Vec<(u64, String)>;
+
+/// The database behind a [`Durable`] store. Queues are stored by name,
+/// since their URLs follow the server's settings.
+pub trait Backend: Send + Sync + fmt::Debug + 'static {
+    /// A transaction on one queue, begun by [`select`](Self::select).
+    /// Dropping it rolls it back.
+    type Transaction: Send + 'static;
+
+    /// Begins a transaction on the stored queue `name` and reads the
+    /// messages `selection` picks as of `now`, each once.
+    fn select(
+        &self,
+        name: &str,
+        selection: &Selection,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = Result<(Self::Transaction, Records), BackendError>> + Send;
+
+    /// Stores `write` as part of `transaction` and commits it.
+    fn commit(
+        &self,
+        transaction: Self::Transaction,
+        write: QueueWrite,
+    ) -> impl Future<Output = Result<(), BackendError>> + Send;
+
+    /// Ends `transaction` without storing anything.
+    fn rollback(&self, transaction: Self::Transaction) -> impl Future<Output = ()> + Send;
+
+    /// Stores `write` in a transaction of its own.
+    fn write(&self, write: Write) -> impl Future<Output = Result<(), BackendError>> + Send;
+
+    /// The stored settings of queue `name`.
+    fn settings(
+        &self,
+        name: &str,
+    ) -> impl Future<Output = Result<QueueSettings, BackendError>> + Send;
+
+    /// The messages stored for queue `name` by state, as of `now`.
+    fn message_counts(
+        &self,
+        name: &str,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = Result<MessageCounts, BackendError>> + Send;
+
+    /// When the oldest message of queue `name` that is visible at `now`,
+    /// and not in flight, was sent.
+    fn oldest_visible(
+        &self,
+        name: &str,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Option<DateTime<Utc>>, BackendError>> + Send;
+
+    /// Rebuilds every stored queue for `state`, with none of its messages
+    /// loaded; see [`QueueSettings::into_unloaded_queue`]. Queue URLs and
+    /// ARNs follow `state`'s settings, not those of the server that stored
+    /// them.
+    fn load(
+        &self,
+        state: &AppState,
+    ) -> impl Future<Output = Result<Vec<Queue>, BackendError>> + Send;
+}
+
+/// Queues whose messages are kept in a [`Backend`]; see the
+/// [module docs](self).
+#[derive(Debug)]
+pub struct Durable<B> {
+    backend: Arc<B>,
+    /// Every queue, with none of its messages loaded.
+    queues: Arc<MemoryStore>,
+    /// Held for the whole of each operation on a queue, by URL.
+    turns: DashMap<String, Arc<tokio::sync::Mutex<()>>>,
+    /// Held shared by operations on a queue, and exclusively by operations
+    /// that add queues.
+    writes: Arc<RwLock<()>>,
+    /// Names of queues whose last commit failed, which may have been stored
+    /// all the same.
+    stale: Arc<Mutex<HashSet<String>>>,
+    /// How messages are loaded, as [`load`](Self::load) found the server.
+    loading: OnceLock<Loading>,
+}
+
+/// What loading a message takes from the server's settings.
+#[derive(Debug, Clone, Default)]
+struct Loading {
+    clock: Clock,
+    compress_bodies_above: Option<usize>,
+}
+
+impl<B: Backend> Durable<B> {
+    pub fn new(backend: B) -> Self {
+        Self {
+            backend: Arc::new(backend),
+            queues: Arc::default(),
+            turns: DashMap::new(),
+            writes: Arc::default(),
+            stale: Arc::default(),
+            loading: OnceLock::new(),
+        }
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Brings back the queues stored in the backend, for `state`, which must
+    /// be using this store.
+    pub async fn load(&self, state: &AppState) -> Result<ImportSummary, BackendError> {
+        let _ = self.loading.set(Loading {
+            clock: state.clock.clone(),
+            compress_bodies_above: state.compress_bodies_above,
+        });
+        let mut summary = ImportSummary::default();
+        for mut queue in self.backend.load(state).await? {
+            state.total_bytes.fetch_add(queue.stored_bytes, Ordering::Relaxed);
+            summary.queues += 1;
+            summary.messages += queue.messages.len();
+            // What was just read needn't be written back.
+            track(&mut queue);
+            queue.messages.take_changes();
+            queue.deduplication.take_changes();
+            self.queues.put(queue);
+        }
+        Ok(summary)
+    }
+
+    fn loading(&self) -> Loading {
+        self.loading.get().cloned().unwrap_or_default()
+    }
+
+    /// The lock operations on the queue at `url` take turns on, or `None`
+    /// if there is no such queue, so that requests to unknown URLs leave
+    /// nothing behind.
+    fn turn(&self, url: &str) -> Option<Arc<tokio::sync::Mutex<()>>> {
+        if !self.queues.get_queue(url, &mut |_| {}) {
+            return None;
+        }
+        Some(self.turns.entry(url.to_string()).or_default().clone())
+    }
+
+    /// A copy of the queue at `url`, if there is one.
+    fn copy(&self, url: &str) -> Option<Queue> {
+        let mut copy = None;
+        self.queues.get_queue(url, &mut |queue| copy = Some(queue.clone()));
+        copy
+    }
+
+    /// The name of the queue at `url`, or `QueueDoesNotExist`.
+    fn name(&self, url: &str) -> Result<String, SqsError> {
+        let mut name = None;
+        self.queues.get_queue(url, &mut |queue| name = Some(queue.name.clone()));
+        name.ok_or(SqsError::QueueDoesNotExist)
+    }
+
+    /// Writes `queue` whole, ahead of adding it to memory without its
+    /// messages.
+    async fn add(&self, mut queue: Queue) -> Result<Queue, SqsError> {
+        let write = QueueWrite::take(&mut queue, true);
+        let written = self.backend.write(Write::Queue(Box::new(write))).await;
+        written.map_err(|e| failed(&queue.name, e))?;
+        self.stale.lock().unwrap().remove(&queue.name);
+        queue.messages.unload();
+        Ok(queue)
+    }
+
+    /// Brings `queue`'s message count, bytes and counters back in line with
+    /// the backend if its last commit failed.
+    async fn resync(&self, queue: &mut Queue) -> Result<(), SqsError> {
+        if !self.stale.lock().unwrap().contains(&queue.name) {
+            return Ok(());
+        }
+        let settings = self.backend.settings(&queue.name).await;
+        let settings = settings.map_err(|e| failed(&queue.name, e))?;
+        queue.messages = MessageStore::unloaded(settings.stored_messages, settings.next_seq);
+        queue.messages.track_changes();
+        if settings.stored_bytes > queue.stored_bytes {
+            let gained = settings.stored_bytes - queue.stored_bytes;
+            queue.total_bytes.fetch_add(gained, Ordering::Relaxed);
+        } else {
+            let lost = queue.stored_bytes - settings.stored_bytes;
+            queue.total_bytes.fetch_sub(lost, Ordering::Relaxed);
+        }
+        queue.stored_bytes = settings.stored_bytes;
+        queue.sequence_number = settings.sequence_number;
+        queue.stats = settings.stats;
+        Ok(())
+    }
+
+    /// Loads `messages` into `queue`.
+    fn load_messages(
+        &self,
+        queue: &mut Queue,
+        messages: Records,
+        loading: &Loading,
+    ) -> Result<(), BackendError> {
+        let now = loading.clock.now();
+        for (seq, json) in messages {
+            let record: MessageRecord = serde_json::from_str(&json)
+                .map_err(|e| format!("invalid message {} on queue {}: {}", seq, queue.name, e))?;
+            let (mut message, duplicate_handles) = record.into_message(seq);
+            message.compress_body(loading.compress_bodies_above);
+            queue.messages.load(message, duplicate_handles, now);
+        }
+        Ok(())
+    }
+
+    /// Reads what `select` picks of the messages of a copy of the queue at
+    /// `url` and applies `update` to it, then commits what it changed and
+    /// keeps the copy.
+    async fn modify(
+        &self,
+        url: &str,
+        select: &(dyn Fn(&Queue) -> Selection + Sync),
+        update: &mut (dyn FnMut(&mut Queue) + Send),
+    ) -> Result<bool, SqsError> {
+        let writes = self.writes.clone().read_owned().await;
+        let Some(turn) = self.turn(url) else {
+            return Ok(false);
+        };
+        let turn = turn.lock_owned().await;
+        let Some(mut queue) = self.copy(url) else {
+            return Ok(false);
+        };
+        let stored_bytes = queue.stored_bytes;
+        let purge_generation = queue.purge_generation;
+
+        let updated = async {
+            self.resync(&mut queue).await?;
+            let loading = self.loading();
+            let selection = select(&queue);
+            let selected = self.backend.select(&queue.name, &selection, loading.clock.now());
+            let (transaction, messages) = selected.await.map_err(|e| failed(&queue.name, e))?;
+            self.load_messages(&mut queue, messages, &loading)
+                .map_err(|e| failed(&queue.name, e))?;
+
+            let settings = QueueSettings::from(&queue);
+            let tags: BTreeMap<String, String> = queue.tags.clone().into_iter().collect();
+            update(&mut queue);
+            let write = QueueWrite::take(&mut queue, false);
+            let write = (!write.changes_nothing(&settings, &tags)).then_some(write);
+            Ok::<_, SqsError>((transaction, write))
+        };
+        let (transaction, write) = match updated.await {
+            Ok(updated) => updated,
+            Err(e) => {
+                give_back_bytes(&queue, stored_bytes);
+                return Err(e);
+            }
+        };
+
+        // Committed, and the copy kept, on a task of its own, so that the
+        // two happen together even if the request goes away meanwhile.
+        let backend = self.backend.clone();
+        let (queues, stale) = (self.queues.clone(), self.stale.clone());
+        let name = queue.name.clone();
+        let committed = tokio::spawn(async move {
+            let _held = (writes, turn);
+            let committed = match write {
+                Some(write) => backend.commit(transaction, write).await,
+                None => {
+                    backend.rollback(transaction).await;
+                    Ok(())
+                }
+            };
+            if let Err(e) = committed {
+                stale.lock().unwrap().insert(queue.name.clone());
+                give_back_bytes(&queue, stored_bytes);
+                return Err(failed(&queue.name, e));
+            }
+            stale.lock().unwrap().remove(&queue.name);
+            queue.messages.unload();
+            let purged = queue.purge_generation != purge_generation;
+            let notify = queue.notify.clone();
+            queues.put(queue);
+            // Long polls tell a purge by the queue in memory, which it
+            // reaches only now, so those it woke are woken again.
+            if purged {
+                notify.notify_waiters();
+            }
+            Ok(())
+        });
+        committed.await.map_err(|e| failed(&name, e.into()))??;
+        Ok(true)
+    }
+}
+
+/// Takes back from the server-wide byte count what `queue` gained or lost
+/// since it held `stored_bytes`.
+fn give_back_bytes(queue: &Queue, stored_bytes: u64) {
+    if queue.stored_bytes > stored_bytes {
+        let gained = queue.stored_bytes - stored_bytes;
+        queue.total_bytes.fetch_sub(gained, Ordering::Relaxed);
+    } else {
+        let lost = stored_bytes - queue.stored_bytes;
+        queue.total_bytes.fetch_add(lost, Ordering::Relaxed);
+    }
+}
+
+impl<B: Backend> QueueStore for Durable<B> {
+    fn create_queue<'a>(
+        &'a self,
+        queue: Queue,
+        existing: &'a mut (dyn FnMut(&Queue) + Send),
+    ) -> StoreFuture<'a, bool> {
+        Box::pin(async move {
+            let _writes = self.writes.write().await;
+            if self.queues.get_queue(&queue.url, existing) {
+                return Ok(false);
+            }
+            let queue = self.add(queue).await?;
+            Ok(self.queues.create(queue, existing))
+        })
+    }
+
+    fn put_queue(&self, queue: Queue) -> StoreFuture<'_, Option<Queue>> {
+        Box::pin(async move {
+            let _writes = self.writes.write().await;
+            let queue = self.add(queue).await?;
+            Ok(self.queues.put(queue))
+        })
+    }
+
+    fn get_queue(&self, url: &str, read: &mut dyn FnMut(&Queue)) -> bool {
+        self.queues.get_queue(url, read)
+    }
+
+    fn update_queue<'a>(
+        &'a self,
+        url: &'a str,
+        update: &'a mut (dyn FnMut(&mut Queue) + Send),
+    ) -> StoreFuture<'a, bool> {
+        Box::pin(self.modify(url, &|_| Selection::all(), update))
+    }
+
+    fn update_queue_selected<'a>(
+        &'a self,
+        url: &'a str,
+        select: &'a (dyn Fn(&Queue) -> Selection + Sync),
+        update: &'a mut (dyn FnMut(&mut Queue) + Send),
+    ) -> StoreFuture<'a, bool> {
+        Box::pin(self.modify(url, select, update))
+    }
+
+    fn inspect_queue<'a>(
+        &'a self,
+        url: &'a str,
+        read: &'a mut (dyn FnMut(&Queue) + Send),
+    ) -> StoreFuture<'a, bool> {
+        Box::pin(async move {
+            let _writes = self.writes.read().await;
+            let Some(turn) = self.turn(url) else {
+                return Ok(false);
+            };
+            let _turn = turn.lock().await;
+            let Some(mut queue) = self.copy(url) else {
+                return Ok(false);
+            };
+            let stored_bytes = queue.stored_bytes;
+            self.resync(&mut queue).await?;
+            // Nothing is kept of what was read, so whatever the stale
+            // queue had to give back was only borrowed.
+            give_back_bytes(&queue, stored_bytes);
+            let loading = self.loading();
+            let all = Selection::all();
+            let selected = self.backend.select(&queue.name, &all, loading.clock.now());
+            let (transaction, messages) = selected.await.map_err(|e| failed(&queue.name, e))?;
+            self.backend.rollback(transaction).await;
+            self.load_messages(&mut queue, messages, &loading)
+                .map_err(|e| failed(&queue.name, e))?;
+            read(&queue);
+            Ok(true)
+        })
+    }
+
+    fn message_counts<'a>(
+        &'a self,
+        url: &'a str,
+        now: DateTime<Utc>,
+    ) -> StoreFuture<'a, MessageCounts> {
+        Box::pin(async move {
+            let name = self.name(url)?;
+            let counts = self.backend.message_counts(&name, now).await;
+            counts.map_err(|e| failed(&name, e))
+        })
+    }
+
+    fn oldest_visible_message_age<'a>(
+        &'a self,
+        url: &'a str,
+        now: DateTime<Utc>,
+    ) -> StoreFuture<'a, f64> {
+        Box::pin(async move {
+            let name = self.name(url)?;
+            let oldest = self.backend.oldest_visible(&name, now).await;
+            let oldest = oldest.map_err(|e| failed(&name, e))?;
+            Ok(oldest.map_or(0.0, |sent| metrics::seconds_between(sent, now)))
+        })
+    }
+
+    fn remove_queue_if<'a>(
+        &'a self,
+        url: &'a str,
+        condition: &'a mut (dyn FnMut(&Queue) -> bool + Send),
+    ) -> StoreFuture<'a, Option<Queue>> {
+        Box::pin(async move {
+            let _writes = self.writes.read().await;
+            let Some(turn) = self.turn(url) else {
+                return Ok(None);
+            };
+            let _turn = turn.lock().await;
+            let mut removed = None;
+            self.queues.get_queue(url, &mut |queue| {
+                removed = condition(queue).then(|| queue.name.clone());
+            });
+            let Some(name) = removed else {
+                return Ok(None);
+            };
+            let written = self.backend.write(Write::Delete(name.clone())).await;
+            written.map_err(|e| failed(&name, e))?;
+            self.stale.lock().unwrap().remove(&name);
+            self.turns.remove(url);
+            Ok(self.queues.remove_if(url, &mut |_| true))
+        })
+    }
+
+    fn list(&self, visit: &mut dyn FnMut(&Queue)) {
+        self.queues.list(visit)
+    }
+
+    fn replace_all(&self, mut queues: Vec<Queue>) -> StoreFuture<'_, Vec<Queue>> {
+        Box::pin(async move {
+            let _writes = self.writes.write().await;
+            let writes = queues.iter_mut().map(|queue| QueueWrite::take(queue, true)).collect();
+            self.backend.write(Write::ReplaceAll(writes)).await.map_err(|e| {
+                tracing::error!(error = %e, "failed to write to the queue store");
+                SqsError::InternalError("Failed to store the queues.".to_string())
+            })?;
+            self.stale.lock().unwrap().clear();
+            for queue in &mut queues {
+                queue.messages.unload();
+            }
+            Ok(self.queues.replace(queues))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::sqlite::SqliteStore;
+    use std::path::Path;
+
+    #[tokio::test]
+    async fn unknown_urls_leave_no_turns_behind() {
+        let store = SqliteStore::open(Path::new(":memory:")).unwrap();
+        for i in 0..3 {
+            let url = format!("http://localhost:9324/000000000000/missing-{}", i);
+            assert!(!store.update_queue(&url, &mut |_| {}).await.unwrap());
+            assert!(!store.inspect_queue(&url, &mut |_| {}).await.unwrap());
+            assert!(store.remove_queue_if(&url, &mut |_| true).await.unwrap().is_none());
+        }
+        assert!(store.turns.is_empty());
+    }
+}
@@ -0,0 +1,597 @@
+//! A [`QueueStore`](super::QueueStore) backed by Redis, so that queues
+//! survive a restart and several tools can look at the same data.
+//!
+//! [`RedisStore`] is a [`Durable`] store: messages live in Redis, not in
+//! memory. Every operation on a queue `WATCH`es the queue's keys on a
+//! connection of its own, reads the messages it needs through the sorted
+//! sets below, and writes back what it changed in one `MULTI`/`EXEC`
+//! transaction. A receive reads the visible messages by send order and the
+//! lapsed in-flight ones by visibility deadline; a delete or visibility
+//! change reads just the message its receipt handle resolves to. If another
+//! client changes the queue meanwhile, `EXEC` stores nothing and the
+//! operation fails like any that can't be stored, so two servers sharing a
+//! Redis never claim the same message. Other commands go through a
+//! [`ConnectionManager`], which reconnects after Redis goes away.
+//!
+//! Under `local-sqs:`, the set `queues` names every queue, and for each
+//! queue `queue:{name}` is a hash of its settings as JSON values, with
+//! alongside it:
+//!
+//! - `queue:{name}:tags`, a hash of its tags;
+//! - `queue:{name}:messages`, a hash from sequence number to the message as
+//!   a JSON [`MessageRecord`];
+//! - `queue:{name}:ready`, a sorted set of the messages that were visible
+//!   when last written, scored by sequence number;
+//! - `queue:{name}:delayed`, a sorted set of the other messages not in
+//!   flight, scored by when they become visible in Unix milliseconds. Those
+//!   whose delay has passed move to `ready` with the next transaction that
+//!   reads them;
+//! - `queue:{name}:deadlines`, a sorted set of the in-flight messages,
+//!   scored by the end of their visibility timeout;
+//! - `queue:{name}:in_flight`, a hash from receipt handle to the sequence
+//!   number of the in-flight message it resolves to;
+//! - `queue:{name}:sent`, a sorted set of every message, scored by when it
+//!   was sent;
+//! - `queue:{name}:groups`, a hash from sequence number to message group,
+//!   for the messages of FIFO queues;
+//! - `queue:{name}:deduplication`, a hash from `{group}\n{id}` (the group
+//!   empty for IDs scoped to the whole queue) to the FIFO deduplication
+//!   IDs that haven't expired yet. IDs are removed as they expire.
+//!
+//! Latency histograms, event logs and long-poll state are not stored.
+//!
+//! [`MessageRecord`]: crate::store::MessageRecord
+
+use crate::deduplication::Accepted;
+use crate::messages::MessageCounts;
+use crate::state::{AppState, Queue};
+use crate::store::durable::{Backend, Durable, Records};
+use crate::store::{BackendError, QueueSettings, QueueWrite, Selection, Write};
+use chrono::{DateTime, Utc};
+use redis::aio::{ConnectionManager, ConnectionManagerConfig, MultiplexedConnection};
+use redis::{AsyncCommands, AsyncConnectionConfig, Client, Pipeline, RedisError, RedisResult};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const PREFIX: &str = "local-sqs";
+
+/// How long to wait for Redis to accept a connection or answer a command
+/// before failing the request.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many more times to try connecting to Redis before failing the
+/// request. A request that fails that way is answered at once; the next one
+/// tries again.
+const CONNECTION_RETRIES: usize = 1;
+
+/// How many ready messages of a FIFO queue a receive reads the groups of
+/// at a time, looking for those in groups no in-flight message holds.
+const GROUP_PAGE: isize = 100;
+
+/// The keys holding a queue's messages, by part.
+const MESSAGE_PARTS: [&str; 7] =
+    ["messages", "ready", "delayed", "deadlines", "in_flight", "sent", "groups"];
+
+/// Queues kept on a Redis server.
+pub type RedisStore = Durable<RedisBackend>;
+
+impl RedisStore {
+    /// A store for the Redis server at `url`, e.g. `redis://127.0.0.1/`.
+    /// Nothing is sent until [`load`](Durable::load) is called, once the
+    /// server's state exists, to bring back the queues stored there. Must be
+    /// called within a Tokio runtime.
+    pub fn open(url: &str) -> RedisResult<Self> {
+        let client = Client::open(url)?;
+        let server = client.get_connection_info().addr().to_string();
+        let config = ConnectionManagerConfig::new()
+            .set_number_of_retries(CONNECTION_RETRIES)
+            .set_connection_timeout(Some(TIMEOUT))
+            .set_response_timeout(Some(TIMEOUT));
+        Ok(Durable::new(RedisBackend {
+            server,
+            connection: ConnectionManager::new_lazy_with_config(client.clone(), config)?,
+            client,
+            idle: Mutex::default(),
+        }))
+    }
+}
+
+/// The Redis server behind a [`RedisStore`].
+pub struct RedisBackend {
+    server: String,
+    connection: ConnectionManager,
+    client: Client,
+    /// Connections for transactions, which `WATCH` keys and so need one to
+    /// themselves, kept for the next transaction once one ends.
+    idle: Mutex<Vec<MultiplexedConnection>>,
+}
+
+impl fmt::Debug for RedisBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisBackend").field("server", &self.server).finish_non_exhaustive()
+    }
+}
+
+/// A transaction on one stored queue: a connection `WATCH`ing its keys.
+/// Dropping it drops the connection, and with it the `WATCH`.
+pub struct RedisTransaction {
+    con: MultiplexedConnection,
+    /// Unix milliseconds, as of which the messages were selected.
+    now: i64,
+    /// Delayed messages whose delay had passed by `now`.
+    due: Vec<u64>,
+}
+
+/// A deduplication ID as kept in `queue:{name}:deduplication`.
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredId {
+    message_id: String,
+    sequence_number: String,
+    /// Unix milliseconds.
+    expires: i64,
+}
+
+fn queues_key() -> String {
+    format!("{}:queues", PREFIX)
+}
+
+fn queue_key(name: &str, part: &str) -> String {
+    match part {
+        "" => format!("{}:queue:{}", PREFIX, name),
+        part => format!("{}:queue:{}:{}", PREFIX, name, part),
+    }
+}
+
+/// Every key holding part of the queue `name`.
+fn queue_keys(name: &str) -> Vec<String> {
+    ["", "tags", "deduplication"]
+        .into_iter()
+        .chain(MESSAGE_PARTS)
+        .map(|part| queue_key(name, part))
+        .collect()
+}
+
+impl RedisBackend {
+    /// Runs `command` on the connection, and once more if the connection
+    /// had dropped: the manager is reconnecting by then, and every command
+    /// sent here can safely be repeated.
+    async fn retried<T, F: Future<Output = RedisResult<T>>>(
+        &self,
+        mut command: impl FnMut(ConnectionManager) -> F,
+    ) -> RedisResult<T> {
+        match command(self.connection.clone()).await {
+            Err(e) if dropped(&e) => command(self.connection.clone()).await,
+            result => result,
+        }
+    }
+
+    /// A new connection for a transaction.
+    async fn connect(&self) -> RedisResult<MultiplexedConnection> {
+        let config = AsyncConnectionConfig::new()
+            .set_connection_timeout(Some(TIMEOUT))
+            .set_response_timeout(Some(TIMEOUT));
+        self.client.get_multiplexed_async_connection_with_config(&config).await
+    }
+
+    /// Keeps `con`, done with its transaction, for the next one.
+    fn keep(&self, con: MultiplexedConnection) {
+        self.idle.lock().unwrap().push(con);
+    }
+}
+
+/// Whether `e` means the connection has to be made again.
+fn dropped(e: &RedisError) -> bool {
+    e.is_io_error() || e.is_unrecoverable_error()
+}
+
+impl Backend for RedisBackend {
+    type Transaction = RedisTransaction;
+
+    async fn select(
+        &self,
+        name: &str,
+        selection: &Selection,
+        now: DateTime<Utc>,
+    ) -> Result<(RedisTransaction, Records), BackendError> {
+        let now = now.timestamp_millis();
+        let mut idle = self.idle.lock().unwrap().pop();
+        loop {
+            let reused = idle.is_some();
+            let mut con = match idle.take() {
+                Some(con) => con,
+                None => self.connect().await?,
+            };
+            match select_messages(&mut con, name, selection, now).await {
+                Ok(Some((due, messages))) => {
+                    return Ok((RedisTransaction { con, now, due }, messages));
+                }
+                Ok(None) => return Err(format!("queue {} is not stored", name).into()),
+                // An idle connection may have dropped since its last use.
+                Err(e) if reused && dropped(&e) => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    async fn commit(
+        &self,
+        mut transaction: RedisTransaction,
+        write: QueueWrite,
+    ) -> Result<(), BackendError> {
+        let name = write.name.clone();
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        write_queue(&mut pipe, write, Some(transaction.now), &transaction.due);
+        let executed: Option<()> = pipe.query_async(&mut transaction.con).await?;
+        self.keep(transaction.con);
+        executed.ok_or_else(|| format!("queue {} was changed by another client", name).into())
+    }
+
+    async fn rollback(&self, mut transaction: RedisTransaction) {
+        let unwatched = redis::cmd("UNWATCH").exec_async(&mut transaction.con).await;
+        if unwatched.is_ok() {
+            self.keep(transaction.con);
+        }
+    }
+
+    async fn write(&self, write: Write) -> Result<(), BackendError> {
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        match write {
+            Write::Queue(queue) => {
+                if queue.whole {
+                    pipe.del(queue_keys(&queue.name)).ignore();
+                }
+                write_queue(&mut pipe, *queue, None, &[]);
+            }
+            Write::Delete(name) => {
+                pipe.srem(queues_key(), &name).ignore().del(queue_keys(&name)).ignore();
+            }
+            Write::ReplaceAll(queues) => {
+                let stored: Vec<String> = self
+                    .retried(|mut con| async move { con.smembers(queues_key()).await })
+                    .await?;
+                pipe.del(queues_key()).ignore();
+                for name in &stored {
+                    pipe.del(queue_keys(name)).ignore();
+                }
+                for queue in queues {
+                    write_queue(&mut pipe, queue, None, &[]);
+                }
+            }
+        }
+        let pipe = &pipe;
+        Ok(self.retried(|mut con| async move { pipe.exec_async(&mut con).await }).await?)
+    }
+
+    async fn settings(&self, name: &str) -> Result<QueueSettings, BackendError> {
+        let key = &queue_key(name, "");
+        let fields = self.retried(|mut con| async move { con.hgetall(key).await }).await?;
+        read_settings(name, fields)
+    }
+
+    async fn message_counts(
+        &self,
+        name: &str,
+        now: DateTime<Utc>,
+    ) -> Result<MessageCounts, BackendError> {
+        let now = now.timestamp_millis();
+        let (delayed, deadlines) = (queue_key(name, "delayed"), queue_key(name, "deadlines"));
+        let mut pipe = redis::pipe();
+        pipe.zcard(queue_key(name, "ready"))
+            .zcard(&delayed)
+            .zcount(&delayed, "-inf", now)
+            .zcard(&deadlines)
+            .zcount(&deadlines, "-inf", now);
+        let pipe = &pipe;
+        let counted = self.retried(|mut con| async move { pipe.query_async(&mut con).await });
+        let (ready, delayed, delayed_due, in_flight, in_flight_due): (usize, usize, usize, usize, usize) =
+            counted.await?;
+        Ok(MessageCounts {
+            visible: ready + delayed_due + in_flight_due,
+            delayed: delayed - delayed_due,
+            in_flight: in_flight - in_flight_due,
+        })
+    }
+
+    async fn oldest_visible(
+        &self,
+        name: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<DateTime<Utc>>, BackendError> {
+        let mut pipe = redis::pipe();
+        pipe.zrange(queue_key(name, "ready"), 0, 0).zrangebyscore(
+            queue_key(name, "delayed"),
+            "-inf",
+            now.timestamp_millis(),
+        );
+        let pipe = &pipe;
+        let (ready, due): (Vec<u64>, Vec<u64>) =
+            self.retried(|mut con| async move { pipe.query_async(&mut con).await }).await?;
+        let Some(seq) = ready.into_iter().chain(due).min() else {
+            return Ok(None);
+        };
+        let key = &queue_key(name, "sent");
+        let sent: Option<f64> =
+            self.retried(|mut con| async move { con.zscore(key, seq).await }).await?;
+        Ok(sent.and_then(|sent| DateTime::from_timestamp_millis(sent as i64)))
+    }
+
+    /// Deduplication IDs that expired while the server was stopped are
+    /// dropped.
+    async fn load(&self, state: &AppState) -> Result<Vec<Queue>, BackendError> {
+        let mut con = self.connection.clone();
+        let now = state.clock.now();
+        let names: Vec<String> = con.smembers(queues_key()).await?;
+        let mut queues = Vec::new();
+        for name in names {
+            queues.push(read_queue(&mut con, state, &name, now).await?);
+        }
+        Ok(queues)
+    }
+}
+
+/// `WATCH`es the keys of the stored queue `name` on `con` and reads the
+/// messages `selection` picks as of `now` (Unix milliseconds), along with
+/// the delayed messages whose delay has passed, or `None` if the queue
+/// isn't stored.
+async fn select_messages(
+    con: &mut MultiplexedConnection,
+    name: &str,
+    selection: &Selection,
+    now: i64,
+) -> RedisResult<Option<(Vec<u64>, Records)>> {
+    redis::cmd("WATCH").arg(queue_keys(name)).exec_async(con).await?;
+    let stored: bool = con.exists(queue_key(name, "")).await?;
+    if !stored {
+        return Ok(None);
+    }
+    let key = |part| queue_key(name, part);
+
+    let due: Vec<u64> = if selection.all || selection.visible > 0 {
+        con.zrangebyscore(key("delayed"), "-inf", now).await?
+    } else {
+        Vec::new()
+    };
+    if selection.all {
+        let messages: HashMap<u64, String> = con.hgetall(key("messages")).await?;
+        let messages: BTreeMap<u64, String> = messages.into_iter().collect();
+        return Ok(Some((due, messages.into_iter().collect())));
+    }
+
+    let mut seqs = BTreeSet::new();
+    if selection.visible > 0 && selection.unlocked_groups {
+        let locked = locked_groups(con, name, now).await?;
+        seqs.extend(unlocked(con, name, &due, &locked).await?);
+        let mut start = 0;
+        let mut found = 0;
+        loop {
+            let ready: Vec<u64> = con.zrange(key("ready"), start, start + GROUP_PAGE - 1).await?;
+            let unlocked = unlocked(con, name, &ready, &locked).await?;
+            found += unlocked.len();
+            seqs.extend(unlocked);
+            if found >= selection.visible || ready.len() < GROUP_PAGE as usize {
+                break;
+            }
+            start += GROUP_PAGE;
+        }
+    } else if selection.visible > 0 {
+        // Every ready message if there's no end to how many.
+        let stop = isize::try_from(selection.visible).map_or(-1, |visible| visible - 1);
+        let ready: Vec<u64> = con.zrange(key("ready"), 0, stop).await?;
+        seqs.extend(ready);
+        seqs.extend(&due);
+    }
+    if selection.in_flight {
+        let in_flight: Vec<u64> = con.zrange(key("deadlines"), 0, -1).await?;
+        seqs.extend(in_flight);
+    } else if selection.lapsed {
+        let lapsed: Vec<u64> = con.zrangebyscore(key("deadlines"), "-inf", now).await?;
+        seqs.extend(lapsed);
+    }
+    if let Some(sent_by) = selection.sent_by {
+        let sent: Vec<u64> =
+            con.zrangebyscore(key("sent"), "-inf", sent_by.timestamp_millis()).await?;
+        seqs.extend(sent);
+    }
+    if let Some(receipt_handle) = &selection.receipt_handle {
+        let seq: Option<u64> = con.hget(key("in_flight"), receipt_handle).await?;
+        seqs.extend(seq);
+    }
+
+    let seqs: Vec<u64> = seqs.into_iter().collect();
+    if seqs.is_empty() {
+        return Ok(Some((due, Vec::new())));
+    }
+    let messages: Vec<Option<String>> = con.hmget(key("messages"), &seqs).await?;
+    let messages = seqs
+        .into_iter()
+        .zip(messages)
+        .filter_map(|(seq, json)| Some((seq, json?)))
+        .collect();
+    Ok(Some((due, messages)))
+}
+
+/// The groups of the queue `name` that in-flight messages hold locked at
+/// `now`.
+async fn locked_groups(
+    con: &mut MultiplexedConnection,
+    name: &str,
+    now: i64,
+) -> RedisResult<HashSet<String>> {
+    let after_now = format!("({}", now);
+    let in_flight: Vec<u64> =
+        con.zrangebyscore(queue_key(name, "deadlines"), after_now, "+inf").await?;
+    if in_flight.is_empty() {
+        return Ok(HashSet::new());
+    }
+    let groups: Vec<Option<String>> = con.hmget(queue_key(name, "groups"), &in_flight).await?;
+    Ok(groups.into_iter().flatten().collect())
+}
+
+/// Those of the messages `seqs` of the queue `name` not in a `locked`
+/// group.
+async fn unlocked(
+    con: &mut MultiplexedConnection,
+    name: &str,
+    seqs: &[u64],
+    locked: &HashSet<String>,
+) -> RedisResult<Vec<u64>> {
+    if seqs.is_empty() || locked.is_empty() {
+        return Ok(seqs.to_vec());
+    }
+    let groups: Vec<Option<String>> = con.hmget(queue_key(name, "groups"), seqs).await?;
+    Ok(seqs
+        .iter()
+        .zip(groups)
+        .filter(|(_, group)| group.as_ref().is_none_or(|group| !locked.contains(group)))
+        .map(|(seq, _)| *seq)
+        .collect())
+}
+
+/// Adds to `pipe` the commands writing `queue`'s settings, tags, changed
+/// messages and deduplication IDs, and moving the `due` messages that
+/// weren't changed to `ready`. Messages not in flight are `ready` if
+/// visible at `now` (Unix milliseconds), and otherwise, or if there is no
+/// `now`, `delayed`.
+fn write_queue(pipe: &mut Pipeline, queue: QueueWrite, now: Option<i64>, due: &[u64]) {
+    let name = &queue.name;
+    pipe.sadd(queues_key(), name).ignore();
+    let settings = serde_json::to_value(&queue.settings).expect("queue settings serialize");
+    let Value::Object(settings) = settings else {
+        unreachable!("queue settings serialize to an object")
+    };
+    let fields: Vec<(String, String)> = settings
+        .into_iter()
+        .map(|(field, value)| (field, value.to_string()))
+        .collect();
+    pipe.hset_multiple(queue_key(name, ""), &fields).ignore();
+
+    let tags_key = queue_key(name, "tags");
+    pipe.del(&tags_key).ignore();
+    if !queue.tags.is_empty() {
+        let tags: Vec<(&String, &String)> = queue.tags.iter().collect();
+        pipe.hset_multiple(&tags_key, &tags).ignore();
+    }
+
+    let key = |part| queue_key(name, part);
+    let (messages_key, in_flight_key, groups_key) = (key("messages"), key("in_flight"), key("groups"));
+    let (ready_key, delayed_key, deadlines_key) = (key("ready"), key("delayed"), key("deadlines"));
+    if queue.cleared {
+        pipe.del(&MESSAGE_PARTS.map(key)[..]).ignore();
+    }
+    if !queue.released_handles.is_empty() {
+        pipe.hdel(&in_flight_key, &queue.released_handles).ignore();
+    }
+    let mut written = HashSet::new();
+    for (seq, message) in queue.messages {
+        written.insert(seq);
+        for key in [&ready_key, &delayed_key, &deadlines_key] {
+            pipe.zrem(key, seq).ignore();
+        }
+        let Some(message) = message else {
+            pipe.hdel(&messages_key, seq).ignore();
+            pipe.zrem(key("sent"), seq).ignore();
+            pipe.hdel(&groups_key, seq).ignore();
+            continue;
+        };
+        pipe.hset(&messages_key, seq, message.json).ignore();
+        pipe.zadd(key("sent"), seq, message.sent_timestamp).ignore();
+        if let Some(group) = &message.message_group_id {
+            pipe.hset(&groups_key, seq, group).ignore();
+        }
+        match &message.receipt_handle {
+            Some(receipt_handle) => {
+                pipe.zadd(&deadlines_key, seq, message.visible_from).ignore();
+                pipe.hset(&in_flight_key, receipt_handle, seq).ignore();
+                for receipt_handle in &message.duplicate_handles {
+                    pipe.hset(&in_flight_key, receipt_handle, seq).ignore();
+                }
+            }
+            None if now.is_some_and(|now| message.visible_from <= now) => {
+                pipe.zadd(&ready_key, seq, seq).ignore();
+            }
+            None => {
+                pipe.zadd(&delayed_key, seq, message.visible_from).ignore();
+            }
+        }
+    }
+    for &seq in due.iter().filter(|seq| !written.contains(seq)) {
+        pipe.zrem(&delayed_key, seq).ignore().zadd(&ready_key, seq, seq).ignore();
+    }
+
+    let deduplication_key = queue_key(name, "deduplication");
+    let field = |(group, deduplication_id): (Option<String>, String)| {
+        format!("{}\n{}", group.unwrap_or_default(), deduplication_id)
+    };
+    if !queue.deduplication.expired.is_empty() {
+        let expired: Vec<String> = queue.deduplication.expired.into_iter().map(field).collect();
+        pipe.hdel(&deduplication_key, expired).ignore();
+    }
+    for (key, accepted, expires) in queue.deduplication.inserted {
+        let stored = StoredId {
+            message_id: accepted.message_id,
+            sequence_number: accepted.sequence_number,
+            expires: expires.timestamp_millis(),
+        };
+        let json = serde_json::to_string(&stored).expect("deduplication IDs serialize");
+        pipe.hset(&deduplication_key, field(key), json).ignore();
+    }
+}
+
+/// The settings of queue `name`, from the `fields` of its settings hash.
+fn read_settings(name: &str, fields: HashMap<String, String>) -> Result<QueueSettings, BackendError> {
+    let settings = fields
+        .into_iter()
+        .map(|(field, value)| Ok((field, serde_json::from_str(&value)?)))
+        .collect::<serde_json::Result<Map<String, Value>>>()
+        .and_then(|settings| serde_json::from_value::<QueueSettings>(Value::Object(settings)))
+        .map_err(|e| format!("invalid settings for queue {}: {}", name, e))?;
+    Ok(settings)
+}
+
+/// Rebuilds the stored queue `name`, with none of its messages loaded.
+async fn read_queue(
+    con: &mut ConnectionManager,
+    state: &AppState,
+    name: &str,
+    now: DateTime<Utc>,
+) -> Result<Queue, BackendError> {
+    let settings = read_settings(name, con.hgetall(queue_key(name, "")).await?)?;
+    let tags: BTreeMap<String, String> = con.hgetall(queue_key(name, "tags")).await?;
+    let mut queue = settings.into_unloaded_queue(state, name, tags)?;
+
+    let deduplication_key = queue_key(name, "deduplication");
+    let stored: HashMap<String, String> = con.hgetall(&deduplication_key).await?;
+    let mut ids = Vec::new();
+    let mut expired = Vec::new();
+    for (field, json) in stored {
+        let invalid = || format!("invalid deduplication ID on queue {}", name);
+        let (group, deduplication_id) = field.split_once('\n').ok_or_else(invalid)?;
+        let id: StoredId = serde_json::from_str(&json).map_err(|_| invalid())?;
+        let expires = DateTime::<Utc>::from_timestamp_millis(id.expires).ok_or_else(invalid)?;
+        if expires <= now {
+            expired.push(field.clone());
+            continue;
+        }
+        let key = ((!group.is_empty()).then(|| group.to_string()), deduplication_id.to_string());
+        let accepted = Accepted {
+            message_id: id.message_id,
+            sequence_number: id.sequence_number,
+        };
+        ids.push((expires, key, accepted));
+    }
+    ids.sort_by_key(|(expires, _, _)| *expires);
+    for (expires, key, accepted) in ids {
+        queue.deduplication.restore(key, accepted, expires);
+    }
+    if !expired.is_empty() {
+        let _: () = con.hdel(&deduplication_key, expired).await?;
+    }
+    Ok(queue)
+}
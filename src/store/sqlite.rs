@@ -0,0 +1,516 @@
+//! A [`QueueStore`] backed by a SQLite database, so that queues survive a
+//! restart and can be inspected with the usual SQLite tools.
+//!
+//! [`SqliteStore`] is a [`Durable`] store: messages live in the database,
+//! not in memory, and every operation on a queue is one `BEGIN IMMEDIATE`
+//! transaction that reads the messages it needs through the indexes below,
+//! and writes back what it changed. A receive reads the visible messages by
+//! send order and the lapsed in-flight ones by visibility deadline; a delete
+//! or visibility change reads just the message its receipt handle resolves
+//! to. Transactions run on a blocking thread rather than the runtime's
+//! workers, one at a time. [`Durable::load`] brings back each queue's
+//! settings at startup; its messages stay in the database.
+//!
+//! The database holds a `queues` table with each queue's settings as JSON,
+//! its `tags`, its `messages` (as JSON [`MessageRecord`]s, indexed by
+//! visibility deadline, by sent time and by receipt handle), the
+//! `duplicate_handles` of messages delivered more than once and the FIFO
+//! `deduplication` IDs that haven't expired yet. Latency histograms, event
+//! logs and long-poll state are not stored.
+//!
+//! [`MessageRecord`]: crate::store::MessageRecord
+
+use crate::deduplication::Accepted;
+use crate::messages::MessageCounts;
+use crate::state::{AppState, Queue};
+use crate::store::durable::{Backend, Durable, Records};
+use crate::store::{BackendError, QueueSettings, QueueWrite, Selection, Write};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, ToSql};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+const SCHEMA: &str = "
+    PRAGMA journal_mode = WAL;
+    PRAGMA synchronous = NORMAL;
+    PRAGMA foreign_keys = ON;
+
+    CREATE TABLE IF NOT EXISTS queues (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE,
+        settings TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS tags (
+        queue_id INTEGER NOT NULL REFERENCES queues (id) ON DELETE CASCADE,
+        key TEXT NOT NULL,
+        value TEXT NOT NULL,
+        PRIMARY KEY (queue_id, key)
+    );
+
+    -- visible_from is the visibility deadline of in-flight messages, those
+    -- with a receipt_handle. Times are Unix milliseconds.
+    CREATE TABLE IF NOT EXISTS messages (
+        queue_id INTEGER NOT NULL REFERENCES queues (id) ON DELETE CASCADE,
+        seq INTEGER NOT NULL,
+        visible_from INTEGER NOT NULL,
+        sent_timestamp INTEGER NOT NULL,
+        receipt_handle TEXT,
+        message_group_id TEXT,
+        message TEXT NOT NULL,
+        PRIMARY KEY (queue_id, seq)
+    );
+    CREATE INDEX IF NOT EXISTS messages_by_visibility ON messages (queue_id, visible_from);
+    CREATE INDEX IF NOT EXISTS messages_by_sent_timestamp ON messages (queue_id, sent_timestamp);
+    CREATE INDEX IF NOT EXISTS messages_by_receipt_handle ON messages (receipt_handle);
+
+    -- Receipt handles of duplicate deliveries, which resolve to the message
+    -- as its own receipt_handle does.
+    CREATE TABLE IF NOT EXISTS duplicate_handles (
+        receipt_handle TEXT PRIMARY KEY,
+        queue_id INTEGER NOT NULL,
+        seq INTEGER NOT NULL,
+        FOREIGN KEY (queue_id, seq) REFERENCES messages (queue_id, seq) ON DELETE CASCADE
+    );
+    CREATE INDEX IF NOT EXISTS duplicate_handles_by_message ON duplicate_handles (queue_id, seq);
+
+    -- message_group_id is empty for IDs scoped to the whole queue.
+    CREATE TABLE IF NOT EXISTS deduplication (
+        queue_id INTEGER NOT NULL REFERENCES queues (id) ON DELETE CASCADE,
+        message_group_id TEXT NOT NULL,
+        deduplication_id TEXT NOT NULL,
+        message_id TEXT NOT NULL,
+        sequence_number TEXT NOT NULL,
+        expires INTEGER NOT NULL,
+        PRIMARY KEY (queue_id, message_group_id, deduplication_id)
+    );
+";
+
+/// Queues kept in a SQLite database.
+pub type SqliteStore = Durable<SqliteBackend>;
+
+impl SqliteStore {
+    /// Opens (or creates) the database at `path`. Call
+    /// [`load`](Durable::load) once the server's state exists to bring back
+    /// the queues stored there.
+    pub fn open(path: &Path) -> rusqlite::Result<Self> {
+        let db = Connection::open(path)?;
+        db.execute_batch(SCHEMA)?;
+        Ok(Durable::new(SqliteBackend {
+            db: Arc::new(Mutex::new(db)),
+        }))
+    }
+}
+
+/// The database behind a [`SqliteStore`].
+#[derive(Debug)]
+pub struct SqliteBackend {
+    db: Arc<Mutex<Connection>>,
+}
+
+/// A transaction on one stored queue, holding the database until it ends.
+pub struct SqliteTransaction {
+    db: Option<OwnedMutexGuard<Connection>>,
+}
+
+impl Drop for SqliteTransaction {
+    fn drop(&mut self) {
+        if let Some(db) = &self.db
+            && !db.is_autocommit()
+        {
+            let _ = db.execute_batch("ROLLBACK");
+        }
+    }
+}
+
+/// Runs `f` on `db` on a blocking thread, handing `db` back with the
+/// outcome.
+async fn blocking<R: Send + 'static>(
+    mut db: OwnedMutexGuard<Connection>,
+    f: impl FnOnce(&mut Connection) -> Result<R, BackendError> + Send + 'static,
+) -> Result<(OwnedMutexGuard<Connection>, Result<R, BackendError>), BackendError> {
+    let ran = tokio::task::spawn_blocking(move || {
+        let result = f(&mut db);
+        (db, result)
+    });
+    Ok(ran.await?)
+}
+
+impl SqliteBackend {
+    /// Runs `f` on the database on a blocking thread.
+    async fn with_db<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> Result<R, BackendError> + Send + 'static,
+    ) -> Result<R, BackendError> {
+        let db = self.db.clone().lock_owned().await;
+        blocking(db, f).await?.1
+    }
+}
+
+impl Backend for SqliteBackend {
+    type Transaction = SqliteTransaction;
+
+    async fn select(
+        &self,
+        name: &str,
+        selection: &Selection,
+        now: DateTime<Utc>,
+    ) -> Result<(SqliteTransaction, Records), BackendError> {
+        let db = self.db.clone().lock_owned().await;
+        let (name, selection) = (name.to_string(), selection.clone());
+        let (db, selected) = blocking(db, move |db| {
+            // A transaction left open by a request that went away.
+            if !db.is_autocommit() {
+                db.execute_batch("ROLLBACK")?;
+            }
+            db.execute_batch("BEGIN IMMEDIATE")?;
+            select_messages(db, &name, &selection, now.timestamp_millis())
+        })
+        .await?;
+        let transaction = SqliteTransaction { db: Some(db) };
+        Ok((transaction, selected?))
+    }
+
+    async fn commit(
+        &self,
+        mut transaction: SqliteTransaction,
+        write: QueueWrite,
+    ) -> Result<(), BackendError> {
+        let db = transaction.db.take().expect("transactions are committed once");
+        let (_db, committed) = blocking(db, move |db| {
+            let written = write_queue(db, write).and_then(|()| db.execute_batch("COMMIT"));
+            if written.is_err() && !db.is_autocommit() {
+                db.execute_batch("ROLLBACK")?;
+            }
+            Ok(written?)
+        })
+        .await?;
+        committed
+    }
+
+    async fn rollback(&self, mut transaction: SqliteTransaction) {
+        if let Some(db) = transaction.db.take() {
+            let _ = blocking(db, |db| Ok(db.execute_batch("ROLLBACK")?)).await;
+        }
+    }
+
+    async fn write(&self, write: Write) -> Result<(), BackendError> {
+        self.with_db(move |db| {
+            let tx = db.transaction()?;
+            match write {
+                Write::Queue(queue) => {
+                    if queue.whole {
+                        tx.execute("DELETE FROM queues WHERE name = ?1", [&queue.name])?;
+                    }
+                    write_queue(&tx, *queue)?;
+                }
+                Write::Delete(name) => {
+                    tx.execute("DELETE FROM queues WHERE name = ?1", [name])?;
+                }
+                Write::ReplaceAll(queues) => {
+                    tx.execute_batch("DELETE FROM queues")?;
+                    for queue in queues {
+                        write_queue(&tx, queue)?;
+                    }
+                }
+            }
+            Ok(tx.commit()?)
+        })
+        .await
+    }
+
+    async fn settings(&self, name: &str) -> Result<QueueSettings, BackendError> {
+        let name = name.to_string();
+        self.with_db(move |db| {
+            let settings: String = db
+                .prepare_cached("SELECT settings FROM queues WHERE name = ?1")?
+                .query_row([&name], |row| row.get(0))
+                .optional()?
+                .ok_or_else(|| format!("queue {} is not stored", name))?;
+            Ok(serde_json::from_str(&settings)
+                .map_err(|e| format!("invalid settings for queue {}: {}", name, e))?)
+        })
+        .await
+    }
+
+    async fn message_counts(
+        &self,
+        name: &str,
+        now: DateTime<Utc>,
+    ) -> Result<MessageCounts, BackendError> {
+        let name = name.to_string();
+        self.with_db(move |db| {
+            let (visible, delayed, in_flight): (i64, i64, i64) = db
+                .prepare_cached(
+                    "SELECT
+                         coalesce(sum(visible_from <= ?2), 0),
+                         coalesce(sum(visible_from > ?2 AND receipt_handle IS NULL), 0),
+                         coalesce(sum(visible_from > ?2 AND receipt_handle IS NOT NULL), 0)
+                     FROM messages
+                     WHERE queue_id = (SELECT id FROM queues WHERE name = ?1)",
+                )?
+                .query_row(params![name, now.timestamp_millis()], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                })?;
+            Ok(MessageCounts {
+                visible: visible as usize,
+                delayed: delayed as usize,
+                in_flight: in_flight as usize,
+            })
+        })
+        .await
+    }
+
+    async fn oldest_visible(
+        &self,
+        name: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<DateTime<Utc>>, BackendError> {
+        let name = name.to_string();
+        self.with_db(move |db| {
+            let sent: Option<i64> = db
+                .prepare_cached(
+                    "SELECT sent_timestamp FROM messages
+                     WHERE queue_id = (SELECT id FROM queues WHERE name = ?1)
+                       AND receipt_handle IS NULL AND visible_from <= ?2
+                     ORDER BY seq LIMIT 1",
+                )?
+                .query_row(params![name, now.timestamp_millis()], |row| row.get(0))
+                .optional()?;
+            Ok(sent.and_then(DateTime::from_timestamp_millis))
+        })
+        .await
+    }
+
+    /// Deduplication IDs that expired while the server was stopped are
+    /// dropped.
+    async fn load(&self, state: &AppState) -> Result<Vec<Queue>, BackendError> {
+        let state = state.clone();
+        self.with_db(move |db| {
+            let now = state.clock.now();
+            db.execute(
+                "DELETE FROM deduplication WHERE expires <= ?1",
+                [now.timestamp_millis()],
+            )?;
+
+            let rows = db
+                .prepare("SELECT id, name, settings FROM queues")?
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                .collect::<rusqlite::Result<Vec<(i64, String, String)>>>()?;
+            let mut queues = Vec::new();
+            for (id, name, settings) in rows {
+                let settings: QueueSettings = serde_json::from_str(&settings)
+                    .map_err(|e| format!("invalid settings for queue {}: {}", name, e))?;
+                queues.push(read_queue(db, &state, id, name, settings)?);
+            }
+            Ok(queues)
+        })
+        .await
+    }
+}
+
+/// The messages of the stored queue `name` that `selection` picks as of
+/// `now` (Unix milliseconds), by sequence number.
+fn select_messages(
+    db: &Connection,
+    name: &str,
+    selection: &Selection,
+    now: i64,
+) -> Result<Records, BackendError> {
+    let queue_id: i64 = db
+        .prepare_cached("SELECT id FROM queues WHERE name = ?1")?
+        .query_row([name], |row| row.get(0))
+        .optional()?
+        .ok_or_else(|| format!("queue {} is not stored", name))?;
+
+    let mut messages = BTreeMap::new();
+    let mut read = |sql: &str, params: &[&dyn ToSql]| -> rusqlite::Result<()> {
+        let mut statement = db.prepare_cached(sql)?;
+        let mut rows = statement.query(params)?;
+        while let Some(row) = rows.next()? {
+            messages.insert(row.get::<_, i64>(0)? as u64, row.get(1)?);
+        }
+        Ok(())
+    };
+
+    if selection.all {
+        read("SELECT seq, message FROM messages WHERE queue_id = ?1", params![queue_id])?;
+        return Ok(messages.into_iter().collect());
+    }
+    if selection.visible > 0 {
+        // A negative limit is none.
+        let limit = i64::try_from(selection.visible).unwrap_or(-1);
+        if selection.unlocked_groups {
+            read(
+                "SELECT seq, message FROM messages
+                 WHERE queue_id = ?1 AND receipt_handle IS NULL AND visible_from <= ?2
+                   AND (message_group_id IS NULL OR message_group_id NOT IN (
+                       SELECT message_group_id FROM messages
+                       WHERE queue_id = ?1 AND visible_from > ?2
+                         AND receipt_handle IS NOT NULL AND message_group_id IS NOT NULL))
+                 ORDER BY seq LIMIT ?3",
+                params![queue_id, now, limit],
+            )?;
+        } else {
+            read(
+                "SELECT seq, message FROM messages
+                 WHERE queue_id = ?1 AND receipt_handle IS NULL AND visible_from <= ?2
+                 ORDER BY seq LIMIT ?3",
+                params![queue_id, now, limit],
+            )?;
+        }
+    }
+    if selection.in_flight {
+        read(
+            "SELECT seq, message FROM messages
+             WHERE queue_id = ?1 AND receipt_handle IS NOT NULL",
+            params![queue_id],
+        )?;
+    } else if selection.lapsed {
+        read(
+            "SELECT seq, message FROM messages
+             WHERE queue_id = ?1 AND visible_from <= ?2 AND receipt_handle IS NOT NULL",
+            params![queue_id, now],
+        )?;
+    }
+    if let Some(sent_by) = selection.sent_by {
+        read(
+            "SELECT seq, message FROM messages WHERE queue_id = ?1 AND sent_timestamp <= ?2",
+            params![queue_id, sent_by.timestamp_millis()],
+        )?;
+    }
+    if let Some(receipt_handle) = &selection.receipt_handle {
+        read(
+            "SELECT seq, message FROM messages
+             WHERE queue_id = ?1 AND receipt_handle = ?2
+             UNION
+             SELECT messages.seq, messages.message
+             FROM duplicate_handles JOIN messages USING (queue_id, seq)
+             WHERE duplicate_handles.queue_id = ?1 AND duplicate_handles.receipt_handle = ?2",
+            params![queue_id, receipt_handle],
+        )?;
+    }
+    Ok(messages.into_iter().collect())
+}
+
+/// Writes `queue`'s settings, tags, changed messages and deduplication IDs.
+fn write_queue(db: &Connection, queue: QueueWrite) -> rusqlite::Result<()> {
+    let settings = serde_json::to_string(&queue.settings).expect("queue settings serialize");
+    let queue_id: i64 = db
+        .prepare_cached(
+            "INSERT INTO queues (name, settings) VALUES (?1, ?2)
+             ON CONFLICT (name) DO UPDATE SET settings = excluded.settings
+             RETURNING id",
+        )?
+        .query_row(params![queue.name, settings], |row| row.get(0))?;
+
+    db.prepare_cached("DELETE FROM tags WHERE queue_id = ?1")?
+        .execute([queue_id])?;
+    let mut insert_tag =
+        db.prepare_cached("INSERT INTO tags (queue_id, key, value) VALUES (?1, ?2, ?3)")?;
+    for (key, value) in &queue.tags {
+        insert_tag.execute(params![queue_id, key, value])?;
+    }
+
+    if queue.cleared {
+        db.prepare_cached("DELETE FROM messages WHERE queue_id = ?1")?
+            .execute([queue_id])?;
+    }
+    for (seq, message) in queue.messages {
+        match message {
+            Some(message) => {
+                db.prepare_cached(
+                    "INSERT INTO messages
+                     (queue_id, seq, visible_from, sent_timestamp, receipt_handle,
+                      message_group_id, message)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                     ON CONFLICT (queue_id, seq) DO UPDATE SET
+                         visible_from = excluded.visible_from,
+                         receipt_handle = excluded.receipt_handle,
+                         message = excluded.message",
+                )?
+                .execute(params![
+                    queue_id,
+                    seq as i64,
+                    message.visible_from,
+                    message.sent_timestamp,
+                    message.receipt_handle,
+                    message.message_group_id,
+                    message.json,
+                ])?;
+                db.prepare_cached("DELETE FROM duplicate_handles WHERE queue_id = ?1 AND seq = ?2")?
+                    .execute(params![queue_id, seq as i64])?;
+                for receipt_handle in &message.duplicate_handles {
+                    db.prepare_cached(
+                        "INSERT INTO duplicate_handles (receipt_handle, queue_id, seq)
+                         VALUES (?1, ?2, ?3)",
+                    )?
+                    .execute(params![receipt_handle, queue_id, seq as i64])?;
+                }
+            }
+            None => {
+                db.prepare_cached("DELETE FROM messages WHERE queue_id = ?1 AND seq = ?2")?
+                    .execute(params![queue_id, seq as i64])?;
+            }
+        }
+    }
+
+    for (group, deduplication_id) in queue.deduplication.expired {
+        db.prepare_cached(
+            "DELETE FROM deduplication
+             WHERE queue_id = ?1 AND message_group_id = ?2 AND deduplication_id = ?3",
+        )?
+        .execute(params![queue_id, group.unwrap_or_default(), deduplication_id])?;
+    }
+    for ((group, deduplication_id), accepted, expires) in queue.deduplication.inserted {
+        db.prepare_cached(
+            "INSERT OR REPLACE INTO deduplication
+             (queue_id, message_group_id, deduplication_id, message_id, sequence_number, expires)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?
+        .execute(params![
+            queue_id,
+            group.unwrap_or_default(),
+            deduplication_id,
+            accepted.message_id,
+            accepted.sequence_number,
+            expires.timestamp_millis(),
+        ])?;
+    }
+    Ok(())
+}
+
+/// Rebuilds the stored queue `id`, with none of its messages loaded.
+fn read_queue(
+    db: &Connection,
+    state: &AppState,
+    id: i64,
+    name: String,
+    settings: QueueSettings,
+) -> Result<Queue, BackendError> {
+    let tags = db
+        .prepare("SELECT key, value FROM tags WHERE queue_id = ?1")?
+        .query_map([id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    let mut queue = settings.into_unloaded_queue(state, &name, tags)?;
+
+    let mut deduplication = db.prepare(
+        "SELECT message_group_id, deduplication_id, message_id, sequence_number, expires
+         FROM deduplication WHERE queue_id = ?1 ORDER BY expires",
+    )?;
+    let mut rows = deduplication.query([id])?;
+    while let Some(row) = rows.next()? {
+        let group: String = row.get(0)?;
+        let key = ((!group.is_empty()).then_some(group), row.get(1)?);
+        let accepted = Accepted {
+            message_id: row.get(2)?,
+            sequence_number: row.get(3)?,
+        };
+        let expires = DateTime::<Utc>::from_timestamp_millis(row.get(4)?)
+            .ok_or_else(|| format!("invalid expiry on queue {}", name))?;
+        queue.deduplication.restore(key, accepted, expires);
+    }
+    Ok(queue)
+}
@@ -0,0 +1,232 @@
+use crate::error::SqsError;
+use axum::http::HeaderMap;
+use serde::Deserialize;
+use std::time::Duration;
+use tracing::Span;
+
+/// The queue a request is about, for the span's `queue` field.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct QueueRef {
+    queue_url: Option<String>,
+    queue_name: Option<String>,
+}
+
+/// The span a request for `action` runs in. Handlers fill in `message_ids`
+/// and `message_count`, and receives `waited_ms`, the time spent long
+/// polling; dispatch fills in the `error_code` of a failed request. With
+/// `RUST_LOG=local_sqs=debug` a message can be followed from its send to its
+/// delete by its id.
+///
+/// When spans are exported (the `otel` feature plus `--otlp-endpoint`), the
+/// span continues the caller's trace from a `traceparent` or
+/// `X-Amzn-Trace-Id` header, and a receive's span links to the spans that
+/// sent the messages it returns.
+pub fn request_span(action: &str, request_id: &str, headers: &HeaderMap, body: &str) -> Span {
+    let queue = serde_json::from_str::<QueueRef>(body)
+        .ok()
+        .and_then(|r| r.queue_name.or(r.queue_url))
+        .unwrap_or_default();
+    let span = tracing::info_span!(
+        "sqs.request",
+        otel.kind = "server",
+        action,
+        request_id,
+        queue,
+        message_ids = tracing::field::Empty,
+        message_count = tracing::field::Empty,
+        error_code = tracing::field::Empty,
+        waited_ms = tracing::field::Empty,
+    );
+    #[cfg(feature = "otel")]
+    otel::set_parent(&span, headers);
+    #[cfg(not(feature = "otel"))]
+    let _ = headers;
+    span
+}
+
+/// Records the ids of the messages a request sent or returned on its span.
+pub fn record_message_ids<'a>(ids: impl IntoIterator<Item = &'a str>) {
+    let ids: Vec<&str> = ids.into_iter().collect();
+    let span = Span::current();
+    span.record("message_ids", ids.join(",").as_str());
+    span.record("message_count", ids.len());
+}
+
+/// Records how long a receive waited for messages on its span.
+pub fn record_wait(waited: Duration) {
+    Span::current().record("waited_ms", waited.as_millis() as u64);
+}
+
+/// Records the code of the error a request failed with on its span.
+pub fn record_error(error: &SqsError) {
+    Span::current().record("error_code", error.code());
+}
+
+/// The W3C `traceparent` of the current span, stored on sent messages so
+/// that their receive can link back to it. `None` unless spans are being
+/// exported.
+pub fn current_trace_context() -> Option<String> {
+    #[cfg(feature = "otel")]
+    return otel::current_traceparent();
+    #[cfg(not(feature = "otel"))]
+    None
+}
+
+/// Links the current span to the span with W3C `traceparent`.
+pub fn link_to(traceparent: &str) {
+    #[cfg(feature = "otel")]
+    otel::link_to(traceparent);
+    #[cfg(not(feature = "otel"))]
+    let _ = traceparent;
+}
+
+#[cfg(feature = "otel")]
+pub use otel::{init, traceparent_from_xray, OtlpGuard};
+
+#[cfg(feature = "otel")]
+mod otel {
+    use axum::http::HeaderMap;
+    use opentelemetry::propagation::{Extractor, TextMapPropagator};
+    use opentelemetry::trace::{
+        SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState, TracerProvider,
+    };
+    use opentelemetry::Context;
+    use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use opentelemetry_sdk::Resource;
+    use tracing::Span;
+    use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+    use tracing_subscriber::registry::LookupSpan;
+
+    /// Flushes buffered spans when dropped.
+    pub struct OtlpGuard(SdkTracerProvider);
+
+    impl Drop for OtlpGuard {
+        fn drop(&mut self) {
+            if let Err(e) = self.0.shutdown() {
+                eprintln!("failed to flush spans: {}", e);
+            }
+        }
+    }
+
+    /// Builds a layer exporting spans over OTLP/HTTP to `endpoint`, e.g.
+    /// `http://localhost:4318`. Keep the guard alive until exit.
+    pub fn init<S>(
+        endpoint: &str,
+    ) -> Result<(OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>, OtlpGuard), String>
+    where
+        S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let endpoint = endpoint.trim_end_matches('/');
+        // A bare collector address gets the standard traces path.
+        let endpoint = match endpoint.split_once("://") {
+            Some((_, rest)) if !rest.contains('/') => format!("{}/v1/traces", endpoint),
+            _ => endpoint.to_string(),
+        };
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(Resource::builder().with_service_name("local-sqs-rs").build())
+            .build();
+        let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("local-sqs-rs"));
+        Ok((layer, OtlpGuard(provider)))
+    }
+
+    struct Headers<'a>(&'a HeaderMap);
+
+    impl Extractor for Headers<'_> {
+        fn get(&self, key: &str) -> Option<&str> {
+            self.0.get(key).and_then(|v| v.to_str().ok())
+        }
+
+        fn keys(&self) -> Vec<&str> {
+            self.0.keys().map(|k| k.as_str()).collect()
+        }
+    }
+
+    /// Continues the caller's trace: W3C `traceparent` first, then X-Ray.
+    pub fn set_parent(span: &Span, headers: &HeaderMap) {
+        let propagator = TraceContextPropagator::new();
+        let mut cx = propagator.extract(&Headers(headers));
+        if !cx.span().span_context().is_valid()
+            && let Some(parent) = headers
+                .get("X-Amzn-Trace-Id")
+                .and_then(|v| v.to_str().ok())
+                .and_then(traceparent_from_xray)
+                .and_then(|traceparent| parse_traceparent(&traceparent))
+        {
+            cx = Context::new().with_remote_span_context(parent);
+        }
+        if cx.span().span_context().is_valid() {
+            let _ = span.set_parent(cx);
+        }
+    }
+
+    pub fn current_traceparent() -> Option<String> {
+        let cx = Span::current().context();
+        let span = cx.span();
+        let span_context = span.span_context();
+        span_context.is_valid().then(|| {
+            format!(
+                "00-{}-{}-{:02x}",
+                span_context.trace_id(),
+                span_context.span_id(),
+                span_context.trace_flags().to_u8()
+            )
+        })
+    }
+
+    pub fn link_to(traceparent: &str) {
+        if let Some(span_context) = parse_traceparent(traceparent) {
+            Span::current().add_link(span_context);
+        }
+    }
+
+    /// Converts an X-Ray trace header
+    /// (`Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1`)
+    /// to a W3C `traceparent`. `None` without both a root and a parent.
+    pub fn traceparent_from_xray(header: &str) -> Option<String> {
+        let mut root = None;
+        let mut parent = None;
+        let mut sampled = false;
+        for part in header.split(';') {
+            match part.trim().split_once('=') {
+                Some(("Root", value)) => root = Some(value),
+                Some(("Parent", value)) => parent = Some(value),
+                Some(("Sampled", value)) => sampled = value == "1",
+                _ => {}
+            }
+        }
+        let (version, rest) = root?.split_once('-')?;
+        if version != "1" {
+            return None;
+        }
+        let trace_id = rest.replace('-', "");
+        let traceparent = format!("00-{}-{}-{:02x}", trace_id, parent?, sampled as u8);
+        parse_traceparent(&traceparent).map(|_| traceparent)
+    }
+
+    fn parse_traceparent(traceparent: &str) -> Option<SpanContext> {
+        let parts: Vec<&str> = traceparent.split('-').collect();
+        let [_, trace_id, span_id, flags] = parts[..] else {
+            return None;
+        };
+        if trace_id.len() != 32 || span_id.len() != 16 {
+            return None;
+        }
+        let span_context = SpanContext::new(
+            TraceId::from_hex(trace_id).ok()?,
+            SpanId::from_hex(span_id).ok()?,
+            TraceFlags::new(u8::from_str_radix(flags, 16).ok()?),
+            true,
+            TraceState::default(),
+        );
+        span_context.is_valid().then_some(span_context)
+    }
+}
@@ -0,0 +1,251 @@
+//! Queue templates: named bundles of attributes, tags and dead-letter
+//! settings declared in the config file, which queues start from by naming
+//! one, either in the config file or in the `X-Local-Sqs-Template` header of
+//! a `CreateQueue` request.
+
+use crate::attributes;
+use crate::error::{Fault, SqsError};
+use crate::fixtures::QueueFixture;
+use crate::queue::{self, CreateQueueRequest, CreateQueueResponse};
+use crate::state::{AppState, RedrivePolicy};
+use axum::extract::State;
+use axum::Json;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// The header naming the template a `CreateQueue` request starts from.
+pub const TEMPLATE_HEADER: &str = "X-Local-Sqs-Template";
+
+/// The `maxReceiveCount` of the redrive policy `dlq_suffix` sets up when the
+/// template doesn't give one, as in the AWS console.
+const DEFAULT_MAX_RECEIVE_COUNT: u32 = 10;
+
+/// Templates by name.
+pub type Templates = HashMap<String, QueueTemplate>;
+
+/// ```yaml
+/// templates:
+///   standard-worker:
+///     attributes:
+///       VisibilityTimeout: "60"
+///     tags:
+///       team: payments
+///     dlq_suffix: -dlq
+///     max_receive_count: 5
+/// queues:
+///   - name: invoices
+///     template: standard-worker
+///     attributes:
+///       VisibilityTimeout: "120"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QueueTemplate {
+    #[serde(default)]
+    pub attributes: HashMap<String, String>,
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+    /// Gives each queue a dead-letter queue named after it with this suffix
+    /// (ahead of any `.fifo`), created if missing and set as the queue's
+    /// redrive target unless the queue sets a `RedrivePolicy` of its own.
+    #[serde(default)]
+    pub dlq_suffix: Option<String>,
+    /// The `maxReceiveCount` of that redrive policy.
+    #[serde(default)]
+    pub max_receive_count: Option<u32>,
+}
+
+/// A dead-letter queue a template gives a queue.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeadLetterQueue {
+    pub name: String,
+    /// Just `FifoQueue`, for the dead-letter queues of FIFO queues.
+    pub attributes: HashMap<String, String>,
+    /// The template's tags.
+    pub tags: HashMap<String, String>,
+}
+
+impl QueueTemplate {
+    /// Fills in the `attributes` and `tags` declared for queue `queue` from
+    /// the template, the declared values winning. Returns the dead-letter
+    /// queue the queue now redrives to, if the template gives it one; it has
+    /// to be created first.
+    pub fn apply(
+        &self,
+        state: &AppState,
+        queue: &str,
+        attributes: &mut HashMap<String, String>,
+        tags: &mut HashMap<String, String>,
+    ) -> Option<DeadLetterQueue> {
+        for (name, value) in &self.attributes {
+            attributes.entry(name.clone()).or_insert_with(|| value.clone());
+        }
+        for (key, value) in &self.tags {
+            tags.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+
+        let suffix = self.dlq_suffix.as_deref()?;
+        let name = dead_letter_queue_name(queue, suffix);
+        if !attributes.contains_key("RedrivePolicy") {
+            let policy = RedrivePolicy {
+                dead_letter_target_arn: state.queue_arn(&name),
+                max_receive_count: self.max_receive_count.unwrap_or(DEFAULT_MAX_RECEIVE_COUNT),
+            };
+            attributes.insert("RedrivePolicy".to_string(), policy.to_string());
+        }
+        let mut dlq_attributes = HashMap::new();
+        if let Some(fifo) = attributes.get("FifoQueue").filter(|v| *v == "true") {
+            dlq_attributes.insert("FifoQueue".to_string(), fifo.clone());
+        }
+        Some(DeadLetterQueue {
+            name,
+            attributes: dlq_attributes,
+            tags: self.tags.clone(),
+        })
+    }
+}
+
+/// `queue` with `suffix` added, ahead of the `.fifo` of FIFO queue names.
+pub fn dead_letter_queue_name(queue: &str, suffix: &str) -> String {
+    match queue.strip_suffix(".fifo") {
+        Some(base) => format!("{}{}.fifo", base, suffix),
+        None => format!("{}{}", queue, suffix),
+    }
+}
+
+/// A queue to create, with any template it named applied.
+#[derive(Debug, Clone)]
+pub struct ExpandedQueue {
+    pub queue: QueueFixture,
+    /// The template the queue, or the queue it is the dead-letter queue of,
+    /// named.
+    pub template: Option<String>,
+}
+
+/// Applies the templates named by `queues`. Each dead-letter queue a
+/// template gives comes just ahead of its queue, unless `queues` declares
+/// it itself, in which case the declaration is used as it stands.
+pub fn expand(
+    state: &AppState,
+    templates: &Templates,
+    queues: Vec<QueueFixture>,
+) -> Result<Vec<ExpandedQueue>, SqsError> {
+    let declared: Vec<String> = queues.iter().map(|queue| queue.name.clone()).collect();
+    let mut expanded = Vec::with_capacity(queues.len());
+    for mut queue in queues {
+        let Some(name) = queue.template.take() else {
+            expanded.push(ExpandedQueue {
+                queue,
+                template: None,
+            });
+            continue;
+        };
+        let template = lookup(templates, &queue.name, &name)?;
+        let dlq = template.apply(state, &queue.name, &mut queue.attributes, &mut queue.tags);
+        if let Some(dlq) = dlq.filter(|dlq| !declared.contains(&dlq.name))
+            && !expanded.iter().any(|e: &ExpandedQueue| e.queue.name == dlq.name)
+        {
+            expanded.push(ExpandedQueue {
+                queue: QueueFixture {
+                    name: dlq.name,
+                    attributes: dlq.attributes,
+                    tags: dlq.tags,
+                    template: None,
+                    webhook: None,
+                    messages: Vec::new(),
+                },
+                template: Some(name.clone()),
+            });
+        }
+        expanded.push(ExpandedQueue {
+            queue,
+            template: Some(name),
+        });
+    }
+    Ok(expanded)
+}
+
+fn lookup<'a>(
+    templates: &'a Templates,
+    queue: &str,
+    template: &str,
+) -> Result<&'a QueueTemplate, SqsError> {
+    templates.get(template).ok_or_else(|| {
+        SqsError::InvalidParameterValue(format!(
+            "Queue {} names template {}, which does not exist.",
+            queue, template
+        ))
+    })
+}
+
+/// Checks every template's attributes against the attribute table, so that
+/// a mistake in a template no queue uses yet is caught too.
+pub fn validate(state: &AppState, templates: &Templates) -> Result<(), SqsError> {
+    let mut names: Vec<&String> = templates.keys().collect();
+    names.sort();
+    for name in names {
+        let template = &templates[name];
+        for (attribute, value) in &template.attributes {
+            let valid = attributes::validate(attribute, value, state.max_message_size_limit);
+            state.validation.check(valid).map_err(|e| match e {
+                SqsError::InvalidAttributeValue(message) => {
+                    SqsError::InvalidAttributeValue(format!("Template {}: {}", name, message))
+                }
+                e => SqsError::InvalidParameterValue(format!("Template {}: {}", name, e.message())),
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// `e`, raised creating or updating queue `queue` from template `template`,
+/// with a message naming both. Errors that aren't the request's fault are
+/// left as they are.
+pub fn in_template(e: SqsError, queue: &str, template: &str) -> SqsError {
+    let context = |message: String| format!("Queue {} (template {}): {}", queue, template, message);
+    match e {
+        SqsError::InvalidAttributeValue(message) => {
+            SqsError::InvalidAttributeValue(context(message))
+        }
+        e if e.fault() == Fault::Sender && !matches!(e, SqsError::QueueNameExists) => {
+            SqsError::InvalidParameterValue(context(e.message()))
+        }
+        e => e,
+    }
+}
+
+/// `CreateQueue`, starting from the template named in [`TEMPLATE_HEADER`]
+/// if the request has one. A dead-letter queue the template gives is created
+/// first unless it already exists.
+pub async fn create_queue(
+    State(state): State<AppState>,
+    Json(mut request): Json<CreateQueueRequest>,
+) -> Result<CreateQueueResponse, SqsError> {
+    let Some(name) = request.template.take() else {
+        return queue::create_queue(State(state), Json(request)).await;
+    };
+    let template = {
+        let templates = state.templates.lock().unwrap();
+        lookup(&templates, &request.queue_name, &name)?.clone()
+    };
+    let queue_name = request.queue_name.clone();
+    let dlq = template.apply(&state, &queue_name, &mut request.attributes, &mut request.tags);
+    if let Some(dlq) = dlq
+        && state.store.read(&state.queue_url(&dlq.name), |_| ()).is_err()
+    {
+        queue::create_queue(
+            State(state.clone()),
+            Json(CreateQueueRequest {
+                queue_name: dlq.name,
+                attributes: dlq.attributes,
+                tags: dlq.tags,
+                template: None,
+            }),
+        )
+        .await
+        .map_err(|e| in_template(e, &queue_name, &name))?;
+    }
+    queue::create_queue(State(state), Json(request))
+        .await
+        .map_err(|e| in_template(e, &queue_name, &name))
+}
@@ -0,0 +1,120 @@
+//! Queue URLs in canonical form. Queues are stored under, and looked up by,
+//! their normalized URL, so that spellings a client might reasonably send
+//! (`LOCALHOST`, an explicit `:80`, a trailing slash, `%2D` for `-`) all
+//! name the same queue. So do URLs naming the server by another host, such
+//! as `127.0.0.1` or a container's service name for the default `localhost`.
+
+use crate::error::SqsError;
+use percent_encoding::percent_decode_str;
+use std::future::Future;
+use url::Url;
+
+tokio::task_local! {
+    static SERVER: Url;
+}
+
+/// Runs `future` for the server whose queue URLs start with `server`, so
+/// that [`on_this_server`] knows which host its queues are stored under.
+pub async fn scope<F: Future>(server: &str, future: F) -> F::Output {
+    match Url::parse(server) {
+        Ok(server) => SERVER.scope(server, future).await,
+        Err(_) => future.await,
+    }
+}
+
+/// The normalized queue URL `url` with the host of the server handling the
+/// request, if it names the same scheme and port by another host.
+pub fn on_this_server(url: String) -> String {
+    let Ok(Some(base)) = SERVER.try_with(|server| {
+        let parsed = Url::parse(&url).ok()?;
+        let other_host = parsed.host_str() != server.host_str();
+        (other_host && same_endpoint(&parsed, server)).then(|| server.origin())
+    }) else {
+        return url;
+    };
+    // Normalized URLs are `scheme://authority/path`.
+    let path = url.split_once("://").and_then(|(_, rest)| rest.find('/').map(|i| &rest[i..]));
+    format!("{}{}", base.ascii_serialization(), path.unwrap_or_default())
+}
+
+/// The normalized URL the queue URLs of the server handling the request
+/// start with: `{origin}{base_path}/{account}`. `None` outside a request.
+pub fn queues_url() -> Option<String> {
+    SERVER.try_with(|server| normalize_queue_url(server.as_str())).ok()
+}
+
+/// Whether `url` and `server` have the same scheme and port, whatever host
+/// each names.
+fn same_endpoint(url: &Url, server: &Url) -> bool {
+    url.scheme() == server.scheme() && url.port_or_known_default() == server.port_or_known_default()
+}
+
+/// `host:port` as it appears in a URL, with an IPv6 address such as `::1`
+/// in brackets.
+pub fn authority(host: &str, port: u16) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+/// `url` with its host lowercased, the scheme's default port dropped, its
+/// path percent-decoded and stripped of trailing slashes, and any query or
+/// fragment removed. Strings that do not parse as URLs are returned as is;
+/// they name no queue either way.
+pub fn normalize_queue_url(url: &str) -> String {
+    let Ok(parsed) = Url::parse(url.trim()) else {
+        return url.to_string();
+    };
+    let Some(host) = parsed.host_str() else {
+        return url.to_string();
+    };
+    // Parsing has already lowercased the host and dropped a default port.
+    let port = parsed.port().map(|port| format!(":{}", port)).unwrap_or_default();
+    let path = percent_decode_str(parsed.path()).decode_utf8_lossy();
+    format!(
+        "{}://{}{}{}",
+        parsed.scheme(),
+        host,
+        port,
+        path.trim_end_matches('/')
+    )
+}
+
+/// Checks that the `QueueUrl` parameter `url` could name a queue on the
+/// server whose queue URLs start with `server`, so that a queue name, an
+/// empty string or another endpoint's URL gets an error saying what is
+/// wrong rather than `QueueDoesNotExist`. Only the scheme and port must
+/// match: the same server is reached by many host names.
+pub fn validate_queue_url(url: &str, server: &str) -> Result<(), SqsError> {
+    let invalid = |reason: String| {
+        SqsError::InvalidParameterValue(format!(
+            "Value {} for parameter QueueUrl is invalid. Reason: {}",
+            url, reason
+        ))
+    };
+    if url.trim().is_empty() {
+        return Err(invalid("must not be empty.".to_string()));
+    }
+    let parsed = match Url::parse(url.trim()) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") && parsed.has_host() => parsed,
+        _ if !url.contains(['/', ':']) => {
+            return Err(invalid(
+                "this is a queue name, not a queue URL; use GetQueueUrl to look up the URL."
+                    .to_string(),
+            ));
+        }
+        _ => return Err(invalid("must be an absolute http or https URL.".to_string())),
+    };
+    let Ok(server) = Url::parse(server) else {
+        return Ok(());
+    };
+    if !same_endpoint(&parsed, &server) {
+        return Err(invalid(format!(
+            "the URL does not belong to this endpoint; its queue URLs start with {}.",
+            server.origin().ascii_serialization()
+        )));
+    }
+    Ok(())
+}
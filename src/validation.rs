@@ -0,0 +1,86 @@
+use crate::error::SqsError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::warn;
+
+/// How strictly requests are checked against the rules SQS enforces on
+/// names, sizes, ranges, character sets and reserved prefixes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValidationMode {
+    /// Violations fail the request with the error SQS would return.
+    #[default]
+    Strict,
+    /// Violations are logged as warnings and the request goes ahead.
+    Lenient,
+}
+
+impl FromStr for ValidationMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(ValidationMode::Strict),
+            "lenient" => Ok(ValidationMode::Lenient),
+            _ => Err(format!("unknown validation mode {:?}, expected strict or lenient", s)),
+        }
+    }
+}
+
+impl fmt::Display for ValidationMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ValidationMode::Strict => "strict",
+            ValidationMode::Lenient => "lenient",
+        })
+    }
+}
+
+/// The server's current [`ValidationMode`], shared between clones so that
+/// the admin API can switch it while the server runs.
+#[derive(Debug, Clone, Default)]
+pub struct Validation {
+    lenient: Arc<AtomicBool>,
+}
+
+impl Validation {
+    pub fn new(mode: ValidationMode) -> Self {
+        let validation = Self::default();
+        validation.set_mode(mode);
+        validation
+    }
+
+    pub fn mode(&self) -> ValidationMode {
+        if self.lenient.load(Ordering::Relaxed) {
+            ValidationMode::Lenient
+        } else {
+            ValidationMode::Strict
+        }
+    }
+
+    pub fn set_mode(&self, mode: ValidationMode) {
+        self.lenient
+            .store(mode == ValidationMode::Lenient, Ordering::Relaxed);
+    }
+
+    /// Applies the mode to the outcome of a validation: in strict mode the
+    /// error is returned, in lenient mode it is logged and dropped.
+    pub fn check<T>(&self, result: Result<T, SqsError>) -> Result<(), SqsError> {
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) if self.mode() == ValidationMode::Lenient => {
+                warn!(error = %e, "ignoring invalid request in lenient validation mode");
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Like [`check`](Self::check), for a violation the caller found itself.
+    pub fn violation(&self, error: SqsError) -> Result<(), SqsError> {
+        self.check::<()>(Err(error))
+    }
+}
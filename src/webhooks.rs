@@ -0,0 +1,190 @@
+use crate::error::SqsError;
+use crate::state::{AppState, Message, MessageAttributeValue, Queue};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+/// Deliveries waiting for the worker. Once full, new deliveries are dropped
+/// rather than slowing down sends.
+pub const QUEUE_CAPACITY: usize = 1024;
+
+/// Attempts per delivery before it is given up on.
+pub const MAX_ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry; doubled for each retry after that.
+pub const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Consecutive failed deliveries after which a webhook's circuit opens.
+pub const FAILURE_THRESHOLD: u32 = 5;
+
+/// How long an open circuit drops deliveries before trying the webhook again.
+pub const OPEN_CIRCUIT_COOLDOWN: Duration = Duration::from_secs(30);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The JSON body POSTed to a queue's webhook for each message enqueued on it.
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageEvent {
+    pub queue: String,
+    pub message_id: String,
+    pub body: String,
+    pub attributes: HashMap<String, String>,
+    pub message_attributes: HashMap<String, MessageAttributeValue>,
+    /// Epoch milliseconds.
+    pub sent_timestamp: i64,
+}
+
+#[derive(Debug)]
+struct Delivery {
+    url: String,
+    event: MessageEvent,
+}
+
+/// Hands message events to the delivery worker started by [`spawn`].
+#[derive(Debug)]
+pub struct Webhooks {
+    sender: mpsc::Sender<Delivery>,
+    receiver: Mutex<Option<mpsc::Receiver<Delivery>>>,
+}
+
+impl Default for Webhooks {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        Self {
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+        }
+    }
+}
+
+impl Webhooks {
+    /// Queues a notification for `message` if `queue` has a webhook. Never
+    /// blocks: the caller is usually holding the queue's lock.
+    pub fn message_enqueued(&self, queue: &Queue, message: &Message) {
+        let Some(url) = &queue.webhook else {
+            return;
+        };
+        let delivery = Delivery {
+            url: url.clone(),
+            event: MessageEvent {
+                queue: queue.name.clone(),
+                message_id: message.id.clone(),
+                body: message.body.as_str().into_owned(),
+                attributes: message.attributes.clone(),
+                message_attributes: message.message_attributes.clone(),
+                sent_timestamp: message.sent_timestamp.timestamp_millis(),
+            },
+        };
+        if let Err(e) = self.sender.try_send(delivery) {
+            warn!(queue = %queue.name, "dropping webhook delivery: {}", e);
+        }
+    }
+}
+
+/// Per-webhook circuit breaker state.
+#[derive(Debug, Default)]
+struct Circuit {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+/// Spawns the worker that delivers webhook events in the order they were
+/// queued, until `state.shutdown` is cancelled.
+///
+/// Each delivery is retried with exponential backoff on errors and non-2xx
+/// responses. After [`FAILURE_THRESHOLD`] consecutive failed deliveries a
+/// webhook's circuit opens and its events are dropped for
+/// [`OPEN_CIRCUIT_COOLDOWN`], so a dead endpoint can't hold up the others.
+pub fn spawn(state: AppState) -> JoinHandle<()> {
+    let receiver = state.webhooks.receiver.lock().unwrap().take();
+    tokio::spawn(async move {
+        let Some(mut receiver) = receiver else {
+            return;
+        };
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        let mut circuits: HashMap<String, Circuit> = HashMap::new();
+
+        loop {
+            let delivery = tokio::select! {
+                _ = state.shutdown.cancelled() => break,
+                delivery = receiver.recv() => match delivery {
+                    Some(delivery) => delivery,
+                    None => break,
+                },
+            };
+
+            let circuit = circuits.entry(delivery.url.clone()).or_default();
+            if let Some(open_until) = circuit.open_until {
+                if Instant::now() < open_until {
+                    debug!(url = %delivery.url, "circuit open; dropping webhook delivery");
+                    continue;
+                }
+                circuit.open_until = None;
+            }
+
+            let delivered = tokio::select! {
+                _ = state.shutdown.cancelled() => break,
+                delivered = deliver(&client, &delivery) => delivered,
+            };
+            if delivered {
+                circuit.consecutive_failures = 0;
+                continue;
+            }
+
+            circuit.consecutive_failures += 1;
+            if circuit.consecutive_failures >= FAILURE_THRESHOLD {
+                warn!(
+                    url = %delivery.url,
+                    "webhook failed {} times in a row; pausing deliveries for {:?}",
+                    circuit.consecutive_failures,
+                    OPEN_CIRCUIT_COOLDOWN
+                );
+                circuit.consecutive_failures = 0;
+                circuit.open_until = Some(Instant::now() + OPEN_CIRCUIT_COOLDOWN);
+            }
+        }
+
+        debug!("webhook worker stopped");
+    })
+}
+
+/// POSTs one event, retrying up to [`MAX_ATTEMPTS`] times.
+async fn deliver(client: &reqwest::Client, delivery: &Delivery) -> bool {
+    let mut backoff = INITIAL_BACKOFF;
+    for attempt in 1..=MAX_ATTEMPTS {
+        match client.post(&delivery.url).json(&delivery.event).send().await {
+            Ok(response) if response.status().is_success() => return true,
+            Ok(response) => warn!(
+                url = %delivery.url,
+                attempt,
+                "webhook responded with {}",
+                response.status()
+            ),
+            Err(e) => warn!(url = %delivery.url, attempt, "webhook request failed: {}", e),
+        }
+        if attempt < MAX_ATTEMPTS {
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+    false
+}
+
+/// Checks that `url` is an absolute `http://` URL a webhook can be sent to.
+pub fn validate_url(url: &str) -> Result<(), SqsError> {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if parsed.scheme() == "http" && parsed.has_host() => Ok(()),
+        _ => Err(SqsError::InvalidParameterValue(format!(
+            "Invalid webhook URL {}: must be an absolute http:// URL.",
+            url
+        ))),
+    }
+}
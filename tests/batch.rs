@@ -0,0 +1,220 @@
+mod common;
+
+use aws_sdk_sqs::operation::send_message_batch::SendMessageBatchError;
+use aws_sdk_sqs::types::{
+    ChangeMessageVisibilityBatchRequestEntry, DeleteMessageBatchRequestEntry, MessageAttributeValue,
+    QueueAttributeName, SendMessageBatchRequestEntry,
+};
+use common::{TestServer, storage_matrix};
+
+fn entry(id: &str) -> SendMessageBatchRequestEntry {
+    SendMessageBatchRequestEntry::builder()
+        .id(id)
+        .message_body(format!("body-{}", id))
+        .build()
+        .unwrap()
+}
+
+async fn send_batch(
+    server: &TestServer,
+    queue_url: &str,
+    entries: Vec<SendMessageBatchRequestEntry>,
+) -> Result<(), SendMessageBatchError> {
+    server
+        .client
+        .send_message_batch()
+        .queue_url(queue_url)
+        .set_entries(Some(entries))
+        .send()
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into_service_error())
+}
+
+async fn queue_depth(server: &TestServer, queue_url: &str) -> String {
+    server
+        .client
+        .get_queue_attributes()
+        .queue_url(queue_url)
+        .attribute_names(QueueAttributeName::ApproximateNumberOfMessages)
+        .send()
+        .await
+        .unwrap()
+        .attributes
+        .unwrap()[&QueueAttributeName::ApproximateNumberOfMessages]
+        .clone()
+}
+
+storage_matrix!(batch_round_trip);
+async fn batch_round_trip() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("batch").await;
+    let client = &server.client;
+
+    let sent = client
+        .send_message_batch()
+        .queue_url(&queue_url)
+        .set_entries(Some(vec![entry("a"), entry("b"), entry("c")]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(sent.successful.len(), 3);
+    assert!(sent.failed.is_empty());
+
+    let received = client
+        .receive_message()
+        .queue_url(&queue_url)
+        .max_number_of_messages(10)
+        .send()
+        .await
+        .unwrap()
+        .messages
+        .unwrap();
+    assert_eq!(received.len(), 3);
+
+    let changed = client
+        .change_message_visibility_batch()
+        .queue_url(&queue_url)
+        .entries(
+            ChangeMessageVisibilityBatchRequestEntry::builder()
+                .id("ok")
+                .receipt_handle(received[0].receipt_handle.clone().unwrap())
+                .visibility_timeout(60)
+                .build()
+                .unwrap(),
+        )
+        .entries(
+            ChangeMessageVisibilityBatchRequestEntry::builder()
+                .id("stale")
+                .receipt_handle("not-a-handle")
+                .visibility_timeout(60)
+                .build()
+                .unwrap(),
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(changed.successful[0].id, "ok");
+    assert_eq!(changed.failed[0].id, "stale");
+    assert!(changed.failed[0].sender_fault);
+
+    let deleted = client
+        .delete_message_batch()
+        .queue_url(&queue_url)
+        .set_entries(Some(
+            received
+                .iter()
+                .enumerate()
+                .map(|(i, m)| {
+                    DeleteMessageBatchRequestEntry::builder()
+                        .id(format!("d{}", i))
+                        .receipt_handle(m.receipt_handle.clone().unwrap())
+                        .build()
+                        .unwrap()
+                })
+                .collect(),
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(deleted.successful.len(), 3);
+    assert_eq!(queue_depth(&server, &queue_url).await, "0");
+}
+
+storage_matrix!(too_many_entries_fails_the_whole_request);
+async fn too_many_entries_fails_the_whole_request() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("too-many").await;
+
+    let entries = (0..11).map(|i| entry(&i.to_string())).collect();
+    let err = send_batch(&server, &queue_url, entries).await.unwrap_err();
+    assert!(err.is_too_many_entries_in_batch_request(), "{:?}", err);
+    assert_eq!(queue_depth(&server, &queue_url).await, "0");
+}
+
+storage_matrix!(empty_batch_fails_the_whole_request);
+async fn empty_batch_fails_the_whole_request() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("empty-batch").await;
+
+    let err = server
+        .client
+        .delete_message_batch()
+        .queue_url(&queue_url)
+        .set_entries(Some(Vec::new()))
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert!(err.is_empty_batch_request(), "{:?}", err);
+}
+
+storage_matrix!(duplicate_ids_fail_the_whole_request);
+async fn duplicate_ids_fail_the_whole_request() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("duplicates").await;
+
+    let entries = vec![entry("a"), entry("b"), entry("a")];
+    let err = send_batch(&server, &queue_url, entries).await.unwrap_err();
+    assert!(err.is_batch_entry_ids_not_distinct(), "{:?}", err);
+    assert_eq!(queue_depth(&server, &queue_url).await, "0");
+}
+
+storage_matrix!(invalid_ids_fail_the_whole_request);
+async fn invalid_ids_fail_the_whole_request() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("invalid-ids").await;
+
+    for bad in ["has space", "dot.ted", &"x".repeat(81)] {
+        let entries = vec![entry("ok"), entry(bad)];
+        let err = send_batch(&server, &queue_url, entries).await.unwrap_err();
+        assert!(err.is_invalid_batch_entry_id(), "{}: {:?}", bad, err);
+    }
+    assert_eq!(queue_depth(&server, &queue_url).await, "0");
+
+    let entries = vec![entry(&"x".repeat(80))];
+    send_batch(&server, &queue_url, entries).await.unwrap();
+}
+
+fn sized_entry(id: &str, size: usize) -> SendMessageBatchRequestEntry {
+    SendMessageBatchRequestEntry::builder()
+        .id(id)
+        .message_body("x".repeat(size))
+        .build()
+        .unwrap()
+}
+
+storage_matrix!(batch_payload_at_the_limit_is_accepted);
+async fn batch_payload_at_the_limit_is_accepted() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("at-limit").await;
+
+    // Name (5) + data type (6) + value (5) count toward the total.
+    let with_attribute = SendMessageBatchRequestEntry::builder()
+        .id("b")
+        .message_body("x".repeat(131072 - 16))
+        .message_attributes(
+            "color",
+            MessageAttributeValue::builder()
+                .data_type("String")
+                .string_value("green")
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+    let entries = vec![sized_entry("a", 131072), with_attribute];
+    send_batch(&server, &queue_url, entries).await.unwrap();
+    assert_eq!(queue_depth(&server, &queue_url).await, "2");
+}
+
+storage_matrix!(batch_payload_one_byte_over_is_rejected);
+async fn batch_payload_one_byte_over_is_rejected() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("over-limit").await;
+
+    let entries = vec![sized_entry("a", 131072), sized_entry("b", 131073)];
+    let err = send_batch(&server, &queue_url, entries).await.unwrap_err();
+    assert!(err.is_batch_request_too_long(), "{:?}", err);
+    assert_eq!(queue_depth(&server, &queue_url).await, "0");
+}
@@ -0,0 +1,128 @@
+use aws_sdk_sqs::config::{Credentials, Region};
+use aws_sdk_sqs::Client;
+use local_sqs::Config;
+use std::net::SocketAddr;
+
+fn client(endpoint: String) -> Client {
+    let config = aws_sdk_sqs::Config::builder()
+        .endpoint_url(endpoint)
+        .region(Region::new("us-east-1"))
+        .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+        .build();
+    Client::from_conf(config)
+}
+
+/// Whether this machine can listen on the IPv6 loopback address.
+fn has_ipv6() -> bool {
+    std::net::TcpListener::bind("[::1]:0").is_ok()
+}
+
+async fn queue_urls(addr: SocketAddr) -> Vec<String> {
+    let listed = client(format!("http://{}", addr)).list_queues().send().await.unwrap();
+    listed.queue_urls().to_vec()
+}
+
+#[tokio::test]
+async fn every_bind_address_serves_the_same_queues() {
+    let mut bind = vec!["127.0.0.1:0".to_string()];
+    if has_ipv6() {
+        bind.push("[::1]:0".to_string());
+    }
+    let config = Config {
+        host: "127.0.0.1".to_string(),
+        bind: bind.clone(),
+        ..Config::default()
+    };
+    let (addrs, server, shutdown) = local_sqs::serve(config).await.unwrap();
+    assert_eq!(addrs.len(), bind.len());
+
+    let created = client(format!("http://{}", addrs[0])).create_queue().queue_name("shared");
+    let queue_url = created.send().await.unwrap().queue_url.unwrap();
+    // Every address is bound to the port queue URLs carry.
+    assert!(addrs.iter().all(|addr| addr.port() == addrs[0].port()), "{:?}", addrs);
+    assert_eq!(queue_url, format!("http://127.0.0.1:{}/000000000000/shared", addrs[0].port()));
+    for addr in &addrs {
+        assert_eq!(queue_urls(*addr).await, [queue_url.as_str()], "{}", addr);
+    }
+
+    shutdown.shutdown();
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn ipv6_hosts_are_bracketed_in_queue_urls() {
+    let config = Config {
+        host: "::1".to_string(),
+        bind: vec!["127.0.0.1:0".to_string()],
+        ..Config::default()
+    };
+    let (addrs, server, shutdown) = local_sqs::serve(config).await.unwrap();
+    let client = client(format!("http://{}", addrs[0]));
+
+    let created = client.create_queue().queue_name("v6").send().await.unwrap();
+    let queue_url = created.queue_url.unwrap();
+    assert_eq!(queue_url, format!("http://[::1]:{}/000000000000/v6", addrs[0].port()));
+    let sent = client.send_message().queue_url(&queue_url).message_body("m").send().await;
+    assert!(sent.is_ok(), "{:?}", sent);
+
+    shutdown.shutdown();
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn localhost_listens_on_both_loopback_addresses() {
+    let config = Config {
+        port: 0,
+        ..Config::default()
+    };
+    let (addrs, server, shutdown) = local_sqs::serve(config).await.unwrap();
+    assert_eq!(addrs[0].ip().to_string(), "127.0.0.1");
+    if has_ipv6() {
+        assert_eq!(addrs.len(), 2, "{:?}", addrs);
+        assert_eq!(addrs[1].ip().to_string(), "::1");
+        assert_eq!(addrs[1].port(), addrs[0].port());
+    }
+    for addr in &addrs {
+        assert!(queue_urls(*addr).await.is_empty(), "{}", addr);
+    }
+
+    shutdown.shutdown();
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn unusable_bind_addresses_fail_startup() {
+    let config = Config {
+        bind: vec!["127.0.0.1:0".to_string(), "not-an-address".to_string()],
+        ..Config::default()
+    };
+    let error = local_sqs::serve(config).await.err().unwrap();
+    assert!(error.to_string().contains("not-an-address"), "{}", error);
+}
+
+#[tokio::test]
+async fn bind_addresses_on_different_ports_fail_startup() {
+    // Held together so that they can't be given the same port.
+    let taken = [(); 2].map(|_| std::net::TcpListener::bind("127.0.0.1:0").unwrap());
+    let [first, second] = taken.map(|listener| listener.local_addr().unwrap());
+    let config = Config {
+        bind: vec![first.to_string(), second.to_string()],
+        ..Config::default()
+    };
+    let error = local_sqs::serve(config).await.err().unwrap();
+    assert!(error.to_string().contains("must have the port queue URLs carry"), "{}", error);
+}
+
+#[test]
+fn a_port_in_use_exits_with_an_error() {
+    let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = taken.local_addr().unwrap().port().to_string();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_local-sqs-rs"))
+        .args(["--host", "127.0.0.1", "--port", &port])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Address already in use"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
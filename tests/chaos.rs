@@ -0,0 +1,165 @@
+mod common;
+
+use common::{TestServer, storage_matrix};
+use local_sqs::Config;
+use serde_json::Value;
+
+async fn stats(server: &TestServer, queue: &str) -> Value {
+    let (_, body) = server
+        .admin("GET", &format!("/queues/{}/stats", queue), "")
+        .await;
+    serde_json::from_str(&body).unwrap()
+}
+
+storage_matrix!(duplicates_reuse_in_flight_messages_under_new_handles);
+async fn duplicates_reuse_in_flight_messages_under_new_handles() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("flaky").await;
+    let (status, body) = server
+        .admin("PUT", "/queues/flaky/chaos", r#"{"duplicate_delivery_probability": 1.0}"#)
+        .await;
+    assert_eq!(status, 200, "{}", body);
+    server.send(&queue_url, "once").await;
+
+    // Nothing is in flight yet, so there is nothing to duplicate.
+    let first = server.receive(&queue_url, Some(60)).await;
+    assert_eq!(first.len(), 1);
+
+    let second = server.receive(&queue_url, Some(60)).await;
+    assert_eq!(second.len(), 1);
+    assert_eq!(second[0].message_id, first[0].message_id);
+    assert_eq!(second[0].body(), Some("once"));
+    assert_ne!(second[0].receipt_handle, first[0].receipt_handle);
+    assert_eq!(stats(&server, "flaky").await["duplicates_delivered"], 1);
+
+    // The duplicate's handle deletes the one underlying message.
+    server
+        .client
+        .delete_message()
+        .queue_url(&queue_url)
+        .receipt_handle(second[0].receipt_handle().unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(stats(&server, "flaky").await["messages"], 0);
+}
+
+storage_matrix!(server_wide_setting_applies_unless_overridden);
+async fn server_wide_setting_applies_unless_overridden() {
+    let mut config = Config::default();
+    config.chaos.duplicate_delivery_probability = 1.0;
+    let server = TestServer::start_with(config).await;
+    let noisy_url = server.create_queue("noisy").await;
+    let quiet_url = server.create_queue("quiet").await;
+    server
+        .admin("PUT", "/queues/quiet/chaos", r#"{"duplicate_delivery_probability": 0}"#)
+        .await;
+
+    for url in [&noisy_url, &quiet_url] {
+        server.send(url, "hello").await;
+        assert_eq!(server.receive(url, Some(60)).await.len(), 1);
+    }
+    assert_eq!(server.receive(&noisy_url, Some(60)).await.len(), 1);
+    assert!(server.receive(&quiet_url, Some(60)).await.is_empty());
+
+    let (_, body) = server.admin("DELETE", "/queues/quiet/chaos", "").await;
+    let chaos: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(chaos["override"], Value::Null);
+    assert_eq!(chaos["effective"]["duplicate_delivery_probability"], 1.0);
+}
+
+storage_matrix!(duplicates_are_off_by_default);
+async fn duplicates_are_off_by_default() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("plain").await;
+    server.send(&queue_url, "hello").await;
+
+    assert_eq!(server.receive(&queue_url, Some(60)).await.len(), 1);
+    assert!(server.receive(&queue_url, Some(60)).await.is_empty());
+    assert_eq!(stats(&server, "plain").await["duplicates_delivered"], 0);
+}
+
+storage_matrix!(probabilities_outside_zero_to_one_are_rejected);
+async fn probabilities_outside_zero_to_one_are_rejected() {
+    let server = TestServer::start().await;
+    server.create_queue("bounded").await;
+    let (status, _) = server
+        .admin("PUT", "/queues/bounded/chaos", r#"{"duplicate_delivery_probability": 1.5}"#)
+        .await;
+    assert_eq!(status, 400);
+
+    let mut config = Config::default();
+    config.chaos.duplicate_delivery_probability = -0.1;
+    config.port = 0;
+    assert!(local_sqs::serve(config).await.is_err());
+}
+
+async fn receive_bodies(server: &TestServer, queue_url: &str) -> Vec<String> {
+    server
+        .receive(queue_url, Some(60))
+        .await
+        .into_iter()
+        .map(|message| message.body.unwrap())
+        .collect()
+}
+
+/// Sends twenty numbered messages to a fresh shuffled queue on a server with
+/// `seed` and returns the order of the first receive.
+async fn shuffled_order(seed: u64) -> Vec<String> {
+    let mut config = Config::default();
+    config.chaos.shuffle_delivery = true;
+    config.chaos_seed = Some(seed);
+    let server = TestServer::start_with(config).await;
+    let queue_url = server.create_queue("shuffled").await;
+    for i in 0..20 {
+        server.send(&queue_url, &i.to_string()).await;
+    }
+    receive_bodies(&server, &queue_url).await
+}
+
+storage_matrix!(shuffled_delivery_is_reproducible_from_the_seed);
+async fn shuffled_delivery_is_reproducible_from_the_seed() {
+    let first = shuffled_order(42).await;
+    assert_eq!(first.len(), 10);
+    assert_eq!(shuffled_order(42).await, first);
+
+    let in_order: Vec<String> = (0..10).map(|i| i.to_string()).collect();
+    assert_ne!(first, in_order);
+}
+
+storage_matrix!(fifo_queues_are_never_shuffled);
+async fn fifo_queues_are_never_shuffled() {
+    let mut config = Config::default();
+    config.chaos.shuffle_delivery = true;
+    let server = TestServer::start_with(config).await;
+    let queue_url = server
+        .client
+        .create_queue()
+        .queue_name("ordered.fifo")
+        .attributes(aws_sdk_sqs::types::QueueAttributeName::FifoQueue, "true")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    for i in 0..10 {
+        server
+            .client
+            .send_message()
+            .queue_url(&queue_url)
+            .message_body(i.to_string())
+            .message_group_id("group")
+            .message_deduplication_id(i.to_string())
+            .send()
+            .await
+            .unwrap();
+    }
+
+    let in_order: Vec<String> = (0..10).map(|i| i.to_string()).collect();
+    assert_eq!(receive_bodies(&server, &queue_url).await, in_order);
+
+    let (_, body) = server.admin("GET", "/queues/ordered.fifo/chaos", "").await;
+    let chaos: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(chaos["effective"]["shuffle_delivery"], true);
+    assert!(chaos["seed"].is_u64());
+}
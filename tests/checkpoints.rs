@@ -0,0 +1,167 @@
+mod common;
+
+use aws_sdk_sqs::types::QueueAttributeName;
+use common::{TestServer, storage_matrix};
+use local_sqs::Config;
+use serde_json::Value;
+use std::time::{Duration, Instant};
+
+storage_matrix!(restoring_returns_to_the_checkpointed_state);
+async fn restoring_returns_to_the_checkpointed_state() {
+    let server = TestServer::start().await;
+    let orders = server.create_queue("orders").await;
+    let audit = server.create_queue("audit").await;
+    server.send(&orders, "first").await;
+
+    let (status, body) = server.admin("POST", "/checkpoints/topology", "").await;
+    assert_eq!(status, 200, "{}", body);
+    let saved: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(saved["name"], "topology");
+    assert!(saved["size"].as_u64().unwrap() > 0);
+
+    // Each case may do as it likes, and the next starts from the checkpoint
+    // again.
+    for _ in 0..2 {
+        server.send(&orders, "second").await;
+        server.client.delete_queue().queue_url(&audit).send().await.unwrap();
+        server.create_queue("scratch").await;
+
+        let (status, body) = server.admin("POST", "/checkpoints/topology/restore", "").await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(server.queue_names().await, ["audit", "orders"]);
+        let received = server
+            .client
+            .receive_message()
+            .queue_url(&orders)
+            .max_number_of_messages(10)
+            .send()
+            .await
+            .unwrap();
+        let bodies: Vec<&str> = received.messages().iter().filter_map(|m| m.body()).collect();
+        assert_eq!(bodies, ["first"]);
+    }
+
+    let (status, body) = server.admin("GET", "/checkpoints", "").await;
+    assert_eq!(status, 200);
+    let listed: Vec<Value> = serde_json::from_str(&body).unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0]["name"], "topology");
+    assert_eq!(listed[0]["size"], saved["size"]);
+    assert_eq!(listed[0]["created"], saved["created"]);
+
+    let (status, _) = server.admin("DELETE", "/checkpoints/topology", "").await;
+    assert_eq!(status, 200);
+    let (status, body) = server.admin("POST", "/checkpoints/topology/restore", "").await;
+    assert_eq!(status, 404);
+    assert!(body.contains("Checkpoint topology does not exist"), "{}", body);
+    let (status, _) = server.admin("POST", "/checkpoints/..%2Fescape", "").await;
+    assert_eq!(status, 400);
+}
+
+storage_matrix!(restoring_wakes_long_polls);
+async fn restoring_wakes_long_polls() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("polled").await;
+    server.send(&queue_url, "restored").await;
+    let (status, _) = server.admin("POST", "/checkpoints/with-message", "").await;
+    assert_eq!(status, 200);
+    server.client.purge_queue().queue_url(&queue_url).send().await.unwrap();
+
+    let started = Instant::now();
+    let poll = {
+        let (client, queue_url) = (server.client.clone(), queue_url.clone());
+        tokio::spawn(async move {
+            client
+                .receive_message()
+                .queue_url(queue_url)
+                .wait_time_seconds(10)
+                .send()
+                .await
+                .unwrap()
+        })
+    };
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let (status, _) = server.admin("POST", "/checkpoints/with-message/restore", "").await;
+    assert_eq!(status, 200);
+
+    let received = poll.await.unwrap();
+    assert_eq!(received.messages()[0].body(), Some("restored"));
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+storage_matrix!(checkpoints_in_a_directory_outlive_the_server);
+async fn checkpoints_in_a_directory_outlive_the_server() {
+    let dir = std::env::temp_dir().join(format!("local-sqs-checkpoints-{}", uuid::Uuid::new_v4()));
+    let config = || Config {
+        checkpoint_dir: Some(dir.clone()),
+        ..Default::default()
+    };
+    let server = TestServer::start_with(config()).await;
+    let queue_url = server.create_queue("kept").await;
+    server.send(&queue_url, "on disk").await;
+    let (status, _) = server.admin("POST", "/checkpoints/baseline", "").await;
+    assert_eq!(status, 200);
+    assert!(dir.join("baseline.json").exists());
+    server.stop().await;
+
+    let server = TestServer::start_with(config()).await;
+    let (_, body) = server.admin("GET", "/checkpoints", "").await;
+    let listed: Vec<Value> = serde_json::from_str(&body).unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0]["name"], "baseline");
+    let (status, body) = server.admin("POST", "/checkpoints/baseline/restore", "").await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(server.queue_names().await, ["kept"]);
+
+    let (status, _) = server.admin("DELETE", "/checkpoints/baseline", "").await;
+    assert_eq!(status, 200);
+    assert!(!dir.join("baseline.json").exists());
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+storage_matrix!(restoring_keeps_queue_settings_counters_and_numbering);
+async fn restoring_keeps_queue_settings_counters_and_numbering() {
+    let server = TestServer::start().await;
+    let create = server.client.create_queue().queue_name("hooked.fifo");
+    let attributes = [
+        (QueueAttributeName::FifoQueue, "true"),
+        (QueueAttributeName::ContentBasedDeduplication, "true"),
+    ];
+    let create = attributes.into_iter().fold(create, |create, (name, value)| {
+        create.attributes(name, value)
+    });
+    let queue_url = create.send().await.unwrap().queue_url.unwrap();
+    let webhook = r#"{"url": "http://127.0.0.1:9/hook"}"#;
+    let (status, _) = server.admin("PUT", "/queues/hooked.fifo/webhook", webhook).await;
+    assert_eq!(status, 200);
+    let chaos = r#"{"duplicate_delivery_probability": 0.5}"#;
+    let (status, _) = server.admin("PUT", "/queues/hooked.fifo/chaos", chaos).await;
+    assert_eq!(status, 200);
+    let send = server.client.send_message().queue_url(&queue_url).message_group_id("g");
+    send.clone().message_body("first").send().await.unwrap();
+    let (_, body) = server.admin("GET", "/export", "").await;
+    let sequence_number = serde_json::from_str::<Value>(&body).unwrap()["queues"][0]
+        ["sequence_number"]
+        .clone();
+    assert!(sequence_number.as_u64().unwrap() > 0);
+    let (status, _) = server.admin("POST", "/checkpoints/hooked", "").await;
+    assert_eq!(status, 200);
+
+    server.admin("DELETE", "/queues/hooked.fifo/webhook", "").await;
+    server.admin("DELETE", "/queues/hooked.fifo/chaos", "").await;
+    server.admin("DELETE", "/queues/hooked.fifo/stats", "").await;
+    send.message_body("second").send().await.unwrap();
+    let (status, body) = server.admin("POST", "/checkpoints/hooked/restore", "").await;
+    assert_eq!(status, 200, "{}", body);
+
+    let (_, body) = server.admin("GET", "/queues/hooked.fifo/webhook", "").await;
+    assert_eq!(body, r#"{"url":"http://127.0.0.1:9/hook"}"#);
+    let (_, body) = server.admin("GET", "/queues/hooked.fifo/chaos", "").await;
+    let chaos: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(chaos["override"]["duplicate_delivery_probability"], 0.5);
+    let (_, body) = server.admin("GET", "/queues/hooked.fifo/stats", "").await;
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap()["sent"], 1);
+    let (_, body) = server.admin("GET", "/export", "").await;
+    let exported: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(exported["queues"][0]["sequence_number"], sequence_number);
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use common::{TestServer, storage_matrix};
+use local_sqs::client::{Client, ClientError};
+use local_sqs::state::MessageAttributeValue;
+use std::collections::HashMap;
+
+fn client(server: &TestServer) -> Client {
+    Client::new(format!("http://{}/", server.addr))
+}
+
+storage_matrix!(round_trips_a_message);
+async fn round_trips_a_message() {
+    let server = TestServer::start().await;
+    let client = client(&server);
+
+    let created = client
+        .create_queue("jobs", HashMap::from([("VisibilityTimeout".to_string(), "5".to_string())]))
+        .await
+        .unwrap();
+    assert!(created.queue_url.ends_with("/jobs"));
+    assert_eq!(client.list_queues(None).await.unwrap(), vec![created.queue_url.clone()]);
+
+    let attributes = HashMap::from([(
+        "color".to_string(),
+        MessageAttributeValue {
+            string_value: Some("red".to_string()),
+            binary_value: None,
+            data_type: "String".to_string(),
+        },
+    )]);
+    let sent = client
+        .send_message("jobs", "hello", attributes, None)
+        .await
+        .unwrap();
+    assert!(!sent.md5_of_message_attributes.is_empty());
+
+    // Peeking leaves the message where it is.
+    let peeked = client.peek("jobs", None).await.unwrap();
+    assert_eq!(peeked.len(), 1);
+    assert_eq!(peeked[0].id, sent.message_id);
+    assert_eq!(peeked[0].receipt_handle, None);
+
+    let received = client
+        .receive_message(&created.queue_url, 10, None, None)
+        .await
+        .unwrap();
+    assert_eq!(received.messages.len(), 1);
+    assert_eq!(received.messages[0].body, "hello");
+    assert_eq!(
+        received.messages[0].message_attributes["color"].string_value.as_deref(),
+        Some("red")
+    );
+}
+
+storage_matrix!(purge_empties_the_queue);
+async fn purge_empties_the_queue() {
+    let server = TestServer::start().await;
+    let client = client(&server);
+    client.create_queue("full", HashMap::new()).await.unwrap();
+    for body in ["a", "b", "c"] {
+        client.send_message("full", body, HashMap::new(), None).await.unwrap();
+    }
+    assert_eq!(client.peek("full", Some(2)).await.unwrap().len(), 2);
+
+    client.purge_queue("full").await.unwrap();
+    assert!(client.peek("full", None).await.unwrap().is_empty());
+}
+
+storage_matrix!(server_errors_carry_the_sqs_code);
+async fn server_errors_carry_the_sqs_code() {
+    let server = TestServer::start().await;
+    let client = client(&server);
+    match client.receive_message("missing", 1, None, None).await {
+        Err(ClientError::Sqs { code, .. }) => assert_eq!(code, "QueueDoesNotExist"),
+        other => panic!("expected an SQS error, got {:?}", other),
+    }
+}
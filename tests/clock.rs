@@ -0,0 +1,212 @@
+mod common;
+
+use aws_sdk_sqs::types::QueueAttributeName;
+use common::{TestServer, storage_matrix};
+use local_sqs::Config;
+use std::time::{Duration, Instant};
+
+async fn start_manual() -> TestServer {
+    TestServer::start_with(Config {
+        manual_clock: true,
+        ..Config::default()
+    })
+    .await
+}
+
+storage_matrix!(delays_elapse_when_the_clock_advances);
+async fn delays_elapse_when_the_clock_advances() {
+    let server = start_manual().await;
+    let queue_url = server.create_queue("delayed").await;
+
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("later")
+        .delay_seconds(600)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(server.receive(&queue_url, None).await.len(), 0);
+
+    server.advance_clock(599).await;
+    assert_eq!(server.receive(&queue_url, None).await.len(), 0);
+    server.advance_clock(1).await;
+    assert_eq!(server.receive(&queue_url, None).await.len(), 1);
+}
+
+storage_matrix!(visibility_timeouts_expire_when_the_clock_advances);
+async fn visibility_timeouts_expire_when_the_clock_advances() {
+    let server = start_manual().await;
+    let queue_url = server.create_queue("inflight").await;
+
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("body")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(server.receive(&queue_url, None).await.len(), 1);
+    assert_eq!(server.receive(&queue_url, None).await.len(), 0);
+
+    server.advance_clock(30).await;
+    assert_eq!(server.receive(&queue_url, None).await.len(), 1);
+}
+
+storage_matrix!(retention_applies_when_the_clock_advances);
+async fn retention_applies_when_the_clock_advances() {
+    let server = start_manual().await;
+    let queue_url = server
+        .client
+        .create_queue()
+        .queue_name("short-lived")
+        .attributes(QueueAttributeName::MessageRetentionPeriod, "60")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("body")
+        .send()
+        .await
+        .unwrap();
+
+    server.advance_clock(61).await;
+    assert_eq!(server.receive(&queue_url, None).await.len(), 0);
+}
+
+storage_matrix!(advancing_wakes_long_polls);
+async fn advancing_wakes_long_polls() {
+    let server = start_manual().await;
+    let queue_url = server.create_queue("polled").await;
+
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("later")
+        .delay_seconds(300)
+        .send()
+        .await
+        .unwrap();
+
+    let client = server.client.clone();
+    let url = queue_url.clone();
+    let started = Instant::now();
+    let poll = tokio::spawn(async move {
+        client
+            .receive_message()
+            .queue_url(url)
+            .wait_time_seconds(20)
+            .send()
+            .await
+            .unwrap()
+            .messages()
+            .len()
+    });
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    server.advance_clock(300).await;
+    assert_eq!(poll.await.unwrap(), 1);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+storage_matrix!(the_real_clock_cannot_be_advanced);
+async fn the_real_clock_cannot_be_advanced() {
+    let server = TestServer::start().await;
+
+    let (status, body) = server
+        .admin("POST", "/clock/advance", r#"{"seconds": 30}"#)
+        .await;
+    assert_eq!(status, 400);
+    assert!(body.contains("manual clock"), "{}", body);
+
+    let (status, body) = server.admin("GET", "/clock", "").await;
+    assert_eq!(status, 200);
+    assert!(body.contains(r#""manual":false"#), "{}", body);
+}
+
+storage_matrix!(last_modified_moves_with_configuration_changes_only);
+async fn last_modified_moves_with_configuration_changes_only() {
+    let server = start_manual().await;
+    let queue_url = server.create_queue("timestamped").await;
+    let client = &server.client;
+    let timestamps = || async {
+        let get = client.get_queue_attributes().queue_url(&queue_url);
+        let get = get
+            .attribute_names(QueueAttributeName::CreatedTimestamp)
+            .attribute_names(QueueAttributeName::LastModifiedTimestamp);
+        let attributes = get.send().await.unwrap().attributes.unwrap();
+        let seconds = |name| attributes[&name].parse::<i64>().unwrap();
+        (
+            seconds(QueueAttributeName::CreatedTimestamp),
+            seconds(QueueAttributeName::LastModifiedTimestamp),
+        )
+    };
+    let (created, last_modified) = timestamps().await;
+    assert_eq!(last_modified, created);
+
+    // Messages are not configuration.
+    server.advance_clock(10).await;
+    client.send_message().queue_url(&queue_url).message_body("m").send().await.unwrap();
+    client.purge_queue().queue_url(&queue_url).send().await.unwrap();
+    assert_eq!(timestamps().await, (created, created));
+
+    let set = client.set_queue_attributes().queue_url(&queue_url);
+    set.attributes(QueueAttributeName::DelaySeconds, "1").send().await.unwrap();
+    assert_eq!(timestamps().await, (created, created + 10));
+    server.advance_clock(10).await;
+    client.tag_queue().queue_url(&queue_url).tags("team", "a").send().await.unwrap();
+    assert_eq!(timestamps().await, (created, created + 20));
+    server.advance_clock(10).await;
+    client.untag_queue().queue_url(&queue_url).tag_keys("team").send().await.unwrap();
+    assert_eq!(timestamps().await, (created, created + 30));
+    server.advance_clock(10).await;
+    let add = client.add_permission().queue_url(&queue_url).label("read");
+    let add = add.aws_account_ids("111122223333").actions("ReceiveMessage");
+    add.send().await.unwrap();
+    assert_eq!(timestamps().await, (created, created + 40));
+    server.advance_clock(10).await;
+    let remove = client.remove_permission().queue_url(&queue_url).label("read");
+    remove.send().await.unwrap();
+    assert_eq!(timestamps().await, (created, created + 50));
+}
+
+storage_matrix!(message_timestamps_are_epoch_milliseconds_on_the_clock);
+async fn message_timestamps_are_epoch_milliseconds_on_the_clock() {
+    let server = start_manual().await;
+    let queue_url = server.create_queue("stamped").await;
+    let (status, body) = server.admin("GET", "/clock", "").await;
+    assert_eq!(status, 200, "{}", body);
+    let clock: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let sent = clock["now"].as_i64().unwrap();
+
+    let send = server.client.send_message().queue_url(&queue_url).message_body("m");
+    send.send().await.unwrap();
+    let timestamps = || async {
+        let request = serde_json::json!({
+            "QueueUrl": queue_url,
+            "MessageSystemAttributeNames": ["All"],
+        });
+        let (status, body) = server.action("AmazonSQS.ReceiveMessage", &request.to_string()).await;
+        assert_eq!(status, 200, "{}", body);
+        let response: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let attributes = &response["Messages"][0]["Attributes"];
+        // As in AWS, these are strings of milliseconds, not JSON numbers.
+        let millis = |name: &str| attributes[name].as_str().unwrap().parse::<i64>().unwrap();
+        (millis("SentTimestamp"), millis("ApproximateFirstReceiveTimestamp"))
+    };
+
+    server.advance_clock(5).await;
+    assert_eq!(timestamps().await, (sent, sent + 5_000));
+    // Later receives keep the first receive's timestamp.
+    server.advance_clock(30).await;
+    assert_eq!(timestamps().await, (sent, sent + 5_000));
+}
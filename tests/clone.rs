@@ -0,0 +1,157 @@
+mod common;
+
+use aws_sdk_sqs::types::QueueAttributeName;
+use common::{TestServer, storage_matrix};
+use serde_json::Value;
+
+async fn clone(server: &TestServer, queue: &str, body: &str) -> (u16, Value) {
+    let (status, body) = server.admin("POST", &format!("/queues/{}/clone", queue), body).await;
+    (status, serde_json::from_str(&body).unwrap_or(Value::String(body)))
+}
+
+async fn peek(server: &TestServer, queue: &str) -> Vec<Value> {
+    let (status, body) = server.admin("GET", &format!("/queues/{}/messages", queue), "").await;
+    assert_eq!(status, 200);
+    serde_json::from_str(&body).unwrap()
+}
+
+/// Creates `orders` holding three messages, the first of them in flight.
+async fn setup(server: &TestServer) -> String {
+    let queue_url = server
+        .client
+        .create_queue()
+        .queue_name("orders")
+        .attributes(QueueAttributeName::VisibilityTimeout, "45")
+        .tags("team", "payments")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    for body in ["first", "second", "third"] {
+        server
+            .client
+            .send_message()
+            .queue_url(&queue_url)
+            .message_body(body)
+            .send()
+            .await
+            .unwrap();
+    }
+    server.client.receive_message().queue_url(&queue_url).send().await.unwrap();
+    queue_url
+}
+
+storage_matrix!(clones_copy_attributes_tags_and_optionally_messages);
+async fn clones_copy_attributes_tags_and_optionally_messages() {
+    let server = TestServer::start().await;
+    setup(&server).await;
+
+    let (status, cloned) = clone(&server, "orders", r#"{"name": "orders-empty"}"#).await;
+    assert_eq!(status, 200, "{}", cloned);
+    assert_eq!(cloned["messages_copied"], 0);
+    let queue_url = cloned["queue_url"].as_str().unwrap();
+    assert!(queue_url.ends_with("/orders-empty"));
+    let attributes = server
+        .client
+        .get_queue_attributes()
+        .queue_url(queue_url)
+        .attribute_names(QueueAttributeName::VisibilityTimeout)
+        .send()
+        .await
+        .unwrap()
+        .attributes
+        .unwrap();
+    assert_eq!(attributes[&QueueAttributeName::VisibilityTimeout], "45");
+    let tags = server.client.list_queue_tags().queue_url(queue_url).send().await.unwrap();
+    assert_eq!(tags.tags.unwrap()["team"], "payments");
+    assert!(peek(&server, "orders-empty").await.is_empty());
+
+    let body = r#"{"name": "orders-copy", "messages": true}"#;
+    let (status, cloned) = clone(&server, "orders", body).await;
+    assert_eq!(status, 200, "{}", cloned);
+    assert_eq!(cloned["messages_copied"], 3);
+    let originals = peek(&server, "orders").await;
+    let copies = peek(&server, "orders-copy").await;
+    let bodies: Vec<&str> = copies.iter().map(|m| m["body"].as_str().unwrap()).collect();
+    assert_eq!(bodies, ["first", "second", "third"]);
+    for (original, copy) in originals.iter().zip(&copies) {
+        assert_ne!(copy["id"], original["id"]);
+        assert_eq!(copy["receive_count"], original["receive_count"]);
+        assert_eq!(copy["visible_from"], original["visible_from"]);
+    }
+    // The in-flight copy is in flight under a receipt handle of its own.
+    assert!(copies[0]["receipt_handle"].is_string());
+    assert_ne!(copies[0]["receipt_handle"], originals[0]["receipt_handle"]);
+    let received = server
+        .client
+        .receive_message()
+        .queue_url(cloned["queue_url"].as_str().unwrap())
+        .max_number_of_messages(10)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(received.messages().len(), 2);
+
+    let body = r#"{
+        "name": "orders-reset",
+        "messages": true,
+        "preserve_ids": true,
+        "reset_receive_counts": true,
+        "reset_visibility": true
+    }"#;
+    let (status, _) = clone(&server, "orders", body).await;
+    assert_eq!(status, 200);
+    let copies = peek(&server, "orders-reset").await;
+    for (original, copy) in originals.iter().zip(&copies) {
+        assert_eq!(copy["id"], original["id"]);
+        assert_eq!(copy["receive_count"], 0);
+        assert_eq!(copy["receipt_handle"], Value::Null);
+    }
+
+    // The source is untouched.
+    assert_eq!(peek(&server, "orders").await, originals);
+}
+
+storage_matrix!(clones_are_validated_like_new_queues);
+async fn clones_are_validated_like_new_queues() {
+    let server = TestServer::start().await;
+    setup(&server).await;
+    server.create_queue("taken").await;
+
+    let (status, body) = clone(&server, "orders", r#"{"name": "no spaces allowed"}"#).await;
+    assert_eq!(status, 400);
+    assert!(body.to_string().contains("InvalidParameterValue"), "{}", body);
+    let (status, body) = clone(&server, "orders", r#"{"name": "taken"}"#).await;
+    assert_eq!(status, 400);
+    assert!(body.to_string().contains("QueueNameExists"), "{}", body);
+    let (status, _) = clone(&server, "missing", r#"{"name": "orders-copy"}"#).await;
+    assert_eq!(status, 400);
+    let (status, _) = clone(&server, "orders", r#"{"messages": true}"#).await;
+    assert_eq!(status, 400);
+
+    server
+        .client
+        .create_queue()
+        .queue_name("orders.fifo")
+        .attributes(QueueAttributeName::FifoQueue, "true")
+        .send()
+        .await
+        .unwrap();
+    let (status, cloned) = clone(&server, "orders.fifo", r#"{"name": "orders-copy.fifo"}"#).await;
+    assert_eq!(status, 200);
+    let attributes = server
+        .client
+        .get_queue_attributes()
+        .queue_url(cloned["queue_url"].as_str().unwrap())
+        .attribute_names(QueueAttributeName::FifoQueue)
+        .send()
+        .await
+        .unwrap()
+        .attributes
+        .unwrap();
+    assert_eq!(attributes[&QueueAttributeName::FifoQueue], "true");
+
+    let queues = server.client.list_queues().send().await.unwrap();
+    assert_eq!(queues.queue_urls().len(), 4);
+}
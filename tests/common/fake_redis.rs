@@ -0,0 +1,380 @@
+//! Just enough of a Redis server, in process, to run the Redis store
+//! against: hashes, sets, sorted sets, and `MULTI`/`EXEC` transactions
+//! guarded by `WATCH`.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug)]
+enum Value {
+    Hash(HashMap<String, String>),
+    Set(HashSet<String>),
+    SortedSet(HashMap<String, f64>),
+}
+
+#[derive(Debug, Default)]
+struct Data {
+    keys: HashMap<String, Value>,
+    /// When each key was last written, by [`clock`](Self::clock), for
+    /// `WATCH` to tell whether it has changed since.
+    versions: HashMap<String, u64>,
+    clock: u64,
+}
+
+impl Data {
+    fn version(&self, key: &str) -> u64 {
+        self.versions.get(key).copied().unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    data: Mutex<Data>,
+    connections: Mutex<Vec<TcpStream>>,
+    stopped: AtomicBool,
+}
+
+/// A fake Redis server on an ephemeral port, served from threads of its own
+/// so that it answers however busy the test's runtime is. It keeps
+/// its data until dropped, across [`stop`](Self::stop) and
+/// [`resume`](Self::resume).
+pub struct FakeRedis {
+    pub addr: SocketAddr,
+    shared: Arc<Shared>,
+}
+
+impl FakeRedis {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shared = Arc::new(Shared::default());
+        let accepting = shared.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                if Arc::strong_count(&accepting) == 1 {
+                    return;
+                }
+                if accepting.stopped.load(Ordering::SeqCst) {
+                    continue;
+                }
+                stream.set_nodelay(true).unwrap();
+                accepting.connections.lock().unwrap().push(stream.try_clone().unwrap());
+                let shared = accepting.clone();
+                std::thread::spawn(move || serve(&shared, stream));
+            }
+        });
+        Self { addr, shared }
+    }
+
+    pub fn url(&self) -> String {
+        format!("redis://{}/", self.addr)
+    }
+
+    /// Drops every connection and refuses new ones, as if Redis went down.
+    pub fn stop(&self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        for connection in self.shared.connections.lock().unwrap().drain(..) {
+            connection.shutdown(Shutdown::Both).ok();
+        }
+    }
+
+    /// Accepts connections again after [`stop`](Self::stop).
+    pub fn resume(&self) {
+        self.shared.stopped.store(false, Ordering::SeqCst);
+    }
+
+    /// The number of fields in the hash at `key`.
+    pub fn hash_len(&self, key: &str) -> usize {
+        match self.shared.data.lock().unwrap().keys.get(key) {
+            Some(Value::Hash(hash)) => hash.len(),
+            _ => 0,
+        }
+    }
+
+    /// The scores in the sorted set at `key`, lowest first.
+    pub fn scores(&self, key: &str) -> Vec<f64> {
+        let mut scores: Vec<f64> = match self.shared.data.lock().unwrap().keys.get(key) {
+            Some(Value::SortedSet(set)) => set.values().copied().collect(),
+            _ => Vec::new(),
+        };
+        scores.sort_by(f64::total_cmp);
+        scores
+    }
+}
+
+impl Drop for FakeRedis {
+    fn drop(&mut self) {
+        self.stop();
+        // Wake the accept loop so it sees it's the last one holding on.
+        TcpStream::connect(self.addr).ok();
+    }
+}
+
+enum Reply {
+    Ok,
+    Int(i64),
+    Bulk(Option<String>),
+    Array(Vec<Reply>),
+    Error(String),
+    Status(&'static str),
+}
+
+impl Reply {
+    fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            Reply::Ok => out.extend(b"+OK\r\n"),
+            Reply::Status(status) => out.extend(format!("+{}\r\n", status).as_bytes()),
+            Reply::Int(n) => out.extend(format!(":{}\r\n", n).as_bytes()),
+            Reply::Bulk(None) => out.extend(b"$-1\r\n"),
+            Reply::Bulk(Some(s)) => {
+                out.extend(format!("${}\r\n", s.len()).as_bytes());
+                out.extend(s.as_bytes());
+                out.extend(b"\r\n");
+            }
+            Reply::Array(items) => {
+                out.extend(format!("*{}\r\n", items.len()).as_bytes());
+                for item in items {
+                    item.write(out);
+                }
+            }
+            Reply::Error(e) => out.extend(format!("-{}\r\n", e).as_bytes()),
+        }
+    }
+}
+
+fn serve(shared: &Shared, stream: TcpStream) {
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+    let mut queued: Option<Vec<Vec<String>>> = None;
+    // The keys this connection watches, and their versions when it began to.
+    let mut watched: Vec<(String, u64)> = Vec::new();
+    while let Some(command) = read_command(&mut reader) {
+        let name = command[0].to_ascii_uppercase();
+        let reply = match (name.as_str(), &mut queued) {
+            ("WATCH", None) => {
+                let data = shared.data.lock().unwrap();
+                watched.extend(command[1..].iter().map(|key| (key.clone(), data.version(key))));
+                Reply::Ok
+            }
+            ("UNWATCH", None) => {
+                watched.clear();
+                Reply::Ok
+            }
+            ("MULTI", _) => {
+                queued = Some(Vec::new());
+                Reply::Ok
+            }
+            ("EXEC", Some(_)) => {
+                let commands = queued.take().unwrap();
+                let mut data = shared.data.lock().unwrap();
+                let changed = watched.drain(..).any(|(key, version)| data.version(&key) != version);
+                if changed {
+                    Reply::Bulk(None)
+                } else {
+                    Reply::Array(commands.iter().map(|c| run(&mut data, c)).collect())
+                }
+            }
+            ("DISCARD", _) => {
+                queued = None;
+                watched.clear();
+                Reply::Ok
+            }
+            (_, Some(commands)) => {
+                commands.push(command);
+                Reply::Status("QUEUED")
+            }
+            (_, None) => run(&mut shared.data.lock().unwrap(), &command),
+        };
+        let mut out = Vec::new();
+        reply.write(&mut out);
+        if writer.write_all(&out).is_err() {
+            return;
+        }
+    }
+}
+
+fn read_command(reader: &mut impl BufRead) -> Option<Vec<String>> {
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let count: usize = line.trim_end().strip_prefix('*')?.parse().ok()?;
+    let mut args = Vec::with_capacity(count);
+    for _ in 0..count {
+        line.clear();
+        reader.read_line(&mut line).ok()?;
+        let len: usize = line.trim_end().strip_prefix('$')?.parse().ok()?;
+        let mut arg = vec![0; len + 2];
+        reader.read_exact(&mut arg).ok()?;
+        arg.truncate(len);
+        args.push(String::from_utf8(arg).ok()?);
+    }
+    Some(args)
+}
+
+fn run(data: &mut Data, command: &[String]) -> Reply {
+    let name = command[0].to_ascii_uppercase();
+    let args = &command[1..];
+    let writes = match name.as_str() {
+        "DEL" => args,
+        "SADD" | "SREM" | "HSET" | "HMSET" | "HDEL" | "ZADD" | "ZREM" => &args[..1],
+        _ => &[],
+    };
+    data.clock += 1;
+    for key in writes {
+        data.versions.insert(key.clone(), data.clock);
+    }
+    let keys = &mut data.keys;
+    let wrong_type = || Reply::Error("WRONGTYPE wrong kind of value".to_string());
+    macro_rules! entry {
+        ($variant:ident) => {
+            match keys
+                .entry(args[0].clone())
+                .or_insert_with(|| Value::$variant(Default::default()))
+            {
+                Value::$variant(value) => value,
+                _ => return wrong_type(),
+            }
+        };
+    }
+    let bulk = |value: Option<&String>| Reply::Bulk(value.cloned());
+    let reply = match name.as_str() {
+        "CLIENT" | "SELECT" => Reply::Ok,
+        "PING" => Reply::Status("PONG"),
+        "DEL" => Reply::Int(args.iter().filter(|key| keys.remove(*key).is_some()).count() as i64),
+        "EXISTS" => Reply::Int(args.iter().filter(|key| keys.contains_key(*key)).count() as i64),
+        "SADD" => {
+            let set = entry!(Set);
+            Reply::Int(args[1..].iter().filter(|m| set.insert(m.to_string())).count() as i64)
+        }
+        "SREM" => {
+            let set = entry!(Set);
+            Reply::Int(args[1..].iter().filter(|m| set.remove(*m)).count() as i64)
+        }
+        "SMEMBERS" => {
+            let set = entry!(Set);
+            Reply::Array(set.iter().map(|m| Reply::Bulk(Some(m.clone()))).collect())
+        }
+        "HSET" | "HMSET" => {
+            let hash = entry!(Hash);
+            let added = args[1..]
+                .chunks(2)
+                .filter(|pair| hash.insert(pair[0].clone(), pair[1].clone()).is_none())
+                .count();
+            if name == "HMSET" { Reply::Ok } else { Reply::Int(added as i64) }
+        }
+        "HDEL" => {
+            let hash = entry!(Hash);
+            Reply::Int(args[1..].iter().filter(|f| hash.remove(*f).is_some()).count() as i64)
+        }
+        "HGET" => {
+            let hash = entry!(Hash);
+            bulk(hash.get(&args[1]))
+        }
+        "HMGET" => {
+            let hash = entry!(Hash);
+            Reply::Array(args[1..].iter().map(|f| bulk(hash.get(f))).collect())
+        }
+        "HLEN" => {
+            let hash = entry!(Hash);
+            Reply::Int(hash.len() as i64)
+        }
+        "HGETALL" => {
+            let hash = entry!(Hash);
+            let fields = hash
+                .iter()
+                .flat_map(|(f, v)| [Reply::Bulk(Some(f.clone())), Reply::Bulk(Some(v.clone()))]);
+            Reply::Array(fields.collect())
+        }
+        "ZADD" => {
+            let set = entry!(SortedSet);
+            let added = args[1..]
+                .chunks(2)
+                .filter(|pair| set.insert(pair[1].clone(), pair[0].parse().unwrap()).is_none())
+                .count();
+            Reply::Int(added as i64)
+        }
+        "ZREM" => {
+            let set = entry!(SortedSet);
+            Reply::Int(args[1..].iter().filter(|m| set.remove(*m).is_some()).count() as i64)
+        }
+        "ZCARD" => {
+            let set = entry!(SortedSet);
+            Reply::Int(set.len() as i64)
+        }
+        "ZSCORE" => {
+            let set = entry!(SortedSet);
+            bulk(set.get(&args[1]).map(|score| score.to_string()).as_ref())
+        }
+        "ZRANGE" => {
+            let members = ranked(entry!(SortedSet));
+            let len = members.len() as i64;
+            let index = |arg: &String| {
+                let i: i64 = arg.parse().unwrap();
+                if i < 0 { len + i } else { i }
+            };
+            let (start, stop) = (index(&args[1]).max(0), index(&args[2]).min(len - 1));
+            let members = if start > stop { &[][..] } else { &members[start as usize..=stop as usize] };
+            Reply::Array(members.iter().map(|(m, _)| bulk(Some(m))).collect())
+        }
+        "ZRANGEBYSCORE" | "ZCOUNT" => {
+            let members = ranked(entry!(SortedSet));
+            let (min, max) = (Bound::parse(&args[1]), Bound::parse(&args[2]));
+            let within =
+                members.into_iter().filter(|(_, score)| min.below(*score) && max.above(*score));
+            match name.as_str() {
+                "ZCOUNT" => Reply::Int(within.count() as i64),
+                _ => Reply::Array(within.map(|(m, _)| bulk(Some(&m))).collect()),
+            }
+        }
+        _ => Reply::Error(format!("ERR unknown command '{}'", command[0])),
+    };
+    // Like Redis, drop collections once they're empty.
+    keys.retain(|_, value| match value {
+        Value::Hash(hash) => !hash.is_empty(),
+        Value::Set(set) => !set.is_empty(),
+        Value::SortedSet(set) => !set.is_empty(),
+    });
+    reply
+}
+
+/// The members of a sorted set in order: by score, then by member.
+fn ranked(set: &HashMap<String, f64>) -> Vec<(String, f64)> {
+    let mut members: Vec<(String, f64)> = set.iter().map(|(m, s)| (m.clone(), *s)).collect();
+    members.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+    members
+}
+
+/// One end of a `ZRANGEBYSCORE` or `ZCOUNT` range: a score, or `(` and a
+/// score to leave it out.
+struct Bound {
+    score: f64,
+    exclusive: bool,
+}
+
+impl Bound {
+    fn parse(arg: &str) -> Self {
+        let (exclusive, score) = match arg.strip_prefix('(') {
+            Some(score) => (true, score),
+            None => (false, arg),
+        };
+        let score = match score {
+            "-inf" => f64::NEG_INFINITY,
+            "+inf" | "inf" => f64::INFINITY,
+            score => score.parse().unwrap(),
+        };
+        Self { score, exclusive }
+    }
+
+    /// Whether `score` is at or past this bound, as a minimum.
+    fn below(&self, score: f64) -> bool {
+        if self.exclusive { self.score < score } else { self.score <= score }
+    }
+
+    /// Whether `score` is at or short of this bound, as a maximum.
+    fn above(&self, score: f64) -> bool {
+        if self.exclusive { score < self.score } else { score <= self.score }
+    }
+}
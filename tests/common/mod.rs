@@ -0,0 +1,280 @@
+#![allow(dead_code)]
+
+pub mod fake_redis;
+
+use aws_sdk_sqs::Client;
+use aws_sdk_sqs::config::{Credentials, Region};
+use aws_sdk_sqs::types::{Message, MessageSystemAttributeName};
+use fake_redis::FakeRedis;
+use local_sqs::store::Storage;
+use local_sqs::{Config, ShutdownHandle};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+
+tokio::task_local! {
+    /// The store that servers started by the running test keep their queues
+    /// in; see [`storage_matrix!`].
+    static STORAGE: Storage;
+}
+
+/// Runs `test` with the servers it starts keeping their queues in
+/// `storage`, unless their config picks a store.
+pub async fn with_storage<F: Future>(storage: Storage, test: F) -> F::Output {
+    STORAGE.scope(storage, test).await
+}
+
+/// Turns the `async fn` named `$test` into a test against each store,
+/// `$test::memory`, `$test::sqlite` and `$test::redis`, so that every store
+/// is held to the same behaviour. Anything after the name is passed on to
+/// `#[tokio::test]`.
+#[allow(unused_macros)]
+macro_rules! storage_matrix {
+    ($test:ident $(, $($args:tt)+)?) => {
+        mod $test {
+            use local_sqs::store::Storage;
+
+            #[tokio::test$(($($args)+))?]
+            async fn memory() {
+                $crate::common::with_storage(Storage::Memory, super::$test()).await
+            }
+
+            #[tokio::test$(($($args)+))?]
+            async fn sqlite() {
+                $crate::common::with_storage(Storage::Sqlite, super::$test()).await
+            }
+
+            #[tokio::test$(($($args)+))?]
+            async fn redis() {
+                $crate::common::with_storage(Storage::Redis, super::$test()).await
+            }
+        }
+    };
+}
+#[allow(unused_imports)]
+pub(crate) use storage_matrix;
+
+/// A server running in-process on an ephemeral port, with an SDK client
+/// pointed at it. The server is stopped when this is dropped.
+///
+/// Servers that would keep their queues in memory use a fresh SQLite
+/// database or [`FakeRedis`] instead when the test runs against that store;
+/// see [`storage_matrix!`].
+pub struct TestServer {
+    pub addr: SocketAddr,
+    pub client: Client,
+    shutdown: ShutdownHandle,
+    server: Option<JoinHandle<()>>,
+    /// A database created for this server, removed along with it.
+    scratch_db: Option<PathBuf>,
+    /// A Redis server started for this server, stopped along with it.
+    scratch_redis: Option<FakeRedis>,
+}
+
+impl TestServer {
+    pub async fn start() -> Self {
+        Self::start_with(Config::default()).await
+    }
+
+    pub async fn start_with(mut config: Config) -> Self {
+        config.host = "127.0.0.1".to_string();
+        config.port = 0;
+        let mut scratch_db = None;
+        let mut scratch_redis = None;
+        if config.storage == Storage::Memory {
+            match STORAGE.try_with(|storage| *storage).unwrap_or_default() {
+                Storage::Memory => {}
+                Storage::Sqlite => {
+                    let path = scratch_db_path();
+                    config.storage = Storage::Sqlite;
+                    config.db_path = path.clone();
+                    scratch_db = Some(path);
+                }
+                Storage::Redis => {
+                    let redis = FakeRedis::start();
+                    config.storage = Storage::Redis;
+                    config.redis_url = redis.url();
+                    scratch_redis = Some(redis);
+                }
+            }
+        }
+        let (addrs, server, shutdown) = local_sqs::serve(config).await.unwrap();
+        let addr = addrs[0];
+
+        let sdk_config = aws_sdk_sqs::Config::builder()
+            .endpoint_url(format!("http://{}", addr))
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+            .build();
+
+        Self {
+            addr,
+            client: Client::from_conf(sdk_config),
+            shutdown,
+            server: Some(server),
+            scratch_db,
+            scratch_redis,
+        }
+    }
+
+    /// Stops the server and waits until it has.
+    pub async fn stop(mut self) {
+        self.shutdown.shutdown();
+        if let Some(server) = self.server.take() {
+            server.await.unwrap();
+        }
+    }
+
+    pub async fn create_queue(&self, name: &str) -> String {
+        self.client
+            .create_queue()
+            .queue_name(name)
+            .send()
+            .await
+            .unwrap()
+            .queue_url
+            .unwrap()
+    }
+
+    /// Sends `body` to `queue_url`, returning the message ID.
+    pub async fn send(&self, queue_url: &str, body: &str) -> String {
+        self.client
+            .send_message()
+            .queue_url(queue_url)
+            .message_body(body)
+            .send()
+            .await
+            .unwrap()
+            .message_id
+            .unwrap()
+    }
+
+    /// Receives up to ten messages from `queue_url`, with all their system
+    /// attributes, hidden for `visibility_timeout` seconds or the queue's
+    /// default.
+    pub async fn receive(&self, queue_url: &str, visibility_timeout: Option<i32>) -> Vec<Message> {
+        self.client
+            .receive_message()
+            .queue_url(queue_url)
+            .max_number_of_messages(10)
+            .set_visibility_timeout(visibility_timeout)
+            .message_system_attribute_names(MessageSystemAttributeName::All)
+            .send()
+            .await
+            .unwrap()
+            .messages
+            .unwrap_or_default()
+    }
+
+    /// Moves the server's manual clock `seconds` ahead.
+    pub async fn advance_clock(&self, seconds: u64) {
+        let body = format!(r#"{{"seconds": {}}}"#, seconds);
+        let (status, body) = self.admin("POST", "/clock/advance", &body).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+
+    /// The names of the server's queues, sorted.
+    pub async fn queue_names(&self) -> Vec<String> {
+        let queues = self.client.list_queues().send().await.unwrap();
+        let mut names: Vec<String> = queues
+            .queue_urls()
+            .iter()
+            .map(|url| url.rsplit('/').next().unwrap().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Sends a plain HTTP request to the admin API, returning the status code
+    /// and body.
+    pub async fn admin(&self, method: &str, path: &str, body: &str) -> (u16, String) {
+        let (status, _, body) = self.admin_with_headers(method, path, body).await;
+        (status, body)
+    }
+
+    /// Posts a raw JSON-protocol request for `target` (e.g.
+    /// `AmazonSQS.SendMessage`), bypassing the SDK's validation.
+    pub async fn action(&self, target: &str, body: &str) -> (u16, String) {
+        let (status, _, body) = self.action_with_headers(target, body).await;
+        (status, body)
+    }
+
+    /// Like [`action`](Self::action), but also returning the response
+    /// headers, keyed by lowercase name.
+    pub async fn action_with_headers(
+        &self,
+        target: &str,
+        body: &str,
+    ) -> (u16, HashMap<String, String>, String) {
+        let headers = format!("X-Amz-Target: {}\r\n", target);
+        self.raw("POST", "/", &headers, body).await
+    }
+
+    /// Like [`admin`](Self::admin), but also returning the response headers,
+    /// keyed by lowercase name.
+    pub async fn admin_with_headers(
+        &self,
+        method: &str,
+        path: &str,
+        body: &str,
+    ) -> (u16, HashMap<String, String>, String) {
+        self.raw(method, &format!("/_admin{}", path), "", body).await
+    }
+
+    async fn raw(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &str,
+        body: &str,
+    ) -> (u16, HashMap<String, String>, String) {
+        let mut stream = TcpStream::connect(self.addr).await.unwrap();
+        let request = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            method,
+            path,
+            self.addr,
+            headers,
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let mut lines = head.lines();
+        let status = lines.next().unwrap().split(' ').nth(1).unwrap().parse().unwrap();
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.to_ascii_lowercase(), value.trim().to_string()))
+            .collect();
+        (status, headers, body.to_string())
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.shutdown.shutdown();
+        if let Some(path) = &self.scratch_db {
+            remove_db(path);
+        }
+    }
+}
+
+/// A path in the temporary directory for a database no other test uses.
+pub fn scratch_db_path() -> PathBuf {
+    std::env::temp_dir().join(format!("local-sqs-test-{}.db", uuid::Uuid::new_v4()))
+}
+
+/// Removes the SQLite database at `path` along with its journal files.
+pub fn remove_db(path: &std::path::Path) {
+    for suffix in ["", "-wal", "-shm"] {
+        let mut file = path.as_os_str().to_owned();
+        file.push(suffix);
+        std::fs::remove_file(file).ok();
+    }
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use common::{TestServer, storage_matrix};
+use local_sqs::Config;
+use serde_json::Value;
+
+/// A compressible JSON body of about 200 KB.
+fn blob() -> String {
+    let items: Vec<Value> = (0..4000)
+        .map(|i| serde_json::json!({ "id": i, "status": "pending", "tags": ["a", "b"] }))
+        .collect();
+    serde_json::to_string(&items).unwrap()
+}
+
+async fn stats(server: &TestServer, queue: &str) -> Value {
+    let (status, body) = server
+        .admin("GET", &format!("/queues/{}/stats", queue), "")
+        .await;
+    assert_eq!(status, 200, "{}", body);
+    serde_json::from_str(&body).unwrap()
+}
+
+storage_matrix!(large_bodies_are_compressed_at_rest);
+async fn large_bodies_are_compressed_at_rest() {
+    let server = TestServer::start_with(Config {
+        compress_bodies_above: Some(1024),
+        ..Default::default()
+    })
+    .await;
+    let queue_url = server.create_queue("blobs").await;
+    let blob = blob();
+    for body in [blob.as_str(), "small"] {
+        let sent = server
+            .client
+            .send_message()
+            .queue_url(&queue_url)
+            .message_body(body)
+            .send()
+            .await
+            .unwrap();
+        let md5 = format!("{:x}", md5::compute(body.as_bytes()));
+        assert_eq!(sent.md5_of_message_body(), Some(md5.as_str()));
+    }
+
+    let stats = stats(&server, "blobs").await;
+    let logical = stats["logical_bytes"].as_u64().unwrap();
+    let stored = stats["stored_bytes"].as_u64().unwrap();
+    assert_eq!(logical, blob.len() as u64 + 5);
+    assert!(stored < logical / 4, "{} of {} bytes stored", stored, logical);
+
+    // Peeks and exports see the bodies as sent.
+    let (_, peeked) = server.admin("GET", "/queues/blobs/messages", "").await;
+    let peeked: Value = serde_json::from_str(&peeked).unwrap();
+    assert_eq!(peeked[0]["body"], blob.as_str());
+    let (_, exported) = server.admin("GET", "/export", "").await;
+    let exported: Value = serde_json::from_str(&exported).unwrap();
+    assert_eq!(exported["queues"][0]["messages"][0]["body"], blob.as_str());
+
+    let received = server
+        .client
+        .receive_message()
+        .queue_url(&queue_url)
+        .max_number_of_messages(10)
+        .send()
+        .await
+        .unwrap();
+    let messages = received.messages();
+    assert_eq!(messages[0].body(), Some(blob.as_str()));
+    let md5 = format!("{:x}", md5::compute(blob.as_bytes()));
+    assert_eq!(messages[0].md5_of_body(), Some(md5.as_str()));
+    assert_eq!(messages[1].body(), Some("small"));
+}
+
+storage_matrix!(bodies_are_stored_as_sent_by_default);
+async fn bodies_are_stored_as_sent_by_default() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("plain").await;
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body(blob())
+        .send()
+        .await
+        .unwrap();
+
+    let stats = stats(&server, "plain").await;
+    assert_eq!(stats["stored_bytes"], stats["logical_bytes"]);
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use aws_sdk_sqs::types::SendMessageBatchRequestEntry;
+use common::{TestServer, storage_matrix};
+use std::collections::HashSet;
+
+const MESSAGES: usize = 1000;
+const RECEIVERS: usize = 10;
+
+storage_matrix!(
+    concurrent_receivers_get_each_message_once,
+    flavor = "multi_thread",
+    worker_threads = 4
+);
+async fn concurrent_receivers_get_each_message_once() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("contended").await;
+    for batch in 0..MESSAGES / 10 {
+        let mut request = server.client.send_message_batch().queue_url(&queue_url);
+        for i in 0..10 {
+            request = request.entries(
+                SendMessageBatchRequestEntry::builder()
+                    .id(format!("m{}", i))
+                    .message_body(format!("{}", batch * 10 + i))
+                    .build()
+                    .unwrap(),
+            );
+        }
+        let response = request.send().await.unwrap();
+        assert!(response.failed.is_empty(), "{:?}", response.failed);
+    }
+
+    // The visibility timeout outlasts the test, so a message received twice
+    // was claimed twice.
+    let receivers: Vec<_> = (0..RECEIVERS)
+        .map(|_| {
+            let client = server.client.clone();
+            let queue_url = queue_url.clone();
+            tokio::spawn(async move {
+                let mut bodies = Vec::new();
+                loop {
+                    let messages = client
+                        .receive_message()
+                        .queue_url(&queue_url)
+                        .max_number_of_messages(10)
+                        .visibility_timeout(600)
+                        .send()
+                        .await
+                        .unwrap()
+                        .messages
+                        .unwrap_or_default();
+                    if messages.is_empty() {
+                        return bodies;
+                    }
+                    bodies.extend(messages.into_iter().map(|m| m.body.unwrap()));
+                }
+            })
+        })
+        .collect();
+
+    let mut delivered = Vec::new();
+    for receiver in receivers {
+        delivered.extend(receiver.await.unwrap());
+    }
+    let distinct: HashSet<&String> = delivered.iter().collect();
+    assert_eq!(distinct.len(), MESSAGES);
+    assert_eq!(delivered.len(), MESSAGES);
+}
@@ -0,0 +1,263 @@
+mod common;
+
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::Json;
+use common::{TestServer, storage_matrix};
+use local_sqs::dispatch::{self, Registry};
+use local_sqs::error::SqsError;
+use local_sqs::{AppState, Config};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Deserialize)]
+struct EchoRequest {
+    text: String,
+}
+
+#[derive(Serialize)]
+struct EchoResponse {
+    text: String,
+}
+
+async fn echo(
+    State(_): State<AppState>,
+    Json(request): Json<EchoRequest>,
+) -> Result<EchoResponse, SqsError> {
+    if request.text.is_empty() {
+        return Err(SqsError::InvalidParameterValue("Nothing to echo.".to_string()));
+    }
+    Ok(EchoResponse { text: request.text })
+}
+
+async fn run(registry: &Registry, action: &str, body: &str) -> (u16, Value) {
+    let state = AppState::new(&Config::default());
+    let response = registry
+        .dispatch(state, action, &HeaderMap::new(), body)
+        .await;
+    let status = response.status().as_u16();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[tokio::test]
+async fn registered_actions_share_parsing_and_encoding() {
+    let mut registry = Registry::default();
+    registry.register("Echo", echo);
+
+    let (status, body) = run(&registry, "Echo", r#"{"text": "hi"}"#).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["text"], "hi");
+
+    let (status, body) = run(&registry, "Echo", r#"{"text": ""}"#).await;
+    assert_eq!(status, 400);
+    assert_eq!(body["__type"], "com.amazonaws.sqs#InvalidParameterValue");
+
+    let (status, body) = run(&registry, "Echo", "not json").await;
+    assert_eq!(status, 400);
+    assert_eq!(body["__type"], "com.amazonaws.sqs#InvalidParameterValue");
+
+    let (status, body) = run(&registry, "Shout", "{}").await;
+    assert_eq!(status, 400);
+    assert_eq!(body["__type"], "com.amazonaws.sqs#InvalidAction");
+}
+
+#[tokio::test]
+async fn every_implemented_action_is_registered() {
+    let actions: Vec<_> = dispatch::sqs_actions().actions().collect();
+    assert_eq!(actions.len(), 22);
+    for action in ["CreateQueue", "SendMessageBatch", "CancelMessageMoveTask", "UntagQueue"] {
+        assert!(actions.contains(&action), "{} is missing", action);
+    }
+}
+
+storage_matrix!(malformed_requests_get_an_error_instead_of_a_dropped_connection);
+async fn malformed_requests_get_an_error_instead_of_a_dropped_connection() {
+    let server = TestServer::start().await;
+
+    let (status, body) = server.action("AmazonSQS.CreateQueue", "{").await;
+    assert_eq!(status, 400);
+    assert!(body.contains("InvalidParameterValue"), "{}", body);
+
+    let (status, body) = server
+        .action("AmazonSQS.SendMessage", r#"{"QueueUrl": "http://x/1/q"}"#)
+        .await;
+    assert_eq!(status, 400);
+    assert!(body.contains("MissingParameter"), "{}", body);
+    assert!(body.contains("MessageBody"), "{}", body);
+
+    let (status, body) = server.action("AmazonSQS.Explode", "{}").await;
+    assert_eq!(status, 400);
+    assert!(body.contains("InvalidAction"), "{}", body);
+}
+
+storage_matrix!(omitted_required_fields_are_missing_parameters);
+async fn omitted_required_fields_are_missing_parameters() {
+    let server = TestServer::start().await;
+    let url = server.create_queue("required").await;
+    let q = format!(r#""QueueUrl": "{}""#, url);
+    let cases = [
+        ("CreateQueue", "{}".to_string(), "QueueName"),
+        ("GetQueueUrl", "{}".to_string(), "QueueName"),
+        ("DeleteQueue", "{}".to_string(), "QueueUrl"),
+        ("PurgeQueue", "{}".to_string(), "QueueUrl"),
+        ("GetQueueAttributes", "{}".to_string(), "QueueUrl"),
+        ("SetQueueAttributes", format!("{{{}}}", q), "Attributes"),
+        ("SendMessage", format!("{{{}}}", q), "MessageBody"),
+        ("SendMessage", format!(r#"{{{}, "MessageBody": ""}}"#, q), "MessageBody"),
+        ("SendMessage", r#"{"MessageBody": "hi"}"#.to_string(), "QueueUrl"),
+        ("ReceiveMessage", "{}".to_string(), "QueueUrl"),
+        ("DeleteMessage", format!("{{{}}}", q), "ReceiptHandle"),
+        (
+            "ChangeMessageVisibility",
+            format!(r#"{{{}, "ReceiptHandle": "h"}}"#, q),
+            "VisibilityTimeout",
+        ),
+        ("SendMessageBatch", format!("{{{}}}", q), "Entries"),
+        (
+            "SendMessageBatch",
+            format!(r#"{{{}, "Entries": [{{"Id": "a"}}]}}"#, q),
+            "MessageBody",
+        ),
+        ("DeleteMessageBatch", format!("{{{}}}", q), "Entries"),
+        (
+            "DeleteMessageBatch",
+            format!(r#"{{{}, "Entries": [{{"Id": "a"}}]}}"#, q),
+            "ReceiptHandle",
+        ),
+        ("ChangeMessageVisibilityBatch", format!("{{{}}}", q), "Entries"),
+        (
+            "ChangeMessageVisibilityBatch",
+            format!(r#"{{{}, "Entries": [{{"Id": "a", "ReceiptHandle": "h"}}]}}"#, q),
+            "VisibilityTimeout",
+        ),
+        (
+            "AddPermission",
+            format!(r#"{{{}, "Label": "l", "AWSAccountIds": ["1"]}}"#, q),
+            "Actions",
+        ),
+        ("RemovePermission", format!("{{{}}}", q), "Label"),
+        ("StartMessageMoveTask", "{}".to_string(), "SourceArn"),
+        ("ListMessageMoveTasks", "{}".to_string(), "SourceArn"),
+        ("CancelMessageMoveTask", "{}".to_string(), "TaskHandle"),
+        ("ListQueueTags", "{}".to_string(), "QueueUrl"),
+        ("TagQueue", format!("{{{}}}", q), "Tags"),
+        ("UntagQueue", format!("{{{}}}", q), "TagKeys"),
+    ];
+
+    for (action, body, field) in cases {
+        let (status, response) = server.action(&format!("AmazonSQS.{}", action), &body).await;
+        assert_eq!(status, 400, "{}: {}", action, response);
+        let error: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(error["__type"], "com.amazonaws.sqs#MissingParameter", "{}", action);
+        let message = format!("The request must contain the parameter {}.", field);
+        assert_eq!(error["message"], message, "{}", action);
+    }
+    // A batch entry with an empty body fails on its own.
+    let body = format!(r#"{{{}, "Entries": [{{"Id": "a", "MessageBody": ""}}]}}"#, q);
+    let (status, response) = server.action("AmazonSQS.SendMessageBatch", &body).await;
+    assert_eq!(status, 200, "{}", response);
+    let response: Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["Failed"][0]["Code"], "MissingParameter", "{}", response);
+
+    // Nothing was half-applied along the way.
+    let (_, body) = server.admin("GET", "/queues/required/stats", "").await;
+    let stats: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(stats["messages"], 0);
+}
+
+storage_matrix!(fifo_sends_need_a_message_group);
+async fn fifo_sends_need_a_message_group() {
+    let server = TestServer::start().await;
+    let url = server
+        .client
+        .create_queue()
+        .queue_name("grouped.fifo")
+        .attributes(aws_sdk_sqs::types::QueueAttributeName::FifoQueue, "true")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+
+    let body = format!(r#"{{"QueueUrl": "{}", "MessageBody": "hi"}}"#, url);
+    let (status, response) = server.action("AmazonSQS.SendMessage", &body).await;
+    assert_eq!(status, 400, "{}", response);
+    assert!(response.contains("MissingParameter"), "{}", response);
+    assert!(response.contains("MessageGroupId"), "{}", response);
+
+    let body = format!(
+        r#"{{"QueueUrl": "{}", "Entries": [{{"Id": "a", "MessageBody": "hi"}}]}}"#,
+        url
+    );
+    let (status, response) = server.action("AmazonSQS.SendMessageBatch", &body).await;
+    assert_eq!(status, 200, "{}", response);
+    let response: Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["Failed"][0]["Code"], "MissingParameter");
+    assert_eq!(response["Failed"][0]["SenderFault"], true);
+}
+
+storage_matrix!(void_actions_answer_an_empty_object);
+async fn void_actions_answer_an_empty_object() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("void").await;
+    for body in ["first", "second"] {
+        server
+            .client
+            .send_message()
+            .queue_url(&queue_url)
+            .message_body(body)
+            .send()
+            .await
+            .unwrap();
+    }
+    let received = server
+        .client
+        .receive_message()
+        .queue_url(&queue_url)
+        .max_number_of_messages(10)
+        .send()
+        .await
+        .unwrap();
+    let handle = |i: usize| received.messages()[i].receipt_handle().unwrap().to_string();
+
+    let actions = [
+        ("SetQueueAttributes", serde_json::json!({ "Attributes": { "DelaySeconds": "1" } })),
+        ("TagQueue", serde_json::json!({ "Tags": { "team": "billing" } })),
+        ("UntagQueue", serde_json::json!({ "TagKeys": ["team"] })),
+        (
+            "AddPermission",
+            serde_json::json!({
+                "Label": "share",
+                "AWSAccountIds": ["111122223333"],
+                "Actions": ["SendMessage"],
+            }),
+        ),
+        ("RemovePermission", serde_json::json!({ "Label": "share" })),
+        (
+            "ChangeMessageVisibility",
+            serde_json::json!({ "ReceiptHandle": handle(0), "VisibilityTimeout": 60 }),
+        ),
+        ("DeleteMessage", serde_json::json!({ "ReceiptHandle": handle(1) })),
+        ("PurgeQueue", serde_json::json!({})),
+        ("DeleteQueue", serde_json::json!({})),
+    ];
+    let http = reqwest::Client::new();
+    for (action, mut body) in actions {
+        body["QueueUrl"] = queue_url.clone().into();
+        let response = http
+            .post(format!("http://{}/", server.addr))
+            .header("X-Amz-Target", format!("AmazonSQS.{}", action))
+            .header("Content-Type", "application/x-amz-json-1.0")
+            .body(body.to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200, "{}", action);
+        let content_type = &response.headers()["content-type"];
+        assert_eq!(content_type, "application/x-amz-json-1.0", "{}", action);
+        assert_eq!(response.bytes().await.unwrap().as_ref(), b"{}", "{}", action);
+    }
+}
@@ -0,0 +1,36 @@
+use aws_sdk_sqs::config::{Credentials, Region};
+use aws_sdk_sqs::Client;
+use local_sqs::Config;
+use std::time::Duration;
+
+#[tokio::test]
+async fn serve_runs_in_process_on_an_ephemeral_port() {
+    let config = Config {
+        host: "127.0.0.1".to_string(),
+        port: 0,
+        ..Config::default()
+    };
+    let (addrs, server, shutdown) = local_sqs::serve(config).await.unwrap();
+    let addr = addrs[0];
+    assert!(addr.ip().is_loopback());
+    assert_ne!(addr.port(), 0);
+
+    let sdk_config = aws_sdk_sqs::Config::builder()
+        .endpoint_url(format!("http://{}", addr))
+        .region(Region::new("us-east-1"))
+        .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+        .build();
+    let client = Client::from_conf(sdk_config);
+    let created = client.create_queue().queue_name("embedded").send().await.unwrap();
+    let queue_url = created.queue_url.unwrap();
+    // Queue URLs carry the port bound, not the 0 configured.
+    assert_eq!(queue_url, format!("http://127.0.0.1:{}/000000000000/embedded", addr.port()));
+    client.send_message().queue_url(&queue_url).message_body("hi").send().await.unwrap();
+    let received = client.receive_message().queue_url(&queue_url).send().await.unwrap();
+    assert_eq!(received.messages()[0].body(), Some("hi"));
+
+    // Once stopped, the server task finishes and the port is closed.
+    shutdown.shutdown();
+    tokio::time::timeout(Duration::from_secs(5), server).await.unwrap().unwrap();
+    assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+}
@@ -0,0 +1,293 @@
+mod common;
+
+use axum::response::IntoResponse;
+use common::{TestServer, storage_matrix};
+use local_sqs::error::SqsError;
+use local_sqs::Config;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+
+fn all_errors() -> Vec<SqsError> {
+    vec![
+        SqsError::QueueNameExists,
+        SqsError::QueueDoesNotExist,
+        SqsError::InvalidParameterValue("bad value".to_string()),
+        SqsError::MissingParameter("MessageBody".to_string()),
+        SqsError::InvalidMessageContents("bad character".to_string()),
+        SqsError::InvalidAction("Frobnicate".to_string()),
+        SqsError::MessageNotInflight,
+        SqsError::ReceiptHandleIsInvalid("nope".to_string()),
+        SqsError::OverLimit("too many".to_string()),
+        SqsError::PurgeQueueInProgress("again".to_string()),
+        SqsError::QueueFull {
+            code: "RequestThrottled".into(),
+            message: "full".to_string(),
+        },
+        SqsError::InvalidAttributeName("Color".to_string()),
+        SqsError::InvalidAttributeValue("bad attribute".to_string()),
+        SqsError::TooManyEntriesInBatchRequest(11),
+        SqsError::EmptyBatchRequest("SendMessageBatchRequestEntry"),
+        SqsError::BatchEntryIdsNotDistinct("a".to_string()),
+        SqsError::InvalidBatchEntryId,
+        SqsError::BatchRequestTooLong {
+            size: 300000,
+            limit: 262144,
+        },
+        SqsError::RequestEntityTooLarge(1048576),
+        SqsError::ServiceUnavailable("busy".to_string()),
+        SqsError::InternalError("store down".to_string()),
+        SqsError::ResourceNotFound("no such task".to_string()),
+    ]
+}
+
+/// Status, code, query code and fault as AWS documents them. Deliberately
+/// without a wildcard arm, so a new variant doesn't compile until it's
+/// listed here.
+fn expected(error: &SqsError) -> (u16, &str, &str, &'static str) {
+    match error {
+        SqsError::QueueNameExists => (400, "QueueNameExists", "QueueAlreadyExists", "Sender"),
+        SqsError::QueueDoesNotExist => (
+            400,
+            "QueueDoesNotExist",
+            "AWS.SimpleQueueService.NonExistentQueue",
+            "Sender",
+        ),
+        SqsError::InvalidParameterValue(_) => {
+            (400, "InvalidParameterValue", "InvalidParameterValue", "Sender")
+        }
+        SqsError::MissingParameter(_) => (400, "MissingParameter", "MissingParameter", "Sender"),
+        SqsError::InvalidMessageContents(_) => {
+            (400, "InvalidMessageContents", "InvalidMessageContents", "Sender")
+        }
+        SqsError::InvalidAction(_) => (400, "InvalidAction", "InvalidAction", "Sender"),
+        SqsError::MessageNotInflight => (
+            400,
+            "MessageNotInflight",
+            "AWS.SimpleQueueService.MessageNotInflight",
+            "Sender",
+        ),
+        SqsError::ReceiptHandleIsInvalid(_) => {
+            (404, "ReceiptHandleIsInvalid", "ReceiptHandleIsInvalid", "Sender")
+        }
+        SqsError::OverLimit(_) => (403, "OverLimit", "OverLimit", "Sender"),
+        SqsError::PurgeQueueInProgress(_) => (
+            403,
+            "PurgeQueueInProgress",
+            "AWS.SimpleQueueService.PurgeQueueInProgress",
+            "Sender",
+        ),
+        SqsError::QueueFull { code, .. } => (403, code, code, "Sender"),
+        SqsError::InvalidAttributeName(_) => {
+            (400, "InvalidAttributeName", "InvalidAttributeName", "Sender")
+        }
+        SqsError::InvalidAttributeValue(_) => {
+            (400, "InvalidAttributeValue", "InvalidAttributeValue", "Sender")
+        }
+        SqsError::TooManyEntriesInBatchRequest(_) => (
+            400,
+            "TooManyEntriesInBatchRequest",
+            "AWS.SimpleQueueService.TooManyEntriesInBatchRequest",
+            "Sender",
+        ),
+        SqsError::EmptyBatchRequest(_) => (
+            400,
+            "EmptyBatchRequest",
+            "AWS.SimpleQueueService.EmptyBatchRequest",
+            "Sender",
+        ),
+        SqsError::BatchEntryIdsNotDistinct(_) => (
+            400,
+            "BatchEntryIdsNotDistinct",
+            "AWS.SimpleQueueService.BatchEntryIdsNotDistinct",
+            "Sender",
+        ),
+        SqsError::InvalidBatchEntryId => (
+            400,
+            "InvalidBatchEntryId",
+            "AWS.SimpleQueueService.InvalidBatchEntryId",
+            "Sender",
+        ),
+        SqsError::BatchRequestTooLong { .. } => (
+            400,
+            "BatchRequestTooLong",
+            "AWS.SimpleQueueService.BatchRequestTooLong",
+            "Sender",
+        ),
+        SqsError::RequestEntityTooLarge(_) => {
+            (413, "RequestEntityTooLarge", "RequestEntityTooLarge", "Sender")
+        }
+        SqsError::ServiceUnavailable(_) => {
+            (503, "ServiceUnavailable", "ServiceUnavailable", "Receiver")
+        }
+        SqsError::InternalError(_) => (500, "InternalError", "InternalError", "Receiver"),
+        SqsError::ResourceNotFound(_) => {
+            (404, "ResourceNotFoundException", "ResourceNotFoundException", "Sender")
+        }
+    }
+}
+
+#[tokio::test]
+async fn every_error_has_its_aws_wire_shape() {
+    for error in all_errors() {
+        let (status, code, query_code, fault) = expected(&error);
+        let (code, query_code) = (code.to_string(), query_code.to_string());
+        let message = error.message();
+        assert!(!message.is_empty(), "{:?}", error);
+        assert_eq!(error.status().as_u16(), status, "{:?}", error);
+        // 4xx for the sender's mistakes, 5xx for the service's.
+        assert_eq!(status >= 500, fault == "Receiver", "{:?}", error);
+
+        let response = error.into_response();
+        assert_eq!(response.status().as_u16(), status, "{}", code);
+        let query_error = response.headers()["x-amzn-query-error"].to_str().unwrap();
+        assert_eq!(query_error, format!("{};{}", query_code, fault));
+        let content_type = &response.headers()["content-type"];
+        assert_eq!(content_type, "application/x-amz-json-1.0", "{}", code);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        let expected_body = json!({
+            "__type": format!("com.amazonaws.sqs#{}", code),
+            "message": message,
+        });
+        assert_eq!(body, expected_body);
+    }
+}
+
+/// Asserts that `response`, as returned by [`TestServer::action_with_headers`],
+/// carries `error`'s status, query error header and namespaced `__type`.
+fn assert_shape(error: SqsError, response: (u16, HashMap<String, String>, String)) {
+    let (status, code, query_code, fault) = expected(&error);
+    let (actual_status, headers, body) = response;
+    assert_eq!(actual_status, status, "{}: {}", code, body);
+    let query_error = headers.get("x-amzn-query-error").map(String::as_str);
+    assert_eq!(query_error, Some(format!("{};{}", query_code, fault).as_str()), "{}", code);
+    let content_type = headers.get("content-type").map(String::as_str);
+    assert_eq!(content_type, Some("application/x-amz-json-1.0"), "{}", code);
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["__type"], format!("com.amazonaws.sqs#{}", code));
+    assert!(body["message"].as_str().is_some_and(|m| !m.is_empty()), "{}", body);
+}
+
+storage_matrix!(errors_reach_clients_in_their_wire_shape);
+/// Provokes each error a server can answer with through real requests, to
+/// check the shape survives the trip to a client.
+async fn errors_reach_clients_in_their_wire_shape() {
+    let not_a_dir = std::env::temp_dir().join(format!("local-sqs-{}", uuid::Uuid::new_v4()));
+    std::fs::write(&not_a_dir, "").unwrap();
+    let server = TestServer::start_with(Config {
+        max_messages_per_queue: Some(2),
+        purge_cooldown: Some(Duration::from_secs(60)),
+        max_long_polls_per_queue: Some(0),
+        checkpoint_dir: Some(not_a_dir.clone()),
+        ..Config::default()
+    })
+    .await;
+    let server = &server;
+    let action = |action: &str, body: Value| {
+        let target = format!("AmazonSQS.{}", action);
+        async move { server.action_with_headers(&target, &body.to_string()).await }
+    };
+    let error = |message: &str| message.to_string();
+
+    let created = action("CreateQueue", json!({"QueueName": "shapes"})).await;
+    let queue_url: Value = serde_json::from_str(&created.2).unwrap();
+    let queue_url = queue_url["QueueUrl"].as_str().unwrap().to_string();
+    let conflicting = json!({"QueueName": "shapes", "Attributes": {"DelaySeconds": "5"}});
+    assert_shape(SqsError::QueueNameExists, action("CreateQueue", conflicting).await);
+    let missing = json!({"QueueName": "missing"});
+    assert_shape(SqsError::QueueDoesNotExist, action("GetQueueUrl", missing).await);
+    let list = json!({"MaxResults": 0});
+    let invalid = SqsError::InvalidParameterValue(error("bad value"));
+    assert_shape(invalid, action("ListQueues", list).await);
+    let send = json!({"QueueUrl": queue_url});
+    let missing = SqsError::MissingParameter(error("MessageBody"));
+    assert_shape(missing, action("SendMessage", send).await);
+    let unknown = SqsError::InvalidAction(error("Frobnicate"));
+    assert_shape(unknown, action("Frobnicate", json!({})).await);
+
+    // A message deleted, then its handle reused; and a handle never issued.
+    let send = json!({"QueueUrl": queue_url, "MessageBody": "once"});
+    assert_eq!(action("SendMessage", send.clone()).await.0, 200);
+    let received = action("ReceiveMessage", json!({"QueueUrl": queue_url})).await;
+    let received: Value = serde_json::from_str(&received.2).unwrap();
+    let handle = received["Messages"][0]["ReceiptHandle"].clone();
+    let delete = json!({"QueueUrl": queue_url, "ReceiptHandle": handle});
+    assert_eq!(action("DeleteMessage", delete).await.0, 200);
+    let change = json!({"QueueUrl": queue_url, "ReceiptHandle": handle, "VisibilityTimeout": 5});
+    let stale = action("ChangeMessageVisibility", change).await;
+    assert_shape(SqsError::MessageNotInflight, stale);
+    let forged = json!({"QueueUrl": queue_url, "ReceiptHandle": "nope"});
+    let invalid = SqsError::ReceiptHandleIsInvalid(error("nope"));
+    assert_shape(invalid, action("DeleteMessage", forged).await);
+
+    // The server holds two messages per queue; the queue itself, fewer.
+    for _ in 0..2 {
+        assert_eq!(action("SendMessage", send.clone()).await.0, 200);
+    }
+    let over = SqsError::OverLimit(error("too many"));
+    assert_shape(over, action("SendMessage", send.clone()).await);
+    let purge = json!({"QueueUrl": queue_url});
+    assert_eq!(action("PurgeQueue", purge.clone()).await.0, 200);
+    let again = SqsError::PurgeQueueInProgress(error("again"));
+    assert_shape(again, action("PurgeQueue", purge).await);
+    let capped = json!({"QueueUrl": queue_url, "Attributes": {"MaxQueueLength": "1"}});
+    assert_eq!(action("SetQueueAttributes", capped).await.0, 200);
+    assert_eq!(action("SendMessage", send.clone()).await.0, 200);
+    let full = SqsError::QueueFull {
+        code: "OverLimit".into(),
+        message: error("full"),
+    };
+    assert_shape(full, action("SendMessage", send).await);
+
+    let names = json!({"QueueUrl": queue_url, "AttributeNames": ["Color"]});
+    let unknown = SqsError::InvalidAttributeName(error("Color"));
+    assert_shape(unknown, action("GetQueueAttributes", names).await);
+    let timeout = json!({"QueueUrl": queue_url, "Attributes": {"VisibilityTimeout": "99999"}});
+    let invalid = SqsError::InvalidAttributeValue(error("bad attribute"));
+    assert_shape(invalid, action("SetQueueAttributes", timeout).await);
+
+    let batch = |entries: Vec<Value>| json!({"QueueUrl": queue_url, "Entries": entries});
+    let entry = |id: &str, body: &str| json!({"Id": id, "MessageBody": body});
+    let eleven = (0..11).map(|i| entry(&i.to_string(), "m")).collect();
+    let too_many = SqsError::TooManyEntriesInBatchRequest(11);
+    assert_shape(too_many, action("SendMessageBatch", batch(eleven)).await);
+    let empty = SqsError::EmptyBatchRequest("SendMessageBatchRequestEntry");
+    assert_shape(empty, action("SendMessageBatch", batch(Vec::new())).await);
+    let twins = vec![entry("a", "m"), entry("a", "m")];
+    let not_distinct = SqsError::BatchEntryIdsNotDistinct(error("a"));
+    assert_shape(not_distinct, action("SendMessageBatch", batch(twins)).await);
+    let spaced = vec![entry("not valid", "m")];
+    let invalid = SqsError::InvalidBatchEntryId;
+    assert_shape(invalid, action("SendMessageBatch", batch(spaced)).await);
+    let half = "x".repeat(150_000);
+    let halves = vec![entry("a", &half), entry("b", &half)];
+    let too_long = SqsError::BatchRequestTooLong {
+        size: 300000,
+        limit: 262144,
+    };
+    assert_shape(too_long, action("SendMessageBatch", batch(halves)).await);
+    let huge = json!({"QueueUrl": queue_url, "MessageBody": "x".repeat(1_100_000)});
+    let too_large = SqsError::RequestEntityTooLarge(1048576);
+    assert_shape(too_large, action("SendMessage", huge).await);
+
+    let poll = json!({"QueueUrl": queue_url, "WaitTimeSeconds": 1});
+    let busy = SqsError::ServiceUnavailable(error("busy"));
+    assert_shape(busy, action("ReceiveMessage", poll).await);
+    let cancel = json!({"TaskHandle": "missing"});
+    let not_found = SqsError::ResourceNotFound(error("no such task"));
+    assert_shape(not_found, action("CancelMessageMoveTask", cancel).await);
+    let broken = SqsError::InternalError(error("store down"));
+    assert_shape(broken, server.admin_with_headers("GET", "/checkpoints", "").await);
+
+    std::fs::remove_file(&not_a_dir).ok();
+}
+
+#[test]
+fn display_is_code_and_message() {
+    let error = SqsError::InvalidAttributeName("Color".to_string());
+    assert_eq!(error.to_string(), "InvalidAttributeName: Unknown Attribute Color.");
+}
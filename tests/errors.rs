@@ -0,0 +1,181 @@
+mod common;
+
+use common::{TestServer, storage_matrix};
+
+storage_matrix!(missing_queue_is_queue_does_not_exist);
+async fn missing_queue_is_queue_does_not_exist() {
+    let server = TestServer::start().await;
+
+    let err = server
+        .client
+        .get_queue_url()
+        .queue_name("missing")
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert!(err.is_queue_does_not_exist(), "{:?}", err);
+
+    let err = server
+        .client
+        .send_message()
+        .queue_url(format!("http://{}/missing", server.addr))
+        .message_body("x")
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert!(err.is_queue_does_not_exist(), "{:?}", err);
+}
+
+storage_matrix!(conflicting_create_is_queue_name_exists);
+async fn conflicting_create_is_queue_name_exists() {
+    let server = TestServer::start().await;
+    server.create_queue("taken").await;
+
+    let err = server
+        .client
+        .create_queue()
+        .queue_name("taken")
+        .attributes(aws_sdk_sqs::types::QueueAttributeName::DelaySeconds, "5")
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert!(err.is_queue_name_exists(), "{:?}", err);
+}
+
+storage_matrix!(stale_receipt_handle_is_message_not_inflight);
+async fn stale_receipt_handle_is_message_not_inflight() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("stale").await;
+    let receipt_handle = receive_one(&server, &queue_url).await;
+    server
+        .client
+        .delete_message()
+        .queue_url(&queue_url)
+        .receipt_handle(&receipt_handle)
+        .send()
+        .await
+        .unwrap();
+
+    let err = server
+        .client
+        .change_message_visibility()
+        .queue_url(&queue_url)
+        .receipt_handle(&receipt_handle)
+        .visibility_timeout(10)
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert!(err.is_message_not_inflight(), "{:?}", err);
+}
+
+/// Sends a message to `queue_url` and receives it, returning its handle.
+async fn receive_one(server: &TestServer, queue_url: &str) -> String {
+    server.send(queue_url, "hello").await;
+    let messages = server.receive(queue_url, None).await;
+    messages[0].receipt_handle.clone().unwrap()
+}
+
+storage_matrix!(foreign_receipt_handles_are_invalid);
+async fn foreign_receipt_handles_are_invalid() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("handles").await;
+    let other_url = server.create_queue("other").await;
+    let other_handle = receive_one(&server, &other_url).await;
+
+    // An old-style UUID handle, and a valid handle for another queue.
+    for handle in ["0c9cb4c2-5d36-4d5e-9b1f-6a1b9f1d3c2e", other_handle.as_str()] {
+        let err = server
+            .client
+            .delete_message()
+            .queue_url(&queue_url)
+            .receipt_handle(handle)
+            .send()
+            .await
+            .unwrap_err()
+            .into_service_error();
+        assert!(err.is_receipt_handle_is_invalid(), "{}: {:?}", handle, err);
+
+        let err = server
+            .client
+            .change_message_visibility()
+            .queue_url(&queue_url)
+            .receipt_handle(handle)
+            .visibility_timeout(10)
+            .send()
+            .await
+            .unwrap_err()
+            .into_service_error();
+        assert!(err.is_receipt_handle_is_invalid(), "{}: {:?}", handle, err);
+    }
+}
+
+storage_matrix!(signed_receipt_handles_cannot_be_forged);
+async fn signed_receipt_handles_cannot_be_forged() {
+    let server = TestServer::start_with(local_sqs::Config {
+        receipt_handle_secret: Some("s3cret".to_string()),
+        ..Default::default()
+    })
+    .await;
+    let queue_url = server.create_queue("signed").await;
+    let receipt_handle = receive_one(&server, &queue_url).await;
+
+    // The payload alone decodes, but lacks the signature.
+    let (payload, _) = receipt_handle.split_once('.').unwrap();
+    let err = server
+        .client
+        .delete_message()
+        .queue_url(&queue_url)
+        .receipt_handle(payload)
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert!(err.is_receipt_handle_is_invalid(), "{:?}", err);
+
+    server
+        .client
+        .delete_message()
+        .queue_url(&queue_url)
+        .receipt_handle(&receipt_handle)
+        .send()
+        .await
+        .unwrap();
+}
+
+storage_matrix!(invalid_attribute_value_is_rejected);
+async fn invalid_attribute_value_is_rejected() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("attrs").await;
+
+    let err = server
+        .client
+        .set_queue_attributes()
+        .queue_url(&queue_url)
+        .attributes(
+            aws_sdk_sqs::types::QueueAttributeName::VisibilityTimeout,
+            "99999",
+        )
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert!(err.is_invalid_attribute_value(), "{:?}", err);
+}
+
+storage_matrix!(receipt_handles_describe_the_receive);
+async fn receipt_handles_describe_the_receive() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("described").await;
+    let receipt_handle = receive_one(&server, &queue_url).await;
+
+    let handle = local_sqs::receipt::ReceiptHandles::default()
+        .resolve(&receipt_handle)
+        .unwrap();
+    assert_eq!(handle.queue, "described");
+    assert_eq!(handle.receive_count, 1);
+    assert!(!handle.message_id.is_empty());
+}
@@ -0,0 +1,143 @@
+mod common;
+
+use aws_sdk_sqs::operation::RequestId;
+use aws_sdk_sqs::types::QueueAttributeName;
+use common::{TestServer, storage_matrix};
+use local_sqs::Config;
+use serde_json::Value;
+
+async fn events(server: &TestServer, path: &str) -> Vec<Value> {
+    let (status, body) = server.admin("GET", path, "").await;
+    assert_eq!(status, 200, "{}", body);
+    let body: Value = serde_json::from_str(&body).unwrap();
+    body["events"].as_array().unwrap().clone()
+}
+
+fn kinds(events: &[Value]) -> Vec<&str> {
+    events.iter().map(|e| e["kind"].as_str().unwrap()).collect()
+}
+
+storage_matrix!(queue_changes_are_logged_with_their_request);
+async fn queue_changes_are_logged_with_their_request() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("logged").await;
+    let client = &server.client;
+
+    client
+        .set_queue_attributes()
+        .queue_url(&queue_url)
+        .attributes(QueueAttributeName::VisibilityTimeout, "5")
+        .send()
+        .await
+        .unwrap();
+    client
+        .tag_queue()
+        .queue_url(&queue_url)
+        .tags("team", "search")
+        .send()
+        .await
+        .unwrap();
+    let purged = client.purge_queue().queue_url(&queue_url).send().await.unwrap();
+
+    let events = events(&server, "/queues/logged/events").await;
+    assert_eq!(
+        kinds(&events),
+        ["queue_created", "attributes_changed", "tags_changed", "purged"]
+    );
+    assert_eq!(events[1]["details"]["attributes"]["VisibilityTimeout"], "5");
+    assert_eq!(events[2]["details"]["added"]["team"], "search");
+    assert_eq!(events[3]["request_id"].as_str(), purged.request_id());
+    assert!(events.iter().all(|e| e["queue"] == "logged"));
+}
+
+storage_matrix!(dead_lettering_is_logged_on_both_queues);
+async fn dead_lettering_is_logged_on_both_queues() {
+    let server = TestServer::start().await;
+    server.create_queue("dlq").await;
+    let source_url = server
+        .client
+        .create_queue()
+        .queue_name("source")
+        .attributes(
+            QueueAttributeName::RedrivePolicy,
+            r#"{"deadLetterTargetArn":"arn:aws:sqs:us-east-1:000000000000:dlq","maxReceiveCount":"1"}"#,
+        )
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    let sent = server
+        .client
+        .send_message()
+        .queue_url(&source_url)
+        .message_body("doomed")
+        .send()
+        .await
+        .unwrap();
+    for _ in 0..2 {
+        server
+            .client
+            .receive_message()
+            .queue_url(&source_url)
+            .visibility_timeout(0)
+            .send()
+            .await
+            .unwrap();
+    }
+
+    for queue in ["source", "dlq"] {
+        let events = events(&server, &format!("/queues/{}/events", queue)).await;
+        let dead_lettered = events.last().unwrap();
+        assert_eq!(dead_lettered["kind"], "dead_lettered", "{}", queue);
+        assert_eq!(
+            dead_lettered["details"]["message_ids"],
+            serde_json::json!([sent.message_id.as_deref().unwrap()])
+        );
+        assert!(dead_lettered["request_id"].is_string());
+    }
+}
+
+storage_matrix!(server_log_keeps_the_latest_creations_and_deletions);
+async fn server_log_keeps_the_latest_creations_and_deletions() {
+    let server = TestServer::start_with(Config {
+        event_log_capacity: 3,
+        ..Default::default()
+    })
+    .await;
+    for name in ["a", "b"] {
+        let queue_url = server.create_queue(name).await;
+        server
+            .client
+            .delete_queue()
+            .queue_url(&queue_url)
+            .send()
+            .await
+            .unwrap();
+    }
+
+    let events = events(&server, "/events").await;
+    assert_eq!(kinds(&events), ["queue_deleted", "queue_created", "queue_deleted"]);
+    let queues: Vec<&str> = events.iter().map(|e| e["queue"].as_str().unwrap()).collect();
+    assert_eq!(queues, ["a", "b", "b"]);
+    assert_eq!(events[2]["details"]["reason"], "DeleteQueue");
+
+    let (status, _) = server.admin("GET", "/queues/a/events", "").await;
+    assert_eq!(status, 400);
+}
+
+storage_matrix!(events_can_be_fetched_since_a_time);
+async fn events_can_be_fetched_since_a_time() {
+    let server = TestServer::start_with(Config {
+        manual_clock: true,
+        ..Default::default()
+    })
+    .await;
+    let queue_url = server.create_queue("timed").await;
+    let (_, clock) = server.admin("POST", "/clock/advance", r#"{"seconds": 10}"#).await;
+    let now = serde_json::from_str::<Value>(&clock).unwrap()["now"].as_i64().unwrap();
+    server.client.purge_queue().queue_url(&queue_url).send().await.unwrap();
+
+    let events = events(&server, &format!("/queues/timed/events?since={}", now)).await;
+    assert_eq!(kinds(&events), ["purged"]);
+}
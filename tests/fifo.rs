@@ -0,0 +1,325 @@
+mod common;
+
+use aws_sdk_sqs::types::{Message, MessageSystemAttributeName, QueueAttributeName};
+use aws_sdk_sqs::Client;
+use common::{TestServer, storage_matrix};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+async fn create_fifo_queue(server: &TestServer, name: &str) -> String {
+    server
+        .client
+        .create_queue()
+        .queue_name(name)
+        .attributes(QueueAttributeName::FifoQueue, "true")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap()
+}
+
+async fn send_to_group(client: &Client, queue_url: &str, group: &str, body: &str) {
+    client
+        .send_message()
+        .queue_url(queue_url)
+        .message_group_id(group)
+        .message_deduplication_id(body)
+        .message_body(body)
+        .send()
+        .await
+        .unwrap();
+}
+
+/// Receives at most one message, as a consumer working through a queue
+/// one message at a time does.
+async fn receive_one(client: &Client, queue_url: &str) -> Option<Message> {
+    client
+        .receive_message()
+        .queue_url(queue_url)
+        .message_system_attribute_names(MessageSystemAttributeName::All)
+        .send()
+        .await
+        .unwrap()
+        .messages
+        .unwrap_or_default()
+        .pop()
+}
+
+async fn delete(client: &Client, queue_url: &str, message: &Message) {
+    client
+        .delete_message()
+        .queue_url(queue_url)
+        .receipt_handle(message.receipt_handle().unwrap())
+        .send()
+        .await
+        .unwrap();
+}
+
+fn bodies(messages: &[Message]) -> Vec<&str> {
+    messages.iter().map(|m| m.body().unwrap()).collect()
+}
+
+storage_matrix!(a_group_is_locked_while_its_messages_are_in_flight);
+async fn a_group_is_locked_while_its_messages_are_in_flight() {
+    let server = TestServer::start().await;
+    let queue_url = create_fifo_queue(&server, "locked.fifo").await;
+    let (first, second) = (server.client.clone(), server.client.clone());
+    send_to_group(&first, &queue_url, "a", "a1").await;
+    send_to_group(&first, &queue_url, "a", "a2").await;
+    send_to_group(&first, &queue_url, "b", "b1").await;
+
+    let a1 = receive_one(&first, &queue_url).await.unwrap();
+    assert_eq!(a1.body(), Some("a1"));
+    let attributes = a1.attributes().unwrap();
+    assert_eq!(attributes[&MessageSystemAttributeName::MessageGroupId], "a");
+
+    // Another consumer gets the other group, but nothing more of group a.
+    assert_eq!(bodies(&server.receive(&queue_url, None).await), ["b1"]);
+    assert!(server.receive(&queue_url, None).await.is_empty());
+
+    delete(&first, &queue_url, &a1).await;
+    let a2 = server.receive(&queue_url, None).await;
+    assert_eq!(bodies(&a2), ["a2"]);
+
+    // A message that becomes visible again unlocks its group too, and comes
+    // back before anything sent after it.
+    send_to_group(&first, &queue_url, "a", "a3").await;
+    second
+        .change_message_visibility()
+        .queue_url(&queue_url)
+        .receipt_handle(a2[0].receipt_handle().unwrap())
+        .visibility_timeout(0)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(bodies(&server.receive(&queue_url, None).await), ["a2", "a3"]);
+}
+
+storage_matrix!(batches_fill_from_every_unlocked_group);
+async fn batches_fill_from_every_unlocked_group() {
+    let server = TestServer::start().await;
+    let queue_url = create_fifo_queue(&server, "batched.fifo").await;
+    for (group, body) in [("a", "a1"), ("b", "b1"), ("a", "a2"), ("c", "c1"), ("b", "b2")] {
+        send_to_group(&server.client, &queue_url, group, body).await;
+    }
+    let locked = receive_one(&server.client, &queue_url).await.unwrap();
+    assert_eq!(locked.body(), Some("a1"));
+
+    let batch = server.receive(&queue_url, None).await;
+    assert_eq!(bodies(&batch), ["b1", "c1", "b2"]);
+}
+
+storage_matrix!(groups_drain_in_parallel);
+async fn groups_drain_in_parallel() {
+    const GROUPS: usize = 8;
+    const PER_GROUP: usize = 5;
+    const PROCESSING: Duration = Duration::from_millis(50);
+
+    let server = TestServer::start().await;
+    let queue_url = create_fifo_queue(&server, "parallel.fifo").await;
+    for i in 0..PER_GROUP {
+        for group in 0..GROUPS {
+            let body = format!("{}:{}", group, i);
+            send_to_group(&server.client, &queue_url, &group.to_string(), &body).await;
+        }
+    }
+
+    // Each consumer takes one message at a time and "processes" it before
+    // deleting it; the order every group was processed in is recorded.
+    let processed: Arc<Mutex<HashMap<String, Vec<usize>>>> = Default::default();
+    let started = Instant::now();
+    let consumers: Vec<_> = (0..GROUPS)
+        .map(|_| {
+            let (client, queue_url) = (server.client.clone(), queue_url.clone());
+            let processed = processed.clone();
+            tokio::spawn(async move {
+                let mut idle = 0;
+                while idle < 3 {
+                    let Some(message) = receive_one(&client, &queue_url).await else {
+                        idle += 1;
+                        tokio::time::sleep(PROCESSING / 2).await;
+                        continue;
+                    };
+                    idle = 0;
+                    let (group, index) = message.body().unwrap().split_once(':').unwrap();
+                    tokio::time::sleep(PROCESSING).await;
+                    processed
+                        .lock()
+                        .unwrap()
+                        .entry(group.to_string())
+                        .or_default()
+                        .push(index.parse().unwrap());
+                    delete(&client, &queue_url, &message).await;
+                }
+            })
+        })
+        .collect();
+    for consumer in consumers {
+        consumer.await.unwrap();
+    }
+    let elapsed = started.elapsed();
+
+    let processed = processed.lock().unwrap();
+    assert_eq!(processed.len(), GROUPS);
+    for order in processed.values() {
+        assert_eq!(*order, (0..PER_GROUP).collect::<Vec<_>>());
+    }
+    // One group at a time would take GROUPS * PER_GROUP * PROCESSING.
+    let serial = PROCESSING * (GROUPS * PER_GROUP) as u32;
+    assert!(elapsed < serial / 2, "took {:?}; serially {:?}", elapsed, serial);
+}
+
+storage_matrix!(deduplicated_sends_return_the_original_message);
+async fn deduplicated_sends_return_the_original_message() {
+    let server = TestServer::start().await;
+    let queue_url = create_fifo_queue(&server, "dedup.fifo").await;
+    let send = |body: &str, deduplication_id: &str| {
+        server
+            .client
+            .send_message()
+            .queue_url(&queue_url)
+            .message_group_id("g")
+            .message_deduplication_id(deduplication_id)
+            .message_body(body)
+            .send()
+    };
+
+    let first = send("original", "order-1").await.unwrap();
+    let retry = send("retried", "order-1").await.unwrap();
+    assert_eq!(retry.message_id(), first.message_id());
+    assert!(first.sequence_number().is_some());
+    assert_eq!(retry.sequence_number(), first.sequence_number());
+    // The digest is of what this send carried.
+    let md5 = format!("{:x}", md5::compute("retried"));
+    assert_eq!(retry.md5_of_message_body(), Some(md5.as_str()));
+
+    let other = send("next", "order-2").await.unwrap();
+    assert_ne!(other.message_id(), first.message_id());
+    assert!(other.sequence_number() > first.sequence_number());
+
+    // Batch entries are deduplicated against earlier sends too.
+    let entry = aws_sdk_sqs::types::SendMessageBatchRequestEntry::builder()
+        .id("again")
+        .message_body("batched")
+        .message_group_id("g")
+        .message_deduplication_id("order-1")
+        .build()
+        .unwrap();
+    let batch = server
+        .client
+        .send_message_batch()
+        .queue_url(&queue_url)
+        .entries(entry)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(batch.successful()[0].message_id(), first.message_id().unwrap());
+    assert_eq!(batch.successful()[0].sequence_number(), first.sequence_number());
+
+    let received = server.receive(&queue_url, None).await;
+    assert_eq!(bodies(&received), ["original", "next"]);
+    let attributes = received[0].attributes().unwrap();
+    let sequence_number = &attributes[&MessageSystemAttributeName::SequenceNumber];
+    assert_eq!(Some(sequence_number.as_str()), first.sequence_number());
+    let deduplication_id = &attributes[&MessageSystemAttributeName::MessageDeduplicationId];
+    assert_eq!(deduplication_id, "order-1");
+}
+
+storage_matrix!(content_based_deduplication_uses_the_body);
+async fn content_based_deduplication_uses_the_body() {
+    let server = TestServer::start().await;
+    let queue_url = server
+        .client
+        .create_queue()
+        .queue_name("content.fifo")
+        .attributes(QueueAttributeName::FifoQueue, "true")
+        .attributes(QueueAttributeName::ContentBasedDeduplication, "true")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    let mut ids = Vec::new();
+    for body in ["same", "same", "different"] {
+        let sent = server
+            .client
+            .send_message()
+            .queue_url(&queue_url)
+            .message_group_id("g")
+            .message_body(body)
+            .send()
+            .await
+            .unwrap();
+        ids.push(sent.message_id.unwrap());
+    }
+    assert_eq!(ids[0], ids[1]);
+    assert_ne!(ids[0], ids[2]);
+    assert_eq!(bodies(&server.receive(&queue_url, None).await), ["same", "different"]);
+}
+
+storage_matrix!(sends_need_a_deduplication_id_without_content_based_deduplication);
+async fn sends_need_a_deduplication_id_without_content_based_deduplication() {
+    let server = TestServer::start().await;
+    let queue_url = create_fifo_queue(&server, "explicit.fifo").await;
+    let err = server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_group_id("g")
+        .message_body("hi")
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert_eq!(err.meta().code(), Some("InvalidParameterValue"));
+    let message = err.meta().message().unwrap();
+    assert!(message.contains("ContentBasedDeduplication"), "{}", message);
+
+    let entry = |id: &str| {
+        aws_sdk_sqs::types::SendMessageBatchRequestEntry::builder()
+            .id(id)
+            .message_group_id("g")
+            .message_body(id)
+    };
+    let response = server
+        .client
+        .send_message_batch()
+        .queue_url(&queue_url)
+        .entries(entry("explicit").message_deduplication_id("d").build().unwrap())
+        .entries(entry("implicit").build().unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.successful()[0].id(), "explicit");
+    assert_eq!(response.failed()[0].id(), "implicit");
+    assert_eq!(response.failed()[0].code(), "InvalidParameterValue");
+    assert_eq!(bodies(&server.receive(&queue_url, None).await), ["explicit"]);
+}
+
+storage_matrix!(deduplication_ids_expire_after_five_minutes);
+async fn deduplication_ids_expire_after_five_minutes() {
+    let server = TestServer::start_with(local_sqs::Config {
+        manual_clock: true,
+        ..Default::default()
+    })
+    .await;
+    let queue_url = create_fifo_queue(&server, "window.fifo").await;
+    let send = || {
+        server
+            .client
+            .send_message()
+            .queue_url(&queue_url)
+            .message_group_id("g")
+            .message_deduplication_id("once")
+            .message_body("hello")
+            .send()
+    };
+    let first = send().await.unwrap().message_id.unwrap();
+
+    server.advance_clock(299).await;
+    assert_eq!(send().await.unwrap().message_id.unwrap(), first);
+    server.advance_clock(1).await;
+    assert_ne!(send().await.unwrap().message_id.unwrap(), first);
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use aws_sdk_sqs::types::{MessageSystemAttributeName, QueueAttributeName};
+use common::{TestServer, storage_matrix};
+use local_sqs::Config;
+
+const FIXTURES: &str = r#"
+queues:
+  - name: orders
+    attributes:
+      VisibilityTimeout: "5"
+      RedrivePolicy: '{"deadLetterTargetArn": "arn:aws:sqs:us-east-1:000000000000:orders-dlq",
+        "maxReceiveCount": "3"}'
+    tags:
+      team: payments
+    messages:
+      - body: '{"id": 1}'
+        message_attributes:
+          kind: { DataType: String, StringValue: created }
+      - body: retried
+        receive_count: 2
+      - body: later
+        delay_seconds: 30
+  - name: orders-dlq
+  - name: events.fifo
+    attributes:
+      FifoQueue: "true"
+      ContentBasedDeduplication: "true"
+    messages:
+      - body: first
+        message_group_id: g
+      - body: second
+        message_group_id: g
+"#;
+
+/// Asserts that `server` has the queues, attributes and messages `FIXTURES`
+/// creates.
+async fn assert_loaded(server: &TestServer) {
+    assert_eq!(server.queue_names().await, ["events.fifo", "orders", "orders-dlq"]);
+
+    let orders = server.client.get_queue_url().queue_name("orders").send().await.unwrap();
+    let orders = orders.queue_url.unwrap();
+    let attributes = server
+        .client
+        .get_queue_attributes()
+        .queue_url(&orders)
+        .attribute_names(QueueAttributeName::All)
+        .send()
+        .await
+        .unwrap()
+        .attributes
+        .unwrap();
+    assert_eq!(attributes[&QueueAttributeName::VisibilityTimeout], "5");
+    assert!(attributes[&QueueAttributeName::RedrivePolicy].contains("orders-dlq"));
+    // The delayed message is counted, but not yet visible.
+    assert_eq!(attributes[&QueueAttributeName::ApproximateNumberOfMessages], "2");
+    assert_eq!(attributes[&QueueAttributeName::ApproximateNumberOfMessagesDelayed], "1");
+    let tags = server.client.list_queue_tags().queue_url(&orders).send().await.unwrap();
+    assert_eq!(tags.tags.unwrap()["team"], "payments");
+
+    let received = server
+        .client
+        .receive_message()
+        .queue_url(&orders)
+        .max_number_of_messages(10)
+        .message_attribute_names("All")
+        .message_system_attribute_names(MessageSystemAttributeName::ApproximateReceiveCount)
+        .send()
+        .await
+        .unwrap();
+    let mut messages = received.messages().to_vec();
+    messages.sort_by_key(|message| message.body().unwrap().to_string());
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].body(), Some("retried"));
+    // Seeded as received twice, so this is its third receive.
+    let receive_count = MessageSystemAttributeName::ApproximateReceiveCount;
+    assert_eq!(messages[0].attributes().unwrap()[&receive_count], "3");
+    assert_eq!(messages[1].body(), Some(r#"{"id": 1}"#));
+    let kind = &messages[1].message_attributes().unwrap()["kind"];
+    assert_eq!(kind.string_value(), Some("created"));
+
+    let events = server.client.get_queue_url().queue_name("events.fifo").send().await.unwrap();
+    let received = server
+        .client
+        .receive_message()
+        .queue_url(events.queue_url.unwrap())
+        .max_number_of_messages(10)
+        .send()
+        .await
+        .unwrap();
+    let bodies: Vec<_> = received.messages().iter().map(|m| m.body().unwrap()).collect();
+    assert_eq!(bodies, ["first", "second"]);
+}
+
+storage_matrix!(fixture_files_are_loaded_at_startup);
+async fn fixture_files_are_loaded_at_startup() {
+    let name = format!("local-sqs-fixtures-{}.yaml", uuid::Uuid::new_v4());
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, FIXTURES).unwrap();
+    let server = TestServer::start_with(Config {
+        fixtures: Some(path.clone()),
+        ..Config::default()
+    })
+    .await;
+    std::fs::remove_file(&path).ok();
+
+    assert_loaded(&server).await;
+}
+
+storage_matrix!(fixtures_can_be_posted_to_a_running_server);
+async fn fixtures_can_be_posted_to_a_running_server() {
+    let server = TestServer::start().await;
+    let (status, body) = server.admin("POST", "/fixtures", FIXTURES).await;
+    assert_eq!(status, 200, "{}", body);
+    let summary: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(summary, serde_json::json!({"queues": 3, "messages": 5}));
+
+    assert_loaded(&server).await;
+
+    // Malformed documents are rejected before anything is created.
+    let (status, body) = server.admin("POST", "/fixtures", "queues:\n  - nmae: typo\n").await;
+    assert_eq!(status, 400, "{}", body);
+    assert!(body.contains("Invalid fixtures"), "{}", body);
+    let urls = server.client.list_queues().send().await.unwrap().queue_urls.unwrap();
+    assert_eq!(urls.len(), 3);
+}
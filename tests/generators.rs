@@ -0,0 +1,141 @@
+mod common;
+
+use aws_sdk_sqs::types::QueueAttributeName;
+use common::{TestServer, storage_matrix};
+use local_sqs::Config;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+async fn generators(server: &TestServer) -> Vec<Value> {
+    let (status, body) = server.admin("GET", "/generators", "").await;
+    assert_eq!(status, 200, "{}", body);
+    serde_json::from_str(&body).unwrap()
+}
+
+/// Waits for the first generator to satisfy `done`, returning it.
+async fn wait_for(server: &TestServer, done: impl Fn(&Value) -> bool) -> Value {
+    for _ in 0..200 {
+        let generator = generators(server).await.remove(0);
+        if done(&generator) {
+            return generator;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("the generator never got there: {:?}", generators(server).await);
+}
+
+async fn depth(server: &TestServer, queue_url: &str) -> u64 {
+    let name = QueueAttributeName::ApproximateNumberOfMessages;
+    let attributes = server
+        .client
+        .get_queue_attributes()
+        .queue_url(queue_url)
+        .attribute_names(name.clone())
+        .send()
+        .await
+        .unwrap();
+    attributes.attributes().unwrap()[&name].parse().unwrap()
+}
+
+storage_matrix!(generators_send_count_messages_then_stop);
+async fn generators_send_count_messages_then_stop() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("jobs").await;
+    let spec = json!({
+        "queue": "jobs",
+        "interval": "5ms",
+        "body_template": "{\"n\": {{seq}}, \"id\": \"{{uuid}}\", \"at\": \"{{now}}\"}",
+        "count": 20,
+    });
+    let (status, body) = server.admin("POST", "/generators", &spec.to_string()).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let generator = wait_for(&server, |g| g["status"] == "completed").await;
+    assert_eq!(generator["sent"], 20);
+    assert_eq!(depth(&server, &queue_url).await, 20);
+
+    let mut numbers = BTreeSet::new();
+    let mut ids = BTreeSet::new();
+    while numbers.len() < 20 {
+        let received = server
+            .client
+            .receive_message()
+            .queue_url(&queue_url)
+            .max_number_of_messages(10)
+            .send()
+            .await
+            .unwrap();
+        for message in received.messages() {
+            let body: Value = serde_json::from_str(message.body().unwrap()).unwrap();
+            numbers.insert(body["n"].as_u64().unwrap());
+            ids.insert(body["id"].as_str().unwrap().to_string());
+            assert!(body["at"].as_str().unwrap().ends_with('Z'), "{}", body);
+        }
+    }
+    assert_eq!(numbers, (1..=20).collect());
+    assert_eq!(ids.len(), 20);
+}
+
+storage_matrix!(config_file_generators_run_until_stopped);
+async fn config_file_generators_run_until_stopped() {
+    let path = std::env::temp_dir().join(format!("local-sqs-{}.yaml", uuid::Uuid::new_v4()));
+    let config = r#"
+queues:
+  - name: ticks
+generators:
+  - queue: ticks
+    interval: 10ms
+    body_template: "tick {{seq}}"
+"#;
+    std::fs::write(&path, config).unwrap();
+    let server = TestServer::start_with(Config {
+        config_file: Some(path.clone()),
+        ..Config::default()
+    })
+    .await;
+    let queue_url = server.client.get_queue_url().queue_name("ticks").send().await.unwrap();
+    let queue_url = queue_url.queue_url().unwrap();
+
+    let generator = wait_for(&server, |g| g["sent"].as_u64().unwrap() >= 3).await;
+    let id = generator["id"].as_str().unwrap();
+    let (status, body) = server.admin("POST", &format!("/generators/{}/stop", id), "").await;
+    assert_eq!(status, 200, "{}", body);
+    let stopped: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(stopped["status"], "stopped");
+
+    // Nothing more is sent once any send under way has finished.
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let sent = generators(&server).await[0]["sent"].as_u64().unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(generators(&server).await[0]["sent"], sent);
+    assert_eq!(depth(&server, queue_url).await, sent);
+    std::fs::remove_file(path).ok();
+}
+
+storage_matrix!(generators_are_checked_and_fail_with_their_queue);
+async fn generators_are_checked_and_fail_with_their_queue() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("doomed").await;
+    for spec in [
+        json!({"queue": "missing", "interval": "1s", "body_template": "m"}),
+        json!({"queue": "doomed", "interval": "soon", "body_template": "m"}),
+        json!({"queue": "doomed", "interval": "0ms", "body_template": "m"}),
+        json!({"queue": "doomed", "interval": "1s", "body_template": "m", "count": 0}),
+        json!({"queue": "doomed", "interval": "1s", "body": "m"}),
+    ] {
+        let (status, body) = server.admin("POST", "/generators", &spec.to_string()).await;
+        assert_eq!(status, 400, "{}: {}", spec, body);
+    }
+    let (status, _) = server.admin("POST", "/generators/nope/stop", "").await;
+    assert_eq!(status, 404);
+
+    let spec = json!({"queue": "doomed", "interval": "10ms", "body_template": "m"});
+    let (status, body) = server.admin("POST", "/generators", &spec.to_string()).await;
+    assert_eq!(status, 200, "{}", body);
+    wait_for(&server, |g| g["sent"].as_u64().unwrap() >= 1).await;
+    server.client.delete_queue().queue_url(&queue_url).send().await.unwrap();
+    let generator = wait_for(&server, |g| g["status"] == "failed").await;
+    let reason = generator["failure_reason"].as_str().unwrap();
+    assert!(reason.contains("QueueDoesNotExist"), "{}", reason);
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use common::{TestServer, storage_matrix};
+use local_sqs::Config;
+use std::time::Duration;
+
+async fn start(idle_queue_ttl: Option<Duration>) -> TestServer {
+    TestServer::start_with(Config {
+        manual_clock: true,
+        idle_queue_ttl,
+        idle_queue_exempt: vec!["pinned".to_string()],
+        ..Config::default()
+    })
+    .await
+}
+
+async fn url(server: &TestServer, name: &str) -> String {
+    let response = server.client.get_queue_url().queue_name(name).send().await;
+    response.unwrap().queue_url.unwrap()
+}
+
+/// Creates one queue per way of escaping idle deletion, plus one that
+/// doesn't.
+async fn create_queues(server: &TestServer) {
+    for name in ["idle", "polled", "pinned", "changed"] {
+        server.create_queue(name).await;
+    }
+    server
+        .client
+        .create_queue()
+        .queue_name("tagged")
+        .tags("local-sqs:keep", "true")
+        .send()
+        .await
+        .unwrap();
+    let full_url = server.create_queue("full").await;
+    server
+        .client
+        .send_message()
+        .queue_url(&full_url)
+        .message_body("still here")
+        .send()
+        .await
+        .unwrap();
+}
+
+storage_matrix!(unused_empty_queues_are_deleted);
+async fn unused_empty_queues_are_deleted() {
+    let server = start(Some(Duration::from_secs(60))).await;
+    create_queues(&server).await;
+
+    server.advance_clock(45).await;
+    let polled_url = url(&server, "polled").await;
+    server
+        .client
+        .receive_message()
+        .queue_url(&polled_url)
+        .send()
+        .await
+        .unwrap();
+    server
+        .client
+        .tag_queue()
+        .queue_url(url(&server, "changed").await)
+        .tags("team", "search")
+        .send()
+        .await
+        .unwrap();
+    server.advance_clock(30).await;
+
+    assert_eq!(
+        server.queue_names().await,
+        ["changed", "full", "pinned", "polled", "tagged"]
+    );
+    let (_, metrics) = server.admin("GET", "/metrics", "").await;
+    assert!(metrics.contains("local_sqs_idle_queues_deleted_total 1\n"), "{}", metrics);
+
+    server.advance_clock(60).await;
+    assert_eq!(server.queue_names().await, ["full", "pinned", "tagged"]);
+}
+
+storage_matrix!(idle_queues_are_kept_by_default);
+async fn idle_queues_are_kept_by_default() {
+    let server = start(None).await;
+    create_queues(&server).await;
+
+    server.advance_clock(30 * 24 * 3600).await;
+    assert_eq!(server.queue_names().await.len(), 6);
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use aws_sdk_sqs::operation::RequestId;
+use common::{TestServer, storage_matrix};
+use local_sqs::Config;
+use serde_json::Value;
+
+async fn in_flight(server: &TestServer, query: &str) -> Value {
+    let (status, body) = server
+        .admin("GET", &format!("/queues/inflight/in-flight{}", query), "")
+        .await;
+    assert_eq!(status, 200, "{}", body);
+    serde_json::from_str(&body).unwrap()
+}
+
+storage_matrix!(in_flight_messages_are_listed_by_deadline);
+async fn in_flight_messages_are_listed_by_deadline() {
+    let server = TestServer::start_with(Config {
+        manual_clock: true,
+        ..Default::default()
+    })
+    .await;
+    let queue_url = server.create_queue("inflight").await;
+    for body in ["slow", "fast", "waiting"] {
+        server
+            .client
+            .send_message()
+            .queue_url(&queue_url)
+            .message_body(body)
+            .send()
+            .await
+            .unwrap();
+    }
+    let receive = |visibility_timeout| {
+        server
+            .client
+            .receive_message()
+            .queue_url(&queue_url)
+            .visibility_timeout(visibility_timeout)
+            .send()
+    };
+    let slow = receive(60).await.unwrap();
+    let fast = receive(10).await.unwrap();
+
+    let listed = in_flight(&server, "").await;
+    let listed = listed.as_array().unwrap();
+    assert_eq!(listed.len(), 2, "{:?}", listed);
+    for (entry, received) in listed.iter().zip([&fast, &slow]) {
+        let message = &received.messages()[0];
+        assert_eq!(entry["message_id"], message.message_id().unwrap());
+        assert_eq!(entry["receipt_handle"], message.receipt_handle().unwrap());
+        assert_eq!(entry["receive_count"], 1);
+        assert_eq!(entry["claimed_by"], received.request_id().unwrap());
+        assert!(entry["visible_from"].is_string());
+    }
+
+    let expiring = in_flight(&server, "?expiring_within=30s").await;
+    assert_eq!(expiring.as_array().unwrap().len(), 1);
+    assert_eq!(expiring[0]["message_id"], listed[0]["message_id"]);
+
+    // Once its deadline passes, a message is visible rather than in flight.
+    server.advance_clock(10).await;
+    let listed = in_flight(&server, "").await;
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    assert_eq!(listed[0]["message_id"], slow.messages()[0].message_id().unwrap());
+
+    let (status, _) = server
+        .admin("GET", "/queues/inflight/in-flight?expiring_within=soon", "")
+        .await;
+    assert_eq!(status, 400);
+    let (status, _) = server.admin("GET", "/queues/missing/in-flight", "").await;
+    assert_eq!(status, 400);
+}
@@ -0,0 +1,435 @@
+mod common;
+
+use aws_sdk_sqs::types::{MessageAttributeValue, QueueAttributeName};
+use common::{TestServer, storage_matrix};
+use std::time::{Duration, Instant};
+
+storage_matrix!(queue_and_message_lifecycle);
+async fn queue_and_message_lifecycle() {
+    let server = TestServer::start().await;
+    let client = &server.client;
+    let queue_url = server.create_queue("lifecycle").await;
+
+    let url = client
+        .get_queue_url()
+        .queue_name("lifecycle")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    assert_eq!(url, queue_url);
+
+    let sent = client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("hello")
+        .message_attributes(
+            "color",
+            MessageAttributeValue::builder()
+                .data_type("String")
+                .string_value("blue")
+                .build()
+                .unwrap(),
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        sent.md5_of_message_body.as_deref(),
+        Some("5d41402abc4b2a76b9719d911017c592")
+    );
+
+    let received = client
+        .receive_message()
+        .queue_url(&queue_url)
+        .wait_time_seconds(1)
+        .message_attribute_names("All")
+        .send()
+        .await
+        .unwrap();
+    let messages = received.messages.unwrap();
+    assert_eq!(messages.len(), 1);
+    let message = &messages[0];
+    assert_eq!(message.message_id, sent.message_id);
+    assert_eq!(message.body.as_deref(), Some("hello"));
+    assert_eq!(
+        message.message_attributes.as_ref().unwrap()["color"]
+            .string_value
+            .as_deref(),
+        Some("blue")
+    );
+    let attributes = message.attributes.as_ref().unwrap();
+    let sent_timestamp = attributes
+        .get(&aws_sdk_sqs::types::MessageSystemAttributeName::SentTimestamp)
+        .unwrap();
+    assert!(sent_timestamp.parse::<i64>().is_ok());
+
+    // Making the message visible again lets it be received a second time.
+    let receipt_handle = message.receipt_handle.clone().unwrap();
+    client
+        .change_message_visibility()
+        .queue_url(&queue_url)
+        .receipt_handle(&receipt_handle)
+        .visibility_timeout(0)
+        .send()
+        .await
+        .unwrap();
+    let received = client
+        .receive_message()
+        .queue_url(&queue_url)
+        .wait_time_seconds(1)
+        .send()
+        .await
+        .unwrap();
+    let message = &received.messages.unwrap()[0];
+    client
+        .delete_message()
+        .queue_url(&queue_url)
+        .receipt_handle(message.receipt_handle.clone().unwrap())
+        .send()
+        .await
+        .unwrap();
+
+    for body in ["a", "b"] {
+        client
+            .send_message()
+            .queue_url(&queue_url)
+            .message_body(body)
+            .send()
+            .await
+            .unwrap();
+    }
+    client.purge_queue().queue_url(&queue_url).send().await.unwrap();
+    let attributes = client
+        .get_queue_attributes()
+        .queue_url(&queue_url)
+        .attribute_names(QueueAttributeName::All)
+        .send()
+        .await
+        .unwrap()
+        .attributes
+        .unwrap();
+    assert_eq!(
+        attributes[&QueueAttributeName::ApproximateNumberOfMessages],
+        "0"
+    );
+
+    client.delete_queue().queue_url(&queue_url).send().await.unwrap();
+    let queues = client.list_queues().send().await.unwrap();
+    assert!(queues.queue_urls.unwrap_or_default().is_empty());
+}
+
+storage_matrix!(queues_are_listed_in_pages);
+async fn queues_are_listed_in_pages() {
+    let server = TestServer::start().await;
+    let mut created = Vec::new();
+    for name in ["page-e", "page-c", "page-a", "page-d", "page-b"] {
+        created.push(server.create_queue(name).await);
+    }
+    server.create_queue("other").await;
+    created.sort();
+
+    let mut pages = Vec::new();
+    let mut next_token = None;
+    loop {
+        let list = server.client.list_queues().queue_name_prefix("page-").max_results(2);
+        let page = list.set_next_token(next_token).send().await.unwrap();
+        pages.push(page.queue_urls().to_vec());
+        next_token = page.next_token;
+        if next_token.is_none() {
+            break;
+        }
+    }
+    assert_eq!(pages, [&created[0..2], &created[2..4], &created[4..]]);
+
+    // Without MaxResults, everything comes at once and there is no token.
+    let all = server.client.list_queues().queue_name_prefix("page-").send().await.unwrap();
+    assert_eq!(all.queue_urls(), created);
+    assert_eq!(all.next_token(), None);
+
+    for token in ["garbage!", "%%%"] {
+        let body = serde_json::json!({"MaxResults": 2, "NextToken": token});
+        let (status, body) = server.action("AmazonSQS.ListQueues", &body.to_string()).await;
+        assert_eq!(status, 400, "{}", body);
+        let error: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(error["__type"], "com.amazonaws.sqs#InvalidParameterValue");
+    }
+}
+
+storage_matrix!(long_poll_returns_when_a_message_arrives);
+async fn long_poll_returns_when_a_message_arrives() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("long-poll").await;
+
+    let client = server.client.clone();
+    let url = queue_url.clone();
+    let sender = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        client
+            .send_message()
+            .queue_url(url)
+            .message_body("late")
+            .send()
+            .await
+            .unwrap();
+    });
+
+    let started = Instant::now();
+    let received = server
+        .client
+        .receive_message()
+        .queue_url(&queue_url)
+        .wait_time_seconds(10)
+        .send()
+        .await
+        .unwrap();
+    sender.await.unwrap();
+
+    assert_eq!(received.messages.unwrap()[0].body.as_deref(), Some("late"));
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+storage_matrix!(a_waiting_long_poll_does_not_hold_up_other_requests);
+async fn a_waiting_long_poll_does_not_hold_up_other_requests() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("contended").await;
+    let poll = server.client.receive_message().queue_url(&queue_url).wait_time_seconds(10);
+    let polling = Instant::now();
+    let poll = tokio::spawn(async move { (poll.send().await.unwrap(), Instant::now()) });
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    // The queue stays usable while the poll waits on it.
+    let started = Instant::now();
+    let attributes = server.client.get_queue_attributes().queue_url(&queue_url);
+    attributes.attribute_names(QueueAttributeName::All).send().await.unwrap();
+    let send = server.client.send_message().queue_url(&queue_url).message_body("woken");
+    send.send().await.unwrap();
+    let sent = Instant::now();
+    assert!(sent - started < Duration::from_millis(250), "sent after {:?}", sent - started);
+
+    let (received, returned) = poll.await.unwrap();
+    assert_eq!(received.messages()[0].body(), Some("woken"));
+    assert!(returned - sent < Duration::from_millis(250), "woke after {:?}", returned - sent);
+    assert!(returned - polling < Duration::from_secs(2), "{:?}", returned - polling);
+}
+
+/// Starts a 10 second long poll on `queue_url` and gives it time to start
+/// waiting.
+async fn start_long_poll(
+    server: &TestServer,
+    queue_url: &str,
+) -> tokio::task::JoinHandle<(Instant, Result<usize, String>)> {
+    let request = server
+        .client
+        .receive_message()
+        .queue_url(queue_url)
+        .wait_time_seconds(10);
+    let poll = tokio::spawn(async move {
+        let result = request.send().await;
+        let result = result
+            .map(|received| received.messages().len())
+            .map_err(|e| e.into_service_error().meta().code().unwrap_or_default().to_string());
+        (Instant::now(), result)
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    poll
+}
+
+storage_matrix!(deleting_a_queue_ends_its_long_polls);
+async fn deleting_a_queue_ends_its_long_polls() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("doomed").await;
+    let poll = start_long_poll(&server, &queue_url).await;
+
+    let deleted = Instant::now();
+    server.client.delete_queue().queue_url(&queue_url).send().await.unwrap();
+    let (returned, result) = poll.await.unwrap();
+    assert_eq!(result, Err("AWS.SimpleQueueService.NonExistentQueue".to_string()));
+    assert!(returned - deleted < Duration::from_millis(250), "{:?}", returned - deleted);
+}
+
+storage_matrix!(purging_a_queue_ends_its_long_polls_empty);
+async fn purging_a_queue_ends_its_long_polls_empty() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("purged").await;
+    let poll = start_long_poll(&server, &queue_url).await;
+
+    let purged = Instant::now();
+    server.client.purge_queue().queue_url(&queue_url).send().await.unwrap();
+    let (returned, result) = poll.await.unwrap();
+    assert_eq!(result, Ok(0));
+    assert!(returned - purged < Duration::from_millis(250), "{:?}", returned - purged);
+}
+
+storage_matrix!(resetting_deletes_queues_as_delete_queue_does);
+async fn resetting_deletes_queues_as_delete_queue_does() {
+    let server = TestServer::start().await;
+    let doomed = server.create_queue("doomed-a").await;
+    let emptied = server.create_queue("doomed-b").await;
+    let kept = server.create_queue("kept").await;
+    for queue_url in [&emptied, &kept] {
+        let send = server.client.send_message().queue_url(queue_url).message_body("12345");
+        send.send().await.unwrap();
+    }
+    let poll = start_long_poll(&server, &doomed).await;
+
+    let reset = Instant::now();
+    let (status, body) = server.admin("POST", "/reset", r#"{"prefix": "doomed-"}"#).await;
+    assert_eq!(status, 200, "{}", body);
+    let summary: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(summary, serde_json::json!({"queues": 2, "messages": 1}));
+    let (returned, result) = poll.await.unwrap();
+    assert_eq!(result, Err("AWS.SimpleQueueService.NonExistentQueue".to_string()));
+    assert!(returned - reset < Duration::from_millis(250), "{:?}", returned - reset);
+
+    let listed = server.client.list_queues().send().await.unwrap();
+    assert_eq!(listed.queue_urls(), [kept]);
+    let (_, usage) = server.admin("GET", "/usage", "").await;
+    let usage: serde_json::Value = serde_json::from_str(&usage).unwrap();
+    assert_eq!(usage["total_bytes"], 5);
+    let (_, events) = server.admin("GET", "/events", "").await;
+    let events: serde_json::Value = serde_json::from_str(&events).unwrap();
+    let deleted: Vec<_> = events["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|event| event["kind"] == "queue_deleted")
+        .map(|event| (event["queue"].as_str().unwrap(), event["details"]["reason"].as_str()))
+        .collect();
+    assert_eq!(deleted, [("doomed-a", Some("reset")), ("doomed-b", Some("reset"))]);
+
+    // Without a prefix, everything goes.
+    let (status, body) = server.admin("POST", "/reset", "").await;
+    assert_eq!(status, 200, "{}", body);
+    assert!(server.client.list_queues().send().await.unwrap().queue_urls().is_empty());
+    let (_, usage) = server.admin("GET", "/usage", "").await;
+    let usage: serde_json::Value = serde_json::from_str(&usage).unwrap();
+    assert_eq!(usage["total_bytes"], 0);
+    // A queue of the same name starts out empty.
+    let kept = server.create_queue("kept").await;
+    let received = server.client.receive_message().queue_url(&kept).send().await.unwrap();
+    assert!(received.messages().is_empty());
+}
+
+storage_matrix!(long_poll_times_out_empty);
+async fn long_poll_times_out_empty() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("empty").await;
+
+    let started = Instant::now();
+    let received = server
+        .client
+        .receive_message()
+        .queue_url(&queue_url)
+        .wait_time_seconds(1)
+        .send()
+        .await
+        .unwrap();
+
+    assert!(received.messages.unwrap_or_default().is_empty());
+    assert!(started.elapsed() >= Duration::from_millis(900));
+}
+
+storage_matrix!(empty_receive_has_no_messages_field);
+async fn empty_receive_has_no_messages_field() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("empty").await;
+
+    let request = serde_json::json!({ "QueueUrl": queue_url });
+    let (status, body) = server
+        .action("AmazonSQS.ReceiveMessage", &request.to_string())
+        .await;
+    assert_eq!(status, 200);
+    let golden: serde_json::Value =
+        serde_json::from_str(include_str!("data/receive_message_empty.json")).unwrap();
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&body).unwrap(), golden);
+
+    // The SDK reads both the empty and the non-empty shape.
+    let received = server
+        .client
+        .receive_message()
+        .queue_url(&queue_url)
+        .send()
+        .await
+        .unwrap();
+    assert!(received.messages().is_empty());
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("hello")
+        .send()
+        .await
+        .unwrap();
+    let received = server
+        .client
+        .receive_message()
+        .queue_url(&queue_url)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(received.messages().len(), 1);
+}
+
+/// Receives from `queue_url`, sending `WaitTimeSeconds` only if given, and
+/// returns how long the receive took and how many messages it got.
+async fn timed_receive(
+    server: &TestServer,
+    queue_url: &str,
+    wait: Option<i32>,
+) -> (Duration, usize) {
+    let mut request = server.client.receive_message().queue_url(queue_url);
+    if let Some(wait) = wait {
+        request = request.wait_time_seconds(wait);
+    }
+    let started = Instant::now();
+    let received = request.send().await.unwrap();
+    (started.elapsed(), received.messages().len())
+}
+
+storage_matrix!(explicit_wait_time_overrides_the_queue_default);
+async fn explicit_wait_time_overrides_the_queue_default() {
+    let server = TestServer::start().await;
+    let queue_url = server
+        .client
+        .create_queue()
+        .queue_name("patient")
+        .attributes(QueueAttributeName::ReceiveMessageWaitTimeSeconds, "20")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+
+    // An explicit zero short-polls.
+    let (elapsed, received) = timed_receive(&server, &queue_url, Some(0)).await;
+    assert_eq!(received, 0);
+    assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+
+    // An explicit wait is used as given, rather than the queue's 20 seconds.
+    let (elapsed, received) = timed_receive(&server, &queue_url, Some(5)).await;
+    assert_eq!(received, 0);
+    assert!(elapsed >= Duration::from_secs(5), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(7), "{:?}", elapsed);
+
+    // Leaving it out inherits the queue's 20 seconds: the receive is still
+    // waiting well past 5 seconds, and returns once a message arrives.
+    let client = server.client.clone();
+    let sender_url = queue_url.clone();
+    let sender = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(7)).await;
+        client
+            .send_message()
+            .queue_url(&sender_url)
+            .message_body("late")
+            .send()
+            .await
+            .unwrap();
+    });
+    let (elapsed, received) = timed_receive(&server, &queue_url, None).await;
+    sender.await.unwrap();
+    assert_eq!(received, 1);
+    assert!(elapsed >= Duration::from_secs(7), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(20), "{:?}", elapsed);
+}
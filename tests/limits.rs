@@ -0,0 +1,283 @@
+mod common;
+
+use aws_sdk_sqs::types::{QueueAttributeName, SendMessageBatchRequestEntry};
+use common::{TestServer, storage_matrix};
+use local_sqs::Config;
+use serde_json::Value;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+
+/// The value of the metric sample starting with `series`, e.g.
+/// `local_sqs_requests_in_flight`.
+async fn metric(server: &TestServer, series: &str) -> Option<u64> {
+    let (status, body) = server.admin("GET", "/metrics", "").await;
+    assert_eq!(status, 200, "{}", body);
+    body.lines()
+        .find_map(|line| line.strip_prefix(series)?.strip_prefix(' ')?.parse().ok())
+}
+
+async fn wait_for_metric(server: &TestServer, series: &str, value: u64) {
+    for _ in 0..100 {
+        if metric(server, series).await == Some(value) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("{} never reached {}", series, value);
+}
+
+/// Starts a receive on `queue_url` that waits `seconds` for a message.
+fn long_poll(server: &TestServer, queue_url: &str, seconds: i32) -> JoinHandle<()> {
+    let request = server
+        .client
+        .receive_message()
+        .queue_url(queue_url)
+        .wait_time_seconds(seconds);
+    tokio::spawn(async move {
+        request.send().await.unwrap();
+    })
+}
+
+fn assert_service_unavailable(status: u16, body: &str) {
+    assert_eq!(status, 503, "{}", body);
+    let error: Value = serde_json::from_str(body).unwrap();
+    assert_eq!(error["__type"], "com.amazonaws.sqs#ServiceUnavailable");
+}
+
+storage_matrix!(requests_over_the_concurrency_limit_are_refused);
+async fn requests_over_the_concurrency_limit_are_refused() {
+    let server = TestServer::start_with(Config {
+        max_concurrent_requests: Some(1),
+        ..Default::default()
+    })
+    .await;
+    let queue_url = server.create_queue("busy").await;
+    let send = format!(r#"{{"QueueUrl":"{}","MessageBody":"hi"}}"#, queue_url);
+
+    let poll = long_poll(&server, &queue_url, 1);
+    wait_for_metric(&server, "local_sqs_requests_in_flight", 1).await;
+    let (status, body) = server.action("AmazonSQS.SendMessage", &send).await;
+    assert_service_unavailable(status, &body);
+    let rejections = r#"local_sqs_limit_rejections_total{limit="requests"}"#;
+    assert_eq!(metric(&server, rejections).await, Some(1));
+
+    poll.await.unwrap();
+    let (status, body) = server.action("AmazonSQS.SendMessage", &send).await;
+    assert_eq!(status, 200, "{}", body);
+}
+
+storage_matrix!(long_polls_are_limited_per_queue);
+async fn long_polls_are_limited_per_queue() {
+    let server = TestServer::start_with(Config {
+        max_long_polls_per_queue: Some(1),
+        ..Default::default()
+    })
+    .await;
+    let busy = server.create_queue("polled").await;
+    let other = server.create_queue("other").await;
+    let receive = |queue_url: &str, wait: u32| {
+        format!(r#"{{"QueueUrl":"{}","WaitTimeSeconds":{}}}"#, queue_url, wait)
+    };
+
+    let poll = long_poll(&server, &busy, 1);
+    wait_for_metric(&server, r#"local_sqs_long_polls_waiting{queue="polled"}"#, 1).await;
+    let (status, body) = server.action("AmazonSQS.ReceiveMessage", &receive(&busy, 1)).await;
+    assert_service_unavailable(status, &body);
+    let rejections = r#"local_sqs_limit_rejections_total{limit="long_polls"}"#;
+    assert_eq!(metric(&server, rejections).await, Some(1));
+
+    // Short polls, and long polls on other queues, are unaffected.
+    let (status, body) = server.action("AmazonSQS.ReceiveMessage", &receive(&busy, 0)).await;
+    assert_eq!(status, 200, "{}", body);
+    let (status, body) = server.action("AmazonSQS.ReceiveMessage", &receive(&other, 1)).await;
+    assert_eq!(status, 200, "{}", body);
+
+    poll.await.unwrap();
+    wait_for_metric(&server, r#"local_sqs_long_polls_waiting{queue="polled"}"#, 0).await;
+}
+
+storage_matrix!(connections_over_the_limit_are_refused);
+async fn connections_over_the_limit_are_refused() {
+    let server = TestServer::start_with(Config {
+        max_connections: Some(1),
+        ..Default::default()
+    })
+    .await;
+
+    let held = TcpStream::connect(server.addr).await.unwrap();
+    let (status, body) = server.action("AmazonSQS.ListQueues", "{}").await;
+    assert_service_unavailable(status, &body);
+
+    drop(held);
+    for _ in 0..100 {
+        let (status, _) = server.action("AmazonSQS.ListQueues", "{}").await;
+        if status == 200 {
+            let rejections = r#"local_sqs_limit_rejections_total{limit="connections"}"#;
+            assert!(metric(&server, rejections).await >= Some(1));
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("the connection slot was never released");
+}
+
+storage_matrix!(sends_beyond_max_queue_length_are_refused);
+async fn sends_beyond_max_queue_length_are_refused() {
+    let server = TestServer::start().await;
+    let url = server.create_queue("capped").await;
+    let client = &server.client;
+    let set_cap = |cap: &'static str| {
+        client
+            .set_queue_attributes()
+            .queue_url(&url)
+            .attributes(QueueAttributeName::from("MaxQueueLength"), cap)
+            .send()
+    };
+    set_cap("2").await.unwrap();
+    client.send_message().queue_url(&url).message_body("1").send().await.unwrap();
+
+    // In-flight messages count towards the cap.
+    client.receive_message().queue_url(&url).send().await.unwrap();
+    let entries = ["2", "3"].map(|id| {
+        SendMessageBatchRequestEntry::builder().id(id).message_body(id).build().unwrap()
+    });
+    let batch = client.send_message_batch().queue_url(&url).set_entries(Some(entries.to_vec()));
+    let batch = batch.send().await.unwrap();
+    assert_eq!(batch.successful().len(), 1);
+    let failed = &batch.failed()[0];
+    assert_eq!((failed.id(), failed.code(), failed.sender_fault()), ("3", "OverLimit", true));
+
+    let body = serde_json::json!({"QueueUrl": url, "MessageBody": "4"}).to_string();
+    let (status, body) = server.action("AmazonSQS.SendMessage", &body).await;
+    assert_eq!(status, 403, "{}", body);
+    let error: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(error["__type"], "com.amazonaws.sqs#OverLimit");
+    let rejected = r#"local_sqs_sends_rejected_total{queue="capped"}"#;
+    assert_eq!(metric(&server, rejected).await, Some(2));
+
+    // The cap can be raised while the queue is full.
+    set_cap("3").await.unwrap();
+    client.send_message().queue_url(&url).message_body("4").send().await.unwrap();
+}
+
+storage_matrix!(the_queue_full_error_code_is_configurable);
+async fn the_queue_full_error_code_is_configurable() {
+    let server = TestServer::start_with(Config {
+        queue_full_error_code: "RequestThrottled".to_string(),
+        ..Default::default()
+    })
+    .await;
+    let url = server.create_queue("throttled").await;
+    let cap = serde_json::json!({"QueueUrl": url, "Attributes": {"MaxQueueLength": "1"}});
+    let (status, body) = server.action("AmazonSQS.SetQueueAttributes", &cap.to_string()).await;
+    assert_eq!(status, 200, "{}", body);
+    server.client.send_message().queue_url(&url).message_body("m").send().await.unwrap();
+
+    let error = server.client.send_message().queue_url(&url).message_body("m").send().await;
+    let error = error.unwrap_err();
+    assert_eq!(error.raw_response().map(|r| r.status().as_u16()), Some(403));
+    assert_eq!(error.into_service_error().meta().code(), Some("RequestThrottled"));
+}
+
+storage_matrix!(byte_usage_follows_sends_deletes_and_purges);
+async fn byte_usage_follows_sends_deletes_and_purges() {
+    let server = TestServer::start_with(Config {
+        max_total_bytes: Some(20),
+        max_messages_per_queue: Some(3),
+        ..Default::default()
+    })
+    .await;
+    let usage = || async {
+        let (status, body) = server.admin("GET", "/usage", "").await;
+        assert_eq!(status, 200, "{}", body);
+        serde_json::from_str::<Value>(&body).unwrap()
+    };
+    let initial = usage().await;
+    assert_eq!(initial["total_bytes"], 0);
+    assert_eq!(initial["max_total_bytes"], 20);
+    assert_eq!(initial["max_messages_per_queue"], 3);
+
+    let first = server.create_queue("first").await;
+    let second = server.create_queue("second").await;
+    let client = &server.client;
+    let send = |url: &str, body: &str| client.send_message().queue_url(url).message_body(body);
+    for body in ["12345", "1234567890"] {
+        send(&first, body).send().await.unwrap();
+    }
+    send(&second, "123").send().await.unwrap();
+    assert_eq!(usage().await["total_bytes"], 18);
+
+    // Over the server-wide limit, whichever queue it is for.
+    let error = send(&second, "123").send().await.unwrap_err().into_service_error();
+    assert_eq!(error.meta().code(), Some("OverLimit"));
+    assert_eq!(usage().await["total_bytes"], 18);
+
+    let received = client.receive_message().queue_url(&first).send().await.unwrap();
+    let message = &received.messages()[0];
+    let delete = client.delete_message().queue_url(&first);
+    delete.receipt_handle(message.receipt_handle().unwrap()).send().await.unwrap();
+    let deleted = message.body().unwrap().len();
+    assert_eq!(usage().await["total_bytes"], 18 - deleted);
+
+    client.purge_queue().queue_url(&first).send().await.unwrap();
+    assert_eq!(usage().await["total_bytes"], 3);
+    client.delete_queue().queue_url(&second).send().await.unwrap();
+    assert_eq!(usage().await["total_bytes"], 0);
+
+    // Freed bytes can be used again, up to the per-queue message cap.
+    for body in ["a", "b", "c"] {
+        send(&first, body).send().await.unwrap();
+    }
+    let error = send(&first, "d").send().await.unwrap_err().into_service_error();
+    assert_eq!(error.meta().code(), Some("OverLimit"));
+    assert_eq!(usage().await["total_bytes"], 3);
+}
+
+storage_matrix!(the_byte_limit_counts_bodies_as_stored);
+async fn the_byte_limit_counts_bodies_as_stored() {
+    let server = TestServer::start_with(Config {
+        max_total_bytes: Some(1000),
+        compress_bodies_above: Some(100),
+        ..Default::default()
+    })
+    .await;
+    let queue_url = server.create_queue("compressed").await;
+
+    // Far longer than the limit as sent, but not as stored.
+    server.send(&queue_url, &"a".repeat(10_000)).await;
+    let (_, body) = server.admin("GET", "/usage", "").await;
+    let total = serde_json::from_str::<Value>(&body).unwrap()["total_bytes"].as_u64().unwrap();
+    assert!(0 < total && total < 1000, "{}", total);
+}
+
+storage_matrix!(
+    concurrent_sends_stay_within_the_byte_limit,
+    flavor = "multi_thread",
+    worker_threads = 4
+);
+async fn concurrent_sends_stay_within_the_byte_limit() {
+    let server = TestServer::start_with(Config {
+        max_total_bytes: Some(100),
+        ..Default::default()
+    })
+    .await;
+    let mut sends = Vec::new();
+    for queue in 0..4 {
+        let queue_url = server.create_queue(&format!("queue-{}", queue)).await;
+        for _ in 0..10 {
+            let (client, queue_url) = (server.client.clone(), queue_url.clone());
+            sends.push(tokio::spawn(async move {
+                let sent = client.send_message().queue_url(queue_url).message_body("0123456789");
+                sent.send().await.is_ok()
+            }));
+        }
+    }
+    let mut accepted = 0;
+    for send in sends {
+        accepted += u64::from(send.await.unwrap());
+    }
+    assert_eq!(accepted, 10);
+    let (_, body) = server.admin("GET", "/usage", "").await;
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap()["total_bytes"], 100);
+}
@@ -0,0 +1,370 @@
+mod common;
+
+use aws_sdk_sqs::error::ProvideErrorMetadata;
+use aws_sdk_sqs::operation::send_message::SendMessageError;
+use aws_sdk_sqs::types::{MessageAttributeValue, SendMessageBatchRequestEntry};
+use common::{TestServer, storage_matrix};
+
+fn string_value(value: &str) -> MessageAttributeValue {
+    MessageAttributeValue::builder()
+        .data_type("String")
+        .string_value(value)
+        .build()
+        .unwrap()
+}
+
+async fn send_with_attributes(server: &TestServer, names: &[&str]) -> Result<(), SendMessageError> {
+    let queue_url = server.create_queue("attributes").await;
+    let mut request = server
+        .client
+        .send_message()
+        .queue_url(queue_url)
+        .message_body("body");
+    for name in names {
+        request = request.message_attributes(*name, string_value("v"));
+    }
+    request
+        .send()
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into_service_error())
+}
+
+fn assert_invalid(err: SendMessageError, expected: &str) {
+    assert_eq!(err.code(), Some("InvalidParameterValue"), "{:?}", err);
+    let message = err.message().unwrap_or_default();
+    assert!(message.contains(expected), "{}", message);
+}
+
+storage_matrix!(ten_attributes_are_accepted);
+async fn ten_attributes_are_accepted() {
+    let server = TestServer::start().await;
+    let names: Vec<String> = (0..10).map(|i| format!("attr{}", i)).collect();
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    send_with_attributes(&server, &names).await.unwrap();
+}
+
+storage_matrix!(eleventh_attribute_is_rejected);
+async fn eleventh_attribute_is_rejected() {
+    let server = TestServer::start().await;
+    let names: Vec<String> = (0..11).map(|i| format!("attr{}", i)).collect();
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let err = send_with_attributes(&server, &names).await.unwrap_err();
+    assert_invalid(err, "Number of message attributes [11]");
+}
+
+storage_matrix!(reserved_prefix_is_rejected);
+async fn reserved_prefix_is_rejected() {
+    let server = TestServer::start().await;
+    let err = send_with_attributes(&server, &["AWS.Trace"]).await.unwrap_err();
+    assert_invalid(err, "'AWS.Trace'");
+    let err = send_with_attributes(&server, &["amazon.thing"]).await.unwrap_err();
+    assert_invalid(err, "'amazon.thing'");
+}
+
+storage_matrix!(consecutive_and_trailing_periods_are_rejected);
+async fn consecutive_and_trailing_periods_are_rejected() {
+    let server = TestServer::start().await;
+    let err = send_with_attributes(&server, &["a..b"]).await.unwrap_err();
+    assert_invalid(err, "'a..b'");
+    let err = send_with_attributes(&server, &["trailing."]).await.unwrap_err();
+    assert_invalid(err, "'trailing.'");
+    send_with_attributes(&server, &["a.b.c"]).await.unwrap();
+}
+
+storage_matrix!(names_differing_only_in_case_are_rejected);
+async fn names_differing_only_in_case_are_rejected() {
+    let server = TestServer::start().await;
+    let err = send_with_attributes(&server, &["Color", "color"]).await.unwrap_err();
+    assert_invalid(err, "duplicate");
+}
+
+storage_matrix!(overlong_name_is_rejected);
+async fn overlong_name_is_rejected() {
+    let server = TestServer::start().await;
+    let name = "n".repeat(257);
+    let err = send_with_attributes(&server, &[&name]).await.unwrap_err();
+    assert_invalid(err, "at most 256");
+    send_with_attributes(&server, &[&"n".repeat(256)]).await.unwrap();
+}
+
+storage_matrix!(invalid_batch_entry_fails_only_that_entry);
+async fn invalid_batch_entry_fails_only_that_entry() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("batch-attributes").await;
+
+    let response = server
+        .client
+        .send_message_batch()
+        .queue_url(queue_url)
+        .entries(
+            SendMessageBatchRequestEntry::builder()
+                .id("good")
+                .message_body("ok")
+                .message_attributes("fine", string_value("v"))
+                .build()
+                .unwrap(),
+        )
+        .entries(
+            SendMessageBatchRequestEntry::builder()
+                .id("bad")
+                .message_body("ok")
+                .message_attributes("AWS.reserved", string_value("v"))
+                .build()
+                .unwrap(),
+        )
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.successful.len(), 1);
+    assert_eq!(response.failed.len(), 1);
+    assert_eq!(response.failed[0].id, "bad");
+    assert_eq!(response.failed[0].code, "InvalidParameterValue");
+}
+
+async fn send_attribute(
+    server: &TestServer,
+    value: MessageAttributeValue,
+) -> Result<(), SendMessageError> {
+    let queue_url = server.create_queue("data-types").await;
+    server
+        .client
+        .send_message()
+        .queue_url(queue_url)
+        .message_body("body")
+        .message_attributes("attr", value)
+        .send()
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into_service_error())
+}
+
+fn attribute(data_type: &str, string_value: Option<&str>, binary_value: Option<&[u8]>) -> MessageAttributeValue {
+    MessageAttributeValue::builder()
+        .data_type(data_type)
+        .set_string_value(string_value.map(str::to_string))
+        .set_binary_value(binary_value.map(aws_sdk_sqs::primitives::Blob::new))
+        .build()
+        .unwrap()
+}
+
+storage_matrix!(valid_data_types_are_accepted);
+async fn valid_data_types_are_accepted() {
+    let server = TestServer::start().await;
+    for value in [
+        attribute("String", Some("text"), None),
+        attribute("String.json", Some("{}"), None),
+        attribute("Number", Some("-12.5"), None),
+        attribute("Number.float", Some("1.5E10"), None),
+        attribute("Number", Some("0"), None),
+        attribute("Binary", None, Some(b"\x00\x01")),
+        attribute("Binary.gif", None, Some(b"GIF89a")),
+    ] {
+        send_attribute(&server, value.clone())
+            .await
+            .unwrap_or_else(|e| panic!("{:?}: {:?}", value, e));
+    }
+}
+
+storage_matrix!(unknown_base_type_is_rejected);
+async fn unknown_base_type_is_rejected() {
+    let server = TestServer::start().await;
+    for data_type in ["Text", "string", "Number.", "Binaryish"] {
+        let err = send_attribute(&server, attribute(data_type, Some("x"), None))
+            .await
+            .unwrap_err();
+        assert_invalid(err, "data type");
+    }
+}
+
+storage_matrix!(invalid_numbers_are_rejected);
+async fn invalid_numbers_are_rejected() {
+    let server = TestServer::start().await;
+    for number in ["abc", "1.2.3", "NaN", "inf", "1e127", "1e-129", &"9".repeat(39)] {
+        let err = send_attribute(&server, attribute("Number", Some(number), None))
+            .await
+            .unwrap_err();
+        assert_invalid(err, "38 digits");
+    }
+    send_attribute(&server, attribute("Number", Some(&"9".repeat(38)), None))
+        .await
+        .unwrap();
+}
+
+storage_matrix!(value_must_match_the_data_type);
+async fn value_must_match_the_data_type() {
+    let server = TestServer::start().await;
+
+    let err = send_attribute(&server, attribute("String", None, Some(b"x")))
+        .await
+        .unwrap_err();
+    assert_invalid(err, "BinaryValue is not allowed");
+
+    let err = send_attribute(&server, attribute("Binary", Some("x"), None))
+        .await
+        .unwrap_err();
+    assert_invalid(err, "StringValue is not allowed");
+
+    let err = send_attribute(&server, attribute("String", Some(""), None))
+        .await
+        .unwrap_err();
+    assert_invalid(err, "must not be empty");
+
+    let err = send_attribute(&server, attribute("Binary", None, Some(b"")))
+        .await
+        .unwrap_err();
+    assert_invalid(err, "must not be empty");
+}
+
+/// Receives from a queue holding one message with a `color` attribute,
+/// naming `message_attribute_names`, and returns the raw response.
+async fn receive_raw(message_attribute_names: &[&str]) -> serde_json::Value {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("golden").await;
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("hello")
+        .message_attributes("color", string_value("blue"))
+        .send()
+        .await
+        .unwrap();
+
+    let request = serde_json::json!({
+        "QueueUrl": queue_url,
+        "MessageSystemAttributeNames": ["All"],
+        "MessageAttributeNames": message_attribute_names,
+    });
+    let (status, body) = server
+        .action("AmazonSQS.ReceiveMessage", &request.to_string())
+        .await;
+    assert_eq!(status, 200, "{}", body);
+    serde_json::from_str(&body).unwrap()
+}
+
+/// Checks that `actual` has exactly the fields of the captured AWS response
+/// in `golden`, and the same message attributes and digests.
+fn assert_matches_golden(actual: &serde_json::Value, golden: &str) {
+    let expected: serde_json::Value = serde_json::from_str(golden).unwrap();
+    let fields = |message: &serde_json::Value| -> Vec<String> {
+        let mut fields: Vec<String> = message.as_object().unwrap().keys().cloned().collect();
+        fields.sort();
+        fields
+    };
+    let (actual, expected) = (&actual["Messages"][0], &expected["Messages"][0]);
+    assert_eq!(fields(actual), fields(expected));
+    for field in ["MD5OfBody", "MD5OfMessageAttributes", "MessageAttributes"] {
+        assert_eq!(actual.get(field), expected.get(field), "{}", field);
+    }
+}
+
+storage_matrix!(message_attributes_are_omitted_unless_requested);
+async fn message_attributes_are_omitted_unless_requested() {
+    let golden = include_str!("data/receive_message_without_message_attributes.json");
+    assert_matches_golden(&receive_raw(&[]).await, golden);
+    // Nothing matches, so nothing is returned either.
+    assert_matches_golden(&receive_raw(&["size", "shape.*"]).await, golden);
+}
+
+storage_matrix!(requested_message_attributes_are_returned);
+async fn requested_message_attributes_are_returned() {
+    let golden = include_str!("data/receive_message_with_message_attributes.json");
+    for names in [&["All"][..], &[".*"], &["color"], &["size", "color"]] {
+        assert_matches_golden(&receive_raw(names).await, golden);
+    }
+}
+
+storage_matrix!(message_attributes_are_selected_by_prefix);
+async fn message_attributes_are_selected_by_prefix() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("prefix").await;
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("hello")
+        .message_attributes("shape.kind", string_value("round"))
+        .message_attributes("shape.size", string_value("large"))
+        .message_attributes("shapeless", string_value("yes"))
+        .message_attributes("color", string_value("blue"))
+        .send()
+        .await
+        .unwrap();
+
+    let messages = server
+        .client
+        .receive_message()
+        .queue_url(&queue_url)
+        .message_attribute_names("shape.*")
+        .send()
+        .await
+        .unwrap()
+        .messages
+        .unwrap();
+    let mut names: Vec<&String> = messages[0].message_attributes().unwrap().keys().collect();
+    names.sort();
+    assert_eq!(names, ["shape.kind", "shape.size"]);
+}
+
+/// Receives with `request` merged into a `ReceiveMessage` body and returns
+/// the names of the system attributes on the message received.
+async fn received_system_attributes(request: serde_json::Value) -> Vec<String> {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("system").await;
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("hello")
+        .send()
+        .await
+        .unwrap();
+
+    let mut body = serde_json::json!({ "QueueUrl": queue_url });
+    body.as_object_mut()
+        .unwrap()
+        .extend(request.as_object().unwrap().clone());
+    let (status, response) = server
+        .action("AmazonSQS.ReceiveMessage", &body.to_string())
+        .await;
+    assert_eq!(status, 200, "{}", response);
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    let mut names: Vec<String> = match response["Messages"][0]["Attributes"].as_object() {
+        Some(attributes) => attributes.keys().cloned().collect(),
+        None => Vec::new(),
+    };
+    names.sort();
+    names
+}
+
+storage_matrix!(system_attributes_are_selected_by_either_field_name);
+async fn system_attributes_are_selected_by_either_field_name() {
+    let every = received_system_attributes(serde_json::json!({})).await;
+    assert!(every.len() > 2, "{:?}", every);
+
+    for field in ["AttributeNames", "MessageSystemAttributeNames"] {
+        let request = serde_json::json!({ field: ["SentTimestamp", "ApproximateReceiveCount"] });
+        assert_eq!(
+            received_system_attributes(request).await,
+            ["ApproximateReceiveCount", "SentTimestamp"],
+            "{}",
+            field
+        );
+        let request = serde_json::json!({ field: ["All"] });
+        assert_eq!(received_system_attributes(request).await, every, "{}", field);
+    }
+
+    // The current field wins over the deprecated one.
+    let request = serde_json::json!({
+        "AttributeNames": ["SentTimestamp"],
+        "MessageSystemAttributeNames": ["ApproximateReceiveCount"],
+    });
+    assert_eq!(received_system_attributes(request).await, ["ApproximateReceiveCount"]);
+    let request = serde_json::json!({
+        "AttributeNames": ["SentTimestamp"],
+        "MessageSystemAttributeNames": ["All"],
+    });
+    assert_eq!(received_system_attributes(request).await, every);
+}
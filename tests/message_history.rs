@@ -0,0 +1,148 @@
+mod common;
+
+use common::{TestServer, storage_matrix};
+use local_sqs::Config;
+use serde_json::Value;
+
+fn config() -> Config {
+    Config {
+        manual_clock: true,
+        message_history: 10,
+        ..Default::default()
+    }
+}
+
+async fn get_history(server: &TestServer, queue: &str, message_id: &str) -> (u16, Value) {
+    let path = format!("/queues/{}/messages/{}/history", queue, message_id);
+    let (status, body) = server.admin("GET", &path, "").await;
+    (status, serde_json::from_str(&body).unwrap())
+}
+
+fn kinds(history: &Value) -> Vec<&str> {
+    let entries = history.as_array().unwrap();
+    entries.iter().map(|e| e["kind"].as_str().unwrap()).collect()
+}
+
+storage_matrix!(history_follows_a_message_until_it_is_deleted);
+async fn history_follows_a_message_until_it_is_deleted() {
+    let server = TestServer::start_with(config()).await;
+    let queue_url = server.create_queue("audited").await;
+    let sent = server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("traced")
+        .send()
+        .await
+        .unwrap();
+    let message_id = sent.message_id.unwrap();
+
+    let first = server.receive(&queue_url, None).await.remove(0);
+    server.advance_clock(31).await;
+    let second = server.receive(&queue_url, None).await.remove(0);
+    assert_ne!(first.receipt_handle, second.receipt_handle);
+
+    // Until it is deleted, the history is on the peeked message too.
+    let (status, body) = server.admin("GET", "/queues/audited/messages", "").await;
+    assert_eq!(status, 200);
+    let peeked: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(kinds(&peeked[0]["history"]), ["received", "released", "received"]);
+
+    server
+        .client
+        .delete_message()
+        .queue_url(&queue_url)
+        .receipt_handle(second.receipt_handle().unwrap())
+        .send()
+        .await
+        .unwrap();
+
+    let (status, body) = get_history(&server, "audited", &message_id).await;
+    assert_eq!(status, 200, "{}", body);
+    let history = &body["history"];
+    assert_eq!(kinds(history), ["received", "released", "received", "deleted"]);
+    assert_eq!(history[0]["receipt_handle"].as_str(), first.receipt_handle());
+    assert!(history[0]["request_id"].is_string());
+    assert_eq!(
+        history[0]["visible_until"].as_i64().unwrap() - history[0]["timestamp"].as_i64().unwrap(),
+        30_000
+    );
+    assert_eq!(history[1]["timestamp"], history[0]["visible_until"]);
+    assert!(history[1].get("request_id").is_none());
+    assert_eq!(history[2]["receipt_handle"].as_str(), second.receipt_handle());
+    assert_ne!(history[2]["request_id"], history[0]["request_id"]);
+    assert_eq!(history[3]["receipt_handle"].as_str(), second.receipt_handle());
+
+    let (status, _) = get_history(&server, "audited", "no-such-message").await;
+    assert_eq!(status, 404);
+}
+
+storage_matrix!(visibility_changes_and_dead_letter_moves_are_recorded);
+async fn visibility_changes_and_dead_letter_moves_are_recorded() {
+    let server = TestServer::start_with(config()).await;
+    server.create_queue("audited-dlq").await;
+    let dlq_arn = "arn:aws:sqs:us-east-1:000000000000:audited-dlq";
+    let queue_url = server
+        .client
+        .create_queue()
+        .queue_name("audited")
+        .attributes(
+            aws_sdk_sqs::types::QueueAttributeName::RedrivePolicy,
+            format!(r#"{{"deadLetterTargetArn":"{}","maxReceiveCount":"1"}}"#, dlq_arn),
+        )
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    let sent = server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("doomed")
+        .send()
+        .await
+        .unwrap();
+    let message_id = sent.message_id.unwrap();
+
+    let received = server.receive(&queue_url, None).await.remove(0);
+    server
+        .client
+        .change_message_visibility()
+        .queue_url(&queue_url)
+        .receipt_handle(received.receipt_handle().unwrap())
+        .visibility_timeout(0)
+        .send()
+        .await
+        .unwrap();
+    let empty = server.client.receive_message().queue_url(&queue_url).send().await.unwrap();
+    assert!(empty.messages().is_empty());
+
+    let (status, body) = get_history(&server, "audited-dlq", &message_id).await;
+    assert_eq!(status, 200, "{}", body);
+    let history = &body["history"];
+    assert_eq!(kinds(history), ["received", "visibility_changed", "dead_lettered"]);
+    assert_eq!(history[1]["visible_until"], history[1]["timestamp"]);
+    assert_eq!(history[2]["dead_letter_target_arn"], dlq_arn);
+}
+
+storage_matrix!(histories_are_off_by_default);
+async fn histories_are_off_by_default() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("unaudited").await;
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("untraced")
+        .send()
+        .await
+        .unwrap();
+
+    let (_, body) = server.admin("GET", "/queues/unaudited/messages", "").await;
+    let peeked: Value = serde_json::from_str(&body).unwrap();
+    assert!(peeked[0].get("history").is_none());
+    let message_id = peeked[0]["id"].as_str().unwrap();
+    let (status, _) = get_history(&server, "unaudited", message_id).await;
+    assert_eq!(status, 400);
+}
@@ -0,0 +1,228 @@
+mod common;
+
+use aws_sdk_sqs::error::ProvideErrorMetadata;
+use aws_sdk_sqs::types::{MessageAttributeValue, QueueAttributeName, SendMessageBatchRequestEntry};
+use common::{TestServer, storage_matrix};
+use local_sqs::Config;
+
+const MAX_SIZE: usize = 262144;
+
+// Name (4) + data type (6) + value (4) = 14 bytes.
+fn attribute() -> MessageAttributeValue {
+    MessageAttributeValue::builder()
+        .data_type("String")
+        .string_value("blue")
+        .build()
+        .unwrap()
+}
+
+storage_matrix!(attributes_count_toward_the_size_limit);
+async fn attributes_count_toward_the_size_limit() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("size").await;
+    let client = &server.client;
+
+    client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("x".repeat(MAX_SIZE - 14))
+        .message_attributes("tint", attribute())
+        .send()
+        .await
+        .unwrap();
+
+    // The body alone fits, but not together with the attribute.
+    let err = client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("x".repeat(MAX_SIZE - 13))
+        .message_attributes("tint", attribute())
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert_eq!(err.code(), Some("InvalidParameterValue"), "{:?}", err);
+    assert!(err.message().unwrap().contains("262144 bytes"));
+}
+
+storage_matrix!(batch_entries_are_measured_with_attributes);
+async fn batch_entries_are_measured_with_attributes() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("batch-size").await;
+    server
+        .client
+        .set_queue_attributes()
+        .queue_url(&queue_url)
+        .attributes(QueueAttributeName::MaximumMessageSize, "1024")
+        .send()
+        .await
+        .unwrap();
+
+    let response = server
+        .client
+        .send_message_batch()
+        .queue_url(&queue_url)
+        .entries(
+            SendMessageBatchRequestEntry::builder()
+                .id("fits")
+                .message_body("x".repeat(1024 - 14))
+                .message_attributes("tint", attribute())
+                .build()
+                .unwrap(),
+        )
+        .entries(
+            SendMessageBatchRequestEntry::builder()
+                .id("too-big")
+                .message_body("x".repeat(1024))
+                .message_attributes("tint", attribute())
+                .build()
+                .unwrap(),
+        )
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.successful[0].id, "fits");
+    assert_eq!(response.failed[0].id, "too-big");
+    assert_eq!(response.failed[0].code, "InvalidParameterValue");
+}
+
+const LARGE_BODY: usize = 700 * 1024;
+
+storage_matrix!(large_messages_are_rejected_by_default);
+async fn large_messages_are_rejected_by_default() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("classic").await;
+
+    let err = server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("x".repeat(LARGE_BODY))
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert_eq!(err.code(), Some("InvalidParameterValue"), "{:?}", err);
+
+    let err = server
+        .client
+        .create_queue()
+        .queue_name("large")
+        .attributes(QueueAttributeName::MaximumMessageSize, "1048576")
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert_eq!(err.code(), Some("InvalidAttributeValue"), "{:?}", err);
+}
+
+storage_matrix!(raised_limit_allows_messages_up_to_one_mib);
+async fn raised_limit_allows_messages_up_to_one_mib() {
+    let server = TestServer::start_with(Config {
+        max_message_size_limit: 1048576,
+        ..Default::default()
+    })
+    .await;
+    let queue_url = server
+        .client
+        .create_queue()
+        .queue_name("large")
+        .attributes(QueueAttributeName::MaximumMessageSize, "1048576")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("x".repeat(LARGE_BODY))
+        .send()
+        .await
+        .unwrap();
+    // Together well over the classic 262144-byte batch limit.
+    let entry = |id: &str| {
+        SendMessageBatchRequestEntry::builder()
+            .id(id)
+            .message_body("y".repeat(400 * 1024))
+            .build()
+            .unwrap()
+    };
+    let response = server
+        .client
+        .send_message_batch()
+        .queue_url(&queue_url)
+        .entries(entry("a"))
+        .entries(entry("b"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.successful.len(), 2, "{:?}", response.failed);
+
+    let received = server
+        .client
+        .receive_message()
+        .queue_url(&queue_url)
+        .max_number_of_messages(10)
+        .send()
+        .await
+        .unwrap();
+    let sizes: Vec<usize> = received.messages().iter().map(|m| m.body().unwrap().len()).collect();
+    assert_eq!(sizes, [LARGE_BODY, 400 * 1024, 400 * 1024]);
+}
+
+async fn post_raw(server: &TestServer, body: String) -> (u16, serde_json::Value) {
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/", server.addr))
+        .header("X-Amz-Target", "AmazonSQS.SendMessage")
+        .header("Content-Type", "application/x-amz-json-1.0")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    let status = response.status().as_u16();
+    (status, response.json().await.unwrap())
+}
+
+storage_matrix!(oversized_requests_get_an_sqs_error);
+async fn oversized_requests_get_an_sqs_error() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("oversized").await;
+    let limit = local_sqs::request_body_limit(MAX_SIZE);
+
+    let body = format!(r#"{{"QueueUrl":"{}","MessageBody":"{}"}}"#, queue_url, "x".repeat(limit));
+    let (status, error) = post_raw(&server, body).await;
+    assert_eq!(status, 413);
+    assert_eq!(error["__type"], "com.amazonaws.sqs#RequestEntityTooLarge");
+    assert!(error["message"].as_str().unwrap().contains(&limit.to_string()), "{}", error);
+
+    // The server keeps serving afterwards.
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("still here")
+        .send()
+        .await
+        .unwrap();
+}
+
+storage_matrix!(body_limit_scales_with_the_message_size_limit);
+async fn body_limit_scales_with_the_message_size_limit() {
+    let server = TestServer::start_with(Config {
+        max_message_size_limit: 1048576,
+        ..Default::default()
+    })
+    .await;
+    let queue_url = server.create_queue("scaled").await;
+
+    // Over the default body limit, so only the message size check rejects it.
+    let body = "x".repeat(local_sqs::request_body_limit(MAX_SIZE));
+    let body = format!(r#"{{"QueueUrl":"{}","MessageBody":"{}"}}"#, queue_url, body);
+    let (status, error) = post_raw(&server, body).await;
+    assert_eq!(status, 400);
+    assert_eq!(error["__type"], "com.amazonaws.sqs#InvalidParameterValue");
+}
@@ -0,0 +1,217 @@
+mod common;
+
+use aws_sdk_sqs::types::QueueAttributeName;
+use common::{TestServer, storage_matrix};
+use local_sqs::Config;
+use serde_json::{json, Value};
+
+async fn start(histogram_buckets: Vec<f64>) -> TestServer {
+    TestServer::start_with(Config {
+        manual_clock: true,
+        histogram_buckets,
+        ..Config::default()
+    })
+    .await
+}
+
+async fn stats_of(server: &TestServer, queue: &str) -> Value {
+    let (status, body) = server
+        .admin("GET", &format!("/queues/{}/stats", queue), "")
+        .await;
+    assert_eq!(status, 200, "{}", body);
+    serde_json::from_str(&body).unwrap()
+}
+
+async fn latency_of(server: &TestServer, queue: &str) -> Value {
+    stats_of(server, queue).await["latency"].clone()
+}
+
+storage_matrix!(receive_and_delete_latencies_are_recorded);
+async fn receive_and_delete_latencies_are_recorded() {
+    let server = start(vec![1.0, 5.0, 60.0]).await;
+    let queue_url = server.create_queue("timed").await;
+    server.send(&queue_url, "tick").await;
+
+    server.advance_clock(3).await;
+    assert_eq!(server.receive(&queue_url, Some(0)).await.len(), 1);
+    server.advance_clock(30).await;
+    let message = server.receive(&queue_url, Some(600)).await.remove(0);
+    server.advance_clock(2).await;
+    server
+        .client
+        .delete_message()
+        .queue_url(&queue_url)
+        .receipt_handle(message.receipt_handle().unwrap())
+        .send()
+        .await
+        .unwrap();
+
+    let latency = latency_of(&server, "timed").await;
+    assert_eq!(
+        latency["receive_age"],
+        json!({
+            "count": 2,
+            "sum": 36.0,
+            "buckets": [
+                {"le": "1", "count": 0},
+                {"le": "5", "count": 1},
+                {"le": "60", "count": 2},
+                {"le": "+Inf", "count": 2},
+            ],
+        })
+    );
+    // Only the first receive counts towards send-to-first-receive.
+    assert_eq!(latency["send_to_first_receive"]["count"], 1);
+    assert_eq!(latency["send_to_first_receive"]["sum"], 3.0);
+    assert_eq!(latency["first_receive_to_delete"]["count"], 1);
+    assert_eq!(latency["first_receive_to_delete"]["sum"], 32.0);
+
+    server.admin("DELETE", "/queues/timed/stats", "").await;
+    assert_eq!(latency_of(&server, "timed").await["receive_age"]["count"], 0);
+}
+
+storage_matrix!(sweeps_track_the_oldest_visible_message);
+async fn sweeps_track_the_oldest_visible_message() {
+    let server = start(vec![1.0]).await;
+    let queue_url = server.create_queue("lagging").await;
+    server.send(&queue_url, "tick").await;
+    server.advance_clock(4).await;
+    server.send(&queue_url, "tick").await;
+
+    server.advance_clock(10).await;
+    assert_eq!(latency_of(&server, "lagging").await["oldest_visible_message_age"], 14.0);
+
+    // In-flight messages aren't visible.
+    server
+        .client
+        .receive_message()
+        .queue_url(&queue_url)
+        .visibility_timeout(600)
+        .send()
+        .await
+        .unwrap();
+    server.advance_clock(1).await;
+    assert_eq!(latency_of(&server, "lagging").await["oldest_visible_message_age"], 11.0);
+}
+
+#[tokio::test]
+async fn buckets_must_increase() {
+    let config = Config {
+        port: 0,
+        histogram_buckets: vec![5.0, 1.0],
+        ..Config::default()
+    };
+    assert!(local_sqs::serve(config).await.is_err());
+}
+
+const AGE: &str = "ApproximateAgeOfOldestMessage";
+
+async fn oldest_message_age(
+    server: &TestServer,
+    queue_url: &str,
+    names: &[&str],
+) -> Option<String> {
+    let mut request = server.client.get_queue_attributes().queue_url(queue_url);
+    for name in names {
+        request = request.attribute_names(QueueAttributeName::from(*name));
+    }
+    let attributes = request.send().await.unwrap().attributes.unwrap_or_default();
+    attributes
+        .get(&QueueAttributeName::from(AGE))
+        .cloned()
+}
+
+storage_matrix!(oldest_message_age_is_reported_when_requested);
+async fn oldest_message_age_is_reported_when_requested() {
+    let server = start(vec![1.0]).await;
+    let queue_url = server.create_queue("aging").await;
+
+    assert_eq!(oldest_message_age(&server, &queue_url, &[AGE]).await.as_deref(), Some("0"));
+
+    server.send(&queue_url, "tick").await;
+    server.advance_clock(40).await;
+    server.send(&queue_url, "tick").await;
+    server.advance_clock(2).await;
+    assert_eq!(oldest_message_age(&server, &queue_url, &[AGE]).await.as_deref(), Some("42"));
+    // A non-AWS attribute, so `All` leaves it out.
+    assert_eq!(oldest_message_age(&server, &queue_url, &["All"]).await, None);
+
+    // Once the oldest message is in flight, the next one is the oldest.
+    assert_eq!(server.receive(&queue_url, Some(30)).await.len(), 2);
+    server.send(&queue_url, "tick").await;
+    server.advance_clock(5).await;
+    assert_eq!(oldest_message_age(&server, &queue_url, &[AGE]).await.as_deref(), Some("5"));
+}
+
+storage_matrix!(oldest_message_age_is_exported_for_prometheus);
+async fn oldest_message_age_is_exported_for_prometheus() {
+    let server = start(vec![1.0, 10.0]).await;
+    let queue_url = server.create_queue("scraped").await;
+    server.create_queue("empty").await;
+    server.send(&queue_url, "tick").await;
+    server.advance_clock(7).await;
+
+    let (status, body) = server.admin("GET", "/metrics", "").await;
+    assert_eq!(status, 200);
+    assert!(body.contains("# TYPE local_sqs_oldest_message_age_seconds gauge"), "{}", body);
+    for sample in [
+        "local_sqs_oldest_message_age_seconds{queue=\"scraped\"} 7\n",
+        "local_sqs_oldest_message_age_seconds{queue=\"empty\"} 0\n",
+    ] {
+        assert!(body.contains(sample), "{}", body);
+    }
+
+    server.receive(&queue_url, Some(30)).await;
+    let (_, body) = server.admin("GET", "/metrics", "").await;
+    assert!(
+        body.contains("local_sqs_receive_age_seconds_bucket{queue=\"scraped\",le=\"10\"} 1\n"),
+        "{}",
+        body
+    );
+}
+
+storage_matrix!(queue_stats_count_what_was_done_to_the_queue);
+async fn queue_stats_count_what_was_done_to_the_queue() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("counted").await;
+    for _ in 0..5 {
+        server.send(&queue_url, "tick").await;
+    }
+    let receive = server.client.receive_message().queue_url(&queue_url).visibility_timeout(60);
+    let received = receive.clone().max_number_of_messages(3).send().await.unwrap();
+    for message in &received.messages()[..2] {
+        let delete = server.client.delete_message().queue_url(&queue_url);
+        delete.receipt_handle(message.receipt_handle().unwrap()).send().await.unwrap();
+    }
+    receive.clone().max_number_of_messages(10).send().await.unwrap();
+    // Everything left is in flight.
+    assert!(receive.send().await.unwrap().messages().is_empty());
+
+    let counted = stats_of(&server, "counted").await;
+    assert_eq!(counted["sent"], 5);
+    assert_eq!(counted["received"], 5);
+    assert_eq!(counted["deleted"], 2);
+    assert_eq!(counted["empty_receives"], 1);
+    assert_eq!(counted["purged"], 0);
+    assert_eq!(counted["messages"], 3);
+    assert_eq!(counted["stored_bytes"], 3 * "tick".len());
+
+    server.client.purge_queue().queue_url(&queue_url).send().await.unwrap();
+    let counted = stats_of(&server, "counted").await;
+    assert_eq!(counted["purged"], 3);
+    assert_eq!(counted["messages"], 0);
+    assert_eq!(counted["stored_bytes"], 0);
+
+    // Resetting returns the counters as they were and starts them over.
+    let (status, body) = server.admin("DELETE", "/queues/counted/stats", "").await;
+    assert_eq!(status, 200, "{}", body);
+    let reset: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!((reset["sent"].as_u64(), reset["purged"].as_u64()), (Some(5), Some(3)));
+    let counted = stats_of(&server, "counted").await;
+    for counter in ["sent", "received", "deleted", "empty_receives", "purged"] {
+        assert_eq!(counted[counter], 0, "{}", counter);
+    }
+
+    let (status, body) = server.admin("GET", "/queues/missing/stats", "").await;
+    assert_eq!(status, 400, "{}", body);
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use aws_sdk_sqs::Client;
+use aws_sdk_sqs::config::{Credentials, Region};
+use axum::Router;
+use axum::routing::get;
+use common::{TestServer, storage_matrix};
+use local_sqs::{AppState, Config};
+use std::net::SocketAddr;
+
+fn sdk_client(endpoint: String) -> Client {
+    let config = aws_sdk_sqs::Config::builder()
+        .endpoint_url(endpoint)
+        .region(Region::new("us-east-1"))
+        .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+        .build();
+    Client::from_conf(config)
+}
+
+/// Runs the SQS router nested under `/sqs` in an app that has routes of its
+/// own, the way a service's test doubles would host it.
+async fn start_mounted(admin_api: bool) -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let config = Config {
+        host: "127.0.0.1".to_string(),
+        port: addr.port(),
+        base_path: "/sqs".to_string(),
+        admin_api,
+        ..Default::default()
+    };
+    let state = AppState::new(&config);
+    local_sqs::maintenance::spawn(state.clone());
+
+    let app = Router::new()
+        .route("/health", get(|| async { "ok" }))
+        .nest("/sqs/", local_sqs::sqs_router(state));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    addr
+}
+
+async fn http_get(addr: SocketAddr, path: &str) -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        path, addr
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[tokio::test]
+async fn sdk_works_against_a_nested_router() {
+    let addr = start_mounted(true).await;
+    let client = sdk_client(format!("http://{}/sqs", addr));
+
+    let queue_url = client
+        .create_queue()
+        .queue_name("nested")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    assert_eq!(queue_url, format!("http://{}/sqs/000000000000/nested", addr));
+
+    client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("hello")
+        .send()
+        .await
+        .unwrap();
+    let received = client
+        .receive_message()
+        .queue_url(&queue_url)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(received.messages()[0].body(), Some("hello"));
+
+    assert!(http_get(addr, "/health").await.ends_with("ok"));
+    assert!(http_get(addr, "/sqs/_admin/queues").await.contains("\"nested\""));
+}
+
+#[tokio::test]
+async fn admin_api_can_be_left_out() {
+    let addr = start_mounted(false).await;
+    assert!(http_get(addr, "/sqs/_admin/queues").await.starts_with("HTTP/1.1 404"));
+}
+
+storage_matrix!(serve_honors_the_base_path);
+async fn serve_honors_the_base_path() {
+    let config = Config {
+        base_path: "/emulators/sqs/".to_string(),
+        ..Default::default()
+    };
+    let server = TestServer::start_with(config).await;
+    let client = sdk_client(format!("http://{}/emulators/sqs", server.addr));
+
+    let queue_url = client
+        .create_queue()
+        .queue_name("prefixed")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    assert_eq!(
+        queue_url,
+        format!("http://{}/emulators/sqs/000000000000/prefixed", server.addr)
+    );
+}
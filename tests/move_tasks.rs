@@ -0,0 +1,222 @@
+mod common;
+
+use aws_sdk_sqs::types::{
+    ListMessageMoveTasksResultEntry, QueueAttributeName, SendMessageBatchRequestEntry,
+};
+use common::{TestServer, storage_matrix};
+use std::time::Duration;
+
+const ACCOUNT: &str = "arn:aws:sqs:us-east-1:000000000000";
+
+/// Creates `source` redriving into `dlq`, and `dlq` holding `count` messages.
+async fn dead_letter_setup(server: &TestServer, count: usize) -> (String, String) {
+    let dlq_url = server.create_queue("dlq").await;
+    let source_url = server
+        .client
+        .create_queue()
+        .queue_name("source")
+        .attributes(
+            QueueAttributeName::RedrivePolicy,
+            format!(
+                r#"{{"deadLetterTargetArn":"{}:dlq","maxReceiveCount":"1"}}"#,
+                ACCOUNT
+            ),
+        )
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+
+    for chunk in (0..count).collect::<Vec<_>>().chunks(10) {
+        let entries = chunk
+            .iter()
+            .map(|i| {
+                SendMessageBatchRequestEntry::builder()
+                    .id(format!("m{}", i))
+                    .message_body(format!("message {}", i))
+                    .build()
+                    .unwrap()
+            })
+            .collect();
+        server
+            .client
+            .send_message_batch()
+            .queue_url(&dlq_url)
+            .set_entries(Some(entries))
+            .send()
+            .await
+            .unwrap();
+    }
+    (source_url, dlq_url)
+}
+
+async fn latest_task(server: &TestServer) -> ListMessageMoveTasksResultEntry {
+    server
+        .client
+        .list_message_move_tasks()
+        .source_arn(format!("{}:dlq", ACCOUNT))
+        .send()
+        .await
+        .unwrap()
+        .results
+        .unwrap()
+        .remove(0)
+}
+
+async fn depth(server: &TestServer, queue_url: &str) -> String {
+    server
+        .client
+        .get_queue_attributes()
+        .queue_url(queue_url)
+        .attribute_names(QueueAttributeName::ApproximateNumberOfMessages)
+        .send()
+        .await
+        .unwrap()
+        .attributes
+        .unwrap()[&QueueAttributeName::ApproximateNumberOfMessages]
+        .clone()
+}
+
+storage_matrix!(rate_limited_move_reports_progress);
+async fn rate_limited_move_reports_progress() {
+    let server = TestServer::start().await;
+    let (source_url, dlq_url) = dead_letter_setup(&server, 50).await;
+
+    let handle = server
+        .client
+        .start_message_move_task()
+        .source_arn(format!("{}:dlq", ACCOUNT))
+        .destination_arn(format!("{}:source", ACCOUNT))
+        .max_number_of_messages_per_second(10)
+        .send()
+        .await
+        .unwrap()
+        .task_handle
+        .unwrap();
+
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let task = latest_task(&server).await;
+    assert_eq!(task.status.as_deref(), Some("RUNNING"));
+    assert_eq!(task.task_handle.as_deref(), Some(handle.as_str()));
+    assert_eq!(task.approximate_number_of_messages_to_move, Some(50));
+    let moved = task.approximate_number_of_messages_moved;
+    assert!((5..40).contains(&moved), "moved {} after 1.5s", moved);
+
+    let task = loop {
+        let task = latest_task(&server).await;
+        if task.status.as_deref() != Some("RUNNING") {
+            break task;
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    };
+    assert_eq!(task.status.as_deref(), Some("COMPLETED"));
+    assert_eq!(task.approximate_number_of_messages_moved, 50);
+    assert_eq!(task.task_handle, None);
+    assert_eq!(depth(&server, &source_url).await, "50");
+    assert_eq!(depth(&server, &dlq_url).await, "0");
+}
+
+storage_matrix!(deleting_the_destination_fails_the_task);
+async fn deleting_the_destination_fails_the_task() {
+    let server = TestServer::start().await;
+    let (source_url, dlq_url) = dead_letter_setup(&server, 20).await;
+
+    server
+        .client
+        .start_message_move_task()
+        .source_arn(format!("{}:dlq", ACCOUNT))
+        .destination_arn(format!("{}:source", ACCOUNT))
+        .max_number_of_messages_per_second(10)
+        .send()
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(350)).await;
+    server
+        .client
+        .delete_queue()
+        .queue_url(&source_url)
+        .send()
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let task = latest_task(&server).await;
+    assert_eq!(task.status.as_deref(), Some("FAILED"));
+    assert!(task.failure_reason.unwrap().contains("does not exist"));
+    let moved = task.approximate_number_of_messages_moved as usize;
+    assert_eq!(depth(&server, &dlq_url).await, (20 - moved).to_string());
+}
+
+storage_matrix!(move_without_destination_returns_messages_to_their_source);
+async fn move_without_destination_returns_messages_to_their_source() {
+    let server = TestServer::start().await;
+    let (source_url, dlq_url) = dead_letter_setup(&server, 0).await;
+    let client = &server.client;
+
+    client
+        .send_message()
+        .queue_url(&source_url)
+        .message_body("poison")
+        .send()
+        .await
+        .unwrap();
+    // maxReceiveCount is 1: the first receive uses it up and the lapsed
+    // visibility timeout dead-letters the message on the next receive.
+    client
+        .receive_message()
+        .queue_url(&source_url)
+        .visibility_timeout(0)
+        .send()
+        .await
+        .unwrap();
+    client.receive_message().queue_url(&source_url).send().await.unwrap();
+    assert_eq!(depth(&server, &dlq_url).await, "1");
+
+    client
+        .start_message_move_task()
+        .source_arn(format!("{}:dlq", ACCOUNT))
+        .send()
+        .await
+        .unwrap();
+    let received = client
+        .receive_message()
+        .queue_url(&source_url)
+        .wait_time_seconds(5)
+        .send()
+        .await
+        .unwrap()
+        .messages
+        .unwrap();
+    assert_eq!(received[0].body.as_deref(), Some("poison"));
+    let (_, peeked) = server.admin("GET", "/queues/source/messages", "").await;
+    let peeked: serde_json::Value = serde_json::from_str(&peeked).unwrap();
+    assert_eq!(peeked[0]["dead_letter"]["reason"], "move_task");
+    assert_eq!(peeked[0]["dead_letter"]["source_arn"], format!("{}:dlq", ACCOUNT));
+}
+
+storage_matrix!(source_must_be_a_dead_letter_queue);
+async fn source_must_be_a_dead_letter_queue() {
+    let server = TestServer::start().await;
+    server.create_queue("plain").await;
+
+    let err = server
+        .client
+        .start_message_move_task()
+        .source_arn(format!("{}:plain", ACCOUNT))
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert!(format!("{:?}", err).contains("Dead Letter Queue"), "{:?}", err);
+
+    let err = server
+        .client
+        .start_message_move_task()
+        .source_arn(format!("{}:missing", ACCOUNT))
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert!(err.is_resource_not_found_exception(), "{:?}", err);
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use aws_sdk_sqs::config::interceptors::BeforeTransmitInterceptorContextMut;
+use aws_sdk_sqs::config::{ConfigBag, Intercept, RuntimeComponents};
+use aws_sdk_sqs::error::BoxError;
+use aws_sdk_sqs::Client;
+use common::{TestServer, storage_matrix};
+
+/// Sends every request in a namespace.
+#[derive(Debug)]
+struct Namespace(&'static str);
+
+impl Intercept for Namespace {
+    fn name(&self) -> &'static str {
+        "Namespace"
+    }
+
+    fn modify_before_transmit(
+        &self,
+        context: &mut BeforeTransmitInterceptorContextMut<'_>,
+        _: &RuntimeComponents,
+        _: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        context.request_mut().headers_mut().insert("X-LocalSqs-Namespace", self.0);
+        Ok(())
+    }
+}
+
+fn client(server: &TestServer, namespace: &'static str) -> Client {
+    let config = server.client.config().to_builder().interceptor(Namespace(namespace)).build();
+    Client::from_conf(config)
+}
+
+async fn create_queue(client: &Client, name: &str) -> String {
+    let created = client.create_queue().queue_name(name).send().await.unwrap();
+    created.queue_url.unwrap()
+}
+
+async fn list_queues(client: &Client, prefix: Option<&str>) -> Vec<String> {
+    let listed = client.list_queues().set_queue_name_prefix(prefix.map(str::to_string));
+    let mut urls = listed.send().await.unwrap().queue_urls().to_vec();
+    urls.sort();
+    urls
+}
+
+storage_matrix!(namespaced_clients_see_only_their_own_queues);
+async fn namespaced_clients_see_only_their_own_queues() {
+    let server = TestServer::start().await;
+    let first = client(&server, "run-1");
+    let second = client(&server, "run-2");
+
+    let first_url = create_queue(&first, "jobs").await;
+    let second_url = create_queue(&second, "jobs").await;
+    assert!(first_url.ends_with("/000000000000/run-1/jobs"), "{}", first_url);
+    assert!(second_url.ends_with("/000000000000/run-2/jobs"), "{}", second_url);
+    create_queue(&first, "reports").await;
+    let shared_url = server.create_queue("shared").await;
+
+    assert_eq!(list_queues(&first, None).await.len(), 2);
+    assert_eq!(list_queues(&first, Some("jo")).await, [first_url.as_str()]);
+    assert_eq!(list_queues(&second, None).await, [second_url.as_str()]);
+    assert_eq!(list_queues(&server.client, None).await.len(), 4);
+
+    let found = first.get_queue_url().queue_name("jobs").send().await.unwrap();
+    assert_eq!(found.queue_url(), Some(first_url.as_str()));
+
+    first
+        .send_message()
+        .queue_url(&first_url)
+        .message_body("first")
+        .send()
+        .await
+        .unwrap();
+    let received = second.receive_message().queue_url(&second_url).send().await.unwrap();
+    assert!(received.messages().is_empty());
+
+    // A URL built from the bare name resolves within the namespace, and the
+    // namespace's URLs resolve with or without the header.
+    let bare_url = first_url.replace("/run-1/jobs", "/jobs");
+    let received = first.receive_message().queue_url(&bare_url).send().await.unwrap();
+    assert_eq!(received.messages()[0].body(), Some("first"));
+    let attributes = server.client.get_queue_attributes().queue_url(&first_url).send().await;
+    assert!(attributes.is_ok());
+
+    // Queues outside the namespace can't be reached from it.
+    let error = first.get_queue_attributes().queue_url(&shared_url).send().await.unwrap_err();
+    let error = error.into_service_error();
+    assert!(error.is_queue_does_not_exist(), "{:?}", error);
+}
+
+storage_matrix!(names_starting_with_the_namespace_prefix_stay_in_the_namespace);
+async fn names_starting_with_the_namespace_prefix_stay_in_the_namespace() {
+    let server = TestServer::start().await;
+    let first = client(&server, "run-1");
+    let jobs_url = create_queue(&first, "jobs").await;
+    let prefixed_url = create_queue(&first, "run-1_jobs").await;
+    assert!(prefixed_url.ends_with("/000000000000/run-1/run-1_jobs"), "{}", prefixed_url);
+    first.send_message().queue_url(&jobs_url).message_body("jobs").send().await.unwrap();
+    let send = first.send_message().queue_url(&prefixed_url).message_body("prefixed");
+    send.send().await.unwrap();
+
+    // A URL built from the name resolves to the queue CreateQueue made,
+    // not to the namespace's `jobs`, whose stored name it spells.
+    let bare_url = jobs_url.replace("/run-1/jobs", "/run-1_jobs");
+    let received = first.receive_message().queue_url(&bare_url).send().await.unwrap();
+    assert_eq!(received.messages()[0].body(), Some("prefixed"));
+    let received = server.client.receive_message().queue_url(&jobs_url).send().await.unwrap();
+    assert_eq!(received.messages()[0].body(), Some("jobs"));
+
+    let found = first.get_queue_url().queue_name("run-1_jobs").send().await.unwrap();
+    assert_eq!(found.queue_url(), Some(prefixed_url.as_str()));
+    let mut expected = [jobs_url, prefixed_url];
+    expected.sort();
+    assert_eq!(list_queues(&first, None).await, expected);
+}
+
+storage_matrix!(namespaces_are_deleted_at_once);
+async fn namespaces_are_deleted_at_once() {
+    let server = TestServer::start().await;
+    let first = client(&server, "run-1");
+    let second = client(&server, "run-2");
+    let jobs = create_queue(&first, "jobs").await;
+    create_queue(&first, "reports").await;
+    create_queue(&second, "jobs").await;
+    first.send_message().queue_url(&jobs).message_body("m").send().await.unwrap();
+
+    let (status, body) = server.admin("DELETE", "/namespaces/run-1", "").await;
+    assert_eq!(status, 200, "{}", body);
+    let summary: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(summary["queues"], 2);
+    assert_eq!(summary["messages"], 1);
+    assert!(list_queues(&first, None).await.is_empty());
+    assert_eq!(list_queues(&second, None).await.len(), 1);
+
+    let (status, _) = server.admin("DELETE", "/namespaces/run_1", "").await;
+    assert_eq!(status, 400);
+    let error = client(&server, "run 1").list_queues().send().await.unwrap_err();
+    assert_eq!(error.raw_response().map(|r| r.status().as_u16()), Some(400));
+}
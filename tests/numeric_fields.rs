@@ -0,0 +1,111 @@
+mod common;
+
+use common::{TestServer, storage_matrix};
+use serde_json::{json, Value};
+
+/// `value` as a JSON number, or as a string holding it.
+fn number(value: u32, as_string: bool) -> Value {
+    if as_string { json!(value.to_string()) } else { json!(value) }
+}
+
+async fn call(server: &TestServer, action: &str, body: Value) -> Value {
+    let (status, response) = server
+        .action(&format!("AmazonSQS.{}", action), &body.to_string())
+        .await;
+    assert_eq!(status, 200, "{} {}: {}", action, body, response);
+    serde_json::from_str(&response).unwrap()
+}
+
+async fn attribute(server: &TestServer, queue_url: &str, name: &str) -> Value {
+    let body = json!({ "QueueUrl": queue_url, "AttributeNames": [name] });
+    call(server, "GetQueueAttributes", body).await["Attributes"][name].clone()
+}
+
+storage_matrix!(numeric_fields_accept_numbers_and_strings);
+async fn numeric_fields_accept_numbers_and_strings() {
+    for as_string in [false, true] {
+        let server = TestServer::start().await;
+        let n = |value| number(value, as_string);
+        let dlq_url = server.create_queue("dlq").await;
+        let queue_url = server.create_queue("numbers").await;
+
+        let listed = call(&server, "ListQueues", json!({ "MaxResults": n(1) })).await;
+        assert_eq!(listed["QueueUrls"].as_array().unwrap().len(), 1);
+
+        let policy = json!({
+            "deadLetterTargetArn": "arn:aws:sqs:us-east-1:000000000000:dlq",
+            "maxReceiveCount": n(1),
+        });
+        let attributes = json!({ "RedrivePolicy": policy.to_string() });
+        call(
+            &server,
+            "SetQueueAttributes",
+            json!({ "QueueUrl": queue_url, "Attributes": attributes }),
+        )
+        .await;
+
+        let later = json!({ "QueueUrl": queue_url, "MessageBody": "later", "DelaySeconds": n(60) });
+        call(&server, "SendMessage", later).await;
+        let entries = json!([{ "Id": "1", "MessageBody": "later", "DelaySeconds": n(60) }]);
+        let batch = json!({ "QueueUrl": queue_url, "Entries": entries });
+        call(&server, "SendMessageBatch", batch).await;
+        let delayed = attribute(&server, &queue_url, "ApproximateNumberOfMessagesDelayed").await;
+        assert_eq!(delayed, "2");
+
+        for body in ["a", "b", "c"] {
+            let send = json!({ "QueueUrl": queue_url, "MessageBody": body });
+            call(&server, "SendMessage", send).await;
+        }
+        let receive = json!({
+            "QueueUrl": queue_url,
+            "MaxNumberOfMessages": n(2),
+            "VisibilityTimeout": n(30),
+            "WaitTimeSeconds": n(0),
+        });
+        let received = call(&server, "ReceiveMessage", receive).await;
+        let received = received["Messages"].as_array().unwrap().clone();
+        assert_eq!(received.len(), 2);
+
+        // Both messages are made visible again, the second via a batch; as
+        // the redrive policy allows one receive, they are dead-lettered.
+        let change = json!({
+            "QueueUrl": queue_url,
+            "ReceiptHandle": received[0]["ReceiptHandle"],
+            "VisibilityTimeout": n(0),
+        });
+        call(&server, "ChangeMessageVisibility", change).await;
+        let entries = json!([{
+            "Id": "1",
+            "ReceiptHandle": received[1]["ReceiptHandle"],
+            "VisibilityTimeout": n(0),
+        }]);
+        let change = json!({ "QueueUrl": queue_url, "Entries": entries });
+        let changed = call(&server, "ChangeMessageVisibilityBatch", change).await;
+        assert_eq!(changed["Successful"].as_array().unwrap().len(), 1, "{}", changed);
+        let receive = json!({ "QueueUrl": queue_url, "MaxNumberOfMessages": n(10) });
+        let received = call(&server, "ReceiveMessage", receive).await;
+        assert_eq!(received["Messages"].as_array().unwrap().len(), 1);
+        assert_eq!(attribute(&server, &dlq_url, "ApproximateNumberOfMessages").await, "2");
+
+        let source_arn = "arn:aws:sqs:us-east-1:000000000000:dlq";
+        let start = json!({ "SourceArn": source_arn, "MaxNumberOfMessagesPerSecond": n(100) });
+        call(&server, "StartMessageMoveTask", start).await;
+        let list = json!({ "SourceArn": source_arn, "MaxResults": n(1) });
+        let tasks = call(&server, "ListMessageMoveTasks", list).await;
+        assert_eq!(tasks["Results"][0]["MaxNumberOfMessagesPerSecond"], 100);
+    }
+}
+
+storage_matrix!(malformed_numbers_are_rejected);
+async fn malformed_numbers_are_rejected() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("numbers").await;
+    for value in [json!("ten"), json!("-1"), json!(1.5), json!(true)] {
+        let body = json!({ "QueueUrl": queue_url, "MaxNumberOfMessages": value });
+        let (status, response) = server
+            .action("AmazonSQS.ReceiveMessage", &body.to_string())
+            .await;
+        assert_eq!(status, 400, "{}: {}", value, response);
+        assert!(response.contains("InvalidParameterValue"), "{}: {}", value, response);
+    }
+}
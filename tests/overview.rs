@@ -0,0 +1,99 @@
+mod common;
+
+use aws_sdk_sqs::types::QueueAttributeName;
+use common::{TestServer, storage_matrix};
+use serde_json::Value;
+
+async fn overview(server: &TestServer, query: &str) -> Value {
+    let (status, body) = server.admin("GET", &format!("/queues{}", query), "").await;
+    assert_eq!(status, 200, "{}", body);
+    serde_json::from_str(&body).unwrap()
+}
+
+fn names(listing: &Value) -> Vec<&str> {
+    listing
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|queue| queue["name"].as_str().unwrap())
+        .collect()
+}
+
+storage_matrix!(queues_are_listed_with_their_depths);
+async fn queues_are_listed_with_their_depths() {
+    let server = TestServer::start().await;
+    let shallow = server.create_queue("orders-shallow").await;
+    let deep = server.create_queue("orders-deep").await;
+    server.create_queue("audit").await;
+    server
+        .client
+        .create_queue()
+        .queue_name("orders-ordered.fifo")
+        .attributes(QueueAttributeName::FifoQueue, "true")
+        .send()
+        .await
+        .unwrap();
+    server
+        .client
+        .tag_queue()
+        .queue_url(&deep)
+        .tags("team", "billing")
+        .send()
+        .await
+        .unwrap();
+    for (queue_url, count) in [(&shallow, 1), (&deep, 4)] {
+        for i in 0..count {
+            server
+                .client
+                .send_message()
+                .queue_url(queue_url)
+                .message_body(format!("m{}", i))
+                .send()
+                .await
+                .unwrap();
+        }
+    }
+    server
+        .client
+        .send_message()
+        .queue_url(&deep)
+        .message_body("later")
+        .delay_seconds(60)
+        .send()
+        .await
+        .unwrap();
+    server
+        .client
+        .receive_message()
+        .queue_url(&deep)
+        .send()
+        .await
+        .unwrap();
+
+    let listing = overview(&server, "").await;
+    assert_eq!(
+        names(&listing),
+        ["audit", "orders-deep", "orders-ordered.fifo", "orders-shallow"]
+    );
+    let listed = &listing[1];
+    assert_eq!(listed["url"], deep.as_str());
+    assert!(listed["arn"].as_str().unwrap().ends_with(":orders-deep"));
+    assert_eq!(listed["fifo"], false);
+    assert_eq!(listed["visible"], 3);
+    assert_eq!(listed["in_flight"], 1);
+    assert_eq!(listed["delayed"], 1);
+    assert_eq!(listed["messages"], 5);
+    assert!(listed["oldest_message_age_seconds"].is_u64());
+    assert!(listed["created_timestamp"].is_i64());
+    assert_eq!(listed["tags"]["team"], "billing");
+    assert_eq!(listing[2]["fifo"], true);
+
+    let filtered = overview(&server, "?prefix=orders-&sort=depth").await;
+    assert_eq!(
+        names(&filtered),
+        ["orders-deep", "orders-shallow", "orders-ordered.fifo"]
+    );
+
+    let (status, _) = server.admin("GET", "/queues?sort=size", "").await;
+    assert_eq!(status, 400);
+}
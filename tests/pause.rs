@@ -0,0 +1,99 @@
+mod common;
+
+use aws_sdk_sqs::types::QueueAttributeName;
+use common::{TestServer, storage_matrix};
+use std::time::{Duration, Instant};
+
+async fn paused_attribute(server: &TestServer, queue_url: &str) -> String {
+    let attribute = QueueAttributeName::from("LocalSqsPaused");
+    server
+        .client
+        .get_queue_attributes()
+        .queue_url(queue_url)
+        .attribute_names(QueueAttributeName::All)
+        .send()
+        .await
+        .unwrap()
+        .attributes()
+        .unwrap()[&attribute]
+        .clone()
+}
+
+storage_matrix!(paused_queues_accept_sends_but_deliver_nothing);
+async fn paused_queues_accept_sends_but_deliver_nothing() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("paused").await;
+
+    let (status, _) = server.admin("POST", "/queues/paused/pause", "").await;
+    assert_eq!(status, 200);
+    for i in 0..3 {
+        server.send(&queue_url, &format!("message {}", i)).await;
+    }
+    assert_eq!(server.receive(&queue_url, None).await.len(), 0);
+    assert_eq!(paused_attribute(&server, &queue_url).await, "true");
+
+    let (status, _) = server.admin("POST", "/queues/paused/resume", "").await;
+    assert_eq!(status, 200);
+    assert_eq!(server.receive(&queue_url, None).await.len(), 3);
+    assert_eq!(paused_attribute(&server, &queue_url).await, "false");
+}
+
+storage_matrix!(resuming_wakes_long_polls);
+async fn resuming_wakes_long_polls() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("held").await;
+    server.admin("POST", "/queues/held/pause", "").await;
+    server.send(&queue_url, "message 0").await;
+
+    let client = server.client.clone();
+    let url = queue_url.clone();
+    let started = Instant::now();
+    let poll = tokio::spawn(async move {
+        client
+            .receive_message()
+            .queue_url(url)
+            .wait_time_seconds(10)
+            .send()
+            .await
+            .unwrap()
+            .messages()
+            .len()
+    });
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(!poll.is_finished());
+    server.admin("POST", "/queues/held/resume", "").await;
+    assert_eq!(poll.await.unwrap(), 1);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+storage_matrix!(paused_flag_is_listed_and_survives_snapshots);
+async fn paused_flag_is_listed_and_survives_snapshots() {
+    let server = TestServer::start().await;
+    server.create_queue("a").await;
+    server.create_queue("b").await;
+    server.admin("POST", "/queues/b/pause", "").await;
+
+    let (_, listing) = server.admin("GET", "/queues", "").await;
+    let listing: serde_json::Value = serde_json::from_str(&listing).unwrap();
+    assert_eq!(listing[0]["name"], "a");
+    assert_eq!(listing[0]["paused"], false);
+    assert_eq!(listing[1]["name"], "b");
+    assert_eq!(listing[1]["paused"], true);
+
+    let (_, snapshot) = server.admin("GET", "/export", "").await;
+    server.admin("POST", "/reset", "").await;
+    let (status, _) = server.admin("POST", "/import", &snapshot).await;
+    assert_eq!(status, 200);
+
+    let (_, listing) = server.admin("GET", "/queues", "").await;
+    let listing: serde_json::Value = serde_json::from_str(&listing).unwrap();
+    assert_eq!(listing[1]["paused"], true);
+}
+
+storage_matrix!(pausing_a_missing_queue_fails);
+async fn pausing_a_missing_queue_fails() {
+    let server = TestServer::start().await;
+    let (status, _) = server.admin("POST", "/queues/missing/pause", "").await;
+    assert_eq!(status, 400);
+}
@@ -0,0 +1,210 @@
+mod common;
+
+use aws_sdk_sqs::types::QueueAttributeName;
+use common::{TestServer, storage_matrix};
+use serde_json::{json, Value};
+
+async fn policy(server: &TestServer, queue_url: &str) -> Option<Value> {
+    server
+        .client
+        .get_queue_attributes()
+        .queue_url(queue_url)
+        .attribute_names(QueueAttributeName::Policy)
+        .send()
+        .await
+        .unwrap()
+        .attributes
+        .and_then(|mut attributes| attributes.remove(&QueueAttributeName::Policy))
+        .map(|policy| serde_json::from_str(&policy).unwrap())
+}
+
+async fn set_policy(server: &TestServer, queue_url: &str, policy: &str) -> Result<(), String> {
+    server
+        .client
+        .set_queue_attributes()
+        .queue_url(queue_url)
+        .attributes(QueueAttributeName::Policy, policy)
+        .send()
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("{:?}", e.into_service_error()))
+}
+
+storage_matrix!(add_permission_builds_a_policy_document);
+async fn add_permission_builds_a_policy_document() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("permissions").await;
+    let client = &server.client;
+
+    client
+        .add_permission()
+        .queue_url(&queue_url)
+        .label("senders")
+        .aws_account_ids("111111111111")
+        .actions("SendMessage")
+        .send()
+        .await
+        .unwrap();
+    client
+        .add_permission()
+        .queue_url(&queue_url)
+        .label("consumers")
+        .aws_account_ids("222222222222")
+        .actions("ReceiveMessage")
+        .actions("DeleteMessage")
+        .send()
+        .await
+        .unwrap();
+
+    let document = policy(&server, &queue_url).await.unwrap();
+    assert_eq!(document["Version"], "2012-10-17");
+    let statements = document["Statement"].as_array().unwrap();
+    assert_eq!(statements.len(), 2);
+    assert_eq!(
+        statements[0],
+        json!({
+            "Sid": "senders",
+            "Effect": "Allow",
+            "Principal": {"AWS": ["arn:aws:iam::111111111111:root"]},
+            "Action": ["SQS:SendMessage"],
+            "Resource": "arn:aws:sqs:us-east-1:000000000000:permissions"
+        })
+    );
+    assert_eq!(statements[1]["Sid"], "consumers");
+    assert_eq!(
+        statements[1]["Action"],
+        json!(["SQS:ReceiveMessage", "SQS:DeleteMessage"])
+    );
+
+    let err = client
+        .add_permission()
+        .queue_url(&queue_url)
+        .label("senders")
+        .aws_account_ids("333333333333")
+        .actions("SendMessage")
+        .send()
+        .await
+        .unwrap_err();
+    assert!(format!("{:?}", err).contains("Already exists"), "{:?}", err);
+
+    let err = client
+        .add_permission()
+        .queue_url(&queue_url)
+        .label("everything")
+        .aws_account_ids("333333333333")
+        .actions("*")
+        .send()
+        .await
+        .unwrap_err();
+    assert!(format!("{:?}", err).contains("SQS:*"), "{:?}", err);
+
+    client
+        .remove_permission()
+        .queue_url(&queue_url)
+        .label("senders")
+        .send()
+        .await
+        .unwrap();
+    let document = policy(&server, &queue_url).await.unwrap();
+    assert_eq!(document["Statement"].as_array().unwrap().len(), 1);
+
+    client
+        .remove_permission()
+        .queue_url(&queue_url)
+        .label("consumers")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(policy(&server, &queue_url).await, None);
+}
+
+storage_matrix!(raw_policy_round_trips_and_shares_statements);
+async fn raw_policy_round_trips_and_shares_statements() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("raw-policy").await;
+
+    let document = json!({
+        "Version": "2012-10-17",
+        "Statement": {
+            "Sid": "raw",
+            "Effect": "Allow",
+            "Principal": "*",
+            "Action": "SQS:SendMessage",
+            "Resource": "arn:aws:sqs:us-east-1:000000000000:raw-policy"
+        }
+    });
+    set_policy(&server, &queue_url, &document.to_string()).await.unwrap();
+    assert_eq!(policy(&server, &queue_url).await.unwrap(), document);
+
+    // Statements from a raw policy can be removed by label.
+    server
+        .client
+        .remove_permission()
+        .queue_url(&queue_url)
+        .label("raw")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(policy(&server, &queue_url).await, None);
+
+    // Setting the policy replaces labeled statements too.
+    server
+        .client
+        .add_permission()
+        .queue_url(&queue_url)
+        .label("labeled")
+        .aws_account_ids("111111111111")
+        .actions("SendMessage")
+        .send()
+        .await
+        .unwrap();
+    set_policy(&server, &queue_url, &document.to_string()).await.unwrap();
+    assert_eq!(policy(&server, &queue_url).await.unwrap(), document);
+}
+
+storage_matrix!(invalid_policies_are_rejected);
+async fn invalid_policies_are_rejected() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("bad-policy").await;
+
+    for bad in [
+        "{not json".to_string(),
+        json!({"Version": "2012-10-17"}).to_string(),
+        json!({"Statement": "nope"}).to_string(),
+        json!({"Statement": [{"Sid": "x".repeat(21000)}]}).to_string(),
+    ] {
+        let err = set_policy(&server, &queue_url, &bad).await.unwrap_err();
+        assert!(err.contains("InvalidAttributeValue"), "{}", err);
+    }
+    assert_eq!(policy(&server, &queue_url).await, None);
+}
+
+storage_matrix!(added_permissions_round_trip_through_an_export);
+async fn added_permissions_round_trip_through_an_export() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("shared").await;
+    let add = server.client.add_permission().queue_url(&queue_url).label("partners");
+    let add = add.aws_account_ids("111111111111").aws_account_ids("222222222222");
+    add.actions("SendMessage").actions("GetQueueUrl").send().await.unwrap();
+    let document = policy(&server, &queue_url).await.unwrap();
+    let principals = &document["Statement"][0]["Principal"]["AWS"];
+    let expected = ["arn:aws:iam::111111111111:root", "arn:aws:iam::222222222222:root"];
+    assert_eq!(principals, &json!(expected));
+
+    // The document is an ordinary Policy attribute: it can be read back and
+    // set again verbatim, and it moves with the queue.
+    set_policy(&server, &queue_url, &document.to_string()).await.unwrap();
+    let (_, exported) = server.admin("GET", "/export", "").await;
+    let restored = TestServer::start().await;
+    let (status, body) = restored.admin("POST", "/import", &exported).await;
+    assert_eq!(status, 200, "{}", body);
+    let queue_url = restored.client.get_queue_url().queue_name("shared").send().await.unwrap();
+    let queue_url = queue_url.queue_url().unwrap();
+    assert_eq!(policy(&restored, queue_url).await, Some(document));
+
+    let remove = restored.client.remove_permission().queue_url(queue_url);
+    let err = remove.clone().label("strangers").send().await.unwrap_err();
+    assert!(format!("{:?}", err).contains("can't find label"), "{:?}", err);
+    remove.label("partners").send().await.unwrap();
+    assert_eq!(policy(&restored, queue_url).await, None);
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use aws_sdk_sqs::types::{MessageAttributeValue, QueueAttributeName};
+use common::{TestServer, storage_matrix};
+
+async fn create_queue(server: &TestServer, name: &str, priority: bool) -> String {
+    let mut request = server.client.create_queue().queue_name(name);
+    if name.ends_with(".fifo") {
+        request = request.attributes(QueueAttributeName::FifoQueue, "true");
+    }
+    if priority {
+        request = request.attributes(QueueAttributeName::from("LocalSqsPriority"), "true");
+    }
+    request.send().await.unwrap().queue_url.unwrap()
+}
+
+/// Sends a message with body `body` and, if given, priority `priority`.
+async fn send_with_priority(
+    server: &TestServer,
+    queue_url: &str,
+    body: &str,
+    priority: Option<&str>,
+) {
+    let mut request = server.client.send_message().queue_url(queue_url).message_body(body);
+    if let Some(priority) = priority {
+        let value = MessageAttributeValue::builder()
+            .data_type("Number")
+            .string_value(priority)
+            .build()
+            .unwrap();
+        request = request.message_attributes("local-sqs.priority", value);
+    }
+    if queue_url.ends_with(".fifo") {
+        request = request.message_group_id("group").message_deduplication_id(body);
+    }
+    request.send().await.unwrap();
+}
+
+async fn send_interleaved(server: &TestServer, queue_url: &str) {
+    for (body, priority) in [
+        ("low-1", Some("1")),
+        ("none-1", None),
+        ("high-1", Some("10")),
+        ("low-2", Some("1")),
+        ("high-2", Some("10")),
+        ("negative", Some("-2.5")),
+        ("none-2", None),
+        ("mid", Some("5.5")),
+    ] {
+        send_with_priority(server, queue_url, body, priority).await;
+    }
+}
+
+/// The bodies of every message received from `queue_url`, `batch` at a time.
+async fn receive_all(server: &TestServer, queue_url: &str, batch: i32) -> Vec<String> {
+    let mut bodies = Vec::new();
+    loop {
+        let received = server
+            .client
+            .receive_message()
+            .queue_url(queue_url)
+            .max_number_of_messages(batch)
+            .send()
+            .await
+            .unwrap();
+        if received.messages().is_empty() {
+            return bodies;
+        }
+        for message in received.messages() {
+            bodies.push(message.body().unwrap().to_string());
+            let delete = server.client.delete_message().queue_url(queue_url);
+            delete.receipt_handle(message.receipt_handle().unwrap()).send().await.unwrap();
+        }
+    }
+}
+
+storage_matrix!(priority_queues_deliver_higher_priorities_first);
+async fn priority_queues_deliver_higher_priorities_first() {
+    let server = TestServer::start().await;
+    let queue_url = create_queue(&server, "prioritized", true).await;
+    send_interleaved(&server, &queue_url).await;
+
+    let expected = ["high-1", "high-2", "mid", "low-1", "low-2", "none-1", "none-2", "negative"];
+    assert_eq!(receive_all(&server, &queue_url, 3).await, expected);
+
+    // A message sent later still overtakes lower priorities already waiting.
+    send_with_priority(&server, &queue_url, "low", Some("1")).await;
+    send_with_priority(&server, &queue_url, "urgent", Some("100")).await;
+    assert_eq!(receive_all(&server, &queue_url, 1).await, ["urgent", "low"]);
+}
+
+storage_matrix!(priorities_are_ignored_unless_the_queue_opts_in);
+async fn priorities_are_ignored_unless_the_queue_opts_in() {
+    let server = TestServer::start().await;
+    let sent_order = ["low-1", "none-1", "high-1", "low-2", "high-2", "negative", "none-2", "mid"];
+
+    let plain = create_queue(&server, "plain", false).await;
+    send_interleaved(&server, &plain).await;
+    assert_eq!(receive_all(&server, &plain, 10).await, sent_order);
+
+    let fifo = create_queue(&server, "prioritized.fifo", true).await;
+    send_interleaved(&server, &fifo).await;
+    assert_eq!(receive_all(&server, &fifo, 10).await, sent_order);
+}
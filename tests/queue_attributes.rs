@@ -0,0 +1,720 @@
+mod common;
+
+use aws_sdk_sqs::types::QueueAttributeName;
+use common::{TestServer, storage_matrix};
+use local_sqs::attributes::{AttributeKind, AttributeSpec, Mutability, QUEUE_ATTRIBUTES};
+use std::collections::HashMap;
+
+async fn all_attributes(server: &TestServer, queue_url: &str) -> HashMap<QueueAttributeName, String> {
+    server
+        .client
+        .get_queue_attributes()
+        .queue_url(queue_url)
+        .attribute_names(QueueAttributeName::All)
+        .send()
+        .await
+        .unwrap()
+        .attributes
+        .unwrap()
+}
+
+storage_matrix!(kms_attributes_round_trip);
+async fn kms_attributes_round_trip() {
+    let server = TestServer::start().await;
+    let queue_url = server
+        .client
+        .create_queue()
+        .queue_name("kms")
+        .attributes(QueueAttributeName::KmsMasterKeyId, "alias/aws/sqs")
+        .attributes(QueueAttributeName::KmsDataKeyReusePeriodSeconds, "300")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+
+    let attributes = all_attributes(&server, &queue_url).await;
+    assert_eq!(attributes[&QueueAttributeName::KmsMasterKeyId], "alias/aws/sqs");
+    assert_eq!(attributes[&QueueAttributeName::KmsDataKeyReusePeriodSeconds], "300");
+
+    server
+        .client
+        .set_queue_attributes()
+        .queue_url(&queue_url)
+        .attributes(QueueAttributeName::KmsDataKeyReusePeriodSeconds, "86400")
+        .send()
+        .await
+        .unwrap();
+    let attributes = all_attributes(&server, &queue_url).await;
+    assert_eq!(attributes[&QueueAttributeName::KmsDataKeyReusePeriodSeconds], "86400");
+}
+
+storage_matrix!(kms_attribute_values_are_validated);
+async fn kms_attribute_values_are_validated() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("kms-invalid").await;
+
+    for (name, value) in [
+        (QueueAttributeName::KmsDataKeyReusePeriodSeconds, "59"),
+        (QueueAttributeName::KmsDataKeyReusePeriodSeconds, "86401"),
+        (QueueAttributeName::KmsMasterKeyId, ""),
+    ] {
+        let err = server
+            .client
+            .set_queue_attributes()
+            .queue_url(&queue_url)
+            .attributes(name.clone(), value)
+            .send()
+            .await
+            .unwrap_err()
+            .into_service_error();
+        assert!(err.is_invalid_attribute_value(), "{:?}={}: {:?}", name, value, err);
+    }
+}
+
+storage_matrix!(sqs_managed_sse_defaults_to_enabled_and_toggles);
+async fn sqs_managed_sse_defaults_to_enabled_and_toggles() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("sse").await;
+
+    let attributes = all_attributes(&server, &queue_url).await;
+    assert_eq!(attributes[&QueueAttributeName::SqsManagedSseEnabled], "true");
+
+    server
+        .client
+        .set_queue_attributes()
+        .queue_url(&queue_url)
+        .attributes(QueueAttributeName::SqsManagedSseEnabled, "false")
+        .send()
+        .await
+        .unwrap();
+    let attributes = all_attributes(&server, &queue_url).await;
+    assert_eq!(attributes[&QueueAttributeName::SqsManagedSseEnabled], "false");
+
+    let err = server
+        .client
+        .set_queue_attributes()
+        .queue_url(&queue_url)
+        .attributes(QueueAttributeName::SqsManagedSseEnabled, "yes")
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert!(err.is_invalid_attribute_value(), "{:?}", err);
+    assert!(format!("{:?}", err).contains("yes"), "{:?}", err);
+}
+
+storage_matrix!(kms_and_sqs_managed_sse_are_exclusive);
+async fn kms_and_sqs_managed_sse_are_exclusive() {
+    let server = TestServer::start().await;
+
+    let err = server
+        .client
+        .create_queue()
+        .queue_name("both-sse")
+        .attributes(QueueAttributeName::KmsMasterKeyId, "alias/aws/sqs")
+        .attributes(QueueAttributeName::SqsManagedSseEnabled, "true")
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert!(err.is_invalid_attribute_value(), "{:?}", err);
+
+    // A KMS queue reports SQS-managed encryption as off, and can't turn it on.
+    let queue_url = server
+        .client
+        .create_queue()
+        .queue_name("kms-sse")
+        .attributes(QueueAttributeName::KmsMasterKeyId, "alias/aws/sqs")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    let attributes = all_attributes(&server, &queue_url).await;
+    assert_eq!(attributes[&QueueAttributeName::SqsManagedSseEnabled], "false");
+
+    let err = server
+        .client
+        .set_queue_attributes()
+        .queue_url(&queue_url)
+        .attributes(QueueAttributeName::SqsManagedSseEnabled, "true")
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert!(err.is_invalid_attribute_value(), "{:?}", err);
+
+    // Setting a KMS key on an SSE-SQS queue switches encryption over.
+    let queue_url = server.create_queue("switch-sse").await;
+    server
+        .client
+        .set_queue_attributes()
+        .queue_url(&queue_url)
+        .attributes(QueueAttributeName::KmsMasterKeyId, "alias/aws/sqs")
+        .send()
+        .await
+        .unwrap();
+    let attributes = all_attributes(&server, &queue_url).await;
+    assert_eq!(attributes[&QueueAttributeName::SqsManagedSseEnabled], "false");
+}
+
+storage_matrix!(region_and_account_id_appear_in_arns_and_urls);
+async fn region_and_account_id_appear_in_arns_and_urls() {
+    let server = TestServer::start_with(local_sqs::Config {
+        region: "eu-west-1".to_string(),
+        account_id: "999999999999".to_string(),
+        ..Default::default()
+    })
+    .await;
+    let queue_url = server.create_queue("regional").await;
+    assert_eq!(
+        queue_url,
+        format!("http://{}/999999999999/regional", server.addr)
+    );
+
+    let attributes = all_attributes(&server, &queue_url).await;
+    assert_eq!(
+        attributes[&QueueAttributeName::QueueArn],
+        "arn:aws:sqs:eu-west-1:999999999999:regional"
+    );
+}
+
+fn with_default_attributes(defaults: &[(&str, &str)]) -> local_sqs::Config {
+    local_sqs::Config {
+        default_queue_attributes: defaults
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+        ..local_sqs::Config::default()
+    }
+}
+
+storage_matrix!(server_defaults_override_aws_defaults);
+async fn server_defaults_override_aws_defaults() {
+    let server = TestServer::start_with(with_default_attributes(&[
+        ("VisibilityTimeout", "5"),
+        ("ReceiveMessageWaitTimeSeconds", "1"),
+    ]))
+    .await;
+
+    let queue_url = server.create_queue("defaults").await;
+    let attributes = all_attributes(&server, &queue_url).await;
+    assert_eq!(attributes[&QueueAttributeName::VisibilityTimeout], "5");
+    assert_eq!(attributes[&QueueAttributeName::ReceiveMessageWaitTimeSeconds], "1");
+    assert_eq!(attributes[&QueueAttributeName::DelaySeconds], "0");
+
+    let queue_url = server
+        .client
+        .create_queue()
+        .queue_name("explicit")
+        .attributes(QueueAttributeName::VisibilityTimeout, "60")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    let attributes = all_attributes(&server, &queue_url).await;
+    assert_eq!(attributes[&QueueAttributeName::VisibilityTimeout], "60");
+    assert_eq!(attributes[&QueueAttributeName::ReceiveMessageWaitTimeSeconds], "1");
+}
+
+#[tokio::test]
+async fn invalid_server_defaults_fail_startup() {
+    for defaults in [
+        [("VisibilityTimeout", "-1")],
+        [("NotAnAttribute", "1")],
+        [("FifoQueue", "true")],
+    ] {
+        let mut config = with_default_attributes(&defaults);
+        config.host = "127.0.0.1".to_string();
+        config.port = 0;
+        let err = local_sqs::serve(config).await.err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
+
+storage_matrix!(fifo_queue_cannot_be_changed_after_creation);
+async fn fifo_queue_cannot_be_changed_after_creation() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("standard").await;
+
+    for value in ["true", "false"] {
+        let err = server
+            .client
+            .set_queue_attributes()
+            .queue_url(&queue_url)
+            .attributes(QueueAttributeName::FifoQueue, value)
+            .send()
+            .await
+            .unwrap_err()
+            .into_service_error();
+        assert!(err.is_invalid_attribute_name(), "{}: {:?}", value, err);
+    }
+}
+
+storage_matrix!(fifo_attributes_are_rejected_on_standard_queues);
+async fn fifo_attributes_are_rejected_on_standard_queues() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("standard").await;
+    let fifo_url = server
+        .client
+        .create_queue()
+        .queue_name("ordered.fifo")
+        .attributes(QueueAttributeName::FifoQueue, "true")
+        .attributes(QueueAttributeName::ContentBasedDeduplication, "true")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+
+    for (name, value) in [
+        (QueueAttributeName::ContentBasedDeduplication, "true"),
+        (QueueAttributeName::DeduplicationScope, "queue"),
+        (QueueAttributeName::FifoThroughputLimit, "perQueue"),
+    ] {
+        let err = server
+            .client
+            .create_queue()
+            .queue_name("other")
+            .attributes(name.clone(), value)
+            .send()
+            .await
+            .unwrap_err()
+            .into_service_error();
+        assert!(err.is_invalid_attribute_name(), "{:?}: {:?}", name, err);
+
+        let err = server
+            .client
+            .set_queue_attributes()
+            .queue_url(&queue_url)
+            .attributes(name.clone(), value)
+            .send()
+            .await
+            .unwrap_err()
+            .into_service_error();
+        assert!(err.is_invalid_attribute_name(), "{:?}: {:?}", name, err);
+
+        server
+            .client
+            .set_queue_attributes()
+            .queue_url(&fifo_url)
+            .attributes(name.clone(), value)
+            .send()
+            .await
+            .unwrap();
+    }
+}
+
+storage_matrix!(fifo_server_defaults_only_apply_to_fifo_queues);
+async fn fifo_server_defaults_only_apply_to_fifo_queues() {
+    let server =
+        TestServer::start_with(with_default_attributes(&[("ContentBasedDeduplication", "true")]))
+            .await;
+
+    let queue_url = server.create_queue("standard").await;
+    let attributes = all_attributes(&server, &queue_url).await;
+    assert!(!attributes.contains_key(&QueueAttributeName::ContentBasedDeduplication));
+
+    let fifo_url = server
+        .client
+        .create_queue()
+        .queue_name("ordered.fifo")
+        .attributes(QueueAttributeName::FifoQueue, "true")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    let attributes = all_attributes(&server, &fifo_url).await;
+    assert_eq!(attributes[&QueueAttributeName::ContentBasedDeduplication], "true");
+}
+
+/// Values each attribute in the table should accept and reject, derived
+/// from its kind. JSON and free-form string attributes have tests of their
+/// own.
+fn matrix_values(spec: &AttributeSpec) -> Option<(Vec<String>, Vec<String>)> {
+    match spec.kind {
+        AttributeKind::Integer { min, max } => Some((
+            vec![min.to_string(), max.to_string()],
+            vec![(min - 1).to_string(), max.saturating_add(1).to_string(), "ten".to_string()],
+        )),
+        AttributeKind::Boolean => Some((
+            vec!["true".to_string(), "false".to_string()],
+            vec!["yes".to_string()],
+        )),
+        AttributeKind::Enum(values) => Some((
+            values.iter().map(|v| v.to_string()).collect(),
+            vec!["nope".to_string()],
+        )),
+        AttributeKind::Json | AttributeKind::String => None,
+    }
+}
+
+/// The error code of a raw action, or "" if it succeeded.
+async fn error_code(server: &TestServer, action: &str, body: serde_json::Value) -> String {
+    let target = format!("AmazonSQS.{}", action);
+    let (status, response) = server.action(&target, &body.to_string()).await;
+    if status == 200 {
+        return String::new();
+    }
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    response["__type"].as_str().unwrap().rsplit('#').next().unwrap().to_string()
+}
+
+storage_matrix!(attribute_table_is_enforced_everywhere);
+async fn attribute_table_is_enforced_everywhere() {
+    let server = TestServer::start().await;
+    let standard = server.create_queue("matrix").await;
+    let fifo = server
+        .client
+        .create_queue()
+        .queue_name("matrix.fifo")
+        .attributes(QueueAttributeName::FifoQueue, "true")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+
+    let mut created = 0;
+    for spec in QUEUE_ATTRIBUTES {
+        // FifoQueue has only one valid value for a queue once its name is
+        // chosen, and is covered by the FIFO tests.
+        if spec.name == "FifoQueue" {
+            continue;
+        }
+        let Some((valid, invalid)) = matrix_values(spec) else {
+            continue;
+        };
+        let queue_url = if spec.fifo_only { &fifo } else { &standard };
+        let cases = valid.iter().map(|v| (v, true)).chain(invalid.iter().map(|v| (v, false)));
+        for (value, is_valid) in cases {
+            let case = format!("{}={}", spec.name, value);
+            let expected = match (spec.mutability, is_valid) {
+                (Mutability::ReadOnly, _) => "InvalidAttributeName",
+                (_, true) => "",
+                (_, false) => "InvalidAttributeValue",
+            };
+
+            created += 1;
+            let mut attributes = serde_json::json!({ spec.name: value });
+            let mut name = format!("matrix-{}", created);
+            if spec.fifo_only {
+                attributes["FifoQueue"] = "true".into();
+                name.push_str(".fifo");
+            }
+            let body = serde_json::json!({"QueueName": name, "Attributes": attributes});
+            assert_eq!(error_code(&server, "CreateQueue", body).await, expected, "create {}", case);
+
+            let expected_set = match spec.mutability {
+                Mutability::Mutable => expected,
+                _ => "InvalidAttributeName",
+            };
+            let body = serde_json::json!({
+                "QueueUrl": queue_url,
+                "Attributes": { spec.name: value },
+            });
+            let code = error_code(&server, "SetQueueAttributes", body).await;
+            assert_eq!(code, expected_set, "set {}", case);
+
+            if !expected.is_empty() {
+                let fixture = format!(
+                    "queues:\n  - name: fixture-{}\n    attributes:\n      {}: \"{}\"\n",
+                    created, spec.name, value
+                );
+                let (status, body) = server.admin("POST", "/fixtures", &fixture).await;
+                assert_eq!(status, 400, "fixture {}: {}", case, body);
+            }
+        }
+    }
+
+    // Parameters that stand in for an attribute in a single request have the
+    // attribute's range.
+    let url = standard.as_str();
+    for (action, body) in [
+        ("ReceiveMessage", serde_json::json!({"QueueUrl": url, "WaitTimeSeconds": 21})),
+        ("ReceiveMessage", serde_json::json!({"QueueUrl": url, "VisibilityTimeout": 43201})),
+        (
+            "SendMessage",
+            serde_json::json!({"QueueUrl": url, "MessageBody": "m", "DelaySeconds": 901}),
+        ),
+        (
+            "ChangeMessageVisibility",
+            serde_json::json!({"QueueUrl": url, "ReceiptHandle": "h", "VisibilityTimeout": 43201}),
+        ),
+    ] {
+        let case = body.to_string();
+        assert_eq!(error_code(&server, action, body).await, "InvalidParameterValue", "{}", case);
+    }
+
+    // `All` returns every attribute the table lists for it, and no others.
+    let body = serde_json::json!({"QueueUrl": url, "AttributeNames": ["All"]});
+    let (_, response) = server.action("AmazonSQS.GetQueueAttributes", &body.to_string()).await;
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    for name in response["Attributes"].as_object().unwrap().keys() {
+        let spec = QUEUE_ATTRIBUTES.iter().find(|spec| spec.name == name);
+        assert!(spec.is_some_and(|spec| spec.in_all), "{} returned for All", name);
+    }
+    for spec in QUEUE_ATTRIBUTES.iter().filter(|spec| spec.mutability == Mutability::ReadOnly) {
+        let listed = response["Attributes"].get(spec.name).is_some();
+        assert_eq!(listed, spec.in_all, "{} for All", spec.name);
+    }
+}
+
+storage_matrix!(unknown_attribute_names_are_rejected);
+async fn unknown_attribute_names_are_rejected() {
+    let server = TestServer::start().await;
+    let url = server.create_queue("typos").await;
+
+    for name in ["VisibilityTimeOut", "Colour"] {
+        let body = serde_json::json!({"QueueUrl": url, "AttributeNames": ["All", name]});
+        let (status, response) = server
+            .action("AmazonSQS.GetQueueAttributes", &body.to_string())
+            .await;
+        assert_eq!(status, 400, "{}", response);
+        assert!(response.contains("InvalidAttributeName"), "{}", response);
+        assert!(response.contains(&format!("Unknown Attribute {}.", name)), "{}", response);
+
+        let body = serde_json::json!({"QueueUrl": url, "Attributes": {name: "60"}});
+        let (status, response) = server
+            .action("AmazonSQS.SetQueueAttributes", &body.to_string())
+            .await;
+        assert_eq!(status, 400, "{}", response);
+        assert!(response.contains("InvalidAttributeName"), "{}", response);
+        assert!(response.contains(name), "{}", response);
+    }
+    let attributes = all_attributes(&server, &url).await;
+    assert_eq!(attributes[&QueueAttributeName::VisibilityTimeout], "30");
+    assert!(!attributes.contains_key(&QueueAttributeName::from("VisibilityTimeOut")));
+
+    // The emulator's own attributes are known.
+    let body = serde_json::json!({
+        "QueueUrl": url,
+        "AttributeNames": ["LocalSqsPaused", "ApproximateAgeOfOldestMessage"],
+    });
+    let (status, response) = server
+        .action("AmazonSQS.GetQueueAttributes", &body.to_string())
+        .await;
+    assert_eq!(status, 200, "{}", response);
+
+    // Lenient validation leaves unknown names out instead.
+    server.admin("PUT", "/validation", r#"{"mode": "lenient"}"#).await;
+    let body = serde_json::json!({"QueueUrl": url, "AttributeNames": ["Colour", "QueueArn"]});
+    let (status, response) = server
+        .action("AmazonSQS.GetQueueAttributes", &body.to_string())
+        .await;
+    assert_eq!(status, 200, "{}", response);
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["Attributes"].as_object().unwrap().len(), 1);
+}
+
+storage_matrix!(recreating_compares_effective_attributes);
+async fn recreating_compares_effective_attributes() {
+    let server = TestServer::start().await;
+    let dlq_url = server.create_queue("idempotent-dlq").await;
+    let dlq_arn = all_attributes(&server, &dlq_url).await[&QueueAttributeName::QueueArn].clone();
+    let policy = format!(
+        concat!(
+            r#"{{"Version":"2012-10-17","Statement":[{{"Effect":"Allow","Principal":"*","#,
+            r#""Action":"sqs:SendMessage","Resource":"{}"}}]}}"#
+        ),
+        dlq_arn
+    );
+    let redrive = format!(r#"{{"deadLetterTargetArn":"{}","maxReceiveCount":"3"}}"#, dlq_arn);
+    let create = |attributes: &[(QueueAttributeName, &str)]| {
+        let mut create = server.client.create_queue().queue_name("idempotent");
+        for (name, value) in attributes {
+            create = create.attributes(name.clone(), *value);
+        }
+        create.send()
+    };
+    let url = create(&[
+        (QueueAttributeName::RedrivePolicy, &redrive),
+        (QueueAttributeName::Policy, &policy),
+    ])
+    .await
+    .unwrap()
+    .queue_url
+    .unwrap();
+
+    // Defaults spelled out, and the same documents formatted differently.
+    let reformatted_redrive = format!(
+        "{{ \"maxReceiveCount\": 3,\n  \"deadLetterTargetArn\": \"{}\" }}",
+        dlq_arn
+    );
+    let reformatted_policy = serde_json::to_string_pretty(
+        &serde_json::from_str::<serde_json::Value>(&policy).unwrap(),
+    )
+    .unwrap();
+    let same = create(&[
+        (QueueAttributeName::RedrivePolicy, &reformatted_redrive),
+        (QueueAttributeName::Policy, &reformatted_policy),
+        (QueueAttributeName::VisibilityTimeout, "30"),
+        (QueueAttributeName::DelaySeconds, "0"),
+        (QueueAttributeName::MessageRetentionPeriod, "345600"),
+    ])
+    .await
+    .unwrap();
+    assert_eq!(same.queue_url(), Some(url.as_str()));
+
+    let other_redrive = redrive.replace(r#""3""#, r#""4""#);
+    for different in [
+        vec![
+            (QueueAttributeName::RedrivePolicy, redrive.as_str()),
+            (QueueAttributeName::Policy, policy.as_str()),
+            (QueueAttributeName::VisibilityTimeout, "31"),
+        ],
+        vec![
+            (QueueAttributeName::RedrivePolicy, other_redrive.as_str()),
+            (QueueAttributeName::Policy, policy.as_str()),
+        ],
+        vec![(QueueAttributeName::RedrivePolicy, redrive.as_str())],
+    ] {
+        let err = create(&different).await.unwrap_err().into_service_error();
+        assert!(err.is_queue_name_exists(), "{:?}: {:?}", different, err);
+    }
+}
+
+storage_matrix!(set_queue_attributes_validates_the_whole_request_first);
+async fn set_queue_attributes_validates_the_whole_request_first() {
+    let server = TestServer::start().await;
+    let url = server.create_queue("settable").await;
+    let missing_dlq = concat!(
+        r#"{"deadLetterTargetArn":"arn:aws:sqs:us-east-1:000000000000:missing","#,
+        r#""maxReceiveCount":"3"}"#
+    );
+    let cases = [
+        (vec![("VisibilityTimeout", "43201")], "InvalidAttributeValue"),
+        (vec![("MessageRetentionPeriod", "59")], "InvalidAttributeValue"),
+        (vec![("MaximumMessageSize", "1023")], "InvalidAttributeValue"),
+        (vec![("ReceiveMessageWaitTimeSeconds", "21")], "InvalidAttributeValue"),
+        (vec![("DelaySeconds", "soon")], "InvalidAttributeValue"),
+        (vec![("RedrivePolicy", missing_dlq)], "InvalidParameterValue"),
+        (vec![("Policy", "{}")], "InvalidAttributeValue"),
+        (vec![("QueueArn", "arn:aws:sqs:us-east-1:0:other")], "InvalidAttributeName"),
+        (vec![("FifoQueue", "true")], "InvalidAttributeName"),
+        (vec![("ContentBasedDeduplication", "true")], "InvalidAttributeName"),
+        (vec![("Colour", "blue")], "InvalidAttributeName"),
+        // One bad attribute fails the request, and the good ones with it.
+        (vec![("VisibilityTimeout", "10"), ("DelaySeconds", "901")], "InvalidAttributeValue"),
+    ];
+    for (attributes, expected) in cases {
+        let attributes: serde_json::Map<_, _> =
+            attributes.iter().map(|(name, value)| (name.to_string(), (*value).into())).collect();
+        let body = serde_json::json!({"QueueUrl": url, "Attributes": attributes});
+        let code = error_code(&server, "SetQueueAttributes", body).await;
+        assert_eq!(code, expected, "{:?}", attributes);
+    }
+    let attributes = all_attributes(&server, &url).await;
+    assert_eq!(attributes[&QueueAttributeName::VisibilityTimeout], "30");
+    assert_eq!(attributes[&QueueAttributeName::DelaySeconds], "0");
+
+    // Queue-level delays and waits apply to requests that don't set their own.
+    let set = server.client.set_queue_attributes().queue_url(&url);
+    let set = set
+        .attributes(QueueAttributeName::DelaySeconds, "2")
+        .attributes(QueueAttributeName::ReceiveMessageWaitTimeSeconds, "1");
+    set.send().await.unwrap();
+    server.client.send_message().queue_url(&url).message_body("m").send().await.unwrap();
+    let attributes = all_attributes(&server, &url).await;
+    assert_eq!(attributes[&QueueAttributeName::ApproximateNumberOfMessagesDelayed], "1");
+    let started = std::time::Instant::now();
+    let received = server.client.receive_message().queue_url(&url).send().await.unwrap();
+    assert!(received.messages().is_empty());
+    let waited = started.elapsed();
+    assert!(waited >= std::time::Duration::from_millis(900), "{:?}", waited);
+}
+
+/// The names of the attributes `All` returns for the queue at `queue_url`,
+/// sorted.
+async fn all_attribute_names(server: &TestServer, queue_url: &str) -> Vec<String> {
+    let attributes = all_attributes(server, queue_url).await;
+    let mut names: Vec<_> = attributes.keys().map(|name| name.as_str().to_string()).collect();
+    names.sort();
+    names
+}
+
+storage_matrix!(all_returns_every_attribute_the_queue_has);
+async fn all_returns_every_attribute_the_queue_has() {
+    let server = TestServer::start().await;
+    let standard = [
+        "ApproximateNumberOfMessages",
+        "ApproximateNumberOfMessagesDelayed",
+        "ApproximateNumberOfMessagesNotVisible",
+        "CreatedTimestamp",
+        "DelaySeconds",
+        "LastModifiedTimestamp",
+        "LocalSqsPaused",
+        "MaximumMessageSize",
+        "MessageRetentionPeriod",
+        "QueueArn",
+        "ReceiveMessageWaitTimeSeconds",
+        "SqsManagedSseEnabled",
+        "VisibilityTimeout",
+    ];
+    let dlq_url = server.create_queue("all-dlq").await;
+    assert_eq!(all_attribute_names(&server, &dlq_url).await, standard);
+
+    // FIFO queues add their own attributes, at their AWS defaults.
+    let fifo_url = server
+        .client
+        .create_queue()
+        .queue_name("all.fifo")
+        .attributes(QueueAttributeName::FifoQueue, "true")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    let fifo_only = [
+        "ContentBasedDeduplication",
+        "DeduplicationScope",
+        "FifoQueue",
+        "FifoThroughputLimit",
+    ];
+    let mut expected: Vec<_> = standard.iter().chain(&fifo_only).copied().collect();
+    expected.sort();
+    assert_eq!(all_attribute_names(&server, &fifo_url).await, expected);
+    let attributes = all_attributes(&server, &fifo_url).await;
+    assert_eq!(attributes[&QueueAttributeName::ContentBasedDeduplication], "false");
+    assert_eq!(attributes[&QueueAttributeName::DeduplicationScope], "queue");
+    assert_eq!(attributes[&QueueAttributeName::FifoThroughputLimit], "perQueue");
+
+    // Attributes that are only set on some queues are returned once set.
+    let dlq_arn = &all_attributes(&server, &dlq_url).await[&QueueAttributeName::QueueArn];
+    let redrive_policy =
+        format!(r#"{{"deadLetterTargetArn":"{}","maxReceiveCount":"2"}}"#, dlq_arn);
+    let url = server
+        .client
+        .create_queue()
+        .queue_name("all-source")
+        .attributes(QueueAttributeName::RedrivePolicy, &redrive_policy)
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    let mut expected: Vec<_> = standard.iter().chain(&["RedrivePolicy"]).copied().collect();
+    expected.sort();
+    assert_eq!(all_attribute_names(&server, &url).await, expected);
+
+    // `All` alongside other names still returns everything, and names only
+    // returned when asked for are added to it.
+    let attributes = server
+        .client
+        .get_queue_attributes()
+        .queue_url(&url)
+        .attribute_names(QueueAttributeName::VisibilityTimeout)
+        .attribute_names(QueueAttributeName::All)
+        .attribute_names(QueueAttributeName::from("ApproximateAgeOfOldestMessage"))
+        .send()
+        .await
+        .unwrap()
+        .attributes
+        .unwrap();
+    assert_eq!(attributes.len(), expected.len() + 1);
+    let age = QueueAttributeName::from("ApproximateAgeOfOldestMessage");
+    assert_eq!(attributes[&age], "0");
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use common::{TestServer, storage_matrix};
+use local_sqs::urls::normalize_queue_url;
+
+storage_matrix!(equivalent_queue_url_spellings_resolve_to_the_same_queue);
+async fn equivalent_queue_url_spellings_resolve_to_the_same_queue() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("jobs-2").await;
+    let port = server.addr.port();
+    assert_eq!(queue_url, format!("http://127.0.0.1:{}/000000000000/jobs-2", port));
+
+    let spellings = [
+        queue_url.clone(),
+        format!("{}/", queue_url),
+        format!("{}//", queue_url),
+        format!("HTTP://127.0.0.1:{}/000000000000/jobs-2/", port),
+        format!("http://127.0.0.1:{}/000000000000/jobs%2D2", port),
+        format!("http://127.0.0.1:{}/%30%30%30000000000/jobs-2", port),
+        format!("http://127.0.0.1:{}/000000000000/jobs-2?x=1", port),
+    ];
+    for (i, spelling) in spellings.iter().enumerate() {
+        let body = format!(r#"{{"QueueUrl": "{}", "MessageBody": "m{}"}}"#, spelling, i);
+        let (status, response) = server.action("AmazonSQS.SendMessage", &body).await;
+        assert_eq!(status, 200, "{}: {}", spelling, response);
+
+        let body = format!(
+            r#"{{"QueueUrl": "{}", "AttributeNames": ["ApproximateNumberOfMessages"]}}"#,
+            spelling
+        );
+        let (status, response) = server.action("AmazonSQS.GetQueueAttributes", &body).await;
+        assert_eq!(status, 200, "{}: {}", spelling, response);
+        assert!(response.contains(&format!(r#""{}""#, i + 1)), "{}: {}", spelling, response);
+    }
+
+    let other = format!("http://127.0.0.1:{}/000000000000/jobs-3", port);
+    let body = format!(r#"{{"QueueUrl": "{}", "MessageBody": "m"}}"#, other);
+    let (status, response) = server.action("AmazonSQS.SendMessage", &body).await;
+    assert_eq!(status, 400);
+    assert!(response.contains("QueueDoesNotExist"), "{}", response);
+}
+
+#[test]
+fn hosts_are_lowercased_and_default_ports_dropped() {
+    for (url, normalized) in [
+        ("http://localhost:80/000000000000/q", "http://localhost/000000000000/q"),
+        ("https://EXAMPLE.com:443/000000000000/q/", "https://example.com/000000000000/q"),
+        ("http://LocalHost:9324/000000000000/q", "http://localhost:9324/000000000000/q"),
+        ("http://[::1]:9324/000000000000/q", "http://[::1]:9324/000000000000/q"),
+        ("not a url", "not a url"),
+    ] {
+        assert_eq!(normalize_queue_url(url), normalized, "{}", url);
+    }
+}
+
+storage_matrix!(malformed_queue_urls_explain_what_is_wrong);
+async fn malformed_queue_urls_explain_what_is_wrong() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("jobs").await;
+    let port = server.addr.port();
+
+    for (queue_url, reason) in [
+        ("jobs".to_string(), "use GetQueueUrl"),
+        ("".to_string(), "must not be empty"),
+        ("ftp://127.0.0.1/000000000000/jobs".to_string(), "absolute http or https URL"),
+        ("/000000000000/jobs".to_string(), "absolute http or https URL"),
+        (
+            "http://sqs.us-east-1.amazonaws.com/000000000000/jobs".to_string(),
+            "does not belong to this endpoint",
+        ),
+        (queue_url.replace(&port.to_string(), "1"), "does not belong to this endpoint"),
+    ] {
+        let body = format!(r#"{{"QueueUrl": "{}", "MessageBody": "m"}}"#, queue_url);
+        let (status, response) = server.action("AmazonSQS.SendMessage", &body).await;
+        assert_eq!(status, 400, "{}: {}", queue_url, response);
+        assert!(response.contains("InvalidParameterValue"), "{}: {}", queue_url, response);
+        assert!(response.contains(reason), "{}: {}", queue_url, response);
+    }
+
+    let error = server
+        .client
+        .get_queue_attributes()
+        .queue_url("http://localhost:1/000000000000/jobs")
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    let message = error.meta().message().unwrap();
+    assert!(message.contains(&format!("http://127.0.0.1:{}", port)), "{}", message);
+
+    // A URL on this endpoint for a queue that doesn't exist still just
+    // doesn't exist.
+    let missing = queue_url.replace("jobs", "other");
+    let body = format!(r#"{{"QueueUrl": "{}", "MessageBody": "m"}}"#, missing);
+    let (_, response) = server.action("AmazonSQS.SendMessage", &body).await;
+    assert!(response.contains("QueueDoesNotExist"), "{}", response);
+}
+
+storage_matrix!(other_host_names_for_the_server_resolve_to_its_queues);
+async fn other_host_names_for_the_server_resolve_to_its_queues() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("jobs").await;
+    let port = server.addr.port();
+
+    for host in ["localhost", "[::1]", "sqs.internal"] {
+        let spelling = queue_url.replace("127.0.0.1", host);
+        let body = format!(r#"{{"QueueUrl": "{}", "MessageBody": "{}"}}"#, spelling, host);
+        let (status, response) = server.action("AmazonSQS.SendMessage", &body).await;
+        assert_eq!(status, 200, "{}: {}", spelling, response);
+    }
+    let received = server.receive(&queue_url, None).await;
+    let mut bodies: Vec<_> = received.iter().filter_map(|m| m.body()).collect();
+    bodies.sort();
+    assert_eq!(bodies, ["[::1]", "localhost", "sqs.internal"]);
+
+    // Another port is another endpoint, unless validation is lenient.
+    let elsewhere = format!("http://localhost:{}/000000000000/jobs", port + 1);
+    let body = format!(r#"{{"QueueUrl": "{}", "MessageBody": "m"}}"#, elsewhere);
+    let (_, response) = server.action("AmazonSQS.SendMessage", &body).await;
+    assert!(response.contains("does not belong to this endpoint"), "{}", response);
+    server.admin("PUT", "/validation", r#"{"mode": "lenient"}"#).await;
+    let (_, response) = server.action("AmazonSQS.SendMessage", &body).await;
+    assert!(response.contains("QueueDoesNotExist"), "{}", response);
+}
@@ -0,0 +1,257 @@
+mod common;
+
+use aws_sdk_sqs::types::QueueAttributeName;
+use common::fake_redis::FakeRedis;
+use common::TestServer;
+use local_sqs::store::Storage;
+use local_sqs::Config;
+
+fn config(redis: &FakeRedis) -> Config {
+    Config {
+        storage: Storage::Redis,
+        redis_url: redis.url(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn queues_and_messages_survive_a_restart() {
+    let redis = FakeRedis::start();
+    let server = TestServer::start_with(config(&redis)).await;
+    let queue_url = server
+        .client
+        .create_queue()
+        .queue_name("durable")
+        .attributes(QueueAttributeName::VisibilityTimeout, "300")
+        .tags("team", "payments")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    for body in ["first", "second", "third"] {
+        server
+            .client
+            .send_message()
+            .queue_url(&queue_url)
+            .message_body(body)
+            .send()
+            .await
+            .unwrap();
+    }
+    let in_flight = server
+        .client
+        .receive_message()
+        .queue_url(&queue_url)
+        .send()
+        .await
+        .unwrap()
+        .messages
+        .unwrap()
+        .remove(0);
+    assert_eq!(in_flight.body(), Some("first"));
+    server.stop().await;
+
+    assert_eq!(redis.hash_len("local-sqs:queue:durable:messages"), 3);
+    assert_eq!(redis.hash_len("local-sqs:queue:durable:in_flight"), 1);
+
+    let server = TestServer::start_with(config(&redis)).await;
+    let queue_url = server.client.get_queue_url().queue_name("durable").send().await.unwrap();
+    let queue_url = queue_url.queue_url.unwrap();
+    let attributes = server
+        .client
+        .get_queue_attributes()
+        .queue_url(&queue_url)
+        .attribute_names(QueueAttributeName::All)
+        .send()
+        .await
+        .unwrap()
+        .attributes
+        .unwrap();
+    assert_eq!(attributes[&QueueAttributeName::VisibilityTimeout], "300");
+    assert_eq!(attributes[&QueueAttributeName::ApproximateNumberOfMessages], "2");
+    assert_eq!(attributes[&QueueAttributeName::ApproximateNumberOfMessagesNotVisible], "1");
+    let tags = server.client.list_queue_tags().queue_url(&queue_url).send().await.unwrap();
+    assert_eq!(tags.tags.unwrap()["team"], "payments");
+
+    server
+        .client
+        .delete_message()
+        .queue_url(&queue_url)
+        .receipt_handle(in_flight.receipt_handle().unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(redis.hash_len("local-sqs:queue:durable:messages"), 2);
+    assert_eq!(redis.hash_len("local-sqs:queue:durable:in_flight"), 0);
+
+    server.client.delete_queue().queue_url(&queue_url).send().await.unwrap();
+    assert_eq!(redis.hash_len("local-sqs:queue:durable"), 0);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn redis_failures_are_internal_errors() {
+    let redis = FakeRedis::start();
+    let server = TestServer::start_with(config(&redis)).await;
+    let queue_url = server.create_queue("fragile").await;
+
+    redis.stop();
+    let body = format!(r#"{{"QueueUrl":"{}","MessageBody":"lost"}}"#, queue_url);
+    let (status, body) = server.action("AmazonSQS.SendMessage", &body).await;
+    assert_eq!(status, 500);
+    assert!(body.contains("InternalError"), "{}", body);
+    let (status, _) = server
+        .action("AmazonSQS.CreateQueue", r#"{"QueueName":"never-stored"}"#)
+        .await;
+    assert_eq!(status, 500);
+
+    // Once Redis is back, the store reconnects; the send that failed never
+    // happened.
+    redis.resume();
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("kept")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(redis.hash_len("local-sqs:queue:fragile:messages"), 1);
+    let queues = server.client.list_queues().send().await.unwrap();
+    assert_eq!(queues.queue_urls().len(), 1);
+}
+
+#[tokio::test]
+async fn an_unreachable_redis_fails_startup() {
+    let redis = FakeRedis::start();
+    let config = Config {
+        host: "127.0.0.1".to_string(),
+        port: 0,
+        ..config(&redis)
+    };
+    drop(redis);
+    assert!(local_sqs::serve(config).await.is_err());
+}
+
+#[tokio::test]
+async fn in_flight_messages_are_scored_by_their_visibility_deadline() {
+    let redis = FakeRedis::start();
+    let server = TestServer::start_with(Config {
+        manual_clock: true,
+        ..config(&redis)
+    })
+    .await;
+    let queue_url = server.create_queue("claimed").await;
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("claimed")
+        .send()
+        .await
+        .unwrap();
+    let sent = redis.scores("local-sqs:queue:claimed:sent");
+    assert_eq!(sent.len(), 1);
+    assert_eq!(redis.scores("local-sqs:queue:claimed:ready").len(), 1);
+
+    server
+        .client
+        .receive_message()
+        .queue_url(&queue_url)
+        .visibility_timeout(300)
+        .send()
+        .await
+        .unwrap();
+    let claimed = redis.scores("local-sqs:queue:claimed:deadlines");
+    assert_eq!(claimed, [sent[0] + 300_000.0]);
+    assert!(redis.scores("local-sqs:queue:claimed:ready").is_empty());
+    assert_eq!(redis.hash_len("local-sqs:queue:claimed:in_flight"), 1);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn servers_sharing_a_redis_never_deliver_a_message_twice() {
+    let redis = FakeRedis::start();
+    let first = TestServer::start_with(config(&redis)).await;
+    let queue_url = first.create_queue("shared").await;
+    for i in 0..5 {
+        first
+            .client
+            .send_message()
+            .queue_url(&queue_url)
+            .message_body(format!("message {}", i))
+            .send()
+            .await
+            .unwrap();
+    }
+    let second = TestServer::start_with(config(&redis)).await;
+    let second_url = second.client.get_queue_url().queue_name("shared").send().await.unwrap();
+    let second_url = second_url.queue_url.unwrap();
+
+    // A receive that loses the race for a claim fails rather than hand out
+    // a message the other server has claimed.
+    let body = format!(r#"{{"QueueUrl":"{}","VisibilityTimeout":300}}"#, queue_url);
+    let second_body = format!(r#"{{"QueueUrl":"{}","VisibilityTimeout":300}}"#, second_url);
+    let (a, b, c, d, e, f) = tokio::join!(
+        first.action("AmazonSQS.ReceiveMessage", &body),
+        second.action("AmazonSQS.ReceiveMessage", &second_body),
+        first.action("AmazonSQS.ReceiveMessage", &body),
+        second.action("AmazonSQS.ReceiveMessage", &second_body),
+        first.action("AmazonSQS.ReceiveMessage", &body),
+        second.action("AmazonSQS.ReceiveMessage", &second_body),
+    );
+    let mut received = Vec::new();
+    for (status, body) in [a, b, c, d, e, f] {
+        if status != 200 {
+            assert!(body.contains("InternalError"), "{}", body);
+            continue;
+        }
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        for message in body["Messages"].as_array().into_iter().flatten() {
+            received.push(message["MessageId"].as_str().unwrap().to_string());
+        }
+    }
+    let mut unique = received.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(unique.len(), received.len(), "{:?}", received);
+    assert_eq!(redis.hash_len("local-sqs:queue:shared:in_flight"), received.len());
+    first.stop().await;
+    second.stop().await;
+}
+
+#[tokio::test]
+async fn expired_deduplication_ids_are_removed() {
+    let redis = FakeRedis::start();
+    let server = TestServer::start_with(Config {
+        manual_clock: true,
+        ..config(&redis)
+    })
+    .await;
+    let queue_url = server
+        .client
+        .create_queue()
+        .queue_name("orders.fifo")
+        .attributes(QueueAttributeName::FifoQueue, "true")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("order")
+        .message_group_id("orders")
+        .message_deduplication_id("order-1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(redis.hash_len("local-sqs:queue:orders.fifo:deduplication"), 1);
+
+    server.advance_clock(301).await;
+    assert_eq!(redis.hash_len("local-sqs:queue:orders.fifo:deduplication"), 0);
+    server.stop().await;
+}
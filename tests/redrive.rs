@@ -0,0 +1,349 @@
+mod common;
+
+use aws_sdk_sqs::error::ProvideErrorMetadata;
+use aws_sdk_sqs::types::{MessageSystemAttributeName, QueueAttributeName};
+use common::{TestServer, storage_matrix};
+use serde_json::{json, Value};
+
+/// Creates `source` redriving into `dlq` after a single receive.
+async fn dead_letter_setup(server: &TestServer) -> (String, String) {
+    let dlq_url = server.create_queue("dlq").await;
+    let source_url = server
+        .client
+        .create_queue()
+        .queue_name("source")
+        .attributes(
+            QueueAttributeName::RedrivePolicy,
+            r#"{"deadLetterTargetArn":"arn:aws:sqs:us-east-1:000000000000:dlq","maxReceiveCount":"1"}"#,
+        )
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    (source_url, dlq_url)
+}
+
+async fn redrive(server: &TestServer, queue: &str, body: &str) -> (u16, Value) {
+    let (status, body) = server
+        .admin("POST", &format!("/queues/{}/redrive", queue), body)
+        .await;
+    (status, serde_json::from_str(&body).unwrap())
+}
+
+storage_matrix!(dead_lettered_messages_go_back_to_their_source);
+async fn dead_lettered_messages_go_back_to_their_source() {
+    let server = TestServer::start().await;
+    let (source_url, _) = dead_letter_setup(&server).await;
+    for i in 0..3 {
+        server.send(&source_url, &format!("message {}", i)).await;
+    }
+
+    // The first receive uses up maxReceiveCount; the next one dead-letters.
+    assert_eq!(server.receive(&source_url, Some(0)).await.len(), 3);
+    assert!(server.receive(&source_url, Some(0)).await.is_empty());
+
+    let (status, summary) = redrive(&server, "dlq", "").await;
+    assert_eq!(status, 200);
+    assert_eq!(summary["moved"], json!({"source": 3}));
+    assert_eq!(summary["total"], 3);
+    assert_eq!(summary["error"], Value::Null);
+
+    let messages = server.receive(&source_url, Some(0)).await;
+    assert_eq!(messages.len(), 3);
+    for message in messages {
+        let attributes = message.attributes.unwrap();
+        assert_eq!(
+            attributes[&MessageSystemAttributeName::ApproximateReceiveCount],
+            "1"
+        );
+    }
+}
+
+storage_matrix!(destination_and_limit_are_honored);
+async fn destination_and_limit_are_honored() {
+    let server = TestServer::start().await;
+    let (_, dlq_url) = dead_letter_setup(&server).await;
+    let other_url = server.create_queue("other").await;
+    for i in 0..5 {
+        server.send(&dlq_url, &format!("message {}", i)).await;
+    }
+
+    let (status, summary) = redrive(&server, "dlq", r#"{"destination": "other", "limit": 2}"#).await;
+    assert_eq!(status, 200);
+    assert_eq!(summary["moved"], json!({"other": 2}));
+
+    assert_eq!(server.receive(&other_url, Some(0)).await.len(), 2);
+    assert_eq!(server.receive(&dlq_url, Some(0)).await.len(), 3);
+}
+
+storage_matrix!(queues_without_sources_need_a_destination);
+async fn queues_without_sources_need_a_destination() {
+    let server = TestServer::start().await;
+    let lonely_url = server.create_queue("lonely").await;
+    server.create_queue("target").await;
+    server.send(&lonely_url, "message 0").await;
+
+    let (status, _) = redrive(&server, "lonely", "").await;
+    assert_eq!(status, 400);
+
+    let (status, summary) = redrive(
+        &server,
+        "lonely",
+        r#"{"destination": "arn:aws:sqs:us-east-1:000000000000:target"}"#,
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(summary["moved"], json!({"target": 1}));
+
+    let (status, _) = redrive(&server, "lonely", r#"{"destination": "missing"}"#).await;
+    assert_eq!(status, 400);
+}
+
+fn policy(target: &str, max_receive_count: &str) -> String {
+    format!(
+        r#"{{"deadLetterTargetArn":"arn:aws:sqs:us-east-1:000000000000:{}","maxReceiveCount":"{}"}}"#,
+        target, max_receive_count
+    )
+}
+
+async fn set_redrive_policy(
+    server: &TestServer,
+    queue_url: &str,
+    policy: &str,
+) -> Result<(), (Option<String>, Option<String>)> {
+    server
+        .client
+        .set_queue_attributes()
+        .queue_url(queue_url)
+        .attributes(QueueAttributeName::RedrivePolicy, policy)
+        .send()
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            let e = e.into_service_error();
+            (e.code().map(str::to_string), e.message().map(str::to_string))
+        })
+}
+
+storage_matrix!(invalid_redrive_policies_are_rejected);
+async fn invalid_redrive_policies_are_rejected() {
+    let server = TestServer::start().await;
+    let source_url = server.create_queue("source").await;
+    server.create_queue("dlq").await;
+    server
+        .client
+        .create_queue()
+        .queue_name("dlq.fifo")
+        .attributes(QueueAttributeName::FifoQueue, "true")
+        .send()
+        .await
+        .unwrap();
+
+    let cases = [
+        ("{not json".to_string(), "not a valid JSON map"),
+        (policy("dlq", "0"), "maxReceiveCount: 0"),
+        (policy("dlq", "1001"), "maxReceiveCount: 1001"),
+        (policy("missing", "3"), "Dead letter target does not exist"),
+        (policy("dlq.fifo", "3"), "same type of queue"),
+    ];
+    for (value, expected) in cases {
+        let (code, message) = set_redrive_policy(&server, &source_url, &value)
+            .await
+            .unwrap_err();
+        assert_eq!(code.as_deref(), Some("InvalidParameterValue"), "{}", value);
+        assert!(message.unwrap().contains(expected), "{}", value);
+    }
+
+    let err = server
+        .client
+        .create_queue()
+        .queue_name("other")
+        .attributes(QueueAttributeName::RedrivePolicy, policy("missing", "3"))
+        .send()
+        .await
+        .unwrap_err();
+    assert!(format!("{:?}", err).contains("InvalidParameterValue"));
+}
+
+storage_matrix!(valid_redrive_policy_can_be_set_and_cleared);
+async fn valid_redrive_policy_can_be_set_and_cleared() {
+    let server = TestServer::start().await;
+    let source_url = server.create_queue("source").await;
+    server.create_queue("dlq").await;
+
+    set_redrive_policy(&server, &source_url, &policy("dlq", "1000"))
+        .await
+        .unwrap();
+    set_redrive_policy(&server, &source_url, "").await.unwrap();
+}
+
+storage_matrix!(redrive_policy_set_later_moves_messages);
+async fn redrive_policy_set_later_moves_messages() {
+    let server = TestServer::start().await;
+    let source_url = server.create_queue("source").await;
+    let dlq_url = server.create_queue("dlq").await;
+    set_redrive_policy(&server, &source_url, &policy("dlq", "1"))
+        .await
+        .unwrap();
+
+    let attributes = server
+        .client
+        .get_queue_attributes()
+        .queue_url(&source_url)
+        .attribute_names(QueueAttributeName::RedrivePolicy)
+        .send()
+        .await
+        .unwrap()
+        .attributes
+        .unwrap();
+    let returned: Value =
+        serde_json::from_str(&attributes[&QueueAttributeName::RedrivePolicy]).unwrap();
+    assert_eq!(
+        returned["deadLetterTargetArn"],
+        "arn:aws:sqs:us-east-1:000000000000:dlq"
+    );
+
+    server.send(&source_url, "message 0").await;
+    assert_eq!(server.receive(&source_url, Some(0)).await.len(), 1);
+    assert!(server.receive(&source_url, Some(0)).await.is_empty());
+    assert_eq!(server.receive(&dlq_url, Some(0)).await.len(), 1);
+}
+
+/// `GetQueueAttributes` for `queue_url` as the raw JSON response.
+async fn get_attributes_raw(server: &TestServer, queue_url: &str, names: &[&str]) -> Value {
+    let body = json!({ "QueueUrl": queue_url, "AttributeNames": names }).to_string();
+    let (status, body) = server.action("AmazonSQS.GetQueueAttributes", &body).await;
+    assert_eq!(status, 200, "{}", body);
+    serde_json::from_str(&body).unwrap()
+}
+
+storage_matrix!(redrive_policies_are_returned_in_the_aws_format);
+async fn redrive_policies_are_returned_in_the_aws_format() {
+    let server = TestServer::start().await;
+    // Neither is given in the canonical format: keys are out of order and
+    // spaced out.
+    let dlq_url = server
+        .client
+        .create_queue()
+        .queue_name("golden-dlq")
+        .attributes(
+            QueueAttributeName::RedriveAllowPolicy,
+            r#"{ "sourceQueueArns": ["arn:aws:sqs:us-east-1:000000000000:golden-source"],
+                 "redrivePermission": "byQueue" }"#,
+        )
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    let source_url = server
+        .client
+        .create_queue()
+        .queue_name("golden-source")
+        .attributes(
+            QueueAttributeName::RedrivePolicy,
+            r#"{ "maxReceiveCount": "5",
+                 "deadLetterTargetArn": "arn:aws:sqs:us-east-1:000000000000:golden-dlq" }"#,
+        )
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+
+    let golden: Value =
+        serde_json::from_str(include_str!("data/get_queue_attributes_redrive.json")).unwrap();
+    let golden = &golden["Attributes"];
+    for names in [&["RedrivePolicy"][..], &["All"]] {
+        let response = get_attributes_raw(&server, &source_url, names).await;
+        assert_eq!(response["Attributes"]["RedrivePolicy"], golden["RedrivePolicy"]);
+    }
+    for names in [&["RedriveAllowPolicy"][..], &["All"]] {
+        let response = get_attributes_raw(&server, &dlq_url, names).await;
+        assert_eq!(response["Attributes"]["RedriveAllowPolicy"], golden["RedriveAllowPolicy"]);
+    }
+}
+
+storage_matrix!(invalid_redrive_allow_policies_are_rejected);
+async fn invalid_redrive_allow_policies_are_rejected() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("allow").await;
+    let set = |policy: &'static str| {
+        server
+            .client
+            .set_queue_attributes()
+            .queue_url(&queue_url)
+            .attributes(QueueAttributeName::RedriveAllowPolicy, policy)
+            .send()
+    };
+
+    for policy in [
+        r#"{"redrivePermission":"sometimes"}"#,
+        r#"{"redrivePermission":"byQueue"}"#,
+        r#"{"redrivePermission":"allowAll","sourceQueueArns":["arn:aws:sqs:us-east-1:0:a"]}"#,
+    ] {
+        let err = set(policy).await.unwrap_err().into_service_error();
+        assert_eq!(err.code(), Some("InvalidParameterValue"), "{}: {:?}", policy, err);
+    }
+
+    set(r#"{"redrivePermission":"denyAll"}"#).await.unwrap();
+    let response = get_attributes_raw(&server, &queue_url, &["RedriveAllowPolicy"]).await;
+    assert_eq!(
+        response["Attributes"]["RedriveAllowPolicy"],
+        r#"{"redrivePermission":"denyAll"}"#
+    );
+    set("").await.unwrap();
+    let response = get_attributes_raw(&server, &queue_url, &["All"]).await;
+    assert!(response["Attributes"].get("RedriveAllowPolicy").is_none(), "{}", response);
+}
+
+/// The first message on `queue`, as peeked.
+async fn peek_first(server: &TestServer, queue: &str) -> Value {
+    let (status, body) = server.admin("GET", &format!("/queues/{}/messages", queue), "").await;
+    assert_eq!(status, 200);
+    let mut peeked: Value = serde_json::from_str(&body).unwrap();
+    peeked[0].take()
+}
+
+storage_matrix!(moved_messages_record_how_they_were_moved);
+async fn moved_messages_record_how_they_were_moved() {
+    let server = TestServer::start().await;
+    let source_url = server.create_queue("source").await;
+    server.create_queue("dlq").await;
+    set_redrive_policy(&server, &source_url, &policy("dlq", "3"))
+        .await
+        .unwrap();
+    server.send(&source_url, "message 0").await;
+    for _ in 0..3 {
+        assert_eq!(server.receive(&source_url, Some(0)).await.len(), 1);
+    }
+    assert!(server.receive(&source_url, Some(0)).await.is_empty());
+
+    let message = peek_first(&server, "dlq").await;
+    let dead_letter = &message["dead_letter"];
+    assert_eq!(dead_letter["source_arn"], "arn:aws:sqs:us-east-1:000000000000:source");
+    assert_eq!(dead_letter["receive_count"], 3);
+    assert_eq!(dead_letter["reason"], "redrive_policy");
+    assert!(dead_letter["moved_at"].is_string());
+    assert_eq!(
+        message["attributes"]["DeadLetterQueueSourceArn"],
+        "arn:aws:sqs:us-east-1:000000000000:source"
+    );
+
+    // The metadata is kept through an export and import.
+    let (_, exported) = server.admin("GET", "/export", "").await;
+    let (status, _) = server.admin("POST", "/import", &exported).await;
+    assert_eq!(status, 200);
+    assert_eq!(peek_first(&server, "dlq").await["dead_letter"], *dead_letter);
+
+    let (status, _) = redrive(&server, "dlq", "").await;
+    assert_eq!(status, 200);
+    let message = peek_first(&server, "source").await;
+    let dead_letter = &message["dead_letter"];
+    assert_eq!(dead_letter["source_arn"], "arn:aws:sqs:us-east-1:000000000000:dlq");
+    // Dead-lettering keeps the receive count; redriving resets it.
+    assert_eq!(dead_letter["receive_count"], 3);
+    assert_eq!(dead_letter["reason"], "admin_redrive");
+    assert_eq!(message["receive_count"], 0);
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use aws_sdk_sqs::types::QueueAttributeName;
+use common::{TestServer, storage_matrix};
+use local_sqs::Config;
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn config_path() -> PathBuf {
+    std::env::temp_dir().join(format!("local-sqs-{}.yaml", uuid::Uuid::new_v4()))
+}
+
+async fn visibility_timeout(server: &TestServer, name: &str) -> String {
+    let url = server
+        .client
+        .get_queue_url()
+        .queue_name(name)
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    server
+        .client
+        .get_queue_attributes()
+        .queue_url(url)
+        .attribute_names(QueueAttributeName::VisibilityTimeout)
+        .send()
+        .await
+        .unwrap()
+        .attributes()
+        .unwrap()[&QueueAttributeName::VisibilityTimeout]
+        .clone()
+}
+
+/// Polls until `names` are exactly the server's queues, giving the reloader
+/// a few poll intervals to notice the change.
+async fn wait_for_queues(server: &TestServer, names: &[&str]) {
+    for _ in 0..50 {
+        if server.queue_names().await == names {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(server.queue_names().await, names);
+}
+
+storage_matrix!(declared_queues_are_created_at_startup);
+async fn declared_queues_are_created_at_startup() {
+    let path = config_path();
+    std::fs::write(
+        &path,
+        "queues:\n  - name: jobs\n    attributes:\n      VisibilityTimeout: \"5\"\n",
+    )
+    .unwrap();
+
+    let server = TestServer::start_with(Config {
+        config_file: Some(path.clone()),
+        ..Config::default()
+    })
+    .await;
+
+    assert_eq!(server.queue_names().await, ["jobs"]);
+    assert_eq!(visibility_timeout(&server, "jobs").await, "5");
+    std::fs::remove_file(path).ok();
+}
+
+storage_matrix!(edits_are_applied_without_restarting);
+async fn edits_are_applied_without_restarting() {
+    let path = config_path();
+    std::fs::write(&path, "queues:\n  - name: a\n").unwrap();
+    let server = TestServer::start_with(Config {
+        config_file: Some(path.clone()),
+        ..Config::default()
+    })
+    .await;
+
+    // A new queue and a changed attribute are picked up; the port change is
+    // only warned about.
+    std::fs::write(
+        &path,
+        "port: 1\nqueues:\n  - name: a\n    attributes:\n      VisibilityTimeout: \"7\"\n  - name: b\n",
+    )
+    .unwrap();
+    wait_for_queues(&server, &["a", "b"]).await;
+    assert_eq!(visibility_timeout(&server, "a").await, "7");
+
+    // Dropping a queue from the file keeps it around...
+    std::fs::write(&path, "queues:\n  - name: b\n").unwrap();
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    assert_eq!(server.queue_names().await, ["a", "b"]);
+
+    // ...unless pruning is on.
+    std::fs::write(&path, "prune: true\nqueues:\n  - name: a\n").unwrap();
+    wait_for_queues(&server, &["a"]).await;
+    std::fs::remove_file(path).ok();
+}
+
+storage_matrix!(invalid_attributes_are_rejected_on_reload);
+async fn invalid_attributes_are_rejected_on_reload() {
+    let path = config_path();
+    std::fs::write(&path, "queues:\n  - name: a\n").unwrap();
+    let server = TestServer::start_with(Config {
+        config_file: Some(path.clone()),
+        ..Config::default()
+    })
+    .await;
+
+    std::fs::write(
+        &path,
+        "queues:\n  - name: a\n    attributes:\n      VisibilityTimeout: \"99999\"\n",
+    )
+    .unwrap();
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    assert_eq!(visibility_timeout(&server, "a").await, "30");
+    std::fs::remove_file(path).ok();
+}
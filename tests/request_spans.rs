@@ -0,0 +1,111 @@
+mod common;
+
+use common::{TestServer, storage_matrix};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+type Fields = BTreeMap<String, String>;
+
+/// Collects the fields of every `sqs.request` span, as they stand when the
+/// span closes.
+#[derive(Clone, Default)]
+struct Capture {
+    open: Arc<Mutex<HashMap<Id, Fields>>>,
+    closed: Arc<Mutex<Vec<Fields>>>,
+}
+
+impl Capture {
+    /// The closed span of the request for `action`, the latest if several.
+    fn span(&self, action: &str) -> Fields {
+        let closed = self.closed.lock().unwrap();
+        let span = closed.iter().rev().find(|fields| fields["action"] == action);
+        span.unwrap_or_else(|| panic!("no span for {}", action)).clone()
+    }
+}
+
+struct Visitor<'a>(&'a mut Fields);
+
+impl Visit for Visitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Capture {
+    fn on_new_span(&self, attributes: &Attributes<'_>, id: &Id, _: Context<'_, S>) {
+        if attributes.metadata().name() != "sqs.request" {
+            return;
+        }
+        let mut fields = Fields::new();
+        attributes.record(&mut Visitor(&mut fields));
+        self.open.lock().unwrap().insert(id.clone(), fields);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, _: Context<'_, S>) {
+        if let Some(fields) = self.open.lock().unwrap().get_mut(id) {
+            values.record(&mut Visitor(fields));
+        }
+    }
+
+    fn on_close(&self, id: Id, _: Context<'_, S>) {
+        if let Some(fields) = self.open.lock().unwrap().remove(&id) {
+            self.closed.lock().unwrap().push(fields);
+        }
+    }
+}
+
+// The server runs on the test's single thread, so the subscriber set for it
+// sees the server's spans.
+storage_matrix!(
+    request_spans_follow_a_message_from_send_to_delete,
+    flavor = "current_thread"
+);
+async fn request_spans_follow_a_message_from_send_to_delete() {
+    let capture = Capture::default();
+    let subscriber = tracing_subscriber::registry().with(capture.clone());
+    let _guard = tracing::subscriber::set_default(subscriber);
+    let server = TestServer::start().await;
+    let url = server.create_queue("spans").await;
+
+    let sent = server.client.send_message().queue_url(&url).message_body("m").send().await;
+    let message_id = sent.unwrap().message_id.unwrap();
+    let received = server.client.receive_message().queue_url(&url).send().await.unwrap();
+    let receipt_handle = received.messages()[0].receipt_handle().unwrap();
+    let deleted = server.client.delete_message().queue_url(&url).receipt_handle(receipt_handle);
+    deleted.send().await.unwrap();
+
+    for action in ["SendMessage", "ReceiveMessage", "DeleteMessage"] {
+        let span = capture.span(action);
+        assert_eq!(span["message_ids"], message_id, "{}: {:?}", action, span);
+        assert_eq!(span["message_count"], "1", "{}: {:?}", action, span);
+        assert_eq!(span["queue"], url, "{}: {:?}", action, span);
+        assert!(!span.contains_key("error_code"), "{}: {:?}", action, span);
+    }
+    let waited: u64 = capture.span("ReceiveMessage")["waited_ms"].parse().unwrap();
+    assert!(waited < 500, "a short poll waited {}ms", waited);
+
+    // A long poll records the time it waited.
+    let polled = server.client.receive_message().queue_url(&url).wait_time_seconds(1);
+    assert!(polled.send().await.unwrap().messages().is_empty());
+    let span = capture.span("ReceiveMessage");
+    let waited: u64 = span["waited_ms"].parse().unwrap();
+    assert!(waited >= 900, "{:?}", span);
+    assert_eq!(span.get("message_ids"), None);
+
+    // A failed request records its error code.
+    let missing = url.replace("spans", "missing");
+    let result = server.client.receive_message().queue_url(&missing).send().await;
+    assert!(result.is_err());
+    let span = capture.span("ReceiveMessage");
+    assert_eq!(span["error_code"], "QueueDoesNotExist", "{:?}", span);
+}
@@ -0,0 +1,161 @@
+mod common;
+
+use aws_sdk_sqs::types::MessageAttributeValue;
+use common::{TestServer, storage_matrix};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+
+async fn send_for_customer(
+    server: &TestServer,
+    queue_url: &str,
+    body: &str,
+    customer: &str,
+) -> String {
+    let customer = MessageAttributeValue::builder()
+        .data_type("String")
+        .string_value(customer)
+        .build()
+        .unwrap();
+    server
+        .client
+        .send_message()
+        .queue_url(queue_url)
+        .message_body(body)
+        .message_attributes("customer", customer)
+        .send()
+        .await
+        .unwrap()
+        .message_id
+        .unwrap()
+}
+
+async fn search(server: &TestServer, query: &str) -> (u16, Value) {
+    let path = format!("/queues/orders/search?{}", query);
+    let (status, body) = server.admin("GET", &path, "").await;
+    match serde_json::from_str(&body) {
+        Ok(value) => (status, value),
+        Err(_) => (status, Value::String(body)),
+    }
+}
+
+fn bodies(found: &Value) -> Vec<&str> {
+    let messages = found["messages"].as_array().unwrap();
+    messages.iter().map(|m| m["body"].as_str().unwrap()).collect()
+}
+
+storage_matrix!(filters_combine);
+async fn filters_combine() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("orders").await;
+    send_for_customer(&server, &queue_url, r#"{"order": 1, "sku": "A-100"}"#, "alice").await;
+    let bob = r#"{"order": 2, "sku": "B-200"}"#;
+    let bob = send_for_customer(&server, &queue_url, bob, "bob").await;
+    send_for_customer(&server, &queue_url, r#"{"order": 3, "sku": "A-300"}"#, "bob").await;
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body(r#"{"order": 4, "sku": "A-400"}"#)
+        .delay_seconds(600)
+        .send()
+        .await
+        .unwrap();
+    // Takes order 1 in flight.
+    server.client.receive_message().queue_url(&queue_url).send().await.unwrap();
+
+    let (status, found) = search(&server, "body_contains=A-").await;
+    assert_eq!(status, 200);
+    assert_eq!(bodies(&found).len(), 3);
+    assert!(found.get("next_token").is_none());
+
+    // "sku": "A-[0-9]{3}
+    let pattern = "%22sku%22%3A%20%22A-%5B0-9%5D%7B3%7D";
+    let (_, found) = search(&server, &format!("body_matches={}&state=visible", pattern)).await;
+    assert_eq!(bodies(&found), [r#"{"order": 3, "sku": "A-300"}"#]);
+
+    let (_, found) = search(&server, "attribute_name=customer&attribute_value=bob").await;
+    assert_eq!(bodies(&found).len(), 2);
+    let (_, found) = search(&server, "attribute_name=customer&body_contains=A-").await;
+    assert_eq!(bodies(&found).len(), 2);
+
+    let (_, found) = search(&server, &format!("message_id={}", bob)).await;
+    assert_eq!(found["messages"][0]["id"], bob.as_str());
+
+    let (_, found) = search(&server, "state=in_flight&min_receive_count=1").await;
+    assert_eq!(bodies(&found), [r#"{"order": 1, "sku": "A-100"}"#]);
+    assert!(found["messages"][0]["receipt_handle"].is_string());
+    let (_, found) = search(&server, "state=delayed").await;
+    assert_eq!(bodies(&found), [r#"{"order": 4, "sku": "A-400"}"#]);
+    let (_, found) = search(&server, "min_receive_count=2").await;
+    assert!(bodies(&found).is_empty());
+
+    let sent: Vec<i64> = {
+        let (_, body) = server.admin("GET", "/queues/orders/messages", "").await;
+        let peeked: Vec<Value> = serde_json::from_str(&body).unwrap();
+        peeked
+            .iter()
+            .map(|m| m["attributes"]["SentTimestamp"].as_str().unwrap().parse().unwrap())
+            .collect()
+    };
+    let (_, found) = search(&server, &format!("sent_after={}", sent[3] + 1)).await;
+    assert!(bodies(&found).is_empty());
+    let (_, found) = search(&server, &format!("sent_before={}", sent[0] + 1)).await;
+    assert!(bodies(&found).contains(&r#"{"order": 1, "sku": "A-100"}"#));
+}
+
+storage_matrix!(results_are_paginated);
+async fn results_are_paginated() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("orders").await;
+    for i in 0..7 {
+        send_for_customer(&server, &queue_url, &format!("match {}", i), "alice").await;
+        send_for_customer(&server, &queue_url, &format!("skip {}", i), "alice").await;
+    }
+
+    let mut seen = Vec::new();
+    let mut query = "body_contains=match&limit=3".to_string();
+    loop {
+        let (status, found) = search(&server, &query).await;
+        assert_eq!(status, 200, "{}", found);
+        seen.extend(bodies(&found).into_iter().map(str::to_string));
+        match found["next_token"].as_str() {
+            Some(token) => query = format!("body_contains=match&limit=3&next_token={}", token),
+            None => break,
+        }
+    }
+    let expected: Vec<String> = (0..7).map(|i| format!("match {}", i)).collect();
+    assert_eq!(seen, expected);
+}
+
+storage_matrix!(bad_searches_are_rejected);
+async fn bad_searches_are_rejected() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("orders").await;
+    send_for_customer(&server, &queue_url, &"a".repeat(100_000), "alice").await;
+
+    for query in [
+        "body_matches=(unclosed",
+        "state=lost",
+        "attribute_value=bob",
+        "limit=0",
+        "limit=1001",
+        "next_token=nonsense",
+        // Compiles to far more than the size cap.
+        "body_matches=(%5Cw%7B100%7D)%7B100%7D",
+    ] {
+        let (status, body) = search(&server, query).await;
+        assert_eq!(status, 400, "{}: {}", query, body);
+    }
+    let (status, _) = search(&server, &format!("body_matches={}", "a".repeat(1001))).await;
+    assert_eq!(status, 400);
+
+    // Matching is linear in the body, however the pattern backtracks.
+    let started = Instant::now();
+    let (status, found) = search(&server, "body_matches=%5E(a%2B)%2B%24b").await;
+    assert_eq!(status, 200);
+    assert!(bodies(&found).is_empty());
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    let (status, _) = server.admin("GET", "/queues/missing/search", "").await;
+    assert_eq!(status, 400);
+}
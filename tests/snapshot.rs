@@ -0,0 +1,210 @@
+mod common;
+
+use aws_sdk_sqs::types::{MessageSystemAttributeName, QueueAttributeName};
+use common::{TestServer, storage_matrix};
+use serde_json::Value;
+use std::time::Duration;
+
+/// The minute every timestamp in the `tests/data` snapshots falls in.
+const FIXTURE_MINUTE: &str = "2023-11-14T22:13";
+
+/// Imports `snapshot`, then returns the server's export of it. Timestamps
+/// are moved to the previous minute first so that the messages aren't
+/// already past retention.
+async fn round_trip(server: &TestServer, snapshot: &str) -> Value {
+    let minute = (chrono::Utc::now() - chrono::Duration::minutes(1)).format("%Y-%m-%dT%H:%M");
+    let snapshot = snapshot.replace(FIXTURE_MINUTE, &minute.to_string());
+    let (status, body) = server.admin("POST", "/import", &snapshot).await;
+    assert_eq!(status, 200, "{}", body);
+    let (status, body) = server.admin("GET", "/export", "").await;
+    assert_eq!(status, 200);
+    serde_json::from_str(&body).unwrap()
+}
+
+storage_matrix!(loads_unversioned_snapshots_with_defaults);
+async fn loads_unversioned_snapshots_with_defaults() {
+    let server = TestServer::start().await;
+    let exported = round_trip(&server, include_str!("data/snapshot_v1.json")).await;
+
+    assert_eq!(exported["version"], 5);
+    let orders = &exported["queues"][0];
+    assert_eq!(orders["name"], "orders");
+    assert_eq!(orders["tags"], serde_json::json!({}));
+    assert_eq!(orders["paused"], false);
+    assert_eq!(orders["messages"].as_array().unwrap().len(), 2);
+    assert_eq!(orders["messages"][1]["first_received"], Value::Null);
+
+    let queue_url = server.client.get_queue_url().queue_name("orders").send().await.unwrap();
+    let received = server
+        .client
+        .receive_message()
+        .queue_url(queue_url.queue_url().unwrap())
+        .max_number_of_messages(10)
+        .send()
+        .await
+        .unwrap();
+    let bodies: Vec<&str> = received.messages().iter().filter_map(|m| m.body()).collect();
+    assert_eq!(bodies, ["first order", "second order"]);
+}
+
+storage_matrix!(loads_version_2_snapshots);
+async fn loads_version_2_snapshots() {
+    let server = TestServer::start().await;
+    let exported = round_trip(&server, include_str!("data/snapshot_v2.json")).await;
+
+    assert_eq!(exported["queues"][0]["tags"]["team"], "billing");
+    assert_eq!(exported["queues"][1]["paused"], true);
+    let first_received = exported["queues"][0]["messages"][1]["first_received"].as_str();
+    assert!(first_received.is_some_and(|t| t.ends_with(":25Z")));
+    assert_eq!(exported["queues"][0]["messages"][1]["dead_letter"], Value::Null);
+}
+
+storage_matrix!(refuses_snapshots_newer_than_the_server);
+async fn refuses_snapshots_newer_than_the_server() {
+    let server = TestServer::start().await;
+    let (status, body) = server
+        .admin("POST", "/import", r#"{"version": 99, "queues": []}"#)
+        .await;
+    assert_eq!(status, 400);
+    assert!(body.contains("format version 99 is newer"), "{}", body);
+
+    let (status, _) = server.admin("GET", "/queues", "").await;
+    assert_eq!(status, 200);
+}
+
+storage_matrix!(in_flight_messages_stay_in_flight_across_a_reload);
+async fn in_flight_messages_stay_in_flight_across_a_reload() {
+    let before = TestServer::start().await;
+    let queue_url = before.create_queue("work").await;
+    for body in ["kept", "deleted"] {
+        let send = before.client.send_message().queue_url(&queue_url).message_body(body);
+        send.send().await.unwrap();
+    }
+    let received = before
+        .client
+        .receive_message()
+        .queue_url(&queue_url)
+        .max_number_of_messages(10)
+        .visibility_timeout(2)
+        .send()
+        .await
+        .unwrap();
+    let received_at = std::time::Instant::now();
+    assert_eq!(received.messages().len(), 2);
+    let (status, snapshot) = before.admin("GET", "/export", "").await;
+    assert_eq!(status, 200);
+    before.stop().await;
+
+    let after = TestServer::start().await;
+    let (status, body) = after.admin("POST", "/import", &snapshot).await;
+    assert_eq!(status, 200, "{}", body);
+    let queue_url = after.client.get_queue_url().queue_name("work").send().await.unwrap();
+    let queue_url = queue_url.queue_url().unwrap();
+
+    // Still invisible for what was left of the timeout, not visible at once
+    // and not given a fresh one.
+    let receive = after.client.receive_message().queue_url(queue_url).max_number_of_messages(10);
+    assert!(receive.clone().send().await.unwrap().messages().is_empty());
+
+    // A handle from before the reload still deletes its message.
+    let deleted = received.messages().iter().find(|m| m.body() == Some("deleted")).unwrap();
+    let delete = after.client.delete_message().queue_url(queue_url);
+    delete.receipt_handle(deleted.receipt_handle().unwrap()).send().await.unwrap();
+
+    let receive_count = MessageSystemAttributeName::ApproximateReceiveCount;
+    let receive = receive.message_system_attribute_names(receive_count.clone());
+    let redelivered = receive.wait_time_seconds(5).send().await.unwrap();
+    let waited = received_at.elapsed();
+    assert!(waited >= Duration::from_millis(1900), "visible again after {:?}", waited);
+    assert!(waited < Duration::from_secs(4), "visible again after {:?}", waited);
+    let bodies: Vec<_> = redelivered.messages().iter().filter_map(|m| m.body()).collect();
+    assert_eq!(bodies, ["kept"]);
+    let attributes = redelivered.messages()[0].attributes().unwrap();
+    assert_eq!(attributes[&receive_count], "2");
+}
+
+storage_matrix!(exports_survive_an_import_into_a_fresh_server_unchanged);
+async fn exports_survive_an_import_into_a_fresh_server_unchanged() {
+    let before = TestServer::start().await;
+    let queue_url = before.create_queue("work").await;
+    let tag = before.client.tag_queue().queue_url(&queue_url).tags("team", "billing");
+    tag.send().await.unwrap();
+    for body in ["waiting", "in flight"] {
+        let send = before.client.send_message().queue_url(&queue_url).message_body(body);
+        send.send().await.unwrap();
+    }
+    let receive = before.client.receive_message().queue_url(&queue_url).visibility_timeout(600);
+    receive.send().await.unwrap();
+    let fifo_url = before
+        .client
+        .create_queue()
+        .queue_name("work.fifo")
+        .attributes(QueueAttributeName::FifoQueue, "true")
+        .tags("team", "payments")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    let send = |client: &aws_sdk_sqs::Client, queue_url: &str| {
+        let send = client.send_message().queue_url(queue_url).message_body("once");
+        send.message_group_id("g").message_deduplication_id("d").send()
+    };
+    let sent = send(&before.client, &fifo_url).await.unwrap();
+
+    let (status, exported) = before.admin("GET", "/export", "").await;
+    assert_eq!(status, 200);
+    let exported: Value = serde_json::from_str(&exported).unwrap();
+    assert_eq!(exported["queues"][0]["tags"]["team"], "billing");
+    let messages = exported["queues"][0]["messages"].as_array().unwrap();
+    assert_eq!(messages.iter().filter(|m| m["receipt_handle"].is_string()).count(), 1);
+    assert_eq!(exported["queues"][1]["deduplication"][0]["deduplication_id"], "d");
+    before.stop().await;
+
+    let after = TestServer::start().await;
+    let (status, body) = after.admin("POST", "/import", &exported.to_string()).await;
+    assert_eq!(status, 200, "{}", body);
+    let (status, reexported) = after.admin("GET", "/export", "").await;
+    assert_eq!(status, 200);
+    let reexported: Value = serde_json::from_str(&reexported).unwrap();
+    assert_eq!(reexported, exported);
+
+    // The deduplication ID still suppresses a repeated send.
+    let fifo_url = after.client.get_queue_url().queue_name("work.fifo").send().await.unwrap();
+    let resent = send(&after.client, fifo_url.queue_url().unwrap()).await.unwrap();
+    assert_eq!(resent.message_id(), sent.message_id());
+    assert_eq!(resent.sequence_number(), sent.sequence_number());
+    let (_, reexported) = after.admin("GET", "/export", "").await;
+    let reexported: Value = serde_json::from_str(&reexported).unwrap();
+    assert_eq!(reexported["queues"][1]["messages"].as_array().unwrap().len(), 1);
+}
+
+storage_matrix!(numbering_carries_on_after_deduplicated_messages_that_were_deleted);
+async fn numbering_carries_on_after_deduplicated_messages_that_were_deleted() {
+    let before = TestServer::start().await;
+    let create = before.client.create_queue().queue_name("work.fifo");
+    let create = create.attributes(QueueAttributeName::FifoQueue, "true").send().await.unwrap();
+    let queue_url = create.queue_url.unwrap();
+    let send = |client: &aws_sdk_sqs::Client, queue_url: &str, deduplication_id: &str| {
+        let send = client.send_message().queue_url(queue_url).message_body("work");
+        send.message_group_id("g").message_deduplication_id(deduplication_id).send()
+    };
+    let sent = send(&before.client, &queue_url, "first").await.unwrap();
+    let received = before.receive(&queue_url, None).await;
+    let delete = before.client.delete_message().queue_url(&queue_url);
+    delete.receipt_handle(received[0].receipt_handle().unwrap()).send().await.unwrap();
+    let (status, snapshot) = before.admin("GET", "/export", "").await;
+    assert_eq!(status, 200);
+    before.stop().await;
+
+    let after = TestServer::start().await;
+    let (status, body) = after.admin("POST", "/import", &snapshot).await;
+    assert_eq!(status, 200, "{}", body);
+    let queue_url = after.client.get_queue_url().queue_name("work.fifo").send().await.unwrap();
+    let queue_url = queue_url.queue_url().unwrap();
+    let resent = send(&after.client, queue_url, "first").await.unwrap();
+    let next = send(&after.client, queue_url, "second").await.unwrap();
+    let number = |sequence_number: Option<&str>| sequence_number.unwrap().parse::<u128>().unwrap();
+    assert_eq!(resent.sequence_number(), sent.sequence_number());
+    assert!(number(next.sequence_number()) > number(sent.sequence_number()));
+}
@@ -0,0 +1,64 @@
+use aws_sdk_sqs::config::{Credentials, Region};
+use aws_sdk_sqs::Client;
+use local_sqs::socket_activation::listen_fds;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+#[test]
+fn listen_fds_are_only_taken_when_passed_to_this_process() {
+    assert_eq!(listen_fds(None, None, 42).unwrap(), None);
+    assert_eq!(listen_fds(Some("42"), None, 42).unwrap(), None);
+    assert_eq!(listen_fds(None, Some("1"), 42).unwrap(), None);
+    assert_eq!(listen_fds(Some("42"), Some("1"), 42).unwrap(), Some(3..4));
+    assert_eq!(listen_fds(Some("42"), Some("3"), 42).unwrap(), Some(3..6));
+    assert_eq!(listen_fds(Some(" 42\n"), Some("2 "), 42).unwrap(), Some(3..5));
+    assert_eq!(listen_fds(Some("42"), Some("0"), 42).unwrap(), None);
+    // Left behind by a parent, for it and not for us.
+    assert_eq!(listen_fds(Some("41"), Some("1"), 42).unwrap(), None);
+    assert_eq!(listen_fds(Some("41"), Some("many"), 42).unwrap(), None);
+
+    for (pid, fds) in [("me", "1"), ("", "1"), ("-42", "1"), ("42", "many"), ("42", "-1")] {
+        let error = listen_fds(Some(pid), Some(fds), 42).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput, "{} {}", pid, fds);
+    }
+    assert!(listen_fds(Some("42"), Some(&i32::MAX.to_string()), 42).is_err());
+}
+
+/// Runs the server under `systemd-socket-activate`, which owns the listening
+/// socket and passes it on as file descriptor 3. Run with `--ignored` where
+/// the tool is installed.
+#[tokio::test]
+#[ignore = "needs systemd-socket-activate"]
+async fn socket_activated_servers_serve_the_inherited_socket() {
+    // A port that was free a moment ago, for the tool to listen on.
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let address = format!("127.0.0.1:{}", port);
+    let mut server = Command::new("systemd-socket-activate")
+        .args(["--listen", &address, env!("CARGO_BIN_EXE_local-sqs-rs"), "--port", "1"])
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("systemd-socket-activate");
+
+    let config = aws_sdk_sqs::Config::builder()
+        .endpoint_url(format!("http://{}", address))
+        .region(Region::new("us-east-1"))
+        .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+        .build();
+    let client = Client::from_conf(config);
+    let mut created = None;
+    for _ in 0..100 {
+        match client.create_queue().queue_name("activated").send().await {
+            Ok(output) => {
+                created = output.queue_url;
+                break;
+            }
+            Err(_) => tokio::time::sleep(Duration::from_millis(50)).await,
+        }
+    }
+    server.kill().ok();
+    server.wait().ok();
+
+    // The URL names the inherited socket, not the --port given.
+    let expected = format!("http://127.0.0.1:{}/000000000000/activated", port);
+    assert_eq!(created.as_deref(), Some(expected.as_str()));
+}
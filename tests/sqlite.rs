@@ -0,0 +1,314 @@
+mod common;
+
+use aws_sdk_sqs::types::{MessageSystemAttributeName, QueueAttributeName};
+use common::TestServer;
+use local_sqs::store::Storage;
+use local_sqs::Config;
+use std::path::Path;
+use std::time::Duration;
+
+async fn start(db_path: &Path) -> TestServer {
+    TestServer::start_with(Config {
+        storage: Storage::Sqlite,
+        db_path: db_path.to_path_buf(),
+        ..Default::default()
+    })
+    .await
+}
+
+/// The messages on the queue at `url`, visible or not.
+async fn message_count(server: &TestServer, url: &str) -> usize {
+    let attributes = server
+        .client
+        .get_queue_attributes()
+        .queue_url(url)
+        .attribute_names(QueueAttributeName::All)
+        .send()
+        .await
+        .unwrap()
+        .attributes
+        .unwrap();
+    let count = |name| attributes[&name].parse::<usize>().unwrap();
+    count(QueueAttributeName::ApproximateNumberOfMessages)
+        + count(QueueAttributeName::ApproximateNumberOfMessagesNotVisible)
+}
+
+#[tokio::test]
+async fn queues_and_messages_survive_a_restart() {
+    let db_path = common::scratch_db_path();
+    let server = start(&db_path).await;
+    let queue_url = server
+        .client
+        .create_queue()
+        .queue_name("durable")
+        .attributes(QueueAttributeName::VisibilityTimeout, "300")
+        .tags("team", "payments")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    for body in ["first", "second", "third"] {
+        server
+            .client
+            .send_message()
+            .queue_url(&queue_url)
+            .message_body(body)
+            .send()
+            .await
+            .unwrap();
+    }
+    let in_flight = server
+        .client
+        .receive_message()
+        .queue_url(&queue_url)
+        .send()
+        .await
+        .unwrap()
+        .messages
+        .unwrap()
+        .remove(0);
+    assert_eq!(in_flight.body(), Some("first"));
+
+    let fifo_url = server
+        .client
+        .create_queue()
+        .queue_name("durable.fifo")
+        .attributes(QueueAttributeName::FifoQueue, "true")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    let send_fifo = |client: &aws_sdk_sqs::Client, url: &str| {
+        client
+            .send_message()
+            .queue_url(url)
+            .message_group_id("g")
+            .message_deduplication_id("once")
+            .message_body("fifo")
+            .send()
+    };
+    let original = send_fifo(&server.client, &fifo_url).await.unwrap();
+    server.stop().await;
+
+    // The database can be inspected directly.
+    let db = rusqlite::Connection::open(&db_path).unwrap();
+    let stored: i64 = db
+        .query_row("SELECT count(*) FROM messages", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(stored, 4);
+    drop(db);
+
+    let server = start(&db_path).await;
+    let queue_url = server.client.get_queue_url().queue_name("durable").send().await.unwrap();
+    let queue_url = queue_url.queue_url.unwrap();
+    let attributes = server
+        .client
+        .get_queue_attributes()
+        .queue_url(&queue_url)
+        .attribute_names(QueueAttributeName::All)
+        .send()
+        .await
+        .unwrap()
+        .attributes
+        .unwrap();
+    assert_eq!(attributes[&QueueAttributeName::VisibilityTimeout], "300");
+    assert_eq!(attributes[&QueueAttributeName::ApproximateNumberOfMessages], "2");
+    assert_eq!(attributes[&QueueAttributeName::ApproximateNumberOfMessagesNotVisible], "1");
+    let tags = server.client.list_queue_tags().queue_url(&queue_url).send().await.unwrap();
+    assert_eq!(tags.tags.unwrap()["team"], "payments");
+
+    // The in-flight message is still in flight, and its receipt handle
+    // still deletes it.
+    let received = server
+        .client
+        .receive_message()
+        .queue_url(&queue_url)
+        .max_number_of_messages(10)
+        .send()
+        .await
+        .unwrap()
+        .messages
+        .unwrap();
+    let bodies: Vec<_> = received.iter().map(|m| m.body().unwrap()).collect();
+    assert_eq!(bodies, ["second", "third"]);
+    server
+        .client
+        .delete_message()
+        .queue_url(&queue_url)
+        .receipt_handle(in_flight.receipt_handle().unwrap())
+        .send()
+        .await
+        .unwrap();
+
+    // Deduplication IDs are remembered too.
+    let fifo_url = server.client.get_queue_url().queue_name("durable.fifo").send().await.unwrap();
+    let retry = send_fifo(&server.client, fifo_url.queue_url().unwrap()).await.unwrap();
+    assert_eq!(retry.message_id(), original.message_id());
+    assert_eq!(retry.sequence_number(), original.sequence_number());
+
+    server.stop().await;
+    common::remove_db(&db_path);
+}
+
+#[tokio::test]
+async fn deleted_queues_stay_deleted() {
+    let db_path = common::scratch_db_path();
+    let server = start(&db_path).await;
+    server.create_queue("kept").await;
+    let deleted = server.create_queue("deleted").await;
+    server.client.delete_queue().queue_url(&deleted).send().await.unwrap();
+    server.stop().await;
+
+    let server = start(&db_path).await;
+    let queues = server.client.list_queues().send().await.unwrap();
+    let urls = queues.queue_urls();
+    assert_eq!(urls.len(), 1);
+    assert!(urls[0].ends_with("/kept"));
+
+    server.stop().await;
+    common::remove_db(&db_path);
+}
+
+#[tokio::test]
+async fn dead_letter_metadata_survives_a_restart() {
+    let db_path = common::scratch_db_path();
+    let server = start(&db_path).await;
+    server.create_queue("dlq").await;
+    let source_url = server
+        .client
+        .create_queue()
+        .queue_name("source")
+        .attributes(
+            QueueAttributeName::RedrivePolicy,
+            r#"{"deadLetterTargetArn":"arn:aws:sqs:us-east-1:000000000000:dlq","maxReceiveCount":"1"}"#,
+        )
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    server
+        .client
+        .send_message()
+        .queue_url(&source_url)
+        .message_body("poison")
+        .send()
+        .await
+        .unwrap();
+    for _ in 0..2 {
+        let receive = server.client.receive_message().queue_url(&source_url);
+        receive.visibility_timeout(0).send().await.unwrap();
+    }
+    server.stop().await;
+
+    let server = start(&db_path).await;
+    let (_, peeked) = server.admin("GET", "/queues/dlq/messages", "").await;
+    let peeked: serde_json::Value = serde_json::from_str(&peeked).unwrap();
+    assert_eq!(peeked[0]["dead_letter"]["reason"], "redrive_policy");
+    assert_eq!(peeked[0]["dead_letter"]["receive_count"], 1);
+
+    server.stop().await;
+    common::remove_db(&db_path);
+}
+
+#[tokio::test]
+async fn operations_that_cannot_be_stored_do_not_happen() {
+    let db_path = common::scratch_db_path();
+    let server = start(&db_path).await;
+    let queue_url = server.create_queue("failing").await;
+    let send = |body: &str| server.client.send_message().queue_url(&queue_url).message_body(body);
+    send("kept").send().await.unwrap();
+
+    // Every write to the messages table now fails.
+    let db = rusqlite::Connection::open(&db_path).unwrap();
+    db.execute_batch(
+        "CREATE TRIGGER no_inserts BEFORE INSERT ON messages BEGIN SELECT RAISE(ABORT, 'no'); END;
+         CREATE TRIGGER no_updates BEFORE UPDATE ON messages BEGIN SELECT RAISE(ABORT, 'no'); END;",
+    )
+    .unwrap();
+    let error = send("lost").send().await.unwrap_err().into_service_error();
+    assert_eq!(error.meta().code(), Some("InternalError"));
+    let receive = server.client.receive_message().queue_url(&queue_url);
+    assert!(receive.send().await.is_err());
+
+    db.execute_batch("DROP TRIGGER no_inserts; DROP TRIGGER no_updates;").unwrap();
+    let attributes = server
+        .client
+        .get_queue_attributes()
+        .queue_url(&queue_url)
+        .attribute_names(QueueAttributeName::All)
+        .send()
+        .await
+        .unwrap()
+        .attributes
+        .unwrap();
+    assert_eq!(attributes[&QueueAttributeName::ApproximateNumberOfMessages], "1");
+    assert_eq!(attributes[&QueueAttributeName::ApproximateNumberOfMessagesNotVisible], "0");
+    // The failed receive didn't count as one.
+    let received = server
+        .client
+        .receive_message()
+        .queue_url(&queue_url)
+        .message_system_attribute_names(MessageSystemAttributeName::ApproximateReceiveCount)
+        .send()
+        .await
+        .unwrap()
+        .messages
+        .unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].body(), Some("kept"));
+    let receive_count = MessageSystemAttributeName::ApproximateReceiveCount;
+    assert_eq!(received[0].attributes().unwrap()[&receive_count], "1");
+
+    server.stop().await;
+    common::remove_db(&db_path);
+}
+
+#[tokio::test]
+async fn messages_the_dead_letter_queue_cannot_store_stay_on_their_queue() {
+    let db_path = common::scratch_db_path();
+    // Only receives release the lapsed message, not the maintenance task.
+    let server = TestServer::start_with(Config {
+        storage: Storage::Sqlite,
+        db_path: db_path.clone(),
+        sweep_interval: Duration::from_secs(3600),
+        ..Default::default()
+    })
+    .await;
+    let dlq_url = server.create_queue("dlq").await;
+    let policy = r#"{"deadLetterTargetArn":"arn:aws:sqs:us-east-1:000000000000:dlq","maxReceiveCount":1}"#;
+    let queue_url = server
+        .client
+        .create_queue()
+        .queue_name("work")
+        .attributes(QueueAttributeName::RedrivePolicy, policy)
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    server.client.send_message().queue_url(&queue_url).message_body("kept").send().await.unwrap();
+    let receive = || server.client.receive_message().queue_url(&queue_url).visibility_timeout(0);
+    assert_eq!(receive().send().await.unwrap().messages().len(), 1);
+
+    // Writes to the dead-letter queue's messages now fail.
+    let db = rusqlite::Connection::open(&db_path).unwrap();
+    db.execute_batch(
+        "CREATE TRIGGER no_dead_letters BEFORE INSERT ON messages
+         WHEN NEW.queue_id = (SELECT id FROM queues WHERE name = 'dlq')
+         BEGIN SELECT RAISE(ABORT, 'no'); END;",
+    )
+    .unwrap();
+    // The lapsed message is taken for the dead-letter queue, which can't
+    // store it, so it goes back, where the receive may pick it up again.
+    receive().send().await.unwrap();
+
+    db.execute_batch("DROP TRIGGER no_dead_letters;").unwrap();
+    assert_eq!(message_count(&server, &queue_url).await, 1);
+    assert_eq!(message_count(&server, &dlq_url).await, 0);
+
+    server.stop().await;
+    common::remove_db(&db_path);
+}
@@ -0,0 +1,170 @@
+use aws_sdk_sqs::Client;
+use aws_sdk_sqs::config::{Credentials, Region};
+use local_sqs::queue::{
+    AttributeChanges, Claimed, ReceiveMessageRequest, SendMessageRequest, SendMessageResponse,
+};
+use local_sqs::state::Queue;
+use local_sqs::store::{MemoryStore, QueueStore, StoreFuture};
+use local_sqs::{AppState, Config};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Keeps queues in a [`MemoryStore`], counting the operations on messages
+/// and attributes that reach it.
+#[derive(Debug, Default)]
+struct CountingStore {
+    inner: MemoryStore,
+    operations: AtomicUsize,
+}
+
+impl QueueStore for CountingStore {
+    fn create_queue<'a>(
+        &'a self,
+        queue: Queue,
+        existing: &'a mut (dyn FnMut(&Queue) + Send),
+    ) -> StoreFuture<'a, bool> {
+        self.inner.create_queue(queue, existing)
+    }
+
+    fn put_queue(&self, queue: Queue) -> StoreFuture<'_, Option<Queue>> {
+        self.inner.put_queue(queue)
+    }
+
+    fn get_queue(&self, url: &str, read: &mut dyn FnMut(&Queue)) -> bool {
+        self.inner.get_queue(url, read)
+    }
+
+    fn update_queue<'a>(
+        &'a self,
+        url: &'a str,
+        update: &'a mut (dyn FnMut(&mut Queue) + Send),
+    ) -> StoreFuture<'a, bool> {
+        self.inner.update_queue(url, update)
+    }
+
+    fn remove_queue_if<'a>(
+        &'a self,
+        url: &'a str,
+        condition: &'a mut (dyn FnMut(&Queue) -> bool + Send),
+    ) -> StoreFuture<'a, Option<Queue>> {
+        self.inner.remove_queue_if(url, condition)
+    }
+
+    fn list(&self, visit: &mut dyn FnMut(&Queue)) {
+        self.inner.list(visit)
+    }
+
+    fn replace_all(&self, queues: Vec<Queue>) -> StoreFuture<'_, Vec<Queue>> {
+        self.inner.replace_all(queues)
+    }
+
+    fn enqueue<'a>(
+        &'a self,
+        state: &'a AppState,
+        url: &'a str,
+        request: SendMessageRequest,
+    ) -> StoreFuture<'a, SendMessageResponse> {
+        self.operations.fetch_add(1, Ordering::Relaxed);
+        self.inner.enqueue(state, url, request)
+    }
+
+    fn claim_visible<'a>(
+        &'a self,
+        state: &'a AppState,
+        url: &'a str,
+        request: &'a ReceiveMessageRequest,
+    ) -> StoreFuture<'a, Claimed> {
+        self.operations.fetch_add(1, Ordering::Relaxed);
+        self.inner.claim_visible(state, url, request)
+    }
+
+    fn delete_by_handle<'a>(
+        &'a self,
+        state: &'a AppState,
+        url: &'a str,
+        receipt_handle: &'a str,
+    ) -> StoreFuture<'a, String> {
+        self.operations.fetch_add(1, Ordering::Relaxed);
+        self.inner.delete_by_handle(state, url, receipt_handle)
+    }
+
+    fn set_attributes<'a>(
+        &'a self,
+        state: &'a AppState,
+        url: &'a str,
+        changes: AttributeChanges,
+    ) -> StoreFuture<'a, ()> {
+        self.operations.fetch_add(1, Ordering::Relaxed);
+        self.inner.set_attributes(state, url, changes)
+    }
+}
+
+#[tokio::test]
+async fn handlers_reach_queues_only_through_the_store() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let config = Config {
+        host: "127.0.0.1".to_string(),
+        port: addr.port(),
+        ..Default::default()
+    };
+    let store = Arc::new(CountingStore::default());
+    let state = AppState::with_store(&config, store.clone());
+    let app = local_sqs::sqs_router(state);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let sdk_config = aws_sdk_sqs::Config::builder()
+        .endpoint_url(format!("http://{}", addr))
+        .region(Region::new("us-east-1"))
+        .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+        .build();
+    let client = Client::from_conf(sdk_config);
+
+    let queue_url = client
+        .create_queue()
+        .queue_name("stored")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    let stored: &dyn QueueStore = &store.inner;
+    assert!(stored.contains(&queue_url));
+
+    client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("hello")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(stored.read(&queue_url, |queue| queue.messages.len()).unwrap(), 1);
+    assert_eq!(store.operations.load(Ordering::Relaxed), 1);
+
+    let received = client
+        .receive_message()
+        .queue_url(&queue_url)
+        .send()
+        .await
+        .unwrap()
+        .messages
+        .unwrap();
+    assert_eq!(received[0].body(), Some("hello"));
+    client
+        .delete_message()
+        .queue_url(&queue_url)
+        .receipt_handle(received[0].receipt_handle().unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(stored.read(&queue_url, |queue| queue.messages.len()).unwrap(), 0);
+    assert_eq!(store.operations.load(Ordering::Relaxed), 3);
+
+    let set = client.set_queue_attributes().queue_url(&queue_url);
+    let set = set.attributes(aws_sdk_sqs::types::QueueAttributeName::DelaySeconds, "5");
+    set.send().await.unwrap();
+    assert_eq!(store.operations.load(Ordering::Relaxed), 4);
+
+    client.delete_queue().queue_url(&queue_url).send().await.unwrap();
+    assert!(!stored.contains(&queue_url));
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use aws_sdk_sqs::error::ProvideErrorMetadata;
+use common::{TestServer, storage_matrix};
+use std::collections::HashMap;
+
+fn numbered_tags(range: std::ops::Range<usize>) -> HashMap<String, String> {
+    range.map(|i| (format!("key-{}", i), "value".to_string())).collect()
+}
+
+storage_matrix!(existing_tags_count_toward_the_limit_of_fifty);
+async fn existing_tags_count_toward_the_limit_of_fifty() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("tagged").await;
+    server
+        .client
+        .tag_queue()
+        .queue_url(&queue_url)
+        .set_tags(Some(numbered_tags(0..50)))
+        .send()
+        .await
+        .unwrap();
+
+    // Re-tagging an existing key is fine; a 51st key is not.
+    server
+        .client
+        .tag_queue()
+        .queue_url(&queue_url)
+        .tags("key-0", "changed")
+        .send()
+        .await
+        .unwrap();
+    let err = server
+        .client
+        .tag_queue()
+        .queue_url(&queue_url)
+        .tags("key-50", "value")
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert_eq!(err.code(), Some("InvalidParameterValue"), "{:?}", err);
+
+    let tags = server
+        .client
+        .list_queue_tags()
+        .queue_url(&queue_url)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(tags.tags().unwrap().len(), 50);
+    assert_eq!(tags.tags().unwrap()["key-0"], "changed");
+}
+
+storage_matrix!(create_queue_rejects_long_keys);
+async fn create_queue_rejects_long_keys() {
+    let server = TestServer::start().await;
+    let key = "k".repeat(129);
+    let err = server
+        .client
+        .create_queue()
+        .queue_name("long-key")
+        .tags(&key, "value")
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert_eq!(err.code(), Some("InvalidParameterValue"), "{:?}", err);
+    assert!(err.message().unwrap().contains(&key), "{:?}", err);
+
+    server
+        .client
+        .create_queue()
+        .queue_name("long-key")
+        .tags("k".repeat(128), "v".repeat(256))
+        .send()
+        .await
+        .unwrap();
+}
+
+storage_matrix!(aws_prefix_is_reserved);
+async fn aws_prefix_is_reserved() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("reserved").await;
+    let err = server
+        .client
+        .tag_queue()
+        .queue_url(&queue_url)
+        .tags("aws:cloudformation:stack-name", "mine")
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert_eq!(err.code(), Some("InvalidParameterValue"), "{:?}", err);
+    assert!(err.message().unwrap().contains("aws:cloudformation:stack-name"));
+}
+
+storage_matrix!(create_queue_tags_are_kept_and_must_match_on_recreate);
+async fn create_queue_tags_are_kept_and_must_match_on_recreate() {
+    let server = TestServer::start().await;
+    let create = server
+        .client
+        .create_queue()
+        .queue_name("tagged-at-birth")
+        .tags("team", "payments")
+        .tags("env", "dev");
+    let queue_url = create.clone().send().await.unwrap().queue_url.unwrap();
+    let tags = server.client.list_queue_tags().queue_url(&queue_url).send().await.unwrap();
+    let expected = HashMap::from([
+        ("team".to_string(), "payments".to_string()),
+        ("env".to_string(), "dev".to_string()),
+    ]);
+    assert_eq!(tags.tags(), Some(&expected));
+
+    // The same tags again are a no-op; different ones are a different queue.
+    let recreated = create.clone().send().await.unwrap();
+    assert_eq!(recreated.queue_url(), Some(queue_url.as_str()));
+    let conflicting = [
+        create.clone().tags("team", "billing"),
+        create.clone().tags("owner", "me"),
+        server.client.create_queue().queue_name("tagged-at-birth"),
+    ];
+    for create in conflicting {
+        let err = create.send().await.unwrap_err().into_service_error();
+        assert!(err.is_queue_name_exists(), "{:?}", err);
+    }
+    let tags = server.client.list_queue_tags().queue_url(&queue_url).send().await.unwrap();
+    assert_eq!(tags.tags(), Some(&expected));
+
+    // Once retagged, recreating takes the new tags.
+    let untag = server.client.untag_queue().queue_url(&queue_url).tag_keys("env");
+    untag.send().await.unwrap();
+    let tag = server.client.tag_queue().queue_url(&queue_url).tags("team", "billing");
+    tag.send().await.unwrap();
+    let create = server.client.create_queue().queue_name("tagged-at-birth");
+    create.tags("team", "billing").send().await.unwrap();
+}
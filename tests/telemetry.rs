@@ -0,0 +1,22 @@
+#![cfg(feature = "otel")]
+
+use local_sqs::telemetry::traceparent_from_xray;
+
+#[test]
+fn converts_xray_header_to_traceparent() {
+    assert_eq!(
+        traceparent_from_xray(
+            "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1"
+        )
+        .as_deref(),
+        Some("00-5759e988bd862e3fe1be46a994272793-53995c3f42cd8ad8-01")
+    );
+}
+
+#[test]
+fn rejects_xray_header_without_parent() {
+    assert_eq!(
+        traceparent_from_xray("Root=1-5759e988-bd862e3fe1be46a994272793;Sampled=1"),
+        None
+    );
+}
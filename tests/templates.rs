@@ -0,0 +1,209 @@
+mod common;
+
+use aws_sdk_sqs::types::QueueAttributeName;
+use common::{TestServer, storage_matrix};
+use local_sqs::config::ConfigFile;
+use local_sqs::state::AppState;
+use local_sqs::{reload, Config};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+const CONFIG: &str = r#"
+templates:
+  standard-worker:
+    attributes:
+      VisibilityTimeout: "60"
+      DelaySeconds: "2"
+    tags:
+      team: payments
+    dlq_suffix: -dlq
+    max_receive_count: 5
+  plain:
+    attributes:
+      VisibilityTimeout: "15"
+queues:
+  - name: invoices
+    template: standard-worker
+    attributes:
+      VisibilityTimeout: "120"
+    tags:
+      service: billing
+  - name: orders.fifo
+    template: standard-worker
+    attributes:
+      FifoQueue: "true"
+  - name: refunds
+    template: standard-worker
+  - name: refunds-dlq
+    attributes:
+      MessageRetentionPeriod: "1209600"
+"#;
+
+fn config_path() -> PathBuf {
+    std::env::temp_dir().join(format!("local-sqs-{}.yaml", uuid::Uuid::new_v4()))
+}
+
+async fn start(config: &str) -> (TestServer, PathBuf) {
+    let path = config_path();
+    std::fs::write(&path, config).unwrap();
+    let server = TestServer::start_with(Config {
+        config_file: Some(path.clone()),
+        ..Config::default()
+    })
+    .await;
+    (server, path)
+}
+
+async fn attributes(server: &TestServer, name: &str) -> HashMap<QueueAttributeName, String> {
+    let url = server.client.get_queue_url().queue_name(name).send().await.unwrap();
+    server
+        .client
+        .get_queue_attributes()
+        .queue_url(url.queue_url().unwrap())
+        .attribute_names(QueueAttributeName::All)
+        .send()
+        .await
+        .unwrap()
+        .attributes
+        .unwrap()
+}
+
+async fn tags(server: &TestServer, name: &str) -> HashMap<String, String> {
+    let url = server.client.get_queue_url().queue_name(name).send().await.unwrap();
+    let tags = server
+        .client
+        .list_queue_tags()
+        .queue_url(url.queue_url().unwrap())
+        .send()
+        .await
+        .unwrap();
+    tags.tags.unwrap_or_default()
+}
+
+fn redrive_policy(attributes: &HashMap<QueueAttributeName, String>) -> serde_json::Value {
+    serde_json::from_str(&attributes[&QueueAttributeName::RedrivePolicy]).unwrap()
+}
+
+storage_matrix!(declared_queues_start_from_their_templates);
+async fn declared_queues_start_from_their_templates() {
+    let (server, path) = start(CONFIG).await;
+
+    let invoices = attributes(&server, "invoices").await;
+    assert_eq!(invoices[&QueueAttributeName::VisibilityTimeout], "120");
+    assert_eq!(invoices[&QueueAttributeName::DelaySeconds], "2");
+    let policy = redrive_policy(&invoices);
+    assert_eq!(
+        policy["deadLetterTargetArn"],
+        "arn:aws:sqs:us-east-1:000000000000:invoices-dlq"
+    );
+    assert_eq!(policy["maxReceiveCount"], "5");
+    let invoice_tags = tags(&server, "invoices").await;
+    assert_eq!(invoice_tags["team"], "payments");
+    assert_eq!(invoice_tags["service"], "billing");
+
+    // Generated dead-letter queues carry the template's tags but not its
+    // attributes.
+    let dlq = attributes(&server, "invoices-dlq").await;
+    assert_eq!(dlq[&QueueAttributeName::VisibilityTimeout], "30");
+    assert!(!dlq.contains_key(&QueueAttributeName::RedrivePolicy));
+    assert_eq!(tags(&server, "invoices-dlq").await["team"], "payments");
+
+    let fifo_dlq = attributes(&server, "orders-dlq.fifo").await;
+    assert_eq!(fifo_dlq[&QueueAttributeName::FifoQueue], "true");
+    let policy = redrive_policy(&attributes(&server, "orders.fifo").await);
+    assert_eq!(
+        policy["deadLetterTargetArn"],
+        "arn:aws:sqs:us-east-1:000000000000:orders-dlq.fifo"
+    );
+
+    // A dead-letter queue declared in the file is used as declared.
+    let refunds_dlq = attributes(&server, "refunds-dlq").await;
+    assert_eq!(refunds_dlq[&QueueAttributeName::MessageRetentionPeriod], "1209600");
+    let policy = redrive_policy(&attributes(&server, "refunds").await);
+    assert_eq!(
+        policy["deadLetterTargetArn"],
+        "arn:aws:sqs:us-east-1:000000000000:refunds-dlq"
+    );
+
+    let queues = server.client.list_queues().send().await.unwrap();
+    assert_eq!(queues.queue_urls().len(), 6);
+    std::fs::remove_file(path).ok();
+}
+
+storage_matrix!(create_queue_can_name_a_template_in_a_header);
+async fn create_queue_can_name_a_template_in_a_header() {
+    let (server, path) = start(CONFIG).await;
+
+    let created = server
+        .client
+        .create_queue()
+        .queue_name("payouts")
+        .attributes(QueueAttributeName::DelaySeconds, "0")
+        .customize()
+        .mutate_request(|request| {
+            request.headers_mut().insert("X-Local-Sqs-Template", "standard-worker");
+        })
+        .send()
+        .await
+        .unwrap();
+    assert!(created.queue_url().unwrap().ends_with("/payouts"));
+    let payouts = attributes(&server, "payouts").await;
+    assert_eq!(payouts[&QueueAttributeName::VisibilityTimeout], "60");
+    assert_eq!(payouts[&QueueAttributeName::DelaySeconds], "0");
+    assert_eq!(
+        redrive_policy(&payouts)["deadLetterTargetArn"],
+        "arn:aws:sqs:us-east-1:000000000000:payouts-dlq"
+    );
+    assert_eq!(tags(&server, "payouts-dlq").await["team"], "payments");
+
+    let error = server
+        .client
+        .create_queue()
+        .queue_name("stray")
+        .customize()
+        .mutate_request(|request| {
+            request.headers_mut().insert("X-Local-Sqs-Template", "missing");
+        })
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    let message = error.meta().message().unwrap();
+    assert!(message.contains("stray") && message.contains("missing"), "{}", message);
+    std::fs::remove_file(path).ok();
+}
+
+#[tokio::test]
+async fn template_errors_name_the_queue_and_the_template() {
+    let state = AppState::new(&Config::default());
+    let file = ConfigFile::from_yaml(
+        "queues:\n  - name: invoices\n    template: standard-worker\n",
+    )
+    .unwrap();
+    let error = reload::apply(&state, &file, None).await.unwrap_err();
+    assert_eq!(error.code(), "InvalidParameterValue");
+    let message = error.message();
+    assert!(message.contains("invoices"), "{}", message);
+    assert!(message.contains("standard-worker"), "{}", message);
+
+    let file = ConfigFile::from_yaml(
+        r#"
+templates:
+  slow:
+    attributes:
+      VisibilityTimeout: "forever"
+queues:
+  - name: reports
+    template: slow
+"#,
+    )
+    .unwrap();
+    let error = reload::apply(&state, &file, None).await.unwrap_err();
+    let message = error.message();
+    assert!(message.contains("reports"), "{}", message);
+    assert!(message.contains("slow"), "{}", message);
+    assert!(message.contains("VisibilityTimeout"), "{}", message);
+
+    let unknown_field = "templates:\n  slow:\n    visibility: \"5\"\n";
+    assert!(ConfigFile::from_yaml(unknown_field).is_err());
+}
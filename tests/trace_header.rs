@@ -0,0 +1,128 @@
+mod common;
+
+use aws_sdk_sqs::types::{
+    MessageSystemAttributeName, MessageSystemAttributeNameForSends,
+    MessageSystemAttributeValue, SendMessageBatchRequestEntry,
+};
+use common::{TestServer, storage_matrix};
+
+const TRACE_ID: &str = "Root=1-5759e988-bd862e3fe1be46a994272793;Sampled=1";
+
+async fn received_trace_header(server: &TestServer, queue_url: &str) -> Option<String> {
+    let received = server
+        .client
+        .receive_message()
+        .queue_url(queue_url)
+        .message_system_attribute_names(MessageSystemAttributeName::AwsTraceHeader)
+        .send()
+        .await
+        .unwrap();
+    received.messages()[0]
+        .attributes()
+        .and_then(|attributes| attributes.get(&MessageSystemAttributeName::AwsTraceHeader))
+        .cloned()
+}
+
+storage_matrix!(request_header_becomes_aws_trace_header);
+async fn request_header_becomes_aws_trace_header() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("traced").await;
+
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("body")
+        .customize()
+        .mutate_request(|request| {
+            request.headers_mut().insert("X-Amzn-Trace-Id", TRACE_ID);
+        })
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(
+        received_trace_header(&server, &queue_url).await.as_deref(),
+        Some(TRACE_ID)
+    );
+}
+
+storage_matrix!(explicit_system_attribute_wins_over_header);
+async fn explicit_system_attribute_wins_over_header() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("traced").await;
+
+    let sent = server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("body")
+        .message_system_attributes(
+            MessageSystemAttributeNameForSends::AwsTraceHeader,
+            MessageSystemAttributeValue::builder()
+                .data_type("String")
+                .string_value("Root=explicit")
+                .build()
+                .unwrap(),
+        )
+        .customize()
+        .mutate_request(|request| {
+            request.headers_mut().insert("X-Amzn-Trace-Id", TRACE_ID);
+        })
+        .send()
+        .await
+        .unwrap();
+
+    assert!(sent.md5_of_message_system_attributes().is_some());
+    assert_eq!(
+        received_trace_header(&server, &queue_url).await.as_deref(),
+        Some("Root=explicit")
+    );
+}
+
+storage_matrix!(batch_entries_inherit_the_request_header);
+async fn batch_entries_inherit_the_request_header() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("traced").await;
+
+    server
+        .client
+        .send_message_batch()
+        .queue_url(&queue_url)
+        .entries(
+            SendMessageBatchRequestEntry::builder()
+                .id("a")
+                .message_body("body")
+                .build()
+                .unwrap(),
+        )
+        .customize()
+        .mutate_request(|request| {
+            request.headers_mut().insert("X-Amzn-Trace-Id", TRACE_ID);
+        })
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(
+        received_trace_header(&server, &queue_url).await.as_deref(),
+        Some(TRACE_ID)
+    );
+}
+
+storage_matrix!(messages_without_a_trace_have_no_header);
+async fn messages_without_a_trace_have_no_header() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("untraced").await;
+
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("body")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(received_trace_header(&server, &queue_url).await, None);
+}
@@ -0,0 +1,388 @@
+mod common;
+
+use aws_sdk_sqs::error::ProvideErrorMetadata;
+use aws_sdk_sqs::types::{MessageAttributeValue, QueueAttributeName};
+use common::{TestServer, storage_matrix};
+use local_sqs::validation::ValidationMode;
+use local_sqs::Config;
+
+async fn health(server: &TestServer) -> serde_json::Value {
+    let (status, body) = server.admin("GET", "/health", "").await;
+    assert_eq!(status, 200);
+    serde_json::from_str(&body).unwrap()
+}
+
+fn reserved_attribute() -> MessageAttributeValue {
+    MessageAttributeValue::builder()
+        .data_type("String")
+        .string_value("x")
+        .build()
+        .unwrap()
+}
+
+storage_matrix!(strict_mode_is_the_default);
+async fn strict_mode_is_the_default() {
+    let server = TestServer::start().await;
+    assert_eq!(health(&server).await["validation"], "strict");
+
+    let err = server
+        .client
+        .create_queue()
+        .queue_name("no spaces allowed")
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert_eq!(err.code(), Some("InvalidParameterValue"), "{:?}", err);
+
+    // Names may end in .fifo, but nothing else with a period.
+    server.create_queue("ordered.fifo").await;
+    let err = server
+        .client
+        .create_queue()
+        .queue_name("dotted.name")
+        .send()
+        .await
+        .unwrap_err()
+        .into_service_error();
+    assert_eq!(err.code(), Some("InvalidParameterValue"), "{:?}", err);
+}
+
+storage_matrix!(lenient_mode_lets_invalid_requests_through);
+async fn lenient_mode_lets_invalid_requests_through() {
+    let server = TestServer::start_with(Config {
+        validation: ValidationMode::Lenient,
+        ..Default::default()
+    })
+    .await;
+    assert_eq!(health(&server).await["validation"], "lenient");
+
+    let queue_url = server.create_queue("no spaces allowed").await;
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("hello")
+        .message_attributes("AWS.reserved", reserved_attribute())
+        .send()
+        .await
+        .unwrap();
+
+    let received = server
+        .client
+        .receive_message()
+        .queue_url(&queue_url)
+        .message_attribute_names("All")
+        .send()
+        .await
+        .unwrap();
+    let message = &received.messages()[0];
+    assert!(message.message_attributes().unwrap().contains_key("AWS.reserved"));
+}
+
+storage_matrix!(mode_can_be_switched_at_runtime);
+async fn mode_can_be_switched_at_runtime() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("switch").await;
+    let set_timeout = || {
+        server
+            .client
+            .set_queue_attributes()
+            .queue_url(&queue_url)
+            .attributes(QueueAttributeName::VisibilityTimeout, "50000")
+            .send()
+    };
+
+    let err = set_timeout().await.unwrap_err().into_service_error();
+    assert_eq!(err.code(), Some("InvalidAttributeValue"), "{:?}", err);
+
+    let (status, body) = server
+        .admin("PUT", "/validation", r#"{"mode": "lenient"}"#)
+        .await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(health(&server).await["validation"], "lenient");
+    set_timeout().await.unwrap();
+
+    let (status, _) = server
+        .admin("PUT", "/validation", r#"{"mode": "strict"}"#)
+        .await;
+    assert_eq!(status, 200);
+    assert!(set_timeout().await.is_err());
+
+    let (status, _) = server
+        .admin("PUT", "/validation", r#"{"mode": "sloppy"}"#)
+        .await;
+    assert_eq!(status, 400);
+}
+
+storage_matrix!(unknown_request_fields_are_rejected_in_strict_mode);
+async fn unknown_request_fields_are_rejected_in_strict_mode() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("fields").await;
+
+    let body = serde_json::json!({
+        "QueueUrl": queue_url,
+        "MessageBody": "hello",
+        "MessageGroupID": "typo",
+    })
+    .to_string();
+    let (status, response) = server.action("AmazonSQS.SendMessage", &body).await;
+    assert_eq!(status, 400);
+    assert!(response.contains("InvalidParameterValue"), "{}", response);
+    assert!(response.contains("Unknown field MessageGroupID"), "{}", response);
+
+    let (status, response) = server
+        .admin("PUT", "/validation", r#"{"mode": "lenient"}"#)
+        .await;
+    assert_eq!(status, 200, "{}", response);
+    let (status, response) = server.action("AmazonSQS.SendMessage", &body).await;
+    assert_eq!(status, 200, "{}", response);
+}
+
+storage_matrix!(every_field_the_sdk_sends_is_known);
+async fn every_field_the_sdk_sends_is_known() {
+    let server = TestServer::start().await;
+    let queue_url = server
+        .client
+        .create_queue()
+        .queue_name("sdk-fields")
+        .tags("team", "billing")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    server
+        .client
+        .get_queue_url()
+        .queue_name("sdk-fields")
+        .queue_owner_aws_account_id("000000000000")
+        .send()
+        .await
+        .unwrap();
+    // Receive attempt ids are only valid on FIFO queues.
+    let fifo_url = server
+        .client
+        .create_queue()
+        .queue_name("sdk-fields.fifo")
+        .attributes(QueueAttributeName::FifoQueue, "true")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    server
+        .client
+        .receive_message()
+        .queue_url(&fifo_url)
+        .message_attribute_names("All")
+        .message_system_attribute_names(aws_sdk_sqs::types::MessageSystemAttributeName::All)
+        .receive_request_attempt_id("attempt")
+        .send()
+        .await
+        .unwrap();
+
+    let tags = server
+        .client
+        .list_queue_tags()
+        .queue_url(&queue_url)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(tags.tags().unwrap()["team"], "billing");
+}
+
+storage_matrix!(parameters_must_suit_the_queue_type);
+async fn parameters_must_suit_the_queue_type() {
+    let server = TestServer::start().await;
+    let standard = server.create_queue("standard").await;
+    let fifo = server
+        .client
+        .create_queue()
+        .queue_name("typed.fifo")
+        .attributes(QueueAttributeName::FifoQueue, "true")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    let assert_invalid = |err: aws_sdk_sqs::error::ErrorMetadata, parameter: &str| {
+        assert_eq!(err.code(), Some("InvalidParameterValue"), "{:?}", err);
+        let message = err.message().unwrap();
+        assert!(message.contains(parameter), "{}", message);
+        assert!(message.contains("not valid for this queue type"), "{}", message);
+    };
+
+    let send = || server.client.send_message().message_body("hi");
+    let err = send().queue_url(&standard).message_group_id("g").send().await.unwrap_err();
+    assert_invalid(err.into_service_error().meta().clone(), "MessageGroupId");
+    let err = send()
+        .queue_url(&standard)
+        .message_deduplication_id("d")
+        .send()
+        .await
+        .unwrap_err();
+    assert_invalid(err.into_service_error().meta().clone(), "MessageDeduplicationId");
+    let err = send()
+        .queue_url(&fifo)
+        .message_group_id("g")
+        .message_deduplication_id("d")
+        .delay_seconds(5)
+        .send()
+        .await
+        .unwrap_err();
+    assert_invalid(err.into_service_error().meta().clone(), "DelaySeconds");
+    let send_fifo = || send().queue_url(&fifo).message_group_id("g").message_deduplication_id("d");
+    send_fifo().delay_seconds(0).send().await.unwrap();
+
+    let err = server
+        .client
+        .receive_message()
+        .queue_url(&standard)
+        .receive_request_attempt_id("attempt")
+        .send()
+        .await
+        .unwrap_err();
+    assert_invalid(err.into_service_error().meta().clone(), "ReceiveRequestAttemptId");
+
+    // Batch entries are checked one by one.
+    let entry = |id: &str| {
+        aws_sdk_sqs::types::SendMessageBatchRequestEntry::builder()
+            .id(id)
+            .message_body("hi")
+    };
+    let response = server
+        .client
+        .send_message_batch()
+        .queue_url(&standard)
+        .entries(entry("plain").build().unwrap())
+        .entries(entry("grouped").message_group_id("g").build().unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.successful().len(), 1);
+    assert_eq!(response.failed()[0].id(), "grouped");
+    assert_eq!(response.failed()[0].code(), "InvalidParameterValue");
+}
+
+storage_matrix!(lenient_mode_ignores_parameters_for_the_other_queue_type);
+async fn lenient_mode_ignores_parameters_for_the_other_queue_type() {
+    let server = TestServer::start_with(Config {
+        validation: ValidationMode::Lenient,
+        ..Default::default()
+    })
+    .await;
+    let standard = server.create_queue("standard").await;
+    server
+        .client
+        .send_message()
+        .queue_url(&standard)
+        .message_body("hi")
+        .message_group_id("g")
+        .message_deduplication_id("d")
+        .send()
+        .await
+        .unwrap();
+}
+
+storage_matrix!(lenient_mode_covers_visibility_permission_and_listing_parameters);
+async fn lenient_mode_covers_visibility_permission_and_listing_parameters() {
+    let server = TestServer::start_with(Config {
+        validation: ValidationMode::Lenient,
+        ..Default::default()
+    })
+    .await;
+    let queue_url = server.create_queue("lenient").await;
+    let client = &server.client;
+
+    client.send_message().queue_url(&queue_url).message_body("m").send().await.unwrap();
+    let received = client.receive_message().queue_url(&queue_url).send().await.unwrap();
+    let receipt_handle = received.messages()[0].receipt_handle().unwrap();
+    let change = client.change_message_visibility().queue_url(&queue_url);
+    let change = change.receipt_handle(receipt_handle).visibility_timeout(50_000);
+    change.send().await.unwrap();
+
+    let add = client.add_permission().queue_url(&queue_url).label("not a label");
+    add.aws_account_ids("111122223333").actions("Explode").send().await.unwrap();
+
+    let listed = client.list_queues().max_results(5_000).send().await.unwrap();
+    assert_eq!(listed.queue_urls(), [queue_url.as_str()]);
+    let listed = client.list_queues().max_results(0).send().await.unwrap();
+    assert_eq!(listed.queue_urls(), [queue_url.as_str()]);
+}
+
+storage_matrix!(receive_counts_must_be_between_1_and_10);
+async fn receive_counts_must_be_between_1_and_10() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("counted").await;
+    for body in ["a", "b"] {
+        server.client.send_message().queue_url(&queue_url).message_body(body).send().await.unwrap();
+    }
+    let receive = |max| {
+        let receive = server.client.receive_message().queue_url(&queue_url);
+        receive.max_number_of_messages(max)
+    };
+
+    for max in [0, 11] {
+        let err = receive(max).send().await.unwrap_err().into_service_error();
+        assert_eq!(err.code(), Some("InvalidParameterValue"), "{:?}", err);
+        let expected = format!(
+            "Value {} for parameter MaxNumberOfMessages is invalid. Reason: Must be between 1 and 10.",
+            max
+        );
+        assert_eq!(err.message(), Some(expected.as_str()));
+    }
+    assert_eq!(receive(1).send().await.unwrap().messages().len(), 1);
+
+    let (status, _) = server.admin("PUT", "/validation", r#"{"mode": "lenient"}"#).await;
+    assert_eq!(status, 200);
+    assert_eq!(receive(11).send().await.unwrap().messages().len(), 1);
+}
+
+storage_matrix!(message_contents_and_fifo_ids_are_checked);
+async fn message_contents_and_fifo_ids_are_checked() {
+    let server = TestServer::start().await;
+    let standard = server.create_queue("standard").await;
+    let fifo = server
+        .client
+        .create_queue()
+        .queue_name("checked.fifo")
+        .attributes(QueueAttributeName::FifoQueue, "true")
+        .send()
+        .await
+        .unwrap()
+        .queue_url
+        .unwrap();
+    let attribute = |value: &str| {
+        MessageAttributeValue::builder().data_type("String").string_value(value).build().unwrap()
+    };
+    let send = || server.client.send_message().queue_url(&standard);
+    let send_fifo = |group: &str, deduplication_id: &str| {
+        let send = server.client.send_message().queue_url(&fifo).message_body("m");
+        send.message_group_id(group).message_deduplication_id(deduplication_id)
+    };
+    let long_id = "d".repeat(129);
+
+    let err = send().message_body("bell \u{7}").send().await.unwrap_err().into_service_error();
+    assert_eq!(err.code(), Some("InvalidMessageContents"), "{:?}", err);
+    assert!(err.message().unwrap().contains("#x7"), "{:?}", err);
+    let sent = send().message_body("m").message_attributes("note", attribute("\u{1}"));
+    let err = sent.send().await.unwrap_err().into_service_error();
+    assert_eq!(err.code(), Some("InvalidMessageContents"), "{:?}", err);
+    let invalid_fifo = [("", "d"), ("has space", "d"), ("g", long_id.as_str())];
+    for (group, deduplication_id) in invalid_fifo {
+        let err = send_fifo(group, deduplication_id).send().await.unwrap_err();
+        let err = err.into_service_error();
+        assert_eq!(err.code(), Some("InvalidParameterValue"), "{:?}", err);
+        assert!(err.message().unwrap().contains("1 to 128 in length"), "{:?}", err);
+    }
+    // Tabs, line breaks and characters past the Basic Multilingual Plane
+    // are all allowed, as is every ASCII punctuation character in an ID.
+    send().message_body("\ttab\r\nemoji \u{1F600}").send().await.unwrap();
+    send_fifo("a-b_c.d:e!~", &"d".repeat(128)).send().await.unwrap();
+
+    let (status, _) = server.admin("PUT", "/validation", r#"{"mode": "lenient"}"#).await;
+    assert_eq!(status, 200);
+    send().message_body("bell \u{7}").send().await.unwrap();
+    send_fifo("has space", &long_id).send().await.unwrap();
+}
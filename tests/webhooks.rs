@@ -0,0 +1,182 @@
+mod common;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::{Json, Router};
+use common::{TestServer, storage_matrix};
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A local endpoint that records the webhook callbacks it receives, failing
+/// the first `failures` requests with a 500.
+#[derive(Clone, Default)]
+struct Receiver {
+    events: Arc<Mutex<Vec<Value>>>,
+    requests: Arc<AtomicUsize>,
+    failures: usize,
+}
+
+impl Receiver {
+    async fn start(failures: usize) -> (Self, SocketAddr) {
+        let receiver = Receiver {
+            failures,
+            ..Default::default()
+        };
+        let app = Router::new()
+            .route("/hook", post(record))
+            .with_state(receiver.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (receiver, addr)
+    }
+
+    async fn wait_for(&self, count: usize) -> Vec<Value> {
+        for _ in 0..50 {
+            if self.events.lock().unwrap().len() >= count {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        self.events.lock().unwrap().clone()
+    }
+}
+
+async fn record(State(receiver): State<Receiver>, Json(event): Json<Value>) -> StatusCode {
+    if receiver.requests.fetch_add(1, Ordering::SeqCst) < receiver.failures {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+    receiver.events.lock().unwrap().push(event);
+    StatusCode::OK
+}
+
+async fn set_webhook(server: &TestServer, queue: &str, addr: SocketAddr) {
+    let (status, body) = server
+        .admin(
+            "PUT",
+            &format!("/queues/{}/webhook", queue),
+            &format!(r#"{{"url": "http://{}/hook"}}"#, addr),
+        )
+        .await;
+    assert_eq!(status, 200, "{}", body);
+}
+
+storage_matrix!(sends_are_posted_to_the_queue_webhook);
+async fn sends_are_posted_to_the_queue_webhook() {
+    let (receiver, addr) = Receiver::start(0).await;
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("hooked").await;
+    set_webhook(&server, "hooked", addr).await;
+
+    let sent = server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("hello")
+        .send()
+        .await
+        .unwrap();
+
+    let events = receiver.wait_for(1).await;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["queue"], "hooked");
+    assert_eq!(events[0]["message_id"], sent.message_id().unwrap());
+    assert_eq!(events[0]["body"], "hello");
+    assert!(events[0]["sent_timestamp"].as_i64().unwrap() > 0);
+    assert!(events[0]["attributes"]["SentTimestamp"].is_string());
+}
+
+storage_matrix!(failed_deliveries_are_retried);
+async fn failed_deliveries_are_retried() {
+    let (receiver, addr) = Receiver::start(2).await;
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("flaky").await;
+    set_webhook(&server, "flaky", addr).await;
+
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("eventually")
+        .send()
+        .await
+        .unwrap();
+
+    let events = receiver.wait_for(1).await;
+    assert_eq!(events.len(), 1);
+    assert_eq!(receiver.requests.load(Ordering::SeqCst), 3);
+}
+
+storage_matrix!(a_dead_webhook_does_not_block_sends);
+async fn a_dead_webhook_does_not_block_sends() {
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("dead").await;
+    // Nothing listens on port 9 on the loopback interface.
+    let (status, _) = server
+        .admin("PUT", "/queues/dead/webhook", r#"{"url": "http://127.0.0.1:9/hook"}"#)
+        .await;
+    assert_eq!(status, 200);
+
+    let started = std::time::Instant::now();
+    for i in 0..20 {
+        server
+            .client
+            .send_message()
+            .queue_url(&queue_url)
+            .message_body(format!("message {}", i))
+            .send()
+            .await
+            .unwrap();
+    }
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+storage_matrix!(webhooks_can_be_removed_and_must_be_http_urls);
+async fn webhooks_can_be_removed_and_must_be_http_urls() {
+    let (receiver, addr) = Receiver::start(0).await;
+    let server = TestServer::start().await;
+    let queue_url = server.create_queue("unhooked").await;
+    set_webhook(&server, "unhooked", addr).await;
+
+    let (status, _) = server.admin("DELETE", "/queues/unhooked/webhook", "").await;
+    assert_eq!(status, 200);
+    let (_, body) = server.admin("GET", "/queues/unhooked/webhook", "").await;
+    assert_eq!(body, r#"{"url":null}"#);
+
+    server
+        .client
+        .send_message()
+        .queue_url(&queue_url)
+        .message_body("quiet")
+        .send()
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(receiver.events.lock().unwrap().is_empty());
+
+    let (status, _) = server
+        .admin("PUT", "/queues/unhooked/webhook", r#"{"url": "ftp://example.com"}"#)
+        .await;
+    assert_eq!(status, 400);
+}
+
+storage_matrix!(fixtures_can_declare_webhooks);
+async fn fixtures_can_declare_webhooks() {
+    let (receiver, addr) = Receiver::start(0).await;
+    let server = TestServer::start().await;
+
+    let fixtures = format!(
+        "queues:\n  - name: seeded\n    webhook: http://{}/hook\n    messages:\n      - body: first\n",
+        addr
+    );
+    let (status, body) = server.admin("POST", "/fixtures", &fixtures).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let events = receiver.wait_for(1).await;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["body"], "first");
+}